@@ -135,23 +135,9 @@ async fn init_database(n: i32) -> anyhow::Result<u128> {
     }
 
     let keyspace = MyKeyspace::new();
-    let worker = PrepareWorker::boxed(
-        <MyKeyspace as Insert<String, i32>>::id(&keyspace),
-        <MyKeyspace as Insert<String, i32>>::statement(&keyspace),
-    );
-    let Prepare(payload) = Prepare::new()
-        .statement(&<MyKeyspace as Insert<String, i32>>::statement(&keyspace))
-        .build()?;
-    send_local(token, payload, worker, keyspace.name().to_string());
-
-    let worker = PrepareWorker::boxed(
-        <MyKeyspace as Select<String, i32>>::id(&keyspace),
-        <MyKeyspace as Select<String, i32>>::statement(&keyspace),
-    );
-    let Prepare(payload) = Prepare::new()
-        .statement(&<MyKeyspace as Select<String, i32>>::statement(&keyspace))
-        .build()?;
-    send_local(token, payload, worker, keyspace.name().to_string());
+    // prepare the statements on every node, so the first requests don't hit unprepared errors
+    prepare_all(&<MyKeyspace as Insert<String, i32>>::statement(&keyspace))?;
+    prepare_all(&<MyKeyspace as Select<String, i32>>::statement(&keyspace))?;
 
     tokio::time::sleep(std::time::Duration::from_secs(1)).await;
 
@@ -0,0 +1,191 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! A micro dashboard, which runs the Scylla app and exposes its metrics and its ring topology as JSON over HTTP:
+//! - `GET /metrics` the requests metrics by request type, shard connection and replica index, and the cql errors;
+//! - `GET /topology` the data centers, the replication factor and the reporter connections of the ring;
+//! - `GET /` a page which renders both of them, and refreshes every few seconds.
+use scylla_rs::{
+    app::{
+        metrics::{self, HistogramSnapshot, MetricsSnapshot, RequestStats},
+        ring::{Ring, TopologySnapshot},
+    },
+    prelude::*,
+};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// The address of the dashboard
+const DASHBOARD_ADDRESS: &str = "127.0.0.1:9100";
+
+/// The page of the dashboard, which polls the JSON endpoints
+const DASHBOARD_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><title>scylla.rs dashboard</title></head>
+<body style="font-family: monospace">
+<h2>Topology</h2><pre id="topology"></pre>
+<h2>Metrics</h2><pre id="metrics"></pre>
+<script>
+async function refresh() {
+    for (const endpoint of ["topology", "metrics"]) {
+        const response = await fetch("/" + endpoint);
+        document.getElementById(endpoint).textContent = JSON.stringify(await response.json(), null, 2);
+    }
+}
+refresh();
+setInterval(refresh, 3000);
+</script>
+</body>
+</html>"#;
+
+// launcher
+launcher!(builder: AppsBuilder {[] -> Scylla<Sender>: ScyllaBuilder<Sender>}, state: Apps {});
+
+impl Builder for AppsBuilder {
+    type State = Apps;
+    fn build(self) -> Self::State {
+        // create Scylla app
+        let scylla_builder = ScyllaBuilder::new()
+            .listen_address("127.0.0.1:8080".to_owned())
+            .thread_count(num_cpus::get())
+            .reporter_count(2)
+            .local_dc("datacenter1".to_owned());
+        // add it to launcher
+        self.Scylla(scylla_builder).to_apps()
+    }
+}
+
+fn latency_json(latency: &HistogramSnapshot) -> Value {
+    let micros = |quantile: f64| latency.quantile(quantile).map(|bound| bound.as_micros() as u64);
+    json!({
+        "mean_us": latency.mean().as_micros() as u64,
+        "p50_us": micros(0.5),
+        "p99_us": micros(0.99),
+    })
+}
+
+fn stats_json(stats: &RequestStats) -> Value {
+    json!({
+        "requests": stats.requests,
+        "errors": stats.errors,
+        "latency": latency_json(&stats.latency),
+    })
+}
+
+fn metrics_json(metrics: &MetricsSnapshot) -> Value {
+    json!({
+        "request_types": metrics
+            .request_types
+            .iter()
+            .map(|(request_type, stats)| (request_type.as_str().to_string(), stats_json(stats)))
+            .collect::<serde_json::Map<_, _>>(),
+        "connections": metrics
+            .connections
+            .iter()
+            .map(|(label, stats)| json!({
+                "node": label.node.to_string(),
+                "shard": label.shard,
+                "stats": stats_json(stats),
+            }))
+            .collect::<Vec<_>>(),
+        "nodes": metrics
+            .nodes()
+            .iter()
+            .map(|(node, requests)| json!({ "node": node.to_string(), "requests": requests }))
+            .collect::<Vec<_>>(),
+        "replicas": metrics.replicas.to_vec(),
+        "errors": metrics
+            .errors
+            .iter()
+            .map(|(code, count)| (format!("{:?}", code), json!(count)))
+            .collect::<serde_json::Map<_, _>>(),
+    })
+}
+
+fn topology_json(topology: &TopologySnapshot) -> Value {
+    json!({
+        "version": topology.version,
+        "data_centers": topology.data_centers,
+        "replication_factor": topology.replication_factor,
+        "connections": topology
+            .connections
+            .iter()
+            .map(|label| json!({ "node": label.node.to_string(), "shard": label.shard, "reporter": label.index }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Serve a single HTTP request of the dashboard
+async fn serve(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request = [0; 1024];
+    let len = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..len]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let (status, content_type, body) = match path {
+        "/" => ("200 OK", "text/html", DASHBOARD_PAGE.to_string()),
+        "/metrics" => (
+            "200 OK",
+            "application/json",
+            metrics_json(&metrics::snapshot()).to_string(),
+        ),
+        "/topology" => (
+            "200 OK",
+            "application/json",
+            topology_json(&Ring::snapshot()).to_string(),
+        ),
+        _ => ("404 Not Found", "text/plain", "Not found".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Accept the HTTP connections of the dashboard
+async fn dashboard(listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream).await {
+                        log::warn!("Dashboard request failed: {}", e);
+                    }
+                });
+            }
+            Err(e) => log::error!("Dashboard listener failed: {}", e),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // start the logger
+    env_logger::init();
+    // create apps_builder and build apps
+    let apps = AppsBuilder::new().build();
+    // start launcher and Scylla, along with the dashboard
+    apps.Scylla()
+        .await
+        .future(|apps| async {
+            let ws = format!("ws://{}/", "127.0.0.1:8080");
+            let nodes = vec![([172, 17, 0, 2], 19042).into()];
+            add_nodes(&ws, nodes, 1).await.expect("unable to add nodes");
+            let listener = TcpListener::bind(DASHBOARD_ADDRESS)
+                .await
+                .expect("unable to bind the dashboard address");
+            log::info!("Dashboard is available at http://{}/", DASHBOARD_ADDRESS);
+            tokio::spawn(dashboard(listener));
+            apps
+        })
+        .await
+        .start(None)
+        .await;
+}
@@ -0,0 +1,305 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! A cqlsh-like interactive shell, which reads the statements from the standard input, validates them before
+//! they're sent, then executes them through a driver session and prints the rows along with their column names:
+//! - the statements are terminated by semicolons, and may span several lines;
+//! - the `CONSISTENCY`, `SERIAL CONSISTENCY` and `PAGING` directives change how the next statements are executed;
+//! - `USE <keyspace>` qualifies the unqualified tables of the next DML statements with the keyspace;
+//! - `EXIT` or `QUIT` (or the end of the input) closes the shell.
+//!
+//! The address of the node, and optionally the username and the password, are passed as arguments:
+//! `cargo run --example repl -- 127.0.0.1:9042 cassandra cassandra`
+use scylla_rs::cql::{
+    parse_script,
+    statements::{
+        schema::{CreateKeyspaceStatement, CreateTableStatement},
+        service_level::ServiceLevelStatement,
+    },
+    AsDynamicBatchRequest, AsDynamicDeleteRequest, AsDynamicInsertRequest, AsDynamicSelectRequest,
+    AsDynamicUpdateRequest, Consistency, CqlValue, Directive, DynamicRow, Frame, Name, PasswordAuth, Query, ScriptItem,
+    Session, StatementKind, Statements, DEFAULT_PAGE_SIZE,
+};
+use std::io::{BufRead, Write};
+
+/// The default address of the node
+const DEFAULT_NODE: &str = "127.0.0.1:9042";
+
+/// The settings of the shell, which are changed by the directives
+struct Shell {
+    session: Session,
+    consistency: Consistency,
+    serial_consistency: Consistency,
+    page_size: Option<i32>,
+    keyspace: Option<Name>,
+}
+
+impl Shell {
+    /// Apply the directive
+    fn apply(&mut self, directive: Directive) {
+        match directive {
+            Directive::Consistency(consistency) => {
+                self.consistency = consistency;
+                println!("Consistency level set to {}.", consistency);
+            }
+            Directive::SerialConsistency(consistency) => {
+                self.serial_consistency = consistency;
+                println!("Serial consistency level set to {}.", consistency);
+            }
+            Directive::Paging(page_size) => {
+                self.page_size = page_size;
+                match page_size {
+                    Some(page_size) => println!("Page size: {}", page_size),
+                    None => println!("Disabled Query paging."),
+                }
+            }
+        }
+    }
+
+    /// Validate then execute the statement, fetching its next pages on demand
+    async fn execute(&mut self, statement: &str, input: &mut impl Iterator<Item = String>) -> anyhow::Result<()> {
+        if let Some(keyspace) = use_keyspace(statement)? {
+            println!("Now using the {} keyspace.", keyspace);
+            self.keyspace = Some(keyspace);
+            return Ok(());
+        }
+        validate(statement)?;
+        let mut paging_state = None;
+        loop {
+            let mut builder = Query::new()
+                .statement(statement)
+                .consistency(self.consistency)
+                // a negative page size disables the paging
+                .page_size(self.page_size.unwrap_or(-1))
+                .paging_state(&paging_state)
+                .serial_consistency(self.serial_consistency);
+            if let Some(keyspace) = self.keyspace.as_ref() {
+                builder = builder.with_keyspace(keyspace.as_str());
+            }
+            let Query(payload) = builder.build()?;
+            let decoder = self.session.send(None, payload).await?;
+            if decoder.is_error()? {
+                anyhow::bail!("{}", decoder.get_error()?);
+            }
+            if !decoder.is_rows()? {
+                return Ok(());
+            }
+            print_rows(&DynamicRow::rows(&decoder)?);
+            paging_state = decoder.metadata()?.take_paging_state();
+            if paging_state.is_none() {
+                return Ok(());
+            }
+            print!("---MORE--- (press enter, or q to stop) ");
+            std::io::stdout().flush()?;
+            match input.next() {
+                Some(line) if !line.trim().eq_ignore_ascii_case("q") => {}
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Get the keyspace of the `USE` statement, or None if it's another statement
+fn use_keyspace(statement: &str) -> anyhow::Result<Option<Name>> {
+    let mut words = statement.trim().splitn(2, char::is_whitespace);
+    match words.next() {
+        Some(keyword) if keyword.eq_ignore_ascii_case("USE") => {
+            let keyspace = words.next().unwrap_or_default().trim();
+            Ok(Some(Name::parse(keyspace)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Validate the statement with the parsers of the crate, before it's sent to the node
+fn validate(statement: &str) -> anyhow::Result<()> {
+    if let Some(kind) = StatementKind::of(statement) {
+        // the shell has no bind values, so the partition key doesn't matter
+        match kind {
+            StatementKind::Select => statement.as_select_request(&[])?,
+            StatementKind::Insert => statement.as_insert_request(&[])?,
+            StatementKind::Update => statement.as_update_request(&[])?,
+            StatementKind::Delete => statement.as_delete_request(&[])?,
+            StatementKind::Batch => statement.as_batch_request(&[])?,
+        };
+        return Ok(());
+    }
+    if ServiceLevelStatement::parse(statement)?.is_some() {
+        return Ok(());
+    }
+    let words: Vec<String> = statement
+        .split_whitespace()
+        .take(2)
+        .map(|word| word.to_ascii_uppercase())
+        .collect();
+    match words.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["CREATE", "KEYSPACE"] => CreateKeyspaceStatement::parse(statement).map(|_| ()),
+        ["CREATE", "TABLE"] | ["CREATE", "COLUMNFAMILY"] => CreateTableStatement::parse(statement).map(|_| ()),
+        ["ALTER", _] | ["CREATE", _] | ["DROP", _] | ["TRUNCATE", _] | ["GRANT", _] | ["REVOKE", _] | ["LIST", _] => {
+            Ok(())
+        }
+        _ => anyhow::bail!("Unknown statement: {}", statement),
+    }
+}
+
+/// Format the value as a cql literal, except for the top level texts which are printed as they are, as in cqlsh
+fn format_value(value: &CqlValue, top_level: bool) -> String {
+    match value {
+        CqlValue::Null => "null".to_string(),
+        CqlValue::Boolean(value) => value.to_string(),
+        CqlValue::TinyInt(value) => value.to_string(),
+        CqlValue::SmallInt(value) => value.to_string(),
+        CqlValue::Int(value) => value.to_string(),
+        CqlValue::BigInt(value) => value.to_string(),
+        CqlValue::Float(value) => value.to_string(),
+        CqlValue::Double(value) => value.to_string(),
+        CqlValue::Text(value) if top_level => value.clone(),
+        CqlValue::Text(value) => format!("'{}'", value.replace('\'', "''")),
+        CqlValue::Blob(value) => format!("0x{}", hex(value)),
+        CqlValue::Inet(value) => value.to_string(),
+        CqlValue::List(values) => format!(
+            "[{}]",
+            values
+                .iter()
+                .map(|value| format_value(value, false))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        CqlValue::Map(entries) => format!(
+            "{{{}}}",
+            entries
+                .iter()
+                .map(|(key, value)| format!("{}: {}", format_value(key, false), format_value(value, false)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Print the rows as a table, whose columns are named after the rows metadata
+fn print_rows(rows: &[DynamicRow]) {
+    let columns = match rows.first() {
+        Some(row) => row.columns(),
+        None => {
+            println!("\n(0 rows)\n");
+            return;
+        }
+    };
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            (0..columns.len())
+                .map(|index| match row.value_by_index(index) {
+                    Ok(value) => format_value(&value, true),
+                    // the types which have no dynamic value are printed as their raw bytes
+                    Err(_) => row.raw(index).map(|raw| format!("0x{}", hex(raw))).unwrap_or_default(),
+                })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            cells
+                .iter()
+                .map(|row| row[index].chars().count())
+                .chain(std::iter::once(column.name.chars().count()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |values: Vec<&str>| {
+        values
+            .iter()
+            .zip(widths.iter())
+            .map(|(value, width)| format!(" {:>width$} ", value, width = width))
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+    println!();
+    println!("{}", line(columns.iter().map(|column| column.name.as_str()).collect()));
+    println!(
+        "{}",
+        widths
+            .iter()
+            .map(|width| "-".repeat(width + 2))
+            .collect::<Vec<_>>()
+            .join("+")
+    );
+    for row in cells.iter() {
+        println!("{}", line(row.iter().map(String::as_str).collect()));
+    }
+    println!("\n({} rows)\n", rows.len());
+}
+
+/// Connect the session to the node, with the credentials if any
+async fn connect(mut args: impl Iterator<Item = String>) -> anyhow::Result<Session> {
+    let node = args.next().unwrap_or_else(|| DEFAULT_NODE.to_string()).parse()?;
+    match (args.next(), args.next()) {
+        (Some(username), Some(password)) => {
+            Session::builder()
+                .node(node)
+                .authenticator(PasswordAuth::new(username, password))
+                .build()
+                .await
+        }
+        _ => Session::connect(vec![node]).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let session = connect(std::env::args().skip(1)).await?;
+    println!("Connected to {:?}.", session.nodes());
+    let mut shell = Shell {
+        session,
+        consistency: Consistency::One,
+        serial_consistency: Consistency::Serial,
+        page_size: Some(DEFAULT_PAGE_SIZE),
+        keyspace: None,
+    };
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock().lines().map_while(Result::ok);
+    let mut buffer = String::new();
+    loop {
+        print!("{}", if buffer.is_empty() { "cqlsh> " } else { "   ... " });
+        std::io::stdout().flush()?;
+        let line = match input.next() {
+            Some(line) => line,
+            None => break,
+        };
+        let command = line.trim().trim_end_matches(';');
+        if buffer.is_empty() && (command.eq_ignore_ascii_case("EXIT") || command.eq_ignore_ascii_case("QUIT")) {
+            break;
+        }
+        buffer.push_str(&line);
+        buffer.push('\n');
+        // the statements are complete once they're terminated, while the directives are terminated by their line
+        let directive = !matches!(Directive::parse(&buffer), Ok(None));
+        if !directive && !line.trim_end().ends_with(';') {
+            continue;
+        }
+        let items = parse_script(&std::mem::take(&mut buffer));
+        match items {
+            Ok(items) => {
+                for item in items {
+                    match item {
+                        ScriptItem::Directive(directive) => shell.apply(directive),
+                        ScriptItem::Statement(statement) => {
+                            if let Err(e) = shell.execute(&statement, &mut input).await {
+                                eprintln!("{}", e);
+                            }
+                        }
+                    }
+                }
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    Ok(())
+}
@@ -2,9 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::{delete::DeleteRecommended, insert::InsertRecommended, update::UpdateRecommended, *};
-use crate::cql::{
-    BatchBuild, BatchBuilder, BatchFlags, BatchStatementOrId, BatchTimestamp, BatchType, BatchTypeCounter,
-    BatchTypeLogged, BatchTypeUnlogged, BatchTypeUnset, BatchValues, Consistency,
+use crate::{
+    app::worker::{OneshotHandle, PrepareWorker, PreparedCache},
+    cql::{
+        BatchBuild, BatchBuilder, BatchFlags, BatchFrame, BatchStatementOrId, BatchTimestamp, BatchType,
+        BatchTypeCounter, BatchTypeLogged, BatchTypeUnlogged, BatchTypeUnset, BatchValues, ColumnEncoder, Consistency,
+    },
 };
 use dyn_clone::DynClone;
 use std::{any::Any, collections::HashMap, marker::PhantomData};
@@ -21,6 +24,7 @@ dyn_clone::clone_trait_object!(<S> AnyStatement<S>);
 pub struct BatchRequest<S> {
     token: i64,
     inner: Vec<u8>,
+    timeout: Option<Duration>,
     map: HashMap<[u8; 16], Box<dyn AnyStatement<S>>>,
     keyspace: S,
 }
@@ -112,12 +116,19 @@ impl<S: Keyspace> BatchRequest<S> {
         std::mem::take(&mut self.map)
     }
 
+    /// Set the client-side timeout of the request, after which its worker is resolved with `WorkerError::Timeout`,
+    /// or None to await its response until the connection is lost
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
     /// Send a local request using the keyspace impl and return a type marker
     pub fn send_local(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeVoid<S>> {
         send_local(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Batch, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::batch()
@@ -128,12 +139,23 @@ impl<S: Keyspace> BatchRequest<S> {
         send_global(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Batch, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::batch()
     }
 
+    /// Send a request routed by the provided load balancing policy and return a type marker
+    pub fn send_with(self, policy: &dyn LoadBalancingPolicy, worker: Box<dyn Worker>) -> DecodeResult<DecodeVoid<S>> {
+        send_with(
+            policy,
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Batch, self.timeout),
+        );
+        DecodeResult::batch()
+    }
+
     /// Get a statement given an id from the request's map
     pub fn get_statement(&self, id: &[u8; 16]) -> Option<Cow<'static, str>> {
         self.map.get(id).and_then(|res| Some(res.statement(&self.keyspace)))
@@ -143,6 +165,60 @@ impl<S: Keyspace> BatchRequest<S> {
     pub fn payload(&self) -> &Vec<u8> {
         &self.inner
     }
+
+    /// Get the token which routes the request
+    pub fn token(&self) -> i64 {
+        self.token
+    }
+
+    /// Set the token which routes the request, ie the one of a partition of the batch
+    pub fn with_token(mut self, token: i64) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Re-prepare all the statements of the request, await their prepared ids, and rebuild the batch frame with
+    /// the ids returned by the server.
+    ///
+    /// Each statement is prepared by the provided reporter (ie the one which reported the unprepared error),
+    /// otherwise by a replica of the batch token, while it's also re-prepared on every other shard of the cluster
+    /// by the `PreparedCache`. The returned request is ready to be sent again.
+    pub async fn reprepare_all(mut self, reporter: &Option<ReporterHandle>) -> anyhow::Result<Self> {
+        let mut frame = BatchFrame::decode(&self.inner)?;
+        let keyspace = self.keyspace.name().clone().into_owned();
+        let statement_markers = self.map.drain().collect::<Vec<_>>();
+        let mut map = HashMap::with_capacity(statement_markers.len());
+        for (id, statement_marker) in statement_markers {
+            let statement = statement_marker.statement(&self.keyspace);
+            PreparedCache::reprepare(Md5::statement_id(&statement), &statement)?;
+            let fresh_id = prepare(&statement, self.token, &keyspace, reporter).await?;
+            frame.replace_prepared_id(&id, &fresh_id);
+            map.insert(fresh_id, statement_marker);
+        }
+        self.inner = frame.encode()?;
+        self.map = map;
+        Ok(self)
+    }
+}
+
+/// Prepare the statement with the reporter, or with a replica of the token, and await its prepared id
+async fn prepare(
+    statement: &str,
+    token: i64,
+    keyspace: &str,
+    reporter: &Option<ReporterHandle>,
+) -> anyhow::Result<[u8; 16]> {
+    let Prepare(payload) = Prepare::new().statement(statement).build()?;
+    let (handle, rx) = OneshotHandle::new();
+    let worker = PrepareWorker::reprepare(Md5::statement_id(statement), statement).with_handle(handle);
+    match reporter {
+        Some(reporter) => {
+            reporter.send(ReporterEvent::Request { worker, payload }).ok();
+        }
+        None => send_global(token, payload, worker, keyspace.to_string()),
+    }
+    // the worker is dropped along with its handle if the request is lost
+    Ok(rx.await.unwrap_or(Err(WorkerError::Lost))?)
 }
 
 /// A batch collector, used to collect statements and build a `BatchRequest`.
@@ -224,6 +300,12 @@ impl<S: Keyspace + Clone> BatchCollector<S, BatchTypeUnset, BatchType> {
 }
 
 impl<S: Keyspace, Type: Copy + Into<u8>> BatchCollector<S, Type, BatchStatementOrId> {
+    /// Append an unprepared statement, whose values are bound by the `Values` methods or by name
+    /// (ie `named_value`) for the statements with named bind markers (ie `:name`).
+    pub fn statement(self, statement: &str) -> BatchCollector<S, Type, BatchValues> {
+        Self::step(self.builder.statement(statement), self.map, self.keyspace)
+    }
+
     /// Append an insert query using the default query type defined in the `InsertBatch` impl
     /// and the statement defined in the `Insert` impl.
     pub fn insert<K, V>(mut self, key: &K, value: &V) -> BatchCollector<S, Type, BatchValues>
@@ -418,6 +500,30 @@ impl<S: Keyspace, Type: Copy + Into<u8>> BatchCollector<S, Type, BatchStatementO
 }
 
 impl<S: Keyspace, Type: Copy + Into<u8>> BatchCollector<S, Type, BatchValues> {
+    /// Append an unprepared statement, whose values are bound by the `Values` methods or by name
+    /// (ie `named_value`) for the statements with named bind markers (ie `:name`).
+    pub fn statement(self, statement: &str) -> BatchCollector<S, Type, BatchValues> {
+        Self::step(self.builder.statement(statement), self.map, self.keyspace)
+    }
+
+    /// Bind the value of the named bind marker of the last statement.
+    ///
+    /// Note: the named and positional values can't be mixed within a batch, therefore all the statements
+    /// must be appended by `statement` and bound by name.
+    pub fn named_value<V: ColumnEncoder + ?Sized>(self, name: &str, value: &V) -> BatchCollector<S, Type, BatchValues> {
+        Self::step(self.builder.named_value(name, value), self.map, self.keyspace)
+    }
+
+    /// Bind the value of the named bind marker of the last statement to be unset
+    pub fn named_unset_value(self, name: &str) -> BatchCollector<S, Type, BatchValues> {
+        Self::step(self.builder.named_unset_value(name), self.map, self.keyspace)
+    }
+
+    /// Bind the value of the named bind marker of the last statement to be null
+    pub fn named_null_value(self, name: &str) -> BatchCollector<S, Type, BatchValues> {
+        Self::step(self.builder.named_null_value(name), self.map, self.keyspace)
+    }
+
     /// Append an insert query using the default query type defined in the `InsertBatch` impl
     /// and the statement defined in the `Insert` impl.
     pub fn insert<K, V>(mut self, key: &K, value: &V) -> BatchCollector<S, Type, BatchValues>
@@ -616,6 +722,19 @@ impl<S: Keyspace, Type: Copy + Into<u8>> BatchCollector<S, Type, BatchValues> {
     }
 }
 
+impl<S: Keyspace, Type: Copy + Into<u8>> Values for BatchCollector<S, Type, BatchValues> {
+    type Return = Self;
+    fn value<V: ColumnEncoder + ?Sized>(self, value: &V) -> Self {
+        Self::step(self.builder.value(value), self.map, self.keyspace)
+    }
+    fn unset_value(self) -> Self {
+        Self::step(self.builder.unset_value(), self.map, self.keyspace)
+    }
+    fn null_value(self) -> Self {
+        Self::step(self.builder.null_value(), self.map, self.keyspace)
+    }
+}
+
 impl<S: Keyspace, Type: Copy + Into<u8>> BatchCollector<S, Type, BatchFlags> {
     /// Set the serial consistency for the batch
     pub fn serial_consistency(self, consistency: Consistency) -> BatchCollector<S, Type, BatchTimestamp> {
@@ -631,6 +750,7 @@ impl<S: Keyspace, Type: Copy + Into<u8>> BatchCollector<S, Type, BatchFlags> {
             token: rand::random::<i64>(),
             map: self.map,
             inner: self.builder.build()?.0.into(),
+            timeout: None,
             keyspace: self.keyspace,
         })
     }
@@ -647,6 +767,7 @@ impl<S: Keyspace, Type: Copy + Into<u8>> BatchCollector<S, Type, BatchTimestamp>
             token: rand::random::<i64>(),
             map: self.map,
             inner: self.builder.build()?.0.into(),
+            timeout: None,
             keyspace: self.keyspace,
         })
     }
@@ -659,6 +780,7 @@ impl<S: Keyspace, Type: Copy + Into<u8>> BatchCollector<S, Type, BatchBuild> {
             token: rand::random::<i64>(),
             map: self.map,
             inner: self.builder.build()?.0.into(),
+            timeout: None,
             keyspace: self.keyspace,
         })
     }
@@ -685,6 +807,110 @@ pub trait Batchable {
     {
         BatchCollector::new(self)
     }
+
+    /// Start building an unlogged batch which is split by destination shard (see `ShardAwareBatch`).
+    fn shard_aware_batch(&self) -> ShardAwareBatch<Self>
+    where
+        Self: Keyspace + Clone,
+    {
+        ShardAwareBatch::new(self)
+    }
 }
 
 impl<S: Keyspace + Clone> Batchable for S {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::{access::tests::MyKeyspace, diagnostics::ConnectionLabel},
+        cql::{opcode::RESULT, BatchQuery},
+    };
+
+    /// Encode the RESULT frame of a prepared statement, whose metadata is omitted
+    fn prepared_result(id: [u8; 16]) -> Vec<u8> {
+        let mut body = 4i32.to_be_bytes().to_vec();
+        body.extend(&16u16.to_be_bytes());
+        body.extend(&id);
+        let mut frame = vec![0x84, 0, 0, 0, RESULT];
+        frame.extend(&(body.len() as i32).to_be_bytes());
+        frame.extend(body);
+        frame
+    }
+
+    /// Get the statement of the PREPARE frame
+    fn prepared_statement(payload: &[u8]) -> String {
+        let length = i32::from_be_bytes([payload[9], payload[10], payload[11], payload[12]]) as usize;
+        String::from_utf8(payload[13..13 + length].to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reprepare_all_with_the_server_ids() {
+        let keyspace = MyKeyspace::new();
+        let insert = keyspace.insert_statement::<u32, f32>().to_string();
+        let update = keyspace.update_statement::<u32, f32>().to_string();
+        let batch = keyspace
+            .batch()
+            .logged()
+            .insert_prepared(&1u32, &1.0f32)
+            .update_prepared(&1u32, &2.0f32)
+            .insert_prepared(&2u32, &3.0f32)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        let values = BatchFrame::decode(batch.payload())
+            .unwrap()
+            .queries()
+            .iter()
+            .map(|query| match query {
+                BatchQuery::Prepared(_, values) => values.clone(),
+                BatchQuery::Statement(..) => panic!("unexpected statement"),
+            })
+            .collect::<Vec<_>>();
+        let (reporter, mut events) = ReporterHandle::detached(ConnectionLabel::shard(([127, 0, 0, 1], 9042).into(), 0));
+        let reprepared = tokio::spawn(async move { batch.reprepare_all(&Some(reporter)).await });
+        // the server derives the ids from the statements along with something else, ie the keyspace
+        for _ in 0..2 {
+            match events.recv().await {
+                Some(ReporterEvent::Request { worker, payload }) => {
+                    let id = if prepared_statement(&payload) == insert {
+                        [7; 16]
+                    } else {
+                        [8; 16]
+                    };
+                    worker.handle_response(prepared_result(id)).unwrap();
+                }
+                _ => panic!("expected a prepare request"),
+            }
+        }
+        let batch = reprepared.await.unwrap().unwrap();
+        let frame = BatchFrame::decode(batch.payload()).unwrap();
+        assert_eq!(
+            frame.queries(),
+            &[
+                BatchQuery::Prepared(vec![7; 16], values[0].clone()),
+                BatchQuery::Prepared(vec![8; 16], values[1].clone()),
+                BatchQuery::Prepared(vec![7; 16], values[2].clone()),
+            ]
+        );
+        assert_eq!(frame.consistency(), Consistency::One);
+        assert_eq!(batch.get_statement(&[7; 16]).as_deref(), Some(insert.as_str()));
+        assert_eq!(batch.get_statement(&[8; 16]).as_deref(), Some(update.as_str()));
+    }
+
+    #[tokio::test]
+    async fn reprepare_all_fails_on_a_lost_prepare() {
+        let keyspace = MyKeyspace::new();
+        let batch = keyspace
+            .batch()
+            .unlogged()
+            .insert_prepared(&1u32, &1.0f32)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        let (reporter, events) = ReporterHandle::detached(ConnectionLabel::shard(([127, 0, 0, 1], 9042).into(), 0));
+        // the reporter is closed, therefore the prepare request is dropped
+        drop(events);
+        assert!(batch.reprepare_all(&Some(reporter)).await.is_err());
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{ColumnEncoder, Null, Unset};
+
+/// A statement which is built without inlining any literal value, as every value is rendered as a bind
+/// marker and bound to the request, which prevents the values from being interpreted as CQL.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::app::access::BoundStatement;
+///
+/// let statement = BoundStatement::new("INSERT INTO ")
+///     .qualified_name("my_keyspace", "table")
+///     .push(" (key, note) VALUES (")
+///     .bind(&"key")
+///     .push(", ")
+///     .bind(&"'); DROP TABLE my_keyspace.table; --")
+///     .push(")");
+/// assert_eq!(
+///     statement.statement(),
+///     r#"INSERT INTO my_keyspace."table" (key, note) VALUES (?, ?)"#
+/// );
+/// assert_eq!(statement.values().len(), 2);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BoundStatement {
+    statement: String,
+    values: Vec<Vec<u8>>,
+}
+
+impl BoundStatement {
+    /// Start the statement with the CQL text
+    pub fn new(text: &str) -> Self {
+        Self {
+            statement: text.to_string(),
+            values: Vec::new(),
+        }
+    }
+    /// Append the CQL text, ie keywords and punctuation, which must never hold any value
+    pub fn push(mut self, text: &str) -> Self {
+        self.statement.push_str(text);
+        self
+    }
+    /// Append the identifier, which is only quoted when needed
+    pub fn name(mut self, name: &str) -> Self {
+        self.statement.push_str(&Name::auto(name).to_string());
+        self
+    }
+    /// Append the `keyspace.table` qualified name, where both identifiers are only quoted when needed
+    pub fn qualified_name(mut self, keyspace: &str, table: &str) -> Self {
+        self.statement.push_str(&Name::qualify(keyspace, table));
+        self
+    }
+    /// Append a bind marker, along with its bound value
+    pub fn bind<V: ColumnEncoder + ?Sized>(mut self, value: &V) -> Self {
+        self.statement.push('?');
+        self.values.push(value.encode_new());
+        self
+    }
+    /// Append a bind marker, along with its unset value
+    pub fn bind_unset(mut self) -> Self {
+        self.statement.push('?');
+        self.values.push(Unset.encode_new());
+        self
+    }
+    /// Append a bind marker, along with its null value
+    pub fn bind_null(mut self) -> Self {
+        self.statement.push('?');
+        self.values.push(Null.encode_new());
+        self
+    }
+    /// Get the statement
+    pub fn statement(&self) -> &str {
+        &self.statement
+    }
+    /// Get the encoded bound values, in the bind marker order
+    pub fn values(&self) -> &[Vec<u8>] {
+        &self.values
+    }
+    /// Bind the values to the frame builder, ie the one of the batch statement
+    pub fn bind_values<T: Values<Return = T>>(&self, builder: T) -> T {
+        self.values
+            .iter()
+            .fold(builder, |builder, value| builder.value(&Encoded(value)))
+    }
+    /// Build the query frame of the statement, along with its bound values
+    pub fn query(&self, consistency: Consistency) -> anyhow::Result<Query> {
+        let builder = Query::new().statement(&self.statement).consistency(consistency);
+        match self.values.split_first() {
+            None => builder.build(),
+            Some((first, rest)) => rest
+                .iter()
+                .fold(builder.value(&Encoded(first)), |builder, value| {
+                    builder.value(&Encoded(value))
+                })
+                .build(),
+        }
+    }
+}
+
+/// An already encoded value
+pub(crate) struct Encoded<'a>(pub(crate) &'a [u8]);
+
+impl<'a> ColumnEncoder for Encoded<'a> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.0);
+    }
+}
@@ -0,0 +1,659 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{ColumnDecoder, ColumnEncoder, ColumnValue, Frame, Iter, Row, Rows};
+use anyhow::{anyhow, ensure};
+use futures::stream::{self, Stream, StreamExt};
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The suffix of the CDC log tables
+pub const CDC_LOG_SUFFIX: &str = "_scylla_cdc_log";
+/// The offset between the UUID epoch (1582-10-15) and the unix epoch, in 100ns intervals
+const UUID_EPOCH_OFFSET: i64 = 0x01B2_1DD2_1381_4000;
+
+/// Get the name of the CDC log table of the base table
+pub fn cdc_log_table(table: &str) -> String {
+    format!("{}{}", table, CDC_LOG_SUFFIX)
+}
+
+/// Get the `cdc` table option which enables the change data capture on a base table, ie to be used as the
+/// options of a table declared by `keyspace_model!`.
+pub fn cdc_options(preimage: bool, postimage: bool) -> String {
+    format!(
+        "cdc = {{'enabled': true, 'preimage': {}, 'postimage': {}}}",
+        preimage, postimage
+    )
+}
+
+/// The id of a CDC stream, which is the partition key of the CDC log table.
+///
+/// The stream ids are chosen by Scylla such as their partitions are owned by the same replicas and shard as
+/// the base partitions they log, and their first 8 bytes hold the token of their partitions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CdcStreamId(Vec<u8>);
+
+impl CdcStreamId {
+    /// Create a stream id out of its bytes
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+    /// Get the bytes of the stream id
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+    /// Get the token of the stream partition
+    pub fn token(&self) -> i64 {
+        let mut token = [0; 8];
+        let len = self.0.len().min(8);
+        token[..len].copy_from_slice(&self.0[..len]);
+        i64::from_be_bytes(token)
+    }
+}
+
+impl ColumnEncoder for CdcStreamId {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.0.as_slice().encode(buffer)
+    }
+}
+
+impl ColumnDecoder for CdcStreamId {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self(slice.to_vec()))
+    }
+}
+
+/// The `timeuuid` of a change, which orders the changes of a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CdcTime([u8; 16]);
+
+impl CdcTime {
+    /// Get the smallest timeuuid of the unix timestamp in millis, like the `minTimeuuid` CQL function
+    pub fn min_for(millis: i64) -> Self {
+        Self::from_parts(millis * 10_000 + UUID_EPOCH_OFFSET, 0x80)
+    }
+    /// Get the greatest timeuuid of the unix timestamp in millis, like the `maxTimeuuid` CQL function
+    pub fn max_for(millis: i64) -> Self {
+        Self::from_parts(millis * 10_000 + 9_999 + UUID_EPOCH_OFFSET, 0x7f)
+    }
+    fn from_parts(timestamp: i64, clock_seq_and_node: u8) -> Self {
+        let mut uuid = [clock_seq_and_node; 16];
+        uuid[0..4].copy_from_slice(&(timestamp as u32).to_be_bytes());
+        uuid[4..6].copy_from_slice(&((timestamp >> 32) as u16).to_be_bytes());
+        uuid[6..8].copy_from_slice(&((((timestamp >> 48) as u16) & 0x0fff) | 0x1000).to_be_bytes());
+        Self(uuid)
+    }
+    /// Get the bytes of the timeuuid
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+    /// Get the unix timestamp of the timeuuid in millis
+    pub fn timestamp_millis(&self) -> i64 {
+        let low = u32::from_be_bytes([self.0[0], self.0[1], self.0[2], self.0[3]]) as i64;
+        let mid = u16::from_be_bytes([self.0[4], self.0[5]]) as i64;
+        let high = (u16::from_be_bytes([self.0[6], self.0[7]]) & 0x0fff) as i64;
+        (((high << 48) | (mid << 32) | low) - UUID_EPOCH_OFFSET) / 10_000
+    }
+}
+
+impl ColumnEncoder for CdcTime {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        (&self.0[..]).encode(buffer)
+    }
+}
+
+impl ColumnDecoder for CdcTime {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        ensure!(slice.len() == 16, "Invalid timeuuid length: {}", slice.len());
+        let mut uuid = [0; 16];
+        uuid.copy_from_slice(slice);
+        Ok(Self(uuid))
+    }
+}
+
+/// The operation of a CDC log row
+#[repr(i8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CdcOperation {
+    /// The state of the row before the change
+    PreImage = 0,
+    /// An UPDATE of the row
+    Update = 1,
+    /// An INSERT of the row
+    Insert = 2,
+    /// A DELETE of the row
+    RowDelete = 3,
+    /// A DELETE of the whole partition
+    PartitionDelete = 4,
+    /// The inclusive start of a range delete
+    RangeDeleteStartInclusive = 5,
+    /// The exclusive start of a range delete
+    RangeDeleteStartExclusive = 6,
+    /// The inclusive end of a range delete
+    RangeDeleteEndInclusive = 7,
+    /// The exclusive end of a range delete
+    RangeDeleteEndExclusive = 8,
+    /// The state of the row after the change
+    PostImage = 9,
+}
+
+impl TryFrom<i8> for CdcOperation {
+    type Error = anyhow::Error;
+    fn try_from(operation: i8) -> anyhow::Result<Self> {
+        Ok(match operation {
+            0 => CdcOperation::PreImage,
+            1 => CdcOperation::Update,
+            2 => CdcOperation::Insert,
+            3 => CdcOperation::RowDelete,
+            4 => CdcOperation::PartitionDelete,
+            5 => CdcOperation::RangeDeleteStartInclusive,
+            6 => CdcOperation::RangeDeleteStartExclusive,
+            7 => CdcOperation::RangeDeleteEndInclusive,
+            8 => CdcOperation::RangeDeleteEndExclusive,
+            9 => CdcOperation::PostImage,
+            operation => anyhow::bail!("Unknown CDC operation: {}", operation),
+        })
+    }
+}
+
+/// A row of the CDC log table, made of the CDC metadata columns followed by the selected base columns
+#[derive(Debug, Clone)]
+pub struct CdcRecord<R> {
+    /// The stream of the change
+    pub stream_id: CdcStreamId,
+    /// The time of the change
+    pub time: CdcTime,
+    /// The sequence number of the row within the change batch
+    pub batch_seq_no: i32,
+    /// The operation of the row
+    pub operation: CdcOperation,
+    /// The TTL of the change, if any
+    pub ttl: Option<i64>,
+    /// Whether the row is the last one of the change batch
+    pub end_of_batch: Option<bool>,
+    /// The selected base columns
+    pub row: R,
+}
+
+impl<R: Row> Row for CdcRecord<R> {
+    fn try_decode_row<T: Rows + ColumnValue>(rows: &mut T) -> anyhow::Result<Self> {
+        Ok(Self {
+            stream_id: rows.column_value()?,
+            time: rows.column_value()?,
+            batch_seq_no: rows.column_value()?,
+            operation: CdcOperation::try_from(rows.column_value::<i8>()?)?,
+            ttl: rows.column_value()?,
+            end_of_batch: rows.column_value()?,
+            row: R::try_decode_row(rows)?,
+        })
+    }
+}
+
+/// The key of a CDC log select, which selects the changes of a stream within `(after, until]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdcQuery {
+    /// The stream of the changes
+    pub stream_id: CdcStreamId,
+    /// The exclusive lower bound of the changes time
+    pub after: CdcTime,
+    /// The inclusive upper bound of the changes time
+    pub until: CdcTime,
+}
+
+impl CdcQuery {
+    /// Select the changes of the stream made within the unix timestamps `(after, until]` in millis
+    pub fn new(stream_id: CdcStreamId, after: i64, until: i64) -> Self {
+        Self {
+            stream_id,
+            after: CdcTime::max_for(after),
+            until: CdcTime::max_for(until),
+        }
+    }
+}
+
+/// A CDC generation, which defines the set of streams used from its timestamp until the next generation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdcGeneration {
+    /// The unix timestamp in millis from which the generation is used
+    pub timestamp: i64,
+    /// The streams of the generation
+    pub streams: Vec<CdcStreamId>,
+}
+
+/// The CDC log table of a base table with the change data capture enabled (see `cdc_options`).
+///
+/// It implements `Select<CdcQuery, Vec<CdcRecord<R>>>`, where `R` is the `Row` of the selected base columns,
+/// therefore the changes of a stream can be selected and paged like any other `Select` implementation, and
+/// the requests are routed to the replicas of the stream partition by the token held by the stream id.
+///
+/// ## Examples
+/// ```no_run
+/// use futures::StreamExt;
+/// use scylla_rs::app::access::CdcLog;
+/// # async fn run() -> anyhow::Result<()> {
+/// let log = CdcLog::<(String, Option<i64>)>::new("my_keyspace", "balances", &["account", "balance"]);
+/// let mut changes = log.reader(0).into_stream().boxed();
+/// while let Some(change) = changes.next().await {
+///     let change = change?;
+///     let (account, balance) = change.row;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct CdcLog<R> {
+    keyspace: Cow<'static, str>,
+    table: String,
+    columns: Vec<String>,
+    _marker: PhantomData<fn() -> R>,
+}
+
+impl<R> Clone for CdcLog<R> {
+    fn clone(&self) -> Self {
+        Self {
+            keyspace: self.keyspace.clone(),
+            table: self.table.clone(),
+            columns: self.columns.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R> std::fmt::Debug for CdcLog<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CdcLog")
+            .field("keyspace", &self.keyspace)
+            .field("table", &self.table)
+            .field("columns", &self.columns)
+            .finish()
+    }
+}
+
+impl<R> CdcLog<R> {
+    /// Create the CDC log of the base table, which selects the base columns decoded by `R`
+    pub fn new<K: Into<Cow<'static, str>>, T: Into<String>>(keyspace: K, table: T, columns: &[&str]) -> Self {
+        Self {
+            keyspace: keyspace.into(),
+            table: table.into(),
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            _marker: PhantomData,
+        }
+    }
+    /// Create the CDC log of a base table of the keyspace
+    pub fn of<S: Keyspace, T: Into<String>>(keyspace: &S, table: T, columns: &[&str]) -> Self {
+        Self::new(keyspace.name().clone(), table, columns)
+    }
+    /// Get the statement which enables the change data capture on the base table
+    pub fn enable_statement(&self, preimage: bool, postimage: bool) -> String {
+        format!(
+            "ALTER TABLE {} WITH {}",
+            Name::qualify(&self.keyspace, &self.table),
+            cdc_options(preimage, postimage)
+        )
+    }
+    /// Get the name of the CDC log table
+    pub fn log_table(&self) -> String {
+        cdc_log_table(&self.table)
+    }
+}
+
+impl<R> Keyspace for CdcLog<R> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.keyspace
+    }
+}
+
+impl<R> ComputeToken<CdcQuery> for CdcLog<R> {
+    fn token(query: &CdcQuery) -> i64 {
+        query.stream_id.token()
+    }
+}
+
+impl<R: Row> RowsDecoder<CdcQuery, Vec<CdcRecord<R>>> for CdcLog<R> {
+    type Row = CdcRecord<R>;
+    fn try_decode(decoder: Decoder) -> anyhow::Result<Option<Vec<CdcRecord<R>>>> {
+        ensure!(decoder.is_rows()?, "Decoded response is not rows!");
+        let rows = Iter::<CdcRecord<R>>::new(decoder)?.collect::<Vec<_>>();
+        Ok(if rows.is_empty() { None } else { Some(rows) })
+    }
+}
+
+impl<R: Row> Select<CdcQuery, Vec<CdcRecord<R>>> for CdcLog<R> {
+    type QueryOrPrepared = PreparedStatement;
+    fn statement(&self) -> Cow<'static, str> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| format!(", {}", Name::auto(column)))
+            .collect::<String>();
+        format!(
+            "SELECT \"cdc$stream_id\", \"cdc$time\", \"cdc$batch_seq_no\", \"cdc$operation\", \"cdc$ttl\", \
+             \"cdc$end_of_batch\"{} FROM {} WHERE \"cdc$stream_id\" = ? AND \"cdc$time\" > ? AND \"cdc$time\" <= ?",
+            columns,
+            Name::qualify(&self.keyspace, &self.log_table())
+        )
+        .into()
+    }
+    fn bind_values<T: Values>(builder: T, query: &CdcQuery) -> T::Return {
+        builder.value(&query.stream_id).value(&query.after).value(&query.until)
+    }
+}
+
+impl<R: 'static + Row + Clone + Send> CdcLog<R> {
+    /// Create a reader which tails the changes made after the unix timestamp in millis
+    pub fn reader(&self, after: i64) -> CdcReader<R> {
+        CdcReader::new(self.clone(), after)
+    }
+}
+
+/// Tails the changes of a CDC log across its generations.
+///
+/// The reader polls the streams of the current generation for the changes made within consecutive windows,
+/// which lag behind the current time by a confidence window, as the changes are timestamped by the
+/// coordinators and might be received out of order. Once the next generation is reached, the reader moves to
+/// its streams.
+///
+/// Note: the changes of a window are yielded stream by stream, ordered within each stream.
+pub struct CdcReader<R> {
+    log: CdcLog<R>,
+    position: i64,
+    confidence_window: Duration,
+    poll_interval: Duration,
+    page_size: i32,
+    consistency: Consistency,
+    concurrency: usize,
+    generations: Vec<CdcGeneration>,
+}
+
+impl<R: 'static + Row + Clone + Send> CdcReader<R> {
+    /// Create a reader of the changes made after the unix timestamp in millis
+    pub fn new(log: CdcLog<R>, after: i64) -> Self {
+        Self {
+            log,
+            position: after,
+            confidence_window: Duration::from_secs(30),
+            poll_interval: Duration::from_secs(5),
+            page_size: 1000,
+            consistency: Consistency::Quorum,
+            concurrency: 16,
+            generations: Vec::new(),
+        }
+    }
+
+    /// Set how far the read windows lag behind the current time
+    pub fn confidence_window(mut self, confidence_window: Duration) -> Self {
+        self.confidence_window = confidence_window;
+        self
+    }
+
+    /// Set the delay between the polls once the reader caught up
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Set the page size of the stream selects
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Set the consistency of the stream selects
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Set the maximum number of streams which are read concurrently
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Get the unix timestamp in millis up to which (inclusive) the changes have been read, which can be used
+    /// to resume the reader later
+    pub fn position(&self) -> i64 {
+        self.position
+    }
+
+    /// Read the changes of the next window, or None if the reader caught up with the confidence window.
+    pub async fn next_window(&mut self) -> anyhow::Result<Option<Vec<CdcRecord<R>>>> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        let mut until = now - self.confidence_window.as_millis() as i64;
+        if until <= self.position {
+            return Ok(None);
+        }
+        // the window starts at the next millisecond, which is the first one of the next generation at its boundary
+        let start = self.position + 1;
+        // refresh the generations if the window might start in an unknown one
+        if !matches!(self.generations.last(), Some(last) if last.timestamp > start) {
+            self.generations = fetch_generations(self.consistency).await?;
+        }
+        let current = self
+            .generations
+            .iter()
+            .rposition(|generation| generation.timestamp <= start)
+            .or_else(|| if self.generations.is_empty() { None } else { Some(0) })
+            .ok_or_else(|| anyhow!("No CDC generation found"))?;
+        // a window never spans two generations
+        if let Some(next) = self.generations.get(current + 1) {
+            until = until.min(next.timestamp - 1);
+        }
+        let position = self.position.max(self.generations[current].timestamp - 1);
+        let records = stream::iter(self.generations[current].streams.iter().cloned())
+            .map(|stream_id| self.read_stream(CdcQuery::new(stream_id, position, until)))
+            .buffered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.position = until;
+        Ok(Some(records.into_iter().flatten().collect()))
+    }
+
+    /// Read all the pages of the stream changes
+    async fn read_stream(&self, query: CdcQuery) -> anyhow::Result<Vec<CdcRecord<R>>> {
+        let mut pages = PagedIter::<_, _, Vec<CdcRecord<R>>>::new(self.log.clone(), query, self.page_size)
+            .consistency(self.consistency);
+        let mut records = Vec::new();
+        while let Some(page) = pages.next_page().await? {
+            records.extend(page);
+        }
+        Ok(records)
+    }
+
+    /// Consume the reader into an endless stream of the changes, which polls the CDC log once it caught up.
+    /// The stream ends after the first error.
+    pub fn into_stream(self) -> impl Stream<Item = anyhow::Result<CdcRecord<R>>> {
+        stream::unfold(Some((self, VecDeque::new())), |state| async move {
+            let (mut reader, mut records) = state?;
+            loop {
+                if let Some(record) = records.pop_front() {
+                    return Some((Ok(record), Some((reader, records))));
+                }
+                match reader.next_window().await {
+                    Ok(Some(window)) => records.extend(window),
+                    Ok(None) => tokio::time::sleep(reader.poll_interval).await,
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
+        })
+    }
+}
+
+/// Fetch the CDC generations of the cluster, ordered by their timestamps
+pub async fn fetch_generations(consistency: Consistency) -> anyhow::Result<Vec<CdcGeneration>> {
+    let Query(payload) = Query::new()
+        .statement("SELECT time FROM system_distributed.cdc_generation_timestamps WHERE key = 'timestamps'")
+        .consistency(consistency)
+        .build()?;
+    let decoder = Decoder::try_from(send(payload).await?)?;
+    let mut timestamps = Iter::<(i64,)>::new(decoder)?
+        .map(|(timestamp,)| timestamp)
+        .collect::<Vec<_>>();
+    timestamps.sort_unstable();
+    let mut generations = Vec::with_capacity(timestamps.len());
+    for timestamp in timestamps {
+        let Query(payload) = Query::new()
+            .statement("SELECT streams FROM system_distributed.cdc_streams_descriptions_v2 WHERE time = ?")
+            .consistency(consistency)
+            .value(&timestamp)
+            .build()?;
+        let decoder = Decoder::try_from(send(payload).await?)?;
+        let streams = Iter::<(Vec<CdcStreamId>,)>::new(decoder)?
+            .flat_map(|(streams,)| streams)
+            .collect();
+        generations.push(CdcGeneration { timestamp, streams });
+    }
+    Ok(generations)
+}
+
+/// Send the request and await its response payload
+async fn send(payload: Vec<u8>) -> Result<Vec<u8>, WorkerError> {
+    // the system_distributed tables are small, so any token works
+    send_oneshot(rand::random(), payload, String::new()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::access::stub::{request_values, requests, rows_result},
+        cql::opcode,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// The read of a stream, as `(stream, after, until)`
+    type StreamRead = (Vec<u8>, i64, i64);
+
+    fn now() -> i64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64
+    }
+
+    /// Stub the generations of the cluster along with their streams, where the CDC log streams don't have any
+    /// change. Returns the stream reads, in order.
+    fn generations(generations: Vec<(i64, Vec<&'static [u8]>)>) -> Arc<Mutex<Vec<StreamRead>>> {
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let read = reads.clone();
+        requests(move |payload| {
+            let values = request_values(payload);
+            let frame = if payload[4] == opcode::EXECUTE {
+                // the select of the CDC log stream
+                let time = |index: usize| CdcTime::try_decode(values[index].as_ref().unwrap()).unwrap();
+                read.lock().unwrap().push((
+                    values[0].clone().unwrap(),
+                    time(1).timestamp_millis(),
+                    time(2).timestamp_millis(),
+                ));
+                let columns = [
+                    ("cdc$stream_id", 0x0003),
+                    ("cdc$time", 0x000F),
+                    ("cdc$batch_seq_no", 0x0009),
+                    ("cdc$operation", 0x0014),
+                    ("cdc$ttl", 0x0002),
+                    ("cdc$end_of_batch", 0x0004),
+                    ("balance", 0x0002),
+                ];
+                rows_result(&columns, &[])
+            } else if values.is_empty() {
+                let rows: Vec<_> = generations
+                    .iter()
+                    .map(|(timestamp, _)| vec![Some(timestamp.to_be_bytes().to_vec())])
+                    .collect();
+                rows_result(&[("time", 0x000B)], &rows)
+            } else {
+                let timestamp = i64::try_decode(values[0].as_ref().unwrap()).unwrap();
+                let (_, streams) = generations.iter().find(|(time, _)| *time == timestamp).unwrap();
+                let mut list = (streams.len() as i32).to_be_bytes().to_vec();
+                for stream in streams {
+                    list.extend(&(stream.len() as i32).to_be_bytes());
+                    list.extend(*stream);
+                }
+                rows_result(&[("streams", 0x0003)], &[vec![Some(list)]])
+            };
+            Ok(frame)
+        });
+        reads
+    }
+
+    fn reader(after: i64) -> CdcReader<(Option<i64>,)> {
+        CdcLog::new("ks", "balances", &["balance"])
+            .reader(after)
+            .confidence_window(Duration::from_secs(30))
+    }
+
+    #[test]
+    fn stream_token() {
+        let stream = CdcStreamId::new(vec![0x80, 0, 0, 0, 0, 0, 0, 1, 0xff, 0xff]);
+        assert_eq!(stream.token(), i64::MIN + 1);
+        // the short stream ids are padded
+        assert_eq!(CdcStreamId::new(vec![0, 1]).token(), 1 << 48);
+    }
+
+    #[test]
+    fn time_bounds() {
+        let millis = 1_600_000_000_123;
+        assert_eq!(CdcTime::min_for(millis).timestamp_millis(), millis);
+        assert_eq!(CdcTime::max_for(millis).timestamp_millis(), millis);
+        // the window excludes the changes of its first millisecond, and includes the ones of its last one
+        let query = CdcQuery::new(CdcStreamId::new(vec![1]), millis, millis + 10);
+        assert_eq!(query.after, CdcTime::max_for(millis));
+        assert_eq!(query.until, CdcTime::max_for(millis + 10));
+    }
+
+    #[test]
+    fn statements() {
+        let log = CdcLog::<(Option<i64>,)>::new("ks", "balances", &["balance"]);
+        assert_eq!(log.log_table(), "balances_scylla_cdc_log");
+        assert_eq!(
+            log.enable_statement(true, false),
+            "ALTER TABLE ks.balances WITH cdc = {'enabled': true, 'preimage': true, 'postimage': false}"
+        );
+        assert_eq!(
+            log.statement(),
+            "SELECT \"cdc$stream_id\", \"cdc$time\", \"cdc$batch_seq_no\", \"cdc$operation\", \"cdc$ttl\", \
+             \"cdc$end_of_batch\", balance FROM ks.balances_scylla_cdc_log \
+             WHERE \"cdc$stream_id\" = ? AND \"cdc$time\" > ? AND \"cdc$time\" <= ?"
+        );
+    }
+
+    #[tokio::test]
+    async fn read_the_streams_of_the_current_generation() {
+        let first = now() - 3_600_000;
+        let reads = generations(vec![(first, vec![&[1], &[2]])]);
+        // the reader starts at the first generation, as there's no change before it
+        let mut reader = reader(0);
+        assert!(reader.next_window().await.unwrap().unwrap().is_empty());
+        let until = reader.position();
+        assert!(until <= now() - 30_000);
+        assert_eq!(
+            *reads.lock().unwrap(),
+            vec![(vec![1], first - 1, until), (vec![2], first - 1, until)]
+        );
+    }
+
+    #[tokio::test]
+    async fn switch_generations_at_their_boundary() {
+        let (first, second) = (now() - 3_600_000, now() - 1_800_000);
+        let reads = generations(vec![(first, vec![&[1], &[2]]), (second, vec![&[3]])]);
+        let mut reader = reader(first + 1_000);
+        // the window ends right before the next generation
+        reader.next_window().await.unwrap().unwrap();
+        assert_eq!(reader.position(), second - 1);
+        // then the next window reads the streams of the next generation only
+        reader.next_window().await.unwrap().unwrap();
+        let until = reader.position();
+        assert!(until > second);
+        assert_eq!(
+            *reads.lock().unwrap(),
+            vec![
+                (vec![1], first + 1_000, second - 1),
+                (vec![2], first + 1_000, second - 1),
+                (vec![3], second - 1, until),
+            ]
+        );
+    }
+}
@@ -0,0 +1,588 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{bound::Encoded, *};
+use crate::cql::{statements::schema::ClusteringOrder, ColumnEncoder, Null, Unset};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Checkpointed scan trait which pages through a wide partition by its clustering columns
+/// (`WHERE ck > last_seen LIMIT n`) instead of the opaque server paging state.
+///
+/// The generated statements are derived from the table's partition and clustering columns,
+/// so the application only has to persist the `Checkpoint` in order to resume the scan
+/// after a failure or a restart.
+///
+/// The rows are scanned in the clustering order of the table, see `Checkpointed::clustering_order`.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::{
+///     app::access::{Checkpoint, Checkpointed, ComputeToken, GetCheckpointRequest, Keyspace},
+///     cql::{statements::schema::ClusteringOrder, Consistency, Decoder, Frame, Iter, Rows, RowsDecoder, Values},
+/// };
+/// use std::borrow::Cow;
+/// # #[derive(Default, Clone, Debug)]
+/// # struct MyKeyspace {
+/// #     pub name: Cow<'static, str>,
+/// # }
+/// # impl Keyspace for MyKeyspace {
+/// #     fn name(&self) -> &Cow<'static, str> {
+/// #         &self.name
+/// #     }
+/// # }
+/// # impl ComputeToken<String> for MyKeyspace {
+/// #     fn token(_key: &String) -> i64 {
+/// #         rand::random()
+/// #     }
+/// # }
+/// impl RowsDecoder<String, Vec<(i64, String)>> for MyKeyspace {
+///     type Row = (i64, String);
+///     fn try_decode(decoder: Decoder) -> anyhow::Result<Option<Vec<(i64, String)>>> {
+///         anyhow::ensure!(decoder.is_rows()?, "Decoded response is not rows!");
+///         Ok(Some(Iter::<(i64, String)>::new(decoder)?.collect()))
+///     }
+/// }
+///
+/// impl Checkpointed<String, i64, Vec<(i64, String)>> for MyKeyspace {
+///     fn table(&self) -> Cow<'static, str> {
+///         "events".into()
+///     }
+///     fn columns(&self) -> &'static [&'static str] {
+///         &["seq", "payload"]
+///     }
+///     fn partition_key(&self) -> &'static [&'static str] {
+///         &["stream"]
+///     }
+///     fn clustering_key(&self) -> &'static [&'static str] {
+///         &["seq"]
+///     }
+///     fn clustering_order(&self) -> &'static [ClusteringOrder] {
+///         &[ClusteringOrder::Desc]
+///     }
+///     fn bind_key<T: Values>(builder: T, key: &String) -> T::Return {
+///         builder.value(key)
+///     }
+///     fn bind_checkpoint<T: Values>(builder: T, checkpoint: &i64) -> T::Return {
+///         builder.value(checkpoint)
+///     }
+/// }
+///
+/// # let keyspace = MyKeyspace { name: "my_keyspace".into() };
+/// let key = "stream-1".to_string();
+/// let mut checkpoint = Checkpoint::<i64>::new(2)?;
+/// let request = keyspace
+///     .checkpoint_scan::<i64, Vec<(i64, String)>>(&key, &checkpoint)
+///     .consistency(Consistency::One)
+///     .build()?;
+/// // once the page of the request is decoded, move the checkpoint past its last row
+/// let page = vec![(9, "a".to_string()), (8, "b".to_string())];
+/// assert!(keyspace.next_page(&mut checkpoint, page.len(), page.last().map(|(seq, _)| *seq)));
+/// assert_eq!(
+///     keyspace.checkpoint_statement(&checkpoint),
+///     "SELECT seq, payload FROM my_keyspace.events WHERE stream = ? AND (seq) < (?) LIMIT ?"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait Checkpointed<K, C, V>: Keyspace + RowsDecoder<K, V> + ComputeToken<K> {
+    /// The table name (without the keyspace prefix)
+    fn table(&self) -> Cow<'static, str>;
+
+    /// The selected columns; the clustering columns should be included in order to
+    /// recover the next checkpoint from the decoded rows.
+    fn columns(&self) -> &'static [&'static str];
+
+    /// The partition key columns, in the order they are bound by `bind_key`
+    fn partition_key(&self) -> &'static [&'static str];
+
+    /// The clustering columns, in the order they are bound by `bind_checkpoint`
+    fn clustering_key(&self) -> &'static [&'static str];
+
+    /// The clustering order of the table, in the order of the clustering columns, where the missing ones are
+    /// ascending
+    fn clustering_order(&self) -> &'static [ClusteringOrder] {
+        &[]
+    }
+
+    /// Bind the partition key values to the builder
+    fn bind_key<T: Values>(builder: T, key: &K) -> T::Return;
+
+    /// Bind the last seen clustering key values to the builder
+    fn bind_checkpoint<T: Values>(builder: T, checkpoint: &C) -> T::Return;
+
+    /// Generate the scan statement, which resumes right after the last seen clustering key of the checkpoint if any.
+    ///
+    /// The clustering columns are split in runs of consecutive columns with the same order, as a single relation
+    /// can't follow a mixed clustering order. The statement restricts the columns which precede the resumed run to
+    /// their last seen values, and the columns of the run to the following ones in the clustering order, ie
+    /// `(a, b) > (?, ?)` for ascending columns and `(a, b) < (?, ?)` for descending ones.
+    fn checkpoint_statement(&self, checkpoint: &Checkpoint<C>) -> Cow<'static, str> {
+        let mut restrictions = self
+            .partition_key()
+            .iter()
+            .map(|col| format!("{} = ?", col))
+            .collect::<Vec<_>>();
+        if let Some((run, order)) = resumed_run(self.clustering_key().len(), self.clustering_order(), checkpoint) {
+            let clustering_key = self.clustering_key();
+            restrictions.extend(clustering_key[..run.start].iter().map(|col| format!("{} = ?", col)));
+            let run = &clustering_key[run];
+            restrictions.push(format!(
+                "({}) {} ({})",
+                run.join(", "),
+                match order {
+                    ClusteringOrder::Asc => ">",
+                    ClusteringOrder::Desc => "<",
+                },
+                vec!["?"; run.len()].join(", ")
+            ));
+        }
+        format!(
+            "SELECT {} FROM {}.{} WHERE {} LIMIT ?",
+            self.columns().join(", "),
+            self.name(),
+            self.table(),
+            restrictions.join(" AND ")
+        )
+        .into()
+    }
+
+    /// Move the checkpoint past the latest page, given its number of rows and the clustering key of its last row,
+    /// and return whether the scan has more rows to fetch.
+    ///
+    /// A short page exhausts the resumed run of clustering columns, after which the scan resumes from the previous
+    /// run (see `checkpoint_statement`), until the first one is exhausted.
+    fn next_page(&self, checkpoint: &mut Checkpoint<C>, rows_count: usize, last_seen: Option<C>) -> bool {
+        if !checkpoint.is_exhausted(rows_count) {
+            if let Some(last_seen) = last_seen {
+                checkpoint.advance(last_seen);
+            }
+            return true;
+        }
+        let resumed = checkpoint.last_seen.is_some();
+        if let Some(last_seen) = last_seen {
+            checkpoint.last_seen.replace(last_seen);
+        }
+        let runs = clustering_runs(self.clustering_key().len(), self.clustering_order()).len();
+        if resumed && checkpoint.exhausted_runs + 1 < runs {
+            checkpoint.exhausted_runs += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Split the clustering columns in runs of consecutive columns with the same order
+fn clustering_runs(len: usize, orders: &[ClusteringOrder]) -> Vec<(Range<usize>, ClusteringOrder)> {
+    let mut runs: Vec<(Range<usize>, ClusteringOrder)> = Vec::new();
+    for index in 0..len {
+        let order = orders.get(index).copied().unwrap_or(ClusteringOrder::Asc);
+        match runs.last_mut() {
+            Some((run, run_order)) if *run_order == order => run.end = index + 1,
+            _ => runs.push((index..index + 1, order)),
+        }
+    }
+    runs
+}
+
+/// Get the run of clustering columns which the checkpoint resumes from, if it resumes the scan
+fn resumed_run<C>(
+    len: usize,
+    orders: &[ClusteringOrder],
+    checkpoint: &Checkpoint<C>,
+) -> Option<(Range<usize>, ClusteringOrder)> {
+    checkpoint.last_seen.as_ref()?;
+    let mut runs = clustering_runs(len, orders);
+    let index = runs.len().checked_sub(1 + checkpoint.exhausted_runs)?;
+    Some(runs.swap_remove(index))
+}
+
+/// Collects the encoded values of the last seen clustering key, as the resumed run only binds a prefix of them
+#[derive(Default)]
+struct CheckpointValues(Vec<Vec<u8>>);
+
+impl Values for CheckpointValues {
+    type Return = Self;
+    fn value<V: ColumnEncoder + ?Sized>(mut self, value: &V) -> Self {
+        self.0.push(value.encode_new());
+        self
+    }
+    fn unset_value(mut self) -> Self {
+        self.0.push(Unset.encode_new());
+        self
+    }
+    fn null_value(mut self) -> Self {
+        self.0.push(Null.encode_new());
+        self
+    }
+}
+
+/// The explicit position of a checkpointed scan, which can be persisted by the application
+/// and used to resume the scan later on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint<C> {
+    last_seen: Option<C>,
+    limit: i32,
+    /// The number of exhausted runs of clustering columns since the last full page, see `Checkpointed::next_page`
+    #[serde(default)]
+    exhausted_runs: usize,
+}
+
+impl<C> Checkpoint<C> {
+    /// Create a checkpoint which starts from the beginning of the partition
+    pub fn new(limit: i32) -> anyhow::Result<Self> {
+        Self::resume(None, limit)
+    }
+
+    /// Create a checkpoint which resumes right after the provided clustering key
+    pub fn resume(last_seen: Option<C>, limit: i32) -> anyhow::Result<Self> {
+        anyhow::ensure!(limit > 0, "Checkpoint limit must be greater than zero, got {}", limit);
+        Ok(Self {
+            last_seen,
+            limit,
+            exhausted_runs: 0,
+        })
+    }
+
+    /// Move the checkpoint to the last clustering key seen in the latest page, which is a full one
+    pub fn advance(&mut self, last_seen: C) {
+        self.last_seen.replace(last_seen);
+        self.exhausted_runs = 0;
+    }
+
+    /// Get the last seen clustering key, if any
+    pub fn last_seen(&self) -> Option<&C> {
+        self.last_seen.as_ref()
+    }
+
+    /// Get the maximum number of rows fetched per page
+    pub fn limit(&self) -> i32 {
+        self.limit
+    }
+
+    /// Check whether the latest page is a short one, which exhausts the scan of a table whose clustering columns
+    /// share the same order, see `Checkpointed::next_page` otherwise
+    pub fn is_exhausted(&self, rows_count: usize) -> bool {
+        rows_count < self.limit as usize
+    }
+}
+
+/// Defines a helper method to start a checkpointed scan
+pub trait GetCheckpointRequest<S, K> {
+    /// Specifies the clustering key and value types for an upcoming checkpointed scan
+    fn checkpoint_scan<'a, C, V>(
+        &'a self,
+        key: &'a K,
+        checkpoint: &'a Checkpoint<C>,
+    ) -> CheckpointBuilder<'a, S, K, C, V, QueryConsistency>
+    where
+        S: Checkpointed<K, C, V>;
+}
+
+impl<S: Keyspace, K> GetCheckpointRequest<S, K> for S {
+    fn checkpoint_scan<'a, C, V>(
+        &'a self,
+        key: &'a K,
+        checkpoint: &'a Checkpoint<C>,
+    ) -> CheckpointBuilder<'a, S, K, C, V, QueryConsistency>
+    where
+        S: Checkpointed<K, C, V>,
+    {
+        let statement = self.checkpoint_statement(checkpoint);
+        CheckpointBuilder {
+            _marker: PhantomData,
+            keyspace: self,
+            key,
+            checkpoint,
+            builder: Query::new().statement(&statement),
+        }
+    }
+}
+
+/// Builder of a checkpointed scan request
+pub struct CheckpointBuilder<'a, S, K, C, V, Stage> {
+    _marker: PhantomData<(&'a S, &'a K, &'a C, &'a V)>,
+    keyspace: &'a S,
+    key: &'a K,
+    checkpoint: &'a Checkpoint<C>,
+    builder: QueryBuilder<Stage>,
+}
+
+impl<'a, S: Checkpointed<K, C, V>, K, C, V> CheckpointBuilder<'a, S, K, C, V, QueryConsistency> {
+    /// Set the consistency and bind the partition key, checkpoint and limit values
+    pub fn consistency(self, consistency: Consistency) -> CheckpointBuilder<'a, S, K, C, V, QueryValues> {
+        let mut builder = S::bind_key(self.builder.consistency(consistency), self.key);
+        let clustering_key = self.keyspace.clustering_key();
+        let run = resumed_run(clustering_key.len(), self.keyspace.clustering_order(), self.checkpoint);
+        if let (Some(last_seen), Some((run, _))) = (self.checkpoint.last_seen.as_ref(), run) {
+            // bind the values of the run and of the preceding columns
+            let CheckpointValues(values) = S::bind_checkpoint(CheckpointValues::default(), last_seen);
+            for value in values.iter().take(run.end) {
+                builder = builder.value(&Encoded(value));
+            }
+        }
+        CheckpointBuilder {
+            _marker: self._marker,
+            keyspace: self.keyspace,
+            key: self.key,
+            checkpoint: self.checkpoint,
+            builder: builder.value(&self.checkpoint.limit),
+        }
+    }
+}
+
+impl<'a, S: Checkpointed<K, C, V>, K, C, V> CheckpointBuilder<'a, S, K, C, V, QueryValues> {
+    /// Build the CheckpointRequest
+    pub fn build(self) -> anyhow::Result<CheckpointRequest<S, K, C, V>> {
+        let query = self.builder.build()?;
+        Ok(CheckpointRequest {
+            token: S::token(self.key),
+            inner: query.into(),
+            timeout: None,
+            statement: self.keyspace.checkpoint_statement(self.checkpoint),
+            keyspace: self.keyspace.clone(),
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// A checkpointed scan request which can be sent to the ring
+#[derive(Clone, Debug)]
+pub struct CheckpointRequest<S, K, C, V> {
+    token: i64,
+    inner: Vec<u8>,
+    timeout: Option<Duration>,
+    statement: Cow<'static, str>,
+    keyspace: S,
+    _marker: PhantomData<(S, K, C, V)>,
+}
+
+impl<S, K, C, V> Request for CheckpointRequest<S, K, C, V>
+where
+    S: Checkpointed<K, C, V>,
+    K: Send,
+    C: Send,
+    V: Send,
+{
+    fn statement(&self) -> Cow<'static, str> {
+        self.statement.clone()
+    }
+
+    fn payload(&self) -> &Vec<u8> {
+        &self.inner
+    }
+}
+
+impl<S: Checkpointed<K, C, V>, K, C, V> CheckpointRequest<S, K, C, V> {
+    /// Return DecodeResult marker type, useful in case the worker struct wants to hold the
+    /// decoder in order to decode the response inside handle_response method.
+    pub fn result_decoder(&self) -> DecodeResult<DecodeRows<S, K, V>> {
+        DecodeResult::select()
+    }
+
+    /// Set the client-side timeout of the request, after which its worker is resolved with `WorkerError::Timeout`,
+    /// or None to await its response until the connection is lost
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Decode the query frame of the request, apply the function to it (ie to adjust its consistency or page size),
+    /// then encode it again, so the middlewares don't have to rebuild the request from scratch
+    pub fn map_frame<F: FnOnce(&mut QueryFrame)>(mut self, f: F) -> anyhow::Result<Self> {
+        let mut frame = QueryFrame::decode(&self.inner)?;
+        f(&mut frame);
+        self.inner = frame.encode()?;
+        Ok(self)
+    }
+
+    /// Send a local request using the keyspace impl and return a type marker
+    pub fn send_local(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeRows<S, K, V>> {
+        send_local(
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Select, self.timeout),
+            self.keyspace.name().clone().into_owned(),
+        );
+        DecodeResult::select()
+    }
+
+    /// Send a global request using the keyspace impl and return a type marker
+    pub fn send_global(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeRows<S, K, V>> {
+        send_global(
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Select, self.timeout),
+            self.keyspace.name().clone().into_owned(),
+        );
+        DecodeResult::select()
+    }
+
+    /// Consume the request to retrieve the payload
+    pub fn into_payload(self) -> Vec<u8> {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::{Iter, Rows};
+
+    #[derive(Clone, Debug)]
+    struct Events {
+        name: Cow<'static, str>,
+        clustering_order: &'static [ClusteringOrder],
+    }
+
+    impl Events {
+        fn new(clustering_order: &'static [ClusteringOrder]) -> Self {
+            Self {
+                name: "ks".into(),
+                clustering_order,
+            }
+        }
+    }
+
+    impl Keyspace for Events {
+        fn name(&self) -> &Cow<'static, str> {
+            &self.name
+        }
+    }
+
+    impl ComputeToken<String> for Events {
+        fn token(_key: &String) -> i64 {
+            0
+        }
+    }
+
+    impl RowsDecoder<String, Vec<(i64, i64, i64)>> for Events {
+        type Row = (i64, i64, i64);
+        fn try_decode(decoder: Decoder) -> anyhow::Result<Option<Vec<(i64, i64, i64)>>> {
+            Ok(Some(Iter::<(i64, i64, i64)>::new(decoder)?.collect()))
+        }
+    }
+
+    impl Checkpointed<String, (i64, i64, i64), Vec<(i64, i64, i64)>> for Events {
+        fn table(&self) -> Cow<'static, str> {
+            "events".into()
+        }
+        fn columns(&self) -> &'static [&'static str] {
+            &["a", "b", "c"]
+        }
+        fn partition_key(&self) -> &'static [&'static str] {
+            &["stream"]
+        }
+        fn clustering_key(&self) -> &'static [&'static str] {
+            &["a", "b", "c"]
+        }
+        fn clustering_order(&self) -> &'static [ClusteringOrder] {
+            self.clustering_order
+        }
+        fn bind_key<T: Values>(builder: T, key: &String) -> T::Return {
+            builder.value(key)
+        }
+        fn bind_checkpoint<T: Values>(builder: T, checkpoint: &(i64, i64, i64)) -> T::Return {
+            builder.value(&checkpoint.0).value(&checkpoint.1).value(&checkpoint.2)
+        }
+    }
+
+    type EventsCheckpoint = Checkpoint<(i64, i64, i64)>;
+
+    const SELECT: &str = "SELECT a, b, c FROM ks.events WHERE stream = ?";
+
+    /// Get the value count of the uncompressed query frame
+    fn value_count(payload: &[u8]) -> u16 {
+        let statement_len = i32::from_be_bytes([payload[9], payload[10], payload[11], payload[12]]) as usize;
+        let offset = 13 + statement_len + 3;
+        u16::from_be_bytes([payload[offset], payload[offset + 1]])
+    }
+
+    fn request(events: &Events, checkpoint: &EventsCheckpoint) -> Vec<u8> {
+        events
+            .checkpoint_scan::<(i64, i64, i64), Vec<(i64, i64, i64)>>(&"stream".to_string(), checkpoint)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap()
+            .into_payload()
+    }
+
+    #[test]
+    fn ascending_statements() {
+        let events = Events::new(&[]);
+        let mut checkpoint = EventsCheckpoint::new(10).unwrap();
+        assert_eq!(events.checkpoint_statement(&checkpoint), format!("{} LIMIT ?", SELECT));
+        assert_eq!(value_count(&request(&events, &checkpoint)), 2);
+        assert!(events.next_page(&mut checkpoint, 10, Some((1, 2, 3))));
+        assert_eq!(
+            events.checkpoint_statement(&checkpoint),
+            format!("{} AND (a, b, c) > (?, ?, ?) LIMIT ?", SELECT)
+        );
+        assert_eq!(value_count(&request(&events, &checkpoint)), 5);
+        assert!(!events.next_page(&mut checkpoint, 3, Some((4, 5, 6))));
+    }
+
+    #[test]
+    fn descending_statements() {
+        let events = Events::new(&[ClusteringOrder::Desc, ClusteringOrder::Desc, ClusteringOrder::Desc]);
+        let checkpoint = EventsCheckpoint::resume(Some((1, 2, 3)), 10).unwrap();
+        assert_eq!(
+            events.checkpoint_statement(&checkpoint),
+            format!("{} AND (a, b, c) < (?, ?, ?) LIMIT ?", SELECT)
+        );
+        assert_eq!(value_count(&request(&events, &checkpoint)), 5);
+    }
+
+    #[test]
+    fn mixed_order_statements() {
+        // a ASC, b ASC, c DESC
+        let events = Events::new(&[ClusteringOrder::Asc, ClusteringOrder::Asc, ClusteringOrder::Desc]);
+        let mut checkpoint = EventsCheckpoint::new(10).unwrap();
+        assert!(events.next_page(&mut checkpoint, 10, Some((1, 2, 3))));
+        // the rest of the last seen (a, b) prefix
+        assert_eq!(
+            events.checkpoint_statement(&checkpoint),
+            format!("{} AND a = ? AND b = ? AND (c) < (?) LIMIT ?", SELECT)
+        );
+        assert_eq!(value_count(&request(&events, &checkpoint)), 5);
+        // a short page exhausts the prefix, so the scan resumes from the next (a, b)
+        assert!(events.next_page(&mut checkpoint, 4, Some((1, 2, 0))));
+        assert_eq!(checkpoint.last_seen(), Some(&(1, 2, 0)));
+        assert_eq!(
+            events.checkpoint_statement(&checkpoint),
+            format!("{} AND (a, b) > (?, ?) LIMIT ?", SELECT)
+        );
+        assert_eq!(value_count(&request(&events, &checkpoint)), 4);
+        // a full page restarts from the deepest run
+        assert!(events.next_page(&mut checkpoint, 10, Some((3, 1, 9))));
+        assert_eq!(
+            events.checkpoint_statement(&checkpoint),
+            format!("{} AND a = ? AND b = ? AND (c) < (?) LIMIT ?", SELECT)
+        );
+        // the short pages of both runs exhaust the scan
+        assert!(events.next_page(&mut checkpoint, 0, None));
+        assert!(!events.next_page(&mut checkpoint, 2, Some((4, 0, 0))));
+    }
+
+    #[test]
+    fn alternating_order_runs() {
+        // a DESC, b ASC, c DESC
+        let events = Events::new(&[ClusteringOrder::Desc, ClusteringOrder::Asc, ClusteringOrder::Desc]);
+        let mut checkpoint = EventsCheckpoint::resume(Some((1, 2, 3)), 10).unwrap();
+        let mut statements = vec![events.checkpoint_statement(&checkpoint).into_owned()];
+        while events.next_page(&mut checkpoint, 0, None) {
+            statements.push(events.checkpoint_statement(&checkpoint).into_owned());
+        }
+        assert_eq!(
+            statements,
+            vec![
+                format!("{} AND a = ? AND b = ? AND (c) < (?) LIMIT ?", SELECT),
+                format!("{} AND a = ? AND (b) > (?) LIMIT ?", SELECT),
+                format!("{} AND (a) < (?) LIMIT ?", SELECT),
+            ]
+        );
+        // the first page of a scan without checkpoint is unrestricted, and a short one exhausts it
+        let mut checkpoint = EventsCheckpoint::new(10).unwrap();
+        assert!(!events.next_page(&mut checkpoint, 9, Some((1, 2, 3))));
+    }
+}
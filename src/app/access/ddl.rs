@@ -0,0 +1,114 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{idempotent_ddl, CqlError, ErrorCodes, SystemTable};
+use anyhow::bail;
+use std::{convert::TryFrom, time::Duration};
+
+/// Executes DDL statements, retrying them after transient failures.
+///
+/// As the outcome of a failed DDL statement is ambiguous (ie it timed out after being applied), replaying it
+/// might error with AlreadyExists. Therefore the coordinator rewrites the CREATE statements to include
+/// IF NOT EXISTS, and the DROP statements to include IF EXISTS, unless `idempotent(false)` is set.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::app::access::DdlCoordinator;
+/// # async fn run() -> anyhow::Result<()> {
+/// let coordinator = DdlCoordinator::new().retries(5);
+/// coordinator
+///     .execute("CREATE TABLE my_keyspace.table (key text PRIMARY KEY, value blob)")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct DdlCoordinator {
+    retries: usize,
+    backoff: Duration,
+    idempotent: bool,
+}
+
+impl Default for DdlCoordinator {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            backoff: Duration::from_millis(100),
+            idempotent: true,
+        }
+    }
+}
+
+impl DdlCoordinator {
+    /// Create a new DDL coordinator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of retries after transient failures
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Set the base backoff duration, which doubles after each failed attempt
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set whether the CREATE/DROP statements are rewritten to include IF NOT EXISTS/IF EXISTS
+    pub fn idempotent(mut self, idempotent: bool) -> Self {
+        self.idempotent = idempotent;
+        self
+    }
+
+    /// Execute the DDL statement
+    pub async fn execute(&self, statement: &str) -> anyhow::Result<()> {
+        let statement = if self.idempotent {
+            idempotent_ddl(statement)
+        } else {
+            Cow::Borrowed(statement)
+        };
+        let Query(payload) = Query::new()
+            .statement(&statement)
+            .consistency(Consistency::Quorum)
+            .build()?;
+        for attempt in 0..=self.retries {
+            // DDL statements aren't bound to a partition, so any token works
+            let res = send_oneshot(rand::random(), payload.clone(), String::new())
+                .await
+                .and_then(|giveload| Decoder::try_from(giveload).map_err(WorkerError::Other));
+            match res {
+                Ok(_) => {
+                    // the schema change event of the statement may not be received yet
+                    SystemQueryCache::invalidate(SystemTable::Schema, None);
+                    return Ok(());
+                }
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    tokio::time::sleep(self.backoff * 2u32.pow(attempt.min(16) as u32)).await;
+                }
+                Err(e) => bail!(e),
+            }
+        }
+        bail!("Unable to execute '{}' after {} retries", statement, self.retries)
+    }
+}
+
+/// Check if the DDL statement failure is transient
+fn is_transient(error: &WorkerError) -> bool {
+    match error {
+        WorkerError::Cql(CqlError { code, .. }) => matches!(
+            code,
+            ErrorCodes::ServerError
+                | ErrorCodes::UnavailableException
+                | ErrorCodes::Overloaded
+                | ErrorCodes::IsBoostrapping
+                | ErrorCodes::WriteTimeout
+                | ErrorCodes::ReadTimeout
+        ),
+        WorkerError::ReadOnlyViolation(_) => false,
+        _ => true,
+    }
+}
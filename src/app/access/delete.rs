@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use crate::cql::with_using_timeout;
 
 /// Delete query trait which creates a `DeleteRequest`
 /// that can be sent to the `Ring`.
@@ -74,7 +75,7 @@ pub trait Delete<K, V>: Keyspace + VoidDecoder + ComputeToken<K> {
     /// for use when generating queries that should use
     /// the prepared statement.
     fn id(&self) -> [u8; 16] {
-        md5::compute(self.delete_statement().as_bytes()).into()
+        Md5::statement_id(&self.delete_statement())
     }
 
     /// Bind the cql values to the builder
@@ -151,6 +152,12 @@ pub struct DeleteBuilder<'a, S, K, V, Stage> {
 }
 
 impl<'a, S: Delete<K, V>, K, V> DeleteBuilder<'a, S, K, V, QueryConsistency> {
+    /// Set the Scylla server-side timeout of the delete, by adding the `USING TIMEOUT` clause to the statement
+    /// (see `with_using_timeout`). The request is sent as an unprepared query.
+    pub fn using_timeout(mut self, timeout: Duration) -> anyhow::Result<Self> {
+        self.builder = Query::new().statement(&with_using_timeout(&self.keyspace.statement(), timeout)?);
+        Ok(self)
+    }
     pub fn consistency(self, consistency: Consistency) -> DeleteBuilder<'a, S, K, V, QueryValues> {
         DeleteBuilder {
             _marker: self._marker,
@@ -161,6 +168,17 @@ impl<'a, S: Delete<K, V>, K, V> DeleteBuilder<'a, S, K, V, QueryConsistency> {
     }
 }
 
+impl<'a, S: Delete<K, V> + Table<K, V>, K, V> DeleteBuilder<'a, S, K, V, QueryConsistency> {
+    /// Set the write consistency of the table (see `Table`)
+    pub fn default_consistency(self) -> DeleteBuilder<'a, S, K, V, QueryValues> {
+        self.consistency(S::WRITE_CONSISTENCY)
+    }
+    /// Build the DeleteRequest with the write consistency of the table
+    pub fn build(self) -> anyhow::Result<DeleteRequest<S, K, V>> {
+        self.default_consistency().build()
+    }
+}
+
 impl<'a, S: Delete<K, V>, K, V> DeleteBuilder<'a, S, K, V, QueryValues> {
     pub fn timestamp(self, timestamp: i64) -> DeleteBuilder<'a, S, K, V, QueryBuild> {
         DeleteBuilder {
@@ -221,6 +239,7 @@ impl<S: Keyspace> GetDeleteStatement<S> for S {
 pub struct DeleteRequest<S, K, V> {
     token: i64,
     inner: Vec<u8>,
+    timeout: Option<Duration>,
     keyspace: S,
     _marker: PhantomData<(S, K, V)>,
 }
@@ -231,6 +250,7 @@ impl<K, V, S: Delete<K, V> + Clone> CreateRequest<DeleteRequest<S, K, V>> for S
         DeleteRequest::<S, K, V> {
             token,
             inner: query.into(),
+            timeout: None,
             keyspace: self.clone(),
             _marker: PhantomData,
         }
@@ -253,12 +273,28 @@ where
 }
 
 impl<S: Delete<K, V>, K, V> DeleteRequest<S, K, V> {
+    /// Set the client-side timeout of the request, after which its worker is resolved with `WorkerError::Timeout`,
+    /// or None to await its response until the connection is lost
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Decode the query frame of the request, apply the function to it (ie to adjust its consistency or page size),
+    /// then encode it again, so the middlewares don't have to rebuild the request from scratch
+    pub fn map_frame<F: FnOnce(&mut QueryFrame)>(mut self, f: F) -> anyhow::Result<Self> {
+        let mut frame = QueryFrame::decode(&self.inner)?;
+        f(&mut frame);
+        self.inner = frame.encode()?;
+        Ok(self)
+    }
+
     /// Send a local request using the keyspace impl and return a type marker
     pub fn send_local(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeVoid<S>> {
         send_local(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Delete, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::delete()
@@ -269,12 +305,23 @@ impl<S: Delete<K, V>, K, V> DeleteRequest<S, K, V> {
         send_global(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Delete, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::delete()
     }
 
+    /// Send a request routed by the provided load balancing policy and return a type marker
+    pub fn send_with(self, policy: &dyn LoadBalancingPolicy, worker: Box<dyn Worker>) -> DecodeResult<DecodeVoid<S>> {
+        send_with(
+            policy,
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Delete, self.timeout),
+        );
+        DecodeResult::delete()
+    }
+
     /// Consume the request to retrieve the payload
     pub fn into_payload(self) -> Vec<u8> {
         self.inner
@@ -0,0 +1,388 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{
+    Batch, BatchBuilder, BatchStatementOrId, BatchTypeLogged, BatchValues, CqlError, ErrorCodes, Iter, Rows,
+    TokenEncoder,
+};
+use std::{convert::TryFrom, time::Duration};
+
+/// The delivery guarantee of the critical writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Ambiguous failures are never retried, therefore the mutation is applied once or not at all
+    AtMostOnce,
+    /// Ambiguous failures are retried as long as the idempotency key is not found in the dedup table
+    ExactlyOnce,
+}
+
+/// The outcome of a critical write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// The mutation has been applied by this call
+    Applied,
+    /// The idempotency key was found after an ambiguous failure, therefore the mutation was already applied
+    AlreadyApplied,
+}
+
+/// Writes the mutations along with an idempotency key to a dedup table, within the same logged batch.
+///
+/// As the logged batch is applied atomically, the presence of the idempotency key proves that the mutation
+/// has been applied. Therefore, after an ambiguous failure (ie write timeout or lost connection), the
+/// dedup table is checked before retrying the mutation:
+/// - `Delivery::AtMostOnce`: the failure is returned unless the key is found;
+/// - `Delivery::ExactlyOnce`: the mutation is retried unless the key is found.
+///
+/// The failures which guarantee that the mutation has not been applied (ie unavailable replicas) are
+/// retried in both modes.
+///
+/// Note: a timed out batch might still be replayed from the batchlog after the key was checked, so the
+/// exactly-once delivery assumes that the dedup table is read with a consistency which overlaps the write.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::{
+///     app::access::{Delivery, ExactlyOnce},
+///     cql::{Statements, TokenEncoder, Values},
+/// };
+/// # async fn run() -> anyhow::Result<()> {
+/// let writer = ExactlyOnce::new("bank", "dedup").delivery(Delivery::ExactlyOnce).ttl(86400);
+/// writer.create_table().await?;
+/// let outcome = writer
+///     .execute("transfer-42", "alice".get_token(), |batch| {
+///         batch
+///             .statement("UPDATE bank.accounts SET balance = ? WHERE id = ?")
+///             .value(&90i64)
+///             .value(&"alice")
+///     })
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExactlyOnce {
+    keyspace: String,
+    table: String,
+    ttl: u32,
+    delivery: Delivery,
+    retries: usize,
+    backoff: Duration,
+    consistency: Consistency,
+}
+
+impl ExactlyOnce {
+    /// Create a new exactly-once writer over the keyspace dedup table
+    pub fn new<K: Into<String>, T: Into<String>>(keyspace: K, table: T) -> Self {
+        Self {
+            keyspace: keyspace.into(),
+            table: table.into(),
+            ttl: 86400,
+            delivery: Delivery::ExactlyOnce,
+            retries: 3,
+            backoff: Duration::from_millis(100),
+            consistency: Consistency::Quorum,
+        }
+    }
+
+    /// Set the time to live in seconds of the idempotency keys, which should exceed the retry window
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the delivery guarantee
+    pub fn delivery(mut self, delivery: Delivery) -> Self {
+        self.delivery = delivery;
+        self
+    }
+
+    /// Set the maximum number of retries
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Set the base backoff duration, which doubles after each failed attempt
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set the consistency of both the batches and the dedup table reads
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Get the statement which creates the dedup table
+    pub fn create_table_statement(&self) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (key text PRIMARY KEY)",
+            Name::qualify(&self.keyspace, &self.table)
+        )
+    }
+
+    /// Create the dedup table if it doesn't exist
+    pub async fn create_table(&self) -> anyhow::Result<()> {
+        let Query(payload) = Query::new()
+            .statement(&self.create_table_statement())
+            .consistency(self.consistency)
+            .build()?;
+        let payload = self.send(rand::random(), payload).await?;
+        Decoder::try_from(payload)?;
+        Ok(())
+    }
+
+    /// Check if the idempotency key exists in the dedup table
+    pub async fn is_applied(&self, key: &str) -> anyhow::Result<bool> {
+        let Query(payload) = Query::new()
+            .statement(&format!(
+                "SELECT key FROM {} WHERE key = ?",
+                Name::qualify(&self.keyspace, &self.table)
+            ))
+            .consistency(self.consistency)
+            .value(&key)
+            .build()?;
+        let payload = self.send(key.get_token(), payload).await?;
+        Ok(Iter::<String>::new(Decoder::try_from(payload)?)?.next().is_some())
+    }
+
+    /// Execute the mutations, added to the logged batch by `mutations`, along with the idempotency key.
+    /// The batch is routed by the token, which should be the one of the mutated partition.
+    pub async fn execute<F>(&self, key: &str, token: i64, mutations: F) -> anyhow::Result<WriteOutcome>
+    where
+        F: FnOnce(BatchBuilder<BatchTypeLogged, BatchStatementOrId>) -> BatchBuilder<BatchTypeLogged, BatchValues>,
+    {
+        let Batch(payload) = mutations(Batch::new().logged())
+            .statement(&format!(
+                "INSERT INTO {} (key) VALUES (?) USING TTL {}",
+                Name::qualify(&self.keyspace, &self.table),
+                self.ttl
+            ))
+            .value(&key)
+            .consistency(self.consistency)
+            .build()?;
+        let mut attempt = 0;
+        loop {
+            let error = match self.send(token, payload.clone()).await {
+                Ok(payload) => {
+                    Decoder::try_from(payload)?;
+                    return Ok(WriteOutcome::Applied);
+                }
+                Err(error) => error,
+            };
+            if is_ambiguous(&error) {
+                if self.is_applied(key).await? {
+                    return Ok(WriteOutcome::AlreadyApplied);
+                }
+                if self.delivery == Delivery::AtMostOnce {
+                    return Err(error.into());
+                }
+            } else if !is_unapplied(&error) {
+                return Err(error.into());
+            }
+            if attempt >= self.retries {
+                return Err(error.into());
+            }
+            tokio::time::sleep(self.backoff * 2u32.pow(attempt.min(16) as u32)).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send(&self, token: i64, payload: Vec<u8>) -> Result<Vec<u8>, WorkerError> {
+        send_oneshot(token, payload, self.keyspace.clone()).await
+    }
+}
+
+/// Check if the failure might have been applied
+fn is_ambiguous(error: &WorkerError) -> bool {
+    match error {
+        WorkerError::Cql(CqlError { code, .. }) => matches!(
+            code,
+            ErrorCodes::ServerError | ErrorCodes::WriteTimeout | ErrorCodes::WriteFailure
+        ),
+        WorkerError::Other(_) | WorkerError::Lost | WorkerError::Timeout => true,
+        _ => false,
+    }
+}
+
+/// Check if the failure guarantees that the mutation has not been applied, and might succeed once retried
+fn is_unapplied(error: &WorkerError) -> bool {
+    match error {
+        WorkerError::Cql(CqlError { code, .. }) => matches!(
+            code,
+            ErrorCodes::UnavailableException | ErrorCodes::Overloaded | ErrorCodes::IsBoostrapping
+        ),
+        // the draining session is shutting down, so it's returned as is rather than retried
+        WorkerError::Overload | WorkerError::NoRing => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::access::stub::{replicas, rows_result, void_result};
+
+    fn cql_error(code: ErrorCodes) -> WorkerError {
+        WorkerError::Cql(CqlError {
+            code,
+            message: String::new(),
+            additional: None,
+        })
+    }
+
+    fn writer(delivery: Delivery) -> ExactlyOnce {
+        ExactlyOnce::new("bank", "dedup")
+            .delivery(delivery)
+            .retries(2)
+            .backoff(Duration::from_millis(1))
+    }
+
+    const TRANSFER: &str =
+        "UPDATE bank.accounts SET balance = ? WHERE id = ?; INSERT INTO bank.dedup (key) VALUES (?) USING TTL 86400";
+    const LOOKUP: &str = "SELECT key FROM bank.dedup WHERE key = ?";
+
+    async fn transfer(writer: &ExactlyOnce) -> anyhow::Result<WriteOutcome> {
+        writer
+            .execute("transfer-42", 1, |batch| {
+                batch
+                    .statement("UPDATE bank.accounts SET balance = ? WHERE id = ?")
+                    .value(&90i64)
+                    .value(&"alice")
+            })
+            .await
+    }
+
+    /// The rows of the dedup table lookup
+    fn keys(found: bool) -> Vec<u8> {
+        let rows = match found {
+            true => vec![vec![Some(b"transfer-42".to_vec())]],
+            false => Vec::new(),
+        };
+        rows_result(&[("key", 0x000D)], &rows)
+    }
+
+    #[test]
+    fn classify_failures() {
+        for code in [
+            ErrorCodes::ServerError,
+            ErrorCodes::WriteTimeout,
+            ErrorCodes::WriteFailure,
+        ] {
+            assert!(is_ambiguous(&cql_error(code)));
+            assert!(!is_unapplied(&cql_error(code)));
+        }
+        for code in [
+            ErrorCodes::UnavailableException,
+            ErrorCodes::Overloaded,
+            ErrorCodes::IsBoostrapping,
+        ] {
+            assert!(!is_ambiguous(&cql_error(code)));
+            assert!(is_unapplied(&cql_error(code)));
+        }
+        for error in [
+            WorkerError::Lost,
+            WorkerError::Timeout,
+            WorkerError::Other(anyhow::anyhow!("reset")),
+        ] {
+            assert!(is_ambiguous(&error));
+            assert!(!is_unapplied(&error));
+        }
+        for error in [WorkerError::Overload, WorkerError::NoRing] {
+            assert!(!is_ambiguous(&error));
+            assert!(is_unapplied(&error));
+        }
+        // the failures which are neither ambiguous nor unapplied are returned as is
+        for error in [WorkerError::Draining, cql_error(ErrorCodes::Invalid)] {
+            assert!(!is_ambiguous(&error));
+            assert!(!is_unapplied(&error));
+        }
+    }
+
+    #[tokio::test]
+    async fn applied_once() {
+        let statements = replicas(|_| Ok(void_result()));
+        assert_eq!(
+            transfer(&writer(Delivery::ExactlyOnce)).await.unwrap(),
+            WriteOutcome::Applied
+        );
+        assert_eq!(*statements.lock().unwrap(), vec![TRANSFER]);
+    }
+
+    #[tokio::test]
+    async fn ambiguous_failure_looks_up_the_key() {
+        // the key is found, so the mutation isn't retried
+        let statements = replicas(|statement| match statement {
+            LOOKUP => Ok(keys(true)),
+            _ => Err(WorkerError::Timeout),
+        });
+        for delivery in [Delivery::AtMostOnce, Delivery::ExactlyOnce] {
+            assert_eq!(transfer(&writer(delivery)).await.unwrap(), WriteOutcome::AlreadyApplied);
+        }
+        assert_eq!(*statements.lock().unwrap(), vec![TRANSFER, LOOKUP, TRANSFER, LOOKUP]);
+    }
+
+    #[tokio::test]
+    async fn at_most_once_returns_the_ambiguous_failure() {
+        let statements = replicas(|statement| match statement {
+            LOOKUP => Ok(keys(false)),
+            _ => Err(cql_error(ErrorCodes::WriteTimeout)),
+        });
+        let error = transfer(&writer(Delivery::AtMostOnce)).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<WorkerError>(),
+            Some(WorkerError::Cql(CqlError {
+                code: ErrorCodes::WriteTimeout,
+                ..
+            }))
+        ));
+        assert_eq!(*statements.lock().unwrap(), vec![TRANSFER, LOOKUP]);
+    }
+
+    #[tokio::test]
+    async fn exactly_once_retries_until_applied() {
+        let mut attempts = 0;
+        let statements = replicas(move |statement| match statement {
+            LOOKUP => Ok(keys(false)),
+            _ => {
+                attempts += 1;
+                match attempts {
+                    1 => Err(WorkerError::Lost),
+                    2 => Err(cql_error(ErrorCodes::UnavailableException)),
+                    _ => Ok(void_result()),
+                }
+            }
+        });
+        assert_eq!(
+            transfer(&writer(Delivery::ExactlyOnce)).await.unwrap(),
+            WriteOutcome::Applied
+        );
+        // the unapplied failure is retried without looking up the key
+        assert_eq!(*statements.lock().unwrap(), vec![TRANSFER, LOOKUP, TRANSFER, TRANSFER]);
+    }
+
+    #[tokio::test]
+    async fn retries_are_bounded() {
+        let statements = replicas(|_| Err(WorkerError::Overload));
+        let error = transfer(&writer(Delivery::ExactlyOnce)).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<WorkerError>(),
+            Some(WorkerError::Overload)
+        ));
+        // the first attempt along with the two retries
+        assert_eq!(*statements.lock().unwrap(), vec![TRANSFER; 3]);
+    }
+
+    #[tokio::test]
+    async fn draining_isnt_retried() {
+        let statements = replicas(|_| Err(WorkerError::Draining));
+        let error = transfer(&writer(Delivery::ExactlyOnce)).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<WorkerError>(),
+            Some(WorkerError::Draining)
+        ));
+        assert_eq!(*statements.lock().unwrap(), vec![TRANSFER]);
+    }
+}
@@ -0,0 +1,410 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::{
+    app::ring::DcAwareRoundRobin,
+    cql::{
+        statements::schema::{CreateIndexStatement, IndexKind},
+        QueryPagingState, QuerySerialConsistency,
+    },
+};
+use std::sync::OnceLock;
+
+/// The round robin over the local nodes, which coordinates the indexed selects
+static INDEX_ROUTING: OnceLock<DcAwareRoundRobin> = OnceLock::new();
+
+/// Indexed select trait which selects the rows of a table by a regular column of its global secondary index, rather
+/// than by its partition key, and creates an `IndexSelectRequest` that can be sent to the `Ring`.
+///
+/// The key is the value of the indexed column. As the matching rows are spread over the whole ring, the requests
+/// aren't routed by token but rotated over the nodes of the local data center. The index is created by the
+/// `index_statement`, which is derived from the same table and column as the statement, so both stay in sync.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::{
+///     app::access::{GetIndexSelectRequest, IndexSelect, Keyspace},
+///     cql::{Consistency, Decoder, QueryStatement, RowsDecoder, Values},
+/// };
+/// use std::borrow::Cow;
+/// # #[derive(Clone)]
+/// # struct Shop(Cow<'static, str>);
+/// # impl Keyspace for Shop {
+/// #     fn name(&self) -> &Cow<'static, str> {
+/// #         &self.0
+/// #     }
+/// # }
+/// # impl RowsDecoder<String, Vec<i64>> for Shop {
+/// #     type Row = i64;
+/// #     fn try_decode(decoder: Decoder) -> anyhow::Result<Option<Vec<i64>>> {
+/// #         todo!()
+/// #     }
+/// # }
+///
+/// /// Select the ids of the orders by the email of their customer
+/// impl IndexSelect<String, Vec<i64>> for Shop {
+///     type QueryOrPrepared = QueryStatement;
+///     const TABLE: &'static str = "orders";
+///     const INDEXED_COLUMN: &'static str = "email";
+///
+///     fn bind_values<T: Values>(builder: T, email: &String) -> T::Return {
+///         builder.value(email)
+///     }
+/// }
+///
+/// let shop = Shop("shop".into());
+/// assert_eq!(
+///     <Shop as IndexSelect<String, Vec<i64>>>::statement(&shop),
+///     "SELECT * FROM shop.orders WHERE email = ?"
+/// );
+/// assert_eq!(
+///     <Shop as IndexSelect<String, Vec<i64>>>::index_statement(&shop).to_string(),
+///     "CREATE INDEX IF NOT EXISTS ON shop.orders (email)"
+/// );
+/// let email = "alice@example.com".to_string();
+/// let request = shop
+///     .select_by_index::<Vec<i64>>(&email)
+///     .consistency(Consistency::One)
+///     .build()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait IndexSelect<K, V>: Keyspace + RowsDecoder<K, V> {
+    /// Set the query type; `QueryStatement` or `PreparedStatement`
+    type QueryOrPrepared: IndexSelectRecommended<Self, K, V>;
+    /// The name of the indexed table
+    const TABLE: &'static str;
+    /// The name of the indexed regular column
+    const INDEXED_COLUMN: &'static str;
+
+    /// Create your indexed select statement here, which selects the rows whose indexed column is bound to the key
+    /// by default.
+    fn statement(&self) -> Cow<'static, str> {
+        format!(
+            "SELECT * FROM {}.{} WHERE {} = ?",
+            self.name(),
+            Name::auto(Self::TABLE),
+            Name::auto(Self::INDEXED_COLUMN)
+        )
+        .into()
+    }
+    /// Get the MD5 hash of this implementation's statement
+    /// for use when generating queries that should use
+    /// the prepared statement.
+    fn id(&self) -> [u8; 16] {
+        Md5::statement_id(&<Self as IndexSelect<K, V>>::statement(self))
+    }
+    /// Bind the cql values to the builder
+    fn bind_values<T: Values>(builder: T, key: &K) -> T::Return;
+    /// Get the statement which creates the secondary index of the indexed column, if it doesn't exist
+    fn index_statement(&self) -> CreateIndexStatement {
+        CreateIndexStatement {
+            if_not_exists: true,
+            name: None,
+            keyspace: Some(Name::auto(self.name())),
+            table: Name::auto(Self::TABLE),
+            column: Name::auto(Self::INDEXED_COLUMN),
+            kind: IndexKind::Column,
+        }
+    }
+}
+
+pub trait IndexSelectRecommended<S: IndexSelect<K, V>, K, V>: QueryOrPrepared {
+    fn make<T: Statements>(query_or_batch: T, keyspace: &S) -> T::Return {
+        Self::encode_statement(query_or_batch, &<S as IndexSelect<K, V>>::statement(keyspace))
+    }
+}
+
+impl<S: IndexSelect<K, V>, K, V> IndexSelectRecommended<S, K, V> for QueryStatement {}
+
+impl<S: IndexSelect<K, V>, K, V> IndexSelectRecommended<S, K, V> for PreparedStatement {}
+
+/// Defines a helper method to specify the Value type
+/// expected by the `IndexSelect` trait.
+pub trait GetIndexSelectRequest<S, K> {
+    /// Specifies the returned Value type for an upcoming indexed select request
+    fn select_by_index<'a, V>(&'a self, key: &'a K) -> IndexSelectBuilder<'a, S, K, V, QueryConsistency>
+    where
+        S: IndexSelect<K, V>;
+}
+
+impl<S: Keyspace, K> GetIndexSelectRequest<S, K> for S {
+    fn select_by_index<'a, V>(&'a self, key: &'a K) -> IndexSelectBuilder<'a, S, K, V, QueryConsistency>
+    where
+        S: IndexSelect<K, V>,
+    {
+        IndexSelectBuilder {
+            _marker: PhantomData,
+            keyspace: self,
+            key,
+            builder: S::QueryOrPrepared::make(Query::new(), self),
+        }
+    }
+}
+
+pub struct IndexSelectBuilder<'a, S, K, V, Stage> {
+    _marker: PhantomData<(&'a S, &'a K, &'a V)>,
+    keyspace: &'a S,
+    key: &'a K,
+    builder: QueryBuilder<Stage>,
+}
+
+impl<'a, S: IndexSelect<K, V>, K, V> IndexSelectBuilder<'a, S, K, V, QueryConsistency> {
+    pub fn consistency(self, consistency: Consistency) -> IndexSelectBuilder<'a, S, K, V, QueryValues> {
+        IndexSelectBuilder {
+            _marker: self._marker,
+            keyspace: self.keyspace,
+            key: self.key,
+            builder: S::bind_values(self.builder.consistency(consistency), self.key),
+        }
+    }
+}
+
+impl<'a, S: IndexSelect<K, V> + Table<K, V>, K, V> IndexSelectBuilder<'a, S, K, V, QueryConsistency> {
+    /// Set the read consistency of the table (see `Table`)
+    pub fn default_consistency(self) -> IndexSelectBuilder<'a, S, K, V, QueryValues> {
+        self.consistency(S::READ_CONSISTENCY)
+    }
+    /// Build the IndexSelectRequest with the read consistency of the table
+    pub fn build(self) -> anyhow::Result<IndexSelectRequest<S, K, V>> {
+        self.default_consistency().build()
+    }
+}
+
+impl<'a, S: IndexSelect<K, V>, K, V> IndexSelectBuilder<'a, S, K, V, QueryValues> {
+    pub fn page_size(self, page_size: i32) -> IndexSelectBuilder<'a, S, K, V, QueryPagingState> {
+        IndexSelectBuilder {
+            _marker: self._marker,
+            keyspace: self.keyspace,
+            key: self.key,
+            builder: self.builder.page_size(page_size),
+        }
+    }
+    /// Set the paging state.
+    pub fn paging_state(
+        self,
+        paging_state: &Option<Vec<u8>>,
+    ) -> IndexSelectBuilder<'a, S, K, V, QuerySerialConsistency> {
+        IndexSelectBuilder {
+            _marker: self._marker,
+            keyspace: self.keyspace,
+            key: self.key,
+            builder: self.builder.paging_state(paging_state),
+        }
+    }
+    /// Build the IndexSelectRequest
+    pub fn build(self) -> anyhow::Result<IndexSelectRequest<S, K, V>> {
+        Ok(IndexSelectRequest::new(self.keyspace, self.builder.build()?))
+    }
+}
+
+impl<'a, S: IndexSelect<K, V>, K, V> IndexSelectBuilder<'a, S, K, V, QueryPagingState> {
+    /// Set the paging state in the query frame.
+    pub fn paging_state(
+        self,
+        paging_state: &Option<Vec<u8>>,
+    ) -> IndexSelectBuilder<'a, S, K, V, QuerySerialConsistency> {
+        IndexSelectBuilder {
+            _marker: self._marker,
+            keyspace: self.keyspace,
+            key: self.key,
+            builder: self.builder.paging_state(paging_state),
+        }
+    }
+    /// Build the IndexSelectRequest
+    pub fn build(self) -> anyhow::Result<IndexSelectRequest<S, K, V>> {
+        Ok(IndexSelectRequest::new(self.keyspace, self.builder.build()?))
+    }
+}
+
+impl<'a, S: IndexSelect<K, V>, K, V> IndexSelectBuilder<'a, S, K, V, QuerySerialConsistency> {
+    /// Build the IndexSelectRequest
+    pub fn build(self) -> anyhow::Result<IndexSelectRequest<S, K, V>> {
+        Ok(IndexSelectRequest::new(self.keyspace, self.builder.build()?))
+    }
+}
+
+/// A request to select the records by their indexed column which can be sent to the ring
+#[derive(Clone, Debug)]
+pub struct IndexSelectRequest<S, K, V> {
+    inner: Vec<u8>,
+    timeout: Option<Duration>,
+    keyspace: S,
+    _marker: PhantomData<(S, K, V)>,
+}
+
+impl<S, K, V> Request for IndexSelectRequest<S, K, V>
+where
+    S: IndexSelect<K, V>,
+    K: Send,
+    V: Send,
+{
+    fn statement(&self) -> Cow<'static, str> {
+        <S as IndexSelect<K, V>>::statement(&self.keyspace)
+    }
+
+    fn payload(&self) -> &Vec<u8> {
+        &self.inner
+    }
+}
+
+impl<S: IndexSelect<K, V>, K, V> IndexSelectRequest<S, K, V> {
+    fn new<Q: Into<Vec<u8>>>(keyspace: &S, query: Q) -> Self {
+        Self {
+            inner: query.into(),
+            timeout: None,
+            keyspace: keyspace.clone(),
+            _marker: PhantomData,
+        }
+    }
+    /// Return DecodeResult marker type, useful in case the worker struct wants to hold the
+    /// decoder in order to decode the response inside handle_response method.
+    pub fn result_decoder(&self) -> DecodeResult<DecodeRows<S, K, V>> {
+        DecodeResult::select()
+    }
+    /// Set the client-side timeout of the request, after which its worker is resolved with `WorkerError::Timeout`,
+    /// or None to await its response until the connection is lost
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    /// Send the request to the next node of the local data center, whose coordinator gathers the indexed rows from
+    /// the other nodes, and return a type marker
+    pub fn send(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeRows<S, K, V>> {
+        send_with(
+            INDEX_ROUTING.get_or_init(DcAwareRoundRobin::new),
+            // the token only selects the shard of the coordinator
+            rand::random(),
+            self.inner,
+            wrap_worker(worker, RequestType::Select, self.timeout),
+        );
+        DecodeResult::select()
+    }
+    /// Consume the request to retrieve the payload
+    pub fn into_payload(self) -> Vec<u8> {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::access::stub::{request_statement, request_values},
+        cql::opcode,
+    };
+
+    #[derive(Clone)]
+    struct Shop(Cow<'static, str>);
+
+    impl Keyspace for Shop {
+        fn name(&self) -> &Cow<'static, str> {
+            &self.0
+        }
+    }
+
+    impl RowsDecoder<String, Vec<i64>> for Shop {
+        type Row = i64;
+        fn try_decode(_decoder: Decoder) -> anyhow::Result<Option<Vec<i64>>> {
+            unimplemented!()
+        }
+    }
+
+    impl RowsDecoder<i64, Vec<i64>> for Shop {
+        type Row = i64;
+        fn try_decode(_decoder: Decoder) -> anyhow::Result<Option<Vec<i64>>> {
+            unimplemented!()
+        }
+    }
+
+    /// Select the ids of the orders by the email of their customer
+    impl IndexSelect<String, Vec<i64>> for Shop {
+        type QueryOrPrepared = QueryStatement;
+        const TABLE: &'static str = "orders";
+        const INDEXED_COLUMN: &'static str = "email";
+
+        fn bind_values<T: Values>(builder: T, email: &String) -> T::Return {
+            builder.value(email)
+        }
+    }
+
+    /// Select the ids of the orders by their case sensitive zip code, through the prepared statement
+    impl IndexSelect<i64, Vec<i64>> for Shop {
+        type QueryOrPrepared = PreparedStatement;
+        const TABLE: &'static str = "Orders";
+        const INDEXED_COLUMN: &'static str = "zipCode";
+
+        fn bind_values<T: Values>(builder: T, zip_code: &i64) -> T::Return {
+            builder.value(zip_code)
+        }
+    }
+
+    fn shop() -> Shop {
+        Shop("shop".into())
+    }
+
+    #[test]
+    fn statements() {
+        let shop = shop();
+        assert_eq!(
+            IndexSelect::<String, Vec<i64>>::statement(&shop),
+            "SELECT * FROM shop.orders WHERE email = ?"
+        );
+        assert_eq!(
+            IndexSelect::<String, Vec<i64>>::index_statement(&shop).to_string(),
+            "CREATE INDEX IF NOT EXISTS ON shop.orders (email)"
+        );
+        // the case sensitive names are quoted
+        assert_eq!(
+            IndexSelect::<i64, Vec<i64>>::statement(&shop),
+            "SELECT * FROM shop.\"Orders\" WHERE \"zipCode\" = ?"
+        );
+        assert_eq!(
+            IndexSelect::<i64, Vec<i64>>::index_statement(&shop).to_string(),
+            "CREATE INDEX IF NOT EXISTS ON shop.\"Orders\" (\"zipCode\")"
+        );
+        assert_eq!(
+            IndexSelect::<i64, Vec<i64>>::id(&shop),
+            Md5::statement_id("SELECT * FROM shop.\"Orders\" WHERE \"zipCode\" = ?")
+        );
+    }
+
+    #[test]
+    fn query_request() {
+        let shop = shop();
+        let email = "alice@example.com".to_string();
+        let request = shop
+            .select_by_index::<Vec<i64>>(&email)
+            .consistency(Consistency::One)
+            .page_size(10)
+            .build()
+            .unwrap();
+        assert_eq!(request.statement(), "SELECT * FROM shop.orders WHERE email = ?");
+        assert_eq!(request.payload()[4], opcode::QUERY);
+        assert_eq!(
+            request_statement(request.payload()),
+            "SELECT * FROM shop.orders WHERE email = ?"
+        );
+        assert_eq!(request_values(request.payload()), vec![Some(email.into_bytes())]);
+    }
+
+    #[test]
+    fn prepared_request() {
+        let shop = shop();
+        let request = shop
+            .select_by_index::<Vec<i64>>(&94110)
+            .consistency(Consistency::LocalOne)
+            .build()
+            .unwrap();
+        let payload = request.payload();
+        // the request executes the statement by its id, which is prepared on demand
+        assert_eq!(payload[4], opcode::EXECUTE);
+        assert_eq!(&payload[9..11], &16u16.to_be_bytes());
+        assert_eq!(payload[11..27], IndexSelect::<i64, Vec<i64>>::id(&shop));
+        assert_eq!(
+            request.statement(),
+            "SELECT * FROM shop.\"Orders\" WHERE \"zipCode\" = ?"
+        );
+        assert_eq!(request_values(payload), vec![Some(94110i64.to_be_bytes().to_vec())]);
+    }
+}
@@ -0,0 +1,362 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{shard_batch::send_one, *};
+use crate::cql::{CqlError, ErrorCodes};
+use anyhow::anyhow;
+use futures::stream::{Stream, StreamExt};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::{
+    sync::{mpsc, OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+};
+
+/// The destination shard of a batch, or None if the ring isn't built yet
+type Destination = Option<SocketAddr>;
+
+/// The progress of an ingestion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IngestProgress {
+    /// The number of inserted rows
+    pub ingested: u64,
+    /// The number of rows which failed to be inserted, see `IngestHandle::next_failure`
+    pub failed: u64,
+    /// The number of sent batches
+    pub batches: u64,
+    /// The number of in-flight batches
+    pub in_flight: usize,
+}
+
+/// The rows of a batch which failed to be inserted, along with the error of the batch
+#[derive(Debug)]
+pub struct IngestFailure<K, V> {
+    /// The rows of the failed batch, in the order they were ingested
+    pub rows: Vec<(K, V)>,
+    /// The error of the batch
+    pub error: WorkerError,
+}
+
+/// The progress counters shared by the ingestion task, its batches and its handle
+#[derive(Default)]
+struct Counters {
+    ingested: AtomicU64,
+    failed: AtomicU64,
+    batches: AtomicU64,
+    in_flight: AtomicUsize,
+}
+
+impl Counters {
+    fn snapshot(&self) -> IngestProgress {
+        IngestProgress {
+            ingested: self.ingested.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            batches: self.batches.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Inserts an async stream of rows with a bounded number of in-flight batches per shard, so a producer which is
+/// faster than the cluster is backpressured rather than buffered without bound.
+///
+/// The rows which are ready together are grouped by partition, and the rows of a partition are inserted by a single
+/// unlogged batch of up to `batch_size` rows, which is sent to the shard which owns the partition on its primary
+/// replica in the local datacenter. The stream is only polled once the shard of the next batch has a free slot,
+/// therefore a slow shard backpressures the whole stream.
+///
+/// The failed batches don't stop the ingestion, instead their rows are reported through the `IngestHandle`, along
+/// with the progress of the ingestion.
+///
+/// ## Example
+/// ```no_run
+/// # use scylla_rs::app::access::tests::MyKeyspace;
+/// use scylla_rs::{app::access::Ingester, cql::Consistency};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// # let keyspace = MyKeyspace::new();
+/// let rows = futures::stream::iter((0..10_000).map(|i| (i as u32, i as f32)));
+/// let mut handle = Ingester::new(&keyspace)
+///     .concurrency(8)
+///     .batch_size(16)
+///     .consistency(Consistency::One)
+///     .spawn(rows);
+/// while let Some(failure) = handle.next_failure().await {
+///     println!("failed to insert {} rows: {}", failure.rows.len(), failure.error);
+/// }
+/// let progress = handle.join().await?;
+/// println!("inserted {} rows", progress.ingested);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Ingester<S, K, V> {
+    keyspace: S,
+    concurrency: usize,
+    batch_size: usize,
+    consistency: Consistency,
+    _marker: PhantomData<fn(K, V)>,
+}
+
+impl<S, K, V> Ingester<S, K, V>
+where
+    S: 'static + Insert<K, V> + ComputeToken<K>,
+    K: 'static + Clone + Send,
+    V: 'static + Clone + Send,
+{
+    /// Create a new ingester of the rows of the `Insert` implementation of the keyspace
+    pub fn new(keyspace: &S) -> Self {
+        Self {
+            keyspace: keyspace.clone(),
+            concurrency: 16,
+            batch_size: 32,
+            consistency: Consistency::One,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Set the maximum number of in-flight batches per shard
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set the maximum number of rows of a partition batch
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Set the consistency of the batches
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Spawn the ingestion of the rows, which completes once all of them are either inserted or failed
+    pub fn spawn<R>(self, rows: R) -> IngestHandle<K, V>
+    where
+        R: 'static + Stream<Item = (K, V)> + Send,
+    {
+        let counters = Arc::new(Counters::default());
+        let (failures, failures_rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(self.run(rows, counters.clone(), failures, apply::<S>));
+        IngestHandle {
+            counters,
+            failures: failures_rx,
+            task,
+        }
+    }
+
+    /// Ingest the rows, where each partition batch is applied by the provided function
+    async fn run<R, A, F>(
+        self,
+        rows: R,
+        counters: Arc<Counters>,
+        failures: mpsc::UnboundedSender<IngestFailure<K, V>>,
+        apply: A,
+    ) where
+        R: Stream<Item = (K, V)> + Send,
+        A: 'static + Fn(BatchRequest<S>, String) -> F + Copy + Send,
+        F: 'static + Future<Output = Result<(), WorkerError>> + Send,
+    {
+        let mut shards: HashMap<Destination, Arc<Semaphore>> = HashMap::new();
+        let mut chunks = Box::pin(rows.ready_chunks(self.batch_size));
+        while let Some(chunk) = chunks.next().await {
+            for (token, rows) in group_by_partition::<S, K, V>(chunk) {
+                let shard = shards
+                    .entry(Ring::local_shard(0, token))
+                    .or_insert_with(|| Arc::new(Semaphore::new(self.concurrency)))
+                    .clone();
+                // await a free slot of the shard, which stops polling the stream meanwhile
+                let permit = match shard.acquire_owned().await {
+                    Ok(permit) => permit,
+                    Err(_) => return,
+                };
+                self.send(token, rows, permit, counters.clone(), failures.clone(), apply);
+            }
+        }
+        // await the in-flight batches
+        for shard in shards.values() {
+            shard.acquire_many(self.concurrency as u32).await.ok();
+        }
+    }
+
+    /// Send the batch of the rows of a partition, which releases the permit of its shard once it's applied
+    fn send<A, F>(
+        &self,
+        token: i64,
+        rows: Vec<(K, V)>,
+        permit: OwnedSemaphorePermit,
+        counters: Arc<Counters>,
+        failures: mpsc::UnboundedSender<IngestFailure<K, V>>,
+        apply: A,
+    ) where
+        A: 'static + Fn(BatchRequest<S>, String) -> F + Send,
+        F: 'static + Future<Output = Result<(), WorkerError>> + Send,
+    {
+        let request = self.build(token, &rows);
+        let keyspace = self.keyspace.name().clone().into_owned();
+        counters.batches.fetch_add(1, Ordering::Relaxed);
+        counters.in_flight.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(async move {
+            let res = match request {
+                Ok(request) => apply(request, keyspace).await,
+                Err(e) => Err(WorkerError::Other(e)),
+            };
+            match res {
+                Ok(()) => {
+                    counters.ingested.fetch_add(rows.len() as u64, Ordering::Relaxed);
+                }
+                Err(error) => {
+                    counters.failed.fetch_add(rows.len() as u64, Ordering::Relaxed);
+                    failures.send(IngestFailure { rows, error }).ok();
+                }
+            }
+            counters.in_flight.fetch_sub(1, Ordering::Relaxed);
+            drop(permit);
+        });
+    }
+
+    /// Build the unlogged batch of the rows of a partition, which is routed by the partition token
+    fn build(&self, token: i64, rows: &[(K, V)]) -> anyhow::Result<BatchRequest<S>> {
+        let (first, rest) = rows.split_first().ok_or_else(|| anyhow!("The batch has no rows"))?;
+        let mut batch = self.keyspace.batch().unlogged().insert(&first.0, &first.1);
+        for (key, value) in rest {
+            batch = batch.insert(key, value);
+        }
+        Ok(batch.consistency(self.consistency).build()?.with_token(token))
+    }
+}
+
+/// Group the rows by partition, in the order of their first row, where each group retains the order of its rows
+fn group_by_partition<S: ComputeToken<K>, K, V>(rows: Vec<(K, V)>) -> Vec<(i64, Vec<(K, V)>)> {
+    let mut groups: Vec<(i64, Vec<(K, V)>)> = Vec::new();
+    let mut positions: HashMap<i64, usize> = HashMap::new();
+    for (key, value) in rows {
+        let token = S::token(&key);
+        let position = *positions.entry(token).or_insert_with(|| {
+            groups.push((token, Vec::new()));
+            groups.len() - 1
+        });
+        groups[position].1.push((key, value));
+    }
+    groups
+}
+
+/// Send the batch to its destination shard, and send it once more if it references unprepared statements
+async fn apply<S: Keyspace>(batch: BatchRequest<S>, keyspace: String) -> Result<(), WorkerError> {
+    let mut res = send_one(&batch, &keyspace).await;
+    if let Err(WorkerError::Cql(CqlError {
+        code: ErrorCodes::Unprepared,
+        ..
+    })) = res
+    {
+        res = match batch.reprepare_all(&None).await {
+            Ok(batch) => send_one(&batch, &keyspace).await,
+            Err(e) => Err(WorkerError::Other(e)),
+        };
+    }
+    res
+}
+
+/// The handle of a spawned ingestion, which reports its progress and its failed rows
+pub struct IngestHandle<K, V> {
+    counters: Arc<Counters>,
+    failures: mpsc::UnboundedReceiver<IngestFailure<K, V>>,
+    task: JoinHandle<()>,
+}
+
+impl<K, V> IngestHandle<K, V> {
+    /// Get the current progress of the ingestion
+    pub fn progress(&self) -> IngestProgress {
+        self.counters.snapshot()
+    }
+
+    /// Receive the next failed batch, or None once the ingestion is completed and all its failures are received
+    pub async fn next_failure(&mut self) -> Option<IngestFailure<K, V>> {
+        self.failures.recv().await
+    }
+
+    /// Stop polling the stream of rows, while the in-flight batches are still applied
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+
+    /// Await the completion of the ingestion, and return its final progress
+    pub async fn join(self) -> anyhow::Result<IngestProgress> {
+        self.task.await?;
+        Ok(self.counters.snapshot())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app::access::tests::MyKeyspace, cql::BatchFrame};
+    use std::time::Duration;
+
+    #[test]
+    fn group_by_partition_keeps_the_order_of_the_rows() {
+        let rows = vec![(1u32, 1.0f32), (2, 2.0), (1, 3.0), (3, 4.0), (2, 5.0), (1, 6.0)];
+        let groups = group_by_partition::<MyKeyspace, u32, f32>(rows);
+        let expected = vec![
+            (MyKeyspace::token(&1), vec![(1, 1.0), (1, 3.0), (1, 6.0)]),
+            (MyKeyspace::token(&2), vec![(2, 2.0), (2, 5.0)]),
+            (MyKeyspace::token(&3), vec![(3, 4.0)]),
+        ];
+        assert_eq!(groups, expected);
+        assert!(group_by_partition::<MyKeyspace, u32, f32>(Vec::new()).is_empty());
+    }
+
+    /// Apply the batches after a delay, while recording the highest number of concurrently applied batches,
+    /// and fail the batches which insert an odd number of rows
+    async fn stalled_apply(batch: BatchRequest<MyKeyspace>, _: String) -> Result<(), WorkerError> {
+        let applied = APPLIED.fetch_add(1, Ordering::SeqCst) + 1;
+        MAX_APPLIED.fetch_max(applied, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        APPLIED.fetch_sub(1, Ordering::SeqCst);
+        match BatchFrame::decode(&batch.payload()).map(|frame| frame.queries().len()) {
+            Ok(rows) if rows % 2 == 0 => Ok(()),
+            Ok(_) => Err(WorkerError::Overload),
+            Err(e) => Err(WorkerError::Other(e)),
+        }
+    }
+
+    static APPLIED: AtomicUsize = AtomicUsize::new(0);
+    static MAX_APPLIED: AtomicUsize = AtomicUsize::new(0);
+
+    #[tokio::test]
+    async fn in_flight_batches_are_bounded_per_shard() {
+        let keyspace = MyKeyspace::new();
+        let ingester = Ingester::new(&keyspace).concurrency(3).batch_size(4);
+        let counters = Arc::new(Counters::default());
+        let (failures, mut failures_rx) = mpsc::unbounded_channel();
+        // every chunk of 4 rows has a partition of 2 rows and 2 partitions of 1 row
+        let rows = futures::stream::iter((0..40u32).map(|i| (if i % 4 == 1 { i - 1 } else { i }, i as f32)));
+        // without a ring every batch has the same destination, which is bounded by the concurrency
+        ingester.run(rows, counters.clone(), failures, stalled_apply).await;
+        assert_eq!(MAX_APPLIED.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            counters.snapshot(),
+            IngestProgress {
+                ingested: 20,
+                failed: 20,
+                batches: 30,
+                in_flight: 0,
+            }
+        );
+        let mut failed = Vec::new();
+        while let Some(failure) = failures_rx.recv().await {
+            assert!(matches!(failure.error, WorkerError::Overload));
+            failed.extend(failure.rows.into_iter().map(|(key, _)| key));
+        }
+        failed.sort_unstable();
+        assert_eq!(failed, (0..40).filter(|i| i % 4 > 1).collect::<Vec<_>>());
+    }
+}
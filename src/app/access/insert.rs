@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use crate::cql::with_using_timeout;
 
 /// Insert query trait which creates an `InsertRequest`
 /// that can be sent to the `Ring`.
@@ -72,7 +73,7 @@ pub trait Insert<K, V>: Keyspace + VoidDecoder + ComputeToken<K> {
     /// for use when generating queries that should use
     /// the prepared statement.
     fn id(&self) -> [u8; 16] {
-        md5::compute(self.insert_statement().as_bytes()).into()
+        Md5::statement_id(&self.insert_statement())
     }
     /// Bind the cql values to the builder
     fn bind_values<T: Values>(builder: T, key: &K, value: &V) -> T::Return;
@@ -113,6 +114,7 @@ impl<S: Insert<K, V>, K, V> GetInsertRequest<S, K, V> for S {
             _marker: PhantomData,
             keyspace: self,
             key,
+            token: None,
             value,
             builder: S::QueryOrPrepared::make(Query::new(), self),
         }
@@ -125,6 +127,7 @@ impl<S: Insert<K, V>, K, V> GetInsertRequest<S, K, V> for S {
             _marker: PhantomData,
             keyspace: self,
             key,
+            token: None,
             value,
             builder: <QueryStatement as InsertRecommended<S, K, V>>::make(Query::new(), self),
         }
@@ -137,6 +140,7 @@ impl<S: Insert<K, V>, K, V> GetInsertRequest<S, K, V> for S {
             _marker: PhantomData,
             keyspace: self,
             key,
+            token: None,
             value,
             builder: <PreparedStatement as InsertRecommended<S, K, V>>::make(Query::new(), self),
         }
@@ -146,45 +150,104 @@ pub struct InsertBuilder<'a, S, K, V, Stage> {
     _marker: PhantomData<(&'a S, &'a K, &'a V)>,
     keyspace: &'a S,
     key: &'a K,
+    token: Option<i64>,
     value: &'a V,
     builder: QueryBuilder<Stage>,
 }
 impl<'a, S: Insert<K, V>, K, V> InsertBuilder<'a, S, K, V, QueryConsistency> {
+    /// Set the Scylla server-side timeout of the insert, by adding the `USING TIMEOUT` clause to the statement
+    /// (see `with_using_timeout`). The request is sent as an unprepared query.
+    pub fn using_timeout(mut self, timeout: Duration) -> anyhow::Result<Self> {
+        self.builder = Query::new().statement(&with_using_timeout(&self.keyspace.statement(), timeout)?);
+        Ok(self)
+    }
+    /// Route the request by the token of the partition key components, serialized by the
+    /// `PartitionKeyEncoder`, instead of the keyspace `ComputeToken` implementation
+    pub fn partition_key<P: PartitionKey>(mut self, partition_key: &P) -> Self {
+        self.token = Some(partition_key.partition_token());
+        self
+    }
     pub fn consistency(self, consistency: Consistency) -> InsertBuilder<'a, S, K, V, QueryValues> {
         InsertBuilder {
             _marker: self._marker,
             keyspace: self.keyspace,
             key: self.key,
+            token: self.token,
             value: self.value,
             builder: S::bind_values(self.builder.consistency(consistency), self.key, self.value),
         }
     }
 }
 
+impl<'a, S: Insert<K, V> + Table<K, V>, K, V> InsertBuilder<'a, S, K, V, QueryConsistency> {
+    /// Set the write consistency of the table (see `Table`)
+    pub fn default_consistency(self) -> InsertBuilder<'a, S, K, V, QueryValues> {
+        self.consistency(S::WRITE_CONSISTENCY)
+    }
+    /// Build the InsertRequest with the write consistency of the table
+    pub fn build(self) -> anyhow::Result<InsertRequest<S, K, V>> {
+        self.default_consistency().build()
+    }
+}
+
 impl<'a, S: Insert<K, V>, K, V> InsertBuilder<'a, S, K, V, QueryValues> {
+    /// Set the serial consistency of the conditional insert, ie `Serial` or `LocalSerial`
+    pub fn serial_consistency(self, consistency: Consistency) -> InsertBuilder<'a, S, K, V, QueryTimestamp> {
+        InsertBuilder {
+            _marker: self._marker,
+            keyspace: self.keyspace,
+            key: self.key,
+            token: self.token,
+            value: self.value,
+            builder: self.builder.serial_consistency(consistency),
+        }
+    }
+    pub fn timestamp(self, timestamp: i64) -> InsertBuilder<'a, S, K, V, QueryBuild> {
+        InsertBuilder {
+            _marker: self._marker,
+            keyspace: self.keyspace,
+            key: self.key,
+            token: self.token,
+            value: self.value,
+            builder: self.builder.timestamp(timestamp),
+        }
+    }
+    /// Build the InsertRequest
+    pub fn build(self) -> anyhow::Result<InsertRequest<S, K, V>> {
+        let token = self.token.unwrap_or_else(|| S::token(self.key));
+        let query = self.builder.build()?;
+        // create the request
+        Ok(self.keyspace.create_request(query, token))
+    }
+}
+
+impl<'a, S: Insert<K, V>, K, V> InsertBuilder<'a, S, K, V, QueryTimestamp> {
     pub fn timestamp(self, timestamp: i64) -> InsertBuilder<'a, S, K, V, QueryBuild> {
         InsertBuilder {
             _marker: self._marker,
             keyspace: self.keyspace,
             key: self.key,
+            token: self.token,
             value: self.value,
             builder: self.builder.timestamp(timestamp),
         }
     }
     /// Build the InsertRequest
     pub fn build(self) -> anyhow::Result<InsertRequest<S, K, V>> {
+        let token = self.token.unwrap_or_else(|| S::token(self.key));
         let query = self.builder.build()?;
         // create the request
-        Ok(self.keyspace.create_request(query, S::token(self.key)))
+        Ok(self.keyspace.create_request(query, token))
     }
 }
 
 impl<'a, S: Insert<K, V>, K, V> InsertBuilder<'a, S, K, V, QueryBuild> {
     /// Build the InsertRequest
     pub fn build(self) -> anyhow::Result<InsertRequest<S, K, V>> {
+        let token = self.token.unwrap_or_else(|| S::token(self.key));
         let query = self.builder.build()?;
         // create the request
-        Ok(self.keyspace.create_request(query, S::token(self.key)))
+        Ok(self.keyspace.create_request(query, token))
     }
 }
 
@@ -217,11 +280,59 @@ impl<S: Keyspace> GetInsertStatement<S> for S {
     }
 }
 
+/// Defines how a column is handled when generating an insert statement with `insert_columns_statement`
+#[derive(Clone, Copy, Debug)]
+pub enum InsertColumn<'a> {
+    /// Insert the column with a bind marker
+    Bind(&'a str),
+    /// Insert the column with a default cql literal instead of a bound value
+    Default(&'a str, &'a str),
+    /// Omit the column from the column list, which avoids writing a null tombstone
+    Skip(&'a str),
+}
+
+/// Generate an insert statement over the provided columns, where `Skip` columns are omitted from
+/// the column list and `Default` columns are inserted with their literal.
+/// Note: only the `Bind` columns should be bound in `Insert::bind_values`, in the same order.
+///
+/// ## Example
+/// ```
+/// use scylla_rs::app::access::{insert_columns_statement, InsertColumn};
+///
+/// let statement = insert_columns_statement(
+///     "my_keyspace",
+///     "table",
+///     &[
+///         InsertColumn::Bind("key"),
+///         InsertColumn::Default("amount", "0"),
+///         InsertColumn::Skip("note"),
+///     ],
+/// );
+/// assert_eq!(statement, r#"INSERT INTO my_keyspace."table" (key, amount) VALUES (?, 0)"#);
+/// ```
+pub fn insert_columns_statement(keyspace: &str, table: &str, columns: &[InsertColumn]) -> String {
+    let (names, values): (Vec<String>, Vec<&str>) = columns
+        .iter()
+        .filter_map(|column| match column {
+            InsertColumn::Bind(name) => Some((Name::auto(name).to_string(), "?")),
+            InsertColumn::Default(name, literal) => Some((Name::auto(name).to_string(), *literal)),
+            InsertColumn::Skip(_) => None,
+        })
+        .unzip();
+    format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        Name::qualify(keyspace, table),
+        names.join(", "),
+        values.join(", ")
+    )
+}
+
 /// A request to insert a record which can be sent to the ring
 #[derive(Clone, Debug)]
 pub struct InsertRequest<S, K, V> {
     token: i64,
     inner: Vec<u8>,
+    timeout: Option<Duration>,
     keyspace: S,
     _marker: PhantomData<(S, K, V)>,
 }
@@ -232,6 +343,7 @@ impl<K, V, S: Insert<K, V> + Clone> CreateRequest<InsertRequest<S, K, V>> for S
         InsertRequest {
             token,
             inner: query.into(),
+            timeout: None,
             keyspace: self.clone(),
             _marker: PhantomData,
         }
@@ -254,12 +366,28 @@ where
 }
 
 impl<S: Insert<K, V>, K, V> InsertRequest<S, K, V> {
+    /// Set the client-side timeout of the request, after which its worker is resolved with `WorkerError::Timeout`,
+    /// or None to await its response until the connection is lost
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Decode the query frame of the request, apply the function to it (ie to adjust its consistency or page size),
+    /// then encode it again, so the middlewares don't have to rebuild the request from scratch
+    pub fn map_frame<F: FnOnce(&mut QueryFrame)>(mut self, f: F) -> anyhow::Result<Self> {
+        let mut frame = QueryFrame::decode(&self.inner)?;
+        f(&mut frame);
+        self.inner = frame.encode()?;
+        Ok(self)
+    }
+
     /// Send a local request using the keyspace impl and return a type marker
     pub fn send_local(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeVoid<S>> {
         send_local(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Insert, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::insert()
@@ -270,14 +398,51 @@ impl<S: Insert<K, V>, K, V> InsertRequest<S, K, V> {
         send_global(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Insert, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::insert()
     }
 
+    /// Send a request routed by the provided load balancing policy and return a type marker
+    pub fn send_with(self, policy: &dyn LoadBalancingPolicy, worker: Box<dyn Worker>) -> DecodeResult<DecodeVoid<S>> {
+        send_with(
+            policy,
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Insert, self.timeout),
+        );
+        DecodeResult::insert()
+    }
+
     /// Consume the request to retrieve the payload
     pub fn into_payload(self) -> Vec<u8> {
         self.inner
     }
 }
+
+impl<S: Insert<K, V> + LwtDecoder<K, V>, K, V: Row> InsertRequest<S, K, V> {
+    /// Send a local conditional request (ie `INSERT .. IF NOT EXISTS`) using the keyspace impl and return a type marker
+    /// which decodes its `[applied]` result
+    pub fn send_local_conditional(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeLwt<S, K, V>> {
+        send_local(
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Insert, self.timeout),
+            self.keyspace.name().clone().into_owned(),
+        );
+        DecodeResult::conditional_insert()
+    }
+
+    /// Send a global conditional request using the keyspace impl and return a type marker which decodes its
+    /// `[applied]` result
+    pub fn send_global_conditional(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeLwt<S, K, V>> {
+        send_global(
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Insert, self.timeout),
+            self.keyspace.name().clone().into_owned(),
+        );
+        DecodeResult::conditional_insert()
+    }
+}
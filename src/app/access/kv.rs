@@ -0,0 +1,358 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{ColumnDecoder, ColumnEncoder, Frame, Iter, Rows, TokenEncoder};
+use anyhow::{anyhow, ensure};
+
+/// A key-value store over a generated two-column table, which is a one-page entry point to the crate.
+///
+/// The store implements the `Select`, `Insert` and `Delete` access traits, so it can be used with the
+/// request builders as well, while its async methods send the requests and await their responses.
+///
+/// The table looks like:
+/// `CREATE TABLE IF NOT EXISTS ks.table (key <key_type> PRIMARY KEY, value <value_type>)`,
+/// where both types default to `blob`.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::app::access::KvStore;
+/// # async fn run() -> anyhow::Result<()> {
+/// let store = KvStore::<String, String>::new("my_keyspace", "kv")
+///     .key_type("text")
+///     .value_type("text")
+///     .ttl(3600);
+/// store.create_table().await?;
+/// store.put(&"hello".to_string(), &"world".to_string()).await?;
+/// assert_eq!(store.get(&"hello".to_string()).await?, Some("world".to_string()));
+/// let swapped = store
+///     .compare_and_set(&"hello".to_string(), Some(&"world".to_string()), &"there".to_string())
+///     .await?;
+/// store.delete(&"hello".to_string()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct KvStore<K, V> {
+    keyspace: Cow<'static, str>,
+    table: Cow<'static, str>,
+    key_type: Cow<'static, str>,
+    value_type: Cow<'static, str>,
+    ttl: Option<u32>,
+    consistency: Consistency,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> Clone for KvStore<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            keyspace: self.keyspace.clone(),
+            table: self.table.clone(),
+            key_type: self.key_type.clone(),
+            value_type: self.value_type.clone(),
+            ttl: self.ttl,
+            consistency: self.consistency,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K, V> KvStore<K, V> {
+    /// Create a new key-value store over the keyspace table
+    pub fn new<T: Into<Cow<'static, str>>>(keyspace: T, table: T) -> Self {
+        Self {
+            keyspace: keyspace.into(),
+            table: table.into(),
+            key_type: "blob".into(),
+            value_type: "blob".into(),
+            ttl: None,
+            consistency: Consistency::Quorum,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Set the cql type of the key column
+    pub fn key_type<T: Into<Cow<'static, str>>>(mut self, key_type: T) -> Self {
+        self.key_type = key_type.into();
+        self
+    }
+
+    /// Set the cql type of the value column
+    pub fn value_type<T: Into<Cow<'static, str>>>(mut self, value_type: T) -> Self {
+        self.value_type = value_type.into();
+        self
+    }
+
+    /// Set the time to live in seconds of the written values
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl.replace(ttl);
+        self
+    }
+
+    /// Set the consistency of the requests
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Get the statement which creates the table
+    pub fn create_table_statement(&self) -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} (key {} PRIMARY KEY, value {})",
+            Name::qualify(&self.keyspace, &self.table),
+            self.key_type,
+            self.value_type
+        )
+    }
+
+    fn using_ttl(&self) -> String {
+        self.ttl.map(|ttl| format!(" USING TTL {}", ttl)).unwrap_or_default()
+    }
+
+    fn cas_update_statement(&self) -> String {
+        format!(
+            "UPDATE {}{} SET value = ? WHERE key = ? IF value = ?",
+            Name::qualify(&self.keyspace, &self.table),
+            self.using_ttl()
+        )
+    }
+
+    fn cas_insert_statement(&self) -> String {
+        format!(
+            "INSERT INTO {} (key, value) VALUES (?, ?) IF NOT EXISTS{}",
+            Name::qualify(&self.keyspace, &self.table),
+            self.using_ttl()
+        )
+    }
+}
+
+impl<K, V> KvStore<K, V>
+where
+    K: 'static + ColumnEncoder,
+    V: 'static + ColumnEncoder + ColumnDecoder,
+{
+    /// Create the table if it doesn't exist
+    pub async fn create_table(&self) -> anyhow::Result<()> {
+        let Query(payload) = Query::new()
+            .statement(&self.create_table_statement())
+            .consistency(self.consistency)
+            .build()?;
+        let payload = send_oneshot(rand::random(), payload, self.keyspace.to_string()).await?;
+        <Self as VoidDecoder>::try_decode(payload.try_into()?)
+    }
+
+    /// Get the value of the key
+    pub async fn get(&self, key: &K) -> anyhow::Result<Option<V>> {
+        let request = self.select::<V>(key).consistency(self.consistency).build()?;
+        let payload = send_oneshot(key.get_token(), request.into_payload(), self.keyspace.to_string()).await?;
+        <Self as RowsDecoder<K, V>>::try_decode(payload.try_into()?)
+    }
+
+    /// Put the value of the key
+    pub async fn put(&self, key: &K, value: &V) -> anyhow::Result<()> {
+        let request = self.insert(key, value).consistency(self.consistency).build()?;
+        let payload = send_oneshot(key.get_token(), request.into_payload(), self.keyspace.to_string()).await?;
+        <Self as VoidDecoder>::try_decode(payload.try_into()?)
+    }
+
+    /// Delete the value of the key
+    pub async fn delete(&self, key: &K) -> anyhow::Result<()> {
+        let request = GetDeleteRequest::delete::<V>(self, key)
+            .consistency(self.consistency)
+            .build()?;
+        let payload = send_oneshot(key.get_token(), request.into_payload(), self.keyspace.to_string()).await?;
+        <Self as VoidDecoder>::try_decode(payload.try_into()?)
+    }
+
+    /// Set the value of the key only if its current value is the expected one, where None expects the key to
+    /// not exist, by using a lightweight transaction. Returns whether the value has been set.
+    pub async fn compare_and_set(&self, key: &K, expected: Option<&V>, value: &V) -> anyhow::Result<bool> {
+        let Query(payload) = match expected {
+            Some(expected) => Query::new()
+                .statement(&self.cas_update_statement())
+                .consistency(self.consistency)
+                .value(value)
+                .value(key)
+                .value(expected)
+                .serial_consistency(Consistency::Serial)
+                .build()?,
+            None => Query::new()
+                .statement(&self.cas_insert_statement())
+                .consistency(self.consistency)
+                .value(key)
+                .value(value)
+                .serial_consistency(Consistency::Serial)
+                .build()?,
+        };
+        let payload = send_oneshot(key.get_token(), payload, self.keyspace.to_string()).await?;
+        Iter::<bool>::new(payload.try_into()?)?
+            .next()
+            .ok_or_else(|| anyhow!("Expected the [applied] row in the conditional update response"))
+    }
+}
+
+impl<K, V> Keyspace for KvStore<K, V> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.keyspace
+    }
+}
+
+impl<K: ColumnEncoder, V> ComputeToken<K> for KvStore<K, V> {
+    fn token(key: &K) -> i64 {
+        key.get_token()
+    }
+}
+
+impl<K, V: ColumnDecoder> RowsDecoder<K, V> for KvStore<K, V> {
+    type Row = (V,);
+    fn try_decode(decoder: Decoder) -> anyhow::Result<Option<V>> {
+        ensure!(decoder.is_rows()?, "Decoded response is not rows!");
+        Ok(Iter::<(V,)>::new(decoder)?.next().map(|(value,)| value))
+    }
+}
+
+impl<K, V> VoidDecoder for KvStore<K, V> {}
+
+impl<K: ColumnEncoder, V: ColumnDecoder> Select<K, V> for KvStore<K, V> {
+    type QueryOrPrepared = QueryStatement;
+    fn statement(&self) -> Cow<'static, str> {
+        format!(
+            "SELECT value FROM {} WHERE key = ?",
+            Name::qualify(&self.keyspace, &self.table)
+        )
+        .into()
+    }
+    fn bind_values<T: Values>(builder: T, key: &K) -> T::Return {
+        builder.value(key)
+    }
+}
+
+impl<K: ColumnEncoder, V: ColumnEncoder> Insert<K, V> for KvStore<K, V> {
+    type QueryOrPrepared = QueryStatement;
+    fn statement(&self) -> Cow<'static, str> {
+        format!(
+            "INSERT INTO {} (key, value) VALUES (?, ?){}",
+            Name::qualify(&self.keyspace, &self.table),
+            self.using_ttl()
+        )
+        .into()
+    }
+    fn bind_values<T: Values>(builder: T, key: &K, value: &V) -> T::Return {
+        builder.value(key).value(value)
+    }
+}
+
+impl<K: ColumnEncoder, V> Delete<K, V> for KvStore<K, V> {
+    type QueryOrPrepared = QueryStatement;
+    fn statement(&self) -> Cow<'static, str> {
+        format!(
+            "DELETE FROM {} WHERE key = ?",
+            Name::qualify(&self.keyspace, &self.table)
+        )
+        .into()
+    }
+    fn bind_values<T: Values>(builder: T, key: &K) -> T::Return {
+        builder.value(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::opcode::RESULT;
+    use std::convert::TryFrom;
+
+    fn store() -> KvStore<String, String> {
+        KvStore::new("my_keyspace", "kv").key_type("text").value_type("text")
+    }
+
+    /// The frame of the result of `SELECT value FROM my_keyspace.kv WHERE key = ?`
+    fn rows_result(values: &[&str]) -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 1];
+        for string in ["my_keyspace", "kv", "value"] {
+            body.extend(&(string.len() as u16).to_be_bytes());
+            body.extend(string.as_bytes());
+        }
+        body.extend(&[0, 0x0D]);
+        body.extend(&(values.len() as i32).to_be_bytes());
+        for value in values {
+            body.extend(&(value.len() as i32).to_be_bytes());
+            body.extend(value.as_bytes());
+        }
+        let mut frame = vec![0x84, 0, 0, 0, RESULT];
+        frame.extend(&(body.len() as i32).to_be_bytes());
+        frame.extend(body);
+        frame
+    }
+
+    /// The frame of a void result
+    fn void_result() -> Vec<u8> {
+        vec![0x84, 0, 0, 0, RESULT, 0, 0, 0, 4, 0, 0, 0, 1]
+    }
+
+    #[test]
+    fn statements() {
+        let store = store();
+        assert_eq!(
+            store.create_table_statement(),
+            "CREATE TABLE IF NOT EXISTS my_keyspace.kv (key text PRIMARY KEY, value text)"
+        );
+        assert_eq!(
+            Select::<String, String>::statement(&store),
+            "SELECT value FROM my_keyspace.kv WHERE key = ?"
+        );
+        assert_eq!(
+            Insert::<String, String>::statement(&store),
+            "INSERT INTO my_keyspace.kv (key, value) VALUES (?, ?)"
+        );
+        assert_eq!(
+            Delete::<String, String>::statement(&store),
+            "DELETE FROM my_keyspace.kv WHERE key = ?"
+        );
+        let store = store.ttl(60);
+        assert_eq!(
+            Insert::<String, String>::statement(&store),
+            "INSERT INTO my_keyspace.kv (key, value) VALUES (?, ?) USING TTL 60"
+        );
+        assert_eq!(
+            store.cas_update_statement(),
+            "UPDATE my_keyspace.kv USING TTL 60 SET value = ? WHERE key = ? IF value = ?"
+        );
+        assert_eq!(
+            store.cas_insert_statement(),
+            "INSERT INTO my_keyspace.kv (key, value) VALUES (?, ?) IF NOT EXISTS USING TTL 60"
+        );
+    }
+
+    #[test]
+    fn decode_values() {
+        let decode = |frame: Vec<u8>| {
+            <KvStore<String, String> as RowsDecoder<String, String>>::try_decode(Decoder::try_from(frame).unwrap())
+        };
+        assert_eq!(decode(rows_result(&["world"])).unwrap(), Some("world".to_string()));
+        assert_eq!(decode(rows_result(&[])).unwrap(), None);
+        assert!(decode(void_result()).is_err());
+        assert!(
+            <KvStore<String, String> as VoidDecoder>::try_decode(Decoder::try_from(void_result()).unwrap()).is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_fail_without_a_ring() {
+        let store = store();
+        let key = "hello".to_string();
+        for res in vec![
+            store.get(&key).await.map(|_| ()),
+            store.put(&key, &"world".to_string()).await,
+            store.delete(&key).await,
+            store
+                .compare_and_set(&key, None, &"world".to_string())
+                .await
+                .map(|_| ()),
+        ] {
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<WorkerError>(),
+                Some(WorkerError::NoRing)
+            ));
+        }
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::statements::schema::{created_type, CreateTableStatement, KeyspaceSchema, SchemaChange};
+
+/// The statements which migrate the live schema of a keyspace to the one declared by its model, along with the
+/// changes which can't be migrated without dropping data.
+#[derive(Debug, Clone, Default)]
+pub struct MigrationPlan {
+    /// The statements, in their execution order
+    pub statements: Vec<String>,
+    /// The changes which aren't migrated, ie the changed column types or primary keys, and the unexpected
+    /// columns or tables
+    pub unsupported: Vec<SchemaChange>,
+}
+
+impl MigrationPlan {
+    /// Check if the live schema is up to date, ie if there's nothing to migrate nor any unsupported change
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty() && self.unsupported.is_empty()
+    }
+}
+
+/// Migrates the live schema of a keyspace to the one declared by its model, such as the models are the source of
+/// truth of the schema.
+///
+/// The missing user defined types and tables are created, while the missing columns are added and the changed
+/// standard table options are set through `ALTER TABLE` statements. The other changes are reported, but never
+/// migrated, as they would drop data.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::app::access::{Migrator, Model};
+///
+/// # async fn run<M: Model>(model: M) -> anyhow::Result<()> {
+/// let migrator = Migrator::new();
+/// // review the plan before migrating
+/// for statement in migrator.plan(&model).await?.statements {
+///     println!("{}", statement);
+/// }
+/// let plan = migrator.migrate(&model).await?;
+/// for change in plan.unsupported {
+///     println!("manual migration required: {}", change);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Migrator {
+    fetcher: SchemaFetcher,
+    ddl: DdlCoordinator,
+}
+
+impl Migrator {
+    /// Create a new migrator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the schema fetcher which fetches the live schema
+    pub fn fetcher(mut self, fetcher: SchemaFetcher) -> Self {
+        self.fetcher = fetcher;
+        self
+    }
+
+    /// Set the DDL coordinator which executes the migration statements
+    pub fn ddl(mut self, ddl: DdlCoordinator) -> Self {
+        self.ddl = ddl;
+        self
+    }
+
+    /// Plan the migration of the live schema to the one declared by the model, without executing it
+    pub async fn plan<M: Model>(&self, model: &M) -> anyhow::Result<MigrationPlan> {
+        let statements = model.schema_statements();
+        let expected = KeyspaceSchema::from_statements(&statements)?;
+        let actual = match self.fetcher.keyspace(model.name()).await? {
+            Some(actual) => actual,
+            None => {
+                return Ok(MigrationPlan {
+                    statements,
+                    unsupported: Vec::new(),
+                })
+            }
+        };
+        let types = self.fetcher.types(model.name()).await?;
+        let changes = expected.diff(Some(&actual))?;
+        let mut plan = MigrationPlan::default();
+        for statement in statements {
+            let missing = match created_type(&statement)? {
+                Some(name) => !types.contains(&name),
+                None => match CreateTableStatement::parse(&statement) {
+                    Ok(table) => changes.contains(&SchemaChange::MissingTable(table.name)),
+                    Err(_) => false,
+                },
+            };
+            if missing {
+                plan.statements.push(statement);
+            }
+        }
+        plan.statements.extend(expected.alter_statements(&changes));
+        plan.unsupported = changes
+            .into_iter()
+            .filter(|change| {
+                !matches!(
+                    change,
+                    SchemaChange::MissingTable(_)
+                        | SchemaChange::MissingColumn { .. }
+                        | SchemaChange::TableOption { .. }
+                )
+            })
+            .collect();
+        Ok(plan)
+    }
+
+    /// Plan the migration of the live schema to the one declared by the model, and execute its statements in
+    /// order. Returns the executed plan, whose unsupported changes are left to the user.
+    pub async fn migrate<M: Model>(&self, model: &M) -> anyhow::Result<MigrationPlan> {
+        let plan = self.plan(model).await?;
+        for statement in plan.statements.iter() {
+            self.ddl.execute(statement).await?;
+        }
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::access::stub::{replicas, rows_result};
+    use std::borrow::Cow;
+
+    const TEXT: u16 = 0x000D;
+
+    /// The keyspace model, whose name is unique per test as the system queries are cached
+    #[derive(Clone)]
+    struct Shop {
+        name: Cow<'static, str>,
+    }
+
+    impl Keyspace for Shop {
+        fn name(&self) -> &Cow<'static, str> {
+            &self.name
+        }
+    }
+
+    impl Model for Shop {
+        fn schema_statements(&self) -> Vec<String> {
+            vec![
+                format!(
+                    "CREATE KEYSPACE IF NOT EXISTS {} WITH replication = \
+                     {{'class': 'SimpleStrategy', 'replication_factor': '1'}}",
+                    self.name
+                ),
+                format!(
+                    "CREATE TYPE IF NOT EXISTS {}.address (street text, city text)",
+                    self.name
+                ),
+                format!(
+                    "CREATE TABLE IF NOT EXISTS {}.customers (id text PRIMARY KEY, name text, address frozen<address>)",
+                    self.name
+                ),
+                format!(
+                    "CREATE TABLE IF NOT EXISTS {}.orders (id text, item text, quantity int, PRIMARY KEY (id, item))",
+                    self.name
+                ),
+            ]
+        }
+    }
+
+    /// The `system_schema.columns` rows of the live tables, as `(table, column, kind, position, type)`
+    type Columns = &'static [(&'static str, &'static str, &'static str, i32, &'static str)];
+
+    const CUSTOMERS: Columns = &[
+        ("customers", "id", "partition_key", 0, "text"),
+        ("customers", "name", "regular", -1, "text"),
+        ("customers", "address", "regular", -1, "frozen<address>"),
+    ];
+
+    const ORDERS: Columns = &[
+        ("orders", "id", "partition_key", 0, "text"),
+        ("orders", "item", "clustering", 0, "text"),
+        ("orders", "quantity", "regular", -1, "int"),
+    ];
+
+    fn text(value: &str) -> Option<Vec<u8>> {
+        Some(value.as_bytes().to_vec())
+    }
+
+    fn text_map(entries: &[(&str, &str)]) -> Option<Vec<u8>> {
+        let mut map = (entries.len() as i32).to_be_bytes().to_vec();
+        for string in entries.iter().flat_map(|(key, value)| [key, value]) {
+            map.extend(&(string.len() as i32).to_be_bytes());
+            map.extend(string.as_bytes());
+        }
+        Some(map)
+    }
+
+    /// Stub the system schema of the live keyspace, if it exists, along with its types and the columns of its tables
+    fn live(keyspace: bool, types: &'static [&'static str], tables: &[Columns]) -> Shop {
+        let columns = tables.concat();
+        replicas(move |statement| {
+            let rows = if statement.starts_with("SELECT durable_writes") {
+                let replication = text_map(&[
+                    ("class", "org.apache.cassandra.locator.SimpleStrategy"),
+                    ("replication_factor", "1"),
+                ]);
+                let rows = if keyspace {
+                    vec![vec![Some(vec![1]), replication]]
+                } else {
+                    Vec::new()
+                };
+                rows_result(&[("durable_writes", 0x0004), ("replication", 0x0003)], &rows)
+            } else if statement.starts_with("SELECT type_name") {
+                let rows: Vec<_> = types.iter().map(|name| vec![text(name)]).collect();
+                rows_result(&[("type_name", TEXT)], &rows)
+            } else if statement.starts_with("SELECT table_name, column_name") {
+                let rows: Vec<_> = columns
+                    .iter()
+                    .map(|(table, column, kind, position, cql_type)| {
+                        vec![
+                            text(table),
+                            text(column),
+                            text(kind),
+                            Some(position.to_be_bytes().to_vec()),
+                            text("none"),
+                            text(cql_type),
+                        ]
+                    })
+                    .collect();
+                let specs = [
+                    ("table_name", TEXT),
+                    ("column_name", TEXT),
+                    ("kind", TEXT),
+                    ("position", 0x0009),
+                    ("clustering_order", TEXT),
+                    ("type", TEXT),
+                ];
+                rows_result(&specs, &rows)
+            } else {
+                // the tables along with their unset options
+                let mut tables: Vec<_> = columns.iter().map(|(table, ..)| *table).collect();
+                tables.dedup();
+                let rows: Vec<_> = tables
+                    .into_iter()
+                    .map(|table| std::iter::once(text(table)).chain(vec![None; 12]).collect())
+                    .collect();
+                let specs: Vec<_> = std::iter::once(("table_name", TEXT))
+                    .chain(vec![("option", 0x0003); 12])
+                    .collect();
+                rows_result(&specs, &rows)
+            };
+            Ok(rows)
+        });
+        Shop {
+            name: format!("shop_{}", rand::random::<u32>()).into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn plan_the_missing_keyspace_in_order() {
+        let shop = live(false, &[], &[]);
+        let plan = Migrator::new().plan(&shop).await.unwrap();
+        // the keyspace is created first, then its types, and finally its tables
+        assert_eq!(plan.statements, shop.schema_statements());
+        assert!(plan.unsupported.is_empty());
+    }
+
+    #[tokio::test]
+    async fn skip_the_applied_steps() {
+        let shop = live(true, &[], &[ORDERS]);
+        let plan = Migrator::new().plan(&shop).await.unwrap();
+        let statements = shop.schema_statements();
+        // the type is created before the table which uses it
+        assert_eq!(plan.statements, statements[1..3].to_vec());
+        assert!(plan.unsupported.is_empty());
+    }
+
+    #[tokio::test]
+    async fn plan_the_missing_column() {
+        let shop = live(true, &["address"], &[CUSTOMERS, &ORDERS[..2]]);
+        let plan = Migrator::new().plan(&shop).await.unwrap();
+        assert_eq!(
+            plan.statements,
+            vec![format!("ALTER TABLE {}.orders ADD quantity int", shop.name)]
+        );
+        assert!(plan.unsupported.is_empty());
+    }
+
+    #[tokio::test]
+    async fn idempotent_once_applied() {
+        let shop = live(true, &["address"], &[CUSTOMERS, ORDERS]);
+        let migrator = Migrator::new();
+        for _ in 0..2 {
+            let plan = migrator.plan(&shop).await.unwrap();
+            assert!(plan.is_empty(), "{:?}", plan);
+        }
+    }
+}
@@ -2,10 +2,31 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub(crate) mod batch;
+/// Provides the `BoundStatement` which builds the statements with
+/// bind markers and bound values rather than inlined literals
+pub(crate) mod bound;
+/// Provides the `CdcLog` which selects and tails the changes
+/// captured by the CDC log table of a base table
+pub(crate) mod cdc;
+/// Provides the `Checkpointed` trait which can be implemented to
+/// scan wide partitions by explicit clustering key checkpoints
+pub(crate) mod checkpoint;
+/// Provides the `DdlCoordinator` which retries the DDL
+/// statements and makes them idempotent
+pub(crate) mod ddl;
 /// Provides the `Delete` trait which can be implemented to
 /// define delete queries for Key / Value pairs and how
 /// they are decoded
 pub(crate) mod delete;
+/// Provides the `ExactlyOnce` writer which deduplicates the
+/// critical writes by their idempotency keys
+pub(crate) mod exactly_once;
+/// Provides the `IndexSelect` trait which can be implemented to
+/// select the rows by a secondary index column
+pub(crate) mod index;
+/// Provides the `Ingester` which inserts a stream of rows with
+/// a bounded concurrency per shard
+pub(crate) mod ingest;
 /// Provides the `Insert` trait which can be implemented to
 /// define insert queries for Key / Value pairs and how
 /// they are decoded
@@ -14,44 +35,177 @@ pub(crate) mod insert;
 /// keyspace. Structs that impl this trait should also impl
 /// required query and decoder traits.
 pub(crate) mod keyspace;
+/// Provides the `KvStore` key-value convenience layer
+/// over a generated two-column table
+pub(crate) mod kv;
+/// Provides the `Migrator` which migrates the live schema
+/// of a keyspace to the one declared by its model
+pub(crate) mod migration;
+/// Provides the `keyspace_model!` macro which declares a
+/// keyspace along with its tables, and the `Model` trait
+pub(crate) mod model;
+/// Provides the `PagedIter` which follows the paging state
+/// of the select requests
+pub(crate) mod paged;
+/// Provides the hot statement profiler which suggests to
+/// prepare the statements of the frequent unprepared queries
+pub(crate) mod profiler;
+/// Provides the `Purge` which deletes the partitions matching
+/// a predicate by scanning the token ring
+pub(crate) mod purge;
+/// Provides the role administration helpers, ie to
+/// rotate the role passwords
+pub(crate) mod role;
+/// Provides the `Saga` which executes a sequence of mutations
+/// and compensates the applied ones on failure
+pub(crate) mod saga;
+/// Provides the `SchemaFetcher` which reconstructs the live
+/// schema of a keyspace, ie to diff it against its model
+pub(crate) mod schema;
+/// Provides the `SchemaCache` which preloads and watches the schemas
+/// of the configured keyspaces, and loads the others lazily
+pub(crate) mod schema_cache;
 /// Provides the `Select` trait which can be implemented to
 /// define select queries for Key / Value pairs and how
 /// they are decoded
 pub(crate) mod select;
+/// Provides the `Sequencer` which allocates monotonic
+/// sequence values using lightweight transactions
+pub(crate) mod sequencer;
+/// Provides the service level administration helpers, ie to
+/// prioritize the workloads of the roles
+pub(crate) mod service_level;
+/// Provides the `ShardAwareBatch` which splits the unlogged
+/// batches by destination shard
+pub(crate) mod shard_batch;
+/// Provides the `SystemQueryCache` which caches the results
+/// of the system queries with short TTLs
+pub(crate) mod system_cache;
+/// Provides the `Table` trait which declares the request
+/// defaults of a table, and the `table_model!` macro which
+/// declares a table out of its row struct
+pub(crate) mod table;
 /// Provides the `Update` trait which can be implemented to
 /// define update queries for Key / Value pairs and how
 /// they are decoded
 pub(crate) mod update;
+/// Provides the `MaterializedView` trait which declares a view
+/// of a base table, and the `View` which selects its rows
+pub(crate) mod view;
 
 use super::{Worker, WorkerError};
 use crate::{
     app::{
-        ring::Ring,
+        metrics::MeasuredWorker,
+        ring::{load_balancing_policy, LoadBalancingPolicy, Ring},
         stage::{ReporterEvent, ReporterHandle},
+        worker::{OneshotWorker, PrepareWorker, RequestTimeout},
     },
     cql::{
-        Consistency, Decoder, Prepare, PreparedStatement, Query, QueryBuild, QueryBuilder, QueryConsistency,
-        QueryOrPrepared, QueryStatement, QueryValues, RowsDecoder, Statements, Values, VoidDecoder,
+        Consistency, Decoder, LwtDecoder, LwtResult, Md5, Name, PartitionKey, Prepare, PreparedStatement, Query,
+        QueryBuild, QueryBuilder, QueryConsistency, QueryFrame, QueryOrPrepared, QueryStatement, QueryTimestamp,
+        QueryValues, Row, RowsDecoder, StatementIdAlgorithm, Statements, Values, VoidDecoder,
     },
 };
 pub use batch::*;
+pub use bound::BoundStatement;
+pub use cdc::{
+    cdc_log_table, cdc_options, fetch_generations, CdcGeneration, CdcLog, CdcOperation, CdcQuery, CdcReader, CdcRecord,
+    CdcStreamId, CdcTime, CDC_LOG_SUFFIX,
+};
+pub use checkpoint::{Checkpoint, CheckpointBuilder, CheckpointRequest, Checkpointed, GetCheckpointRequest};
+pub use ddl::DdlCoordinator;
 pub use delete::{Delete, DeleteRequest, GetDeleteRequest, GetDeleteStatement};
-pub use insert::{GetInsertRequest, GetInsertStatement, Insert, InsertRequest};
+pub use exactly_once::{Delivery, ExactlyOnce, WriteOutcome};
+pub use index::{GetIndexSelectRequest, IndexSelect, IndexSelectRequest};
+pub use ingest::{IngestFailure, IngestHandle, IngestProgress, Ingester};
+pub use insert::{insert_columns_statement, GetInsertRequest, GetInsertStatement, Insert, InsertColumn, InsertRequest};
 pub use keyspace::Keyspace;
+pub use kv::KvStore;
+pub use migration::{MigrationPlan, Migrator};
+pub use model::Model;
+pub use paged::PagedIter;
+pub use profiler::{
+    disable_profiler, enable_profiler, hot_statements, is_profiling, ProfilerConfig, StatementSuggestion,
+};
+pub use purge::{delete_partitions, PartitionRow, Purge, PurgeProgress};
+pub use role::{alter_role_password, alter_role_password_statement};
+pub use saga::{Saga, SagaEvent, SagaReport};
+pub use schema::SchemaFetcher;
+pub use schema_cache::SchemaCache;
 pub use select::{GetSelectRequest, GetSelectStatement, Select, SelectRequest};
-use std::{borrow::Cow, convert::TryInto, marker::PhantomData, ops::Deref};
+pub use sequencer::Sequencer;
+pub use service_level::{
+    alter_service_level, attach_service_level, create_service_level, detach_service_level, drop_service_level,
+    execute_service_level_statement,
+};
+pub use shard_batch::{ShardAwareBatch, ShardAwareBatchRequest};
+use std::{
+    borrow::Cow,
+    convert::TryInto,
+    marker::PhantomData,
+    net::{IpAddr, SocketAddr},
+    ops::Deref,
+    time::Duration,
+};
+pub use system_cache::SystemQueryCache;
+pub use table::Table;
 pub use update::{GetUpdateRequest, GetUpdateStatement, Update, UpdateRequest};
+pub use view::{MaterializedView, View};
 
+/// The type of the requests sent through the access traits
 #[repr(u8)]
-#[derive(Copy, Clone)]
-enum RequestType {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RequestType {
+    /// An insert request
     Insert = 0,
+    /// An update request
     Update = 1,
+    /// A delete request
     Delete = 2,
+    /// A select request
     Select = 3,
+    /// A batch request
     Batch = 4,
 }
 
+impl RequestType {
+    /// All the request types, ordered by their discriminant
+    pub const ALL: [RequestType; 5] = [
+        RequestType::Insert,
+        RequestType::Update,
+        RequestType::Delete,
+        RequestType::Select,
+        RequestType::Batch,
+    ];
+    /// Get the lowercase name of the request type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestType::Insert => "insert",
+            RequestType::Update => "update",
+            RequestType::Delete => "delete",
+            RequestType::Select => "select",
+            RequestType::Batch => "batch",
+        }
+    }
+}
+
+/// Trace the request of the worker by its span, if the `tracing` feature is enabled (see `TracedWorker`)
+#[cfg(feature = "tracing")]
+fn traced(worker: Box<dyn Worker>, token: i64, payload: &[u8], keyspace: Option<&str>) -> Box<dyn Worker> {
+    crate::app::worker::TracedWorker::boxed(worker, token, payload, keyspace)
+}
+
+#[cfg(not(feature = "tracing"))]
+fn traced(worker: Box<dyn Worker>, _token: i64, _payload: &[u8], _keyspace: Option<&str>) -> Box<dyn Worker> {
+    worker
+}
+
+/// Measure the worker of the request by its type, and bound it with the request timeout if any
+fn wrap_worker(worker: Box<dyn Worker>, request_type: RequestType, timeout: Option<Duration>) -> Box<dyn Worker> {
+    RequestTimeout::wrap(MeasuredWorker::boxed(worker, request_type), timeout)
+}
+
 /// Defines a computed token for a key type
 pub trait ComputeToken<K>: Keyspace {
     /// Compute the token from the provided partition_key by using murmur3 hash function
@@ -91,6 +245,10 @@ impl<'a, S: RowsDecoder<K, V>, K, V> DecodeRows<S, K, V> {
     pub fn decode(&self, bytes: Vec<u8>) -> anyhow::Result<Option<V>> {
         S::try_decode(bytes.try_into()?)
     }
+    /// Decode a result payload using the `RowsDecoder` impl, along with the warnings of the response
+    pub fn decode_response(&self, bytes: Vec<u8>) -> anyhow::Result<Response<Option<V>>> {
+        Response::decode(bytes, S::try_decode)
+    }
 }
 
 /// A marker struct which holds the keyspace type
@@ -112,6 +270,71 @@ impl<S: VoidDecoder> DecodeVoid<S> {
     pub fn decode(&self, bytes: Vec<u8>) -> anyhow::Result<()> {
         S::try_decode(bytes.try_into()?)
     }
+    /// Decode a result payload using the `VoidDecoder` impl, along with the warnings of the response
+    pub fn decode_response(&self, bytes: Vec<u8>) -> anyhow::Result<Response<()>> {
+        Response::decode(bytes, S::try_decode)
+    }
+}
+
+/// A marker struct which holds types used for a conditional
+/// query (lightweight transaction) so that its `[applied]`
+/// result may be decoded via `LwtDecoder` later
+#[derive(Clone, Copy, Default)]
+pub struct DecodeLwt<S, K, V> {
+    _marker: PhantomData<(S, K, V)>,
+}
+
+impl<S, K, V> DecodeLwt<S, K, V> {
+    fn new() -> Self {
+        Self { _marker: PhantomData }
+    }
+}
+
+impl<S: LwtDecoder<K, V>, K, V: Row> DecodeLwt<S, K, V> {
+    /// Decode a result payload using the `LwtDecoder` impl
+    pub fn decode(&self, bytes: Vec<u8>) -> anyhow::Result<LwtResult<V>> {
+        S::try_decode(bytes.try_into()?)
+    }
+    /// Decode a result payload using the `LwtDecoder` impl, along with the warnings of the response
+    pub fn decode_response(&self, bytes: Vec<u8>) -> anyhow::Result<Response<LwtResult<V>>> {
+        Response::decode(bytes, S::try_decode)
+    }
+}
+
+/// A decoded response along with its warnings, which are set by the server, ie on large aggregations or on reading
+/// too many tombstones. The warnings are also emitted as `DiagnosticEvent::ServerWarnings` by the reporters.
+#[derive(Clone, Debug)]
+pub struct Response<T> {
+    value: T,
+    warnings: Vec<String>,
+}
+
+impl<T> Response<T> {
+    /// Decode the response payload with the decoder, and take its warnings beforehand
+    fn decode(bytes: Vec<u8>, decode: impl FnOnce(Decoder) -> anyhow::Result<T>) -> anyhow::Result<Self> {
+        let mut decoder: Decoder = bytes.try_into()?;
+        let warnings = decoder.take_warnings().unwrap_or_default();
+        Ok(Self {
+            value: decode(decoder)?,
+            warnings,
+        })
+    }
+    /// Get the decoded value
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+    /// Get the warnings of the response
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+    /// Consume the response to retrieve the decoded value
+    pub fn into_value(self) -> T {
+        self.value
+    }
+    /// Consume the response to retrieve the decoded value along with the warnings
+    pub fn into_parts(self) -> (T, Vec<String>) {
+        (self.value, self.warnings)
+    }
 }
 
 /// A synchronous marker type returned when sending
@@ -159,18 +382,100 @@ impl<S> DecodeResult<DecodeVoid<S>> {
     }
 }
 
-/// Send a local request to the Ring
-pub fn send_local(token: i64, payload: Vec<u8>, worker: Box<dyn Worker>, _keyspace: String) {
+impl<S, K, V> DecodeResult<DecodeLwt<S, K, V>> {
+    fn conditional_insert() -> Self {
+        Self {
+            inner: DecodeLwt::<S, K, V>::new(),
+            request_type: RequestType::Insert,
+        }
+    }
+    fn conditional_update() -> Self {
+        Self {
+            inner: DecodeLwt::<S, K, V>::new(),
+            request_type: RequestType::Update,
+        }
+    }
+}
+
+/// Send a local request to the Ring, or route it with the load balancing policy of the keyspace if it has one
+pub fn send_local(token: i64, payload: Vec<u8>, worker: Box<dyn Worker>, keyspace: String) {
+    let worker = traced(worker, token, &payload, Some(&keyspace));
+    profiler::record(&payload);
+    let request = ReporterEvent::Request { worker, payload };
+
+    match load_balancing_policy(&keyspace) {
+        Some(policy) => Ring::send_with(policy.as_ref(), token, request),
+        None => Ring::send_local_random_replica(token, request),
+    }
+}
+
+/// Send a local request to the primary replica of the token, ie to the shard returned by `Ring::local_shard(0, token)`
+pub fn send_local_primary(token: i64, payload: Vec<u8>, worker: Box<dyn Worker>, keyspace: String) {
+    let worker = traced(worker, token, &payload, Some(&keyspace));
+    profiler::record(&payload);
     let request = ReporterEvent::Request { worker, payload };
 
-    Ring::send_local_random_replica(token, request);
+    Ring::send_local(0, token, request);
 }
 
-/// Send a global request to the Ring
-pub fn send_global(token: i64, payload: Vec<u8>, worker: Box<dyn Worker>, _keyspace: String) {
+/// Send a global request to the Ring, or route it with the load balancing policy of the keyspace if it has one
+pub fn send_global(token: i64, payload: Vec<u8>, worker: Box<dyn Worker>, keyspace: String) {
+    let worker = traced(worker, token, &payload, Some(&keyspace));
+    profiler::record(&payload);
     let request = ReporterEvent::Request { worker, payload };
 
-    Ring::send_global_random_replica(token, request);
+    match load_balancing_policy(&keyspace) {
+        Some(policy) => Ring::send_with(policy.as_ref(), token, request),
+        None => Ring::send_global_random_replica(token, request),
+    }
+}
+
+/// Send a global request to the Ring, see `send_global`, and await its response payload
+pub(crate) async fn send_oneshot(token: i64, payload: Vec<u8>, keyspace: String) -> Result<Vec<u8>, WorkerError> {
+    let (worker, rx) = OneshotWorker::boxed();
+    send_global(token, payload, worker, keyspace);
+    rx.await.unwrap_or(Err(WorkerError::Lost))
+}
+
+/// Send a request to the Ring, routed by the provided load balancing policy
+pub fn send_with(policy: &dyn LoadBalancingPolicy, token: i64, payload: Vec<u8>, worker: Box<dyn Worker>) {
+    let worker = traced(worker, token, &payload, None);
+    profiler::record(&payload);
+    let request = ReporterEvent::Request { worker, payload };
+
+    Ring::send_with(policy, token, request);
+}
+
+/// Prepare the statement on every shard of every node in the ring, returns the number of targeted shards
+pub fn prepare_all(statement: &str) -> anyhow::Result<usize> {
+    prepare_with(statement, false, |make_request| Ring::send_all(make_request))
+}
+
+/// Prepare the statement on every shard of the provided node, returns the number of targeted shards
+pub fn prepare_node(node: IpAddr, statement: &str) -> anyhow::Result<usize> {
+    prepare_with(statement, false, |make_request| Ring::send_node(node, make_request))
+}
+
+/// Re-prepare the statement on every shard of every node in the ring after an unprepared error, returns the
+/// number of targeted shards
+pub(crate) fn reprepare_all_shards(statement: &str) -> anyhow::Result<usize> {
+    prepare_with(statement, true, |make_request| Ring::send_all(make_request))
+}
+
+fn prepare_with(
+    statement: &str,
+    reprepare: bool,
+    send: impl FnOnce(&mut dyn FnMut(SocketAddr) -> ReporterEvent) -> usize,
+) -> anyhow::Result<usize> {
+    let id = Md5::statement_id(statement);
+    let Prepare(payload) = Prepare::new().statement(statement).build()?;
+    Ok(send(&mut |_| ReporterEvent::Request {
+        worker: match reprepare {
+            true => PrepareWorker::reprepare(id, statement),
+            false => PrepareWorker::boxed(id, statement),
+        },
+        payload: payload.clone(),
+    }))
 }
 
 impl<T> Deref for DecodeResult<T> {
@@ -185,6 +490,7 @@ impl<T> Deref for DecodeResult<T> {
 pub mod tests {
 
     use crate::app::worker::InsertWorker;
+    use std::collections::HashMap;
 
     use super::*;
 
@@ -229,8 +535,8 @@ pub mod tests {
     }
 
     impl ComputeToken<u32> for MyKeyspace {
-        fn token(_key: &u32) -> i64 {
-            rand::random()
+        fn token(key: &u32) -> i64 {
+            *key as i64
         }
     }
     impl Insert<u32, f32> for MyKeyspace {
@@ -484,4 +790,207 @@ pub mod tests {
         let worker = BatchWorker { request: req.clone() };
         let _res = req.clone().send_local(Box::new(worker));
     }
+
+    /// Drain the PREPARE requests received by the detached reporters as (shard address, reporter id, statement)
+    fn prepared(
+        receivers: &mut HashMap<SocketAddr, Vec<tokio::sync::mpsc::UnboundedReceiver<ReporterEvent>>>,
+    ) -> Vec<(SocketAddr, usize, String)> {
+        let mut prepared = Vec::new();
+        for (address, receivers) in receivers.iter_mut() {
+            for (reporter_id, rx) in receivers.iter_mut().enumerate() {
+                while let Ok(event) = rx.try_recv() {
+                    match event {
+                        ReporterEvent::Request { payload, .. } => {
+                            assert_eq!(payload[4], crate::cql::opcode::PREPARE);
+                            let length = i32::from_be_bytes([payload[9], payload[10], payload[11], payload[12]]);
+                            let statement = String::from_utf8(payload[13..13 + length as usize].to_vec()).unwrap();
+                            prepared.push((*address, reporter_id, statement));
+                        }
+                        _ => panic!("expected a prepare request"),
+                    }
+                }
+            }
+        }
+        prepared.sort();
+        prepared
+    }
+
+    /// The shards of two nodes, where the port is the shard id
+    fn shards() -> Vec<SocketAddr> {
+        vec![
+            ([10, 0, 0, 1], 0).into(),
+            ([10, 0, 0, 1], 1).into(),
+            ([10, 0, 0, 2], 0).into(),
+            ([10, 0, 0, 2], 1).into(),
+        ]
+    }
+
+    #[test]
+    fn prepare_all_targets_every_node() {
+        let mut receivers = Ring::detach(&shards(), 2);
+        let statement = MyKeyspace::new().insert_statement::<u32, f32>().to_string();
+        // the routing doesn't depend on any randomness, so every attempt targets the same shards
+        for _ in 0..3 {
+            assert_eq!(prepare_all(&statement).unwrap(), 4);
+            assert_eq!(
+                prepared(&mut receivers),
+                shards()
+                    .into_iter()
+                    .map(|address| (address, 0, statement.clone()))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn prepare_node_targets_exactly_the_node() {
+        let mut receivers = Ring::detach(&shards(), 2);
+        let statement = MyKeyspace::new().select_statement::<u32, f32>().to_string();
+        let node: IpAddr = [10, 0, 0, 2].into();
+        for _ in 0..3 {
+            assert_eq!(prepare_node(node, &statement).unwrap(), 2);
+            assert_eq!(
+                prepared(&mut receivers),
+                vec![
+                    ((node, 0).into(), 0, statement.clone()),
+                    ((node, 1).into(), 0, statement.clone()),
+                ]
+            );
+        }
+        // a node which isn't in the ring isn't targeted
+        assert_eq!(prepare_node([10, 0, 0, 3].into(), &statement).unwrap(), 0);
+        assert!(prepared(&mut receivers).is_empty());
+    }
+}
+
+/// The stubs of the ring replicas, so the access helpers can be tested without a cluster
+#[cfg(test)]
+pub(crate) mod stub {
+    use super::*;
+    use crate::cql::{opcode, BatchFrame, BatchQuery};
+    use std::sync::{Arc, Mutex};
+
+    /// The frame of a void result
+    pub(crate) fn void_result() -> Vec<u8> {
+        vec![0x84, 0, 0, 0, opcode::RESULT, 0, 0, 0, 4, 0, 0, 0, 1]
+    }
+
+    /// The frame of the rows result of `ks.t`, along with the name and the type id of its columns, where the
+    /// cells are already encoded and None is null
+    pub(crate) fn rows_result(columns: &[(&str, u16)], rows: &[Vec<Option<Vec<u8>>>]) -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 2, 0, 0, 0, 1];
+        body.extend(&(columns.len() as i32).to_be_bytes());
+        for string in ["ks", "t"] {
+            body.extend(&(string.len() as u16).to_be_bytes());
+            body.extend(string.as_bytes());
+        }
+        for (name, type_id) in columns {
+            body.extend(&(name.len() as u16).to_be_bytes());
+            body.extend(name.as_bytes());
+            body.extend(&type_id.to_be_bytes());
+        }
+        body.extend(&(rows.len() as i32).to_be_bytes());
+        for cell in rows.iter().flatten() {
+            match cell {
+                Some(cell) => {
+                    body.extend(&(cell.len() as i32).to_be_bytes());
+                    body.extend(cell);
+                }
+                None => body.extend(&(-1i32).to_be_bytes()),
+            }
+        }
+        let mut frame = vec![0x84, 0, 0, 0, opcode::RESULT];
+        frame.extend(&(body.len() as i32).to_be_bytes());
+        frame.extend(body);
+        frame
+    }
+
+    /// Get the statement of the QUERY or PREPARE request, or the statements of the BATCH request separated by `; `
+    pub(crate) fn request_statement(payload: &[u8]) -> String {
+        match payload[4] {
+            opcode::BATCH => BatchFrame::decode(payload)
+                .unwrap()
+                .queries()
+                .iter()
+                .map(|query| match query {
+                    BatchQuery::Statement(statement, _) => statement.clone(),
+                    BatchQuery::Prepared(id, _) => format!("{:x?}", id),
+                })
+                .collect::<Vec<_>>()
+                .join("; "),
+            _ => crate::cql::read_only::statement(payload, crate::cql::CompressionType::Uncompressed).unwrap(),
+        }
+    }
+
+    /// Get the values of the QUERY or EXECUTE request, where None is null
+    pub(crate) fn request_values(payload: &[u8]) -> Vec<Option<Vec<u8>>> {
+        let read_i32 = |offset: usize| {
+            i32::from_be_bytes([
+                payload[offset],
+                payload[offset + 1],
+                payload[offset + 2],
+                payload[offset + 3],
+            ])
+        };
+        // skip the header and the statement or the prepared id, followed by the consistency
+        let mut offset = match payload[4] {
+            opcode::EXECUTE => 11 + u16::from_be_bytes([payload[9], payload[10]]) as usize,
+            _ => 13 + read_i32(9) as usize,
+        } + 2;
+        let flags = payload[offset];
+        offset += 1;
+        if flags & crate::cql::queryflags::VALUES == 0 {
+            return Vec::new();
+        }
+        let count = u16::from_be_bytes([payload[offset], payload[offset + 1]]);
+        offset += 2;
+        (0..count)
+            .map(|_| {
+                let length = read_i32(offset);
+                offset += 4;
+                (length >= 0).then(|| {
+                    offset += length as usize;
+                    payload[offset - length as usize..offset].to_vec()
+                })
+            })
+            .collect()
+    }
+
+    /// Replace the ring of the current thread by a single replica, whose requests are answered by `answer` according
+    /// to their payload
+    pub(crate) fn requests<F>(mut answer: F)
+    where
+        F: FnMut(&[u8]) -> Result<Vec<u8>, WorkerError> + Send + 'static,
+    {
+        let mut receivers = Ring::detach_node([127, 0, 0, 1].into(), 1, 1);
+        let mut events = receivers.drain().next().unwrap().1.remove(0);
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if let ReporterEvent::Request { worker, payload } = event {
+                    match answer(&payload) {
+                        Ok(frame) => worker.handle_response(frame),
+                        Err(error) => worker.handle_error(error, &None),
+                    }
+                    .unwrap();
+                }
+            }
+        });
+    }
+
+    /// Replace the ring of the current thread by a single replica, whose requests are answered by `answer` according
+    /// to their statement. Returns the statements of the answered requests, in order.
+    pub(crate) fn replicas<F>(mut answer: F) -> Arc<Mutex<Vec<String>>>
+    where
+        F: FnMut(&str) -> Result<Vec<u8>, WorkerError> + Send + 'static,
+    {
+        let statements = Arc::new(Mutex::new(Vec::new()));
+        let answered = statements.clone();
+        requests(move |payload| {
+            let statement = request_statement(payload);
+            let res = answer(&statement);
+            answered.lock().unwrap().push(statement);
+            res
+        });
+        statements
+    }
 }
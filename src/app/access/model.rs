@@ -0,0 +1,411 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{Frame, Session};
+use anyhow::bail;
+
+/// Defines the schema of a keyspace model, which is implemented by the `keyspace_model!` macro
+#[async_trait::async_trait]
+pub trait Model: Keyspace {
+    /// Get the statements which create the keyspace, its user defined types and its tables, in order
+    fn schema_statements(&self) -> Vec<String>;
+
+    /// Get the select, insert, update and delete statements of the tables, which are prepared ahead of the requests
+    fn table_statements(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Create the keyspace, its user defined types and its tables if they don't exist
+    async fn sync_schema(&self, session: &Session) -> anyhow::Result<()> {
+        for statement in self.schema_statements() {
+            let Query(payload) = Query::new()
+                .statement(&statement)
+                .consistency(Consistency::One)
+                .build()?;
+            let decoder = session.send(None, payload).await?;
+            if decoder.is_error()? {
+                bail!(
+                    "Failed to sync the schema: '{}', error: {}",
+                    statement,
+                    decoder.get_error()?
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Prepare the statements of the tables and pin them in the session statement cache, so the hot statements
+    /// are never evicted by the other ones (see `SessionBuilder::statement_cache_capacity`)
+    async fn prepare_statements(&self, session: &Session) -> anyhow::Result<()> {
+        for statement in self.table_statements() {
+            session.prepare_pinned(&statement).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Declares a keyspace along with its user defined types and tables in one block.
+///
+/// The macro expands to:
+/// - the keyspace struct, which implements `Keyspace`, `VoidDecoder` and `Model`;
+/// - a struct per user defined type, which implements `ColumnEncoder` and `ColumnDecoder`;
+/// - a row struct and a primary key struct per table, while the keyspace implements `ComputeToken`,
+///   `RowsDecoder`, `LwtDecoder`, `Select`, `Insert`, `Update` and `Delete` for them, using prepared statements.
+///
+/// Each column is declared as `name: RustType => "cql type"`. The tables are optionally followed by their
+/// `WITH` options and by their `Table` defaults, ie `DEFAULT_TTL`, `READ_CONSISTENCY` and `WRITE_CONSISTENCY`,
+/// and `Model::sync_schema` creates everything that doesn't exist yet, while `Model::prepare_statements` prepares
+/// and pins the statements of the tables.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::{
+///     app::access::{GetSelectRequest, Model},
+///     cql::{Consistency, Session},
+///     keyspace_model,
+/// };
+///
+/// keyspace_model! {
+///     keyspace Shop("shop") {
+///         replication: "{'class': 'SimpleStrategy', 'replication_factor': 1}";
+///         udt Address("address") {
+///             street: String => "text",
+///             zip: i32 => "int",
+///         }
+///         table Order, OrderKey("orders") {
+///             partition_key { customer: String => "text" }
+///             clustering_key { id: i64 => "bigint" }
+///             columns {
+///                 total: f64 => "double",
+///                 shipping: Option<Address> => "frozen<address>",
+///             }
+///             options "CLUSTERING ORDER BY (id DESC)"
+///             defaults {
+///                 DEFAULT_TTL: Some(86400),
+///                 WRITE_CONSISTENCY: Consistency::Quorum,
+///             }
+///         }
+///     }
+/// }
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let session = Session::connect(vec!["127.0.0.1:9042".parse()?]).await?;
+/// let shop = Shop::new();
+/// shop.sync_schema(&session).await?;
+/// shop.prepare_statements(&session).await?;
+/// let key = OrderKey {
+///     customer: "alice".to_string(),
+///     id: 1,
+/// };
+/// // the select request uses the default read consistency of the orders table
+/// let request = shop.select::<Order>(&key).build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! keyspace_model {
+    (
+        $(#[$meta:meta])*
+        keyspace $keyspace:ident($name:literal) {
+            replication: $replication:literal;
+            $(
+                $(#[$udt_meta:meta])*
+                udt $udt:ident($udt_name:literal) {
+                    $($udt_field:ident: $udt_type:ty => $udt_cql:literal),+ $(,)?
+                }
+            )*
+            $(
+                $(#[$table_meta:meta])*
+                table $row:ident, $key:ident($table:literal) {
+                    partition_key { $($pk:ident: $pk_type:ty => $pk_cql:literal),+ $(,)? }
+                    $(clustering_key { $($ck:ident: $ck_type:ty => $ck_cql:literal),+ $(,)? })?
+                    columns { $($col:ident: $col_type:ty => $col_cql:literal),* $(,)? }
+                    $(options $options:literal)?
+                    $(defaults { $($default:ident: $default_value:expr),* $(,)? })?
+                }
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        pub struct $keyspace {
+            name: std::borrow::Cow<'static, str>,
+        }
+
+        impl $keyspace {
+            /// Create the keyspace with its declared name
+            pub fn new() -> Self {
+                Self { name: $name.into() }
+            }
+            /// Create the keyspace with another name, ie per environment
+            pub fn with_name<T: Into<std::borrow::Cow<'static, str>>>(name: T) -> Self {
+                Self { name: name.into() }
+            }
+        }
+
+        impl Default for $keyspace {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl $crate::app::access::Keyspace for $keyspace {
+            fn name(&self) -> &std::borrow::Cow<'static, str> {
+                &self.name
+            }
+        }
+
+        impl $crate::cql::VoidDecoder for $keyspace {}
+
+        impl $crate::app::access::Model for $keyspace {
+            fn schema_statements(&self) -> Vec<String> {
+                let mut statements = vec![format!(
+                    "CREATE KEYSPACE IF NOT EXISTS {} WITH replication = {}",
+                    self.name, $replication
+                )];
+                $(
+                    statements.push(<$udt as $crate::cql::Udt>::create_statement(&self.name));
+                )*
+                $(
+                    statements.push($crate::keyspace_model!(
+                        @create_table self.name, $table,
+                        {$($pk $pk_cql),+},
+                        {$($($ck $ck_cql),+)?},
+                        {$($col $col_cql),*},
+                        $($options)?
+                    ));
+                )*
+                statements
+            }
+
+            fn table_statements(&self) -> Vec<String> {
+                #[allow(unused_mut)]
+                let mut statements = Vec::new();
+                $(
+                    statements.push(<Self as $crate::app::access::Select<$key, $row>>::statement(self).into_owned());
+                    statements.push(<Self as $crate::app::access::Insert<$key, $row>>::statement(self).into_owned());
+                    statements.push(<Self as $crate::app::access::Update<$key, $row>>::statement(self).into_owned());
+                    statements.push(<Self as $crate::app::access::Delete<$key, $row>>::statement(self).into_owned());
+                )*
+                statements
+            }
+        }
+
+        $(
+            $crate::udt! {
+                $(#[$udt_meta])*
+                pub struct $udt($udt_name) {
+                    $($udt_field: $udt_type => $udt_cql),+
+                }
+            }
+        )*
+
+        $(
+            $crate::keyspace_model!(
+                @table $keyspace, $(#[$table_meta])* $row, $key, $table,
+                {$($pk: $pk_type),+},
+                {$($($ck: $ck_type),+)?},
+                {$($col: $col_type),*},
+                {$($($default: $default_value),*)?}
+            );
+        )*
+    };
+    (@create_table $keyspace:expr, $table:literal,
+        {$($pk:ident $pk_cql:literal),+},
+        {$($ck:ident $ck_cql:literal),*},
+        {$($col:ident $col_cql:literal),*},
+        $($options:literal)?
+    ) => {{
+        let columns: Vec<String> = vec![
+            $(format!("{} {}", stringify!($pk), $pk_cql),)+
+            $(format!("{} {}", stringify!($ck), $ck_cql),)*
+            $(format!("{} {}", stringify!($col), $col_cql),)*
+        ];
+        #[allow(unused_mut)]
+        let mut primary_key = format!("({})", [$(stringify!($pk)),+].join(", "));
+        $(
+            primary_key.push_str(", ");
+            primary_key.push_str(stringify!($ck));
+        )*
+        #[allow(unused_mut)]
+        let mut statement = format!(
+            "CREATE TABLE IF NOT EXISTS {}.{} ({}, PRIMARY KEY ({}))",
+            $keyspace,
+            $table,
+            columns.join(", "),
+            primary_key
+        );
+        $(
+            statement.push_str(" WITH ");
+            statement.push_str($options);
+        )?
+        statement
+    }};
+    (@table $keyspace:ident, $(#[$meta:meta])* $row:ident, $key:ident, $table:literal,
+        {$($pk:ident: $pk_type:ty),+},
+        {$($ck:ident: $ck_type:ty),*},
+        {$($col:ident: $col_type:ty),*},
+        {$($default:ident: $default_value:expr),*}
+    ) => {
+        #[doc = concat!("The primary key of the `", $table, "` table")]
+        #[derive(Debug, Clone)]
+        pub struct $key {
+            $(
+                #[allow(missing_docs)]
+                pub $pk: $pk_type,
+            )+
+            $(
+                #[allow(missing_docs)]
+                pub $ck: $ck_type,
+            )*
+        }
+
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        pub struct $row {
+            $(
+                #[allow(missing_docs)]
+                pub $pk: $pk_type,
+            )+
+            $(
+                #[allow(missing_docs)]
+                pub $ck: $ck_type,
+            )*
+            $(
+                #[allow(missing_docs)]
+                pub $col: $col_type,
+            )*
+        }
+
+        impl $row {
+            /// Get the primary key of the row
+            pub fn key(&self) -> $key {
+                $key {
+                    $($pk: self.$pk.clone(),)+
+                    $($ck: self.$ck.clone(),)*
+                }
+            }
+        }
+
+        impl $crate::cql::Row for $row {
+            fn try_decode_row<R: $crate::cql::Rows + $crate::cql::ColumnValue>(rows: &mut R) -> anyhow::Result<Self> {
+                Ok(Self {
+                    $($pk: rows.column_value::<$pk_type>()?,)+
+                    $($ck: rows.column_value::<$ck_type>()?,)*
+                    $($col: rows.column_value::<$col_type>()?,)*
+                })
+            }
+        }
+
+        impl $crate::app::access::ComputeToken<$key> for $keyspace {
+            fn token(key: &$key) -> i64 {
+                let mut encoder = $crate::cql::PartitionKeyEncoder::new();
+                $(encoder.push(&key.$pk);)+
+                encoder.token()
+            }
+        }
+
+        impl $crate::cql::RowsDecoder<$key, $row> for $keyspace {
+            type Row = $row;
+            fn try_decode(decoder: $crate::cql::Decoder) -> anyhow::Result<Option<$row>> {
+                anyhow::ensure!($crate::cql::Frame::is_rows(&decoder)?, "Decoded response is not rows!");
+                Ok(Iterator::next(&mut <$crate::cql::Iter<$row> as $crate::cql::Rows>::new(decoder)?))
+            }
+        }
+
+        impl $crate::app::access::Select<$key, $row> for $keyspace {
+            type QueryOrPrepared = $crate::cql::PreparedStatement;
+            fn statement(&self) -> std::borrow::Cow<'static, str> {
+                format!(
+                    "SELECT {} FROM {}.{} WHERE {}",
+                    [$(stringify!($pk),)+ $(stringify!($ck),)* $(stringify!($col),)*].join(", "),
+                    $crate::app::access::Keyspace::name(self),
+                    $table,
+                    [$(concat!(stringify!($pk), " = ?"),)+ $(concat!(stringify!($ck), " = ?"),)*].join(" AND ")
+                )
+                .into()
+            }
+            fn bind_values<T: $crate::cql::Values>(builder: T, key: &$key) -> T::Return {
+                use $crate::cql::Values as _;
+                builder$(.value(&key.$pk))+$(.value(&key.$ck))*
+            }
+            fn partition_key_columns(&self) -> &'static [&'static str] {
+                &[$(stringify!($pk)),+]
+            }
+        }
+
+        impl $crate::cql::LwtDecoder<$key, $row> for $keyspace {}
+
+        impl $crate::app::access::Table<$key, $row> for $keyspace {
+            $($crate::keyspace_model!(@default $default, $default_value);)*
+        }
+
+        impl $crate::app::access::Insert<$key, $row> for $keyspace {
+            type QueryOrPrepared = $crate::cql::PreparedStatement;
+            fn statement(&self) -> std::borrow::Cow<'static, str> {
+                let columns = [$(stringify!($pk),)+ $(stringify!($ck),)* $(stringify!($col),)*];
+                format!(
+                    "INSERT INTO {}.{} ({}) VALUES ({}){}",
+                    $crate::app::access::Keyspace::name(self),
+                    $table,
+                    columns.join(", "),
+                    vec!["?"; columns.len()].join(", "),
+                    <Self as $crate::app::access::Table<$key, $row>>::using_ttl()
+                )
+                .into()
+            }
+            #[allow(unused_variables)]
+            fn bind_values<T: $crate::cql::Values>(builder: T, key: &$key, value: &$row) -> T::Return {
+                use $crate::cql::Values as _;
+                builder$(.value(&key.$pk))+$(.value(&key.$ck))*$(.value(&value.$col))*
+            }
+        }
+
+        impl $crate::app::access::Update<$key, $row> for $keyspace {
+            type QueryOrPrepared = $crate::cql::PreparedStatement;
+            fn statement(&self) -> std::borrow::Cow<'static, str> {
+                format!(
+                    "UPDATE {}.{}{} SET {} WHERE {}",
+                    $crate::app::access::Keyspace::name(self),
+                    $table,
+                    <Self as $crate::app::access::Table<$key, $row>>::using_ttl(),
+                    <[&str]>::join(&[$(concat!(stringify!($col), " = ?")),*], ", "),
+                    [$(concat!(stringify!($pk), " = ?"),)+ $(concat!(stringify!($ck), " = ?"),)*].join(" AND ")
+                )
+                .into()
+            }
+            #[allow(unused_variables)]
+            fn bind_values<T: $crate::cql::Values>(builder: T, key: &$key, value: &$row) -> T::Return {
+                use $crate::cql::Values as _;
+                builder$(.value(&value.$col))*$(.value(&key.$pk))+$(.value(&key.$ck))*
+            }
+        }
+
+        impl $crate::app::access::Delete<$key, $row> for $keyspace {
+            type QueryOrPrepared = $crate::cql::PreparedStatement;
+            fn statement(&self) -> std::borrow::Cow<'static, str> {
+                format!(
+                    "DELETE FROM {}.{} WHERE {}",
+                    $crate::app::access::Keyspace::name(self),
+                    $table,
+                    [$(concat!(stringify!($pk), " = ?"),)+ $(concat!(stringify!($ck), " = ?"),)*].join(" AND ")
+                )
+                .into()
+            }
+            fn bind_values<T: $crate::cql::Values>(builder: T, key: &$key) -> T::Return {
+                use $crate::cql::Values as _;
+                builder$(.value(&key.$pk))+$(.value(&key.$ck))*
+            }
+        }
+    };
+    (@default DEFAULT_TTL, $value:expr) => {
+        const DEFAULT_TTL: Option<u32> = $value;
+    };
+    (@default READ_CONSISTENCY, $value:expr) => {
+        const READ_CONSISTENCY: $crate::cql::Consistency = $value;
+    };
+    (@default WRITE_CONSISTENCY, $value:expr) => {
+        const WRITE_CONSISTENCY: $crate::cql::Consistency = $value;
+    };
+}
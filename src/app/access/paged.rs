@@ -0,0 +1,177 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::{
+    app::worker::{OneshotHandle, SelectWorker},
+    cql::{Iter, Rows},
+};
+use futures::stream::{self, Stream};
+use std::collections::VecDeque;
+
+/// Pages through the result-set of a `Select` implementation, re-issuing the select request with the
+/// paging state of the previous page until the last page is received.
+///
+/// The pages can be awaited one by one with `next_page`, or the decoded rows can be consumed as a
+/// `futures::Stream` with `into_stream`.
+///
+/// ## Examples
+/// ```no_run
+/// use futures::StreamExt;
+/// use scylla_rs::{
+///     app::access::{ComputeToken, GetSelectRequest, Keyspace, Select},
+///     cql::{Consistency, Decoder, PreparedStatement, RowsDecoder, Values},
+/// };
+/// use std::borrow::Cow;
+/// # #[derive(Default, Clone, Debug)]
+/// # struct MyKeyspace {
+/// #     pub name: Cow<'static, str>,
+/// # }
+/// # impl Keyspace for MyKeyspace {
+/// #     fn name(&self) -> &Cow<'static, str> {
+/// #         &self.name
+/// #     }
+/// # }
+/// # impl ComputeToken<String> for MyKeyspace {
+/// #     fn token(_key: &String) -> i64 {
+/// #         rand::random()
+/// #     }
+/// # }
+/// # impl RowsDecoder<String, Vec<(i64, String)>> for MyKeyspace {
+/// #     type Row = (i64, String);
+/// #     fn try_decode(decoder: Decoder) -> anyhow::Result<Option<Vec<(i64, String)>>> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl Select<String, Vec<(i64, String)>> for MyKeyspace {
+/// #     type QueryOrPrepared = PreparedStatement;
+/// #     fn statement(&self) -> Cow<'static, str> {
+/// #         format!("SELECT ts, event FROM {}.events WHERE key = ?", self.name()).into()
+/// #     }
+/// #     fn bind_values<T: Values>(builder: T, key: &String) -> T::Return {
+/// #         builder.value(key)
+/// #     }
+/// # }
+/// # async fn run() -> anyhow::Result<()> {
+/// # let keyspace = MyKeyspace::default();
+/// let mut rows = keyspace
+///     .select_pages::<Vec<(i64, String)>>(&"my_partition".to_string(), 500)
+///     .consistency(Consistency::Quorum)
+///     .into_stream()
+///     .boxed();
+/// while let Some(row) = rows.next().await {
+///     let (ts, event) = row?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PagedIter<S, K, V> {
+    keyspace: S,
+    key: K,
+    consistency: Consistency,
+    page_size: i32,
+    paging_state: Option<Vec<u8>>,
+    retries: usize,
+    done: bool,
+    _marker: PhantomData<V>,
+}
+
+impl<S, K, V> PagedIter<S, K, V>
+where
+    S: 'static + Select<K, V>,
+    K: 'static + Send + Clone,
+    V: 'static + Send + Clone,
+{
+    /// Create a new paged iterator over the rows selected by the key
+    pub fn new(keyspace: S, key: K, page_size: i32) -> Self {
+        Self {
+            keyspace,
+            key,
+            consistency: Consistency::One,
+            page_size,
+            paging_state: None,
+            retries: 3,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Set the consistency of the select requests
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Set the number of times each page request will be retried on failure
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Resume the paging from a paging state returned by a previous page
+    pub fn resume(mut self, paging_state: Option<Vec<u8>>) -> Self {
+        self.paging_state = paging_state;
+        self
+    }
+
+    /// Get the paging state of the next page, which can be used to resume the paging later
+    pub fn paging_state(&self) -> Option<&Vec<u8>> {
+        self.paging_state.as_ref()
+    }
+
+    /// Check if the last page has been received
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Request the next page and decode its rows, or return None once the last page has been received.
+    /// Note: a page might be empty even if it's not the last one.
+    pub async fn next_page(&mut self) -> anyhow::Result<Option<Vec<S::Row>>> {
+        if self.done {
+            return Ok(None);
+        }
+        let request = self
+            .keyspace
+            .select::<V>(&self.key)
+            .consistency(self.consistency)
+            .page_size(self.page_size)
+            .paging_state(&self.paging_state)
+            .build()?;
+        let (handle, rx) = OneshotHandle::new();
+        let worker = SelectWorker::new(
+            handle,
+            self.keyspace.clone(),
+            self.key.clone(),
+            self.retries,
+            PhantomData,
+        )
+        .with_paging(self.page_size, self.paging_state.clone());
+        request.send_global(Box::new(worker));
+        let decoder = rx.await.unwrap_or(Err(WorkerError::Lost))?;
+        let mut rows = Iter::<S::Row>::new(decoder)?;
+        self.paging_state = rows.take_paging_state();
+        self.done = !rows.has_more_pages() || self.paging_state.is_none();
+        Ok(Some(rows.collect()))
+    }
+
+    /// Consume the paged iterator into a stream of the decoded rows, which requests the next page once the
+    /// rows of the current one have been consumed. The stream ends after the first error.
+    pub fn into_stream(self) -> impl Stream<Item = anyhow::Result<S::Row>> {
+        stream::unfold((self, VecDeque::new()), |(mut pages, mut rows)| async move {
+            loop {
+                if let Some(row) = rows.pop_front() {
+                    return Some((Ok(row), (pages, rows)));
+                }
+                match pages.next_page().await {
+                    Ok(Some(page)) => rows.extend(page),
+                    Ok(None) => return None,
+                    Err(e) => {
+                        pages.done = true;
+                        return Some((Err(e), (pages, rows)));
+                    }
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::prepare_all;
+use crate::{
+    app::diagnostics::{self, DiagnosticEvent},
+    cql::{parameterize, read_only, CompressionType, Fnv64, Md5, StatementIdAlgorithm, StatementKind},
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// The query frame opcode
+const QUERY: u8 = 0x07;
+/// The compression flag of the frame header
+const COMPRESSION: u8 = 0x01;
+
+static PROFILING: AtomicBool = AtomicBool::new(false);
+static PROFILER: OnceLock<Mutex<Profiler>> = OnceLock::new();
+
+/// The configuration of the hot statement profiler
+#[derive(Debug, Clone, Copy)]
+pub struct ProfilerConfig {
+    /// The rate of unprepared queries per second above which a statement is hot
+    pub min_rate: f64,
+    /// The window over which the rates are measured
+    pub window: Duration,
+    /// Prepare the hot statements on every shard of the cluster, unless they inline their values
+    pub auto_prepare: bool,
+    /// The maximum number of profiled statements, the statements beyond it aren't profiled
+    pub max_statements: usize,
+}
+
+impl Default for ProfilerConfig {
+    fn default() -> Self {
+        Self {
+            min_rate: 100.0,
+            window: Duration::from_secs(10),
+            auto_prepare: false,
+            max_statements: 1024,
+        }
+    }
+}
+
+/// The suggestion to prepare a hot statement, which is still executed as unprepared QUERY frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementSuggestion {
+    /// The fingerprint of the statement
+    pub fingerprint: u64,
+    /// The statement to prepare, whose inlined literals are replaced with bind markers
+    pub statement: String,
+    /// Whether the queries inline their values, which must be bound to the prepared statement instead
+    pub inlined_literals: bool,
+    /// The number of profiled queries of the statement
+    pub queries: u64,
+    /// The rate of queries per second, over the last window
+    pub rate: f64,
+    /// The id of the prepared statement, if it was auto prepared
+    pub prepared_id: Option<[u8; 16]>,
+}
+
+impl Display for StatementSuggestion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Hot unprepared statement ({:.1} queries/s): prepare '{}'",
+            self.rate, self.statement
+        )?;
+        if self.inlined_literals {
+            write!(f, " and bind the values which are inlined in its queries")?;
+        }
+        if let Some(id) = self.prepared_id {
+            write!(f, ", it was prepared with the id {:?}", id)?;
+        }
+        Ok(())
+    }
+}
+
+/// The profile of a statement
+struct Profile {
+    statement: String,
+    inlined_literals: bool,
+    queries: u64,
+    window_start: Instant,
+    window_queries: u64,
+    rate: f64,
+    prepared_id: Option<[u8; 16]>,
+    hot: bool,
+}
+
+impl Profile {
+    fn suggestion(&self, fingerprint: u64) -> StatementSuggestion {
+        StatementSuggestion {
+            fingerprint,
+            statement: self.statement.clone(),
+            inlined_literals: self.inlined_literals,
+            queries: self.queries,
+            rate: self.rate,
+            prepared_id: self.prepared_id,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Profiler {
+    config: ProfilerConfig,
+    profiles: HashMap<u64, Profile>,
+}
+
+impl Profiler {
+    /// Profile a query of the statement at the instant. Returns the fingerprint and the profile of the statement
+    /// once it becomes hot, which is only the case once.
+    fn profile(&mut self, statement: &str, now: Instant) -> Option<(u64, &mut Profile)> {
+        let parameterized = parameterize(statement);
+        let fingerprint = Fnv64::statement_id(&parameterized);
+        let config = self.config;
+        if !self.profiles.contains_key(&fingerprint) {
+            if self.profiles.len() >= config.max_statements {
+                return None;
+            }
+            let inlined_literals = matches!(parameterized, Cow::Owned(_));
+            self.profiles.insert(
+                fingerprint,
+                Profile {
+                    statement: parameterized.into_owned(),
+                    inlined_literals,
+                    queries: 0,
+                    window_start: now,
+                    window_queries: 0,
+                    rate: 0.0,
+                    prepared_id: None,
+                    hot: false,
+                },
+            );
+        }
+        let profile = self.profiles.get_mut(&fingerprint).expect("The profile is inserted");
+        let elapsed = now.duration_since(profile.window_start);
+        if elapsed >= config.window {
+            profile.rate = profile.window_queries as f64 / elapsed.as_secs_f64();
+            profile.window_start = now;
+            profile.window_queries = 0;
+        }
+        profile.queries += 1;
+        profile.window_queries += 1;
+        // the statement is hot as soon as its queries within the window exceed the rate
+        let window_rate = profile.window_queries as f64 / config.window.as_secs_f64().max(f64::EPSILON);
+        if profile.hot || window_rate < config.min_rate {
+            return None;
+        }
+        profile.hot = true;
+        profile.rate = profile.rate.max(window_rate);
+        Some((fingerprint, profile))
+    }
+
+    /// Get the suggestions of the hot statements, ordered by their descending rate
+    fn hot_statements(&self) -> Vec<StatementSuggestion> {
+        let mut suggestions: Vec<StatementSuggestion> = self
+            .profiles
+            .iter()
+            .filter(|(_, profile)| profile.hot)
+            .map(|(fingerprint, profile)| profile.suggestion(*fingerprint))
+            .collect();
+        suggestions.sort_by(|a, b| b.rate.total_cmp(&a.rate));
+        suggestions
+    }
+}
+
+fn profiler() -> &'static Mutex<Profiler> {
+    PROFILER.get_or_init(Default::default)
+}
+
+/// Enable the hot statement profiler, which counts the unprepared SELECT, INSERT, UPDATE and DELETE queries by
+/// their statement, once their inlined literals are replaced with bind markers (see `parameterize`).
+///
+/// A statement whose rate exceeds the configured rate is reported once, as a `HotStatement` diagnostic event and
+/// through `hot_statements`, then it's prepared on every shard of the cluster if the auto prepare is enabled and
+/// its queries don't inline their values, so it's ready to be executed with its id (see `Md5::statement_id`).
+pub fn enable_profiler(config: ProfilerConfig) {
+    let mut profiler = profiler().lock().expect("Profiler lock is poisoned");
+    profiler.config = config;
+    profiler.profiles.clear();
+    PROFILING.store(true, Ordering::Relaxed);
+}
+
+/// Disable the hot statement profiler
+pub fn disable_profiler() {
+    PROFILING.store(false, Ordering::Relaxed);
+}
+
+/// Check if the hot statement profiler is enabled
+pub fn is_profiling() -> bool {
+    PROFILING.load(Ordering::Relaxed)
+}
+
+/// Get the suggestions of the hot statements, ordered by their descending rate
+pub fn hot_statements() -> Vec<StatementSuggestion> {
+    profiler().lock().expect("Profiler lock is poisoned").hot_statements()
+}
+
+/// Profile the request payload, if it's an unprepared query and the profiler is enabled. The payloads which are
+/// built compressed are skipped, as they're only decompressed by the connection which negotiated their compression.
+pub(crate) fn record(payload: &[u8]) {
+    if !is_profiling() {
+        return;
+    }
+    let statement = match profiled_statement(payload) {
+        Some(statement) => statement,
+        None => return,
+    };
+    let mut profiler = profiler().lock().expect("Profiler lock is poisoned");
+    let config = profiler.config;
+    let (fingerprint, profile) = match profiler.profile(&statement, Instant::now()) {
+        Some(hot) => hot,
+        None => return,
+    };
+    if config.auto_prepare && !profile.inlined_literals {
+        match prepare_all(&profile.statement) {
+            Ok(_) => profile.prepared_id = Some(Md5::statement_id(&profile.statement)),
+            Err(e) => diagnostics::emit(DiagnosticEvent::PrepareFailed {
+                statement: profile.statement.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
+    let suggestion = profile.suggestion(fingerprint);
+    // emit the event without holding the lock
+    drop(profiler);
+    diagnostics::emit(DiagnosticEvent::HotStatement { suggestion });
+}
+
+/// Get the statement of the payload if it's an uncompressed QUERY frame of a SELECT, INSERT, UPDATE or DELETE
+fn profiled_statement(payload: &[u8]) -> Option<String> {
+    if payload.len() < 9 || payload[4] != QUERY || payload[1] & COMPRESSION == COMPRESSION {
+        return None;
+    }
+    read_only::statement(payload, CompressionType::Uncompressed)
+        .filter(|statement| StatementKind::of(statement).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::{Consistency, Prepare, Query, Statements};
+
+    /// A local profiler along with its config, so the tests don't share the global one with the requests of the
+    /// other tests
+    fn profiler(min_rate: f64, max_statements: usize) -> Profiler {
+        Profiler {
+            config: ProfilerConfig {
+                min_rate,
+                window: Duration::from_secs(1),
+                auto_prepare: false,
+                max_statements,
+            },
+            profiles: HashMap::new(),
+        }
+    }
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn aggregate_the_parameterized_queries() {
+        let mut profiler = profiler(2.0, 16);
+        let start = Instant::now();
+        assert!(profiler.profile("SELECT v FROM ks.t WHERE k = 1", start).is_none());
+        // the queries which only differ by their literals are profiled as the same statement
+        let (fingerprint, profile) = profiler
+            .profile("SELECT v FROM ks.t WHERE k = 2", start + millis(100))
+            .unwrap();
+        assert_eq!(profile.statement, "SELECT v FROM ks.t WHERE k = ?");
+        assert!(profile.inlined_literals);
+        assert_eq!(profile.queries, 2);
+        assert_eq!(profile.rate, 2.0);
+        assert_eq!(fingerprint, Fnv64::statement_id("SELECT v FROM ks.t WHERE k = ?"));
+        // the hot statement is only reported once
+        assert!(profiler
+            .profile("SELECT v FROM ks.t WHERE k = 3", start + millis(200))
+            .is_none());
+        assert_eq!(profiler.profiles.len(), 1);
+        assert_eq!(profiler.profiles[&fingerprint].queries, 3);
+    }
+
+    #[test]
+    fn measure_the_rate_by_window() {
+        let mut profiler = profiler(2.0, 16);
+        let start = Instant::now();
+        let statement = "UPDATE ks.t SET v = ? WHERE k = ?";
+        assert!(profiler.profile(statement, start).is_none());
+        // the next window starts with the rate of the previous one
+        assert!(profiler.profile(statement, start + millis(2000)).is_none());
+        let profile = profiler.profiles.values().next().unwrap();
+        assert_eq!(profile.rate, 0.5);
+        assert_eq!(profile.window_queries, 1);
+        let (_, profile) = profiler.profile(statement, start + millis(2500)).unwrap();
+        assert!(!profile.inlined_literals);
+        assert_eq!(profile.queries, 3);
+        assert_eq!(profile.rate, 2.0);
+    }
+
+    #[test]
+    fn bounded_statements() {
+        let mut profiler = profiler(1.0, 1);
+        let start = Instant::now();
+        assert!(profiler.profile("SELECT v FROM ks.t1 WHERE k = ?", start).is_some());
+        // the statements beyond the maximum aren't profiled
+        assert!(profiler.profile("SELECT v FROM ks.t2 WHERE k = ?", start).is_none());
+        assert_eq!(profiler.profiles.len(), 1);
+    }
+
+    #[test]
+    fn summarize_the_hot_statements() {
+        let mut profiler = profiler(3.0, 16);
+        let start = Instant::now();
+        for (statement, queries) in [
+            ("SELECT v FROM ks.t WHERE k = 'a'", 3),
+            ("DELETE FROM ks.t WHERE k = ?", 5),
+            ("INSERT INTO ks.t (k, v) VALUES (?, ?)", 2),
+        ] {
+            for _ in 0..queries {
+                profiler.profile(statement, start);
+            }
+        }
+        // the rate of the hot statement is measured once its window elapses
+        profiler.profile("DELETE FROM ks.t WHERE k = ?", start + millis(1250));
+        // the cold statement isn't suggested, while the hot ones are ordered by their descending rate
+        let suggestions = profiler.hot_statements();
+        let statements: Vec<_> = suggestions
+            .iter()
+            .map(|suggestion| (suggestion.statement.as_str(), suggestion.queries))
+            .collect();
+        assert_eq!(
+            statements,
+            vec![
+                ("DELETE FROM ks.t WHERE k = ?", 6),
+                ("SELECT v FROM ks.t WHERE k = ?", 3)
+            ]
+        );
+        assert_eq!(suggestions[0].rate, 4.0);
+        assert_eq!(
+            suggestions[1].to_string(),
+            "Hot unprepared statement (3.0 queries/s): prepare 'SELECT v FROM ks.t WHERE k = ?' and bind the values \
+             which are inlined in its queries"
+        );
+    }
+
+    #[test]
+    fn profiled_statements() {
+        let query = |statement: &str| {
+            let Query(payload) = Query::new()
+                .statement(statement)
+                .consistency(Consistency::One)
+                .build()
+                .unwrap();
+            payload
+        };
+        assert_eq!(
+            profiled_statement(&query("SELECT v FROM ks.t WHERE k = 1")).as_deref(),
+            Some("SELECT v FROM ks.t WHERE k = 1")
+        );
+        // neither the other statements, the other requests nor the compressed queries are profiled
+        assert_eq!(
+            profiled_statement(&query("CREATE TABLE ks.t (k int PRIMARY KEY)")),
+            None
+        );
+        let Prepare(payload) = Prepare::new()
+            .statement("SELECT v FROM ks.t WHERE k = ?")
+            .build()
+            .unwrap();
+        assert_eq!(profiled_statement(&payload), None);
+        let mut compressed = query("SELECT v FROM ks.t WHERE k = 1");
+        compressed[1] |= COMPRESSION;
+        assert_eq!(profiled_statement(&compressed), None);
+    }
+}
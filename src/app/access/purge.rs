@@ -0,0 +1,464 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{ColumnDecoder, Frame};
+use anyhow::{anyhow, ensure};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use std::{convert::TryFrom, sync::Arc};
+
+/// A row of the purge scan, which holds the partition key values followed by the extra selected columns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionRow {
+    token: i64,
+    values: Vec<Option<Vec<u8>>>,
+}
+
+impl PartitionRow {
+    /// Get the token of the partition
+    pub fn token(&self) -> i64 {
+        self.token
+    }
+    /// Decode the column value at the index, where the partition key columns come first
+    pub fn get<C: ColumnDecoder>(&self, index: usize) -> anyhow::Result<C> {
+        let value = self
+            .values
+            .get(index)
+            .ok_or_else(|| anyhow!("Column index {} out of bounds", index))?;
+        C::try_decode(value.as_deref().unwrap_or_default())
+    }
+}
+
+/// The progress of a purge, reported once all the partitions of a scanned page have been deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PurgeProgress {
+    /// The token up to which (inclusive) the ring has been purged, which can be used to resume the purge
+    pub token: i64,
+    /// The number of scanned rows
+    pub scanned: u64,
+    /// The number of deleted partitions
+    pub deleted: u64,
+}
+
+type Predicate = Arc<dyn Fn(&PartitionRow) -> bool + Send + Sync>;
+
+/// Deletes the partitions of a table which match a predicate, by scanning the table in token order and
+/// issuing the partition deletes, routed by their tokens, with a bounded concurrency.
+///
+/// The scan only restricts the partition key tokens, so it doesn't need `ALLOW FILTERING`. Instead, the
+/// predicate is evaluated on the client over the partition key values and the extra selected columns.
+/// A partition is deleted if any of its scanned rows matches the predicate.
+///
+/// The progress is checkpointed once the partitions of each page have been deleted, and a failed purge
+/// can be resumed from the last checkpointed token.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::app::access::Purge;
+/// # async fn run() -> anyhow::Result<()> {
+/// let progress = Purge::new("my_keyspace", "events", &["user_id", "day"])
+///     .columns(&["owner"])
+///     .filter(|row| matches!(row.get::<String>(2), Ok(owner) if owner == "alice"))
+///     .concurrency(32)
+///     .run(|progress| println!("purged up to token {}", progress.token))
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Purge {
+    keyspace: String,
+    table: String,
+    partition_key: Vec<String>,
+    columns: Vec<String>,
+    predicate: Option<Predicate>,
+    concurrency: usize,
+    page_size: i32,
+    consistency: Consistency,
+    start: i64,
+    end: i64,
+}
+
+impl Purge {
+    /// Create a new purge of the table partitions, identified by the partition key columns
+    pub fn new<K: Into<String>, T: Into<String>>(keyspace: K, table: T, partition_key: &[&str]) -> Self {
+        Self {
+            keyspace: keyspace.into(),
+            table: table.into(),
+            partition_key: partition_key.iter().map(|column| column.to_string()).collect(),
+            columns: Vec::new(),
+            predicate: None,
+            concurrency: 16,
+            page_size: 1000,
+            consistency: Consistency::Quorum,
+            start: i64::MIN,
+            end: i64::MAX,
+        }
+    }
+
+    /// Select extra columns, which can be used by the predicate. Note that selecting regular columns scans
+    /// every row instead of the distinct partitions.
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = columns.iter().map(|column| column.to_string()).collect();
+        self
+    }
+
+    /// Only delete the partitions which match the predicate. Every scanned partition is deleted otherwise.
+    pub fn filter<F: 'static + Fn(&PartitionRow) -> bool + Send + Sync>(mut self, predicate: F) -> Self {
+        self.predicate = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Set the maximum number of concurrent partition deletes
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set the page size of the scan
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Set the consistency of both the scan and the deletes
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Restrict the purge to the tokens in `(start, end]`, ie to split it across multiple purges
+    pub fn token_range(mut self, start: i64, end: i64) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    /// Resume the purge after the token of a checkpointed progress.
+    /// Note: when extra columns are selected, the remaining rows of the checkpointed partition are skipped.
+    pub fn resume(mut self, token: i64) -> Self {
+        self.start = token;
+        self
+    }
+
+    /// Get the scan statement
+    pub fn scan_statement(&self) -> String {
+        let partition_key = self
+            .partition_key
+            .iter()
+            .map(|column| Name::auto(column).to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let distinct = if self.columns.is_empty() { "DISTINCT " } else { "" };
+        let columns = self
+            .partition_key
+            .iter()
+            .chain(self.columns.iter())
+            .map(|column| Name::auto(column).to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "SELECT {}token({}), {} FROM {} WHERE token({}) > ? AND token({}) <= ?",
+            distinct,
+            partition_key,
+            columns,
+            Name::qualify(&self.keyspace, &self.table),
+            partition_key,
+            partition_key
+        )
+    }
+
+    /// Get the partition delete statement
+    pub fn delete_statement(&self) -> String {
+        let restrictions = self
+            .partition_key
+            .iter()
+            .map(|column| format!("{} = ?", Name::auto(column)))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        format!(
+            "DELETE FROM {} WHERE {}",
+            Name::qualify(&self.keyspace, &self.table),
+            restrictions
+        )
+    }
+
+    /// Run the purge, invoking `checkpoint` with the progress once the partitions of each page have been
+    /// deleted. Returns the final progress.
+    pub async fn run<F: FnMut(&PurgeProgress)>(&self, mut checkpoint: F) -> anyhow::Result<PurgeProgress> {
+        ensure!(!self.partition_key.is_empty(), "The partition key columns are required");
+        let scan_statement = self.scan_statement();
+        let delete_statement = self.delete_statement();
+        let mut progress = PurgeProgress {
+            token: self.start,
+            scanned: 0,
+            deleted: 0,
+        };
+        let mut paging_state = None;
+        let mut last_deleted: Option<Vec<Option<Vec<u8>>>> = None;
+        loop {
+            let Query(payload) = Query::new()
+                .statement(&scan_statement)
+                .consistency(self.consistency)
+                .value(&self.start)
+                .value(&self.end)
+                .page_size(self.page_size)
+                .paging_state(&paging_state)
+                .build()?;
+            let decoder = Decoder::try_from(send_oneshot(self.start, payload, self.keyspace.clone()).await?)?;
+            let (rows, next_paging_state) = self.decode_rows(decoder)?;
+            progress.scanned += rows.len() as u64;
+            // the rows of a partition are adjacent, therefore a partition is deleted once
+            let mut partitions: Vec<PartitionRow> = Vec::new();
+            for row in rows.iter() {
+                progress.token = row.token;
+                let key = &row.values[..self.partition_key.len()];
+                let deleted = partitions
+                    .last()
+                    .map(|partition| &partition.values[..self.partition_key.len()])
+                    .or_else(|| last_deleted.as_deref());
+                if deleted == Some(key) {
+                    continue;
+                }
+                let matches = match self.predicate.as_ref() {
+                    Some(predicate) => predicate(row),
+                    None => true,
+                };
+                if matches {
+                    partitions.push(row.clone());
+                }
+            }
+            stream::iter(partitions.iter().map(Ok))
+                .try_for_each_concurrent(self.concurrency, |partition| self.delete(&delete_statement, partition))
+                .await?;
+            progress.deleted += partitions.len() as u64;
+            if let Some(partition) = partitions.pop() {
+                last_deleted.replace(partition.values[..self.partition_key.len()].to_vec());
+            }
+            checkpoint(&progress);
+            match next_paging_state {
+                Some(next_paging_state) => paging_state.replace(next_paging_state),
+                None => break,
+            };
+        }
+        Ok(progress)
+    }
+
+    /// Delete the partition of the row
+    async fn delete(&self, statement: &str, partition: &PartitionRow) -> anyhow::Result<()> {
+        let mut builder = Query::new()
+            .statement(statement)
+            .consistency(self.consistency)
+            .value(&partition.values[0].as_deref().unwrap_or_default());
+        for value in partition.values[1..self.partition_key.len()].iter() {
+            builder = builder.value(&value.as_deref().unwrap_or_default());
+        }
+        let Query(payload) = builder.build()?;
+        Decoder::try_from(send_oneshot(partition.token, payload, self.keyspace.clone()).await?)?;
+        Ok(())
+    }
+
+    /// Decode the scanned rows, as the token followed by the selected columns, along with the paging state
+    fn decode_rows(&self, decoder: Decoder) -> anyhow::Result<(Vec<PartitionRow>, Option<Vec<u8>>)> {
+        let mut metadata = decoder.metadata()?;
+        let columns_count = metadata.columns_count() as usize;
+        ensure!(
+            columns_count == 1 + self.partition_key.len() + self.columns.len(),
+            "Unexpected scan columns count: {}",
+            columns_count
+        );
+        let buffer = decoder.buffer_as_ref();
+        let mut offset = metadata.rows_start();
+        let rows_count = read_i32(buffer, offset)?;
+        offset += 4;
+        let mut rows = Vec::with_capacity(rows_count.max(0) as usize);
+        for _ in 0..rows_count {
+            let mut values = Vec::with_capacity(columns_count - 1);
+            let mut token = None;
+            for column in 0..columns_count {
+                let length = read_i32(buffer, offset)?;
+                offset += 4;
+                let value = if length >= 0 {
+                    let end = offset + length as usize;
+                    ensure!(buffer.len() >= end, "Buffer is too small!");
+                    let value = buffer[offset..end].to_vec();
+                    offset = end;
+                    Some(value)
+                } else {
+                    None
+                };
+                if column == 0 {
+                    token = Some(i64::try_decode(value.as_deref().unwrap_or_default())?);
+                } else {
+                    values.push(value);
+                }
+            }
+            rows.push(PartitionRow {
+                token: token.unwrap_or_default(),
+                values,
+            });
+        }
+        Ok((rows, metadata.take_paging_state()))
+    }
+}
+
+/// Deletes the partitions of a `Delete` implementation by their keys, with a bounded concurrency.
+/// Returns the number of deleted keys.
+pub async fn delete_partitions<S, K, V, I>(
+    keyspace: &S,
+    keys: I,
+    consistency: Consistency,
+    concurrency: usize,
+) -> anyhow::Result<u64>
+where
+    S: Delete<K, V>,
+    I: IntoIterator<Item = K>,
+{
+    let requests = keys
+        .into_iter()
+        .map(|key| {
+            let token = S::token(&key);
+            keyspace
+                .delete::<V>(&key)
+                .consistency(consistency)
+                .build()
+                .map(|request| (token, request.into_payload()))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let deleted = requests.len() as u64;
+    stream::iter(requests)
+        .map(|(token, payload)| async move {
+            Decoder::try_from(send_oneshot(token, payload, keyspace.name().to_string()).await?)?;
+            Ok::<_, anyhow::Error>(())
+        })
+        .buffer_unordered(concurrency.max(1))
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(deleted)
+}
+
+fn read_i32(buffer: &[u8], offset: usize) -> anyhow::Result<i32> {
+    ensure!(buffer.len() >= offset + 4, "Buffer is too small!");
+    Ok(i32::from_be_bytes([
+        buffer[offset],
+        buffer[offset + 1],
+        buffer[offset + 2],
+        buffer[offset + 3],
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::access::stub::{request_statement, request_values, requests, rows_result, void_result};
+    use std::sync::Mutex;
+
+    const SCAN: &str = "SELECT token(id), id, owner FROM ks.events WHERE token(id) > ? AND token(id) <= ?";
+    const DELETE: &str = "DELETE FROM ks.events WHERE id = ?";
+
+    /// Stub the replica, which answers the scan by the rows of `(token, id, owner)` and records the values of
+    /// both the scans and the deletes
+    fn replica(rows: &[(i64, &str, &str)]) -> (Arc<Mutex<Vec<(i64, i64)>>>, Arc<Mutex<Vec<String>>>) {
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|(token, id, owner)| {
+                vec![
+                    Some(token.to_be_bytes().to_vec()),
+                    Some(id.as_bytes().to_vec()),
+                    Some(owner.as_bytes().to_vec()),
+                ]
+            })
+            .collect();
+        let scan = rows_result(&[("token(id)", 0x0002), ("id", 0x000D), ("owner", 0x000D)], &rows);
+        let (ranges, deletes) = (Arc::new(Mutex::new(Vec::new())), Arc::new(Mutex::new(Vec::new())));
+        let (scanned, deleted) = (ranges.clone(), deletes.clone());
+        requests(move |payload| {
+            let values = request_values(payload);
+            let value = |index: usize| values[index].clone().unwrap();
+            match request_statement(payload).as_str() {
+                SCAN => {
+                    let token = |index| i64::try_decode(&value(index)).unwrap();
+                    scanned.lock().unwrap().push((token(0), token(1)));
+                    Ok(scan.clone())
+                }
+                DELETE => {
+                    deleted.lock().unwrap().push(String::from_utf8(value(0)).unwrap());
+                    Ok(void_result())
+                }
+                statement => panic!("unexpected statement {}", statement),
+            }
+        });
+        (ranges, deletes)
+    }
+
+    fn purge() -> Purge {
+        Purge::new("ks", "events", &["id"]).columns(&["owner"])
+    }
+
+    #[test]
+    fn statements() {
+        let purge = Purge::new("ks", "events", &["user_id", "day"]);
+        assert_eq!(
+            purge.scan_statement(),
+            "SELECT DISTINCT token(user_id, day), user_id, day FROM ks.events \
+             WHERE token(user_id, day) > ? AND token(user_id, day) <= ?"
+        );
+        assert_eq!(
+            purge.delete_statement(),
+            "DELETE FROM ks.events WHERE user_id = ? AND day = ?"
+        );
+        // the extra columns scan every row
+        let purge = purge.columns(&["owner"]);
+        assert_eq!(
+            purge.scan_statement(),
+            "SELECT token(user_id, day), user_id, day, owner FROM ks.events \
+             WHERE token(user_id, day) > ? AND token(user_id, day) <= ?"
+        );
+        assert_eq!(
+            Purge::new("ks", "events", &["id"]).columns(&["owner"]).scan_statement(),
+            SCAN
+        );
+    }
+
+    #[tokio::test]
+    async fn token_ranges() {
+        let (ranges, _) = replica(&[]);
+        // the whole ring, then its split halves, whose boundaries are excluded from the first token of the next one
+        purge().run(|_| ()).await.unwrap();
+        purge().token_range(i64::MIN, 0).run(|_| ()).await.unwrap();
+        purge().token_range(0, i64::MAX).run(|_| ()).await.unwrap();
+        // the resumed purge starts after the checkpointed token
+        purge().token_range(0, i64::MAX).resume(42).run(|_| ()).await.unwrap();
+        assert_eq!(
+            *ranges.lock().unwrap(),
+            vec![(i64::MIN, i64::MAX), (i64::MIN, 0), (0, i64::MAX), (42, i64::MAX)]
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_the_matching_partitions_once() {
+        let (_, deletes) = replica(&[
+            (10, "a", "bob"),
+            (10, "a", "alice"),
+            (20, "b", "bob"),
+            (30, "c", "alice"),
+            (30, "c", "alice"),
+        ]);
+        let mut checkpoints = Vec::new();
+        let progress = purge()
+            .filter(|row| matches!(row.get::<String>(1), Ok(owner) if owner == "alice"))
+            .run(|progress| checkpoints.push(*progress))
+            .await
+            .unwrap();
+        let expected = PurgeProgress {
+            token: 30,
+            scanned: 5,
+            deleted: 2,
+        };
+        assert_eq!(progress, expected);
+        assert_eq!(checkpoints, vec![expected]);
+        let mut deletes = deletes.lock().unwrap().clone();
+        deletes.sort();
+        assert_eq!(deletes, vec!["a", "c"]);
+    }
+}
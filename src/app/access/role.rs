@@ -0,0 +1,40 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::LitStr;
+use std::convert::TryFrom;
+
+/// Create the statement which alters the password of the role.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::app::access::alter_role_password_statement;
+/// assert_eq!(
+///     alter_role_password_statement("app", "it's secret"),
+///     "ALTER ROLE 'app' WITH PASSWORD = 'it''s secret'"
+/// );
+/// ```
+pub fn alter_role_password_statement(role: &str, password: &str) -> String {
+    format!(
+        "ALTER ROLE {} WITH PASSWORD = {}",
+        LitStr::new(role),
+        LitStr::new(password)
+    )
+}
+
+/// Alter the password of the role, through the current driver sessions.
+///
+/// Note: this doesn't update the credentials used by the driver itself,
+/// refer to `ClusterHandle::rotate_password` for that.
+pub async fn alter_role_password(role: &str, password: &str) -> Result<(), WorkerError> {
+    let Query(payload) = Query::new()
+        .statement(&alter_role_password_statement(role, password))
+        .consistency(Consistency::Quorum)
+        .build()
+        .map_err(WorkerError::Other)?;
+    // role statements aren't bound to a partition, so any token works
+    send_oneshot(rand::random(), payload, "system_auth".to_string())
+        .await
+        .and_then(|giveload| Decoder::try_from(giveload).map(|_| ()).map_err(WorkerError::Other))
+}
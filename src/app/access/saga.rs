@@ -0,0 +1,390 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use std::convert::TryFrom;
+
+/// A saga step, which holds the mutation and the compensating mutation that undoes it
+struct SagaStep {
+    name: Cow<'static, str>,
+    token: i64,
+    mutation: Vec<u8>,
+    compensation: Vec<u8>,
+}
+
+/// The saga journal events, which are reported to the journal hook while the saga executes
+#[derive(Debug, Clone, Copy)]
+pub enum SagaEvent<'a> {
+    /// The mutation of a step is about to be sent
+    StepStarted {
+        /// The step index
+        index: usize,
+        /// The step name
+        name: &'a str,
+    },
+    /// The mutation of a step has been applied
+    StepApplied {
+        /// The step index
+        index: usize,
+        /// The step name
+        name: &'a str,
+    },
+    /// The mutation of a step failed, therefore the applied steps are about to be compensated
+    StepFailed {
+        /// The step index
+        index: usize,
+        /// The step name
+        name: &'a str,
+        /// The error
+        error: &'a WorkerError,
+    },
+    /// The compensating mutation of a step has been applied
+    Compensated {
+        /// The step index
+        index: usize,
+        /// The step name
+        name: &'a str,
+    },
+    /// The compensating mutation of a step failed after exhausting its retries
+    CompensationFailed {
+        /// The step index
+        index: usize,
+        /// The step name
+        name: &'a str,
+        /// The last error
+        error: &'a WorkerError,
+    },
+}
+
+/// The completion report of a saga
+#[derive(Debug, Clone, Default)]
+pub struct SagaReport {
+    /// The names of the applied steps, in execution order
+    pub applied: Vec<String>,
+    /// The name of the failed step along with its error
+    pub failed: Option<(String, String)>,
+    /// The names of the compensated steps, in compensation order
+    pub compensated: Vec<String>,
+    /// The names of the steps which failed to be compensated along with their last error
+    pub compensation_failures: Vec<(String, String)>,
+}
+
+impl SagaReport {
+    /// Check if every step has been applied
+    pub fn is_completed(&self) -> bool {
+        self.failed.is_none()
+    }
+    /// Check if the saga failed and every applied step has been compensated
+    pub fn is_rolled_back(&self) -> bool {
+        self.failed.is_some() && self.compensation_failures.is_empty()
+    }
+}
+
+/// Executes a sequence of mutations as a transaction-like unit, by compensating the applied ones on failure.
+///
+/// Scylla doesn't support multi-partition transactions, therefore each step registers a mutation along with
+/// a compensating mutation which undoes it. The steps are executed sequentially, and once a step fails the
+/// compensating mutations of the applied steps are executed in reverse order.
+///
+/// Note: the saga isn't isolated, other readers might observe the intermediate state, and the compensating
+/// mutations should be idempotent as they are retried on failure.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::{
+///     app::access::Saga,
+///     cql::{Consistency, Query, Statements, TokenEncoder},
+/// };
+/// # async fn run() -> anyhow::Result<()> {
+/// let debit = Query::new()
+///     .statement("UPDATE bank.accounts SET balance = 90 WHERE id = 'alice'")
+///     .consistency(Consistency::Quorum)
+///     .build()?;
+/// let undo_debit = Query::new()
+///     .statement("UPDATE bank.accounts SET balance = 100 WHERE id = 'alice'")
+///     .consistency(Consistency::Quorum)
+///     .build()?;
+/// let report = Saga::new("bank")
+///     .step("debit", "alice".get_token(), debit, undo_debit)
+///     .journal(|event| log::info!("{:?}", event))
+///     .execute()
+///     .await;
+/// assert!(report.is_completed() || report.is_rolled_back());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Saga {
+    keyspace: String,
+    steps: Vec<SagaStep>,
+    compensation_retries: usize,
+    journal: Option<Box<dyn Fn(&SagaEvent) + Send + Sync>>,
+}
+
+impl Saga {
+    /// Create a new empty saga over the keyspace
+    pub fn new<T: Into<String>>(keyspace: T) -> Self {
+        Self {
+            keyspace: keyspace.into(),
+            steps: Vec::new(),
+            compensation_retries: 3,
+            journal: None,
+        }
+    }
+
+    /// Add a step with its mutation and the compensating mutation, both routed by the token
+    pub fn step<N, M, C>(mut self, name: N, token: i64, mutation: M, compensation: C) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+        M: Into<Vec<u8>>,
+        C: Into<Vec<u8>>,
+    {
+        self.steps.push(SagaStep {
+            name: name.into(),
+            token,
+            mutation: mutation.into(),
+            compensation: compensation.into(),
+        });
+        self
+    }
+
+    /// Set the number of retries of a failed compensating mutation
+    pub fn compensation_retries(mut self, compensation_retries: usize) -> Self {
+        self.compensation_retries = compensation_retries;
+        self
+    }
+
+    /// Set the journal hook, which is invoked with every saga event, ie to persist the saga progress
+    pub fn journal<F: Fn(&SagaEvent) + Send + Sync + 'static>(mut self, journal: F) -> Self {
+        self.journal.replace(Box::new(journal));
+        self
+    }
+
+    /// Execute the steps sequentially, and compensate the applied steps in reverse order on failure
+    pub async fn execute(self) -> SagaReport {
+        let mut report = SagaReport::default();
+        let mut applied = 0;
+        for (index, step) in self.steps.iter().enumerate() {
+            self.record(SagaEvent::StepStarted {
+                index,
+                name: &step.name,
+            });
+            match self.send(step.token, step.mutation.clone()).await {
+                Ok(()) => {
+                    self.record(SagaEvent::StepApplied {
+                        index,
+                        name: &step.name,
+                    });
+                    report.applied.push(step.name.to_string());
+                    applied += 1;
+                }
+                Err(error) => {
+                    self.record(SagaEvent::StepFailed {
+                        index,
+                        name: &step.name,
+                        error: &error,
+                    });
+                    report.failed.replace((step.name.to_string(), error.to_string()));
+                    break;
+                }
+            }
+        }
+        if report.failed.is_some() {
+            for (index, step) in self.steps[..applied].iter().enumerate().rev() {
+                match self.compensate(step).await {
+                    Ok(()) => {
+                        self.record(SagaEvent::Compensated {
+                            index,
+                            name: &step.name,
+                        });
+                        report.compensated.push(step.name.to_string());
+                    }
+                    Err(error) => {
+                        self.record(SagaEvent::CompensationFailed {
+                            index,
+                            name: &step.name,
+                            error: &error,
+                        });
+                        report
+                            .compensation_failures
+                            .push((step.name.to_string(), error.to_string()));
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    fn record(&self, event: SagaEvent) {
+        if let Some(journal) = self.journal.as_ref() {
+            journal(&event);
+        }
+    }
+
+    async fn compensate(&self, step: &SagaStep) -> Result<(), WorkerError> {
+        let mut attempt = 0;
+        loop {
+            match self.send(step.token, step.compensation.clone()).await {
+                Err(WorkerError::ReadOnlyViolation(violation)) => {
+                    return Err(WorkerError::ReadOnlyViolation(violation));
+                }
+                Err(_) if attempt < self.compensation_retries => attempt += 1,
+                res => return res,
+            }
+        }
+    }
+
+    async fn send(&self, token: i64, payload: Vec<u8>) -> Result<(), WorkerError> {
+        send_oneshot(token, payload, self.keyspace.clone())
+            .await
+            .and_then(|giveload| Decoder::try_from(giveload).map(|_| ()).map_err(WorkerError::Other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::access::stub::{replicas, void_result},
+        cql::ReadOnlyViolation,
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn query(statement: &str) -> Vec<u8> {
+        let Query(payload) = Query::new()
+            .statement(statement)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        payload
+    }
+
+    /// Build the saga of the steps, where each step applies `apply <name>` and is compensated by `undo <name>`,
+    /// along with its journal
+    fn saga(steps: &[&'static str]) -> (Saga, Arc<Mutex<Vec<String>>>) {
+        let journal = Arc::new(Mutex::new(Vec::new()));
+        let events = journal.clone();
+        let saga = steps
+            .iter()
+            .enumerate()
+            .fold(Saga::new("ks"), |saga, (token, name)| {
+                saga.step(
+                    *name,
+                    token as i64,
+                    query(&format!("apply {}", name)),
+                    query(&format!("undo {}", name)),
+                )
+            })
+            .journal(move |event| {
+                events.lock().unwrap().push(match event {
+                    SagaEvent::StepStarted { index, name } => format!("started {} {}", index, name),
+                    SagaEvent::StepApplied { index, name } => format!("applied {} {}", index, name),
+                    SagaEvent::StepFailed { index, name, .. } => format!("failed {} {}", index, name),
+                    SagaEvent::Compensated { index, name } => format!("compensated {} {}", index, name),
+                    SagaEvent::CompensationFailed { index, name, .. } => {
+                        format!("compensation failed {} {}", index, name)
+                    }
+                })
+            });
+        (saga, journal)
+    }
+
+    fn strings(strings: &[&str]) -> Vec<String> {
+        strings.iter().map(|string| string.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn every_step_applied() {
+        let statements = replicas(|_| Ok(void_result()));
+        let (saga, journal) = saga(&["a", "b", "c"]);
+        let report = saga.execute().await;
+        assert!(report.is_completed());
+        assert!(!report.is_rolled_back());
+        assert_eq!(report.applied, strings(&["a", "b", "c"]));
+        assert!(report.compensated.is_empty());
+        assert_eq!(*statements.lock().unwrap(), strings(&["apply a", "apply b", "apply c"]));
+        assert_eq!(
+            *journal.lock().unwrap(),
+            strings(&[
+                "started 0 a",
+                "applied 0 a",
+                "started 1 b",
+                "applied 1 b",
+                "started 2 c",
+                "applied 2 c"
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_step_compensates_the_applied_steps_in_reverse() {
+        let statements = replicas(|statement| match statement {
+            "apply c" => Err(WorkerError::Overload),
+            _ => Ok(void_result()),
+        });
+        let (saga, journal) = saga(&["a", "b", "c", "d"]);
+        let report = saga.execute().await;
+        assert!(!report.is_completed());
+        assert!(report.is_rolled_back());
+        assert_eq!(report.applied, strings(&["a", "b"]));
+        assert_eq!(report.failed.unwrap().0, "c");
+        assert_eq!(report.compensated, strings(&["b", "a"]));
+        // the failed step and the following ones are neither applied nor compensated
+        assert_eq!(
+            *statements.lock().unwrap(),
+            strings(&["apply a", "apply b", "apply c", "undo b", "undo a"])
+        );
+        assert_eq!(
+            *journal.lock().unwrap(),
+            strings(&[
+                "started 0 a",
+                "applied 0 a",
+                "started 1 b",
+                "applied 1 b",
+                "started 2 c",
+                "failed 2 c",
+                "compensated 1 b",
+                "compensated 0 a"
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_compensation_after_retries() {
+        let statements = replicas(|statement| match statement {
+            "apply b" | "undo a" => Err(WorkerError::Timeout),
+            _ => Ok(void_result()),
+        });
+        let (saga, journal) = saga(&["a", "b"]);
+        let report = saga.compensation_retries(2).execute().await;
+        assert!(!report.is_completed());
+        assert!(!report.is_rolled_back());
+        assert!(report.compensated.is_empty());
+        assert_eq!(report.compensation_failures.len(), 1);
+        assert_eq!(report.compensation_failures[0].0, "a");
+        // the compensation is attempted once, then retried twice
+        assert_eq!(
+            *statements.lock().unwrap(),
+            strings(&["apply a", "apply b", "undo a", "undo a", "undo a"])
+        );
+        assert_eq!(
+            journal.lock().unwrap()[3..],
+            strings(&["failed 1 b", "compensation failed 0 a"])[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn read_only_violation_isnt_retried() {
+        let statements = replicas(|statement| match statement {
+            "apply b" => Err(WorkerError::Overload),
+            "undo a" => Err(WorkerError::ReadOnlyViolation(ReadOnlyViolation {
+                statement: statement.to_string(),
+            })),
+            _ => Ok(void_result()),
+        });
+        let (saga, _journal) = saga(&["a", "b"]);
+        let report = saga.compensation_retries(5).execute().await;
+        assert!(!report.is_rolled_back());
+        assert_eq!(report.compensation_failures[0].0, "a");
+        assert_eq!(*statements.lock().unwrap(), strings(&["apply a", "apply b", "undo a"]));
+    }
+}
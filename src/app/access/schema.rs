@@ -0,0 +1,280 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{
+    statements::schema::{
+        normalize_type, ClusteringOrder, ColumnDefinition, CreateKeyspaceStatement, CreateTableStatement,
+        KeyspaceSchema, SchemaChange,
+    },
+    statements::schema_docs::{self, DocFormat},
+    statements::scylla_ext::ScyllaOptions,
+    ColumnValue, Constant, Iter, Row, Rows, Term,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+};
+
+/// A row of `system_schema.columns`
+type ColumnRow = (String, String, String, i32, String, String);
+
+/// Fetches the live schema of a keyspace out of the `system_schema` keyspace, and reconstructs its keyspace and
+/// table creation statements, ie to diff it against the schema declared by a `Model`.
+///
+/// Note: only the standard table options are reconstructed, ie the Scylla specific ones aren't.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::app::access::{Model, SchemaFetcher};
+///
+/// # async fn run<M: Model>(model: M) -> anyhow::Result<()> {
+/// for change in SchemaFetcher::new().diff(&model).await? {
+///     println!("schema drift: {}", change);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SchemaFetcher {
+    consistency: Consistency,
+}
+
+impl Default for SchemaFetcher {
+    fn default() -> Self {
+        Self {
+            consistency: Consistency::One,
+        }
+    }
+}
+
+impl SchemaFetcher {
+    /// Create a new schema fetcher
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the consistency of the schema queries
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Fetch the schema of the keyspace, or None if it doesn't exist
+    pub async fn keyspace(&self, keyspace: &str) -> anyhow::Result<Option<KeyspaceSchema>> {
+        let decoder = self
+            .query(
+                "SELECT durable_writes, replication FROM system_schema.keyspaces WHERE keyspace_name = ?",
+                keyspace,
+            )
+            .await?;
+        let (durable_writes, replication) = match Iter::<(bool, HashMap<String, String>)>::new(decoder)?.next() {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let replication: BTreeMap<String, String> = replication.into_iter().collect();
+        let keyspace_statement = CreateKeyspaceStatement {
+            if_not_exists: false,
+            name: Name::auto(keyspace),
+            replication: Term::Map(
+                replication
+                    .into_iter()
+                    .map(|(k, v)| (Term::Constant(Constant::String(k)), Term::Constant(Constant::String(v))))
+                    .collect(),
+            ),
+            durable_writes: Some(durable_writes),
+        };
+        // the columns of the materialized views are stored along the ones of the tables
+        let decoder = self
+            .query(
+                "SELECT table_name, bloom_filter_fp_chance, caching, comment, compaction, compression, \
+                 crc_check_chance, default_time_to_live, gc_grace_seconds, max_index_interval, \
+                 memtable_flush_period_in_ms, min_index_interval, speculative_retry \
+                 FROM system_schema.tables WHERE keyspace_name = ?",
+                keyspace,
+            )
+            .await?;
+        let mut table_options: HashMap<String, Vec<(String, Term)>> = Iter::<TableRow>::new(decoder)?
+            .map(|row| (row.table, row.options))
+            .collect();
+        let decoder = self
+            .query(
+                "SELECT table_name, column_name, kind, position, clustering_order, type \
+                 FROM system_schema.columns WHERE keyspace_name = ?",
+                keyspace,
+            )
+            .await?;
+        let mut columns: BTreeMap<String, Vec<ColumnRow>> = BTreeMap::new();
+        for row in Iter::<ColumnRow>::new(decoder)?.filter(|row| table_options.contains_key(&row.0)) {
+            columns.entry(row.0.clone()).or_default().push(row);
+        }
+        let tables = columns
+            .into_iter()
+            .map(|(table, columns)| {
+                let options = table_options.remove(&table).unwrap_or_default();
+                table_statement(keyspace, &table, columns, options)
+            })
+            .collect();
+        Ok(Some(KeyspaceSchema {
+            keyspace: keyspace_statement,
+            tables,
+        }))
+    }
+
+    /// Fetch the names of the user defined types of the keyspace
+    pub async fn types(&self, keyspace: &str) -> anyhow::Result<Vec<Name>> {
+        let decoder = self
+            .query(
+                "SELECT type_name FROM system_schema.types WHERE keyspace_name = ?",
+                keyspace,
+            )
+            .await?;
+        Ok(Iter::<(String,)>::new(decoder)?
+            .map(|(type_name,)| Name::auto(&type_name))
+            .collect())
+    }
+
+    /// Render the documentation of the live schema of the keyspace, or None if it doesn't exist
+    pub async fn docs(&self, keyspace: &str, format: DocFormat) -> anyhow::Result<Option<String>> {
+        Ok(self
+            .keyspace(keyspace)
+            .await?
+            .map(|schema| schema_docs::render(&schema, format)))
+    }
+
+    /// Get the changes from the schema declared by the model to the live one
+    pub async fn diff<M: Model>(&self, model: &M) -> anyhow::Result<Vec<SchemaChange>> {
+        let expected = KeyspaceSchema::from_statements(&model.schema_statements())?;
+        let actual = self.keyspace(model.name()).await?;
+        expected.diff(actual.as_ref())
+    }
+
+    /// Fetch the names of the keyspaces of the cluster. They aren't cached, as the cached results are only
+    /// invalidated by the schema changes of their own keyspace.
+    pub async fn keyspaces(&self) -> anyhow::Result<Vec<String>> {
+        let Query(payload) = Query::new()
+            .statement("SELECT keyspace_name FROM system_schema.keyspaces")
+            .consistency(self.consistency)
+            .build()?;
+        let giveload = self.send(payload).await?;
+        Ok(Iter::<(String,)>::new(Decoder::try_from(giveload)?)?
+            .map(|(keyspace,)| keyspace)
+            .collect())
+    }
+
+    async fn query(&self, statement: &str, keyspace: &str) -> anyhow::Result<Decoder> {
+        if let Some(giveload) = SystemQueryCache::get(statement, keyspace.as_bytes()) {
+            return Decoder::try_from(giveload);
+        }
+        let Query(payload) = Query::new()
+            .statement(statement)
+            .consistency(self.consistency)
+            .value(&keyspace)
+            .build()?;
+        let giveload = self.send(payload).await?;
+        SystemQueryCache::insert(statement, keyspace.as_bytes(), Some(keyspace), giveload.clone());
+        Decoder::try_from(giveload)
+    }
+
+    async fn send(&self, payload: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        // the schema tables are small, so any token works
+        Ok(send_oneshot(rand::random(), payload, String::new()).await?)
+    }
+}
+
+/// Reconstruct the table creation statement out of its `system_schema.columns` rows
+fn table_statement(
+    keyspace: &str,
+    table: &str,
+    mut rows: Vec<ColumnRow>,
+    options: Vec<(String, Term)>,
+) -> CreateTableStatement {
+    // the primary key columns come first, in their key order
+    rows.sort_by_key(|(_, column, kind, position, _, _)| {
+        let rank = match kind.as_str() {
+            "partition_key" => 0,
+            "clustering" => 1,
+            _ => 2,
+        };
+        (rank, *position, column.clone())
+    });
+    let mut partition_key = Vec::new();
+    let mut clustering_key = Vec::new();
+    let mut columns = Vec::with_capacity(rows.len());
+    for (_, column, kind, _, clustering_order, cql_type) in rows {
+        let name = Name::auto(&column);
+        match kind.as_str() {
+            "partition_key" => partition_key.push(name.clone()),
+            "clustering" if clustering_order.eq_ignore_ascii_case("desc") => {
+                clustering_key.push((name.clone(), ClusteringOrder::Desc))
+            }
+            "clustering" => clustering_key.push((name.clone(), ClusteringOrder::Asc)),
+            _ => {}
+        }
+        columns.push(ColumnDefinition {
+            name,
+            cql_type: normalize_type(&cql_type),
+            is_static: kind == "static",
+        });
+    }
+    CreateTableStatement {
+        if_not_exists: false,
+        keyspace: Some(Name::auto(keyspace)),
+        name: Name::auto(table),
+        columns,
+        partition_key,
+        clustering_key,
+        options: ScyllaOptions {
+            options,
+            ..Default::default()
+        },
+    }
+}
+
+/// A row of `system_schema.tables`, along with its standard options
+struct TableRow {
+    table: String,
+    options: Vec<(String, Term)>,
+}
+
+impl Row for TableRow {
+    fn try_decode_row<R: Rows + ColumnValue>(rows: &mut R) -> anyhow::Result<Self> {
+        fn string(value: String) -> Term {
+            Term::Constant(Constant::String(value))
+        }
+        fn map(value: BTreeMap<String, String>) -> Term {
+            Term::Map(value.into_iter().map(|(k, v)| (string(k), string(v))).collect())
+        }
+        fn float(value: f64) -> Term {
+            Term::Constant(Constant::Float(value))
+        }
+        fn int(value: i32) -> Term {
+            Term::Constant(Constant::Integer(value as i128))
+        }
+        let table = rows.column_value()?;
+        let options = vec![
+            ("bloom_filter_fp_chance", rows.column_value::<Option<f64>>()?.map(float)),
+            ("caching", rows.column_value::<Option<BTreeMap<_, _>>>()?.map(map)),
+            ("comment", rows.column_value::<Option<String>>()?.map(string)),
+            ("compaction", rows.column_value::<Option<BTreeMap<_, _>>>()?.map(map)),
+            ("compression", rows.column_value::<Option<BTreeMap<_, _>>>()?.map(map)),
+            ("crc_check_chance", rows.column_value::<Option<f64>>()?.map(float)),
+            ("default_time_to_live", rows.column_value::<Option<i32>>()?.map(int)),
+            ("gc_grace_seconds", rows.column_value::<Option<i32>>()?.map(int)),
+            ("max_index_interval", rows.column_value::<Option<i32>>()?.map(int)),
+            (
+                "memtable_flush_period_in_ms",
+                rows.column_value::<Option<i32>>()?.map(int),
+            ),
+            ("min_index_interval", rows.column_value::<Option<i32>>()?.map(int)),
+            ("speculative_retry", rows.column_value::<Option<String>>()?.map(string)),
+        ];
+        Ok(Self {
+            table,
+            options: options
+                .into_iter()
+                .filter_map(|(option, value)| value.map(|value| (option.to_string(), value)))
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::SchemaFetcher;
+use crate::cql::{statements::schema::KeyspaceSchema, Event};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+static SCHEMA_CACHE: OnceLock<Mutex<SchemaCacheState>> = OnceLock::new();
+static PRELOADED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Default)]
+struct SchemaCacheState {
+    /// The patterns of the preloaded and watched keyspaces
+    patterns: Vec<String>,
+    schemas: HashMap<String, Arc<KeyspaceSchema>>,
+}
+
+/// The driver-wide cache of the keyspace schemas, which are fetched by the `SchemaFetcher`.
+///
+/// Only the keyspaces which match the preload patterns are fetched once the ring is first built, and they're
+/// refreshed on their schema changes. The other keyspaces are fetched lazily on their first use, and evicted on
+/// their schema changes, so the clusters with hundreds of keyspaces aren't introspected as a whole.
+///
+/// The patterns match the keyspace names, where `*` matches any sequence of chars and `?` matches any char.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::app::access::SchemaCache;
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// SchemaCache::set_preload(&["shop", "tenant_*"]);
+/// // fetched on its first use, unless it was preloaded
+/// if let Some(schema) = SchemaCache::get("billing").await? {
+///     println!("{} tables", schema.tables.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct SchemaCache;
+
+impl SchemaCache {
+    fn state() -> &'static Mutex<SchemaCacheState> {
+        SCHEMA_CACHE.get_or_init(Default::default)
+    }
+    /// Set the patterns of the keyspaces which are preloaded and watched, none by default
+    pub fn set_preload<S: AsRef<str>>(patterns: &[S]) {
+        Self::state().lock().unwrap().patterns = patterns.iter().map(|pattern| pattern.as_ref().to_string()).collect();
+    }
+    /// Check if the keyspace matches the preload patterns
+    pub fn is_watched(keyspace: &str) -> bool {
+        Self::state()
+            .lock()
+            .unwrap()
+            .patterns
+            .iter()
+            .any(|pattern| matches_pattern(pattern, keyspace))
+    }
+    /// Get the schema of the keyspace, which is fetched if it's not cached yet, or None if it doesn't exist
+    pub async fn get(keyspace: &str) -> anyhow::Result<Option<Arc<KeyspaceSchema>>> {
+        if let Some(schema) = Self::cached(keyspace) {
+            return Ok(Some(schema));
+        }
+        Self::load(keyspace).await
+    }
+    /// Get the cached schema of the keyspace, without fetching it
+    pub fn cached(keyspace: &str) -> Option<Arc<KeyspaceSchema>> {
+        Self::state().lock().unwrap().schemas.get(keyspace).cloned()
+    }
+    /// Get the names of the cached keyspaces
+    pub fn keyspaces() -> Vec<String> {
+        let mut keyspaces: Vec<String> = Self::state().lock().unwrap().schemas.keys().cloned().collect();
+        keyspaces.sort();
+        keyspaces
+    }
+    /// Evict the schema of the keyspace, so it's fetched again on its next use
+    pub fn invalidate(keyspace: &str) -> bool {
+        Self::state().lock().unwrap().schemas.remove(keyspace).is_some()
+    }
+    /// Fetch the schemas of the keyspaces which match the preload patterns, returns the number of preloaded
+    /// keyspaces
+    pub async fn preload() -> anyhow::Result<usize> {
+        let fetcher = SchemaFetcher::new();
+        let keyspaces: Vec<String> = fetcher
+            .keyspaces()
+            .await?
+            .into_iter()
+            .filter(|keyspace| Self::is_watched(keyspace))
+            .collect();
+        let mut preloaded = 0;
+        for keyspace in keyspaces {
+            if Self::load(&keyspace).await?.is_some() {
+                preloaded += 1;
+            }
+        }
+        Ok(preloaded)
+    }
+    /// Fetch the schema of the keyspace, and cache it if it exists
+    async fn load(keyspace: &str) -> anyhow::Result<Option<Arc<KeyspaceSchema>>> {
+        let schema = SchemaFetcher::new().keyspace(keyspace).await?.map(Arc::new);
+        let mut state = Self::state().lock().unwrap();
+        match schema.as_ref() {
+            Some(schema) => state.schemas.insert(keyspace.to_string(), schema.clone()),
+            None => state.schemas.remove(keyspace),
+        };
+        Ok(schema)
+    }
+    /// Spawn the preload of the watched keyspaces, unless they were already preloaded
+    pub(crate) fn spawn_preload() {
+        if Self::state().lock().unwrap().patterns.is_empty() || PRELOADED.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        tokio::spawn(async {
+            match Self::preload().await {
+                Ok(preloaded) => log::info!("Preloaded the schemas of {} keyspaces", preloaded),
+                Err(e) => {
+                    log::warn!("Unable to preload the keyspace schemas: {}", e);
+                    // retry once the ring is rebuilt
+                    PRELOADED.store(false, Ordering::Relaxed);
+                }
+            }
+        });
+    }
+    /// Refresh the schema of the watched keyspace which is changed by the server event, or evict the schema of an
+    /// unwatched one
+    pub(crate) fn refresh_on(event: &Event) {
+        let keyspace = match event {
+            Event::SchemaChange { keyspace, .. } => keyspace.clone(),
+            _ => return,
+        };
+        if !Self::is_watched(&keyspace) {
+            Self::invalidate(&keyspace);
+            return;
+        }
+        tokio::spawn(async move {
+            if let Err(e) = Self::load(&keyspace).await {
+                log::warn!("Unable to refresh the schema of the {} keyspace: {}", keyspace, e);
+                Self::invalidate(&keyspace);
+            }
+        });
+    }
+}
+
+/// Check if the name matches the pattern, where `*` matches any sequence of chars and `?` matches any char
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    // the positions after the last star in the pattern and the name, to backtrack to
+    let (mut p, mut n, mut star) = (0, 0, None);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
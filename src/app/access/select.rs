@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
-use crate::cql::{QueryPagingState, QuerySerialConsistency};
+use crate::cql::{with_token_range, with_using_timeout, QueryPagingState, QuerySerialConsistency};
 
 /// Select query trait which creates a `SelectRequest`
 /// that can be sent to the `Ring`.
@@ -83,10 +83,15 @@ pub trait Select<K, V>: Keyspace + RowsDecoder<K, V> + ComputeToken<K> {
     /// for use when generating queries that should use
     /// the prepared statement.
     fn id(&self) -> [u8; 16] {
-        md5::compute(self.select_statement().as_bytes()).into()
+        Md5::statement_id(&self.select_statement())
     }
     /// Bind the cql values to the builder
     fn bind_values<T: Values>(builder: T, key: &K) -> T::Return;
+    /// Get the partition key columns of the selected table, which are restricted by the token range relations (see
+    /// `SelectBuilder::where_token_range`)
+    fn partition_key_columns(&self) -> &'static [&'static str] {
+        &[]
+    }
 }
 
 pub trait SelectRecommended<S: Select<K, V>, K, V>: QueryOrPrepared {
@@ -114,6 +119,12 @@ pub trait GetSelectRequest<S, K> {
     fn select_prepared<'a, V>(&'a self, key: &'a K) -> SelectBuilder<'a, S, K, V, QueryConsistency>
     where
         S: Select<K, V>;
+    /// Specifies the returned Value type for an upcoming paged select, which follows the paging state
+    fn select_pages<V>(&self, key: &K, page_size: i32) -> PagedIter<S, K, V>
+    where
+        S: 'static + Select<K, V>,
+        K: 'static + Send + Clone,
+        V: 'static + Send + Clone;
 }
 
 impl<S: Keyspace, K> GetSelectRequest<S, K> for S {
@@ -125,6 +136,9 @@ impl<S: Keyspace, K> GetSelectRequest<S, K> for S {
             _marker: PhantomData,
             keyspace: self,
             key,
+            token: None,
+            token_range: None,
+            using_timeout: None,
             builder: S::QueryOrPrepared::make(Query::new(), self),
         }
     }
@@ -136,6 +150,9 @@ impl<S: Keyspace, K> GetSelectRequest<S, K> for S {
             _marker: PhantomData,
             keyspace: self,
             key,
+            token: None,
+            token_range: None,
+            using_timeout: None,
             builder: <QueryStatement as SelectRecommended<S, K, V>>::make(Query::new(), self),
         }
     }
@@ -147,34 +164,107 @@ impl<S: Keyspace, K> GetSelectRequest<S, K> for S {
             _marker: PhantomData,
             keyspace: self,
             key,
+            token: None,
+            token_range: None,
+            using_timeout: None,
             builder: <PreparedStatement as SelectRecommended<S, K, V>>::make(Query::new(), self),
         }
     }
+    fn select_pages<V>(&self, key: &K, page_size: i32) -> PagedIter<S, K, V>
+    where
+        S: 'static + Select<K, V>,
+        K: 'static + Send + Clone,
+        V: 'static + Send + Clone,
+    {
+        PagedIter::new(self.clone(), key.clone(), page_size)
+    }
 }
 
 pub struct SelectBuilder<'a, S, K, V, Stage> {
     _marker: PhantomData<(&'a S, &'a K, &'a V)>,
     keyspace: &'a S,
     key: &'a K,
+    token: Option<i64>,
+    token_range: Option<(i64, i64)>,
+    using_timeout: Option<Duration>,
     builder: QueryBuilder<Stage>,
 }
 
 impl<'a, S: Select<K, V>, K, V> SelectBuilder<'a, S, K, V, QueryConsistency> {
+    /// Route the request by the token of the partition key components, serialized by the
+    /// `PartitionKeyEncoder`, instead of the keyspace `ComputeToken` implementation
+    pub fn partition_key<P: PartitionKey>(mut self, partition_key: &P) -> Self {
+        self.token = Some(partition_key.partition_token());
+        self
+    }
+    /// Restrict the select to the partitions whose token is in `(start, end]`, by prepending the
+    /// `token(pk) > ? AND token(pk) <= ?` relation over the `Select::partition_key_columns` to the WHERE clause of
+    /// the statement (see `with_token_range`).
+    ///
+    /// The request is sent as an unprepared query, routed by the end of the range, ie to the primary replica of
+    /// the range if it doesn't span several vnodes.
+    pub fn where_token_range(mut self, start: i64, end: i64) -> anyhow::Result<Self> {
+        self.token = Some(end);
+        self.token_range = Some((start, end));
+        self.rewrite_statement()
+    }
+    /// Set the Scylla server-side timeout of the select, by adding the `USING TIMEOUT` clause to the statement
+    /// (see `with_using_timeout`). The request is sent as an unprepared query.
+    pub fn using_timeout(mut self, timeout: Duration) -> anyhow::Result<Self> {
+        self.using_timeout = Some(timeout);
+        self.rewrite_statement()
+    }
+    /// Rewrite the statement with the token range relation and the timeout, as an unprepared query
+    fn rewrite_statement(mut self) -> anyhow::Result<Self> {
+        let mut statement = self.keyspace.statement().into_owned();
+        if self.token_range.is_some() {
+            statement = with_token_range(&statement, self.keyspace.partition_key_columns())?;
+        }
+        if let Some(timeout) = self.using_timeout {
+            statement = with_using_timeout(&statement, timeout)?;
+        }
+        self.builder = Query::new().statement(&statement);
+        Ok(self)
+    }
     pub fn consistency(self, consistency: Consistency) -> SelectBuilder<'a, S, K, V, QueryValues> {
+        let builder = self.builder.consistency(consistency);
+        // the token range relation precedes the other relations of the statement
+        let builder = match self.token_range {
+            Some((start, end)) => S::bind_values(builder.value(&start).value(&end), self.key),
+            None => S::bind_values(builder, self.key),
+        };
         SelectBuilder {
             _marker: self._marker,
             keyspace: self.keyspace,
             key: self.key,
-            builder: S::bind_values(self.builder.consistency(consistency), self.key),
+            token: self.token,
+            token_range: self.token_range,
+            using_timeout: self.using_timeout,
+            builder,
         }
     }
 }
+
+impl<'a, S: Select<K, V> + Table<K, V>, K, V> SelectBuilder<'a, S, K, V, QueryConsistency> {
+    /// Set the read consistency of the table (see `Table`)
+    pub fn default_consistency(self) -> SelectBuilder<'a, S, K, V, QueryValues> {
+        self.consistency(S::READ_CONSISTENCY)
+    }
+    /// Build the SelectRequest with the read consistency of the table
+    pub fn build(self) -> anyhow::Result<SelectRequest<S, K, V>> {
+        self.default_consistency().build()
+    }
+}
+
 impl<'a, S: Select<K, V>, K, V> SelectBuilder<'a, S, K, V, QueryValues> {
     pub fn page_size(self, page_size: i32) -> SelectBuilder<'a, S, K, V, QueryPagingState> {
         SelectBuilder {
             _marker: self._marker,
             keyspace: self.keyspace,
             key: self.key,
+            token: self.token,
+            token_range: self.token_range,
+            using_timeout: self.using_timeout,
             builder: self.builder.page_size(page_size),
         }
     }
@@ -184,6 +274,9 @@ impl<'a, S: Select<K, V>, K, V> SelectBuilder<'a, S, K, V, QueryValues> {
             _marker: self._marker,
             keyspace: self.keyspace,
             key: self.key,
+            token: self.token,
+            token_range: self.token_range,
+            using_timeout: self.using_timeout,
             builder: self.builder.paging_state(paging_state),
         }
     }
@@ -192,23 +285,28 @@ impl<'a, S: Select<K, V>, K, V> SelectBuilder<'a, S, K, V, QueryValues> {
             _marker: self._marker,
             keyspace: self.keyspace,
             key: self.key,
+            token: self.token,
+            token_range: self.token_range,
+            using_timeout: self.using_timeout,
             builder: self.builder.timestamp(timestamp),
         }
     }
     /// Build the SelectRequest
     pub fn build(self) -> anyhow::Result<SelectRequest<S, K, V>> {
+        let token = self.token.unwrap_or_else(|| S::token(self.key));
         let query = self.builder.build()?;
         // create the request
-        Ok(self.keyspace.create_request(query, S::token(self.key)))
+        Ok(self.keyspace.create_request(query, token))
     }
 }
 
 impl<'a, S: Select<K, V>, K, V> SelectBuilder<'a, S, K, V, QueryBuild> {
     /// Build the InsertRequest
     pub fn build(self) -> anyhow::Result<SelectRequest<S, K, V>> {
+        let token = self.token.unwrap_or_else(|| S::token(self.key));
         let query = self.builder.build()?;
         // create the request
-        Ok(self.keyspace.create_request(query, S::token(self.key)))
+        Ok(self.keyspace.create_request(query, token))
     }
 }
 
@@ -219,6 +317,9 @@ impl<'a, S: Select<K, V>, K, V> SelectBuilder<'a, S, K, V, QueryPagingState> {
             _marker: self._marker,
             keyspace: self.keyspace,
             key: self.key,
+            token: self.token,
+            token_range: self.token_range,
+            using_timeout: self.using_timeout,
             builder: self.builder.paging_state(paging_state),
         }
     }
@@ -229,14 +330,18 @@ impl<'a, S: Select<K, V>, K, V> SelectBuilder<'a, S, K, V, QueryPagingState> {
             _marker: self._marker,
             keyspace: self.keyspace,
             key: self.key,
+            token: self.token,
+            token_range: self.token_range,
+            using_timeout: self.using_timeout,
             builder: self.builder.timestamp(timestamp),
         }
     }
 
     pub fn build(self) -> anyhow::Result<SelectRequest<S, K, V>> {
+        let token = self.token.unwrap_or_else(|| S::token(self.key));
         let query = self.builder.build()?;
         // create the request
-        Ok(self.keyspace.create_request(query, S::token(self.key)))
+        Ok(self.keyspace.create_request(query, token))
     }
 }
 impl<'a, S: Select<K, V>, K, V> SelectBuilder<'a, S, K, V, QuerySerialConsistency> {
@@ -246,14 +351,18 @@ impl<'a, S: Select<K, V>, K, V> SelectBuilder<'a, S, K, V, QuerySerialConsistenc
             _marker: self._marker,
             keyspace: self.keyspace,
             key: self.key,
+            token: self.token,
+            token_range: self.token_range,
+            using_timeout: self.using_timeout,
             builder: self.builder.timestamp(timestamp),
         }
     }
 
     pub fn build(self) -> anyhow::Result<SelectRequest<S, K, V>> {
+        let token = self.token.unwrap_or_else(|| S::token(self.key));
         let query = self.builder.build()?;
         // create the request
-        Ok(self.keyspace.create_request(query, S::token(self.key)))
+        Ok(self.keyspace.create_request(query, token))
     }
 }
 
@@ -291,6 +400,7 @@ impl<S: Keyspace> GetSelectStatement<S> for S {
 pub struct SelectRequest<S, K, V> {
     token: i64,
     inner: Vec<u8>,
+    timeout: Option<Duration>,
     keyspace: S,
     _marker: PhantomData<(S, K, V)>,
 }
@@ -301,6 +411,7 @@ impl<K, V, S: Select<K, V> + Clone> CreateRequest<SelectRequest<S, K, V>> for S
         SelectRequest::<S, K, V> {
             token,
             inner: query.into(),
+            timeout: None,
             keyspace: self.clone(),
             _marker: PhantomData,
         }
@@ -328,12 +439,28 @@ impl<S: Select<K, V>, K, V> SelectRequest<S, K, V> {
     pub fn result_decoder(&self) -> DecodeResult<DecodeRows<S, K, V>> {
         DecodeResult::select()
     }
+    /// Set the client-side timeout of the request, after which its worker is resolved with `WorkerError::Timeout`,
+    /// or None to await its response until the connection is lost
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Decode the query frame of the request, apply the function to it (ie to adjust its consistency or page size),
+    /// then encode it again, so the middlewares don't have to rebuild the request from scratch
+    pub fn map_frame<F: FnOnce(&mut QueryFrame)>(mut self, f: F) -> anyhow::Result<Self> {
+        let mut frame = QueryFrame::decode(&self.inner)?;
+        f(&mut frame);
+        self.inner = frame.encode()?;
+        Ok(self)
+    }
+
     /// Send a local request using the keyspace impl and return a type marker
     pub fn send_local(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeRows<S, K, V>> {
         send_local(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Select, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::select()
@@ -344,12 +471,27 @@ impl<S: Select<K, V>, K, V> SelectRequest<S, K, V> {
         send_global(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Select, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::select()
     }
 
+    /// Send a request routed by the provided load balancing policy and return a type marker
+    pub fn send_with(
+        self,
+        policy: &dyn LoadBalancingPolicy,
+        worker: Box<dyn Worker>,
+    ) -> DecodeResult<DecodeRows<S, K, V>> {
+        send_with(
+            policy,
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Select, self.timeout),
+        );
+        DecodeResult::select()
+    }
+
     /// Consume the request to retrieve the payload
     pub fn into_payload(self) -> Vec<u8> {
         self.inner
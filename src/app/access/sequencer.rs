@@ -0,0 +1,275 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{ColumnEncoder, CqlError, ErrorCodes, Frame, Iter, Rows, TokenEncoder};
+use anyhow::{anyhow, bail};
+use std::{convert::TryFrom, time::Duration};
+
+/// Allocates monotonic sequence values per partition using lightweight transactions.
+///
+/// Each call to `next` performs a serial read of the current value followed by a
+/// conditional update (`UPDATE .. SET seq = ? WHERE pk = ? IF seq = ?`, or
+/// `INSERT .. IF NOT EXISTS` for a fresh partition), retrying with exponential backoff
+/// when the condition doesn't apply due to contention.
+///
+/// The sequence table is expected to look like:
+/// `CREATE TABLE ks.sequences (pk <type> PRIMARY KEY, seq bigint)`.
+///
+/// Allocated values are never handed out twice, however a value can be skipped when the
+/// outcome of a conditional update is unknown (ie the write timed out).
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::app::access::Sequencer;
+/// # async fn run() -> anyhow::Result<()> {
+/// let sequencer = Sequencer::new("my_keyspace", "sequences", "name", "seq").max_retries(10);
+/// let value = sequencer.next(&"orders".to_string()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sequencer {
+    keyspace: Cow<'static, str>,
+    table: Cow<'static, str>,
+    partition_column: Cow<'static, str>,
+    sequence_column: Cow<'static, str>,
+    max_retries: usize,
+    backoff: Duration,
+}
+
+impl Sequencer {
+    /// Create a new sequencer over the provided table and columns
+    pub fn new<T: Into<Cow<'static, str>>>(keyspace: T, table: T, partition_column: T, sequence_column: T) -> Self {
+        Self {
+            keyspace: keyspace.into(),
+            table: table.into(),
+            partition_column: partition_column.into(),
+            sequence_column: sequence_column.into(),
+            max_retries: 5,
+            backoff: Duration::from_millis(10),
+        }
+    }
+
+    /// Set the maximum number of retries when the conditional update is not applied
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the base backoff duration, which doubles after each failed attempt
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Allocate the next sequence value of the partition
+    pub async fn next<P: ColumnEncoder>(&self, partition: &P) -> anyhow::Result<i64> {
+        let token = partition.get_token();
+        let mut current = self.read(partition, token).await?;
+        for attempt in 0..=self.max_retries {
+            let next = current.unwrap_or_default() + 1;
+            let query = match current {
+                Some(current) => Query::new()
+                    .statement(&self.update_statement())
+                    .consistency(Consistency::Quorum)
+                    .value(&next)
+                    .value(partition)
+                    .value(&current)
+                    .serial_consistency(Consistency::Serial)
+                    .build()?,
+                None => Query::new()
+                    .statement(&self.insert_statement())
+                    .consistency(Consistency::Quorum)
+                    .value(partition)
+                    .value(&next)
+                    .serial_consistency(Consistency::Serial)
+                    .build()?,
+            };
+            match self.send(token, query.0).await {
+                Ok(decoder) => match Self::decode_applied(decoder, current.is_some())? {
+                    (true, _) => return Ok(next),
+                    // the update returns the current value when the condition doesn't apply
+                    (false, Some(existing)) => current = Some(existing),
+                    (false, None) => current = self.read(partition, token).await?,
+                },
+                // the outcome of the conditional update is unknown, therefore we re-read the sequence
+                Err(WorkerError::Cql(CqlError {
+                    code: ErrorCodes::WriteTimeout,
+                    ..
+                })) => current = self.read(partition, token).await?,
+                Err(e) => bail!(e),
+            }
+            if attempt < self.max_retries {
+                tokio::time::sleep(self.backoff * 2u32.pow(attempt.min(16) as u32) + self.jitter()).await;
+            }
+        }
+        bail!(
+            "Unable to allocate sequence value from {}.{} after {} retries",
+            self.keyspace,
+            self.table,
+            self.max_retries
+        )
+    }
+
+    fn select_statement(&self) -> String {
+        format!(
+            "SELECT {} FROM {} WHERE {} = ?",
+            Name::auto(&self.sequence_column),
+            Name::qualify(&self.keyspace, &self.table),
+            Name::auto(&self.partition_column)
+        )
+    }
+
+    fn insert_statement(&self) -> String {
+        format!(
+            "INSERT INTO {} ({}, {}) VALUES (?, ?) IF NOT EXISTS",
+            Name::qualify(&self.keyspace, &self.table),
+            Name::auto(&self.partition_column),
+            Name::auto(&self.sequence_column)
+        )
+    }
+
+    fn update_statement(&self) -> String {
+        let sequence_column = Name::auto(&self.sequence_column);
+        format!(
+            "UPDATE {} SET {} = ? WHERE {} = ? IF {} = ?",
+            Name::qualify(&self.keyspace, &self.table),
+            sequence_column,
+            Name::auto(&self.partition_column),
+            sequence_column
+        )
+    }
+
+    fn jitter(&self) -> Duration {
+        let max = self.backoff.as_micros().max(1) as u64;
+        Duration::from_micros(rand::random::<u64>() % max)
+    }
+
+    async fn read<P: ColumnEncoder>(&self, partition: &P, token: i64) -> anyhow::Result<Option<i64>> {
+        let Query(payload) = Query::new()
+            .statement(&self.select_statement())
+            .consistency(Consistency::Serial)
+            .value(partition)
+            .build()?;
+        let decoder = self.send(token, payload).await?;
+        Ok(Iter::<Option<i64>>::new(decoder)?.next().flatten())
+    }
+
+    /// Decode the `[applied]` column, and the current sequence value when returned by the update
+    fn decode_applied(decoder: Decoder, is_update: bool) -> anyhow::Result<(bool, Option<i64>)> {
+        if is_update && decoder.columns_count()? > 1 {
+            Iter::<(bool, Option<i64>)>::new(decoder)?.next()
+        } else {
+            Iter::<bool>::new(decoder)?.next().map(|applied| (applied, None))
+        }
+        .ok_or_else(|| anyhow!("Expected the [applied] row in the conditional update response"))
+    }
+
+    async fn send(&self, token: i64, payload: Vec<u8>) -> Result<Decoder, WorkerError> {
+        send_oneshot(token, payload, self.keyspace.to_string())
+            .await
+            .and_then(|giveload| Decoder::try_from(giveload).map_err(WorkerError::Other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::access::stub::{replicas, rows_result};
+
+    const SELECT: &str = "SELECT seq FROM ks.sequences WHERE pk = ?";
+    const INSERT: &str = "INSERT INTO ks.sequences (pk, seq) VALUES (?, ?) IF NOT EXISTS";
+    const UPDATE: &str = "UPDATE ks.sequences SET seq = ? WHERE pk = ? IF seq = ?";
+
+    fn sequencer() -> Sequencer {
+        Sequencer::new("ks", "sequences", "pk", "seq")
+            .max_retries(2)
+            .backoff(Duration::from_millis(1))
+    }
+
+    /// The rows of the sequence read
+    fn sequence(value: Option<i64>) -> Vec<u8> {
+        let rows: Vec<_> = value
+            .map(|value| vec![Some(value.to_be_bytes().to_vec())])
+            .into_iter()
+            .collect();
+        rows_result(&[("seq", 0x0002)], &rows)
+    }
+
+    /// The row of the conditional update, along with the current value when it isn't applied
+    fn applied(applied: bool, current: Option<i64>) -> Vec<u8> {
+        let mut columns = vec![("[applied]", 0x0004)];
+        let mut row = vec![Some(vec![applied as u8])];
+        if let Some(current) = current {
+            columns.push(("seq", 0x0002));
+            row.push(Some(current.to_be_bytes().to_vec()));
+        }
+        rows_result(&columns, &[row])
+    }
+
+    #[test]
+    fn statements() {
+        let sequencer = sequencer();
+        assert_eq!(sequencer.select_statement(), SELECT);
+        assert_eq!(sequencer.insert_statement(), INSERT);
+        assert_eq!(sequencer.update_statement(), UPDATE);
+    }
+
+    #[tokio::test]
+    async fn allocate_when_the_cas_applies() {
+        let mut current = None;
+        let statements = replicas(move |statement| {
+            Ok(match statement {
+                SELECT => sequence(current),
+                _ => {
+                    current = Some(current.unwrap_or_default() + 1);
+                    applied(true, None)
+                }
+            })
+        });
+        let sequencer = sequencer();
+        // the fresh partition is inserted, then updated
+        assert_eq!(sequencer.next(&"orders").await.unwrap(), 1);
+        assert_eq!(sequencer.next(&"orders").await.unwrap(), 2);
+        assert_eq!(*statements.lock().unwrap(), vec![SELECT, INSERT, SELECT, UPDATE]);
+    }
+
+    #[tokio::test]
+    async fn retry_the_lost_cas_with_the_current_value() {
+        let mut updates = 0;
+        let statements = replicas(move |statement| {
+            Ok(match statement {
+                SELECT => sequence(Some(5)),
+                _ => {
+                    updates += 1;
+                    match updates {
+                        // another allocator won the race, and the update returns the current value
+                        1 => applied(false, Some(7)),
+                        _ => applied(true, None),
+                    }
+                }
+            })
+        });
+        // the update is retried from the returned value, without reading it again
+        assert_eq!(sequencer().next(&"orders").await.unwrap(), 8);
+        assert_eq!(*statements.lock().unwrap(), vec![SELECT, UPDATE, UPDATE]);
+    }
+
+    #[tokio::test]
+    async fn give_up_after_the_retry_limit() {
+        let mut current = 5;
+        let statements = replicas(move |statement| {
+            Ok(match statement {
+                SELECT => sequence(Some(current)),
+                _ => {
+                    current += 1;
+                    applied(false, Some(current))
+                }
+            })
+        });
+        assert!(sequencer().next(&"orders").await.is_err());
+        // the first attempt along with the two retries
+        assert_eq!(*statements.lock().unwrap(), vec![SELECT, UPDATE, UPDATE, UPDATE]);
+    }
+}
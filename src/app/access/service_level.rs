@@ -0,0 +1,79 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::statements::service_level::{ServiceLevelOptions, ServiceLevelStatement};
+use std::convert::TryFrom;
+
+/// Execute the service level statement through the current driver sessions, returning its response, ie the rows
+/// of the `LIST .. SERVICE LEVEL(S)` statements.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::{
+///     app::access::execute_service_level_statement,
+///     cql::statements::service_level::ServiceLevelStatement,
+/// };
+/// # async fn run() -> anyhow::Result<()> {
+/// let statement = ServiceLevelStatement::parse("LIST ALL SERVICE LEVELS")?.unwrap();
+/// let decoder = execute_service_level_statement(&statement).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn execute_service_level_statement(statement: &ServiceLevelStatement) -> Result<Decoder, WorkerError> {
+    let Query(payload) = Query::new()
+        .statement(&statement.to_string())
+        .consistency(Consistency::Quorum)
+        .build()
+        .map_err(WorkerError::Other)?;
+    // service level statements aren't bound to a partition, so any token works
+    send_oneshot(rand::random(), payload, "system_distributed".to_string())
+        .await
+        .and_then(|giveload| Decoder::try_from(giveload).map_err(WorkerError::Other))
+}
+
+/// Create the service level if it doesn't exist yet
+pub async fn create_service_level(name: &str, options: ServiceLevelOptions) -> Result<(), WorkerError> {
+    execute(ServiceLevelStatement::Create {
+        if_not_exists: true,
+        name: Name::auto(name),
+        options,
+    })
+    .await
+}
+
+/// Alter the options of the service level
+pub async fn alter_service_level(name: &str, options: ServiceLevelOptions) -> Result<(), WorkerError> {
+    execute(ServiceLevelStatement::Alter {
+        name: Name::auto(name),
+        options,
+    })
+    .await
+}
+
+/// Drop the service level if it exists
+pub async fn drop_service_level(name: &str) -> Result<(), WorkerError> {
+    execute(ServiceLevelStatement::Drop {
+        if_exists: true,
+        name: Name::auto(name),
+    })
+    .await
+}
+
+/// Attach the service level to the role, so the workload of the role is prioritized by it
+pub async fn attach_service_level(name: &str, role: &str) -> Result<(), WorkerError> {
+    execute(ServiceLevelStatement::Attach {
+        name: Name::auto(name),
+        role: Name::auto(role),
+    })
+    .await
+}
+
+/// Detach the service level of the role
+pub async fn detach_service_level(role: &str) -> Result<(), WorkerError> {
+    execute(ServiceLevelStatement::Detach { role: Name::auto(role) }).await
+}
+
+async fn execute(statement: ServiceLevelStatement) -> Result<(), WorkerError> {
+    execute_service_level_statement(&statement).await.map(|_| ())
+}
@@ -0,0 +1,343 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::{BatchStatementOrId, BatchTypeUnlogged, BatchValues, CqlError, ErrorCodes};
+use anyhow::{anyhow, bail};
+use std::{collections::HashMap, convert::TryFrom, future::Future};
+
+/// The destination shard of a sub-batch, or None if the ring isn't built yet
+type Destination = Option<SocketAddr>;
+
+/// An unlogged batch collector
+type Collector<S, Stage> = BatchCollector<S, BatchTypeUnlogged, Stage>;
+
+/// A sub-batch collector along with the token of its first partition
+type SubBatch<S> = (i64, Collector<S, BatchValues>);
+
+/// An unlogged batch collector which splits the statements by destination shard, ie by the shard which owns
+/// their partition on its primary replica in the local datacenter.
+///
+/// Each sub-batch only spans a single shard, so its coordinator applies it without forwarding the mutations to
+/// other nodes or shards, which performs much better on Scylla than a multi partition batch sent to a random
+/// coordinator. The sub-batches are sent concurrently, and retain the order of their statements.
+///
+/// Note: the statements of a destination are applied atomically with each other only, as with any unlogged batch.
+///
+/// ## Example
+/// ```no_run
+/// # use scylla_rs::app::access::tests::MyKeyspace;
+/// use scylla_rs::{app::access::Batchable, cql::Consistency};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// # let keyspace = MyKeyspace::new();
+/// keyspace
+///     .shard_aware_batch()
+///     .insert(&1, &1.0)
+///     .insert(&2, &2.0)
+///     .delete::<_, f32>(&3)
+///     .consistency(Consistency::One)
+///     .build()?
+///     .send()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ShardAwareBatch<S> {
+    keyspace: S,
+    consistency: Consistency,
+    destinations: Vec<Destination>,
+    batches: HashMap<Destination, SubBatch<S>>,
+}
+
+impl<S: Keyspace + Clone> ShardAwareBatch<S> {
+    /// Create a new shard aware batch collector for the keyspace
+    pub fn new(keyspace: &S) -> Self {
+        Self {
+            keyspace: keyspace.clone(),
+            consistency: Consistency::One,
+            destinations: Vec::new(),
+            batches: HashMap::new(),
+        }
+    }
+
+    /// Append an insert query using the default query type defined in the `Insert` impl
+    pub fn insert<K, V>(self, key: &K, value: &V) -> Self
+    where
+        S: 'static + Insert<K, V> + ComputeToken<K>,
+        K: 'static + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        self.append(
+            S::token(key),
+            |batch| batch.insert(key, value),
+            |batch| batch.insert(key, value),
+        )
+    }
+
+    /// Append an update query using the default query type defined in the `Update` impl
+    pub fn update<K, V>(self, key: &K, value: &V) -> Self
+    where
+        S: 'static + Update<K, V> + ComputeToken<K>,
+        K: 'static + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        self.append(
+            S::token(key),
+            |batch| batch.update(key, value),
+            |batch| batch.update(key, value),
+        )
+    }
+
+    /// Append a delete query using the default query type defined in the `Delete` impl
+    pub fn delete<K, V>(self, key: &K) -> Self
+    where
+        S: 'static + Delete<K, V> + ComputeToken<K>,
+        K: 'static + Clone + Send,
+        V: 'static + Clone + Send,
+    {
+        self.append(
+            S::token(key),
+            |batch| batch.delete::<K, V>(key),
+            |batch| batch.delete::<K, V>(key),
+        )
+    }
+
+    /// Set the consistency of the sub-batches
+    pub fn consistency(mut self, consistency: Consistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Get the number of sub-batches, ie of distinct destination shards
+    pub fn len(&self) -> usize {
+        self.destinations.len()
+    }
+
+    /// Check if the batch has no statements
+    pub fn is_empty(&self) -> bool {
+        self.destinations.is_empty()
+    }
+
+    /// Build the sub-batch requests, which are routed by the token of their first partition
+    pub fn build(mut self) -> anyhow::Result<ShardAwareBatchRequest<S>> {
+        let mut batches = Vec::with_capacity(self.destinations.len());
+        for destination in self.destinations {
+            let (token, batch) = self
+                .batches
+                .remove(&destination)
+                .ok_or_else(|| anyhow!("Missing the sub-batch of {:?}", destination))?;
+            batches.push((
+                destination,
+                batch.consistency(self.consistency).build()?.with_token(token),
+            ));
+        }
+        Ok(ShardAwareBatchRequest {
+            keyspace: self.keyspace,
+            batches,
+        })
+    }
+
+    fn append(
+        mut self,
+        token: i64,
+        first: impl FnOnce(Collector<S, BatchStatementOrId>) -> Collector<S, BatchValues>,
+        next: impl FnOnce(Collector<S, BatchValues>) -> Collector<S, BatchValues>,
+    ) -> Self {
+        let destination = Ring::local_shard(0, token);
+        let sub_batch = match self.batches.remove(&destination) {
+            Some((first_token, batch)) => (first_token, next(batch)),
+            None => {
+                self.destinations.push(destination);
+                (token, first(self.keyspace.batch().unlogged()))
+            }
+        };
+        self.batches.insert(destination, sub_batch);
+        self
+    }
+}
+
+/// The sub-batch requests of a `ShardAwareBatch`, along with their destination shards
+pub struct ShardAwareBatchRequest<S> {
+    keyspace: S,
+    batches: Vec<(Destination, BatchRequest<S>)>,
+}
+
+impl<S: 'static + Keyspace + Clone> ShardAwareBatchRequest<S> {
+    /// Get the sub-batch requests along with their destination shards (where the port is the shard id)
+    pub fn batches(&self) -> &Vec<(Option<SocketAddr>, BatchRequest<S>)> {
+        &self.batches
+    }
+
+    /// Take the sub-batch requests, ie to send them with custom workers
+    pub fn into_batches(self) -> Vec<(Option<SocketAddr>, BatchRequest<S>)> {
+        self.batches
+    }
+
+    /// Send the sub-batches concurrently to their destination shards, and await all of them. The sub-batches
+    /// which reference unprepared statements are re-prepared and sent once more.
+    ///
+    /// Returns an error which lists the failed sub-batches, if any, while the other ones are applied.
+    pub async fn send(self) -> anyhow::Result<()> {
+        let keyspace = self.keyspace.name().clone().into_owned();
+        let count = self.batches.len();
+        let pending = self
+            .batches
+            .into_iter()
+            .map(|(destination, batch)| {
+                let response = send_one(&batch, &keyspace);
+                (destination, batch, response)
+            })
+            .collect::<Vec<_>>();
+        let mut failures = Vec::new();
+        for (destination, batch, response) in pending {
+            let mut res = response.await;
+            if let Err(WorkerError::Cql(CqlError {
+                code: ErrorCodes::Unprepared,
+                ..
+            })) = res
+            {
+                res = match batch.reprepare_all(&None).await {
+                    Ok(batch) => send_one(&batch, &keyspace).await,
+                    Err(e) => Err(WorkerError::Other(e)),
+                };
+            }
+            if let Err(e) = res {
+                failures.push(format!("{:?}: {}", destination, e));
+            }
+        }
+        if !failures.is_empty() {
+            bail!(
+                "{} of {} sub-batches failed: {}",
+                failures.len(),
+                count,
+                failures.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Send the sub-batch to its destination shard, and return the future of its response
+pub(crate) fn send_one<S: Keyspace>(
+    batch: &BatchRequest<S>,
+    keyspace: &str,
+) -> impl Future<Output = Result<(), WorkerError>> {
+    let (worker, rx) = OneshotWorker::boxed();
+    send_local_primary(batch.token(), batch.payload().clone(), worker, keyspace.to_string());
+    async move {
+        rx.await
+            .unwrap_or(Err(WorkerError::Lost))
+            .and_then(|giveload| Decoder::try_from(giveload).map(|_| ()).map_err(WorkerError::Other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::access::stub::request_statement;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const INSERT: &str = "INSERT INTO ks.events (id, value) VALUES (?, ?)";
+    const DELETE: &str = "DELETE FROM ks.events WHERE id = ?";
+
+    /// The token of the second shard, on a node with two shards
+    const SECOND_SHARD: i64 = 1 << 51;
+
+    #[derive(Clone)]
+    struct Events {
+        name: Cow<'static, str>,
+    }
+
+    impl Keyspace for Events {
+        fn name(&self) -> &Cow<'static, str> {
+            &self.name
+        }
+    }
+
+    impl VoidDecoder for Events {}
+
+    impl ComputeToken<i64> for Events {
+        fn token(key: &i64) -> i64 {
+            *key
+        }
+    }
+
+    impl Insert<i64, i32> for Events {
+        type QueryOrPrepared = QueryStatement;
+        fn statement(&self) -> Cow<'static, str> {
+            INSERT.into()
+        }
+        fn bind_values<T: Values>(builder: T, key: &i64, value: &i32) -> T::Return {
+            builder.value(key).value(value)
+        }
+    }
+
+    impl Delete<i64, i32> for Events {
+        type QueryOrPrepared = QueryStatement;
+        fn statement(&self) -> Cow<'static, str> {
+            DELETE.into()
+        }
+        fn bind_values<T: Values>(builder: T, key: &i64) -> T::Return {
+            builder.value(key)
+        }
+    }
+
+    fn events() -> Events {
+        Ring::detach_node(IpAddr::V4(Ipv4Addr::LOCALHOST), 2, 1);
+        Events { name: "ks".into() }
+    }
+
+    fn shard(shard: u16) -> Option<SocketAddr> {
+        Some(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), shard))
+    }
+
+    #[test]
+    fn group_by_shard() {
+        let events = events();
+        let batch = ShardAwareBatch::new(&events)
+            .insert(&0, &0)
+            .insert(&SECOND_SHARD, &1)
+            .insert(&2, &2)
+            .insert(&(SECOND_SHARD + 3), &3);
+        assert_eq!(batch.len(), 2);
+        let batches = batch.build().unwrap();
+        let destinations: Vec<_> = batches.batches().iter().map(|(destination, _)| *destination).collect();
+        // the sub-batches are ordered by the first statement of their shard
+        assert_eq!(destinations, vec![shard(0), shard(1)]);
+        // and routed by the token of their first partition
+        let tokens: Vec<_> = batches.batches().iter().map(|(_, batch)| batch.token()).collect();
+        assert_eq!(tokens, vec![0, SECOND_SHARD]);
+    }
+
+    #[test]
+    fn keep_the_statements_order() {
+        let events = events();
+        let batches = ShardAwareBatch::new(&events)
+            .insert(&0, &0)
+            .delete::<_, i32>(&SECOND_SHARD)
+            .delete::<_, i32>(&1)
+            .insert(&(SECOND_SHARD + 1), &1)
+            .insert(&2, &2)
+            .build()
+            .unwrap();
+        let statements: Vec<_> = batches
+            .batches()
+            .iter()
+            .map(|(_, batch)| request_statement(batch.payload()))
+            .collect();
+        assert_eq!(
+            statements,
+            vec![[INSERT, DELETE, INSERT].join("; "), [DELETE, INSERT].join("; "),]
+        );
+    }
+
+    #[test]
+    fn empty_batch() {
+        let events = events();
+        let batch = ShardAwareBatch::new(&events);
+        assert!(batch.is_empty());
+        assert_eq!(batch.len(), 0);
+        assert!(batch.build().unwrap().into_batches().is_empty());
+    }
+}
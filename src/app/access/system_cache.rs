@@ -0,0 +1,60 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::cql::{query_cache::QueryCache, Event, QueryCacheStats, SystemTable};
+use std::{
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+static SYSTEM_QUERY_CACHE: OnceLock<Mutex<QueryCache>> = OnceLock::new();
+
+/// The driver-wide cache of the system queries results, which are repeated frequently by the driver subsystems
+/// (ie the schema lookups of the `SchemaFetcher`), so they don't hammer the system tables of busy clusters.
+///
+/// The results expire after the TTL of their system table, and they are invalidated by the server events, ie the
+/// schema change of their keyspace.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::{app::access::SystemQueryCache, cql::SystemTable};
+/// use std::time::Duration;
+///
+/// SystemQueryCache::set_ttl(SystemTable::Schema, Duration::from_secs(10));
+/// // disable the caching of the size estimates
+/// SystemQueryCache::set_ttl(SystemTable::SizeEstimates, Duration::from_secs(0));
+/// ```
+pub struct SystemQueryCache;
+
+impl SystemQueryCache {
+    fn cache() -> &'static Mutex<QueryCache> {
+        SYSTEM_QUERY_CACHE.get_or_init(Default::default)
+    }
+    /// Set the TTL of the cached results of the system table, where a zero TTL disables their caching
+    pub fn set_ttl(table: SystemTable, ttl: Duration) {
+        Self::cache().lock().unwrap().set_ttl(table, ttl);
+    }
+    /// Invalidate the cached results of the system table, which belong to the keyspace if any
+    pub fn invalidate(table: SystemTable, keyspace: Option<&str>) -> usize {
+        Self::cache().lock().unwrap().invalidate(table, keyspace)
+    }
+    /// Get the metrics of the cache
+    pub fn stats() -> QueryCacheStats {
+        Self::cache().lock().unwrap().stats()
+    }
+    /// Get the live result of the system query
+    pub(crate) fn get(statement: &str, values: &[u8]) -> Option<Vec<u8>> {
+        Self::cache().lock().unwrap().get(statement, values, Instant::now())
+    }
+    /// Cache the result of the system query, which belongs to the keyspace if any
+    pub(crate) fn insert(statement: &str, values: &[u8], keyspace: Option<&str>, payload: Vec<u8>) {
+        Self::cache()
+            .lock()
+            .unwrap()
+            .insert(statement, values, keyspace, payload, Instant::now());
+    }
+    /// Invalidate the cached results which are outdated by the server event
+    pub(crate) fn invalidate_on(event: &Event) {
+        Self::cache().lock().unwrap().invalidate_on(event);
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+/// Declares the request defaults of a table, keyed by its Key / Value pair, which keeps the policies close to
+/// the schema definition.
+///
+/// The select, insert, update and delete builders apply the consistency defaults when they're built without an
+/// explicit consistency, or with `default_consistency()`, while the default TTL has to be part of the insert and
+/// update statements (see `Table::using_ttl`), which `keyspace_model!` does for its tables.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::{app::access::Table, cql::Consistency};
+/// # use scylla_rs::app::access::Keyspace;
+/// # use std::borrow::Cow;
+/// # #[derive(Clone)]
+/// # struct MyKeyspace(Cow<'static, str>);
+/// # impl Keyspace for MyKeyspace {
+/// #     fn name(&self) -> &Cow<'static, str> {
+/// #         &self.0
+/// #     }
+/// # }
+///
+/// impl Table<u64, String> for MyKeyspace {
+///     const DEFAULT_TTL: Option<u32> = Some(3600);
+///     const WRITE_CONSISTENCY: Consistency = Consistency::Quorum;
+/// }
+///
+/// assert_eq!(<MyKeyspace as Table<u64, String>>::using_ttl(), " USING TTL 3600");
+/// ```
+pub trait Table<K, V>: Keyspace {
+    /// The TTL of the inserted and updated rows in seconds, if any
+    const DEFAULT_TTL: Option<u32> = None;
+    /// The consistency of the select requests
+    const READ_CONSISTENCY: Consistency = Consistency::One;
+    /// The consistency of the insert, update and delete requests
+    const WRITE_CONSISTENCY: Consistency = Consistency::One;
+
+    /// Get the `USING TTL` clause of the default TTL, with its leading space, or an empty string if there's none
+    fn using_ttl() -> String {
+        Self::DEFAULT_TTL
+            .map(|ttl| format!(" USING TTL {}", ttl))
+            .unwrap_or_default()
+    }
+}
+
+/// Declares a table of a keyspace as a row struct, whose primary key columns are marked by their attributes, ie
+/// `#[partition_key]` and `#[clustering(order = "desc")]` (or `#[clustering]` for the ascending order).
+///
+/// The macro expands to the row struct and its primary key struct, along with the same implementations as the
+/// tables of `keyspace_model!`, ie the `Row` decoder and the prepared `Select`, `Insert`, `Update` and `Delete`
+/// statements. The row struct also gets the `NAME`, `PARTITION_KEY` and `CLUSTERING_COLS` constants, and the
+/// `create_statement` of the table, whose clustering order is derived from the attributes.
+///
+/// Note: the fields of the row struct are ordered by the partition key, then by the clustering columns, then by
+/// the regular columns.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::{
+///     app::access::{GetSelectRequest, Keyspace},
+///     cql::{Consistency, VoidDecoder},
+///     table_model,
+/// };
+/// use std::borrow::Cow;
+///
+/// #[derive(Clone)]
+/// struct Shop(Cow<'static, str>);
+///
+/// impl Keyspace for Shop {
+///     fn name(&self) -> &Cow<'static, str> {
+///         &self.0
+///     }
+/// }
+///
+/// impl VoidDecoder for Shop {}
+///
+/// table_model! {
+///     /// The orders of the customers
+///     table Order, OrderKey("orders") for Shop {
+///         #[partition_key]
+///         customer: String => "text",
+///         #[clustering(order = "desc")]
+///         id: i64 => "bigint",
+///         total: f64 => "double",
+///     }
+///     options "comment = 'the orders'"
+///     defaults {
+///         WRITE_CONSISTENCY: Consistency::Quorum,
+///     }
+/// }
+///
+/// assert_eq!(Order::CLUSTERING_COLS, &[("id", "desc")]);
+/// assert_eq!(
+///     Order::create_statement("shop"),
+///     "CREATE TABLE IF NOT EXISTS shop.orders (customer text, id bigint, total double, PRIMARY KEY ((customer), id)) \
+///      WITH CLUSTERING ORDER BY (id DESC) AND comment = 'the orders'"
+/// );
+/// let key = OrderKey {
+///     customer: "alice".to_string(),
+///     id: 1,
+/// };
+/// let request = Shop("shop".into()).select::<Order>(&key).build()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[macro_export]
+macro_rules! table_model {
+    (
+        $(#[$meta:meta])*
+        table $row:ident, $key:ident($table:literal) for $keyspace:ident {
+            $($fields:tt)*
+        }
+        $(options $options:literal)?
+        $(defaults { $($default:ident: $default_value:expr),* $(,)? })?
+    ) => {
+        $crate::table_model!(
+            @fields {
+                [$(#[$meta])*] $row $key $table $keyspace [$($options)?] [$($($default: $default_value),*)?]
+            }
+            [] [] []
+            $($fields)*
+        );
+    };
+    (@fields $header:tt [$($pk:tt)*] [$($ck:tt)*] [$($col:tt)*]) => {
+        $crate::table_model!(@expand $header [$($pk)*] [$($ck)*] [$($col)*]);
+    };
+    (@fields $header:tt [$($pk:tt)*] [$($ck:tt)*] [$($col:tt)*]
+        #[partition_key] $field:ident: $type:ty => $cql:literal $(, $($rest:tt)*)?
+    ) => {
+        $crate::table_model!(
+            @fields $header [$($pk)* {$field: $type => $cql}] [$($ck)*] [$($col)*] $($($rest)*)?
+        );
+    };
+    (@fields $header:tt [$($pk:tt)*] [$($ck:tt)*] [$($col:tt)*]
+        #[clustering] $field:ident: $type:ty => $cql:literal $(, $($rest:tt)*)?
+    ) => {
+        $crate::table_model!(
+            @fields $header [$($pk)*] [$($ck)* {$field: $type => $cql, "asc"}] [$($col)*] $($($rest)*)?
+        );
+    };
+    (@fields $header:tt [$($pk:tt)*] [$($ck:tt)*] [$($col:tt)*]
+        #[clustering(order = $order:literal)] $field:ident: $type:ty => $cql:literal $(, $($rest:tt)*)?
+    ) => {
+        $crate::table_model!(
+            @fields $header [$($pk)*] [$($ck)* {$field: $type => $cql, $order}] [$($col)*] $($($rest)*)?
+        );
+    };
+    (@fields $header:tt [$($pk:tt)*] [$($ck:tt)*] [$($col:tt)*]
+        $field:ident: $type:ty => $cql:literal $(, $($rest:tt)*)?
+    ) => {
+        $crate::table_model!(
+            @fields $header [$($pk)*] [$($ck)*] [$($col)* {$field: $type => $cql}] $($($rest)*)?
+        );
+    };
+    (@expand {
+            [$(#[$meta:meta])*] $row:ident $key:ident $table:literal $keyspace:ident
+            [$($options:literal)?] [$($default:ident: $default_value:expr),*]
+        }
+        [$({$pk:ident: $pk_type:ty => $pk_cql:literal})+]
+        [$({$ck:ident: $ck_type:ty => $ck_cql:literal, $order:literal})*]
+        [$({$col:ident: $col_type:ty => $col_cql:literal})*]
+    ) => {
+        $crate::keyspace_model!(
+            @table $keyspace, $(#[$meta])* $row, $key, $table,
+            {$($pk: $pk_type),+},
+            {$($ck: $ck_type),*},
+            {$($col: $col_type),*},
+            {$($default: $default_value),*}
+        );
+
+        impl $row {
+            /// The name of the table
+            pub const NAME: &'static str = $table;
+            /// The partition key columns of the table
+            pub const PARTITION_KEY: &'static [&'static str] = &[$(stringify!($pk)),+];
+            /// The clustering columns of the table, along with their order
+            pub const CLUSTERING_COLS: &'static [(&'static str, &'static str)] = &[$((stringify!($ck), $order)),*];
+
+            /// Get the statement which creates the table in the keyspace, if it doesn't exist
+            pub fn create_statement(keyspace: &str) -> String {
+                let mut options: Vec<String> = Vec::new();
+                if !Self::CLUSTERING_COLS.is_empty() {
+                    let order: Vec<String> = Self::CLUSTERING_COLS
+                        .iter()
+                        .map(|(column, order)| format!("{} {}", column, order.to_uppercase()))
+                        .collect();
+                    options.push(format!("CLUSTERING ORDER BY ({})", order.join(", ")));
+                }
+                $(options.push($options.to_string());)?
+                let mut statement = $crate::keyspace_model!(
+                    @create_table keyspace, $table,
+                    {$($pk $pk_cql),+},
+                    {$($ck $ck_cql),*},
+                    {$($col $col_cql),*},
+                );
+                if !options.is_empty() {
+                    statement.push_str(" WITH ");
+                    statement.push_str(&options.join(" AND "));
+                }
+                statement
+            }
+        }
+    };
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use crate::cql::with_using_timeout;
 
 /// Update query trait which creates an `UpdateRequest`
 /// that can be sent to the `Ring`.
@@ -68,7 +69,7 @@ pub trait Update<K, V>: Keyspace + VoidDecoder + ComputeToken<K> {
     /// for use when generating queries that should use
     /// the prepared statement.
     fn id(&self) -> [u8; 16] {
-        md5::compute(self.update_statement().as_bytes()).into()
+        Md5::statement_id(&self.update_statement())
     }
     /// Bind the cql values to the builder
     fn bind_values<T: Values>(builder: T, key: &K, value: &V) -> T::Return;
@@ -146,6 +147,12 @@ pub struct UpdateBuilder<'a, S, K, V, Stage> {
     builder: QueryBuilder<Stage>,
 }
 impl<'a, S: Update<K, V>, K, V> UpdateBuilder<'a, S, K, V, QueryConsistency> {
+    /// Set the Scylla server-side timeout of the update, by adding the `USING TIMEOUT` clause to the statement
+    /// (see `with_using_timeout`). The request is sent as an unprepared query.
+    pub fn using_timeout(mut self, timeout: Duration) -> anyhow::Result<Self> {
+        self.builder = Query::new().statement(&with_using_timeout(&self.keyspace.statement(), timeout)?);
+        Ok(self)
+    }
     pub fn consistency(self, consistency: Consistency) -> UpdateBuilder<'a, S, K, V, QueryValues> {
         UpdateBuilder {
             _marker: self._marker,
@@ -157,7 +164,46 @@ impl<'a, S: Update<K, V>, K, V> UpdateBuilder<'a, S, K, V, QueryConsistency> {
     }
 }
 
+impl<'a, S: Update<K, V> + Table<K, V>, K, V> UpdateBuilder<'a, S, K, V, QueryConsistency> {
+    /// Set the write consistency of the table (see `Table`)
+    pub fn default_consistency(self) -> UpdateBuilder<'a, S, K, V, QueryValues> {
+        self.consistency(S::WRITE_CONSISTENCY)
+    }
+    /// Build the UpdateRequest with the write consistency of the table
+    pub fn build(self) -> anyhow::Result<UpdateRequest<S, K, V>> {
+        self.default_consistency().build()
+    }
+}
+
 impl<'a, S: Update<K, V>, K, V> UpdateBuilder<'a, S, K, V, QueryValues> {
+    /// Set the serial consistency of the conditional update, ie `Serial` or `LocalSerial`
+    pub fn serial_consistency(self, consistency: Consistency) -> UpdateBuilder<'a, S, K, V, QueryTimestamp> {
+        UpdateBuilder {
+            _marker: self._marker,
+            keyspace: self.keyspace,
+            key: self.key,
+            value: self.value,
+            builder: self.builder.serial_consistency(consistency),
+        }
+    }
+    pub fn timestamp(self, timestamp: i64) -> UpdateBuilder<'a, S, K, V, QueryBuild> {
+        UpdateBuilder {
+            _marker: self._marker,
+            keyspace: self.keyspace,
+            key: self.key,
+            value: self.value,
+            builder: self.builder.timestamp(timestamp),
+        }
+    }
+    /// Build the UpdateRequest
+    pub fn build(self) -> anyhow::Result<UpdateRequest<S, K, V>> {
+        let query = self.builder.build()?;
+        // create the request
+        Ok(self.keyspace.create_request(query, S::token(self.key)))
+    }
+}
+
+impl<'a, S: Update<K, V>, K, V> UpdateBuilder<'a, S, K, V, QueryTimestamp> {
     pub fn timestamp(self, timestamp: i64) -> UpdateBuilder<'a, S, K, V, QueryBuild> {
         UpdateBuilder {
             _marker: self._marker,
@@ -218,6 +264,7 @@ impl<S: Keyspace> GetUpdateStatement<S> for S {
 pub struct UpdateRequest<S, K, V> {
     token: i64,
     inner: Vec<u8>,
+    timeout: Option<Duration>,
     keyspace: S,
     _marker: PhantomData<(S, K, V)>,
 }
@@ -228,6 +275,7 @@ impl<K, V, S: Update<K, V> + Clone> CreateRequest<UpdateRequest<S, K, V>> for S
         UpdateRequest::<S, K, V> {
             token,
             inner: query.into(),
+            timeout: None,
             keyspace: self.clone(),
             _marker: PhantomData,
         }
@@ -250,12 +298,28 @@ where
 }
 
 impl<S: Update<K, V>, K, V> UpdateRequest<S, K, V> {
+    /// Set the client-side timeout of the request, after which its worker is resolved with `WorkerError::Timeout`,
+    /// or None to await its response until the connection is lost
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Decode the query frame of the request, apply the function to it (ie to adjust its consistency or page size),
+    /// then encode it again, so the middlewares don't have to rebuild the request from scratch
+    pub fn map_frame<F: FnOnce(&mut QueryFrame)>(mut self, f: F) -> anyhow::Result<Self> {
+        let mut frame = QueryFrame::decode(&self.inner)?;
+        f(&mut frame);
+        self.inner = frame.encode()?;
+        Ok(self)
+    }
+
     /// Send a local request using the keyspace impl and return a type marker
     pub fn send_local(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeVoid<S>> {
         send_local(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Update, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::update()
@@ -266,14 +330,51 @@ impl<S: Update<K, V>, K, V> UpdateRequest<S, K, V> {
         send_global(
             self.token,
             self.inner,
-            worker,
+            wrap_worker(worker, RequestType::Update, self.timeout),
             self.keyspace.name().clone().into_owned(),
         );
         DecodeResult::update()
     }
 
+    /// Send a request routed by the provided load balancing policy and return a type marker
+    pub fn send_with(self, policy: &dyn LoadBalancingPolicy, worker: Box<dyn Worker>) -> DecodeResult<DecodeVoid<S>> {
+        send_with(
+            policy,
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Update, self.timeout),
+        );
+        DecodeResult::update()
+    }
+
     /// Consume the request to retrieve the payload
     pub fn into_payload(self) -> Vec<u8> {
         self.inner
     }
 }
+
+impl<S: Update<K, V> + LwtDecoder<K, V>, K, V: Row> UpdateRequest<S, K, V> {
+    /// Send a local conditional request (ie `UPDATE .. IF ..`) using the keyspace impl and return a type marker
+    /// which decodes its `[applied]` result
+    pub fn send_local_conditional(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeLwt<S, K, V>> {
+        send_local(
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Update, self.timeout),
+            self.keyspace.name().clone().into_owned(),
+        );
+        DecodeResult::conditional_update()
+    }
+
+    /// Send a global conditional request using the keyspace impl and return a type marker which decodes its
+    /// `[applied]` result
+    pub fn send_global_conditional(self, worker: Box<dyn Worker>) -> DecodeResult<DecodeLwt<S, K, V>> {
+        send_global(
+            self.token,
+            self.inner,
+            wrap_worker(worker, RequestType::Update, self.timeout),
+            self.keyspace.name().clone().into_owned(),
+        );
+        DecodeResult::conditional_update()
+    }
+}
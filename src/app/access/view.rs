@@ -0,0 +1,354 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::cql::statements::schema::{ClusteringOrder, CreateMaterializedViewStatement};
+
+/// Materialized view trait which declares a view of a base table, along with its own primary key, so the view can
+/// be created by its `create_statement` and read by typed `Select` requests through its `View` wrapper.
+///
+/// The key is the partition key of the view, which is bound by `bind_values` and hashed by `token`, so the view
+/// reads are routed to the replicas of the view partition rather than of the base one. As the key and the value
+/// types of a view might be the same as the base table ones, the view implements the request traits through the
+/// `View` wrapper, whose `Table` defaults are the ones of the base table.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::{
+///     app::access::{GetSelectRequest, Keyspace, MaterializedView, Table},
+///     cql::{statements::schema::ClusteringOrder, Consistency, Decoder, Values},
+/// };
+/// use std::borrow::Cow;
+/// # #[derive(Clone)]
+/// # struct Shop(Cow<'static, str>);
+/// # impl Keyspace for Shop {
+/// #     fn name(&self) -> &Cow<'static, str> {
+/// #         &self.0
+/// #     }
+/// # }
+/// impl Table<i64, String> for Shop {
+///     const READ_CONSISTENCY: Consistency = Consistency::LocalQuorum;
+/// }
+///
+/// /// The ids of the orders by customer, the most recent first
+/// impl MaterializedView<String, Vec<i64>> for Shop {
+///     type BaseKey = i64;
+///     type BaseValue = String;
+///     type Row = i64;
+///     const NAME: &'static str = "orders_by_customer";
+///     const BASE_TABLE: &'static str = "orders";
+///     const PARTITION_KEY: &'static [&'static str] = &["customer"];
+///     const CLUSTERING_KEY: &'static [(&'static str, ClusteringOrder)] = &[("id", ClusteringOrder::Desc)];
+///     const COLUMNS: &'static [&'static str] = &["customer", "id"];
+///
+///     fn token(customer: &String) -> i64 {
+///         // the murmur3 token of the customer
+/// #       0
+///     }
+///     fn try_decode(decoder: Decoder) -> anyhow::Result<Option<Vec<i64>>> {
+///         // decode the ids of the rows
+/// #       todo!()
+///     }
+///     fn bind_values<T: Values>(builder: T, customer: &String) -> T::Return {
+///         builder.value(customer)
+///     }
+/// }
+///
+/// let shop = Shop("shop".into());
+/// let view = MaterializedView::<String, Vec<i64>>::view(&shop);
+/// assert_eq!(
+///     MaterializedView::<String, Vec<i64>>::create_statement(&shop).to_string(),
+///     "CREATE MATERIALIZED VIEW IF NOT EXISTS shop.orders_by_customer AS SELECT customer, id FROM shop.orders \
+///      WHERE customer IS NOT NULL AND id IS NOT NULL PRIMARY KEY ((customer), id) \
+///      WITH CLUSTERING ORDER BY (id DESC)"
+/// );
+/// let customer = "alice".to_string();
+/// // routed by the token of the customer, with the read consistency of the base table
+/// let request = view.select::<Vec<i64>>(&customer).build()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait MaterializedView<K, V>: Keyspace {
+    /// The key type of the base table, whose `Table` defaults apply to the view
+    type BaseKey;
+    /// The value type of the base table, whose `Table` defaults apply to the view
+    type BaseValue;
+    /// The row of the view
+    type Row: Row;
+    /// The name of the view
+    const NAME: &'static str;
+    /// The name of the base table
+    const BASE_TABLE: &'static str;
+    /// The partition key columns of the view
+    const PARTITION_KEY: &'static [&'static str];
+    /// The clustering columns of the view along with their order
+    const CLUSTERING_KEY: &'static [(&'static str, ClusteringOrder)] = &[];
+    /// The columns of the base table which are selected by the view, or none for all of them
+    const COLUMNS: &'static [&'static str] = &[];
+
+    /// Compute the token of the view partition key by using murmur3 hash function
+    fn token(key: &K) -> i64;
+    /// Try to decode the rows of the view
+    fn try_decode(decoder: Decoder) -> anyhow::Result<Option<V>>;
+    /// Bind the cql values of the view partition key to the builder
+    fn bind_values<T: Values>(builder: T, key: &K) -> T::Return;
+    /// Create your view select statement here, which selects the rows of the partition bound to the key by default.
+    fn statement(&self) -> Cow<'static, str> {
+        let columns = match Self::COLUMNS {
+            [] => "*".to_string(),
+            columns => columns
+                .iter()
+                .map(|column| Name::auto(column).to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        let relations = Self::PARTITION_KEY
+            .iter()
+            .map(|column| format!("{} = ?", Name::auto(column)))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        format!(
+            "SELECT {} FROM {}.{} WHERE {}",
+            columns,
+            self.name(),
+            Name::auto(Self::NAME),
+            relations
+        )
+        .into()
+    }
+    /// Get the statement which creates the view, if it doesn't exist, whose base rows are filtered by the non null
+    /// primary key columns of the view
+    fn create_statement(&self) -> CreateMaterializedViewStatement {
+        let partition_key: Vec<Name> = Self::PARTITION_KEY.iter().map(|column| Name::auto(column)).collect();
+        let clustering_key: Vec<(Name, ClusteringOrder)> = Self::CLUSTERING_KEY
+            .iter()
+            .map(|(column, order)| (Name::auto(column), *order))
+            .collect();
+        let where_clause = partition_key
+            .iter()
+            .chain(clustering_key.iter().map(|(column, _)| column))
+            .map(|column| format!("{} IS NOT NULL", column))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        CreateMaterializedViewStatement {
+            if_not_exists: true,
+            keyspace: Some(Name::auto(self.name())),
+            name: Name::auto(Self::NAME),
+            columns: Self::COLUMNS.iter().map(|column| Name::auto(column)).collect(),
+            base_keyspace: Some(Name::auto(self.name())),
+            base_table: Name::auto(Self::BASE_TABLE),
+            where_clause,
+            partition_key,
+            clustering_key,
+            per_partition_limit: None,
+            options: Default::default(),
+        }
+    }
+    /// Wrap the keyspace into the `View`, which implements the request traits of the view
+    fn view(&self) -> View<Self, K, V> {
+        View::new(self.clone())
+    }
+}
+
+/// The wrapper of a keyspace which reads its materialized view, by implementing the `Select` trait with the
+/// statement, the token and the decoder of the `MaterializedView` implementation, so the view reads are requested
+/// like the other selects (ie `view.select::<V>(&key)`).
+pub struct View<S, K, V> {
+    keyspace: S,
+    _marker: PhantomData<fn(K) -> V>,
+}
+
+impl<S: MaterializedView<K, V>, K, V> View<S, K, V> {
+    /// Wrap the keyspace which declares the view
+    pub fn new(keyspace: S) -> Self {
+        Self {
+            keyspace,
+            _marker: PhantomData,
+        }
+    }
+    /// Get the keyspace which declares the view
+    pub fn keyspace(&self) -> &S {
+        &self.keyspace
+    }
+}
+
+impl<S: Clone, K, V> Clone for View<S, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            keyspace: self.keyspace.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<S: Keyspace, K, V> Keyspace for View<S, K, V> {
+    fn name(&self) -> &Cow<'static, str> {
+        self.keyspace.name()
+    }
+}
+
+impl<S: MaterializedView<K, V>, K, V> ComputeToken<K> for View<S, K, V> {
+    fn token(key: &K) -> i64 {
+        <S as MaterializedView<K, V>>::token(key)
+    }
+}
+
+impl<S: MaterializedView<K, V>, K, V> RowsDecoder<K, V> for View<S, K, V> {
+    type Row = S::Row;
+    fn try_decode(decoder: Decoder) -> anyhow::Result<Option<V>> {
+        <S as MaterializedView<K, V>>::try_decode(decoder)
+    }
+}
+
+impl<S: MaterializedView<K, V>, K, V> Select<K, V> for View<S, K, V> {
+    type QueryOrPrepared = PreparedStatement;
+    fn statement(&self) -> Cow<'static, str> {
+        <S as MaterializedView<K, V>>::statement(&self.keyspace)
+    }
+    fn bind_values<T: Values>(builder: T, key: &K) -> T::Return {
+        <S as MaterializedView<K, V>>::bind_values(builder, key)
+    }
+    fn partition_key_columns(&self) -> &'static [&'static str] {
+        S::PARTITION_KEY
+    }
+}
+
+impl<S, K, V> Table<K, V> for View<S, K, V>
+where
+    S: MaterializedView<K, V> + Table<<S as MaterializedView<K, V>>::BaseKey, <S as MaterializedView<K, V>>::BaseValue>,
+{
+    const READ_CONSISTENCY: Consistency = <S as Table<
+        <S as MaterializedView<K, V>>::BaseKey,
+        <S as MaterializedView<K, V>>::BaseValue,
+    >>::READ_CONSISTENCY;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::access::stub::{request_statement, request_values},
+        cql::opcode,
+    };
+
+    const BY_CUSTOMER: &str = "SELECT customer, id FROM shop.orders_by_customer WHERE customer = ?";
+
+    #[derive(Clone)]
+    struct Shop(Cow<'static, str>);
+
+    impl Keyspace for Shop {
+        fn name(&self) -> &Cow<'static, str> {
+            &self.0
+        }
+    }
+
+    impl Table<i64, String> for Shop {
+        const READ_CONSISTENCY: Consistency = Consistency::LocalQuorum;
+    }
+
+    /// The ids of the orders by customer, the most recent first
+    impl MaterializedView<String, Vec<i64>> for Shop {
+        type BaseKey = i64;
+        type BaseValue = String;
+        type Row = i64;
+        const NAME: &'static str = "orders_by_customer";
+        const BASE_TABLE: &'static str = "orders";
+        const PARTITION_KEY: &'static [&'static str] = &["customer"];
+        const CLUSTERING_KEY: &'static [(&'static str, ClusteringOrder)] = &[("id", ClusteringOrder::Desc)];
+        const COLUMNS: &'static [&'static str] = &["customer", "id"];
+
+        fn token(customer: &String) -> i64 {
+            customer.len() as i64
+        }
+        fn try_decode(_decoder: Decoder) -> anyhow::Result<Option<Vec<i64>>> {
+            unimplemented!()
+        }
+        fn bind_values<T: Values>(builder: T, customer: &String) -> T::Return {
+            builder.value(customer)
+        }
+    }
+
+    /// The whole orders by the case sensitive day and shop of their customer
+    impl MaterializedView<(String, i32), Vec<String>> for Shop {
+        type BaseKey = i64;
+        type BaseValue = String;
+        type Row = String;
+        const NAME: &'static str = "ordersByDay";
+        const BASE_TABLE: &'static str = "orders";
+        const PARTITION_KEY: &'static [&'static str] = &["shopId", "day"];
+
+        fn token(_key: &(String, i32)) -> i64 {
+            0
+        }
+        fn try_decode(_decoder: Decoder) -> anyhow::Result<Option<Vec<String>>> {
+            unimplemented!()
+        }
+        fn bind_values<T: Values>(builder: T, (shop_id, day): &(String, i32)) -> T::Return {
+            builder.value(shop_id).value(day)
+        }
+    }
+
+    fn shop() -> Shop {
+        Shop("shop".into())
+    }
+
+    #[test]
+    fn statements() {
+        let shop = shop();
+        assert_eq!(MaterializedView::<String, Vec<i64>>::statement(&shop), BY_CUSTOMER);
+        assert_eq!(
+            MaterializedView::<String, Vec<i64>>::create_statement(&shop).to_string(),
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS shop.orders_by_customer AS SELECT customer, id FROM shop.orders \
+             WHERE customer IS NOT NULL AND id IS NOT NULL PRIMARY KEY ((customer), id) \
+             WITH CLUSTERING ORDER BY (id DESC)"
+        );
+        // the view of all the columns, whose case sensitive names are quoted
+        assert_eq!(
+            MaterializedView::<(String, i32), Vec<String>>::statement(&shop),
+            "SELECT * FROM shop.\"ordersByDay\" WHERE \"shopId\" = ? AND day = ?"
+        );
+        assert_eq!(
+            MaterializedView::<(String, i32), Vec<String>>::create_statement(&shop).to_string(),
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS shop.\"ordersByDay\" AS SELECT * FROM shop.orders \
+             WHERE \"shopId\" IS NOT NULL AND day IS NOT NULL PRIMARY KEY ((\"shopId\", day))"
+        );
+    }
+
+    #[test]
+    fn view_select() {
+        let view = MaterializedView::<String, Vec<i64>>::view(&shop());
+        assert_eq!(view.name(), "shop");
+        assert_eq!(Select::<String, Vec<i64>>::statement(&view), BY_CUSTOMER);
+        assert_eq!(view.partition_key_columns(), &["customer"]);
+        // the view reads are routed by the token of the view partition key
+        assert_eq!(
+            <View<Shop, String, Vec<i64>> as ComputeToken<String>>::token(&"alice".to_string()),
+            5
+        );
+    }
+
+    #[test]
+    fn view_request() {
+        let view = MaterializedView::<String, Vec<i64>>::view(&shop());
+        let customer = "alice".to_string();
+        let request = view.select::<Vec<i64>>(&customer).build().unwrap();
+        let payload = request.payload();
+        // the view statement is prepared, and read with the consistency of the base table
+        assert_eq!(request.statement(), BY_CUSTOMER);
+        assert_eq!(payload[4], opcode::EXECUTE);
+        assert_eq!(payload[11..27], Md5::statement_id(BY_CUSTOMER));
+        assert_eq!(payload[27..29], (Consistency::LocalQuorum as u16).to_be_bytes());
+        assert_eq!(request_values(payload), vec![Some(customer.into_bytes())]);
+        // the token range relations restrict the view partition key
+        let request = view
+            .select::<Vec<i64>>(&"alice".to_string())
+            .where_token_range(0, 100)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            request_statement(request.payload()),
+            "SELECT customer, id FROM shop.orders_by_customer \
+             WHERE token(customer) > ? AND token(customer) <= ? AND customer = ?"
+        );
+    }
+}
@@ -3,17 +3,21 @@
 
 use super::{
     cluster::{ClusterBuilder, ClusterHandle},
+    diagnostics::{self, DiagnosticEvent},
     listener::{ListenerBuilder, ListenerHandle},
+    node::WatchdogConfig,
+    stage::HeartbeatConfig,
     websocket::WsTx,
     *,
 };
-pub(crate) use crate::cql::{CqlBuilder, PasswordAuth};
+pub(crate) use crate::cql::{CompressionType, ConnectError, CqlBuilder, PasswordAuth, ReadOnly, TlsConfig};
 use anyhow::{anyhow, bail};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     net::SocketAddr,
     ops::{Deref, DerefMut},
+    time::Duration,
 };
 
 mod event_loop;
@@ -36,9 +40,16 @@ builder!(
         buffer_size: usize,
         recv_buffer_size: u32,
         send_buffer_size: u32,
+        compression: CompressionType,
         listener_handle: ListenerHandle,
         cluster_handle: ClusterHandle,
-        authenticator: PasswordAuth
+        authenticator: PasswordAuth,
+        tls: TlsConfig,
+        read_only: bool,
+        heartbeat: HeartbeatConfig,
+        drain_timeout: Duration,
+        decode_offload_threshold: usize,
+        watchdog: WatchdogConfig
 });
 
 #[derive(Deserialize, Serialize)]
@@ -68,6 +79,13 @@ pub struct ScyllaInbox<H: ScyllaScope> {
     rx: tokio::sync::mpsc::UnboundedReceiver<ScyllaEvent<H::AppsEvents>>,
 }
 
+impl<H: ScyllaScope> ScyllaHandle<H> {
+    /// Subscribe to the driver diagnostic events
+    pub fn events(&self) -> tokio::sync::broadcast::Receiver<DiagnosticEvent> {
+        diagnostics::subscribe()
+    }
+}
+
 impl<H: ScyllaScope> Clone for ScyllaHandle<H> {
     fn clone(&self) -> Self {
         ScyllaHandle::<H> {
@@ -104,7 +122,7 @@ pub enum ScyllaEvent<T> {
     /// Used by scylla children to push their service
     Children(ScyllaChild),
     /// Used by cluster to inform scylla in order to inform the sockets with the result of topology events
-    Result(SocketMsg<Result<Topology, Topology>>),
+    Result(SocketMsg<TopologyResult>),
     /// Abort the scylla app, sent by launcher
     Abort,
 }
@@ -122,6 +140,24 @@ pub enum Topology {
     BuildRing(u8),
 }
 
+/// The result of a topology event, which is reported to the sockets
+pub type TopologyResult = Result<Topology, TopologyError>;
+
+#[derive(Deserialize, Serialize, Debug)]
+/// The failed topology event, along with the reason of its failure
+pub struct TopologyError {
+    /// The failed topology event
+    pub topology: Topology,
+    /// The connection error which failed the event, if the node couldn't be reached
+    pub error: Option<ConnectError>,
+}
+
+impl From<Topology> for TopologyError {
+    fn from(topology: Topology) -> Self {
+        Self { topology, error: None }
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 /// Indicates which app this message is for
 pub enum SocketMsg<T> {
@@ -31,7 +31,14 @@ impl<H: ScyllaScope> Starter<H> for ScyllaBuilder<H> {
             .buffer_size(self.buffer_size.clone().unwrap_or(1024000))
             .recv_buffer_size(self.recv_buffer_size.clone())
             .send_buffer_size(self.send_buffer_size.clone())
+            .compression(self.compression)
             .authenticator(self.authenticator.clone().unwrap_or(PasswordAuth::default()))
+            .tls(self.tls.clone())
+            .read_only(ReadOnly::new(self.read_only.unwrap_or_default()))
+            .heartbeat(self.heartbeat)
+            .drain_timeout(self.drain_timeout)
+            .decode_offload_threshold(self.decode_offload_threshold)
+            .watchdog(self.watchdog)
             .build();
         // clone cluster handle
         let cluster_handle = cluster
@@ -2,6 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use crate::app::{
+    access::SchemaCache,
+    diagnostics::{self, DiagnosticEvent},
+    worker::PreparedCache,
+};
 
 #[async_trait::async_trait]
 impl<H: ScyllaScope> EventLoop<ScyllaHandle<H>> for Cluster {
@@ -34,13 +39,14 @@ impl<H: ScyllaScope> EventLoop<ScyllaHandle<H>> for Cluster {
                         }
                         let event = ScyllaEvent::Children(ScyllaChild::Cluster(self.service.clone()));
                         let _ = supervisor.send(event);
+                        self.rebuild_on_topology_change();
                     }
                     // Maybe let the variant to set the PasswordAuth instead of forcing global_auth at the cluster
                     // level?
                     ClusterEvent::AddNode(address) => {
                         // make sure it doesn't already exist in our cluster
                         if self.nodes.contains_key(&address) {
-                            let event = ScyllaEvent::Result(SocketMsg::Scylla(Err(Topology::AddNode(address))));
+                            let event = ScyllaEvent::Result(SocketMsg::Scylla(Err(Topology::AddNode(address).into())));
                             let _ = supervisor.send(event);
                             continue;
                         }
@@ -50,7 +56,9 @@ impl<H: ScyllaScope> EventLoop<ScyllaHandle<H>> for Cluster {
                             .tokens()
                             .recv_buffer_size(self.recv_buffer_size)
                             .send_buffer_size(self.send_buffer_size)
+                            .compression(self.compression)
                             .authenticator(self.authenticator.clone())
+                            .tls(self.tls.clone())
                             .build();
                         match cql.await {
                             Ok(mut cqlconn) => {
@@ -68,7 +76,14 @@ impl<H: ScyllaScope> EventLoop<ScyllaHandle<H>> for Cluster {
                                         .buffer_size(self.buffer_size)
                                         .recv_buffer_size(self.recv_buffer_size)
                                         .send_buffer_size(self.send_buffer_size)
+                                        .compression(self.compression)
                                         .authenticator(self.authenticator.clone())
+                                        .tls(self.tls.clone())
+                                        .read_only(self.read_only.clone())
+                                        .heartbeat(self.heartbeat)
+                                        .drain_timeout(self.drain_timeout)
+                                        .decode_offload_threshold(self.decode_offload_threshold)
+                                        .watchdog(self.watchdog)
                                         .build();
                                     // clone the node_handle
                                     let node_handle = node.clone_handle();
@@ -86,16 +101,41 @@ impl<H: ScyllaScope> EventLoop<ScyllaHandle<H>> for Cluster {
                                     // add node_info to nodes
                                     self.nodes.insert(address, node_info);
                                     tokio::spawn(node.start(self.handle.clone()));
+                                    // make sure the cluster is registered for the topology changes
+                                    self.spawn_events_listener(None);
                                 } else {
                                     error!("Failed to retrieve data from CQL Connection!");
                                 }
                             }
-                            Err(_) => {
-                                let event = ScyllaEvent::Result(SocketMsg::Scylla(Err(Topology::AddNode(address))));
+                            Err(error) => {
+                                warn!("Unable to add node {}: {}", address, error);
+                                let error = TopologyError {
+                                    topology: Topology::AddNode(address),
+                                    error: Some(error),
+                                };
+                                let event = ScyllaEvent::Result(SocketMsg::Scylla(Err(error)));
                                 let _ = supervisor.send(event);
                             }
                         }
                     }
+                    ClusterEvent::RotateCredentials(authenticator, responder) => {
+                        if authenticator.user() != self.authenticator.user() {
+                            // the driver isn't authenticated as the altered role
+                            responder.send(Ok(())).ok();
+                            continue;
+                        }
+                        let res = self.verify_credentials(&authenticator).await;
+                        if res.is_ok() {
+                            for node_info in self.nodes.values() {
+                                node_info
+                                    .node_handle
+                                    .send(NodeEvent::RotateCredentials(authenticator.clone()))
+                                    .ok();
+                            }
+                            self.authenticator = authenticator;
+                        }
+                        responder.send(res).ok();
+                    }
                     ClusterEvent::RemoveNode(address) => {
                         // get and remove node_info
                         if let Some(mut node_info) = self.nodes.remove(&address) {
@@ -111,10 +151,14 @@ impl<H: ScyllaScope> EventLoop<ScyllaHandle<H>> for Cluster {
                             self.should_build = true;
                             // note: the node tree will not get shutdown unless we drop the ring
                             // but we cannot drop the ring unless we build a new one and atomically swap it,
-                            // therefore dashboard admin supposed to BuildRing
+                            // therefore dashboard admin supposed to BuildRing, unless the node left the cluster
+                            self.abort_events_listener(Some(address));
+                            self.spawn_events_listener(None);
+                            self.rebuild_on_topology_change();
                         } else {
                             // Cannot remove non-existing node.
-                            let event = ScyllaEvent::Result(SocketMsg::Scylla(Err(Topology::RemoveNode(address))));
+                            let event =
+                                ScyllaEvent::Result(SocketMsg::Scylla(Err(Topology::RemoveNode(address).into())));
                             let _ = supervisor.send(event);
                         };
                     }
@@ -123,10 +167,13 @@ impl<H: ScyllaScope> EventLoop<ScyllaHandle<H>> for Cluster {
                         if let Ok(address) = microservice.get_name().parse() {
                             // update service
                             self.service.update_microservice(microservice.get_name(), microservice);
+                            // prepare the cached statements on the shards of that node
+                            PreparedCache::prepare_registry(&reporters_handles);
                             // merge/add reporters_handles of that node to registry
                             self.registry.extend(reporters_handles);
                             // update waiting for build to true
                             self.should_build = true;
+                            self.rebuild_on_topology_change();
                             // reply to scylla/dashboard
                             let event = ScyllaEvent::Result(SocketMsg::Scylla(Ok(Topology::AddNode(address))));
                             let _ = supervisor.send(event);
@@ -163,21 +210,35 @@ impl<H: ScyllaScope> EventLoop<ScyllaHandle<H>> for Cluster {
                                 self.weak_rings.push(old_weak_ring);
                             }
                             Ring::rebuild();
+                            diagnostics::emit(DiagnosticEvent::RingRebuilt { version });
+                            // preload the schemas of the watched keyspaces, once the ring is first built
+                            SchemaCache::spawn_preload();
                             // reset should_build state to false becaue we built it and we don't want to rebuild again
                             // incase of another BuildRing event
                             self.should_build = false;
+                            // keep the replication factor to rebuild the ring on the topology changes
+                            self.uniform_rf.replace(uniform_rf);
+                            self.auto_build = false;
                             // reply to scylla/dashboard
                             let event = ScyllaEvent::Result(SocketMsg::Scylla(Ok(Topology::BuildRing(uniform_rf))));
                             let _ = supervisor.send(event);
                         } else {
                             // reply to scylla/dashboard
-                            let event = ScyllaEvent::Result(SocketMsg::Scylla(Err(Topology::BuildRing(uniform_rf))));
+                            let event =
+                                ScyllaEvent::Result(SocketMsg::Scylla(Err(Topology::BuildRing(uniform_rf).into())));
                             let _ = supervisor.send(event);
                         }
                     }
+                    ClusterEvent::ServerEvent(event) => self.handle_server_event(event),
+                    ClusterEvent::EventsListenerClosed(address) => {
+                        // respawn the events listener, which fails over to the other nodes first
+                        self.abort_events_listener(address);
+                        self.spawn_events_listener(address);
+                    }
                     ClusterEvent::Shutdown => {
                         // do self cleanup on weaks
                         self.cleanup();
+                        self.abort_events_listener(None);
                         // shutdown everything and drop self.tx
                         for (_, mut node_info) in self.nodes.drain() {
                             for shard_id in 0..node_info.shard_count {
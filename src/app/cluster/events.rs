@@ -0,0 +1,124 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::{
+    app::{
+        access::{SchemaCache, SystemQueryCache},
+        diagnostics::{self, DiagnosticEvent},
+    },
+    cql::{Event, EventType, TopologyChange, DEFAULT_STAGGER},
+};
+use std::sync::{Arc, OnceLock};
+
+/// The delay before the events listener reconnects, once its connection is closed
+const EVENTS_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The events listener, which connects to the first reachable node, see `Cluster::spawn_events_listener`
+pub(super) struct EventsListener {
+    /// The address of the node it's connected to, once it's connected
+    address: Arc<OnceLock<SocketAddr>>,
+    task: JoinHandle<()>,
+}
+
+impl Cluster {
+    /// Spawn the events listener on a dedicated connection to one of the nodes, unless it's already running.
+    ///
+    /// The nodes are tried in parallel staggered attempts (see `CqlBuilder::build_any`), so a down node doesn't
+    /// block the listener for the full TCP timeout, where the node of the closed listener (if any) is tried last.
+    pub(super) fn spawn_events_listener(&mut self, closed: Option<SocketAddr>) {
+        if self.events_listener.is_some() || self.nodes.is_empty() {
+            return;
+        }
+        let handle = match self.handle.clone() {
+            Some(handle) => handle,
+            None => return,
+        };
+        let mut contact_points: Vec<SocketAddr> = self.nodes.keys().cloned().collect();
+        contact_points.sort_by_key(|address| (Some(*address) == closed, *address));
+        let cql = CqlBuilder::new()
+            .recv_buffer_size(self.recv_buffer_size)
+            .send_buffer_size(self.send_buffer_size)
+            .compression(self.compression)
+            .authenticator(self.authenticator.clone())
+            .tls(self.tls.clone());
+        let address = Arc::new(OnceLock::new());
+        let connected = address.clone();
+        let task = tokio::spawn(async move {
+            if let Err(e) = listen_events(cql, &contact_points, &connected, &handle).await {
+                warn!("Events listener stopped: {}", e);
+            }
+            tokio::time::sleep(EVENTS_RECONNECT_DELAY).await;
+            handle
+                .send(ClusterEvent::EventsListenerClosed(connected.get().cloned()))
+                .ok();
+        });
+        self.events_listener.replace(EventsListener { address, task });
+    }
+    /// Abort the events listener, unless it's connected to another node than the provided one (if any)
+    pub(super) fn abort_events_listener(&mut self, address: Option<SocketAddr>) {
+        if let Some(listener) = self.events_listener.take() {
+            let connected = listener.address.get().cloned();
+            if address.is_some() && connected.is_some() && connected != address {
+                self.events_listener.replace(listener);
+            } else {
+                listener.task.abort();
+            }
+        }
+    }
+    /// Handle the server event, ie add/remove the node which joined/left the cluster, and rebuild the ring
+    /// once it's ready (see `Cluster::rebuild_on_topology_change`), and invalidate the system queries results which
+    /// it outdates, along with the cached schemas
+    pub(super) fn handle_server_event(&mut self, event: Event) {
+        SystemQueryCache::invalidate_on(&event);
+        SchemaCache::refresh_on(&event);
+        if let (Some(handle), Event::TopologyChange { change, address }) = (self.handle.as_ref(), &event) {
+            let address = *address;
+            let event = match change {
+                TopologyChange::NewNode if !self.nodes.contains_key(&address) => Some(ClusterEvent::AddNode(address)),
+                TopologyChange::RemovedNode if self.nodes.contains_key(&address) => {
+                    Some(ClusterEvent::RemoveNode(address))
+                }
+                _ => None,
+            };
+            if let Some(event) = event {
+                self.auto_build = true;
+                handle.send(event).ok();
+            }
+        }
+        diagnostics::emit(DiagnosticEvent::ServerEvent { event });
+    }
+    /// Rebuild the ring with the latest replication factor, if a topology change is pending and none of the nodes
+    /// is still starting. The ring isn't rebuilt automatically before it's first built.
+    pub(super) fn rebuild_on_topology_change(&mut self) {
+        if !self.auto_build || !self.should_build {
+            return;
+        }
+        if self.service.microservices.values().any(|ms| ms.is_starting()) {
+            return;
+        }
+        if let (Some(uniform_rf), Some(handle)) = (self.uniform_rf, self.handle.as_ref()) {
+            self.auto_build = false;
+            handle.send(ClusterEvent::BuildRing(uniform_rf)).ok();
+        }
+    }
+}
+
+/// Register a connection to the first reachable contact point for the server events, and forward them to the cluster
+/// until the connection is closed
+async fn listen_events(
+    cql: CqlBuilder<PasswordAuth>,
+    contact_points: &[SocketAddr],
+    connected: &OnceLock<SocketAddr>,
+    handle: &ClusterHandle,
+) -> anyhow::Result<()> {
+    let mut cql = cql.build_any(contact_points, DEFAULT_STAGGER).await?;
+    connected.set(cql.address()).ok();
+    cql.register(&EventType::ALL).await?;
+    loop {
+        let event = cql.next_event().await?;
+        handle
+            .send(ClusterEvent::ServerEvent(event))
+            .map_err(|_| anyhow::anyhow!("Cluster is not running"))?;
+    }
+}
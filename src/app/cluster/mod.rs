@@ -2,25 +2,38 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::{
-    node::{NodeBuilder, NodeHandle},
+    node::{NodeBuilder, NodeEvent, NodeHandle},
     *,
 };
-use crate::app::{
-    ring::{build_ring, initialize_ring, ArcRing, Registry, Ring, WeakRing},
-    stage::ReportersHandles,
+use crate::{
+    app::{
+        node::WatchdogConfig,
+        ring::{build_ring, initialize_ring, ArcRing, Registry, Ring, WeakRing},
+        stage::{HeartbeatConfig, ReportersHandles},
+    },
+    cql::Event,
 };
+use events::EventsListener;
 use std::{
     collections::HashMap,
     net::SocketAddr,
     ops::{Deref, DerefMut},
+    time::Duration,
 };
+use tokio::{sync::oneshot, task::JoinHandle};
 
 mod event_loop;
+mod events;
 mod init;
 mod terminating;
 
 pub(crate) type Nodes = HashMap<SocketAddr, NodeInfo>;
 
+/// The number of attempts to verify the rotated credentials against a node
+const VERIFY_CREDENTIALS_ATTEMPTS: u32 = 10;
+/// The delay between the attempts to verify the rotated credentials
+const VERIFY_CREDENTIALS_DELAY: Duration = Duration::from_millis(500);
+
 // Cluster builder
 builder!(ClusterBuilder {
     reporter_count: u8,
@@ -29,18 +42,53 @@ builder!(ClusterBuilder {
     buffer_size: usize,
     recv_buffer_size: Option<u32>,
     send_buffer_size: Option<u32>,
-    authenticator: PasswordAuth
+    compression: Option<CompressionType>,
+    authenticator: PasswordAuth,
+    tls: Option<TlsConfig>,
+    read_only: ReadOnly,
+    heartbeat: Option<HeartbeatConfig>,
+    drain_timeout: Option<Duration>,
+    decode_offload_threshold: Option<usize>,
+    watchdog: Option<WatchdogConfig>
 });
 /// ClusterHandle to be passed to the children (Node)
 #[derive(Clone)]
 pub struct ClusterHandle {
     tx: mpsc::UnboundedSender<ClusterEvent>,
+    read_only: ReadOnly,
 }
 /// ClusterInbox is used to recv events
 pub struct ClusterInbox {
     rx: mpsc::UnboundedReceiver<ClusterEvent>,
 }
 
+impl ClusterHandle {
+    /// Get the read-only mode of the cluster, which rejects the mutation and DDL requests before they are sent,
+    /// and can be toggled at runtime
+    pub fn read_only(&self) -> &ReadOnly {
+        &self.read_only
+    }
+    /// Query the SUPPORTED options of the node, ie to audit the compression and protocol capabilities
+    /// across the cluster nodes
+    pub async fn node_options(&self, address: SocketAddr) -> anyhow::Result<HashMap<String, Vec<String>>> {
+        crate::cql::node_options(address).await
+    }
+    /// Rotate the password of the role, and the credentials used by the driver if it's authenticated as the role.
+    ///
+    /// The password is altered through the current sessions, then the new credentials are verified against
+    /// every node one by one, as the auth data might take a while to propagate. Only once every node accepts
+    /// them, the driver credentials are rotated, therefore a failed verification never locks the driver out.
+    /// The current sessions remain authenticated, while the reconnections use the new credentials.
+    pub async fn rotate_password(&self, role: &str, password: &str) -> anyhow::Result<()> {
+        access::alter_role_password(role, password).await?;
+        let (tx, rx) = oneshot::channel();
+        let authenticator = PasswordAuth::new(role.to_string(), password.to_string());
+        self.send(ClusterEvent::RotateCredentials(authenticator, tx))
+            .map_err(|_| anyhow::anyhow!("Cluster is not running"))?;
+        rx.await?
+    }
+}
+
 impl Deref for ClusterHandle {
     type Target = mpsc::UnboundedSender<ClusterEvent>;
 
@@ -72,13 +120,26 @@ pub struct Cluster {
     buffer_size: usize,
     recv_buffer_size: Option<u32>,
     send_buffer_size: Option<u32>,
+    compression: Option<CompressionType>,
     authenticator: PasswordAuth,
+    tls: Option<TlsConfig>,
+    read_only: ReadOnly,
+    heartbeat: Option<HeartbeatConfig>,
+    drain_timeout: Option<Duration>,
+    decode_offload_threshold: Option<usize>,
+    watchdog: Option<WatchdogConfig>,
     nodes: Nodes,
     should_build: bool,
     version: u8,
     registry: Registry,
     arc_ring: Option<ArcRing>,
     weak_rings: Vec<Box<WeakRing>>,
+    /// The uniform replication factor of the latest ring build
+    uniform_rf: Option<u8>,
+    /// Whether the ring should be rebuilt once the pending topology change is applied
+    auto_build: bool,
+    /// The events listener, which is the control connection of the cluster
+    events_listener: Option<EventsListener>,
     handle: Option<ClusterHandle>,
     inbox: ClusterInbox,
 }
@@ -87,6 +148,35 @@ impl Cluster {
     pub(crate) fn clone_handle(&self) -> Option<ClusterHandle> {
         self.handle.clone()
     }
+    /// Verify the credentials by connecting to every node one by one
+    async fn verify_credentials(&self, authenticator: &PasswordAuth) -> anyhow::Result<()> {
+        let mut addresses: Vec<SocketAddr> = self.nodes.keys().cloned().collect();
+        addresses.sort();
+        for address in addresses {
+            let mut attempt = 1;
+            loop {
+                let cql = CqlBuilder::new()
+                    .address(address)
+                    .recv_buffer_size(self.recv_buffer_size)
+                    .send_buffer_size(self.send_buffer_size)
+                    .compression(self.compression)
+                    .authenticator(authenticator.clone())
+                    .tls(self.tls.clone())
+                    .build();
+                match cql.await {
+                    Ok(_) => break,
+                    Err(e) if attempt >= VERIFY_CREDENTIALS_ATTEMPTS => {
+                        anyhow::bail!("Node {} rejected the rotated credentials: {}", address, e)
+                    }
+                    Err(_) => {
+                        attempt += 1;
+                        tokio::time::sleep(VERIFY_CREDENTIALS_DELAY).await;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
 /// Cluster Event type
 pub enum ClusterEvent {
@@ -100,6 +190,13 @@ pub enum ClusterEvent {
     RemoveNode(SocketAddr),
     /// Used by Scylla/dashboard to build new ring and expose the recent cluster topology
     BuildRing(u8),
+    /// Used to rotate the credentials of the driver, once the role password is altered
+    RotateCredentials(PasswordAuth, oneshot::Sender<anyhow::Result<()>>),
+    /// Used by the events listener to forward the server events, ie the topology changes
+    ServerEvent(Event),
+    /// Used by the events listener once its connection is closed, along with the address of its node if it was
+    /// connected, so it's respawned on another node
+    EventsListenerClosed(Option<SocketAddr>),
     /// Used by Scylla/dashboard to shutdown the cluster
     Shutdown,
 }
@@ -121,7 +218,11 @@ impl Builder for ClusterBuilder {
     type State = Cluster;
     fn build(self) -> Self::State {
         let (tx, rx) = mpsc::unbounded_channel::<ClusterEvent>();
-        let handle = Some(ClusterHandle { tx });
+        let read_only = self.read_only.unwrap_or_default();
+        let handle = Some(ClusterHandle {
+            tx,
+            read_only: read_only.clone(),
+        });
         let inbox = ClusterInbox { rx };
         // initialize global_ring
         let (arc_ring, _none) = initialize_ring(0, false);
@@ -133,13 +234,23 @@ impl Builder for ClusterBuilder {
             buffer_size: self.buffer_size.unwrap(),
             recv_buffer_size: self.recv_buffer_size.unwrap(),
             send_buffer_size: self.send_buffer_size.unwrap(),
+            compression: self.compression.unwrap(),
             authenticator: self.authenticator.unwrap(),
+            tls: self.tls.unwrap(),
+            read_only,
+            heartbeat: self.heartbeat.unwrap_or_default(),
+            drain_timeout: self.drain_timeout.unwrap_or_default(),
+            decode_offload_threshold: self.decode_offload_threshold.unwrap_or_default(),
+            watchdog: self.watchdog.unwrap_or_default(),
             nodes: HashMap::new(),
             should_build: false,
             version: 0,
             registry: HashMap::new(),
             arc_ring: Some(arc_ring),
             weak_rings: Vec::new(),
+            uniform_rf: None,
+            auto_build: false,
+            events_listener: None,
             handle,
             inbox,
         }
@@ -0,0 +1,436 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    app::{access::StatementSuggestion, stage::RequestPhases},
+    cql::{Consistency, ErrorCodes, Event},
+};
+use log::{Level, LevelFilter};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
+use tokio::sync::broadcast;
+
+/// The number of events retained for slow subscribers before they start lagging
+const EVENTS_CAPACITY: usize = 1024;
+
+static EVENTS: OnceLock<broadcast::Sender<DiagnosticEvent>> = OnceLock::new();
+static VERBOSITY: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
+/// The slow request threshold in micros, zero means disabled
+static SLOW_REQUEST_THRESHOLD: AtomicU64 = AtomicU64::new(0);
+/// The number of buckets of the missing replicas histogram
+pub const MISSING_REPLICAS_BUCKETS: usize = 8;
+static MISSING_REPLICAS: [AtomicU64; MISSING_REPLICAS_BUCKETS] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+/// The label of a connection, which identifies the stage, sender or receiver of a shard, or one of its reporters,
+/// in the diagnostic events, the logs and the metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ConnectionLabel {
+    /// The node address
+    pub node: SocketAddr,
+    /// The shard id of the connection
+    pub shard: u16,
+    /// The reporter id, None for the stage, the sender and the receiver of the connection
+    pub index: Option<u8>,
+}
+
+impl ConnectionLabel {
+    /// Create the label of the shard connection
+    pub fn shard(node: SocketAddr, shard: u16) -> Self {
+        Self {
+            node,
+            shard,
+            index: None,
+        }
+    }
+    /// Get the label of the reporter of the shard connection
+    pub fn reporter(self, index: u8) -> Self {
+        Self {
+            index: Some(index),
+            ..self
+        }
+    }
+}
+
+impl Display for ConnectionLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "address: {}, shard_id: {}", self.node, self.shard)?;
+        if let Some(index) = self.index {
+            write!(f, ", reporter_id: {}", index)?;
+        }
+        Ok(())
+    }
+}
+
+/// Structured diagnostic events emitted by the driver internals
+#[derive(Debug, Clone)]
+pub enum DiagnosticEvent {
+    /// A stage successfully opened a cql connection
+    ConnectionOpened {
+        /// The label of the shard connection
+        label: ConnectionLabel,
+    },
+    /// A reporter closed its session, as both the sender and receiver of the connection stopped
+    ConnectionClosed {
+        /// The label of the reporter connection
+        label: ConnectionLabel,
+    },
+    /// A draining reporter closed its session, once its in-flight requests were answered or its drain deadline
+    /// elapsed
+    ConnectionDrained {
+        /// The label of the reporter connection
+        label: ConnectionLabel,
+        /// The number of in-flight requests which were failed, as they weren't answered before the drain deadline
+        abandoned: usize,
+    },
+    /// The ring has been re/built
+    RingRebuilt {
+        /// The new ring version
+        version: u8,
+    },
+    /// A statement has been re-prepared on a shard after an unprepared error
+    Reprepared {
+        /// The prepared statement id
+        id: [u8; 16],
+        /// The prepared statement
+        statement: String,
+    },
+    /// A statement failed to be prepared
+    PrepareFailed {
+        /// The statement
+        statement: String,
+        /// The error reason
+        error: String,
+    },
+    /// A worker issued a retry of its request
+    RetryIssued {
+        /// The retried statement
+        statement: String,
+        /// The number of remaining retries
+        retries_left: usize,
+    },
+    /// A reporter didn't make any progress while having in-flight requests, therefore its stage is restarted
+    ReporterStalled {
+        /// The label of the reporter connection
+        label: ConnectionLabel,
+        /// The number of in-flight requests
+        in_flight: usize,
+        /// The elapsed duration since the reporter last made progress
+        idle: Duration,
+    },
+    /// A stage failed to connect to its shard, therefore it retries to connect after a delay
+    ReconnectScheduled {
+        /// The label of the shard connection
+        label: ConnectionLabel,
+        /// The delay before the next connection attempt
+        retry_in: Duration,
+        /// The error reason
+        error: String,
+    },
+    /// A connection didn't answer its heartbeat ping before its deadline, therefore its stage is restarted
+    HeartbeatFailed {
+        /// The label of the shard connection
+        label: ConnectionLabel,
+        /// The error reason
+        error: String,
+    },
+    /// A request took longer than the slow request threshold
+    SlowRequest {
+        /// The label of the reporter connection
+        label: ConnectionLabel,
+        /// The latency phases of the request
+        phases: RequestPhases,
+    },
+    /// A response carried a custom payload, which is set by the server-side plugins
+    CustomPayload {
+        /// The label of the reporter connection
+        label: ConnectionLabel,
+        /// The custom payload of the response
+        payload: HashMap<String, Vec<u8>>,
+    },
+    /// A response carried warnings, ie on large aggregations or on reading too many tombstones
+    ServerWarnings {
+        /// The label of the reporter connection
+        label: ConnectionLabel,
+        /// The warnings of the response
+        warnings: Vec<String>,
+    },
+    /// A request failed with an unavailable or timeout error, as not enough replicas answered it
+    ReplicasMissing {
+        /// The label of the reporter connection
+        label: ConnectionLabel,
+        /// The error code, ie `UnavailableException`, `ReadTimeout` or `WriteTimeout`
+        code: ErrorCodes,
+        /// The consistency level of the request
+        consistency: Consistency,
+        /// The number of replicas which were missing to achieve the consistency level
+        missing: i32,
+    },
+    /// The cluster received a server event, ie a topology, status or schema change
+    ServerEvent {
+        /// The server event
+        event: Event,
+    },
+    /// A statement is frequently executed as unprepared queries, see `enable_profiler`
+    HotStatement {
+        /// The suggestion to prepare the statement
+        suggestion: StatementSuggestion,
+    },
+}
+
+impl DiagnosticEvent {
+    /// Get the label of the connection which emitted the event, if any
+    pub fn label(&self) -> Option<ConnectionLabel> {
+        match self {
+            DiagnosticEvent::ConnectionOpened { label }
+            | DiagnosticEvent::ConnectionClosed { label }
+            | DiagnosticEvent::ConnectionDrained { label, .. }
+            | DiagnosticEvent::ReporterStalled { label, .. }
+            | DiagnosticEvent::ReconnectScheduled { label, .. }
+            | DiagnosticEvent::HeartbeatFailed { label, .. }
+            | DiagnosticEvent::SlowRequest { label, .. }
+            | DiagnosticEvent::CustomPayload { label, .. }
+            | DiagnosticEvent::ServerWarnings { label, .. }
+            | DiagnosticEvent::ReplicasMissing { label, .. } => Some(*label),
+            _ => None,
+        }
+    }
+    /// Get the log level of the event
+    pub fn level(&self) -> Level {
+        match self {
+            DiagnosticEvent::ConnectionOpened { .. } => Level::Info,
+            DiagnosticEvent::ConnectionClosed { .. } => Level::Warn,
+            DiagnosticEvent::ConnectionDrained { abandoned: 0, .. } => Level::Info,
+            DiagnosticEvent::ConnectionDrained { .. } => Level::Warn,
+            DiagnosticEvent::RingRebuilt { .. } => Level::Info,
+            DiagnosticEvent::Reprepared { .. } => Level::Info,
+            DiagnosticEvent::PrepareFailed { .. } => Level::Error,
+            DiagnosticEvent::RetryIssued { .. } => Level::Debug,
+            DiagnosticEvent::ReporterStalled { .. } => Level::Error,
+            DiagnosticEvent::ReconnectScheduled { .. } => Level::Error,
+            DiagnosticEvent::HeartbeatFailed { .. } => Level::Error,
+            DiagnosticEvent::SlowRequest { .. } => Level::Warn,
+            DiagnosticEvent::CustomPayload { .. } => Level::Debug,
+            DiagnosticEvent::ServerWarnings { .. } => Level::Warn,
+            DiagnosticEvent::ReplicasMissing { .. } => Level::Warn,
+            DiagnosticEvent::ServerEvent { .. } => Level::Info,
+            DiagnosticEvent::HotStatement { .. } => Level::Warn,
+        }
+    }
+}
+
+impl Display for DiagnosticEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticEvent::ConnectionOpened { label } => write!(f, "{}, opened connection", label),
+            DiagnosticEvent::ConnectionClosed { label } => write!(f, "{}, closed session", label),
+            DiagnosticEvent::ConnectionDrained { label, abandoned } => write!(
+                f,
+                "{}, drained session, abandoned {} in-flight requests",
+                label, abandoned
+            ),
+            DiagnosticEvent::RingRebuilt { version } => write!(f, "Rebuilt the ring, version: {}", version),
+            DiagnosticEvent::Reprepared { id, statement } => {
+                write!(f, "Re-prepared statement: '{}', id: '{:?}'", statement, id)
+            }
+            DiagnosticEvent::PrepareFailed { statement, error } => {
+                write!(f, "Failed to prepare statement: {}, error: {}", statement, error)
+            }
+            DiagnosticEvent::RetryIssued {
+                statement,
+                retries_left,
+            } => write!(f, "Retrying statement: '{}', retries left: {}", statement, retries_left),
+            DiagnosticEvent::ReporterStalled { label, in_flight, idle } => write!(
+                f,
+                "{}, stalled with {} in-flight requests for {:?}",
+                label, in_flight, idle
+            ),
+            DiagnosticEvent::ReconnectScheduled { label, retry_in, error } => {
+                write!(f, "{}, failed to connect: {}, retrying in {:?}", label, error, retry_in)
+            }
+            DiagnosticEvent::HeartbeatFailed { label, error } => {
+                write!(f, "{}, failed to answer the heartbeat: {}, restarting", label, error)
+            }
+            DiagnosticEvent::SlowRequest { label, phases } => write!(
+                f,
+                "{}, slow request: {:?} (queue: {:?}, wire: {:?}, server: {:?})",
+                label,
+                phases.total(),
+                phases.queue,
+                phases.wire,
+                phases.server
+            ),
+            DiagnosticEvent::CustomPayload { label, payload } => write!(
+                f,
+                "{}, custom payload keys: {:?}",
+                label,
+                payload.keys().collect::<Vec<_>>()
+            ),
+            DiagnosticEvent::ServerWarnings { label, warnings } => {
+                write!(f, "{}, server warnings: {}", label, warnings.join("; "))
+            }
+            DiagnosticEvent::ReplicasMissing {
+                label,
+                code,
+                consistency,
+                missing,
+            } => write!(
+                f,
+                "{}, {:?} with {} missing replicas at consistency {}",
+                label, code, missing, consistency
+            ),
+            DiagnosticEvent::ServerEvent { event } => write!(f, "Received server event: {}", event),
+            DiagnosticEvent::HotStatement { suggestion } => write!(f, "{}", suggestion),
+        }
+    }
+}
+
+fn events() -> &'static broadcast::Sender<DiagnosticEvent> {
+    EVENTS.get_or_init(|| broadcast::channel(EVENTS_CAPACITY).0)
+}
+
+/// Subscribe to the diagnostic events emitted after this call
+pub fn subscribe() -> broadcast::Receiver<DiagnosticEvent> {
+    events().subscribe()
+}
+
+/// Set the verbosity of the diagnostic events log emission at runtime
+pub fn set_verbosity(verbosity: LevelFilter) {
+    VERBOSITY.store(verbosity as usize, Ordering::Relaxed);
+}
+
+/// Get the current verbosity of the diagnostic events log emission
+pub fn verbosity() -> LevelFilter {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Set the latency threshold above which the requests are reported as slow, None disables it
+pub fn set_slow_request_threshold(threshold: Option<Duration>) {
+    let micros = threshold.map_or(0, |threshold| (threshold.as_micros() as u64).max(1));
+    SLOW_REQUEST_THRESHOLD.store(micros, Ordering::Relaxed);
+}
+
+/// Get the latency threshold above which the requests are reported as slow
+pub fn slow_request_threshold() -> Option<Duration> {
+    match SLOW_REQUEST_THRESHOLD.load(Ordering::Relaxed) {
+        0 => None,
+        micros => Some(Duration::from_micros(micros)),
+    }
+}
+
+/// Record the number of replicas which were missing to achieve the consistency level of a failed request
+pub fn record_missing_replicas(missing: i32) {
+    let bucket = (missing.max(0) as usize).min(MISSING_REPLICAS_BUCKETS - 1);
+    MISSING_REPLICAS[bucket].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Get the histogram of the missing replicas, where the bucket `i` counts the failed requests which missed `i`
+/// replicas, while the last bucket counts the ones which missed at least as many replicas
+pub fn missing_replicas_histogram() -> [u64; MISSING_REPLICAS_BUCKETS] {
+    let mut histogram = [0; MISSING_REPLICAS_BUCKETS];
+    for (count, bucket) in histogram.iter_mut().zip(MISSING_REPLICAS.iter()) {
+        *count = bucket.load(Ordering::Relaxed);
+    }
+    histogram
+}
+
+/// Emit a diagnostic event to the subscribers, and log it if it's within the current verbosity
+pub fn emit(event: DiagnosticEvent) {
+    let level = event.level();
+    if level <= verbosity() {
+        log::log!(level, "{}", event);
+    }
+    // an error only means there are no subscribers at the moment
+    events().send(event).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::worker::{PrepareWorker, Worker};
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    /// Receive the pending events which match the filter, as the other tests emit events concurrently
+    fn pending<F: Fn(&DiagnosticEvent) -> bool>(
+        events: &mut broadcast::Receiver<DiagnosticEvent>,
+        filter: F,
+    ) -> Vec<DiagnosticEvent> {
+        let mut pending = Vec::new();
+        loop {
+            match events.try_recv() {
+                Ok(event) if filter(&event) => pending.push(event),
+                Ok(_) | Err(TryRecvError::Lagged(_)) => (),
+                Err(_) => return pending,
+            }
+        }
+    }
+
+    #[test]
+    fn subscribe_and_emit() {
+        let label = ConnectionLabel::shard("10.0.0.1:19042".parse().unwrap(), 3);
+        let is_labeled = |event: &DiagnosticEvent| event.label().map(|label| label.node) == Some(label.node);
+        let mut early = subscribe();
+        emit(DiagnosticEvent::ConnectionOpened { label });
+        let mut late = subscribe();
+        emit(DiagnosticEvent::ReconnectScheduled {
+            label,
+            retry_in: Duration::from_secs(5),
+            error: "connection refused".to_string(),
+        });
+        emit(DiagnosticEvent::ConnectionClosed {
+            label: label.reporter(1),
+        });
+        let events = pending(&mut early, is_labeled);
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[0], DiagnosticEvent::ConnectionOpened { .. }));
+        assert!(matches!(events[1], DiagnosticEvent::ReconnectScheduled { .. }));
+        assert_eq!(events[1].level(), Level::Error);
+        assert_eq!(
+            events[1].to_string(),
+            "address: 10.0.0.1:19042, shard_id: 3, failed to connect: connection refused, retrying in 5s"
+        );
+        assert_eq!(events[2].label(), Some(label.reporter(1)));
+        // the subscribers only receive the events emitted after they subscribed
+        let events = pending(&mut late, is_labeled);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], DiagnosticEvent::ReconnectScheduled { .. }));
+    }
+
+    #[test]
+    fn reprepared_only_on_reprepare() {
+        let statement = "SELECT * FROM diagnostics_test.reprepared";
+        let is_reprepared = |event: &DiagnosticEvent| matches!(event, DiagnosticEvent::Reprepared { statement: reprepared, .. } if reprepared == statement);
+        let mut events = subscribe();
+        PrepareWorker::boxed([1; 16], statement)
+            .handle_response(Vec::new())
+            .unwrap();
+        assert!(pending(&mut events, is_reprepared).is_empty());
+        PrepareWorker::reprepare([1; 16], statement)
+            .handle_response(Vec::new())
+            .unwrap();
+        assert_eq!(pending(&mut events, is_reprepared).len(), 1);
+    }
+}
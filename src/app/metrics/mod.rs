@@ -0,0 +1,499 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    app::{
+        access::RequestType,
+        diagnostics::ConnectionLabel,
+        stage::ReporterHandle,
+        worker::{Worker, WorkerError, WorkerFuture},
+    },
+    cql::ErrorCodes,
+};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// The number of buckets of the latency histograms, where the bucket `i` counts the latencies up to `2^i` micros,
+/// while the last bucket counts the ones above `2^(LATENCY_BUCKETS - 2)` micros (about 33 seconds)
+pub const LATENCY_BUCKETS: usize = 27;
+/// The number of replica indexes whose routed requests are counted, where the last one counts the requests routed
+/// to the replicas from that index onwards
+pub const REPLICA_INDEXES: usize = 8;
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// The global metrics registry
+#[derive(Default)]
+struct Metrics {
+    request_types: [RequestMetrics; RequestType::ALL.len()],
+    errors: Mutex<HashMap<ErrorCodes, u64>>,
+    connections: RwLock<HashMap<(SocketAddr, u16), Arc<RequestMetrics>>>,
+    replicas: [AtomicU64; REPLICA_INDEXES],
+}
+
+fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Default::default)
+}
+
+/// A latency histogram with exponential buckets, which can be recorded concurrently
+#[derive(Default)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl LatencyHistogram {
+    /// Record the latency
+    pub fn record(&self, latency: Duration) {
+        let micros = latency.as_micros() as u64;
+        let bucket = if micros <= 1 {
+            0
+        } else {
+            (64 - (micros - 1).leading_zeros() as usize).min(LATENCY_BUCKETS - 1)
+        };
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+    /// Get the snapshot of the histogram
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut buckets = [0; LATENCY_BUCKETS];
+        for (count, bucket) in buckets.iter_mut().zip(self.buckets.iter()) {
+            *count = bucket.load(Ordering::Relaxed);
+        }
+        HistogramSnapshot {
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            sum: Duration::from_micros(self.sum_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The snapshot of a latency histogram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    /// The number of latencies recorded in each bucket, see `LATENCY_BUCKETS`
+    pub buckets: [u64; LATENCY_BUCKETS],
+    /// The number of recorded latencies
+    pub count: u64,
+    /// The sum of the recorded latencies
+    pub sum: Duration,
+}
+
+impl HistogramSnapshot {
+    /// Get the upper bound of the bucket, None for the last one which is unbounded
+    pub fn upper_bound(bucket: usize) -> Option<Duration> {
+        if bucket + 1 < LATENCY_BUCKETS {
+            Some(Duration::from_micros(1 << bucket))
+        } else {
+            None
+        }
+    }
+    /// Get the mean of the recorded latencies
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::default(),
+            count => Duration::from_nanos((self.sum.as_nanos() / count as u128) as u64),
+        }
+    }
+    /// Get the upper bound of the bucket which holds the quantile (ie 0.99) of the recorded latencies, None if
+    /// there are no latencies, or the quantile falls into the last bucket
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut cumulative = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= rank {
+                return Self::upper_bound(bucket);
+            }
+        }
+        None
+    }
+}
+
+/// The request metrics of a request type or of a shard connection
+#[derive(Default)]
+pub struct RequestMetrics {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    latency: LatencyHistogram,
+}
+
+impl RequestMetrics {
+    /// Record a completed request, along with its latency if it's known
+    pub(crate) fn record(&self, latency: Option<Duration>, failed: bool) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(latency) = latency {
+            self.latency.record(latency);
+        }
+    }
+    /// Get the snapshot of the request metrics
+    pub fn snapshot(&self) -> RequestStats {
+        RequestStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            latency: self.latency.snapshot(),
+        }
+    }
+}
+
+/// The snapshot of the request metrics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestStats {
+    /// The number of completed requests
+    pub requests: u64,
+    /// The number of failed requests
+    pub errors: u64,
+    /// The latency histogram of the requests
+    pub latency: HistogramSnapshot,
+}
+
+/// The snapshot of the driver metrics
+#[derive(Debug, Clone)]
+pub struct MetricsSnapshot {
+    /// The metrics of the requests sent through the access traits, by request type
+    pub request_types: Vec<(RequestType, RequestStats)>,
+    /// The number of cql errors, by error code
+    pub errors: Vec<(ErrorCodes, u64)>,
+    /// The metrics of the requests, by shard connection, ordered by node and shard
+    pub connections: Vec<(ConnectionLabel, RequestStats)>,
+    /// The number of requests routed by the load balancing policies, by replica index (0 being the primary
+    /// replica), see `REPLICA_INDEXES`
+    pub replicas: [u64; REPLICA_INDEXES],
+}
+
+impl MetricsSnapshot {
+    /// Get the number of completed requests by node, which is their distribution over the replicas
+    pub fn nodes(&self) -> Vec<(SocketAddr, u64)> {
+        let mut nodes: Vec<(SocketAddr, u64)> = Vec::new();
+        for (label, stats) in self.connections.iter() {
+            match nodes.last_mut() {
+                Some((node, requests)) if *node == label.node => *requests += stats.requests,
+                _ => nodes.push((label.node, stats.requests)),
+            }
+        }
+        nodes
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl MetricsSnapshot {
+    /// Render the metrics in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        use std::fmt::Write;
+        let mut text = String::new();
+        let request_types: Vec<(String, &RequestStats)> = self
+            .request_types
+            .iter()
+            .map(|(request_type, stats)| (format!("type=\"{}\"", request_type.as_str()), stats))
+            .collect();
+        render_requests(
+            &mut text,
+            "scylla_requests",
+            "the requests sent through the access traits",
+            &request_types,
+        );
+        let connections: Vec<(String, &RequestStats)> = self
+            .connections
+            .iter()
+            .map(|(label, stats)| (format!("node=\"{}\",shard=\"{}\"", label.node, label.shard), stats))
+            .collect();
+        render_requests(
+            &mut text,
+            "scylla_node_requests",
+            "the requests by shard connection",
+            &connections,
+        );
+        writeln!(
+            text,
+            "# HELP scylla_cql_errors_total The number of cql errors by error code"
+        )
+        .ok();
+        writeln!(text, "# TYPE scylla_cql_errors_total counter").ok();
+        for (code, count) in self.errors.iter() {
+            writeln!(text, "scylla_cql_errors_total{{code=\"{:?}\"}} {}", code, count).ok();
+        }
+        writeln!(
+            text,
+            "# HELP scylla_replica_requests_total The number of routed requests by replica index"
+        )
+        .ok();
+        writeln!(text, "# TYPE scylla_replica_requests_total counter").ok();
+        for (replica, count) in self.replicas.iter().enumerate() {
+            writeln!(
+                text,
+                "scylla_replica_requests_total{{replica=\"{}\"}} {}",
+                replica, count
+            )
+            .ok();
+        }
+        text
+    }
+}
+
+/// Render the counters and the latency histogram of the labeled request stats
+#[cfg(feature = "prometheus")]
+fn render_requests(text: &mut String, name: &str, help: &str, stats: &[(String, &RequestStats)]) {
+    use std::fmt::Write;
+    writeln!(text, "# HELP {}_total The number of {}", name, help).ok();
+    writeln!(text, "# TYPE {}_total counter", name).ok();
+    for (labels, stats) in stats.iter() {
+        writeln!(text, "{}_total{{{}}} {}", name, labels, stats.requests).ok();
+    }
+    writeln!(text, "# HELP {}_errors_total The number of failed {}", name, help).ok();
+    writeln!(text, "# TYPE {}_errors_total counter", name).ok();
+    for (labels, stats) in stats.iter() {
+        writeln!(text, "{}_errors_total{{{}}} {}", name, labels, stats.errors).ok();
+    }
+    writeln!(text, "# HELP {}_latency_seconds The latency of {}", name, help).ok();
+    writeln!(text, "# TYPE {}_latency_seconds histogram", name).ok();
+    for (labels, stats) in stats.iter() {
+        let mut cumulative = 0;
+        for (bucket, count) in stats.latency.buckets.iter().enumerate() {
+            cumulative += count;
+            let le = HistogramSnapshot::upper_bound(bucket)
+                .map_or_else(|| "+Inf".to_string(), |bound| bound.as_secs_f64().to_string());
+            writeln!(
+                text,
+                "{}_latency_seconds_bucket{{{},le=\"{}\"}} {}",
+                name, labels, le, cumulative
+            )
+            .ok();
+        }
+        writeln!(
+            text,
+            "{}_latency_seconds_sum{{{}}} {}",
+            name,
+            labels,
+            stats.latency.sum.as_secs_f64()
+        )
+        .ok();
+        writeln!(
+            text,
+            "{}_latency_seconds_count{{{}}} {}",
+            name, labels, stats.latency.count
+        )
+        .ok();
+    }
+}
+
+/// Get the snapshot of the driver metrics
+pub fn snapshot() -> MetricsSnapshot {
+    let metrics = metrics();
+    let request_types = RequestType::ALL
+        .iter()
+        .map(|request_type| (*request_type, metrics.request_types[*request_type as usize].snapshot()))
+        .collect();
+    let mut errors: Vec<(ErrorCodes, u64)> = metrics
+        .errors
+        .lock()
+        .expect("Metrics lock is poisoned")
+        .iter()
+        .map(|(code, count)| (*code, *count))
+        .collect();
+    errors.sort_by_key(|(code, _)| *code as i32);
+    let mut connections: Vec<(ConnectionLabel, RequestStats)> = metrics
+        .connections
+        .read()
+        .expect("Metrics lock is poisoned")
+        .iter()
+        .map(|((node, shard), metrics)| (ConnectionLabel::shard(*node, *shard), metrics.snapshot()))
+        .collect();
+    connections.sort_by_key(|(label, _)| *label);
+    let mut replicas = [0; REPLICA_INDEXES];
+    for (count, replica) in replicas.iter_mut().zip(metrics.replicas.iter()) {
+        *count = replica.load(Ordering::Relaxed);
+    }
+    MetricsSnapshot {
+        request_types,
+        errors,
+        connections,
+        replicas,
+    }
+}
+
+/// Get the request metrics of the shard connection, which are shared by its reporters
+pub(crate) fn connection(label: ConnectionLabel) -> Arc<RequestMetrics> {
+    metrics()
+        .connections
+        .write()
+        .expect("Metrics lock is poisoned")
+        .entry((label.node, label.shard))
+        .or_default()
+        .clone()
+}
+
+/// Record a request routed to the replica with the given index
+pub(crate) fn record_replica(replica_index: usize) {
+    metrics().replicas[replica_index.min(REPLICA_INDEXES - 1)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record a cql error by its code
+pub(crate) fn record_error(code: ErrorCodes) {
+    *metrics()
+        .errors
+        .lock()
+        .expect("Metrics lock is poisoned")
+        .entry(code)
+        .or_default() += 1;
+}
+
+/// Measures the latency of the request of the wrapped worker, from its creation until it's handled, along with
+/// its outcome, in the metrics of its request type.
+pub struct MeasuredWorker {
+    worker: Box<dyn Worker>,
+    request_type: RequestType,
+    started: Instant,
+}
+
+impl MeasuredWorker {
+    /// Wrap the worker of a request of the provided type
+    pub fn boxed(worker: Box<dyn Worker>, request_type: RequestType) -> Box<Self> {
+        Box::new(Self {
+            worker,
+            request_type,
+            started: Instant::now(),
+        })
+    }
+    fn record(&self, failed: bool) {
+        metrics().request_types[self.request_type as usize].record(Some(self.started.elapsed()), failed);
+    }
+}
+
+impl Worker for MeasuredWorker {
+    fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()> {
+        self.record(false);
+        self.worker.handle_response(giveload)
+    }
+    fn handle_error(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> anyhow::Result<()> {
+        self.record(true);
+        self.worker.handle_error(error, reporter)
+    }
+    fn handle_response_async(self: Box<Self>, giveload: Vec<u8>) -> WorkerFuture {
+        self.record(false);
+        self.worker.handle_response_async(giveload)
+    }
+    fn handle_error_async(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> WorkerFuture {
+        self.record(true);
+        self.worker.handle_error_async(error, reporter)
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.worker.timeout()
+    }
+    fn dispatched(&self, label: &ConnectionLabel) {
+        self.worker.dispatched(label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn micros(micros: u64) -> Duration {
+        Duration::from_micros(micros)
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        let histogram = LatencyHistogram::default();
+        let latencies = [
+            0,
+            1,
+            2,
+            3,
+            4,
+            5,
+            1000,
+            1024,
+            1025,
+            1 << 25,
+            (1 << 25) + 1,
+            u32::MAX as u64,
+        ];
+        for latency in latencies {
+            histogram.record(micros(latency));
+        }
+        let snapshot = histogram.snapshot();
+        let mut expected = [0; LATENCY_BUCKETS];
+        // the bucket `i` counts the latencies within `(2^(i-1), 2^i]` micros
+        for (bucket, count) in [(0, 2), (1, 1), (2, 2), (3, 1), (10, 2), (11, 1), (25, 1), (26, 2)] {
+            expected[bucket] = count;
+        }
+        assert_eq!(snapshot.buckets, expected);
+        assert_eq!(snapshot.count, latencies.len() as u64);
+        assert_eq!(snapshot.sum, micros(latencies.iter().sum()));
+        // every latency is bounded by its bucket, except the ones of the last unbounded bucket
+        assert_eq!(HistogramSnapshot::upper_bound(10), Some(micros(1024)));
+        assert_eq!(
+            HistogramSnapshot::upper_bound(LATENCY_BUCKETS - 2),
+            Some(micros(1 << 25))
+        );
+        assert_eq!(HistogramSnapshot::upper_bound(LATENCY_BUCKETS - 1), None);
+    }
+
+    #[test]
+    fn histogram_quantiles() {
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.snapshot().quantile(0.5), None);
+        assert_eq!(histogram.snapshot().mean(), Duration::default());
+        for latency in (1..=100).map(|i| i * 10) {
+            histogram.record(micros(latency));
+        }
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.mean(), micros(505));
+        // the 50th latency is 500 micros, and the 99th one is 990 micros
+        assert_eq!(snapshot.quantile(0.5), Some(micros(512)));
+        assert_eq!(snapshot.quantile(0.99), Some(micros(1024)));
+        assert_eq!(snapshot.quantile(0.0), Some(micros(16)));
+        // the quantile of the last bucket is unbounded
+        histogram.record(Duration::from_secs(60));
+        assert_eq!(histogram.snapshot().quantile(1.0), None);
+    }
+
+    #[test]
+    fn request_metrics() {
+        let metrics = RequestMetrics::default();
+        metrics.record(Some(micros(3)), false);
+        metrics.record(None, true);
+        let stats = metrics.snapshot();
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.errors, 1);
+        // the latency is only recorded when it's known
+        assert_eq!(stats.latency.count, 1);
+        assert_eq!(stats.latency.buckets[2], 1);
+    }
+
+    #[test]
+    fn error_counts() {
+        let count = |code: ErrorCodes| {
+            snapshot()
+                .errors
+                .into_iter()
+                .find(|(error, _)| *error == code)
+                .map_or(0, |(_, count)| count)
+        };
+        let (truncate, function) = (count(ErrorCodes::TruncateError), count(ErrorCodes::FunctionFailure));
+        record_error(ErrorCodes::TruncateError);
+        record_error(ErrorCodes::FunctionFailure);
+        record_error(ErrorCodes::TruncateError);
+        assert_eq!(count(ErrorCodes::TruncateError), truncate + 2);
+        assert_eq!(count(ErrorCodes::FunctionFailure), function + 1);
+        // the errors are ordered by code
+        let codes: Vec<i32> = snapshot().errors.iter().map(|(code, _)| *code as i32).collect();
+        assert!(codes.windows(2).all(|codes| codes[0] < codes[1]));
+    }
+}
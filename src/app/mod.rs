@@ -10,8 +10,12 @@ mod application;
 pub mod access;
 /// Cluster application
 pub mod cluster;
+/// Diagnostics event bus which applications can subscribe to
+pub mod diagnostics;
 /// Listener application which monitors for incoming connections
 pub mod listener;
+/// Metrics of the requests, by request type and by shard connection
+pub mod metrics;
 /// Node application which manages scylla nodes
 pub mod node;
 /// The ring, which manages scylla access
@@ -23,6 +27,7 @@ pub mod websocket;
 /// Workers which can be used when sending requests to handle the responses
 pub mod worker;
 
+pub use crate::cql::{Session, SessionBuilder};
 pub use application::*;
 use backstage::*;
 use log::*;
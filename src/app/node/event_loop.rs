@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use futures::future::Abortable;
 
 #[async_trait::async_trait]
 impl EventLoop<ClusterHandle> for Node {
@@ -25,6 +26,15 @@ impl EventLoop<ClusterHandle> for Node {
                                 if reporters_handles_ref.len() == self.shard_count as usize {
                                     // reporters_handles should be passed to cluster supervisor
                                     if let Some(reporters_handles) = self.reporters_handles.take() {
+                                        // start the watchdog to monitor the progress of the reporters
+                                        let watchdog = Watchdog::new(
+                                            self.stages.clone(),
+                                            &reporters_handles,
+                                            self.watchdog_config.unwrap_or_default(),
+                                        );
+                                        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+                                        tokio::spawn(Abortable::new(watchdog.run(), abort_registration));
+                                        self.watchdog.replace(abort_handle);
                                         let event =
                                             ClusterEvent::RegisterReporters(self.service.clone(), reporters_handles);
                                         supervisor.send(event).ok();
@@ -61,8 +71,18 @@ impl EventLoop<ClusterHandle> for Node {
                         let event = ClusterEvent::Service(self.service.clone());
                         supervisor.send(event).ok();
                     }
+                    NodeEvent::RotateCredentials(authenticator) => {
+                        for stage in self.stages.values() {
+                            stage.send(StageEvent::RotateCredentials(authenticator.clone())).ok();
+                        }
+                        self.authenticator = authenticator;
+                    }
                     NodeEvent::Shutdown => {
                         self.handle = None;
+                        // abort the watchdog, as it holds the stages handles
+                        if let Some(watchdog) = self.watchdog.take() {
+                            watchdog.abort();
+                        }
                         self.service.update_status(ServiceStatus::Stopping);
                         // shutdown children (stages)
                         for (_, stage) in self.stages.drain() {
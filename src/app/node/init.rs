@@ -19,7 +19,13 @@ impl Init<ClusterHandle> for Node {
                     .buffer_size(self.buffer_size)
                     .recv_buffer_size(self.recv_buffer_size)
                     .send_buffer_size(self.send_buffer_size)
+                    .compression(self.compression)
                     .authenticator(self.authenticator.clone())
+                    .tls(self.tls.clone())
+                    .read_only(self.read_only.clone())
+                    .heartbeat(self.heartbeat)
+                    .drain_timeout(self.drain_timeout)
+                    .decode_offload_threshold(self.decode_offload_threshold)
                     .build();
                 if let Some(stage_handle) = stage.clone_handle() {
                     self.stages.insert(shard_id, stage_handle);
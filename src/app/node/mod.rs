@@ -3,18 +3,23 @@
 
 use super::{
     cluster::{ClusterEvent, ClusterHandle},
-    stage::{ReportersHandles, StageBuilder, StageEvent, StageHandle},
+    stage::{HeartbeatConfig, ReportersHandles, StageBuilder, StageEvent, StageHandle},
     *,
 };
+use futures::future::AbortHandle;
 use std::{
     collections::HashMap,
     net::SocketAddr,
     ops::{Deref, DerefMut},
+    time::Duration,
 };
+use watchdog::Watchdog;
+pub use watchdog::WatchdogConfig;
 
 mod event_loop;
 mod init;
 mod terminating;
+mod watchdog;
 
 // Node builder
 builder!(NodeBuilder {
@@ -25,7 +30,14 @@ builder!(NodeBuilder {
     buffer_size: usize,
     recv_buffer_size: Option<u32>,
     send_buffer_size: Option<u32>,
-    authenticator: PasswordAuth
+    compression: Option<CompressionType>,
+    authenticator: PasswordAuth,
+    tls: Option<TlsConfig>,
+    read_only: ReadOnly,
+    heartbeat: Option<HeartbeatConfig>,
+    drain_timeout: Option<Duration>,
+    decode_offload_threshold: Option<usize>,
+    watchdog: Option<WatchdogConfig>
 });
 
 /// NodeHandle to be passed to the children (Stage)
@@ -68,6 +80,8 @@ pub enum NodeEvent {
     RegisterReporters(Service, ReportersHandles),
     /// To keep the node with up to date stage service
     Service(Service),
+    /// Propagate the rotated credentials to the stages.
+    RotateCredentials(PasswordAuth),
 }
 /// Node state
 pub struct Node {
@@ -80,7 +94,16 @@ pub struct Node {
     buffer_size: usize,
     recv_buffer_size: Option<u32>,
     send_buffer_size: Option<u32>,
+    compression: Option<CompressionType>,
     authenticator: PasswordAuth,
+    tls: Option<TlsConfig>,
+    read_only: ReadOnly,
+    heartbeat: Option<HeartbeatConfig>,
+    drain_timeout: Option<Duration>,
+    decode_offload_threshold: Option<usize>,
+    /// The watchdog of the reporters, or the default one
+    watchdog_config: Option<WatchdogConfig>,
+    watchdog: Option<AbortHandle>,
     handle: Option<NodeHandle>,
     inbox: NodeInbox,
 }
@@ -108,7 +131,15 @@ impl Builder for NodeBuilder {
             buffer_size: self.buffer_size.unwrap(),
             recv_buffer_size: self.recv_buffer_size.unwrap(),
             send_buffer_size: self.send_buffer_size.unwrap(),
+            compression: self.compression.unwrap(),
             authenticator: self.authenticator.unwrap(),
+            tls: self.tls.unwrap(),
+            read_only: self.read_only.unwrap(),
+            heartbeat: self.heartbeat.unwrap(),
+            drain_timeout: self.drain_timeout.unwrap(),
+            decode_offload_threshold: self.decode_offload_threshold.unwrap(),
+            watchdog_config: self.watchdog.unwrap(),
+            watchdog: None,
             handle,
             inbox,
         }
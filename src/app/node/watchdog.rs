@@ -0,0 +1,150 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::app::{
+    diagnostics::{self, ConnectionLabel, DiagnosticEvent},
+    stage::ReporterProgress,
+};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+/// The watchdog of the nodes, which checks the progress of their reporters at the interval, and restarts the stage
+/// of a reporter which has in-flight requests but hasn't made any progress for the stalled threshold.
+///
+/// Note: the default interval is 5 seconds, and the default stalled threshold is 30 seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct WatchdogConfig {
+    interval: Duration,
+    stalled_threshold: Duration,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5), Duration::from_secs(30))
+    }
+}
+
+impl WatchdogConfig {
+    /// Create the watchdog config, where the interval is at least one milli
+    pub fn new(interval: Duration, stalled_threshold: Duration) -> Self {
+        Self {
+            interval: interval.max(Duration::from_millis(1)),
+            stalled_threshold,
+        }
+    }
+    /// Get the interval at which the progress of the reporters is checked
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+    /// Get the duration after which a reporter with in-flight requests and no progress is considered stalled
+    pub fn stalled_threshold(&self) -> Duration {
+        self.stalled_threshold
+    }
+}
+
+/// The node watchdog which restarts the stages of stalled reporters.
+pub(crate) struct Watchdog {
+    stages: HashMap<u16, StageHandle>,
+    reporters: Vec<(ConnectionLabel, Arc<ReporterProgress>)>,
+    config: WatchdogConfig,
+}
+
+impl Watchdog {
+    /// Create a new watchdog over the node stages and their registered reporters
+    pub(crate) fn new(
+        stages: HashMap<u16, StageHandle>,
+        reporters_handles: &HashMap<SocketAddr, ReportersHandles>,
+        config: WatchdogConfig,
+    ) -> Self {
+        let reporters = reporters_handles
+            .values()
+            .flat_map(|reporters_handles| reporters_handles.values())
+            .map(|handle| (handle.label(), handle.progress()))
+            .collect();
+        Self {
+            stages,
+            reporters,
+            config,
+        }
+    }
+
+    /// Run the watchdog, it should be aborted once the node is shutting down,
+    /// as it holds the stages handles.
+    pub(crate) async fn run(self) {
+        let mut interval = tokio::time::interval(self.config.interval);
+        loop {
+            interval.tick().await;
+            let mut stalled_shards = HashSet::new();
+            for (label, progress) in self.reporters.iter() {
+                let (in_flight, idle) = (progress.in_flight(), progress.idle());
+                if in_flight > 0 && idle > self.config.stalled_threshold {
+                    diagnostics::emit(DiagnosticEvent::ReporterStalled {
+                        label: *label,
+                        in_flight,
+                        idle,
+                    });
+                    stalled_shards.insert(label.shard);
+                }
+            }
+            for shard_id in stalled_shards {
+                if let Some(stage) = self.stages.get(&shard_id) {
+                    stage.send(StageEvent::Restart).ok();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{sync::mpsc::UnboundedReceiver, time::timeout};
+
+    /// Stub the stages of the node shards, along with their reporters, and start the watchdog over them
+    fn watchdog(
+        shard_count: u16,
+        config: WatchdogConfig,
+    ) -> (Vec<Arc<ReporterProgress>>, Vec<UnboundedReceiver<StageEvent>>) {
+        let node: SocketAddr = ([127, 0, 0, 1], 9042).into();
+        let (mut stages, mut reporters_handles, mut progress, mut events) =
+            (HashMap::new(), HashMap::new(), Vec::new(), Vec::new());
+        for shard_id in 0..shard_count {
+            let (stage, rx) = StageHandle::detached();
+            let (handles, _) = ReportersHandles::detached(ConnectionLabel::shard(node, shard_id), 1);
+            progress.push(handles.values().next().unwrap().progress());
+            stages.insert(shard_id, stage);
+            reporters_handles.insert(SocketAddr::new(node.ip(), shard_id), handles);
+            events.push(rx);
+        }
+        tokio::spawn(Watchdog::new(stages, &reporters_handles, config).run());
+        (progress, events)
+    }
+
+    #[test]
+    fn config() {
+        let config = WatchdogConfig::default();
+        assert_eq!(config.interval(), Duration::from_secs(5));
+        assert_eq!(config.stalled_threshold(), Duration::from_secs(30));
+        assert_eq!(
+            WatchdogConfig::new(Duration::ZERO, Duration::ZERO).interval(),
+            Duration::from_millis(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn restart_stalled_reporter() {
+        let config = WatchdogConfig::new(Duration::from_millis(10), Duration::from_secs(60));
+        let (progress, mut stages) = watchdog(3, config);
+        // the first reporter has in-flight requests, but hasn't made any progress since it started
+        progress[0].set_in_flight(2);
+        // the second one has in-flight requests, and keeps making progress
+        progress[1].set_in_flight(2);
+        progress[1].touch();
+        // and the last one is idle
+        let restart = timeout(Duration::from_secs(5), stages[0].recv()).await.unwrap();
+        assert!(matches!(restart, Some(StageEvent::Restart)));
+        // the stages of the healthy reporters are left alone
+        assert!(stages[1].try_recv().is_err());
+        assert!(stages[2].try_recv().is_err());
+    }
+}
@@ -3,10 +3,18 @@
 
 use crate::app::{
     cluster::{NodeInfo, Nodes},
-    stage::{ReporterEvent, ReportersHandles},
+    diagnostics::ConnectionLabel,
+    metrics,
+    stage::{ReporterEvent, ReporterHandle, ReportersHandles, StreamsAudit},
     worker::WorkerError,
 };
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+
+mod policy;
+pub use policy::{
+    load_balancing_policy, set_load_balancing_policy, DcAwareRoundRobin, LoadBalancingPolicy, ReplicaSelection,
+    RingView, RoundRobin, Route, TokenAware,
+};
 
 use rand::{distributions::Uniform, prelude::ThreadRng, thread_rng, Rng};
 use std::{
@@ -41,6 +49,7 @@ pub type GlobalRing = (
     Vec<DC>,
     Uniform<usize>,
     Uniform<usize>,
+    usize,
     Uniform<u8>,
     u8,
     Registry,
@@ -73,6 +82,21 @@ pub struct Ring {
     pub uniform_dcs: Uniform<usize>,
     /// Uniform to pick random replication refactor
     pub uniform_rf: Uniform<usize>,
+    /// The replication factor used to pick the replicas
+    pub replication_factor: usize,
+}
+
+/// The snapshot of the ring topology, as seen by the current thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopologySnapshot {
+    /// The version of the ring
+    pub version: u8,
+    /// The data centers of the ring, where the first one is the local one
+    pub data_centers: Vec<DC>,
+    /// The replication factor used to pick the replicas
+    pub replication_factor: usize,
+    /// The labels of the registered reporter connections, ordered by node, shard and reporter id
+    pub connections: Vec<ConnectionLabel>,
 }
 
 static mut VERSION: u8 = 0;
@@ -89,6 +113,7 @@ thread_local! {
         let dcs = vec!["".to_string()];
         let uniform_dcs: Uniform<usize> = Uniform::new(0,dcs.len());
         let uniform_rf: Uniform<usize> = Uniform::new(0,1);
+        let replication_factor = 1;
         RefCell::new(Ring{
             version,
             weak,
@@ -98,6 +123,7 @@ thread_local! {
             rng,
             uniform_dcs,
             uniform_rf,
+            replication_factor,
             dcs
         })
     };
@@ -126,6 +152,94 @@ impl Ring {
     pub fn send_global_random_replica(token: Token, request: ReporterEvent) {
         RING.with(|local| local.borrow_mut().sending().global_random_replica(token, request))
     }
+    /// Send request to the replica which the load balancing policy routes the token to.
+    pub fn send_with(policy: &dyn LoadBalancingPolicy, token: Token, request: ReporterEvent) {
+        RING.with(|local| local.borrow_mut().sending().routed(policy, token, request))
+    }
+    /// Get the shard address (where the port is the shard id) which owns the token on the replica with the given
+    /// index in the first local datacenter, or None if the ring isn't built yet.
+    pub fn local_shard(replica_index: usize, token: Token) -> Option<SocketAddr> {
+        RING.with(|local| {
+            let mut ring = local.borrow_mut();
+            let ring = ring.sending();
+            let data_center = ring.dcs[0].clone();
+            ring.root
+                .as_mut()
+                .search(token)
+                .shard(&data_center, replica_index, token)
+        })
+    }
+    /// Send a request, built by the provided closure, to every shard of every node in the ring.
+    /// The closure receives the shard address (where the port is the shard id), and the lowest reporter id
+    /// of each shard is used, so the routing is deterministic. Returns the number of targeted shards.
+    pub fn send_all(make_request: impl FnMut(SocketAddr) -> ReporterEvent) -> usize {
+        RING.with(|local| local.borrow_mut().sending().all(|_| true, make_request))
+    }
+    /// Send a request, built by the provided closure, to every shard of the provided node.
+    /// Returns the number of targeted shards, which is zero if the node is not in the ring.
+    pub fn send_node(node: IpAddr, make_request: impl FnMut(SocketAddr) -> ReporterEvent) -> usize {
+        RING.with(|local| {
+            local
+                .borrow_mut()
+                .sending()
+                .all(|address| address.ip() == node, make_request)
+        })
+    }
+    /// Send a request, built by the provided closure, to a single shard (the lowest one) of every node in the
+    /// ring, bypassing the token routing. Returns the number of targeted nodes.
+    pub fn send_nodes(make_request: impl FnMut(SocketAddr) -> ReporterEvent) -> usize {
+        RING.with(|local| {
+            let mut ring = local.borrow_mut();
+            let ring = ring.sending();
+            let mut first_shards: HashMap<IpAddr, SocketAddr> = HashMap::new();
+            for address in ring.registry.keys() {
+                let first = first_shards.entry(address.ip()).or_insert(*address);
+                if address.port() < first.port() {
+                    *first = *address;
+                }
+            }
+            ring.all(|address| first_shards.get(&address.ip()) == Some(address), make_request)
+        })
+    }
+    /// Get the snapshot of the most up to date ring topology, along with the labels of its reporter connections
+    pub fn snapshot() -> TopologySnapshot {
+        RING.with(|local| {
+            let mut ring = local.borrow_mut();
+            let ring = ring.sending();
+            let mut connections: Vec<ConnectionLabel> = ring
+                .registry
+                .values()
+                .flat_map(|reporters_handles| reporters_handles.values().map(|handle| handle.label()))
+                .collect();
+            connections.sort();
+            TopologySnapshot {
+                version: ring.version,
+                data_centers: ring.dcs.clone(),
+                replication_factor: ring.replication_factor,
+                connections,
+            }
+        })
+    }
+    /// Audit the stream ids of every reporter connection of the most up to date ring topology, ordered by node,
+    /// shard and reporter id. The closed reporters are skipped.
+    pub async fn audit_streams() -> Vec<StreamsAudit> {
+        let reporters: Vec<ReporterHandle> = RING.with(|local| {
+            let mut ring = local.borrow_mut();
+            ring.sending()
+                .registry
+                .values()
+                .flat_map(|reporters_handles| reporters_handles.values().cloned())
+                .collect()
+        });
+        let mut audits: Vec<StreamsAudit> =
+            futures::future::join_all(reporters.iter().map(|reporter| reporter.audit_streams()))
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+        audits.sort_by_key(|audit| audit.label);
+        audits
+    }
     /// Rebuild the Ring the most up to date version
     pub fn rebuild() {
         RING.with(|local| {
@@ -137,11 +251,13 @@ impl Ring {
                         Weak::upgrade(GLOBAL_RING.as_ref().unwrap().load(Ordering::Relaxed).as_ref().unwrap())
                     {
                         let new_weak = Arc::downgrade(&arc);
-                        let (dcs, uniform_dcs, uniform_rf, uniform, version, registry, root) = Arc::make_mut(&mut arc);
+                        let (dcs, uniform_dcs, uniform_rf, replication_factor, uniform, version, registry, root) =
+                            Arc::make_mut(&mut arc);
                         // update the local ring
                         ring.dcs = dcs.clone();
                         ring.uniform_dcs = *uniform_dcs;
                         ring.uniform_rf = *uniform_rf;
+                        ring.replication_factor = *replication_factor;
                         ring.uniform = *uniform;
                         ring.version = *version;
                         ring.registry = registry.clone();
@@ -160,11 +276,13 @@ impl Ring {
                     Weak::upgrade(GLOBAL_RING.as_ref().unwrap().load(Ordering::Relaxed).as_ref().unwrap())
                 {
                     let new_weak = Arc::downgrade(&arc);
-                    let (dcs, uniform_dcs, uniform_rf, uniform, version, registry, root) = Arc::make_mut(&mut arc);
+                    let (dcs, uniform_dcs, uniform_rf, replication_factor, uniform, version, registry, root) =
+                        Arc::make_mut(&mut arc);
                     // update the local ring
                     self.dcs = dcs.clone();
                     self.uniform_dcs = *uniform_dcs;
                     self.uniform_rf = *uniform_rf;
+                    self.replication_factor = *replication_factor;
                     self.uniform = *uniform;
                     self.version = *version;
                     self.registry = registry.clone();
@@ -223,6 +341,47 @@ impl Ring {
             self.uniform,
         );
     }
+    fn routed(&mut self, policy: &dyn LoadBalancingPolicy, token: Token, request: ReporterEvent) {
+        let route = {
+            let view = RingView::new(
+                &self.dcs,
+                self.replication_factor,
+                &**self.root.as_mut().search(token),
+                &self.registry,
+            );
+            policy.route(token, &view)
+        };
+        metrics::record_replica(route.replica_index);
+        let data_center = self.dcs.get(route.data_center).unwrap_or(&self.dcs[0]);
+        // send request.
+        self.root.as_mut().search(route.token).send(
+            data_center,
+            route.replica_index,
+            route.token,
+            request,
+            &mut self.registry,
+            &mut self.rng,
+            self.uniform,
+        );
+    }
+    fn all(
+        &mut self,
+        filter: impl Fn(&SocketAddr) -> bool,
+        mut make_request: impl FnMut(SocketAddr) -> ReporterEvent,
+    ) -> usize {
+        let mut shards: Vec<&SocketAddr> = self.registry.keys().filter(|address| filter(address)).collect();
+        // sort the shards to keep the sending order deterministic
+        shards.sort();
+        let mut count = 0;
+        for address in shards {
+            let reporters_handles = &self.registry[address];
+            if let Some(reporter_id) = reporters_handles.keys().min() {
+                let _ = reporters_handles[reporter_id].send(make_request(*address));
+                count += 1;
+            }
+        }
+        count
+    }
     fn initialize_ring(version: u8, rebuild: bool) -> (ArcRing, Option<Box<Weak<GlobalRing>>>) {
         // create empty Registry
         let registry: Registry = HashMap::new();
@@ -233,6 +392,7 @@ impl Ring {
             vec!["".to_string()], // dcs
             Uniform::new(0, 1),
             Uniform::new(0, 1),
+            1,
             Uniform::new(0, 1),
             version,
             registry,
@@ -263,6 +423,7 @@ impl Ring {
     }
 }
 trait SmartId {
+    fn shard(&self, token: Token) -> SocketAddr;
     fn send_reporter(
         &mut self,
         token: Token,
@@ -273,6 +434,12 @@ trait SmartId {
     );
 }
 impl SmartId for Replica {
+    fn shard(&self, token: Token) -> SocketAddr {
+        // shard awareness algo,
+        let mut address = self.0;
+        address.set_port((((((token as i128 + MIN as i128) as u64) << self.1) as u128 * self.2 as u128) >> 64) as u16);
+        address
+    }
     fn send_reporter(
         &mut self,
         token: Token,
@@ -281,9 +448,7 @@ impl SmartId for Replica {
         uniform: Uniform<u8>,
         request: ReporterEvent,
     ) {
-        // shard awareness algo,
-        self.0
-            .set_port((((((token as i128 + MIN as i128) as u64) << self.1) as u128 * self.2 as u128) >> 64) as u16);
+        self.0 = self.shard(token);
         let _ = registry
             .get_mut(&self.0)
             .unwrap()
@@ -306,6 +471,11 @@ pub trait Endpoints: EndpointsClone + Send + Sync {
         rng: &mut ThreadRng,
         uniform: Uniform<u8>,
     );
+    /// Get the shard address which owns the token on the replica with the given index in the data center.
+    fn shard(&self, data_center: &str, replica_index: usize, token: Token) -> Option<SocketAddr>;
+    /// Get the number of nodes in the data center, which are ordered by their distance to the token, such as the
+    /// first ones are its replicas.
+    fn nodes(&self, data_center: &str) -> usize;
 }
 
 /// Clone the endpoints.
@@ -350,6 +520,14 @@ impl Endpoints for Replicas {
             replica.send_reporter(token, &mut registry, &mut rng, uniform, request);
         }
     }
+    fn shard(&self, data_center: &str, replica_index: usize, token: Token) -> Option<SocketAddr> {
+        self.get(data_center)
+            .and_then(|replicas| replicas.get(replica_index))
+            .map(|replica| replica.shard(token))
+    }
+    fn nodes(&self, data_center: &str) -> usize {
+        self.get(data_center).map_or(0, |replicas| replicas.len())
+    }
 }
 impl Endpoints for Option<Replicas> {
     // this method will be invoked when we store Replicas as None.
@@ -371,6 +549,12 @@ impl Endpoints for Option<Replicas> {
                 .unwrap_or_else(|e| log::error!("{}", e));
         };
     }
+    fn shard(&self, _: &str, _: usize, _: Token) -> Option<SocketAddr> {
+        None
+    }
+    fn nodes(&self, _: &str) -> usize {
+        0
+    }
 }
 
 /// Search the endpoint of the virtual node.
@@ -592,6 +776,7 @@ pub fn build_ring(
         dcs.clone(),
         Uniform::new(0, dcs.len()),
         Uniform::new(0, uniform_rf),
+        uniform_rf,
         Uniform::new(0, reporter_count),
         version,
         registry,
@@ -718,3 +903,158 @@ fn generate_and_compute_fake_ring() {
     // and it will be mild where both of its childern are deadends.
     let _root = compute_vnode(&chain);
 }
+
+#[cfg(test)]
+impl Ring {
+    /// Replace the registry of the ring of the current thread by detached reporters, so the tests can stub them
+    /// without building the global ring. Returns the receivers of the reporter events by shard address and
+    /// reporter id.
+    pub(crate) fn detach(
+        shards: &[SocketAddr],
+        reporter_count: u8,
+    ) -> HashMap<SocketAddr, Vec<tokio::sync::mpsc::UnboundedReceiver<ReporterEvent>>> {
+        let mut registry = Registry::new();
+        let mut receivers = HashMap::new();
+        for address in shards {
+            let label = ConnectionLabel::shard(SocketAddr::new(address.ip(), 9042), address.port());
+            let (reporters_handles, rx) = ReportersHandles::detached(label, reporter_count);
+            registry.insert(*address, reporters_handles);
+            receivers.insert(*address, rx);
+        }
+        RING.with(|local| local.borrow_mut().registry = registry);
+        receivers
+    }
+    /// Replace the ring of the current thread by a single node which owns the whole token range along with its
+    /// shards, whose reporters are detached, so the tests can stub the token routed requests. Returns the receivers
+    /// of the reporter events by shard address and reporter id.
+    pub(crate) fn detach_node(
+        node: IpAddr,
+        shard_count: ShardCount,
+        reporter_count: u8,
+    ) -> HashMap<SocketAddr, Vec<tokio::sync::mpsc::UnboundedReceiver<ReporterEvent>>> {
+        let shards: Vec<SocketAddr> = (0..shard_count).map(|shard| SocketAddr::new(node, shard)).collect();
+        let receivers = Ring::detach(&shards, reporter_count);
+        let data_center = "datacenter1".to_string();
+        let mut replicas = Replicas::new();
+        replicas.insert(
+            data_center.clone(),
+            vec![(SocketAddr::new(node, 9042), 12, shard_count)],
+        );
+        RING.with(|local| {
+            let mut ring = local.borrow_mut();
+            ring.root = Box::new(DeadEnd {
+                replicas: Box::new(replicas),
+            });
+            ring.dcs = vec![data_center];
+            ring.uniform = Uniform::new(0, reporter_count);
+        });
+        receivers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::worker::OneshotWorker;
+
+    /// The shards of two nodes, where the port is the shard id
+    fn shards() -> Vec<SocketAddr> {
+        vec![
+            ([10, 0, 0, 2], 1).into(),
+            ([10, 0, 0, 1], 0).into(),
+            ([10, 0, 0, 2], 0).into(),
+            ([10, 0, 0, 1], 1).into(),
+            ([10, 0, 0, 2], 2).into(),
+        ]
+    }
+
+    fn request(address: SocketAddr) -> ReporterEvent {
+        ReporterEvent::Request {
+            worker: OneshotWorker::boxed().0,
+            payload: address.to_string().into_bytes(),
+        }
+    }
+
+    /// Drain the received requests as (shard address, reporter id, payload)
+    fn received(
+        receivers: &mut HashMap<SocketAddr, Vec<tokio::sync::mpsc::UnboundedReceiver<ReporterEvent>>>,
+    ) -> Vec<(SocketAddr, usize, String)> {
+        let mut received = Vec::new();
+        for (address, receivers) in receivers.iter_mut() {
+            for (reporter_id, rx) in receivers.iter_mut().enumerate() {
+                while let Ok(event) = rx.try_recv() {
+                    match event {
+                        ReporterEvent::Request { payload, .. } => {
+                            received.push((*address, reporter_id, String::from_utf8(payload).unwrap()))
+                        }
+                        _ => panic!("expected a request"),
+                    }
+                }
+            }
+        }
+        received.sort();
+        received
+    }
+
+    #[test]
+    fn send_all_targets_every_shard_once() {
+        let mut receivers = Ring::detach(&shards(), 3);
+        let mut expected = shards();
+        expected.sort();
+        for _ in 0..3 {
+            let mut targeted = Vec::new();
+            let count = Ring::send_all(|address| {
+                targeted.push(address);
+                request(address)
+            });
+            assert_eq!(count, 5);
+            // the shards are targeted in order, through their lowest reporter
+            assert_eq!(targeted, expected);
+            assert_eq!(
+                received(&mut receivers),
+                expected
+                    .iter()
+                    .map(|address| (*address, 0, address.to_string()))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn send_node_targets_only_the_node() {
+        let mut receivers = Ring::detach(&shards(), 2);
+        let node: IpAddr = [10, 0, 0, 2].into();
+        let mut targeted = Vec::new();
+        let count = Ring::send_node(node, |address| {
+            targeted.push(address);
+            request(address)
+        });
+        assert_eq!(count, 3);
+        let expected: Vec<SocketAddr> = vec![(node, 0).into(), (node, 1).into(), (node, 2).into()];
+        assert_eq!(targeted, expected);
+        assert_eq!(
+            received(&mut receivers),
+            expected
+                .iter()
+                .map(|address| (*address, 0, address.to_string()))
+                .collect::<Vec<_>>()
+        );
+        // a node which isn't in the ring isn't targeted
+        assert_eq!(Ring::send_node([10, 0, 0, 3].into(), request), 0);
+        assert!(received(&mut receivers).is_empty());
+    }
+
+    #[test]
+    fn send_nodes_targets_the_lowest_shard_of_every_node() {
+        let mut receivers = Ring::detach(&shards(), 2);
+        assert_eq!(Ring::send_nodes(request), 2);
+        let expected: Vec<SocketAddr> = vec![([10, 0, 0, 1], 0).into(), ([10, 0, 0, 2], 0).into()];
+        assert_eq!(
+            received(&mut receivers),
+            expected
+                .iter()
+                .map(|address| (*address, 0, address.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{Endpoints, Registry, Token, DC};
+use rand::{thread_rng, Rng};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock, RwLock,
+    },
+};
+
+static POLICIES: OnceLock<RwLock<HashMap<String, Arc<dyn LoadBalancingPolicy>>>> = OnceLock::new();
+/// Whether any keyspace has a load balancing policy, which skips the registry lookup otherwise
+static HAS_POLICIES: AtomicBool = AtomicBool::new(false);
+
+fn policies() -> &'static RwLock<HashMap<String, Arc<dyn LoadBalancingPolicy>>> {
+    POLICIES.get_or_init(Default::default)
+}
+
+/// Set the load balancing policy of the keyspace, which routes its requests instead of the local/global random
+/// replica selection, or remove it with None.
+pub fn set_load_balancing_policy(keyspace: &str, policy: Option<Arc<dyn LoadBalancingPolicy>>) {
+    let mut policies = policies().write().expect("Load balancing policies lock is poisoned");
+    match policy {
+        Some(policy) => {
+            policies.insert(keyspace.to_string(), policy);
+        }
+        None => {
+            policies.remove(keyspace);
+        }
+    }
+    HAS_POLICIES.store(!policies.is_empty(), Ordering::Relaxed);
+}
+
+/// Get the load balancing policy of the keyspace, if any
+pub fn load_balancing_policy(keyspace: &str) -> Option<Arc<dyn LoadBalancingPolicy>> {
+    if !HAS_POLICIES.load(Ordering::Relaxed) {
+        return None;
+    }
+    policies()
+        .read()
+        .expect("Load balancing policies lock is poisoned")
+        .get(keyspace)
+        .cloned()
+}
+
+/// The route of a request in the ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// The index of the data center in the ring data centers
+    pub data_center: usize,
+    /// The index of the node in the data center, where the nodes are ordered by their distance to the token, such
+    /// as the first `replication_factor` ones are its replicas
+    pub replica_index: usize,
+    /// The token which selects the nodes and the shard
+    pub token: Token,
+}
+
+/// The view of the ring through which the load balancing policies route the requests
+pub struct RingView<'a> {
+    data_centers: &'a [DC],
+    replication_factor: usize,
+    endpoints: &'a dyn Endpoints,
+    registry: &'a Registry,
+}
+
+impl<'a> RingView<'a> {
+    pub(crate) fn new(
+        data_centers: &'a [DC],
+        replication_factor: usize,
+        endpoints: &'a dyn Endpoints,
+        registry: &'a Registry,
+    ) -> Self {
+        Self {
+            data_centers,
+            replication_factor,
+            endpoints,
+            registry,
+        }
+    }
+    /// Get the data centers of the ring, where the first one is the local one
+    pub fn data_centers(&self) -> &[DC] {
+        self.data_centers
+    }
+    /// Get the replication factor of the ring
+    pub fn replication_factor(&self) -> usize {
+        self.replication_factor
+    }
+    /// Get the number of nodes in the data center
+    pub fn nodes(&self, data_center: usize) -> usize {
+        self.data_centers
+            .get(data_center)
+            .map_or(0, |data_center| self.endpoints.nodes(data_center))
+    }
+    /// Get the number of in-flight requests on the shard which owns the token on the node with the given index in
+    /// the data center, as last reported by its reporters
+    pub fn in_flight(&self, data_center: usize, replica_index: usize, token: Token) -> usize {
+        self.data_centers
+            .get(data_center)
+            .and_then(|data_center| self.endpoints.shard(data_center, replica_index, token))
+            .and_then(|shard| self.registry.get(&shard))
+            .map_or(0, |reporters| {
+                reporters.values().map(|reporter| reporter.progress().in_flight()).sum()
+            })
+    }
+}
+
+/// Routes the requests to the nodes of the ring
+pub trait LoadBalancingPolicy: Send + Sync {
+    /// Route the request of the token
+    fn route(&self, token: Token, ring: &RingView) -> Route;
+}
+
+/// Rotates the requests over the nodes of every data center, regardless of their token.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    counter: AtomicUsize,
+}
+
+impl RoundRobin {
+    /// Create a new round robin policy
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LoadBalancingPolicy for RoundRobin {
+    fn route(&self, token: Token, ring: &RingView) -> Route {
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        let data_center = count % ring.data_centers().len().max(1);
+        let replica_index = (count / ring.data_centers().len().max(1)) % ring.nodes(data_center).max(1);
+        Route {
+            data_center,
+            replica_index,
+            token,
+        }
+    }
+}
+
+/// Rotates the requests over the nodes of a single data center, which is the local one unless another one is set.
+#[derive(Debug, Default)]
+pub struct DcAwareRoundRobin {
+    data_center: Option<DC>,
+    counter: AtomicUsize,
+}
+
+impl DcAwareRoundRobin {
+    /// Create a new data center aware round robin policy, which routes the requests to the local data center
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Route the requests to the provided data center instead of the local one, if it exists in the ring
+    pub fn data_center<T: Into<DC>>(mut self, data_center: T) -> Self {
+        self.data_center.replace(data_center.into());
+        self
+    }
+}
+
+impl LoadBalancingPolicy for DcAwareRoundRobin {
+    fn route(&self, token: Token, ring: &RingView) -> Route {
+        let data_center = self
+            .data_center
+            .as_ref()
+            .and_then(|data_center| ring.data_centers().iter().position(|dc| dc == data_center))
+            .unwrap_or(0);
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        Route {
+            data_center,
+            replica_index: count % ring.nodes(data_center).max(1),
+            token,
+        }
+    }
+}
+
+/// Selects the replica of the token which coordinates the request, once the token is looked up in the ring
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplicaSelection {
+    /// Select the primary replica, which keeps the partition hot in a single node cache, but imbalances the load
+    PrimaryFirst,
+    /// Select a random replica
+    Random,
+    /// Rotate the requests over the replicas
+    #[default]
+    RoundRobin,
+    /// Select the replica whose shard has the least in-flight requests, while the ties are rotated
+    LeastInFlight,
+}
+
+/// Restricts the routes of another policy to the replicas of the token, in the data center which that policy
+/// picks, so the requests are coordinated by the nodes which own their partition. The replica is selected by the
+/// `ReplicaSelection` strategy, which rotates over the replicas by default.
+///
+/// The routed requests are counted by replica index (see `MetricsSnapshot::replicas`), which shows their
+/// distribution over the replicas.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::app::ring::{set_load_balancing_policy, DcAwareRoundRobin, ReplicaSelection, TokenAware};
+/// use std::sync::Arc;
+///
+/// let policy = TokenAware::new(DcAwareRoundRobin::new().data_center("eu-west"))
+///     .replica_selection(ReplicaSelection::LeastInFlight);
+/// set_load_balancing_policy("my_keyspace", Some(Arc::new(policy)));
+/// ```
+#[derive(Debug, Default)]
+pub struct TokenAware<P> {
+    child: P,
+    selection: ReplicaSelection,
+    counter: AtomicUsize,
+}
+
+impl<P: LoadBalancingPolicy> TokenAware<P> {
+    /// Create a new token aware policy, which wraps the provided one
+    pub fn new(child: P) -> Self {
+        Self {
+            child,
+            selection: ReplicaSelection::default(),
+            counter: AtomicUsize::new(0),
+        }
+    }
+    /// Set the strategy which selects the replica of the token
+    pub fn replica_selection(mut self, selection: ReplicaSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+}
+
+impl<P: LoadBalancingPolicy> LoadBalancingPolicy for TokenAware<P> {
+    fn route(&self, token: Token, ring: &RingView) -> Route {
+        let data_center = self.child.route(token, ring).data_center;
+        let replicas = ring.replication_factor().min(ring.nodes(data_center)).max(1);
+        let replica_index = match self.selection {
+            ReplicaSelection::PrimaryFirst => 0,
+            ReplicaSelection::Random => thread_rng().gen_range(0..replicas),
+            ReplicaSelection::RoundRobin => self.counter.fetch_add(1, Ordering::Relaxed) % replicas,
+            ReplicaSelection::LeastInFlight => {
+                let offset = self.counter.fetch_add(1, Ordering::Relaxed);
+                (0..replicas)
+                    .map(|i| (offset + i) % replicas)
+                    .min_by_key(|replica_index| ring.in_flight(data_center, *replica_index, token))
+                    .unwrap_or(0)
+            }
+        };
+        Route {
+            data_center,
+            replica_index,
+            token,
+        }
+    }
+}
+
+#[test]
+fn route_policies() {
+    use super::Replicas;
+    let node = |ip: &str| (format!("{}:0", ip).parse().unwrap(), 12, 8);
+    let mut replicas: Replicas = HashMap::new();
+    replicas.insert(
+        "local".to_string(),
+        vec![node("127.0.0.1"), node("127.0.0.2"), node("127.0.0.3")],
+    );
+    replicas.insert("remote".to_string(), vec![node("128.0.0.1"), node("128.0.0.2")]);
+    let data_centers = vec!["local".to_string(), "remote".to_string()];
+    let registry = Registry::new();
+    let ring = RingView::new(&data_centers, 2, &replicas, &registry);
+    // rotates over every node of every data center
+    let round_robin = RoundRobin::new();
+    let routes: Vec<(usize, usize)> = (0..6)
+        .map(|_| round_robin.route(7, &ring))
+        .map(|route| (route.data_center, route.replica_index))
+        .collect();
+    assert_eq!(routes, vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2), (1, 0)]);
+    // rotates over the nodes of the remote data center
+    let dc_aware = DcAwareRoundRobin::new().data_center("remote");
+    let indexes: Vec<usize> = (0..3).map(|_| dc_aware.route(7, &ring).replica_index).collect();
+    assert_eq!(indexes, vec![0, 1, 0]);
+    // rotates over the replicas of the token in the local data center
+    let token_aware = TokenAware::new(DcAwareRoundRobin::new());
+    let routes: Vec<Route> = (0..3).map(|_| token_aware.route(7, &ring)).collect();
+    assert!(routes.iter().all(|route| route.data_center == 0 && route.token == 7));
+    assert_eq!(
+        routes.iter().map(|route| route.replica_index).collect::<Vec<_>>(),
+        vec![0, 1, 0]
+    );
+    // selects the primary replica, or any replica
+    let primary_first = TokenAware::new(RoundRobin::new()).replica_selection(ReplicaSelection::PrimaryFirst);
+    assert!((0..3).all(|_| primary_first.route(7, &ring).replica_index == 0));
+    let random = TokenAware::new(DcAwareRoundRobin::new()).replica_selection(ReplicaSelection::Random);
+    assert!((0..10).all(|_| random.route(7, &ring).replica_index < 2));
+    // rotates over the replicas as long as their shards have the same in-flight requests
+    let least_in_flight = TokenAware::new(DcAwareRoundRobin::new()).replica_selection(ReplicaSelection::LeastInFlight);
+    let indexes: Vec<usize> = (0..3).map(|_| least_in_flight.route(7, &ring).replica_index).collect();
+    assert_eq!(indexes, vec![0, 1, 0]);
+}
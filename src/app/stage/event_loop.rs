@@ -2,6 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use crate::{
+    app::diagnostics::{self, DiagnosticEvent},
+    cql::CqlStream,
+};
+use futures::future::Abortable;
 use std::time::Duration;
 
 #[async_trait::async_trait]
@@ -47,14 +52,37 @@ impl EventLoop<NodeHandle> for Stage {
 
                     StageEvent::Shutdown => {
                         self.handle = None;
+                        self.stop_heartbeat();
                         self.service.update_status(ServiceStatus::Stopping);
                         // shutdown children
                         if let Some(reporters_handles) = self.reporters_handles.take() {
-                            reporters_handles.shutdown();
+                            match self.drain_timeout {
+                                Some(timeout) => reporters_handles.drain(timeout),
+                                None => {
+                                    reporters_handles.shutdown();
+                                }
+                            }
                         };
                         let event = NodeEvent::Service(self.service.clone());
                         supervisor.send(event).ok();
                     }
+                    StageEvent::Restart => {
+                        // the heartbeat of the next connection is started once it's opened
+                        self.stop_heartbeat();
+                        // the reporters will drop their sender handles and fail their in-flight requests,
+                        // then the stage reconnects once the old session is closed.
+                        if let Some(reporters_handles) = self.reporters_handles.as_ref() {
+                            for reporter_handle in reporters_handles.values() {
+                                reporter_handle
+                                    .send(ReporterEvent::Session(reporter::Session::Restart))
+                                    .ok();
+                            }
+                        }
+                    }
+                    StageEvent::RotateCredentials(authenticator) => {
+                        // the current session remains authenticated, only the reconnections use the new credentials
+                        self.authenticator = authenticator;
+                    }
                     StageEvent::Connect => {
                         if let Some(handle) = self.handle.as_ref() {
                             // ensure the service is not stopping
@@ -66,32 +94,59 @@ impl EventLoop<NodeHandle> for Stage {
                                     .shard_id(self.shard_id)
                                     .recv_buffer_size(self.recv_buffer_size)
                                     .send_buffer_size(self.send_buffer_size)
+                                    .compression(self.compression)
+                                    .tls(self.tls.clone())
                                     .build();
                                 match cql_builder.await {
                                     Ok(cql_conn) => {
                                         self.session_id += 1;
+                                        diagnostics::emit(DiagnosticEvent::ConnectionOpened { label: self.label() });
+                                        let compression = cql_conn.compression();
                                         // Split the stream
-                                        let stream: TcpStream = cql_conn.into();
+                                        let stream: CqlStream = cql_conn.into();
                                         let (socket_rx, socket_tx) = stream.into_split();
                                         // spawn sender
                                         let sender = SenderBuilder::new()
                                             .socket(socket_tx)
+                                            .label(self.label())
                                             .appends_num(self.appends_num)
                                             .payloads(self.payloads.clone())
+                                            .compression(compression)
                                             .build();
                                         tokio::spawn(sender.start(self.reporters_handles.clone()));
                                         // spawn receiver
                                         let receiver = ReceiverBuilder::new()
                                             .socket(socket_rx)
+                                            .label(self.label())
                                             .appends_num(self.appends_num)
                                             .payloads(self.payloads.clone())
                                             .session_id(self.session_id)
                                             .buffer_size(self.buffer_size)
+                                            .compression(compression)
                                             .build();
                                         tokio::spawn(receiver.start(self.reporters_handles.clone()));
+                                        // ping the new connection through its first reporter once it's idle
+                                        let stage = handle.clone();
+                                        self.stop_heartbeat();
+                                        let reporter = self
+                                            .reporters_handles
+                                            .as_ref()
+                                            .and_then(|reporters_handles| reporters_handles.values().next().cloned());
+                                        if let (Some(reporter), Some(config)) = (reporter, self.heartbeat_config) {
+                                            let heartbeat = Heartbeat::new(self.label(), stage, reporter, config);
+                                            let (abort_handle, abort_registration) = AbortHandle::new_pair();
+                                            tokio::spawn(Abortable::new(heartbeat.run(), abort_registration));
+                                            self.heartbeat.replace(abort_handle);
+                                        }
                                     }
-                                    Err(_) => {
-                                        tokio::time::sleep(Duration::from_millis(5000)).await;
+                                    Err(e) => {
+                                        let retry_in = Duration::from_millis(5000);
+                                        diagnostics::emit(DiagnosticEvent::ReconnectScheduled {
+                                            label: self.label(),
+                                            retry_in,
+                                            error: e.to_string(),
+                                        });
+                                        tokio::time::sleep(retry_in).await;
                                         // try to reconnent
                                         handle.send(StageEvent::Connect).ok();
                                     }
@@ -0,0 +1,227 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::{
+    app::{
+        diagnostics::{self, DiagnosticEvent},
+        worker::{RequestTimeout, Worker, WorkerError},
+    },
+    cql::{Decoder, Frame, Options},
+};
+use anyhow::anyhow;
+use std::convert::TryFrom;
+use tokio::sync::oneshot;
+
+/// The heartbeat of the connections, which pings them by an OPTIONS request once they're idle for the interval, so
+/// the idle connections aren't silently dropped by the NATs and the firewalls. A connection which doesn't answer its
+/// ping by a SUPPORTED response before the deadline is restarted.
+///
+/// Note: the heartbeat is disabled unless it's configured.
+#[derive(Debug, Clone, Copy)]
+pub struct HeartbeatConfig {
+    interval: Duration,
+    deadline: Duration,
+}
+
+impl HeartbeatConfig {
+    /// Create the heartbeat config, where both durations are at least one milli
+    pub fn new(interval: Duration, deadline: Duration) -> Self {
+        let min = Duration::from_millis(1);
+        Self {
+            interval: interval.max(min),
+            deadline: deadline.max(min),
+        }
+    }
+    /// Get the idle duration after which a connection is pinged
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+    /// Get the deadline of the SUPPORTED response to the ping
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+}
+
+/// The heartbeat of a stage connection, which pings the connection through one of its reporters once it's idle for
+/// the heartbeat interval, so the idle connections aren't silently dropped by the NATs and the firewalls, and the
+/// dropped ones are detected.
+pub(crate) struct Heartbeat {
+    label: ConnectionLabel,
+    stage: StageHandle,
+    reporter: ReporterHandle,
+    interval: Duration,
+    deadline: Duration,
+}
+
+impl Heartbeat {
+    /// Create the heartbeat of the stage connection
+    pub(crate) fn new(
+        label: ConnectionLabel,
+        stage: StageHandle,
+        reporter: ReporterHandle,
+        config: HeartbeatConfig,
+    ) -> Self {
+        Self {
+            label,
+            stage,
+            reporter,
+            interval: config.interval,
+            deadline: config.deadline,
+        }
+    }
+
+    /// Run the heartbeat until the connection fails to answer its ping, then restart the stage. It should be aborted
+    /// once the connection is closed, as it holds the stage handle.
+    pub(crate) async fn run(self) {
+        let progress = self.reporter.progress();
+        let mut interval = tokio::time::interval(self.interval);
+        // the first tick completes immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            // the connection isn't idle while its requests are answered
+            if progress.in_flight() > 0 || progress.idle() < self.interval {
+                continue;
+            }
+            if let Err(error) = self.ping().await {
+                diagnostics::emit(DiagnosticEvent::HeartbeatFailed {
+                    label: self.label,
+                    error: error.to_string(),
+                });
+                self.stage.send(StageEvent::Restart).ok();
+                return;
+            }
+        }
+    }
+
+    /// Send the OPTIONS request, and await its SUPPORTED response
+    async fn ping(&self) -> anyhow::Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        let Options(payload) = Options::new().build();
+        let worker = RequestTimeout::wrap(Box::new(HeartbeatWorker { sender }), Some(self.deadline));
+        self.reporter
+            .send(ReporterEvent::Request { worker, payload })
+            .map_err(|_| anyhow!("The reporter is closed"))?;
+        receiver.await.map_err(|_| anyhow!("The ping was dropped"))?
+    }
+}
+
+/// The worker of the heartbeat ping, which expects a SUPPORTED response
+struct HeartbeatWorker {
+    sender: oneshot::Sender<anyhow::Result<()>>,
+}
+
+impl Worker for HeartbeatWorker {
+    fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()> {
+        let res = Decoder::try_from(giveload).and_then(|decoder| match decoder.is_supported()? {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "Unexpected response to the ping, opcode: {}",
+                decoder.opcode()?
+            )),
+        });
+        self.sender.send(res).ok();
+        Ok(())
+    }
+    fn handle_error(self: Box<Self>, error: WorkerError, _reporter: &Option<ReporterHandle>) -> anyhow::Result<()> {
+        self.sender.send(Err(anyhow!(error))).ok();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::opcode::{OPTIONS, READY, SUPPORTED};
+    use tokio::{task::JoinHandle, time::timeout};
+
+    const INTERVAL: Duration = Duration::from_millis(50);
+    const DEADLINE: Duration = Duration::from_millis(500);
+
+    /// Encode the bodyless response frame of the opcode, where an empty body is an empty SUPPORTED multimap
+    fn response(opcode: u8) -> Vec<u8> {
+        let body = match opcode {
+            SUPPORTED => 0u16.to_be_bytes().to_vec(),
+            _ => Vec::new(),
+        };
+        let mut frame = vec![0x84, 0, 0, 0, opcode];
+        frame.extend(&(body.len() as i32).to_be_bytes());
+        frame.extend(body);
+        frame
+    }
+
+    /// Start the heartbeat of a stub reporter, along with the receivers of the reporter and the stage events
+    fn start() -> (
+        JoinHandle<()>,
+        Arc<ReporterProgress>,
+        mpsc::UnboundedReceiver<ReporterEvent>,
+        mpsc::UnboundedReceiver<StageEvent>,
+    ) {
+        let label = ConnectionLabel::shard(([127, 0, 0, 1], 9042).into(), 0);
+        let (reporter, events) = ReporterHandle::detached(label.reporter(0));
+        let progress = reporter.progress();
+        let (tx, stage_events) = mpsc::unbounded_channel();
+        let heartbeat = Heartbeat::new(
+            label,
+            StageHandle { tx },
+            reporter,
+            HeartbeatConfig::new(INTERVAL, DEADLINE),
+        );
+        (tokio::spawn(heartbeat.run()), progress, events, stage_events)
+    }
+
+    /// Receive the next ping, which is an OPTIONS request along with the heartbeat deadline
+    async fn ping(events: &mut mpsc::UnboundedReceiver<ReporterEvent>) -> Box<dyn Worker> {
+        match timeout(Duration::from_secs(5), events.recv()).await {
+            Ok(Some(ReporterEvent::Request { worker, payload })) => {
+                assert_eq!(payload[4], OPTIONS);
+                assert_eq!(worker.timeout(), Some(DEADLINE));
+                worker
+            }
+            _ => panic!("expected a ping"),
+        }
+    }
+
+    #[tokio::test]
+    async fn ping_idle_connection() {
+        let (heartbeat, progress, mut events, mut stage_events) = start();
+        // the connection isn't pinged while it has in-flight requests
+        progress.set_in_flight(1);
+        tokio::time::sleep(INTERVAL * 4).await;
+        assert!(events.try_recv().is_err());
+        // nor while it received a response within the interval
+        progress.set_in_flight(0);
+        for _ in 0..20 {
+            progress.touch();
+            tokio::time::sleep(INTERVAL / 5).await;
+        }
+        assert!(events.try_recv().is_err());
+        // the idle connection is pinged at every interval, as long as it answers
+        for _ in 0..2 {
+            ping(&mut events).await.handle_response(response(SUPPORTED)).unwrap();
+        }
+        assert!(stage_events.try_recv().is_err());
+        heartbeat.abort();
+    }
+
+    #[tokio::test]
+    async fn restart_without_supported_response() {
+        let answers: [fn(Box<dyn Worker>); 3] = [
+            // the node replied by another opcode
+            |worker| worker.handle_response(response(READY)).unwrap(),
+            // the reporter failed the ping once its deadline elapsed
+            |worker| worker.handle_error(WorkerError::Timeout, &None).unwrap(),
+            // the reporter dropped the ping, ie its session was closed
+            drop,
+        ];
+        for answer in answers.iter() {
+            let (heartbeat, _progress, mut events, mut stage_events) = start();
+            answer(ping(&mut events).await);
+            // the stage is restarted, and the heartbeat stops
+            timeout(Duration::from_secs(5), heartbeat).await.unwrap().unwrap();
+            assert!(matches!(stage_events.try_recv(), Ok(StageEvent::Restart)));
+            assert!(events.try_recv().is_err());
+        }
+    }
+}
@@ -22,6 +22,7 @@ impl Init<NodeHandle> for Stage {
             }
             let streams: Vec<i16> = (0..last_range).collect();
             let mut streams_iter = streams.chunks_exact(self.appends_num as usize);
+            let label = self.label();
             if let Some(reporter_handles) = self.reporters_handles.as_mut() {
                 // Start reporters
                 for reporter_id in 0..self.reporter_count {
@@ -29,11 +30,11 @@ impl Init<NodeHandle> for Stage {
                         // build reporter
                         let reporter = ReporterBuilder::new()
                             .session_id(self.session_id)
-                            .reporter_id(reporter_id)
-                            .shard_id(self.shard_id)
-                            .address(self.address.clone())
+                            .label(label.reporter(reporter_id))
                             .payloads(self.payloads.clone())
                             .streams(streams.to_owned().into_iter().collect())
+                            .read_only(self.read_only.clone())
+                            .decode_offload_threshold(self.decode_offload_threshold)
                             .build();
                         // clone reporter_handle
                         if let Some(reporter_handle) = reporter.clone_handle() {
@@ -2,23 +2,31 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::{
+    diagnostics::ConnectionLabel,
     node::{NodeEvent, NodeHandle},
     *,
 };
+use futures::future::AbortHandle;
+use heartbeat::Heartbeat;
+pub use heartbeat::HeartbeatConfig;
 use receiver::ReceiverBuilder;
 use reporter::ReporterBuilder;
-pub use reporter::{ReporterEvent, ReporterHandle};
+pub use reporter::{PhaseStats, ReporterEvent, ReporterHandle, ReporterProgress, StreamsAudit};
 use sender::SenderBuilder;
 use std::{
     cell::UnsafeCell,
     collections::HashMap,
     net::SocketAddr,
     ops::{Deref, DerefMut},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
 };
-use tokio::net::TcpStream;
 
 mod event_loop;
+mod heartbeat;
 mod init;
 mod receiver;
 mod reporter;
@@ -44,6 +52,35 @@ impl DerefMut for ReportersHandles {
     }
 }
 
+#[cfg(test)]
+impl ReportersHandles {
+    /// Create the handles of the shard without reporters, along with the receivers of their events ordered by
+    /// reporter id, so the tests can stub the reporters
+    pub(crate) fn detached(
+        label: ConnectionLabel,
+        reporter_count: u8,
+    ) -> (Self, Vec<tokio::sync::mpsc::UnboundedReceiver<ReporterEvent>>) {
+        let (handles, receivers) = (0..reporter_count)
+            .map(|reporter_id| {
+                let (handle, rx) = ReporterHandle::detached(label.reporter(reporter_id));
+                ((reporter_id, handle), rx)
+            })
+            .unzip();
+        (Self(handles), receivers)
+    }
+}
+
+impl ReportersHandles {
+    /// Drain the sessions of the reporters, which stop assigning streams to the new requests and await the responses
+    /// of their in-flight requests before shutting down. Once the timeout elapses, the remaining requests are failed
+    /// with `WorkerError::Lost`.
+    pub(crate) fn drain(self, timeout: Duration) {
+        for reporter_handle in self.values() {
+            let _ = reporter_handle.send(ReporterEvent::Session(reporter::Session::Drain(timeout)));
+        }
+    }
+}
+
 impl Shutdown for ReportersHandles {
     fn shutdown(self) -> Option<Self>
     where
@@ -65,6 +102,12 @@ builder!(StageBuilder {
     buffer_size: usize,
     recv_buffer_size: Option<u32>,
     send_buffer_size: Option<u32>,
+    compression: Option<CompressionType>,
+    tls: Option<TlsConfig>,
+    read_only: ReadOnly,
+    heartbeat: Option<HeartbeatConfig>,
+    drain_timeout: Option<Duration>,
+    decode_offload_threshold: Option<usize>,
     handle: StageHandle,
     inbox: StageInbox
 });
@@ -79,6 +122,15 @@ pub struct StageInbox {
     rx: mpsc::UnboundedReceiver<StageEvent>,
 }
 
+#[cfg(test)]
+impl StageHandle {
+    /// Create a handle without stage, along with the receiver of its events, so the tests can stub the stage
+    pub(crate) fn detached() -> (Self, mpsc::UnboundedReceiver<StageEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+}
+
 impl Deref for StageHandle {
     type Target = mpsc::UnboundedSender<StageEvent>;
 
@@ -99,6 +151,10 @@ pub enum StageEvent {
     Reporter(Service),
     /// Establish connection to scylla shard.
     Connect,
+    /// Restart the connection of a stalled stage.
+    Restart,
+    /// Use the rotated credentials for the next connections.
+    RotateCredentials(PasswordAuth),
     /// Shutdwon a stage.
     Shutdown,
 }
@@ -116,18 +172,63 @@ pub struct Stage {
     buffer_size: usize,
     recv_buffer_size: Option<u32>,
     send_buffer_size: Option<u32>,
+    compression: Option<CompressionType>,
+    tls: Option<TlsConfig>,
+    read_only: ReadOnly,
+    /// The heartbeat of the connections, if it's enabled
+    heartbeat_config: Option<HeartbeatConfig>,
+    /// The timeout of the graceful shutdown of the connection, if it's enabled
+    drain_timeout: Option<Duration>,
+    /// The response size in bytes from which the responses are handled on the blocking thread pool, if it's enabled
+    decode_offload_threshold: Option<usize>,
     handle: Option<StageHandle>,
     inbox: StageInbox,
+    heartbeat: Option<AbortHandle>,
 }
 impl Stage {
     pub(crate) fn clone_handle(&self) -> Option<StageHandle> {
         self.handle.clone()
     }
+    /// Get the label of the stage connection
+    fn label(&self) -> ConnectionLabel {
+        ConnectionLabel::shard(self.address, self.shard_id)
+    }
+    /// Stop the heartbeat of the previous connection, if any
+    fn stop_heartbeat(&mut self) {
+        if let Some(heartbeat) = self.heartbeat.take() {
+            heartbeat.abort();
+        }
+    }
 }
 #[derive(Default)]
-/// The reusable sender payload.
+/// The reusable sender payload, along with the monotonic timestamps of its request phases.
 pub struct Reusable {
     value: UnsafeCell<Option<Vec<u8>>>,
+    /// When the reporter dispatched the request to the sender queue.
+    dispatched_at: AtomicU64,
+    /// When the sender picked the request from its queue, to write it to the socket.
+    picked_at: AtomicU64,
+    /// When the sender finished writing the request to the socket.
+    written_at: AtomicU64,
+}
+
+/// The latency phases of a request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestPhases {
+    /// The time spent in the sender queue, before the request is written to the socket.
+    pub queue: Duration,
+    /// The time spent writing the request to the socket.
+    pub wire: Duration,
+    /// The time from the end of the socket write until the response is received,
+    /// which is mostly the server time.
+    pub server: Duration,
+}
+
+impl RequestPhases {
+    /// Get the total latency of the request.
+    pub fn total(&self) -> Duration {
+        self.queue + self.wire + self.server
+    }
 }
 impl Reusable {
     #[allow(clippy::mut_from_ref)]
@@ -143,6 +244,41 @@ impl Reusable {
     pub fn as_mut_payload(&self) -> Option<&mut Vec<u8>> {
         self.as_mut().as_mut()
     }
+    pub(crate) fn mark_dispatched(&self) {
+        self.dispatched_at.store(monotonic_nanos(), Ordering::Relaxed);
+        self.picked_at.store(0, Ordering::Relaxed);
+        self.written_at.store(0, Ordering::Relaxed);
+    }
+    pub(crate) fn mark_picked(&self) {
+        self.picked_at.store(monotonic_nanos(), Ordering::Relaxed);
+    }
+    pub(crate) fn mark_written(&self) {
+        self.written_at.store(monotonic_nanos(), Ordering::Relaxed);
+    }
+    /// Compute the request phases once its response is received.
+    ///
+    /// Note: None is returned if the sender didn't record the end of the socket write yet,
+    /// as the response can race with it.
+    pub(crate) fn phases(&self) -> Option<RequestPhases> {
+        let now = monotonic_nanos();
+        let dispatched_at = self.dispatched_at.load(Ordering::Relaxed);
+        let picked_at = self.picked_at.load(Ordering::Relaxed);
+        let written_at = self.written_at.load(Ordering::Relaxed);
+        if dispatched_at == 0 || picked_at == 0 || written_at == 0 {
+            return None;
+        }
+        Some(RequestPhases {
+            queue: Duration::from_nanos(picked_at.saturating_sub(dispatched_at)),
+            wire: Duration::from_nanos(written_at.saturating_sub(picked_at)),
+            server: Duration::from_nanos(now.saturating_sub(written_at)),
+        })
+    }
+}
+
+/// The monotonic time in nanos since the first call, it starts from 1 as 0 means unset.
+fn monotonic_nanos() -> u64 {
+    static BASE: OnceLock<Instant> = OnceLock::new();
+    BASE.get_or_init(Instant::now).elapsed().as_nanos() as u64 + 1
 }
 unsafe impl Sync for Reusable {}
 
@@ -172,8 +308,15 @@ impl Builder for StageBuilder {
             buffer_size: self.buffer_size.unwrap_or(1024000),
             recv_buffer_size: self.recv_buffer_size.unwrap(),
             send_buffer_size: self.send_buffer_size.unwrap(),
+            compression: self.compression.unwrap(),
+            tls: self.tls.unwrap(),
+            read_only: self.read_only.unwrap(),
+            heartbeat_config: self.heartbeat.unwrap(),
+            drain_timeout: self.drain_timeout.unwrap(),
+            decode_offload_threshold: self.decode_offload_threshold.unwrap(),
             handle,
             inbox,
+            heartbeat: None,
         }
         .set_name()
     }
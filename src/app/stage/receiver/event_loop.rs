@@ -25,7 +25,7 @@ impl EventLoop<ReportersHandles> for Receiver {
                         self.handle_frame_header(0)
                             .and_then(|_| self.handle_frame(n, 0, reporter_handles))
                             .map_err(|e| {
-                                error!("{}", e);
+                                error!("{}, {}", self.label, e);
                                 Need::Abort
                             })?;
                     }
@@ -90,6 +90,18 @@ impl Receiver {
             // update padding
             padding += self.total_length - start;
             giveload[start..self.total_length].copy_from_slice(&self.buffer[old_padding..padding]);
+            // decompress the giveload by the compression negotiated on the connection, so the workers decode it as
+            // it is, regardless of the compression of the connection
+            if giveload[1] & header::COMPRESSION == header::COMPRESSION {
+                match self.compression.decompress_frame(std::mem::take(giveload)) {
+                    Ok(decompressed) => *giveload = decompressed,
+                    // the worker fails to decode the empty giveload
+                    Err(e) => error!(
+                        "{}, failed to decompress the giveload of stream {}: {}",
+                        self.label, self.stream_id, e
+                    ),
+                }
+            }
             // tell reporter that giveload is ready.
             let reporter_handle = reporters_handles
                 .get(&compute_reporter_num(self.stream_id, self.appends_num))
@@ -99,7 +111,7 @@ impl Receiver {
                 .send(ReporterEvent::Response {
                     stream_id: self.stream_id,
                 })
-                .unwrap_or_else(|e| error!("{}", e));
+                .unwrap_or_else(|e| error!("{}, {}", self.label, e));
             // set header to false
             self.header = false;
             // update current_length
@@ -1,9 +1,16 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{reporter::*, *};
+use super::{
+    reporter::{Session, *},
+    *,
+};
+use crate::{
+    app::diagnostics::ConnectionLabel,
+    cql::{header, CqlReadHalf},
+};
 use anyhow::anyhow;
-use tokio::{io::AsyncReadExt, net::tcp::OwnedReadHalf};
+use tokio::io::AsyncReadExt;
 
 mod event_loop;
 mod init;
@@ -13,17 +20,20 @@ const CQL_FRAME_HEADER_BYTES_LENGTH: usize = 9;
 
 // Receiver builder
 builder!(ReceiverBuilder {
-    socket: OwnedReadHalf,
+    socket: CqlReadHalf,
+    label: ConnectionLabel,
     session_id: usize,
     payloads: Payloads,
     buffer_size: usize,
-    appends_num: i16
+    appends_num: i16,
+    compression: CompressionType
 });
 
 /// Receiver state
 pub struct Receiver {
     service: Service,
-    socket: OwnedReadHalf,
+    socket: CqlReadHalf,
+    label: ConnectionLabel,
     stream_id: i16,
     total_length: usize,
     current_length: usize,
@@ -32,6 +42,7 @@ pub struct Receiver {
     i: usize,
     appends_num: i16,
     payloads: Payloads,
+    compression: CompressionType,
 }
 
 impl ActorBuilder<ReportersHandles> for ReceiverBuilder {}
@@ -43,6 +54,7 @@ impl Builder for ReceiverBuilder {
         Self::State {
             service: Service::new(),
             socket: self.socket.unwrap(),
+            label: self.label.unwrap(),
             stream_id: 0,
             total_length: 0,
             current_length: 0,
@@ -51,6 +63,7 @@ impl Builder for ReceiverBuilder {
             i: 0,
             appends_num: self.appends_num.unwrap(),
             payloads: self.payloads.unwrap(),
+            compression: self.compression.unwrap(),
         }
         .set_name()
     }
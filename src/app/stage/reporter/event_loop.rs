@@ -2,6 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use crate::{
+    app::diagnostics::{self, DiagnosticEvent},
+    cql::{header, ProtocolVersion},
+};
 
 #[async_trait::async_trait]
 impl EventLoop<StageHandle> for Reporter {
@@ -11,54 +15,84 @@ impl EventLoop<StageHandle> for Reporter {
         supervisor: &mut Option<StageHandle>,
     ) -> Result<(), Need> {
         if let Some(supervisor) = supervisor.as_ref() {
-            while let Some(event) = self.inbox.rx.recv().await {
+            while let Some(event) = self.next_event().await {
                 match event {
-                    ReporterEvent::Request { worker, mut payload } => {
-                        if let Some(stream) = self.streams.iter().next().cloned() {
+                    ReporterEvent::Request { worker, payload } => {
+                        if self.draining {
+                            // the draining session doesn't accept new requests
+                            self.pending
+                                .push(worker.handle_error_async(WorkerError::Draining, &self.handle));
+                        } else if let Err(violation) = self.read_only.check(&payload, self.compression) {
+                            self.pending.push(
+                                worker.handle_error_async(WorkerError::ReadOnlyViolation(violation), &self.handle),
+                            );
+                        } else if let Some(stream) = self.streams.iter().next().cloned() {
+                            // encode the payload for the connection, which speaks v4 and compresses it by its
+                            // negotiated compression
+                            let mut payload = match self.compression.encode_request(ProtocolVersion::V4, &payload) {
+                                Ok(payload) => payload,
+                                Err(e) => {
+                                    self.pending
+                                        .push(worker.handle_error_async(WorkerError::Other(e), &self.handle));
+                                    continue;
+                                }
+                            };
                             // Send the event
-                            match &self.sender_handle {
-                                Some(sender) => {
+                            match self.sender_handle {
+                                Some(_) => {
                                     self.streams.remove(&stream);
                                     // Assign stream_id to the payload
                                     assign_stream_to_payload(stream, &mut payload);
                                     // store payload as reusable at payloads[stream]
                                     self.payloads[stream as usize].as_mut().replace(payload);
+                                    let timeout = worker.timeout();
+                                    worker.dispatched(&self.label);
                                     self.workers.insert(stream, worker);
-                                    sender.send(stream).unwrap_or_else(|e| error!("{}", e));
+                                    self.schedule_timeout(stream, timeout);
+                                    self.update_progress(false);
+                                    self.payloads[stream as usize].mark_dispatched();
+                                    if let Some(sender) = self.sender_handle.as_ref() {
+                                        sender.send(stream).unwrap_or_else(|e| error!("{}, {}", self.label, e));
+                                    }
                                 }
                                 None => {
                                     // This means the sender_tx had been droped as a result of checkpoint from
                                     // receiver
-                                    worker
-                                        .handle_error(WorkerError::Other(anyhow!("No Sender!")), &self.handle)
-                                        .unwrap_or_else(|e| error!("{}", e));
+                                    self.pending.push(
+                                        worker.handle_error_async(
+                                            WorkerError::Other(anyhow!("No Sender!")),
+                                            &self.handle,
+                                        ),
+                                    );
                                 }
                             }
                         } else {
                             // Send overload to the worker in-case we don't have anymore streams
-                            worker
-                                .handle_error(WorkerError::Overload, &self.handle)
-                                .unwrap_or_else(|e| error!("{}", e));
+                            self.pending
+                                .push(worker.handle_error_async(WorkerError::Overload, &self.handle));
                         }
                     }
                     ReporterEvent::Response { stream_id } => {
-                        self.handle_response(stream_id).unwrap_or_else(|e| error!("{}", e));
+                        self.handle_response(stream_id)
+                            .unwrap_or_else(|e| error!("{}, {}", self.label, e));
                     }
                     ReporterEvent::Err(io_error, stream_id) => {
                         self.handle_error(stream_id, WorkerError::Other(io_error))
-                            .unwrap_or_else(|e| error!("{}", e));
+                            .unwrap_or_else(|e| error!("{}, {}", self.label, e));
+                    }
+                    ReporterEvent::AuditStreams(tx) => {
+                        tx.send(self.audit_streams()).ok();
                     }
                     ReporterEvent::Session(session) => {
                         match session {
-                            Session::New(service, sender_handle) => {
+                            Session::New(service, sender_handle, compression) => {
                                 self.session_id += 1;
+                                self.compression = compression;
+                                self.update_progress(true);
                                 self.sender_handle = Some(sender_handle);
                                 // update microservice
                                 self.service.update_microservice(service.get_name(), service);
-                                info!(
-                                    "address: {}, shard_id: {}, reporter_id: {}, received session: {:?}",
-                                    &self.address, self.shard_id, self.reporter_id, self.session_id
-                                );
+                                info!("{}, received session: {:?}", self.label, self.session_id);
                                 if !self.service.is_stopping() {
                                     // degraded service
                                     self.service.update_status(ServiceStatus::Degraded);
@@ -85,10 +119,7 @@ impl EventLoop<StageHandle> for Reporter {
                                     // first we drain workers map from stucked requests, to force_consistency of
                                     // the old_session requests
                                     self.force_consistency();
-                                    warn!(
-                                        "address: {}, shard_id: {}, reporter_id: {}, closing session: {:?}",
-                                        &self.address, self.shard_id, self.reporter_id, self.session_id
-                                    );
+                                    diagnostics::emit(DiagnosticEvent::ConnectionClosed { label: self.label });
                                     if !self.service.is_stopping() {
                                         // Maintenance service mode
                                         self.service.update_status(ServiceStatus::Maintenance);
@@ -107,6 +138,12 @@ impl EventLoop<StageHandle> for Reporter {
                                     }
                                 };
                             }
+                            Session::Restart => {
+                                // drop the sender_handle, which closes the connection once all the reporters of
+                                // the stage drop theirs, and the stage will reconnect once the session is closed.
+                                self.sender_handle = None;
+                                self.fail_stalled();
+                            }
                             Session::Shutdown => {
                                 // drop the sender_handle to gracefully shut it down
                                 self.sender_handle = None;
@@ -114,11 +151,21 @@ impl EventLoop<StageHandle> for Reporter {
                                 self.handle = None;
                                 self.service.update_status(ServiceStatus::Stopping);
                             }
+                            Session::Drain(deadline) => {
+                                self.service.update_status(ServiceStatus::Stopping);
+                                self.drain(deadline);
+                            }
                         }
                         let event = StageEvent::Reporter(self.service.clone());
                         supervisor.send(event).ok();
                     }
                 }
+                // the draining session is closed once its last in-flight request is answered or lost
+                self.close_if_drained();
+            }
+            // complete the pending worker handlers, ie the ones of the lost requests
+            while let Some(res) = self.pending.next().await {
+                res.unwrap_or_else(|e| error!("{}, {}", self.label, e));
             }
             Ok(())
         } else {
@@ -131,38 +178,133 @@ impl Reporter {
     fn handle_response(&mut self, stream: i16) -> anyhow::Result<()> {
         // push the stream_id back to streams vector.
         self.streams.insert(stream);
+        if self.stalled_streams.remove(&stream) {
+            // late response of a stalled request, which has already been failed.
+            self.payloads[stream as usize].as_mut().take();
+            return Ok(());
+        }
         // remove the worker from workers.
         if let Some(worker) = self.workers.remove(&stream) {
+            self.update_progress(true);
+            let latency = self.record_phases(stream);
             if let Some(payload) = self.payloads[stream as usize].as_mut().take() {
                 if is_cql_error(&payload) {
                     let error = Decoder::try_from(payload)
                         .and_then(|decoder| CqlError::new(&decoder).map(|e| WorkerError::Cql(e)))
                         .unwrap_or_else(|e| WorkerError::Other(e));
-                    worker.handle_error(error, &self.handle)?;
+                    self.metrics.record(latency, true);
+                    if let WorkerError::Cql(cql_error) = &error {
+                        metrics::record_error(cql_error.code);
+                        self.record_missing_replicas(cql_error);
+                    }
+                    self.pending.push(worker.handle_error_async(error, &self.handle));
                 } else {
-                    worker.handle_response(payload)?;
+                    self.metrics.record(latency, false);
+                    self.emit_custom_payload(&payload);
+                    self.emit_warnings(&payload);
+                    if matches!(self.decode_offload_threshold, Some(threshold) if payload.len() >= threshold) {
+                        // decode the large response on the blocking pool, so it doesn't stall the reporter, while
+                        // it's still driven along with the other pending handlers
+                        let offloaded = tokio::task::spawn_blocking(move || worker.handle_response(payload));
+                        self.pending.push(Box::pin(async move {
+                            offloaded
+                                .await
+                                .map_err(|e| anyhow!("failed to handle the offloaded response: {}", e))?
+                        }));
+                    } else {
+                        self.pending.push(worker.handle_response_async(payload));
+                    }
                 }
             } else {
-                error!("No payload found while handling response for stream {}!", stream);
+                error!(
+                    "{}, no payload found while handling response for stream {}!",
+                    self.label, stream
+                );
             }
         } else {
-            error!("No worker found while handling response for stream {}!", stream);
+            error!(
+                "{}, no worker found while handling response for stream {}!",
+                self.label, stream
+            );
         }
         Ok(())
     }
+    fn record_missing_replicas(&self, error: &CqlError) {
+        if let (Some(missing), Some(consistency)) = (error.missing_replicas(), error.consistency()) {
+            diagnostics::record_missing_replicas(missing);
+            diagnostics::emit(DiagnosticEvent::ReplicasMissing {
+                label: self.label,
+                code: error.code,
+                consistency,
+                missing,
+            });
+        }
+    }
+    fn emit_custom_payload(&self, payload: &[u8]) {
+        if payload.len() > 1 && payload[1] & header::CUSTOM_PAYLOAD == header::CUSTOM_PAYLOAD {
+            match Decoder::try_from(payload.to_vec()).map(|mut decoder| decoder.take_custom_payload()) {
+                Ok(Some(custom_payload)) => diagnostics::emit(DiagnosticEvent::CustomPayload {
+                    label: self.label,
+                    payload: custom_payload,
+                }),
+                Ok(None) => (),
+                Err(e) => error!("{}, failed to decode the response custom payload: {}", self.label, e),
+            }
+        }
+    }
+    fn emit_warnings(&self, payload: &[u8]) {
+        if payload.len() > 1 && payload[1] & header::WARNING == header::WARNING {
+            match Decoder::try_from(payload.to_vec()).map(|mut decoder| decoder.take_warnings()) {
+                Ok(Some(warnings)) => diagnostics::emit(DiagnosticEvent::ServerWarnings {
+                    label: self.label,
+                    warnings,
+                }),
+                Ok(None) => (),
+                Err(e) => error!("{}, failed to decode the response warnings: {}", self.label, e),
+            }
+        }
+    }
+    /// Record the latency phases of the request, returns its total latency if it's known
+    fn record_phases(&self, stream: i16) -> Option<Duration> {
+        if let (Some(handle), Some(phases)) = (self.handle.as_ref(), self.payloads[stream as usize].phases()) {
+            handle.progress.record(&phases);
+            if matches!(diagnostics::slow_request_threshold(), Some(threshold) if phases.total() >= threshold) {
+                diagnostics::emit(DiagnosticEvent::SlowRequest {
+                    label: self.label,
+                    phases,
+                });
+            }
+            Some(phases.total())
+        } else {
+            None
+        }
+    }
     fn handle_error(&mut self, stream: i16, error: WorkerError) -> anyhow::Result<()> {
         // push the stream_id back to streams vector.
         self.streams.insert(stream);
+        if self.stalled_streams.remove(&stream) {
+            // late error of a stalled request, which has already been failed.
+            self.payloads[stream as usize].as_mut().take();
+            return Ok(());
+        }
         // remove the worker from workers and send error.
         if let Some(worker) = self.workers.remove(&stream) {
+            self.update_progress(true);
+            self.metrics.record(None, true);
             // drop payload.
             if let Some(_payload) = self.payloads[stream as usize].as_mut().take() {
-                worker.handle_error(error, &self.handle)?;
+                self.pending.push(worker.handle_error_async(error, &self.handle));
             } else {
-                error!("No payload found while handling error for stream {}!", stream);
+                error!(
+                    "{}, no payload found while handling error for stream {}!",
+                    self.label, stream
+                );
             }
         } else {
-            error!("No worker found while handling error for stream {}!", stream);
+            error!(
+                "{}, no worker found while handling error for stream {}!",
+                self.label, stream
+            );
         }
         Ok(())
     }
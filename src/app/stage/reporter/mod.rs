@@ -3,44 +3,221 @@
 
 use super::*;
 use crate::{
-    app::worker::{Worker, WorkerError},
-    cql::{CqlError, Decoder},
+    app::{
+        diagnostics::{self, ConnectionLabel, DiagnosticEvent},
+        metrics::{self, RequestMetrics},
+        worker::{Worker, WorkerError, WorkerFuture},
+    },
+    cql::{CompressionType, CqlError, Decoder},
 };
 use anyhow::anyhow;
+use futures::{
+    future::BoxFuture,
+    stream::{FuturesUnordered, StreamExt},
+    task::Poll,
+};
 use sender::SenderHandle;
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashSet},
     convert::TryFrom,
     ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
+use tokio::sync::oneshot;
 
 mod event_loop;
 mod init;
 mod terminating;
 
+/// The sentinel stream id of the drain deadline timer, which is never assigned to a request
+const DRAIN_DEADLINE: i16 = -1;
+
 /// Workers Map holds all the workers_ids
 type Workers = HashMap<i16, Box<dyn Worker>>;
+/// The timers of the requests which have a timeout, which resolve to their stream id and dispatch id
+type Deadlines = FuturesUnordered<BoxFuture<'static, (i16, u64)>>;
+
+/// The next completion of a worker handler or a request timer
+enum Completion {
+    Handled(anyhow::Result<()>),
+    Expired(i16, u64),
+}
 
 // Reporter builder
 builder!(ReporterBuilder {
     session_id: usize,
-    reporter_id: u8,
-    shard_id: u16,
+    label: ConnectionLabel,
     streams: HashSet<i16>,
-    address: SocketAddr,
-    payloads: Payloads
+    payloads: Payloads,
+    read_only: ReadOnly,
+    decode_offload_threshold: Option<usize>
 });
 
 /// ReporterHandle to be passed to the children (Stage)
 #[derive(Clone)]
 pub struct ReporterHandle {
     tx: mpsc::UnboundedSender<ReporterEvent>,
+    progress: Arc<ReporterProgress>,
+    label: ConnectionLabel,
+}
+
+impl ReporterHandle {
+    /// Get the progress of the reporter
+    pub fn progress(&self) -> Arc<ReporterProgress> {
+        self.progress.clone()
+    }
+    /// Get the label of the reporter connection
+    pub fn label(&self) -> ConnectionLabel {
+        self.label
+    }
+    /// Audit the stream ids of the reporter, ie the ones which are leased to its in-flight requests.
+    /// Returns None if the reporter is closed.
+    pub async fn audit_streams(&self) -> Option<StreamsAudit> {
+        let (tx, rx) = oneshot::channel();
+        self.tx.send(ReporterEvent::AuditStreams(tx)).ok()?;
+        rx.await.ok()
+    }
+}
+
+/// The audit of the stream ids of a reporter, which is used to debug the leaked or orphaned streams
+#[derive(Debug, Clone)]
+pub struct StreamsAudit {
+    /// The label of the reporter connection
+    pub label: ConnectionLabel,
+    /// The stream ids which are leased to the in-flight requests, in ascending order
+    pub leased: Vec<i16>,
+    /// The stream ids of the failed requests (ie the timed out ones) which await their late response before being
+    /// reused, in ascending order
+    pub stalled: Vec<i16>,
+    /// The number of free stream ids
+    pub free: usize,
+    /// The number of stream ids owned by the reporter
+    pub total: usize,
+    /// The stream ids which are free while being leased or stalled, which is never expected
+    pub double_booked: Vec<i16>,
+    /// Whether the reporter is draining its in-flight requests before closing its session
+    pub draining: bool,
+}
+
+impl StreamsAudit {
+    /// Get the number of stream ids which are neither free, leased nor stalled, ie the leaked ones
+    pub fn leaked(&self) -> usize {
+        (self.total + self.double_booked.len()).saturating_sub(self.free + self.leased.len() + self.stalled.len())
+    }
+    /// Check whether every stream id is either free, leased or stalled
+    pub fn is_consistent(&self) -> bool {
+        self.leaked() == 0 && self.double_booked.is_empty()
+    }
+}
+
+/// The progress of a reporter, which is monitored by the node watchdog
+#[derive(Default)]
+pub struct ReporterProgress {
+    /// The last time (in millis since the unix epoch) the reporter received a response,
+    /// or started to have in-flight requests.
+    last_progress: AtomicU64,
+    in_flight: AtomicUsize,
+    queue: PhaseStats,
+    wire: PhaseStats,
+    server: PhaseStats,
+}
+
+/// The aggregated latency stats of a request phase
+#[derive(Default)]
+pub struct PhaseStats {
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl PhaseStats {
+    /// Get the number of recorded requests
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+    /// Get the total duration of the recorded requests
+    pub fn total(&self) -> Duration {
+        Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed))
+    }
+    /// Get the mean duration of the recorded requests
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::default(),
+            count => Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed) / count),
+        }
+    }
+    /// Get the max duration of the recorded requests
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed))
+    }
+    fn record(&self, duration: Duration) {
+        let nanos = duration.as_nanos() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+}
+
+impl ReporterProgress {
+    /// Get the number of in-flight requests
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+    /// Get the elapsed duration since the reporter last made progress
+    pub fn idle(&self) -> Duration {
+        Duration::from_millis(now_millis().saturating_sub(self.last_progress.load(Ordering::Relaxed)))
+    }
+    /// Get the time spent by the requests in the sender queue, which is the client-side queuing delay
+    pub fn queue_time(&self) -> &PhaseStats {
+        &self.queue
+    }
+    /// Get the time spent by the requests writing to the socket
+    pub fn wire_time(&self) -> &PhaseStats {
+        &self.wire
+    }
+    /// Get the time from writing the requests until receiving their responses, which is mostly the server time
+    pub fn server_time(&self) -> &PhaseStats {
+        &self.server
+    }
+    fn record(&self, phases: &RequestPhases) {
+        self.queue.record(phases.queue);
+        self.wire.record(phases.wire);
+        self.server.record(phases.server);
+    }
+    pub(crate) fn touch(&self) {
+        self.last_progress.store(now_millis(), Ordering::Relaxed);
+    }
+    pub(crate) fn set_in_flight(&self, in_flight: usize) {
+        self.in_flight.store(in_flight, Ordering::Relaxed);
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
 }
 /// NodeInbox is used to recv events
 pub struct ReporterInbox {
     rx: mpsc::UnboundedReceiver<ReporterEvent>,
 }
 
+#[cfg(test)]
+impl ReporterHandle {
+    /// Create a handle without reporter, along with the receiver of its events, so the tests can stub the reporter
+    pub(crate) fn detached(label: ConnectionLabel) -> (Self, mpsc::UnboundedReceiver<ReporterEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let handle = Self {
+            tx,
+            progress: Arc::new(ReporterProgress::default()),
+            label,
+        };
+        (handle, rx)
+    }
+}
+
 impl Deref for ReporterHandle {
     type Target = mpsc::UnboundedSender<ReporterEvent>;
 
@@ -73,27 +250,56 @@ pub enum ReporterEvent {
     Err(anyhow::Error, i16),
     /// The stage session.
     Session(Session),
+    /// Audit the stream ids of the reporter.
+    AuditStreams(oneshot::Sender<StreamsAudit>),
 }
 
 pub enum Session {
-    New(Service, sender::SenderHandle),
+    /// The new session of the shard connection, along with the compression it negotiated
+    New(Service, sender::SenderHandle, CompressionType),
     Service(Service),
+    /// Restart the session of a stalled reporter
+    Restart,
     Shutdown,
+    /// Stop assigning streams to the new requests, and shut down once the in-flight requests are answered, or once
+    /// the drain deadline elapses, in which case the remaining requests are failed with `WorkerError::Lost`
+    Drain(Duration),
 }
 
 /// Reporter state
 pub struct Reporter {
     service: Service,
-    address: SocketAddr,
+    label: ConnectionLabel,
+    /// The request metrics of the shard connection, which are shared with the other reporters of the stage
+    metrics: Arc<RequestMetrics>,
     session_id: usize,
-    reporter_id: u8,
     streams: HashSet<i16>,
-    shard_id: u16,
+    /// The number of streams owned by the reporter
+    stream_count: usize,
     workers: Workers,
+    stalled_streams: HashSet<i16>,
     sender_handle: Option<SenderHandle>,
     payloads: Payloads,
     handle: Option<ReporterHandle>,
     inbox: ReporterInbox,
+    /// The pending futures of the async worker handlers, which are driven along with the reporter events
+    pending: FuturesUnordered<WorkerFuture>,
+    /// The timers of the in-flight requests which have a timeout
+    deadlines: Deadlines,
+    /// The dispatch id of the in-flight requests which have a timeout, so a timer only expires the request
+    /// it was started for, rather than a later request which reuses its stream
+    expirations: HashMap<i16, u64>,
+    /// The number of dispatched requests, which assigns their dispatch id
+    dispatched: u64,
+    /// Whether the session is draining, ie awaiting the responses of its in-flight requests before shutting down
+    draining: bool,
+    /// The read-only mode of the cluster, which rejects the mutation and DDL requests
+    read_only: ReadOnly,
+    /// The compression negotiated by the connection of the current session, which compresses the requests
+    compression: CompressionType,
+    /// The response size in bytes from which the workers handle (and therefore decode) the responses on the blocking
+    /// thread pool rather than within the reporter, if it's enabled
+    decode_offload_threshold: Option<usize>,
 }
 
 impl Reporter {
@@ -109,21 +315,36 @@ impl Builder for ReporterBuilder {
     type State = Reporter;
     fn build(self) -> Self::State {
         let (tx, rx) = mpsc::unbounded_channel::<ReporterEvent>();
-        let handle = Some(ReporterHandle { tx });
+        let label = self.label.unwrap();
+        let handle = Some(ReporterHandle {
+            tx,
+            progress: Arc::new(ReporterProgress::default()),
+            label,
+        });
         let inbox = ReporterInbox { rx };
+        let streams = self.streams.unwrap();
 
         Self::State {
             service: Service::new(),
-            address: self.address.unwrap(),
+            label,
+            metrics: metrics::connection(label),
             session_id: self.session_id.unwrap(),
-            reporter_id: self.reporter_id.unwrap(),
-            streams: self.streams.unwrap(),
-            shard_id: self.shard_id.unwrap(),
+            stream_count: streams.len(),
+            streams,
             workers: HashMap::new(),
+            stalled_streams: HashSet::new(),
             sender_handle: None,
             payloads: self.payloads.unwrap(),
             handle,
             inbox,
+            pending: FuturesUnordered::new(),
+            deadlines: FuturesUnordered::new(),
+            expirations: HashMap::new(),
+            dispatched: 0,
+            draining: false,
+            read_only: self.read_only.unwrap_or_default(),
+            compression: CompressionType::default(),
+            decode_offload_threshold: self.decode_offload_threshold.unwrap_or_default(),
         }
         .set_name()
     }
@@ -133,7 +354,7 @@ impl Builder for ReporterBuilder {
 impl Name for Reporter {
     fn set_name(mut self) -> Self {
         // create name from the reporter_id
-        let name = self.reporter_id.to_string();
+        let name = self.label.index.unwrap_or_default().to_string();
         self.service.update_name(name);
         self
     }
@@ -153,14 +374,153 @@ impl AknShutdown<Reporter> for StageHandle {
 
 impl Reporter {
     fn force_consistency(&mut self) {
+        // the stalled streams can be reused now, as no more responses are expected from the old session
+        self.streams.extend(self.stalled_streams.drain());
         for (stream_id, worker_id) in self.workers.drain() {
             // push the stream_id back into the streams vector
             self.streams.insert(stream_id);
             // tell worker_id that we lost the response for his request, because we lost scylla connection in
             // middle of request cycle, still this is a rare case.
-            worker_id
-                .handle_error(WorkerError::Lost, &self.handle)
-                .unwrap_or_else(|e| error!("{}", e));
+            self.pending
+                .push(worker_id.handle_error_async(WorkerError::Lost, &self.handle));
+        }
+        self.update_progress(false);
+    }
+    /// Fail the in-flight requests of a stalled session, without reusing their streams
+    /// until the session is closed, as late responses might still be received.
+    fn fail_stalled(&mut self) {
+        for (stream_id, worker) in self.workers.drain() {
+            self.stalled_streams.insert(stream_id);
+            self.pending
+                .push(worker.handle_error_async(WorkerError::Lost, &self.handle));
+        }
+        self.update_progress(false);
+    }
+    /// Start the timer of the dispatched request if it has a timeout
+    fn schedule_timeout(&mut self, stream: i16, timeout: Option<Duration>) {
+        self.dispatched += 1;
+        match timeout {
+            Some(timeout) => {
+                let dispatch = self.dispatched;
+                self.expirations.insert(stream, dispatch);
+                self.deadlines.push(Box::pin(async move {
+                    tokio::time::sleep(timeout).await;
+                    (stream, dispatch)
+                }));
+            }
+            None => {
+                self.expirations.remove(&stream);
+            }
+        }
+    }
+    /// Fail the request with a timeout error if it's still in-flight, without reusing its stream until the late
+    /// response is received or the session is closed.
+    fn expire(&mut self, stream: i16, dispatch: u64) {
+        if self.expirations.get(&stream) != Some(&dispatch) {
+            return;
+        }
+        self.expirations.remove(&stream);
+        if let Some(worker) = self.workers.remove(&stream) {
+            self.stalled_streams.insert(stream);
+            self.metrics.record(None, true);
+            self.pending
+                .push(worker.handle_error_async(WorkerError::Timeout, &self.handle));
+            self.update_progress(false);
+        }
+    }
+    /// Receive the next event, while driving the pending worker handlers and the request timers
+    async fn next_event(&mut self) -> Option<ReporterEvent> {
+        loop {
+            if self.pending.is_empty() && self.deadlines.is_empty() {
+                return self.inbox.rx.recv().await;
+            }
+            let (pending, deadlines) = (&mut self.pending, &mut self.deadlines);
+            // the empty sets are skipped, as they can only grow while handling the next event
+            let completion = futures::future::poll_fn(|cx| {
+                if let Poll::Ready(Some(res)) = pending.poll_next_unpin(cx) {
+                    return Poll::Ready(Completion::Handled(res));
+                }
+                if let Poll::Ready(Some((stream, dispatch))) = deadlines.poll_next_unpin(cx) {
+                    return Poll::Ready(Completion::Expired(stream, dispatch));
+                }
+                Poll::Pending
+            });
+            let completion = match futures::future::select(Box::pin(self.inbox.rx.recv()), completion).await {
+                futures::future::Either::Left((event, _)) => return event,
+                futures::future::Either::Right((completion, _)) => completion,
+            };
+            match completion {
+                Completion::Handled(Err(e)) => error!("{}, {}", self.label, e),
+                Completion::Handled(Ok(())) => (),
+                Completion::Expired(DRAIN_DEADLINE, _) => self.close_drained(),
+                Completion::Expired(stream, dispatch) => {
+                    self.expire(stream, dispatch);
+                    self.close_if_drained();
+                }
+            }
+        }
+    }
+    /// Stop assigning streams to the new requests, and close the session once the in-flight requests are answered
+    /// or the deadline elapses
+    fn drain(&mut self, deadline: Duration) {
+        self.draining = true;
+        self.deadlines.push(Box::pin(async move {
+            tokio::time::sleep(deadline).await;
+            (DRAIN_DEADLINE, 0)
+        }));
+        self.close_if_drained();
+    }
+    /// Close the session of the draining reporter once its in-flight requests are answered
+    fn close_if_drained(&mut self) {
+        if self.draining && self.workers.is_empty() {
+            self.close_drained();
+        }
+    }
+    /// Close the session of the draining reporter, failing the requests which are still in-flight
+    fn close_drained(&mut self) {
+        if !self.draining {
+            return;
+        }
+        self.draining = false;
+        let abandoned = self.workers.len();
+        self.fail_stalled();
+        diagnostics::emit(DiagnosticEvent::ConnectionDrained {
+            label: self.label,
+            abandoned,
+        });
+        // drop the sender_handle to gracefully shut it down, and the self handle, otherwise the reporter never
+        // shuts down.
+        self.sender_handle = None;
+        self.handle = None;
+    }
+    /// Audit the stream ids of the reporter
+    fn audit_streams(&self) -> StreamsAudit {
+        let leased: BTreeSet<i16> = self.workers.keys().copied().collect();
+        let stalled: BTreeSet<i16> = self.stalled_streams.iter().copied().collect();
+        let double_booked = leased
+            .iter()
+            .chain(stalled.iter())
+            .filter(|stream| self.streams.contains(stream))
+            .copied()
+            .collect();
+        StreamsAudit {
+            label: self.label,
+            leased: leased.into_iter().collect(),
+            stalled: stalled.into_iter().collect(),
+            free: self.streams.len(),
+            total: self.stream_count,
+            double_booked,
+            draining: self.draining,
+        }
+    }
+    fn update_progress(&self, responded: bool) {
+        if let Some(handle) = self.handle.as_ref() {
+            let progress = &handle.progress;
+            // the idle time is measured from the last response, or since the first in-flight request
+            if responded || progress.in_flight() == 0 {
+                progress.touch();
+            }
+            progress.set_in_flight(self.workers.len());
         }
     }
 }
@@ -178,3 +538,263 @@ fn assign_stream_to_payload(stream: i16, payload: &mut Vec<u8>) {
 fn is_cql_error(buffer: &[u8]) -> bool {
     buffer[4] == 0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::worker::{OneshotWorker, RequestTimeout},
+        cql::{opcode::RESULT, Consistency, Query, Statements},
+    };
+    use std::thread::{self, ThreadId};
+    use tokio::{task::JoinHandle, time::timeout};
+
+    const STREAMS: i16 = 4;
+
+    /// The reporter of a stub shard connection
+    struct Stub {
+        reporter: JoinHandle<()>,
+        handle: ReporterHandle,
+        payloads: Payloads,
+        /// The stream ids sent to the stub sender, which is closed once the reporter drops its session
+        sent: mpsc::UnboundedReceiver<i16>,
+    }
+
+    impl Stub {
+        /// Start the reporter, and open its session
+        fn start() -> Self {
+            Self::start_with(None)
+        }
+        /// Start the reporter along with its decode offload threshold, and open its session
+        fn start_with(decode_offload_threshold: Option<usize>) -> Self {
+            let payloads: Payloads = Arc::new((0..STREAMS).map(|_| Reusable::default()).collect());
+            let reporter = ReporterBuilder::new()
+                .session_id(0)
+                .label(ConnectionLabel::shard(([127, 0, 0, 1], 9042).into(), 0).reporter(0))
+                .streams((0..STREAMS).collect())
+                .payloads(payloads.clone())
+                .decode_offload_threshold(decode_offload_threshold)
+                .build();
+            let handle = reporter.clone_handle().unwrap();
+            let (tx, _stage_events) = mpsc::unbounded_channel();
+            let reporter = tokio::spawn(reporter.start(Some(StageHandle { tx })));
+            let (sender, sent) = sender::SenderHandle::detached();
+            handle
+                .send(ReporterEvent::Session(Session::New(
+                    Service::new(),
+                    sender,
+                    CompressionType::Uncompressed,
+                )))
+                .ok();
+            Self {
+                reporter,
+                handle,
+                payloads,
+                sent,
+            }
+        }
+        /// Send a request, along with the receiver of its response
+        fn request(&self) -> oneshot::Receiver<Result<Vec<u8>, WorkerError>> {
+            let (worker, rx) = OneshotWorker::boxed();
+            self.send(worker);
+            rx
+        }
+        /// Send a request which times out after the duration, along with the receiver of its response
+        fn request_within(&self, duration: Duration) -> oneshot::Receiver<Result<Vec<u8>, WorkerError>> {
+            let (worker, rx) = OneshotWorker::boxed();
+            self.send(RequestTimeout::wrap(worker, Some(duration)));
+            rx
+        }
+        /// Send a request, along with the receiver of the thread which handles its response
+        fn request_thread(&self) -> oneshot::Receiver<ThreadId> {
+            let (tx, rx) = oneshot::channel();
+            self.send(Box::new(ThreadWorker(tx)));
+            rx
+        }
+        fn send(&self, worker: Box<dyn Worker>) {
+            let Query(payload) = Query::new()
+                .statement("SELECT * FROM ks.t")
+                .consistency(Consistency::One)
+                .build()
+                .unwrap();
+            self.handle.send(ReporterEvent::Request { worker, payload }).ok();
+        }
+        /// Receive the stream id of the next request written to the connection
+        async fn written(&mut self) -> i16 {
+            timeout(Duration::from_secs(5), self.sent.recv())
+                .await
+                .unwrap()
+                .unwrap()
+        }
+        /// Answer the request of the stream by a void RESULT, as the receiver does
+        fn respond(&self, stream: i16) {
+            self.respond_with(stream, 0)
+        }
+        /// Answer the request of the stream by a void RESULT which is padded by the given number of bytes
+        fn respond_with(&self, stream: i16, padding: usize) {
+            let mut frame = vec![0x84, 0, (stream >> 8) as u8, stream as u8, RESULT];
+            frame.extend(&(4 + padding as i32).to_be_bytes());
+            frame.extend(&1i32.to_be_bytes());
+            frame.resize(frame.len() + padding, 0);
+            self.payloads[stream as usize].as_mut().replace(frame);
+            self.handle.send(ReporterEvent::Response { stream_id: stream }).ok();
+        }
+        async fn audit(&self) -> StreamsAudit {
+            self.handle.audit_streams().await.unwrap()
+        }
+        /// Await the reporter to drop its session, then to shut down once its handle is dropped
+        async fn closed(mut self) {
+            assert!(timeout(Duration::from_secs(5), self.sent.recv())
+                .await
+                .unwrap()
+                .is_none());
+            drop(self.handle);
+            timeout(Duration::from_secs(5), self.reporter).await.unwrap().unwrap();
+        }
+    }
+
+    /// The worker which reports the thread which handles its response
+    struct ThreadWorker(oneshot::Sender<ThreadId>);
+
+    impl Worker for ThreadWorker {
+        fn handle_response(self: Box<Self>, _giveload: Vec<u8>) -> anyhow::Result<()> {
+            self.0.send(thread::current().id()).ok();
+            Ok(())
+        }
+        fn handle_error(self: Box<Self>, error: WorkerError, _reporter: &Option<ReporterHandle>) -> anyhow::Result<()> {
+            Err(anyhow!(error))
+        }
+    }
+
+    async fn response<T>(rx: oneshot::Receiver<T>) -> T {
+        timeout(Duration::from_secs(5), rx).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn drain_completes_once_answered() {
+        let mut stub = Stub::start();
+        let (first, second) = (stub.request(), stub.request());
+        let streams = [stub.written().await, stub.written().await];
+        stub.handle
+            .send(ReporterEvent::Session(Session::Drain(Duration::from_secs(60))))
+            .ok();
+        // the draining session rejects the new requests, while it awaits the in-flight ones
+        assert!(matches!(response(stub.request()).await, Err(WorkerError::Draining)));
+        let audit = stub.audit().await;
+        assert!(audit.draining);
+        assert_eq!(audit.leased.len(), 2);
+        assert!(audit.is_consistent());
+        stub.respond(streams[0]);
+        assert!(response(first).await.is_ok());
+        assert!(stub.audit().await.draining);
+        // the session is closed once its last in-flight request is answered, well before the deadline
+        stub.respond(streams[1]);
+        assert!(response(second).await.is_ok());
+        let audit = stub.audit().await;
+        assert!(!audit.draining);
+        assert_eq!(audit.free, STREAMS as usize);
+        assert!(audit.is_consistent());
+        stub.closed().await;
+    }
+
+    #[tokio::test]
+    async fn drain_deadline_abandons_in_flight() {
+        let mut stub = Stub::start();
+        let (answered, abandoned) = (stub.request(), stub.request());
+        let streams = [stub.written().await, stub.written().await];
+        stub.respond(streams[0]);
+        assert!(response(answered).await.is_ok());
+        stub.handle
+            .send(ReporterEvent::Session(Session::Drain(Duration::from_millis(50))))
+            .ok();
+        // the request which isn't answered before the deadline is lost
+        assert!(matches!(response(abandoned).await, Err(WorkerError::Lost)));
+        // its stream awaits the late response before being reused
+        let audit = stub.audit().await;
+        assert!(!audit.draining);
+        assert!(audit.leased.is_empty());
+        assert_eq!(audit.stalled, vec![streams[1]]);
+        assert!(audit.is_consistent());
+        stub.respond(streams[1]);
+        let audit = stub.audit().await;
+        assert!(audit.stalled.is_empty());
+        assert_eq!(audit.free, STREAMS as usize);
+        stub.closed().await;
+    }
+
+    #[tokio::test]
+    async fn timeout_releases_the_stream() {
+        let mut stub = Stub::start();
+        let (expired, answered) = (
+            stub.request_within(Duration::from_millis(50)),
+            stub.request_within(Duration::from_millis(50)),
+        );
+        let streams = [stub.written().await, stub.written().await];
+        // the answered request isn't expired by its timer
+        stub.respond(streams[1]);
+        assert!(response(answered).await.is_ok());
+        assert!(matches!(response(expired).await, Err(WorkerError::Timeout)));
+        // the stream of the expired request is released, but it's stalled until the late response is received
+        let audit = stub.audit().await;
+        assert!(audit.leased.is_empty());
+        assert_eq!(audit.stalled, vec![streams[0]]);
+        assert_eq!(audit.free, STREAMS as usize - 1);
+        assert!(audit.is_consistent());
+        // the late response is dropped, and the stream is reused
+        stub.respond(streams[0]);
+        let audit = stub.audit().await;
+        assert!(audit.stalled.is_empty());
+        assert_eq!(audit.free, STREAMS as usize);
+        assert!(audit.is_consistent());
+        let next = stub.request();
+        let stream = stub.written().await;
+        stub.respond(stream);
+        assert!(response(next).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn offload_large_responses() {
+        let mut stub = Stub::start_with(Some(1024));
+        let (small, large) = (stub.request_thread(), stub.request_thread());
+        let streams = [stub.written().await, stub.written().await];
+        stub.respond(streams[0]);
+        stub.respond_with(streams[1], 1024);
+        // the small response is handled within the reporter, on the runtime thread of the test
+        assert_eq!(response(small).await, thread::current().id());
+        // while the large one is handled on the blocking pool
+        assert_ne!(response(large).await, thread::current().id());
+        let audit = stub.audit().await;
+        assert_eq!(audit.free, STREAMS as usize);
+        assert!(audit.is_consistent());
+    }
+
+    #[test]
+    fn streams_audit() {
+        let audit = StreamsAudit {
+            label: ConnectionLabel::shard(([127, 0, 0, 1], 9042).into(), 0),
+            leased: vec![1, 2],
+            stalled: vec![3],
+            free: 5,
+            total: 8,
+            double_booked: Vec::new(),
+            draining: false,
+        };
+        assert_eq!(audit.leaked(), 0);
+        assert!(audit.is_consistent());
+        // a stream which is neither free, leased nor stalled is leaked
+        let leaked = StreamsAudit {
+            free: 3,
+            ..audit.clone()
+        };
+        assert_eq!(leaked.leaked(), 2);
+        assert!(!leaked.is_consistent());
+        // a stream which is free while being leased is double booked, rather than leaked
+        let double_booked = StreamsAudit {
+            free: 6,
+            double_booked: vec![2],
+            ..audit
+        };
+        assert_eq!(double_booked.leaked(), 0);
+        assert!(!double_booked.is_consistent());
+    }
+}
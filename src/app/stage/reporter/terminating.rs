@@ -11,10 +11,7 @@ impl Terminating<StageHandle> for Reporter {
         _supervisor: &mut Option<StageHandle>,
     ) -> Result<(), Need> {
         self.force_consistency();
-        warn!(
-            "reporter_id: {} of shard_id: {} in node: {}, gracefully shutting down.",
-            self.reporter_id, self.shard_id, &self.address
-        );
+        warn!("{}, gracefully shutting down.", self.label);
         _status
     }
 }
@@ -18,21 +18,27 @@ impl EventLoop<ReportersHandles> for Sender {
             }
             while let Some(stream_id) = self.inbox.rx.recv().await {
                 // write the payload to the socket, make sure the result is valid
-                if let Some(payload) = self.payloads[stream_id as usize].as_ref_payload() {
+                let reusable = &self.payloads[stream_id as usize];
+                if let Some(payload) = reusable.as_ref_payload() {
+                    reusable.mark_picked();
+                    // the payload is encoded by the reporter for the connection, see `Session::New`
                     if let Err(io_error) = self.socket.write_all(payload).await {
-                        // send to reporter ReporterEvent::Err(io_error, stream_id)
+                        let error = anyhow!(io_error);
+                        // send to reporter ReporterEvent::Err(error, stream_id)
                         if let Some(reporter_handle) =
                             reporter_handles.get(&compute_reporter_num(stream_id, self.appends_num))
                         {
                             reporter_handle
-                                .send(ReporterEvent::Err(anyhow!(io_error), stream_id))
-                                .unwrap_or_else(|e| error!("{}", e))
+                                .send(ReporterEvent::Err(error, stream_id))
+                                .unwrap_or_else(|e| error!("{}, {}", self.label, e))
                         } else {
-                            error!("No reporter found for stream {}!", stream_id);
+                            error!("{}, no reporter found for stream {}!", self.label, stream_id);
                         }
+                    } else {
+                        reusable.mark_written();
                     }
                 } else {
-                    error!("No payload found for stream {}!", stream_id);
+                    error!("{}, no payload found for stream {}!", self.label, stream_id);
                 }
             }
             Ok(())
@@ -9,7 +9,8 @@ impl Init<ReportersHandles> for Sender {
         self.service.update_status(ServiceStatus::Initializing);
         if let (Some(my_handle), Some(reporter_handles)) = (self.handle.take(), supervisor.as_ref()) {
             for reporter_handle in reporter_handles.values() {
-                let event = ReporterEvent::Session(Session::New(self.service.clone(), my_handle.clone()));
+                let event =
+                    ReporterEvent::Session(Session::New(self.service.clone(), my_handle.clone(), self.compression));
                 let _ = reporter_handle.send(event);
             }
             status
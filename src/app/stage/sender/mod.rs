@@ -1,9 +1,13 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{reporter::*, *};
+use super::{
+    reporter::{Session, *},
+    *,
+};
+use crate::{app::diagnostics::ConnectionLabel, cql::CqlWriteHalf};
 use anyhow::anyhow;
-use tokio::{io::AsyncWriteExt, net::tcp::OwnedWriteHalf};
+use tokio::io::AsyncWriteExt;
 
 mod event_loop;
 mod init;
@@ -11,9 +15,11 @@ mod terminating;
 
 // Sender builder
 builder!(SenderBuilder {
-    socket: OwnedWriteHalf,
+    socket: CqlWriteHalf,
+    label: ConnectionLabel,
     payloads: Payloads,
-    appends_num: i16
+    appends_num: i16,
+    compression: CompressionType
 });
 
 /// SenderHandle to be passed to the supervisor (reporters)
@@ -26,6 +32,15 @@ pub struct SenderInbox {
     rx: mpsc::UnboundedReceiver<SenderEvent>,
 }
 
+#[cfg(test)]
+impl SenderHandle {
+    /// Create a handle without sender, along with the receiver of its stream ids, so the tests can stub the sender
+    pub(crate) fn detached() -> (Self, mpsc::UnboundedReceiver<SenderEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+}
+
 impl Deref for SenderHandle {
     type Target = mpsc::UnboundedSender<SenderEvent>;
 
@@ -46,11 +61,13 @@ type SenderEvent = i16;
 /// Sender state
 pub struct Sender {
     service: Service,
-    socket: OwnedWriteHalf,
+    socket: CqlWriteHalf,
+    label: ConnectionLabel,
     handle: Option<SenderHandle>,
     inbox: SenderInbox,
     payloads: Payloads,
     appends_num: i16,
+    compression: CompressionType,
 }
 
 impl ActorBuilder<ReportersHandles> for SenderBuilder {}
@@ -67,7 +84,9 @@ impl Builder for SenderBuilder {
             service: Service::new(),
             payloads: self.payloads.unwrap(),
             socket: self.socket.unwrap(),
+            label: self.label.unwrap(),
             appends_num: self.appends_num.unwrap(),
+            compression: self.compression.unwrap(),
             handle,
             inbox,
         }
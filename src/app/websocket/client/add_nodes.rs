@@ -21,15 +21,25 @@ pub async fn add_nodes(ws: &str, addresses: Vec<SocketAddr>, uniform_rf: u8) ->
         while let Some(msg) = ws_stream.next().await {
             let msg = msg.map_err(|_| anyhow!("Expected message from the WebSocketStream while building a ring"))?;
             let msg = msg.to_text()?;
-            if let Ok(event) = serde_json::from_str::<SocketMsg<Result<Topology, Topology>>>(msg) {
-                if let SocketMsg::Scylla(Ok(Topology::AddNode(_))) = event {
-                    info!("Added scylla node: {}", address);
-                    break;
-                } else {
-                    // TODO (handle parallel admins) it's possible other admin is managing the cluster in
-                    // parallel.
-                    ws_stream.close(None).await?;
-                    bail!("Unable to reach scylla node(s)");
+            if let Ok(event) = serde_json::from_str::<SocketMsg<TopologyResult>>(msg) {
+                match event {
+                    SocketMsg::Scylla(Ok(Topology::AddNode(_))) => {
+                        info!("Added scylla node: {}", address);
+                        break;
+                    }
+                    SocketMsg::Scylla(Err(TopologyError {
+                        topology: Topology::AddNode(_),
+                        error: Some(error),
+                    })) => {
+                        ws_stream.close(None).await?;
+                        bail!("Unable to reach scylla node {}: {}", address, error);
+                    }
+                    _ => {
+                        // TODO (handle parallel admins) it's possible other admin is managing the cluster in
+                        // parallel.
+                        ws_stream.close(None).await?;
+                        bail!("Unable to reach scylla node(s)");
+                    }
                 }
             } else {
                 // ensure it's running
@@ -46,14 +56,17 @@ pub async fn add_nodes(ws: &str, addresses: Vec<SocketAddr>, uniform_rf: u8) ->
     while let Some(msg) = ws_stream.next().await {
         let msg = msg.map_err(|_| anyhow!("Expected message from the WebSocketStream while building a ring"))?;
         let msg = msg.to_text()?;
-        if let Ok(event) = serde_json::from_str::<SocketMsg<Result<Topology, Topology>>>(msg) {
-            if let SocketMsg::<Result<Topology, Topology>>::Scylla(result) = event {
+        if let Ok(event) = serde_json::from_str::<SocketMsg<TopologyResult>>(msg) {
+            if let SocketMsg::<TopologyResult>::Scylla(result) = event {
                 match result {
                     Ok(Topology::BuildRing(_)) => {
                         info!("Succesfully Added Nodes and built cluster topology");
                         break_once_ready = true;
                     }
-                    Err(Topology::BuildRing(_)) => {
+                    Err(TopologyError {
+                        topology: Topology::BuildRing(_),
+                        ..
+                    }) => {
                         error!("Unable to build cluster topology, please try again");
                         break_once_ready = true;
                     }
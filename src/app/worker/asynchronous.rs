@@ -0,0 +1,78 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+/// A worker whose handlers are async, so they can await any follow-up (ie a backoff before retrying, or the
+/// re-preparation of a statement) without spawning detached tasks.
+///
+/// The handlers are driven by the reporter of the request, concurrently with its other requests, once the worker
+/// is wrapped into an `AsyncWorkerAdapter` (see `AsyncWorker::boxed`).
+///
+/// ## Example
+/// ```no_run
+/// use scylla_rs::app::worker::{AsyncWorker, ReporterHandle, WorkerError};
+/// use std::time::Duration;
+///
+/// struct MyWorker;
+///
+/// #[async_trait::async_trait]
+/// impl AsyncWorker for MyWorker {
+///     async fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()> {
+///         Ok(())
+///     }
+///     async fn handle_error(
+///         self: Box<Self>,
+///         error: WorkerError,
+///         reporter: Option<ReporterHandle>,
+///     ) -> anyhow::Result<()> {
+///         tokio::time::sleep(Duration::from_millis(100)).await;
+///         // resend the request..
+///         Ok(())
+///     }
+/// }
+///
+/// let worker = MyWorker.boxed();
+/// ```
+#[async_trait::async_trait]
+pub trait AsyncWorker: Send + 'static {
+    /// Handle the cql response of the request
+    async fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()>;
+    /// Handle the error of the request, along with the reporter which sent it, if any
+    async fn handle_error(self: Box<Self>, error: WorkerError, reporter: Option<ReporterHandle>) -> anyhow::Result<()>;
+    /// Box the worker into an `AsyncWorkerAdapter`, which can be sent with the requests
+    fn boxed(self) -> Box<AsyncWorkerAdapter<Self>>
+    where
+        Self: Sized,
+    {
+        Box::new(AsyncWorkerAdapter(Box::new(self)))
+    }
+}
+
+/// Adapts an `AsyncWorker` to the `Worker` trait, whose async handlers are driven by the reporter.
+///
+/// Note: the errors which are raised before the request reaches a reporter (ie `WorkerError::NoRing`) are
+/// handled synchronously, therefore their handler is spawned on the runtime.
+pub struct AsyncWorkerAdapter<W: ?Sized>(pub Box<W>);
+
+impl<W: AsyncWorker + ?Sized> Worker for AsyncWorkerAdapter<W> {
+    fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()> {
+        let future = self.handle_response_async(giveload);
+        tokio::spawn(async move { future.await.unwrap_or_else(|e| error!("{}", e)) });
+        Ok(())
+    }
+
+    fn handle_error(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> anyhow::Result<()> {
+        let future = self.handle_error_async(error, reporter);
+        tokio::spawn(async move { future.await.unwrap_or_else(|e| error!("{}", e)) });
+        Ok(())
+    }
+
+    fn handle_response_async(self: Box<Self>, giveload: Vec<u8>) -> WorkerFuture {
+        self.0.handle_response(giveload)
+    }
+
+    fn handle_error_async(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> WorkerFuture {
+        self.0.handle_error(error, reporter.clone())
+    }
+}
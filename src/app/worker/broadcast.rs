@@ -0,0 +1,100 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::app::ring::Ring;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+/// The per node results of a broadcast request, keyed by the node addresses
+pub type BroadcastResults = HashMap<IpAddr, Result<Decoder, WorkerError>>;
+
+/// The results gathered by the workers of a broadcast request
+struct Gather {
+    results: BroadcastResults,
+    /// The number of targeted nodes, which is known once the request is sent to all of them
+    expected: Option<usize>,
+    sender: Option<oneshot::Sender<BroadcastResults>>,
+}
+
+impl Gather {
+    /// Send the results once every targeted node responded (or failed)
+    fn complete(&mut self) {
+        if self.expected == Some(self.results.len()) {
+            if let Some(sender) = self.sender.take() {
+                sender.send(std::mem::take(&mut self.results)).ok();
+            }
+        }
+    }
+}
+
+/// A worker which sends the same request to every node of the ring, bypassing the token routing, and gathers
+/// the per node responses and errors, ie to query the node-local system tables or to audit the nodes.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::{
+///     app::worker::BroadcastWorker,
+///     cql::{Consistency, Query, Statements},
+/// };
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let Query(payload) = Query::new()
+///     .statement("SELECT release_version FROM system.local")
+///     .consistency(Consistency::One)
+///     .build()?;
+/// for (node, result) in BroadcastWorker::broadcast(payload)?.await? {
+///     println!("{}: {}", node, if result.is_ok() { "ok" } else { "failed" });
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct BroadcastWorker {
+    node: IpAddr,
+    gather: Arc<Mutex<Gather>>,
+}
+
+impl BroadcastWorker {
+    /// Send the request payload to a shard of every node in the ring. Returns the receiver of the per node
+    /// results, which resolves once every targeted node responded (or failed), or right away if the ring is
+    /// empty.
+    pub fn broadcast(payload: Vec<u8>) -> anyhow::Result<oneshot::Receiver<BroadcastResults>> {
+        let (sender, receiver) = oneshot::channel();
+        let gather = Arc::new(Mutex::new(Gather {
+            results: HashMap::new(),
+            expected: None,
+            sender: Some(sender),
+        }));
+        let count = Ring::send_nodes(|address| ReporterEvent::Request {
+            worker: Box::new(BroadcastWorker {
+                node: address.ip(),
+                gather: gather.clone(),
+            }),
+            payload: payload.clone(),
+        });
+        let mut gather = gather.lock().unwrap();
+        gather.expected = Some(count);
+        gather.complete();
+        Ok(receiver)
+    }
+    fn gather(self, result: Result<Decoder, WorkerError>) {
+        let mut gather = self.gather.lock().unwrap();
+        gather.results.insert(self.node, result);
+        gather.complete();
+    }
+}
+
+impl Worker for BroadcastWorker {
+    fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()> {
+        self.gather(Decoder::try_from(giveload).map_err(WorkerError::Other));
+        Ok(())
+    }
+    fn handle_error(self: Box<Self>, error: WorkerError, _reporter: &Option<ReporterHandle>) -> anyhow::Result<()> {
+        self.gather(Err(error));
+        Ok(())
+    }
+}
@@ -42,7 +42,11 @@ where
                 .delete_query::<V>(&self.key)
                 .consistency(Consistency::One)
                 .build()?;
-            tokio::spawn(async { req.send_global(self) });
+            diagnostics::emit(DiagnosticEvent::RetryIssued {
+                statement: self.keyspace.delete_statement::<K, V>().to_string(),
+                retries_left: self.retries,
+            });
+            req.send_global(self);
         }
         Ok(())
     }
@@ -74,9 +78,9 @@ where
     }
 }
 
-/// Handle an unprepared CQL error by sending a prepare
-/// request and resubmitting the original delete query as an
-/// unprepared statement
+/// Handle an unprepared CQL error by re-preparing the
+/// statement on every shard of the cluster and resubmitting
+/// the original delete query as an unprepared statement
 pub fn handle_unprepared_error<W, S, K, V>(
     worker: &Box<W>,
     keyspace: &S,
@@ -91,14 +95,8 @@ where
     V: 'static + Send,
 {
     let statement = keyspace.delete_statement::<K, V>();
-    info!("Attempting to prepare statement '{}', id: '{:?}'", statement, id);
-    let Prepare(payload) = Prepare::new().statement(&statement).build()?;
-    let prepare_worker = PrepareWorker::boxed(id, statement);
-    let prepare_request = ReporterEvent::Request {
-        worker: prepare_worker,
-        payload,
-    };
-    reporter.send(prepare_request).ok();
+    // the other shards are likely to report the statement as unprepared as well, ie after a node restart
+    PreparedCache::reprepare(id, &statement)?;
     let req = keyspace.delete_query(&key).consistency(Consistency::One).build()?;
     let payload = req.into_payload();
     let retry_request = ReporterEvent::Request {
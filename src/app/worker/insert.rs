@@ -44,7 +44,11 @@ where
                 .insert_query(&self.key, &self.value)
                 .consistency(Consistency::One)
                 .build()?;
-            tokio::spawn(async { req.send_global(self) });
+            diagnostics::emit(DiagnosticEvent::RetryIssued {
+                statement: req.statement().to_string(),
+                retries_left: self.retries,
+            });
+            req.send_global(self);
         }
         Ok(())
     }
@@ -76,9 +80,9 @@ where
     }
 }
 
-/// Handle an unprepared CQL error by sending a prepare
-/// request and resubmitting the original query as an
-/// unprepared statement
+/// Handle an unprepared CQL error by re-preparing the
+/// statement on every shard of the cluster and resubmitting
+/// the original query as an unprepared statement
 pub fn handle_unprepared_error<W, S, K, V>(
     worker: &Box<W>,
     keyspace: &S,
@@ -94,14 +98,8 @@ where
     V: 'static + Send,
 {
     let statement = keyspace.insert_statement::<K, V>();
-    info!("Attempting to prepare statement '{}', id: '{:?}'", statement, id);
-    let Prepare(payload) = Prepare::new().statement(&statement).build()?;
-    let prepare_worker = Box::new(PrepareWorker::new(id, statement));
-    let prepare_request = ReporterEvent::Request {
-        worker: prepare_worker,
-        payload,
-    };
-    reporter.send(prepare_request).ok();
+    // the other shards are likely to report the statement as unprepared as well, ie after a node restart
+    PreparedCache::reprepare(id, &statement)?;
     let req = keyspace
         .insert_query(&key, &value)
         .consistency(Consistency::One)
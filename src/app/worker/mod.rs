@@ -3,32 +3,79 @@
 
 pub use crate::app::stage::{ReporterEvent, ReporterHandle};
 use crate::{
-    app::access::*,
-    cql::{Consistency, CqlError, Decoder, Prepare},
+    app::{
+        access::*,
+        diagnostics::{self, ConnectionLabel, DiagnosticEvent},
+    },
+    cql::{Consistency, CqlError, Decoder, DefaultRetryPolicy, Prepare, ReadOnlyViolation, RetryDecision, RetryPolicy},
 };
 use anyhow::anyhow;
+pub use asynchronous::{AsyncWorker, AsyncWorkerAdapter};
+pub use broadcast::{BroadcastResults, BroadcastWorker};
 pub use delete::{handle_unprepared_error as handle_delete_unprepared_error, DeleteWorker};
+use futures::future::BoxFuture;
 pub use insert::{handle_unprepared_error as handle_insert_unprepared_error, InsertWorker};
+#[cfg(not(feature = "tracing"))]
 use log::*;
-pub use prepare::PrepareWorker;
+pub use oneshot::OneshotWorker;
+pub use prepare::{PrepareWorker, PreparedCache};
+pub use respond::{AsyncHandle, OneshotHandle, Respond};
 pub use select::{handle_unprepared_error as handle_select_unprepared_error, SelectWorker};
-use std::convert::{TryFrom, TryInto};
+use std::{
+    convert::{TryFrom, TryInto},
+    time::Duration,
+};
 use thiserror::Error;
+pub use timeout::RequestTimeout;
 use tokio::sync::mpsc::UnboundedSender;
+#[cfg(feature = "tracing")]
+pub use traced::{set_request_levels, TracedWorker};
+#[cfg(feature = "tracing")]
+use tracing::{error, info};
 pub use value::ValueWorker;
 
+mod asynchronous;
+mod broadcast;
 mod delete;
 mod insert;
+mod oneshot;
 mod prepare;
+mod respond;
 mod select;
+mod timeout;
+#[cfg(feature = "tracing")]
+mod traced;
 mod value;
 
+/// The future of a worker handler, which is driven by the reporter of the request
+pub type WorkerFuture = BoxFuture<'static, anyhow::Result<()>>;
+
 /// WorkerId trait type which will be implemented by worker in order to send their channel_tx.
 pub trait Worker: Send {
     /// Reporter will invoke this method to Send the cql response to worker
     fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()>;
     /// Reporter will invoke this method to Send the worker error to worker
     fn handle_error(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> anyhow::Result<()>;
+    /// Reporter will invoke this method to Send the cql response to worker, and drive the returned future
+    /// along with its other events. Defaults to `handle_response`, which completes before returning.
+    fn handle_response_async(self: Box<Self>, giveload: Vec<u8>) -> WorkerFuture {
+        let res = self.handle_response(giveload);
+        Box::pin(futures::future::ready(res))
+    }
+    /// Reporter will invoke this method to Send the worker error to worker, and drive the returned future
+    /// along with its other events. Defaults to `handle_error`, which completes before returning.
+    fn handle_error_async(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> WorkerFuture {
+        let res = self.handle_error(error, reporter);
+        Box::pin(futures::future::ready(res))
+    }
+    /// Reporter will invoke this method to get the client-side timeout of the request, if any.
+    /// Defaults to None, see `RequestTimeout`.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+    /// Reporter will invoke this method once it assigns a stream of its connection to the request, ie to record
+    /// the node and the shard of the request (see `TracedWorker`). Defaults to doing nothing.
+    fn dispatched(&self, _label: &ConnectionLabel) {}
 }
 
 #[derive(Error, Debug)]
@@ -49,6 +96,15 @@ pub enum WorkerError {
     /// There is no ring initialized.
     #[error("Worker NoRing")]
     NoRing,
+    /// The request didn't receive its response before its timeout.
+    #[error("Worker Timeout")]
+    Timeout,
+    /// The connection is draining its in-flight requests before shutting down, so it rejected the request.
+    #[error("Worker Draining")]
+    Draining,
+    /// The request has been rejected by the read-only mode.
+    #[error(transparent)]
+    ReadOnlyViolation(ReadOnlyViolation),
 }
 
 /// should be implemented on the handle of the worker
@@ -75,3 +131,11 @@ impl<W: Worker> DecodeResponse<Decoder> for W {
         Ok(decoder)
     }
 }
+
+/// Check if the failed read request should be retried, according to the `DefaultRetryPolicy` if it's a cql error
+pub(crate) fn is_retryable_read(error: &WorkerError) -> bool {
+    match error {
+        WorkerError::Cql(cql_error) => DefaultRetryPolicy.on_read_error(cql_error) == RetryDecision::Retry,
+        _ => true,
+    }
+}
@@ -0,0 +1,29 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use tokio::sync::oneshot;
+
+/// A worker which responds with the payload of a single response, or with its error, through a oneshot handle,
+/// which is used to await a response whose payload is decoded by the caller.
+pub struct OneshotWorker {
+    handle: OneshotHandle<Vec<u8>>,
+}
+
+impl OneshotWorker {
+    /// Create a new boxed oneshot worker along with the receiver of the response payload
+    pub fn boxed() -> (Box<Self>, oneshot::Receiver<Result<Vec<u8>, WorkerError>>) {
+        let (handle, rx) = OneshotHandle::new();
+        (Box::new(Self { handle }), rx)
+    }
+}
+
+impl Worker for OneshotWorker {
+    fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()> {
+        self.handle.respond(Ok(giveload))
+    }
+
+    fn handle_error(self: Box<Self>, error: WorkerError, _reporter: &Option<ReporterHandle>) -> anyhow::Result<()> {
+        self.handle.respond(Err(error))
+    }
+}
@@ -2,6 +2,120 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use crate::{app::ring::Registry, cql::Frame};
+use std::{
+    collections::HashMap,
+    sync::{OnceLock, RwLock},
+    time::{Duration, Instant},
+};
+
+/// The minimum interval between the cluster-wide re-preparations of the same statement, which prevents the
+/// in-flight requests, that bounce with unprepared errors, from flooding the cluster with prepare requests.
+const REPREPARE_INTERVAL: Duration = Duration::from_secs(1);
+
+static PREPARED_CACHE: OnceLock<RwLock<HashMap<[u8; 16], CachedStatement>>> = OnceLock::new();
+
+struct CachedStatement {
+    statement: String,
+    reprepared_at: Option<Instant>,
+}
+
+/// The cluster-wide cache of the prepared statements, keyed by their MD5 id.
+///
+/// The cache is populated by the `PrepareWorker` once any shard prepared the statement, then it's used to
+/// re-prepare the statement on every shard of the cluster when a shard reports it as unprepared (ie after a
+/// node restart), and to prepare the cached statements on the shards of the nodes which join the cluster.
+pub struct PreparedCache;
+
+impl PreparedCache {
+    fn cache() -> &'static RwLock<HashMap<[u8; 16], CachedStatement>> {
+        PREPARED_CACHE.get_or_init(Default::default)
+    }
+    /// Cache the prepared statement
+    pub fn insert<T: ToString>(id: [u8; 16], statement: T) {
+        let mut cache = Self::cache().write().unwrap();
+        cache.entry(id).or_insert_with(|| CachedStatement {
+            statement: statement.to_string(),
+            reprepared_at: None,
+        });
+    }
+    /// Get the cached statement of the prepared id
+    pub fn get(id: &[u8; 16]) -> Option<String> {
+        Self::cache()
+            .read()
+            .unwrap()
+            .get(id)
+            .map(|cached| cached.statement.clone())
+    }
+    /// Check if the prepared id is cached
+    pub fn contains(id: &[u8; 16]) -> bool {
+        Self::cache().read().unwrap().contains_key(id)
+    }
+    /// Remove the prepared id from the cache, returns its statement
+    pub fn remove(id: &[u8; 16]) -> Option<String> {
+        Self::cache().write().unwrap().remove(id).map(|cached| cached.statement)
+    }
+    /// Get the number of cached statements
+    pub fn len() -> usize {
+        Self::cache().read().unwrap().len()
+    }
+    /// Get the cached statements along with their prepared ids
+    pub fn statements() -> Vec<([u8; 16], String)> {
+        Self::cache()
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, cached)| (*id, cached.statement.clone()))
+            .collect()
+    }
+    /// Re-prepare the statement on every shard of every node in the ring, unless it has already been
+    /// re-prepared within the last second. Returns the number of targeted shards, which is zero if skipped.
+    pub fn reprepare(id: [u8; 16], statement: &str) -> anyhow::Result<usize> {
+        {
+            let mut cache = Self::cache().write().unwrap();
+            let cached = cache.entry(id).or_insert_with(|| CachedStatement {
+                statement: statement.to_string(),
+                reprepared_at: None,
+            });
+            if matches!(cached.reprepared_at, Some(at) if at.elapsed() < REPREPARE_INTERVAL) {
+                return Ok(0);
+            }
+            cached.reprepared_at = Some(Instant::now());
+        }
+        info!("Re-preparing statement '{}', id: '{:?}' on every shard", statement, id);
+        reprepare_all_shards(statement)
+    }
+    /// Prepare all the cached statements on the shards of the registry, ie the ones of a joining node.
+    /// Returns the number of sent prepare requests.
+    pub fn prepare_registry(registry: &Registry) -> usize {
+        let mut count = 0;
+        for (id, statement) in Self::statements() {
+            let payload = match Prepare::new().statement(&statement).build() {
+                Ok(Prepare(payload)) => payload,
+                Err(e) => {
+                    error!("Failed to build the prepare request of '{}': {}", statement, e);
+                    continue;
+                }
+            };
+            for reporters_handles in registry.values() {
+                if let Some(reporter) = reporters_handles
+                    .keys()
+                    .min()
+                    .map(|reporter_id| &reporters_handles[reporter_id])
+                {
+                    let request = ReporterEvent::Request {
+                        worker: PrepareWorker::boxed(id, &statement),
+                        payload: payload.clone(),
+                    };
+                    if reporter.send(request).is_ok() {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        count
+    }
+}
 
 /// A statement prepare worker
 pub struct PrepareWorker {
@@ -9,6 +123,10 @@ pub struct PrepareWorker {
     pub id: [u8; 16],
     /// The statement to prepare
     pub statement: String,
+    /// Whether the statement is re-prepared after an unprepared error, which emits `DiagnosticEvent::Reprepared`
+    pub reprepare: bool,
+    /// The handle which responds with the prepared id returned by the server, if any
+    pub handle: Option<OneshotHandle<[u8; 16]>>,
 }
 impl PrepareWorker {
     /// Create a new prepare worker
@@ -16,12 +134,26 @@ impl PrepareWorker {
         Self {
             id,
             statement: statement.to_string(),
+            reprepare: false,
+            handle: None,
         }
     }
     /// Create a new boxed prepare worker
     pub fn boxed<T: ToString>(id: [u8; 16], statement: T) -> Box<Self> {
         Box::new(Self::new(id, statement))
     }
+    /// Create a new boxed prepare worker which re-prepares the statement after an unprepared error
+    pub fn reprepare<T: ToString>(id: [u8; 16], statement: T) -> Box<Self> {
+        Box::new(Self {
+            reprepare: true,
+            ..Self::new(id, statement)
+        })
+    }
+    /// Respond with the prepared id returned by the server, ie to await the preparation of the statement
+    pub fn with_handle(mut self: Box<Self>, handle: OneshotHandle<[u8; 16]>) -> Box<Self> {
+        self.handle.replace(handle);
+        self
+    }
     /// Create a prepare worker for an insert statement given a keyspace with the
     /// appropriate trait definition
     pub fn insert<S, K, V>(keyspace: &S) -> Self
@@ -31,6 +163,8 @@ impl PrepareWorker {
         Self {
             id: keyspace.id(),
             statement: keyspace.statement().to_string(),
+            reprepare: false,
+            handle: None,
         }
     }
     /// Create a prepare worker for a select statement given a keyspace with the
@@ -42,6 +176,21 @@ impl PrepareWorker {
         Self {
             id: keyspace.id(),
             statement: keyspace.statement().to_string(),
+            reprepare: false,
+            handle: None,
+        }
+    }
+    /// Create a prepare worker for an indexed select statement given a keyspace with the
+    /// appropriate trait definition
+    pub fn index_select<S, K, V>(keyspace: &S) -> Self
+    where
+        S: IndexSelect<K, V>,
+    {
+        Self {
+            id: keyspace.id(),
+            statement: keyspace.statement().to_string(),
+            reprepare: false,
+            handle: None,
         }
     }
     /// Create a prepare worker for an update statement given a keyspace with the
@@ -53,6 +202,8 @@ impl PrepareWorker {
         Self {
             id: keyspace.id(),
             statement: keyspace.statement().to_string(),
+            reprepare: false,
+            handle: None,
         }
     }
     /// Create a prepare worker for a delete statement given a keyspace with the
@@ -64,16 +215,36 @@ impl PrepareWorker {
         Self {
             id: keyspace.id(),
             statement: keyspace.statement().to_string(),
+            reprepare: false,
+            handle: None,
         }
     }
 }
 impl Worker for PrepareWorker {
-    fn handle_response(self: Box<Self>, _giveload: Vec<u8>) -> anyhow::Result<()> {
-        info!("Successfully prepared statement: '{}'", self.statement);
-        Ok(())
+    fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()> {
+        // the server doesn't necessarily derive the id from the statement alone, ie along with the keyspace
+        let res = Decoder::try_from(giveload).and_then(|decoder| decoder.get_prepared_id());
+        let id = *res.as_ref().unwrap_or(&self.id);
+        PreparedCache::insert(id, &self.statement);
+        if self.reprepare {
+            diagnostics::emit(DiagnosticEvent::Reprepared {
+                id,
+                statement: self.statement.clone(),
+            });
+        }
+        match self.handle {
+            Some(handle) => handle.respond(res.map_err(WorkerError::Other)),
+            None => Ok(()),
+        }
     }
     fn handle_error(self: Box<Self>, error: WorkerError, _reporter: &Option<ReporterHandle>) -> anyhow::Result<()> {
-        error!("Failed to prepare statement: {}, error: {}", self.statement, error);
-        Ok(())
+        diagnostics::emit(DiagnosticEvent::PrepareFailed {
+            statement: self.statement.clone(),
+            error: error.to_string(),
+        });
+        match self.handle {
+            Some(handle) => handle.respond(Err(error)),
+            None => Ok(()),
+        }
     }
 }
@@ -0,0 +1,103 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use futures::future::BoxFuture;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::oneshot;
+
+/// Should be implemented on the response handles which can be used by the `ValueWorker` and `SelectWorker`
+/// to respond with the result of a request.
+pub trait Respond<T>: Send + Clone {
+    /// Respond with the result of the request
+    fn respond(&self, result: Result<T, WorkerError>) -> anyhow::Result<()>;
+}
+
+impl<T: Send> Respond<T> for UnboundedSender<Result<T, WorkerError>> {
+    fn respond(&self, result: Result<T, WorkerError>) -> anyhow::Result<()> {
+        self.send(result).map_err(|e| anyhow!(e.to_string()))
+    }
+}
+
+impl<T: Send> Respond<T> for futures::channel::mpsc::UnboundedSender<Result<T, WorkerError>> {
+    fn respond(&self, result: Result<T, WorkerError>) -> anyhow::Result<()> {
+        self.unbounded_send(result).map_err(|e| anyhow!(e.to_string()))
+    }
+}
+
+/// A cloneable response handle over a single use oneshot sender, which allows the callers to
+/// await a single response without constructing channels and loops.
+///
+/// Note: only the first response will be delivered, as the sender is consumed by it.
+pub struct OneshotHandle<T> {
+    tx: Arc<Mutex<Option<oneshot::Sender<Result<T, WorkerError>>>>>,
+}
+
+impl<T> OneshotHandle<T> {
+    /// Create a new oneshot handle along with the receiver of the response
+    pub fn new() -> (Self, oneshot::Receiver<Result<T, WorkerError>>) {
+        let (tx, rx) = oneshot::channel();
+        (tx.into(), rx)
+    }
+}
+
+impl<T> Clone for OneshotHandle<T> {
+    fn clone(&self) -> Self {
+        Self { tx: self.tx.clone() }
+    }
+}
+
+impl<T> From<oneshot::Sender<Result<T, WorkerError>>> for OneshotHandle<T> {
+    fn from(tx: oneshot::Sender<Result<T, WorkerError>>) -> Self {
+        Self {
+            tx: Arc::new(Mutex::new(Some(tx))),
+        }
+    }
+}
+
+impl<T: Send> Respond<T> for OneshotHandle<T> {
+    fn respond(&self, result: Result<T, WorkerError>) -> anyhow::Result<()> {
+        self.tx
+            .lock()
+            .map_err(|_| anyhow!("Poisoned oneshot handle"))?
+            .take()
+            .ok_or_else(|| anyhow!("Oneshot handle already responded"))?
+            .send(result)
+            .map_err(|_| anyhow!("Oneshot receiver dropped"))
+    }
+}
+
+/// A response handle which invokes an async closure with the result of the request,
+/// the returned future is spawned on the tokio runtime.
+pub struct AsyncHandle<T> {
+    f: Arc<dyn Fn(Result<T, WorkerError>) -> BoxFuture<'static, ()> + Send + Sync>,
+}
+
+impl<T: 'static> AsyncHandle<T> {
+    /// Create a new async handle from the provided async closure
+    pub fn new<F, Fut>(f: F) -> Self
+    where
+        F: 'static + Fn(Result<T, WorkerError>) -> Fut + Send + Sync,
+        Fut: 'static + Future<Output = ()> + Send,
+    {
+        Self {
+            f: Arc::new(move |result| Box::pin(f(result))),
+        }
+    }
+}
+
+impl<T> Clone for AsyncHandle<T> {
+    fn clone(&self) -> Self {
+        Self { f: self.f.clone() }
+    }
+}
+
+impl<T: Send> Respond<T> for AsyncHandle<T> {
+    fn respond(&self, result: Result<T, WorkerError>) -> anyhow::Result<()> {
+        tokio::spawn((self.f)(result));
+        Ok(())
+    }
+}
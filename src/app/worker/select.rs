@@ -88,7 +88,10 @@ where
                     reporter,
                 )
                 .or_else(|e| {
-                    error!("Error trying to prepare query: {}", e);
+                    diagnostics::emit(DiagnosticEvent::PrepareFailed {
+                        statement: self.keyspace.select_statement::<K, V>().to_string(),
+                        error: e.to_string(),
+                    });
                     H::handle_error(self, error)
                 })
             } else {
@@ -100,36 +103,29 @@ where
     }
 }
 
-impl<S, K, V> HandleResponse<SelectWorker<UnboundedSender<Result<Decoder, WorkerError>>, S, K, V>>
-    for UnboundedSender<Result<Decoder, WorkerError>>
+impl<H, S, K, V> HandleResponse<SelectWorker<H, S, K, V>> for H
 where
+    H: 'static + Respond<Decoder>,
     S: 'static + Send + Select<K, V>,
     K: 'static + Send + Clone,
     V: 'static + Send + Clone,
 {
     type Response = Decoder;
-    fn handle_response(
-        worker: Box<SelectWorker<UnboundedSender<Result<Decoder, WorkerError>>, S, K, V>>,
-        response: Self::Response,
-    ) -> anyhow::Result<()> {
-        worker.handle.send(Ok(response)).map_err(|e| anyhow!(e.to_string()))
+    fn handle_response(worker: Box<SelectWorker<H, S, K, V>>, response: Self::Response) -> anyhow::Result<()> {
+        worker.handle.respond(Ok(response))
     }
 }
 
-impl<S, K, V> HandleError<SelectWorker<UnboundedSender<Result<Decoder, WorkerError>>, S, K, V>>
-    for UnboundedSender<Result<Decoder, WorkerError>>
+impl<H, S, K, V> HandleError<SelectWorker<H, S, K, V>> for H
 where
+    H: 'static + Respond<Decoder>,
     S: 'static + Send + Select<K, V>,
     K: 'static + Send + Clone,
     V: 'static + Send + Clone,
 {
-    fn handle_error(
-        mut worker: Box<SelectWorker<UnboundedSender<Result<Decoder, WorkerError>>, S, K, V>>,
-        worker_error: WorkerError,
-    ) -> anyhow::Result<()> {
-        if worker.retries > 0 {
+    fn handle_error(mut worker: Box<SelectWorker<H, S, K, V>>, worker_error: WorkerError) -> anyhow::Result<()> {
+        if worker.retries > 0 && is_retryable_read(&worker_error) {
             worker.retries -= 1;
-            // currently we assume all cql/worker errors are retryable, but we might change this in future
             let req = worker
                 .keyspace
                 .select_query::<V>(&worker.key)
@@ -140,20 +136,21 @@ where
                 req.paging_state(&worker.paging_state)
             }
             .build()?;
-            tokio::spawn(async { req.send_global(worker) });
+            diagnostics::emit(DiagnosticEvent::RetryIssued {
+                statement: req.statement().to_string(),
+                retries_left: worker.retries,
+            });
+            req.send_global(worker);
             Ok(())
         } else {
-            worker
-                .handle
-                .send(Err(worker_error))
-                .map_err(|e| anyhow!(e.to_string()))
+            worker.handle.respond(Err(worker_error))
         }
     }
 }
 
-/// Handle an unprepared CQL error by sending a prepare
-/// request and resubmitting the original query as an
-/// unprepared statement
+/// Handle an unprepared CQL error by re-preparing the
+/// statement on every shard of the cluster and resubmitting
+/// the original query as an unprepared statement
 pub fn handle_unprepared_error<W, S, K, V>(
     worker: &Box<W>,
     keyspace: &S,
@@ -170,14 +167,8 @@ where
     V: 'static + Send,
 {
     let statement = keyspace.select_statement::<K, V>();
-    info!("Attempting to prepare statement '{}', id: '{:?}'", statement, id);
-    let Prepare(payload) = Prepare::new().statement(&statement).build()?;
-    let prepare_worker = Box::new(PrepareWorker::new(id, statement));
-    let prepare_request = ReporterEvent::Request {
-        worker: prepare_worker,
-        payload,
-    };
-    reporter.send(prepare_request).ok();
+    // the other shards are likely to report the statement as unprepared as well, ie after a node restart
+    PreparedCache::reprepare(id, &statement)?;
     let req = keyspace.select_query::<V>(&key).consistency(Consistency::One);
     let req = if let Some(page_size) = page_size {
         req.page_size(page_size).paging_state(&paging_state)
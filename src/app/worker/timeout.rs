@@ -0,0 +1,65 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use std::time::Duration;
+
+/// Bounds the time a request waits for its response, so the caller isn't awaiting forever on a connection which
+/// stopped answering.
+///
+/// Once the timeout elapses, the reporter resolves the wrapped worker with `WorkerError::Timeout` and releases its
+/// stream id. The stream id is reused once the late response is received, or the session is closed, so the late
+/// response can't be mistaken for the one of another request.
+///
+/// Note: the retries issued by the wrapped worker (ie by sending itself again) aren't bounded by the timeout.
+///
+/// The requests wrap their worker when their timeout is set, ie `request.timeout(Some(duration)).send_local(worker)`.
+///
+/// ## Example
+/// ```
+/// use scylla_rs::app::worker::{RequestTimeout, Worker};
+/// use std::time::Duration;
+///
+/// fn bounded(worker: Box<dyn Worker>) -> Box<dyn Worker> {
+///     RequestTimeout::wrap(worker, Some(Duration::from_secs(2)))
+/// }
+/// ```
+pub struct RequestTimeout {
+    worker: Box<dyn Worker>,
+    timeout: Duration,
+}
+
+impl RequestTimeout {
+    /// Wrap the worker, so its request times out after the provided duration
+    pub fn new(worker: Box<dyn Worker>, timeout: Duration) -> Self {
+        Self { worker, timeout }
+    }
+    /// Wrap the worker if a timeout is provided, or return it as is otherwise
+    pub fn wrap(worker: Box<dyn Worker>, timeout: Option<Duration>) -> Box<dyn Worker> {
+        match timeout {
+            Some(timeout) => Box::new(Self::new(worker, timeout)),
+            None => worker,
+        }
+    }
+}
+
+impl Worker for RequestTimeout {
+    fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()> {
+        self.worker.handle_response(giveload)
+    }
+    fn handle_error(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> anyhow::Result<()> {
+        self.worker.handle_error(error, reporter)
+    }
+    fn handle_response_async(self: Box<Self>, giveload: Vec<u8>) -> WorkerFuture {
+        self.worker.handle_response_async(giveload)
+    }
+    fn handle_error_async(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> WorkerFuture {
+        self.worker.handle_error_async(error, reporter)
+    }
+    fn timeout(&self) -> Option<Duration> {
+        Some(self.timeout)
+    }
+    fn dispatched(&self, label: &ConnectionLabel) {
+        self.worker.dispatched(label)
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::{
+    app::diagnostics::ConnectionLabel,
+    cql::{opcode, Md5, StatementIdAlgorithm},
+};
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Instant,
+};
+use tracing::{field, Level, Span};
+
+/// The levels of the spans and the events, by their index
+const LEVELS: [Level; 5] = [Level::TRACE, Level::DEBUG, Level::INFO, Level::WARN, Level::ERROR];
+
+/// The level of the request spans and their success events, DEBUG by default
+static SPAN_LEVEL: AtomicU8 = AtomicU8::new(1);
+/// The level of the failure events of the requests, WARN by default
+static FAILURE_LEVEL: AtomicU8 = AtomicU8::new(3);
+
+thread_local! {
+    /// The number of retries of the request whose failure is being handled, so the request which retries it
+    /// inherits them
+    static RETRIES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Dispatch to the tracing macro with the constant level which matches the dynamic one
+macro_rules! with_level {
+    ($macro:ident, $level:expr, $($args:tt)+) => {
+        match $level {
+            Level::TRACE => tracing::$macro!(Level::TRACE, $($args)+),
+            Level::DEBUG => tracing::$macro!(Level::DEBUG, $($args)+),
+            Level::INFO => tracing::$macro!(Level::INFO, $($args)+),
+            Level::WARN => tracing::$macro!(Level::WARN, $($args)+),
+            _ => tracing::$macro!(Level::ERROR, $($args)+),
+        }
+    };
+}
+
+/// Set the level of the request spans along with their success events, and the level of their failure events
+pub fn set_request_levels(span: Level, failure: Level) {
+    SPAN_LEVEL.store(level_index(span), Ordering::Relaxed);
+    FAILURE_LEVEL.store(level_index(failure), Ordering::Relaxed);
+}
+
+fn level_index(level: Level) -> u8 {
+    LEVELS.iter().position(|l| *l == level).unwrap_or_default() as u8
+}
+
+fn level(index: &AtomicU8) -> Level {
+    LEVELS[index.load(Ordering::Relaxed) as usize]
+}
+
+/// Traces the request of the wrapped worker by its span, which carries the keyspace, the statement digest, the
+/// token, the node and the shard, the retries and the latency of the request.
+///
+/// The requests which are sent while the failure of the request is handled, ie its retries, are traced by the child
+/// spans of its span, along with the incremented number of retries.
+///
+/// Note: the requests wrap their worker when the `tracing` feature is enabled.
+pub struct TracedWorker {
+    worker: Box<dyn Worker>,
+    span: Span,
+    retries: usize,
+    started: Instant,
+}
+
+impl TracedWorker {
+    /// Wrap the worker of the request, which is sent with the payload to the replicas of the token
+    pub fn boxed(worker: Box<dyn Worker>, token: i64, payload: &[u8], keyspace: Option<&str>) -> Box<Self> {
+        let retries = RETRIES.with(Cell::get);
+        let span = with_level!(
+            span,
+            level(&SPAN_LEVEL),
+            "request",
+            keyspace = field::Empty,
+            statement = field::Empty,
+            token,
+            retries = retries as u64,
+            node = field::Empty,
+            shard = field::Empty,
+            latency_us = field::Empty
+        );
+        if let Some(keyspace) = keyspace {
+            span.record("keyspace", keyspace);
+        }
+        if let Some(digest) = statement_digest(payload) {
+            span.record("statement", digest.as_str());
+        }
+        Box::new(Self {
+            worker,
+            span,
+            retries,
+            started: Instant::now(),
+        })
+    }
+    fn record_latency(&self) {
+        self.span
+            .record("latency_us", self.started.elapsed().as_micros() as u64);
+    }
+    fn succeeded(&self) {
+        self.record_latency();
+        self.span
+            .in_scope(|| with_level!(event, level(&SPAN_LEVEL), "request succeeded"));
+    }
+    fn failed(&self, error: &WorkerError) {
+        self.record_latency();
+        self.span
+            .in_scope(|| with_level!(event, level(&FAILURE_LEVEL), %error, "request failed"));
+    }
+}
+
+/// Handle the failure of the request within its span, so its retries are traced by the child spans along with the
+/// incremented number of retries
+fn in_retry_scope<T>(span: &Span, retries: usize, handle: impl FnOnce() -> T) -> T {
+    let _entered = span.enter();
+    let previous = RETRIES.with(|current| current.replace(retries + 1));
+    let output = handle();
+    RETRIES.with(|current| current.set(previous));
+    output
+}
+
+impl Worker for TracedWorker {
+    fn handle_response(self: Box<Self>, giveload: Vec<u8>) -> anyhow::Result<()> {
+        self.succeeded();
+        let Self { worker, span, .. } = *self;
+        span.in_scope(|| worker.handle_response(giveload))
+    }
+    fn handle_error(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> anyhow::Result<()> {
+        self.failed(&error);
+        let Self {
+            worker, span, retries, ..
+        } = *self;
+        in_retry_scope(&span, retries, || worker.handle_error(error, reporter))
+    }
+    fn handle_response_async(self: Box<Self>, giveload: Vec<u8>) -> WorkerFuture {
+        self.succeeded();
+        let Self { worker, span, .. } = *self;
+        let mut future = span.in_scope(|| worker.handle_response_async(giveload));
+        Box::pin(futures::future::poll_fn(move |cx| {
+            span.in_scope(|| future.as_mut().poll(cx))
+        }))
+    }
+    fn handle_error_async(self: Box<Self>, error: WorkerError, reporter: &Option<ReporterHandle>) -> WorkerFuture {
+        self.failed(&error);
+        let Self {
+            worker, span, retries, ..
+        } = *self;
+        let mut future = in_retry_scope(&span, retries, || worker.handle_error_async(error, reporter));
+        Box::pin(futures::future::poll_fn(move |cx| {
+            in_retry_scope(&span, retries, || future.as_mut().poll(cx))
+        }))
+    }
+    fn timeout(&self) -> Option<Duration> {
+        self.worker.timeout()
+    }
+    fn dispatched(&self, label: &ConnectionLabel) {
+        self.span.record("node", field::display(label.node));
+        self.span.record("shard", label.shard);
+        self.worker.dispatched(label);
+    }
+}
+
+/// Get the digest of the statement of the payload, ie the hex MD5 hash of the statement of a query frame, or the
+/// hex id of the prepared statement of an execute frame
+fn statement_digest(payload: &[u8]) -> Option<String> {
+    let id: [u8; 16] = match *payload.get(4)? {
+        opcode::QUERY => {
+            let len = u32::from_be_bytes(payload.get(9..13)?.try_into().ok()?) as usize;
+            Md5::statement_id(std::str::from_utf8(payload.get(13..13 + len)?).ok()?)
+        }
+        opcode::EXECUTE => {
+            let len = u16::from_be_bytes(payload.get(9..11)?.try_into().ok()?) as usize;
+            payload.get(11..11 + len)?.try_into().ok()?
+        }
+        _ => return None,
+    };
+    Some(id.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
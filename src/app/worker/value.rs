@@ -100,7 +100,10 @@ where
                     reporter,
                 )
                 .or_else(|e| {
-                    error!("Error trying to prepare query: {}", e);
+                    diagnostics::emit(DiagnosticEvent::PrepareFailed {
+                        statement: self.keyspace.select_statement::<K, V>().to_string(),
+                        error: e.to_string(),
+                    });
                     H::handle_error(self, error)
                 })
             } else {
@@ -112,36 +115,29 @@ where
     }
 }
 
-impl<S, K, V> HandleResponse<ValueWorker<UnboundedSender<Result<Option<V>, WorkerError>>, S, K, V>>
-    for UnboundedSender<Result<Option<V>, WorkerError>>
+impl<H, S, K, V> HandleResponse<ValueWorker<H, S, K, V>> for H
 where
+    H: 'static + Respond<Option<V>>,
     S: 'static + Send + Select<K, V> + Clone,
     K: 'static + Send + Clone,
     V: 'static + Send + Clone,
 {
     type Response = Option<V>;
-    fn handle_response(
-        worker: Box<ValueWorker<UnboundedSender<Result<Option<V>, WorkerError>>, S, K, V>>,
-        response: Self::Response,
-    ) -> anyhow::Result<()> {
-        worker.handle.send(Ok(response)).map_err(|e| anyhow!(e.to_string()))
+    fn handle_response(worker: Box<ValueWorker<H, S, K, V>>, response: Self::Response) -> anyhow::Result<()> {
+        worker.handle.respond(Ok(response))
     }
 }
 
-impl<S, K, V> HandleError<ValueWorker<UnboundedSender<Result<Option<V>, WorkerError>>, S, K, V>>
-    for UnboundedSender<Result<Option<V>, WorkerError>>
+impl<H, S, K, V> HandleError<ValueWorker<H, S, K, V>> for H
 where
+    H: 'static + Respond<Option<V>>,
     S: 'static + Send + Select<K, V> + Clone,
     K: 'static + Send + Clone,
     V: 'static + Send + Clone,
 {
-    fn handle_error(
-        mut worker: Box<ValueWorker<UnboundedSender<Result<Option<V>, WorkerError>>, S, K, V>>,
-        worker_error: WorkerError,
-    ) -> anyhow::Result<()> {
-        if worker.retries > 0 {
+    fn handle_error(mut worker: Box<ValueWorker<H, S, K, V>>, worker_error: WorkerError) -> anyhow::Result<()> {
+        if worker.retries > 0 && is_retryable_read(&worker_error) {
             worker.retries -= 1;
-            // currently we assume all cql/worker errors are retryable, but we might change this in future
             let req = worker
                 .keyspace
                 .select_query::<V>(&worker.key)
@@ -152,13 +148,14 @@ where
                 req.paging_state(&worker.paging_state)
             }
             .build()?;
-            tokio::spawn(async { req.send_global(worker) });
+            diagnostics::emit(DiagnosticEvent::RetryIssued {
+                statement: req.statement().to_string(),
+                retries_left: worker.retries,
+            });
+            req.send_global(worker);
             Ok(())
         } else {
-            worker
-                .handle
-                .send(Err(worker_error))
-                .map_err(|e| anyhow!(e.to_string()))
+            worker.handle.respond(Err(worker_error))
         }
     }
 }
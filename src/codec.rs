@@ -0,0 +1,128 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! The codec of the CQL binary protocol v4, which builds the request frames and parses the response frames
+//! independently of the app layer, so it's available when the crate is built without the `app` feature, ie to
+//! embed the codec into another transport.
+//!
+//! The facade curates the stable types of the `cql` module:
+//! - the `FrameBuilder` entry point of the QUERY, EXECUTE, BATCH, PREPARE, OPTIONS and REGISTER frames, along with
+//!   the `Statements` and `Values` traits of their builders;
+//! - the `Decoder` of the response frames, along with the `Frame` accessors, the rows decoding traits and the
+//!   `DynamicRow`s whose columns are looked up by their names;
+//! - the column types which have no native rust counterpart, ie the `EpochMillis` timestamps or the `Decimal`s;
+//! - the `CqlError` of the ERROR frames, the `Consistency` levels and the compression types.
+//!
+//! ## Examples
+//! ```
+//! use scylla_rs::codec::{Consistency, Decoder, Frame, FrameBuilder, Iter, QueryFrame, Rows, Statements, Values};
+//! use std::convert::TryFrom;
+//!
+//! // build a QUERY frame
+//! let query = FrameBuilder::query()
+//!     .statement("SELECT id FROM shop.orders WHERE customer = ?")
+//!     .consistency(Consistency::One)
+//!     .value("alice")
+//!     .build()?;
+//! assert_eq!(QueryFrame::decode(&query.0)?.consistency(), Consistency::One);
+//! // parse its RESULT frame, which holds the rows without their metadata
+//! let result = vec![
+//!     132, 0, 0, 0, 8, 0, 0, 0, 24, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 7,
+//! ];
+//! let decoder = Decoder::try_from(result)?;
+//! assert!(decoder.is_rows()?);
+//! let ids: Vec<(i32,)> = Iter::new(decoder)?.collect();
+//! assert_eq!(ids, vec![(7,)]);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub use crate::cql::{
+    compression::{Compression, CompressionType, MyCompression},
+    Batch, BatchBuilder, BatchType, BatchTypeUnset, BatchTypes, ColumnDecoder, ColumnEncoder, ColumnSpec, ColumnValue,
+    Consistency, CqlDuration, CqlError, CqlType, CqlValue, DayNanos, Decimal, Decoder, DynamicRow, EpochDays,
+    EpochMillis, ErrorCodes, Event, EventType, Frame, Iter, LwtDecoder, LwtResult, Md5, Null, Options, OptionsBuild,
+    OptionsBuilder, Prepare, PrepareBuilder, PrepareStatement, ProtocolVersion, Query, QueryBuilder, QueryFrame,
+    QueryStatement, Register, RegisterBuilder, RegisterEvents, Row, Rows, RowsDecoder, RowsStream,
+    StatementIdAlgorithm, Statements, Unset, Values, Varint, VoidDecoder,
+};
+
+/// The entry point of the request frames builders
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::codec::{Consistency, FrameBuilder, Md5, StatementIdAlgorithm, Statements, Values};
+///
+/// let statement = "INSERT INTO shop.orders (id) VALUES (?)";
+/// let prepare = FrameBuilder::prepare().statement(statement).build()?;
+/// let execute = FrameBuilder::query()
+///     .id(&Md5::statement_id(statement))
+///     .consistency(Consistency::Quorum)
+///     .value(&1)
+///     .build()?;
+/// let batch = FrameBuilder::batch()
+///     .logged()
+///     .statement("INSERT INTO shop.orders (id) VALUES (2)")
+///     .consistency(Consistency::Quorum)
+///     .build()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct FrameBuilder;
+
+impl FrameBuilder {
+    /// Build a QUERY frame, or an EXECUTE frame if it's built with the id of a prepared statement
+    pub fn query() -> QueryBuilder<QueryStatement> {
+        Query::new()
+    }
+    /// Build a BATCH frame
+    pub fn batch() -> BatchBuilder<BatchTypeUnset, BatchType> {
+        Batch::new()
+    }
+    /// Build a PREPARE frame
+    pub fn prepare() -> PrepareBuilder<PrepareStatement> {
+        Prepare::new()
+    }
+    /// Build an OPTIONS frame
+    pub fn options() -> OptionsBuilder<OptionsBuild> {
+        Options::new()
+    }
+    /// Build a REGISTER frame
+    pub fn register() -> RegisterBuilder<RegisterEvents> {
+        Register::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn codec_round_trips() {
+        let query = FrameBuilder::query()
+            .statement("UPDATE shop.orders SET total = ? WHERE id = ?")
+            .consistency(Consistency::LocalQuorum)
+            .value(&9.5)
+            .value(&1)
+            .timestamp(42)
+            .build()
+            .unwrap();
+        let frame = QueryFrame::decode(&query.0).unwrap();
+        assert_eq!(frame.statement(), Some("UPDATE shop.orders SET total = ? WHERE id = ?"));
+        assert_eq!(
+            (frame.consistency(), frame.timestamp()),
+            (Consistency::LocalQuorum, Some(42))
+        );
+        assert_eq!(frame.encode().unwrap(), query.0);
+        // a VOID result
+        let decoder = Decoder::try_from(vec![132, 0, 0, 0, 8, 0, 0, 0, 4, 0, 0, 0, 1]).unwrap();
+        assert!(decoder.is_void().unwrap());
+        // an ERROR result
+        let mut error = vec![132, 0, 0, 0, 0, 0, 0, 0, 13, 0, 0, 0x22, 0];
+        error.extend(&[0, 7]);
+        error.extend(b"invalid");
+        let decoder = Decoder::try_from(error).unwrap();
+        assert!(decoder.is_error().unwrap());
+        let error = decoder.get_error().unwrap();
+        assert_eq!((error.code, error.message.as_str()), (ErrorCodes::Invalid, "invalid"));
+    }
+}
@@ -3,8 +3,13 @@
 
 //! This crates implements the uncompressed, LZ4, and snappy compression methods for Cassandra.
 
-use super::frame::header::COMPRESSION;
-use std::convert::TryInto;
+use super::frame::{header::COMPRESSION, ProtocolVersion};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    convert::TryInto,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
 
 /// This compression thread provides the buffer compression/decompression methods for uncompressed/Lz4/snappy.
 pub trait Compression: Sync {
@@ -126,10 +131,157 @@ impl Compression for Uncompressed {
         Ok(buffer)
     }
 }
+/// The compression algorithms which can be negotiated on a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CompressionType {
+    /// The frames are sent uncompressed.
+    #[default]
+    Uncompressed,
+    /// The frames are compressed by `LZ4`.
+    Lz4,
+    /// The frames are compressed by `SNAPPY`.
+    Snappy,
+}
+
+impl CompressionType {
+    /// Get the type of the global compression, see `MyCompression`.
+    pub fn global() -> Self {
+        Self::from_option(MyCompression::option()).unwrap_or(CompressionType::Uncompressed)
+    }
+    /// Get the compression type out of the `COMPRESSION` option value, ie `lz4` or `snappy`.
+    pub fn from_option(option: Option<&str>) -> Option<Self> {
+        match option {
+            None => Some(CompressionType::Uncompressed),
+            Some(option) if option.eq_ignore_ascii_case("lz4") => Some(CompressionType::Lz4),
+            Some(option) if option.eq_ignore_ascii_case("snappy") => Some(CompressionType::Snappy),
+            Some(_) => None,
+        }
+    }
+    /// Get the `COMPRESSION` option value of the STARTUP frame, or None if the frames are uncompressed.
+    pub fn option(self) -> Option<&'static str> {
+        self.compression().option()
+    }
+    /// Get the compression method of the type.
+    pub fn compression(self) -> MyCompression {
+        match self {
+            CompressionType::Uncompressed => MyCompression(&UNCOMPRESSED),
+            CompressionType::Lz4 => MyCompression(&LZ4),
+            CompressionType::Snappy => MyCompression(&SNAPPY),
+        }
+    }
+    /// Negotiate the compression out of the `COMPRESSION` SUPPORTED option, the connection falls back to
+    /// uncompressed frames if the node doesn't support the requested compression.
+    pub fn negotiate(self, options: &HashMap<String, Vec<String>>) -> Self {
+        match self.option() {
+            Some(option)
+                if options
+                    .get("COMPRESSION")
+                    .into_iter()
+                    .flatten()
+                    .any(|supported| supported.eq_ignore_ascii_case(option)) =>
+            {
+                self
+            }
+            _ => CompressionType::Uncompressed,
+        }
+    }
+    /// Compress the request frame by this compression if it's compressed, and either the frame body reaches the
+    /// threshold (see `MyCompression::set_threshold`) or the compression is overridden, otherwise only the body
+    /// length is adjusted.
+    pub(crate) fn compress_frame(self, mut buffer: Vec<u8>, compress: Option<bool>) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(buffer.len() >= 9, "Buffer is too small!");
+        let body_length = buffer.len() - 9;
+        let compress =
+            self != CompressionType::Uncompressed && compress.unwrap_or(body_length >= MyCompression::threshold());
+        if compress {
+            // apply compression flag to the header
+            buffer[1] |= COMPRESSION;
+            let buffer = self.compression().compress(buffer)?;
+            COMPRESSED_FRAMES.fetch_add(1, Ordering::Relaxed);
+            UNCOMPRESSED_BYTES.fetch_add(body_length as u64, Ordering::Relaxed);
+            COMPRESSED_BYTES.fetch_add((buffer.len() - 9) as u64, Ordering::Relaxed);
+            Ok(buffer)
+        } else {
+            UNCOMPRESSED.compress(buffer)
+        }
+    }
+    /// Encode the request frame, which is built in the v4 layout, into the frame written on a connection which
+    /// speaks the provided protocol version and negotiated this compression (see `ProtocolVersion::encode_request`).
+    ///
+    /// The frame is compressed by this compression once its body reaches the threshold, while the frames which are
+    /// built compressed (see `QueryBuilder::compression`) must be compressed by this compression, and are kept
+    /// compressed.
+    pub fn encode_request(self, version: ProtocolVersion, frame: &[u8]) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(frame.len() >= 9, "Buffer is too small!");
+        let compressed = frame[1] & COMPRESSION == COMPRESSION;
+        let frame = match compressed {
+            true => Cow::Owned(self.decompress_frame(frame.to_vec())?),
+            false => Cow::Borrowed(frame),
+        };
+        let frame = version.encode_request(&frame)?.into_owned();
+        self.compress_frame(frame, if compressed { Some(true) } else { None })
+    }
+    /// Decompress the frame, which is compressed by this compression if its compression flag is set, ie a response
+    /// received on a connection which negotiated this compression, and clear its compression flag.
+    pub fn decompress_frame(self, frame: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(frame.len() >= 9, "Buffer is too small!");
+        if frame[1] & COMPRESSION != COMPRESSION {
+            return Ok(frame);
+        }
+        let mut frame = self.compression().decompress(frame)?;
+        frame[1] &= !COMPRESSION;
+        Ok(frame)
+    }
+}
+
+/// The compression of a request frame, which is set by its builder. The frames are uncompressed by default, as
+/// the connections compress them by their negotiated compression, see `CompressionType::encode_request`.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct FrameCompression {
+    /// The compression of the frame
+    pub(crate) compression: CompressionType,
+    /// Whether the frame is compressed regardless of the threshold
+    pub(crate) compress: Option<bool>,
+}
+
+impl FrameCompression {
+    /// Compress the built frame by its compression
+    pub(crate) fn compress_frame(self, buffer: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        self.compression.compress_frame(buffer, self.compress)
+    }
+}
+
 /// `MY_COMPRESSION` is used to enable user defines a global compression structure.
 pub static mut MY_COMPRESSION: MyCompression = MyCompression(&UNCOMPRESSED);
 /// `MY_COMPRESSION_FLAG` is used to indicate whether the compression is applied to the buffer.
 pub static mut MY_COMPRESSION_FLAG: u8 = 0;
+/// The minimum body size in bytes of the request frames to be compressed.
+static COMPRESSION_THRESHOLD: AtomicUsize = AtomicUsize::new(0);
+/// The number of compressed request frames.
+static COMPRESSED_FRAMES: AtomicU64 = AtomicU64::new(0);
+/// The total body size of the compressed request frames before compression.
+static UNCOMPRESSED_BYTES: AtomicU64 = AtomicU64::new(0);
+/// The total body size of the compressed request frames after compression.
+static COMPRESSED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// The compression stats of the request frames.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompressionStats {
+    /// The number of compressed request frames.
+    pub frames: u64,
+    /// The total body size of the compressed frames before compression.
+    pub uncompressed_bytes: u64,
+    /// The total body size of the compressed frames after compression.
+    pub compressed_bytes: u64,
+}
+
+impl CompressionStats {
+    /// Get the bytes saved by the compression, which is negative if the compression inflated the frames.
+    pub fn bytes_saved(&self) -> i64 {
+        self.uncompressed_bytes as i64 - self.compressed_bytes as i64
+    }
+}
+
 #[derive(Copy, Clone)]
 /// `MyCompression` structure provides a higher-level wrapper to let the user use a compresion method, i.e.,
 /// ````LZ4`, `SNAPPY`, or `UNCOMPRESSED`.
@@ -172,6 +324,28 @@ impl MyCompression {
     pub fn option() -> Option<&'static str> {
         unsafe { MY_COMPRESSION }.option()
     }
+    /// Set the minimum body size in bytes of the request frames to be compressed,
+    /// smaller frames are sent uncompressed unless overridden per request.
+    pub fn set_threshold(threshold: usize) {
+        COMPRESSION_THRESHOLD.store(threshold, Ordering::Relaxed);
+    }
+    /// Get the minimum body size in bytes of the request frames to be compressed.
+    pub fn threshold() -> usize {
+        COMPRESSION_THRESHOLD.load(Ordering::Relaxed)
+    }
+    /// Get the compression stats of the request frames.
+    pub fn stats() -> CompressionStats {
+        CompressionStats {
+            frames: COMPRESSED_FRAMES.load(Ordering::Relaxed),
+            uncompressed_bytes: UNCOMPRESSED_BYTES.load(Ordering::Relaxed),
+            compressed_bytes: COMPRESSED_BYTES.load(Ordering::Relaxed),
+        }
+    }
+    /// Compress the request frame if the compression is enabled, and either the frame body reaches the
+    /// threshold or the compression is overridden, otherwise only the body length is adjusted.
+    pub(crate) fn compress_frame(buffer: Vec<u8>, compression: Option<bool>) -> anyhow::Result<Vec<u8>> {
+        CompressionType::global().compress_frame(buffer, compression)
+    }
 }
 
 impl Compression for MyCompression {
@@ -187,3 +361,77 @@ impl Compression for MyCompression {
         self.0.compress(buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut frame = vec![4, 0, 0, 1, 0x07];
+        frame.extend_from_slice(&(body.len() as i32).to_be_bytes());
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    #[test]
+    fn negotiate_compression() {
+        let mut options = HashMap::new();
+        assert_eq!(CompressionType::Lz4.negotiate(&options), CompressionType::Uncompressed);
+        options.insert("COMPRESSION".to_string(), vec!["lz4".to_string(), "snappy".to_string()]);
+        assert_eq!(CompressionType::Lz4.negotiate(&options), CompressionType::Lz4);
+        assert_eq!(CompressionType::Snappy.negotiate(&options), CompressionType::Snappy);
+        options.insert("COMPRESSION".to_string(), vec!["snappy".to_string()]);
+        assert_eq!(CompressionType::Lz4.negotiate(&options), CompressionType::Uncompressed);
+        assert_eq!(CompressionType::from_option(Some("LZ4")), Some(CompressionType::Lz4));
+        assert_eq!(CompressionType::from_option(Some("zstd")), None);
+    }
+
+    #[test]
+    fn connection_compression() {
+        use crate::cql::{Consistency, Query, Statements};
+        let statement = format!("SELECT * FROM ks.t WHERE k IN ({})", vec!["1"; 200].join(", "));
+        let Query(query) = Query::new()
+            .statement(&statement)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        assert_eq!(query[1] & COMPRESSION, 0);
+        // the same frame is written on connections which negotiated different compressions
+        for compression in [
+            CompressionType::Lz4,
+            CompressionType::Snappy,
+            CompressionType::Uncompressed,
+        ] {
+            let encoded = compression.encode_request(ProtocolVersion::V4, &query).unwrap();
+            match compression {
+                CompressionType::Uncompressed => assert_eq!(encoded, query),
+                _ => {
+                    assert_eq!(encoded[1] & COMPRESSION, COMPRESSION);
+                    assert!(encoded.len() < query.len());
+                }
+            }
+            assert_eq!(compression.decompress_frame(encoded).unwrap(), query);
+            // the responses are decompressed by the compression of their connection
+            let body = vec![7; 1000];
+            let mut response = frame(&body);
+            if compression != CompressionType::Uncompressed {
+                response[1] |= COMPRESSION;
+            }
+            let response = compression.compression().compress(response).unwrap();
+            assert_eq!(compression.decompress_frame(response).unwrap(), frame(&body));
+        }
+        // the frames built compressed are kept compressed by their compression
+        let Query(compressed) = Query::new()
+            .statement(&statement)
+            .consistency(Consistency::One)
+            .compression(CompressionType::Snappy)
+            .build()
+            .unwrap();
+        assert_eq!(compressed[1] & COMPRESSION, COMPRESSION);
+        let encoded = CompressionType::Snappy
+            .encode_request(ProtocolVersion::V4, &compressed)
+            .unwrap();
+        assert_eq!(encoded, compressed);
+        assert_eq!(CompressionType::Snappy.decompress_frame(encoded).unwrap(), query);
+    }
+}
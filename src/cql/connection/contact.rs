@@ -0,0 +1,102 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{anyhow, bail, ensure};
+use std::{future::Future, net::SocketAddr, time::Duration};
+use tokio::sync::mpsc;
+
+/// The default delay between the staggered connection attempts to the contact points
+pub const DEFAULT_STAGGER: Duration = Duration::from_millis(250);
+
+/// Connect to the first reachable contact point, happy eyeballs style.
+///
+/// The attempts are started one by one in the contact points order, where the next attempt is started once the
+/// stagger delay elapsed or the previous attempt failed, so a down contact point doesn't block the connection
+/// for the full TCP timeout. The first successful connection is returned and the other attempts are cancelled.
+/// If every attempt fails, the error holds the failure of every contact point.
+pub async fn happy_eyeballs<T, F, Fut>(
+    contact_points: &[SocketAddr],
+    stagger: Duration,
+    mut connect: F,
+) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+{
+    ensure!(!contact_points.is_empty(), "No contact point provided!");
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut attempts = Vec::with_capacity(contact_points.len());
+    let mut errors = Vec::new();
+    let mut pending = 0;
+    loop {
+        if let Some(&address) = contact_points.get(attempts.len()) {
+            let attempt = connect(address);
+            let tx = tx.clone();
+            attempts.push(tokio::spawn(async move {
+                tx.send((address, attempt.await)).ok();
+            }));
+            pending += 1;
+        }
+        let result = if attempts.len() < contact_points.len() {
+            match tokio::time::timeout(stagger, rx.recv()).await {
+                Ok(result) => result,
+                // the stagger delay elapsed, so start the next attempt along with the pending ones
+                Err(_) => continue,
+            }
+        } else {
+            rx.recv().await
+        };
+        match result.ok_or_else(|| anyhow!("The connection attempts were dropped!"))? {
+            (_, Ok(connection)) => {
+                for attempt in attempts {
+                    attempt.abort();
+                }
+                return Ok(connection);
+            }
+            (address, Err(e)) => {
+                errors.push(format!("{}: {}", address, e));
+                pending -= 1;
+                if pending == 0 && attempts.len() == contact_points.len() {
+                    bail!("Unable to connect to any contact point: {}", errors.join(", "));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn staggered_attempts() {
+        let contact_points: Vec<SocketAddr> = (1..=3).map(|port| ([127, 0, 0, 1], port).into()).collect();
+        // the first contact point hangs, the second one fails, and the third one succeeds
+        let start = Instant::now();
+        let connected = happy_eyeballs(&contact_points, Duration::from_millis(50), |address| async move {
+            match address.port() {
+                1 => {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok(1)
+                }
+                2 => bail!("refused"),
+                port => Ok(port),
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(connected, 3);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let error = happy_eyeballs(&contact_points, Duration::from_millis(50), |address| async move {
+            anyhow::Result::<()>::Err(anyhow!("refused {}", address.port()))
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unable to connect to any contact point: 127.0.0.1:1: refused 1, 127.0.0.1:2: refused 2, 127.0.0.1:3: refused 3"
+        );
+    }
+}
@@ -1,20 +1,32 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
-use super::tokens::{Info, Row};
+use super::{
+    contact::happy_eyeballs,
+    error::ConnectError,
+    framing::{read_frame, Framing},
+    stream::CqlStream,
+    tls::TlsConfig,
+    tokens::{Info, Row},
+};
 use crate::cql::{
-    compression::{MyCompression, UNCOMPRESSED},
+    compression::{CompressionType, UNCOMPRESSED},
     frame::{
         auth_challenge::AuthChallenge,
         auth_response::{AllowAllAuth, AuthResponse, Authenticator, PasswordAuth},
+        auth_success::AuthSuccess,
         authenticate::Authenticate,
         consistency::Consistency,
         decoder::{Decoder, Frame},
+        error::ErrorCodes,
+        event::{Event, EventType},
         options::Options,
         query::Query,
+        register::Register,
         rows::Rows,
         startup::Startup,
         supported::Supported,
+        version::ProtocolVersion,
         Statements,
     },
 };
@@ -22,14 +34,17 @@ use anyhow::{anyhow, bail, ensure};
 use port_scanner::{local_port_available, request_open_port};
 use std::{
     collections::HashMap,
-    convert::TryInto,
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
 };
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::AsyncWriteExt,
     net::{TcpSocket, TcpStream},
 };
 
+/// The maximum number of AUTH_CHALLENGE frames of an authentication, which bounds a misbehaving SASL exchange
+const MAX_AUTH_ROUNDS: usize = 16;
+
 #[derive(Default)]
 /// CqlBuilder struct to establish cql connection with the provided configurations
 pub struct CqlBuilder<Auth: Authenticator> {
@@ -39,12 +54,15 @@ pub struct CqlBuilder<Auth: Authenticator> {
     recv_buffer_size: Option<u32>,
     send_buffer_size: Option<u32>,
     shard_id: Option<u16>,
+    compression: Option<CompressionType>,
+    protocol_version: Option<ProtocolVersion>,
     authenticator: Option<Auth>,
+    tls: Option<TlsConfig>,
     cql: Option<Cql>,
 }
 /// CQL connection structure.
 pub struct Cql {
-    stream: TcpStream,
+    stream: CqlStream,
     address: SocketAddr,
     tokens: Option<Vec<i64>>,
     dc: Option<String>,
@@ -52,6 +70,9 @@ pub struct Cql {
     shard_aware_port: u16,
     shard_count: u16,
     msb: u8,
+    supported_version: ProtocolVersion,
+    compression: CompressionType,
+    framing: Framing,
 }
 
 impl<Auth: Authenticator> CqlBuilder<Auth> {
@@ -84,6 +105,25 @@ impl<Auth: Authenticator> CqlBuilder<Auth> {
         self.shard_id.replace(shard_id);
         self
     }
+    /// Set the compression requested on the connection, which defaults to the global compression (see
+    /// `crate::cql::MyCompression`). The connection falls back to uncompressed frames if the node doesn't support it.
+    pub fn compression(mut self, compression: Option<CompressionType>) -> Self {
+        self.compression = compression;
+        self
+    }
+    /// Set the highest protocol version requested on the connection, which defaults to V4. The connection speaks
+    /// the highest version supported by the node up to it, where the v5 frames are wrapped into checksummed
+    /// segments once the node replied to the STARTUP request, and aren't compressed.
+    pub fn protocol_version(mut self, version: ProtocolVersion) -> Self {
+        self.protocol_version.replace(version);
+        self
+    }
+    /// Set the TLS configuration of the connection, for the nodes with the client-to-node encryption enabled
+    /// (requires the `tls` feature)
+    pub fn tls(mut self, tls: Option<TlsConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
     /// Instruct the builder to use the provided authenticator for establishing the connection
     pub fn authenticator(mut self, auth: Auth) -> Self {
         self.authenticator.replace(auth);
@@ -92,155 +132,213 @@ impl<Auth: Authenticator> CqlBuilder<Auth> {
     fn set_local_addr(&mut self, local_addr: SocketAddr) {
         self.local_addr.replace(local_addr);
     }
-    async fn connect(&mut self) -> anyhow::Result<()> {
-        let socket = TcpSocket::new_v4()?;
+    async fn connect(&mut self) -> Result<(), ConnectError> {
+        let address = self.address.ok_or(ConnectError::MissingAddress)?;
+        let socket = TcpSocket::new_v4().map_err(|e| ConnectError::tcp(address, e))?;
         if let Some(local_addr) = self.local_addr {
             // set client side port
-            socket.bind(local_addr)?;
+            socket.bind(local_addr).map_err(|e| ConnectError::tcp(address, e))?;
         }
         // set socket flags
         if let Some(recv_buffer_size) = self.recv_buffer_size {
-            socket.set_recv_buffer_size(recv_buffer_size)?
+            socket
+                .set_recv_buffer_size(recv_buffer_size)
+                .map_err(|e| ConnectError::tcp(address, e))?
         }
         if let Some(send_buffer_size) = self.send_buffer_size {
-            socket.set_send_buffer_size(send_buffer_size)?
-        }
-        let mut stream = socket
-            .connect(self.address.ok_or_else(|| anyhow!("Address does not exist!"))?)
-            .await?;
-        // create options frame
-        let Options(opt_buf) = Options::new().build();
-        // write_all options frame to stream
-        stream.write_all(&opt_buf).await?;
-        // collect_frame_response
-        let buffer = collect_frame_response(&mut stream).await?;
-        // Create Decoder from buffer. OPTIONS cannot be compressed as
-        // the client and protocol didn't yet settle on compression algo (if any)
-        let decoder = Decoder::new(buffer, UNCOMPRESSED)?;
-        // make sure the frame response is not error
-        if decoder.is_error()? {
-            // check if response is_error.
-            bail!("CQL connection not supported due to CqlError: {}", decoder.get_error()?);
+            socket
+                .set_send_buffer_size(send_buffer_size)
+                .map_err(|e| ConnectError::tcp(address, e))?
         }
-        ensure!(decoder.is_supported()?, "CQL connection not supported!");
-        // decode supported options from decoder
-        let supported = Supported::new(&decoder)?;
+        let stream = socket
+            .connect(address)
+            .await
+            .map_err(|e| ConnectError::tcp(address, e))?;
+        // perform the TLS handshake if the connection is encrypted
+        let mut stream = match self.tls.as_ref() {
+            Some(tls) => tls.connect(address, stream).await?,
+            None => CqlStream::Tcp(stream),
+        };
+        // exchange the options frame with the supported frame
+        let supported = request_options(&mut stream, address).await?;
         // create empty hashmap options;
         let mut options: HashMap<String, String> = HashMap::new();
         // get the supported_cql_version option;
         let cql_version = supported
             .get_options()
             .get("CQL_VERSION")
-            .ok_or_else(|| anyhow!("Cannot read supported CQL versions!"))?
-            .first()
-            .ok_or_else(|| anyhow!("Cannot read supported CQL version!"))?;
+            .and_then(|versions| versions.first())
+            .ok_or_else(|| ConnectError::protocol_mismatch(address, "Cannot read supported CQL version!"))?;
         // insert the supported_cql_version option into the options;
         options.insert("CQL_VERSION".to_owned(), cql_version.to_owned());
-        // insert the supported_compression option into the options if it was set.;
-        if let Some(compression) = MyCompression::option() {
-            options.insert("COMPRESSION".to_owned(), compression.to_owned());
+        // negotiate the protocol version, where the STARTUP request and the next frames are in its layout
+        let supported_version = ProtocolVersion::negotiate(supported.get_options(), ProtocolVersion::V5);
+        let mut framing = Framing::new(supported_version.min(self.protocol_version.unwrap_or_default()));
+        // negotiate the compression out of the supported ones, and insert its option if it's compressed, unless
+        // the frames are wrapped into segments, which aren't compressed
+        let compression = match framing.version().is_segmented() {
+            true => CompressionType::Uncompressed,
+            false => self
+                .compression
+                .unwrap_or_else(CompressionType::global)
+                .negotiate(supported.get_options()),
+        };
+        if let Some(option) = compression.option() {
+            options.insert("COMPRESSION".to_owned(), option.to_owned());
         }
+        let decode_error = |e: anyhow::Error| ConnectError::bootstrap(address, e);
         // create startup frame using the selected options;
         let Startup(startup_buf) = Startup::new().options(&options).build();
         // write_all startup frame to stream;
-        stream.write_all(&startup_buf).await?;
-        let buffer = collect_frame_response(&mut stream).await?;
+        stream
+            .write_all(&framing.encode(&startup_buf).map_err(decode_error)?)
+            .await
+            .map_err(|e| ConnectError::tcp(address, e))?;
+        // the frames which follow the STARTUP request are compressed by the negotiated compression
+        framing.set_compression(compression);
+        let buffer = framing
+            .read(&mut stream)
+            .await
+            .map_err(|e| ConnectError::tcp(address, e))?;
+        // the frames which follow the reply to the STARTUP request are wrapped into segments
+        framing.start_segments();
         // Create Decoder from buffer.
-        let decoder = Decoder::new(buffer, MyCompression::get())?;
-        if decoder.is_authenticate()? {
-            if self.authenticator.is_none() {
-                Authenticate::new(&decoder)?;
-                bail!("CQL connection not ready due to authenticator is not provided");
-            }
-            let auth_response = AuthResponse::new()
-                .token(
-                    self.authenticator
-                        .as_ref()
-                        .ok_or_else(|| anyhow!("Failed to read Auth Response!"))?,
-                )
-                .build(MyCompression::get())?;
-            // write_all auth_response frame to stream;
-            stream.write_all(&auth_response.0).await?;
-            // collect_frame_response
-            let buffer = collect_frame_response(&mut stream).await?;
-            // Create Decoder from buffer.
-            let decoder = Decoder::new(buffer, MyCompression::get())?;
-            if decoder.is_error()? {
-                bail!("CQL connection not ready due to CqlError: {}", decoder.get_error()?);
-            }
-            if decoder.is_auth_challenge()? {
-                AuthChallenge::new(&decoder)?;
-                bail!("CQL connection not ready due to Unsupported Auth Challenge");
+        let decoder = Decoder::new(buffer, compression.compression()).map_err(decode_error)?;
+        if decoder.is_authenticate().map_err(decode_error)? {
+            let authenticator = Authenticate::new(&decoder).map_err(decode_error)?.authenticator;
+            let authentication_error = |reason: String| ConnectError::Authentication {
+                address,
+                authenticator: authenticator.clone(),
+                reason,
+            };
+            let auth = self
+                .authenticator
+                .as_ref()
+                .ok_or_else(|| authentication_error("No authenticator provided".to_string()))?;
+            let mut session = auth
+                .session(&authenticator)
+                .map_err(|e| authentication_error(e.to_string()))?;
+            let mut token = session
+                .initial_response()
+                .await
+                .map_err(|e| authentication_error(e.to_string()))?;
+            // exchange the challenges until the node accepts or rejects the authentication
+            for round in 0.. {
+                if round == MAX_AUTH_ROUNDS {
+                    return Err(authentication_error(format!(
+                        "No outcome after {} challenges",
+                        MAX_AUTH_ROUNDS
+                    )));
+                }
+                let auth_response = AuthResponse::new()
+                    .bytes(token.as_deref())
+                    .build(compression.compression())
+                    .map_err(decode_error)?;
+                // write_all auth_response frame to stream;
+                stream
+                    .write_all(&framing.encode(&auth_response.0).map_err(decode_error)?)
+                    .await
+                    .map_err(|e| ConnectError::tcp(address, e))?;
+                // collect_frame_response
+                let buffer = framing
+                    .read(&mut stream)
+                    .await
+                    .map_err(|e| ConnectError::tcp(address, e))?;
+                // Create Decoder from buffer.
+                let decoder = Decoder::new(buffer, compression.compression()).map_err(decode_error)?;
+                if decoder.is_error().map_err(decode_error)? {
+                    let error = decoder.get_error().map_err(decode_error)?;
+                    return Err(authentication_error(error.to_string()));
+                }
+                if decoder.is_auth_challenge().map_err(decode_error)? {
+                    let challenge = AuthChallenge::new(&decoder).map_err(decode_error)?.into_token();
+                    token = session
+                        .evaluate_challenge(challenge)
+                        .await
+                        .map_err(|e| authentication_error(e.to_string()))?;
+                    continue;
+                }
+                if !decoder.is_auth_success().map_err(decode_error)? {
+                    return Err(authentication_error("Authorization unsuccessful!".to_string()));
+                }
+                let success = AuthSuccess::new(&decoder).map_err(decode_error)?.into_token();
+                session
+                    .on_success(success)
+                    .map_err(|e| authentication_error(e.to_string()))?;
+                break;
             }
-            ensure!(decoder.is_auth_success()?, "Authorization unsuccessful!");
-        } else if decoder.is_error()? {
-            bail!("CQL connection not ready due to CqlError: {}", decoder.get_error()?);
-        } else {
-            ensure!(decoder.is_ready()?, "Decoder is not ready!");
+        } else if decoder.is_error().map_err(decode_error)? {
+            let error = decoder.get_error().map_err(decode_error)?;
+            return Err(match compression.option() {
+                // the node advertised the compression, but failed to use it
+                Some(requested) if error.code == ErrorCodes::ProtocolError => ConnectError::Compression {
+                    address,
+                    requested: requested.to_string(),
+                    supported: supported.get_options().get("COMPRESSION").cloned().unwrap_or_default(),
+                    reason: error.to_string(),
+                },
+                _ => ConnectError::rejected(address, error),
+            });
+        } else if !decoder.is_ready().map_err(decode_error)? {
+            return Err(ConnectError::bootstrap(address, "Decoder is not ready!"));
         }
         // copy usefull options
-        let shard: u16 = supported
-            .get_options()
-            .get("SCYLLA_SHARD")
-            .ok_or_else(|| anyhow!("Cannot read supported scylla shards!"))?
-            .first()
-            .ok_or_else(|| anyhow!("Cannot read scylla shard!"))?
-            .parse()?;
-        let nr_shard: u16 = supported
-            .get_options()
-            .get("SCYLLA_NR_SHARDS")
-            .ok_or_else(|| anyhow!("Cannot read supported scylla NR shards!"))?
-            .first()
-            .ok_or_else(|| anyhow!("Cannot read scylla NR shard!"))?
-            .parse()?;
-        let ignore_msb: u8 = supported
-            .get_options()
-            .get("SCYLLA_SHARDING_IGNORE_MSB")
-            .ok_or_else(|| anyhow!("Cannot read supported scylla ignore MSBs!"))?
-            .first()
-            .ok_or_else(|| anyhow!("Cannot read scylla scylla ignore MSB!"))?
-            .parse()?;
-        let shard_aware_port: u16 = supported
-            .get_options()
-            .get("SCYLLA_SHARD_AWARE_PORT")
-            .ok_or_else(|| {
-                anyhow!("Cannot read supported scylla shard aware ports! Try upgrading your Scylla to latest release!")
-            })?
-            .first()
-            .ok_or_else(|| {
-                anyhow!("Cannot read supported scylla shard aware port! Try upgrading your Scylla to latest release!")
-            })?
-            .parse()?;
+        let scylla_option = |name: &str| -> Result<&String, ConnectError> {
+            supported
+                .get_options()
+                .get(name)
+                .and_then(|values| values.first())
+                .ok_or_else(|| {
+                    ConnectError::bootstrap(
+                        address,
+                        format!(
+                            "Cannot read the supported {} option! Try upgrading your Scylla to latest release!",
+                            name
+                        ),
+                    )
+                })
+        };
+        let parse_error = |e: std::num::ParseIntError| ConnectError::bootstrap(address, e);
+        let shard: u16 = scylla_option("SCYLLA_SHARD")?.parse().map_err(parse_error)?;
+        let nr_shard: u16 = scylla_option("SCYLLA_NR_SHARDS")?.parse().map_err(parse_error)?;
+        let ignore_msb: u8 = scylla_option("SCYLLA_SHARDING_IGNORE_MSB")?
+            .parse()
+            .map_err(parse_error)?;
+        // the encrypted connections have their own shard aware port
+        let shard_aware_port_option = if stream.is_tls() {
+            "SCYLLA_SHARD_AWARE_PORT_SSL"
+        } else {
+            "SCYLLA_SHARD_AWARE_PORT"
+        };
+        let shard_aware_port: u16 = scylla_option(shard_aware_port_option)?.parse().map_err(parse_error)?;
         // create cqlconn
         let cqlconn = Cql {
             stream,
-            address: self.address.ok_or_else(|| anyhow!("Address does not exist!"))?,
+            address,
             tokens: None,
             shard_id: shard,
             shard_aware_port,
             shard_count: nr_shard,
             msb: ignore_msb,
             dc: None,
+            supported_version,
+            compression,
+            framing,
         };
         self.cql.replace(cqlconn);
         Ok(())
     }
     /// Build the CqlBuilder and then try to connect
-    pub async fn build(mut self) -> anyhow::Result<Cql> {
+    pub async fn build(mut self) -> Result<Cql, ConnectError> {
         // connect
         self.connect().await?;
+        let address = self.address.ok_or(ConnectError::MissingAddress)?;
         // take the cql_connection
-        let mut cqlconn = self.cql.take().ok_or_else(|| anyhow!("No CQL connection!"))?;
+        let mut cqlconn = self.cql.take().ok_or(ConnectError::MissingAddress)?;
         // make sure to connect to the right shard(if provided)
         if let Some(requested_shard_id) = self.shard_id {
             if requested_shard_id != cqlconn.shard_id {
-                if self
-                    .address
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("Address does not exist!"))?
-                    .port()
-                    == cqlconn.shard_aware_port
-                {
+                if address.port() == cqlconn.shard_aware_port {
                     while let Some(requested_open_port) = request_open_port() {
                         let will_get_shard_id = requested_open_port % (cqlconn.shard_count as u16);
                         if will_get_shard_id != requested_shard_id {
@@ -254,11 +352,14 @@ impl<Auth: Authenticator> CqlBuilder<Auth> {
                                 // reconnect
                                 self.connect().await?;
                                 // take the cql_connection
-                                let mut cqlconn = self.cql.take().ok_or_else(|| anyhow!("No CQL connection!"))?;
+                                let mut cqlconn = self.cql.take().ok_or(ConnectError::MissingAddress)?;
                                 // assert shard_id is equal
                                 assert_eq!(cqlconn.shard_id, requested_shard_id);
                                 if self.tokens {
-                                    cqlconn.fetch_tokens().await?;
+                                    cqlconn
+                                        .fetch_tokens()
+                                        .await
+                                        .map_err(|e| ConnectError::bootstrap(address, e))?;
                                 }
                                 return Ok(cqlconn);
                             } else {
@@ -272,17 +373,23 @@ impl<Auth: Authenticator> CqlBuilder<Auth> {
                             // reconnect
                             self.connect().await?;
                             // take the cql_connection
-                            let mut cqlconn = self.cql.take().ok_or_else(|| anyhow!("No CQL connection!"))?;
+                            let mut cqlconn = self.cql.take().ok_or(ConnectError::MissingAddress)?;
                             // assert shard_id is equal
                             assert_eq!(cqlconn.shard_id, requested_shard_id);
                             if self.tokens {
-                                cqlconn.fetch_tokens().await?;
+                                cqlconn
+                                    .fetch_tokens()
+                                    .await
+                                    .map_err(|e| ConnectError::bootstrap(address, e))?;
                             }
                             return Ok(cqlconn);
                         }
                     }
                     // return error no fd/open_ports anymore?
-                    bail!("CQL connection not established due to lack of open ports");
+                    Err(ConnectError::bootstrap(
+                        address,
+                        "CQL connection not established due to lack of open ports",
+                    ))
                 } else {
                     // not shard_aware_port
                     // buffer connections temporary to force scylla connects us to new shard_id
@@ -291,15 +398,21 @@ impl<Auth: Authenticator> CqlBuilder<Auth> {
                     loop {
                         match self.connect().await {
                             Ok(_) => {
-                                let mut cqlconn = self.cql.take().ok_or_else(|| anyhow!("No CQL connection!"))?;
+                                let mut cqlconn = self.cql.take().ok_or(ConnectError::MissingAddress)?;
                                 if cqlconn.shard_id == requested_shard_id {
                                     if self.tokens {
-                                        cqlconn.fetch_tokens().await?;
+                                        cqlconn
+                                            .fetch_tokens()
+                                            .await
+                                            .map_err(|e| ConnectError::bootstrap(address, e))?;
                                     }
                                     return Ok(cqlconn);
                                 } else if requested_shard_id >= cqlconn.shard_count {
                                     // error as it's impossible to connect to shard_id doesn't exist
-                                    bail!("Requested shard ID does not exist: {}", requested_shard_id);
+                                    return Err(ConnectError::bootstrap(
+                                        address,
+                                        format!("Requested shard ID does not exist: {}", requested_shard_id),
+                                    ));
                                 } else {
                                     if conns.len() > cqlconn.shard_count as usize {
                                         // clear conns otherwise we are going to overflow the memory
@@ -319,22 +432,52 @@ impl<Auth: Authenticator> CqlBuilder<Auth> {
             } else {
                 // FOUND connection
                 if self.tokens {
-                    cqlconn.fetch_tokens().await?;
+                    cqlconn
+                        .fetch_tokens()
+                        .await
+                        .map_err(|e| ConnectError::bootstrap(address, e))?;
                 }
                 return Ok(cqlconn);
             }
         } else {
             // shard_id not provided, so connection is ready
             if self.tokens {
-                cqlconn.fetch_tokens().await?;
+                cqlconn
+                    .fetch_tokens()
+                    .await
+                    .map_err(|e| ConnectError::bootstrap(address, e))?;
             }
             return Ok(cqlconn);
         }
     }
+    /// Build the CqlBuilder and then try to connect to the contact points in parallel, using staggered attempts
+    /// (see `happy_eyeballs`), which returns the first established connection. The builder address is ignored.
+    pub async fn build_any(self, contact_points: &[SocketAddr], stagger: Duration) -> anyhow::Result<Cql>
+    where
+        Auth: Send + 'static,
+    {
+        happy_eyeballs(contact_points, stagger, |address| {
+            let builder = CqlBuilder {
+                address: Some(address),
+                local_addr: self.local_addr,
+                tokens: self.tokens,
+                recv_buffer_size: self.recv_buffer_size,
+                send_buffer_size: self.send_buffer_size,
+                shard_id: self.shard_id,
+                compression: self.compression,
+                protocol_version: self.protocol_version,
+                authenticator: self.authenticator.clone(),
+                tls: self.tls.clone(),
+                cql: None,
+            };
+            async move { Ok(builder.build().await?) }
+        })
+        .await
+    }
 }
 
-impl Into<TcpStream> for Cql {
-    fn into(self) -> TcpStream {
+impl Into<CqlStream> for Cql {
+    fn into(self) -> CqlStream {
         self.stream
     }
 }
@@ -355,11 +498,11 @@ impl Cql {
         // create query to fetch tokens and info from system.local;
         let query = fetch_tokens_query()?;
         // write_all query to the stream
-        self.stream.write_all(query.as_slice()).await?;
+        self.stream.write_all(&self.framing.encode(&query)?).await?;
         // collect_frame_response
-        let buffer = collect_frame_response(&mut self.stream).await?;
+        let buffer = self.framing.read(&mut self.stream).await?;
         // Create Decoder from buffer.
-        let decoder = Decoder::new(buffer, MyCompression::get())?;
+        let decoder = Decoder::new(buffer, self.compression.compression())?;
 
         if decoder.is_rows()? {
             let Row { data_center, tokens } = Info::new(decoder)?.next().ok_or(anyhow!("No info found!"))?;
@@ -376,10 +519,40 @@ impl Cql {
         }
         Ok(())
     }
+    /// Register the connection to receive the provided server events, which are then pushed by the node on it
+    /// (see `Cql::next_event`). The connection should be dedicated to the events.
+    pub async fn register(&mut self, events: &[EventType]) -> anyhow::Result<()> {
+        let Register(register) = Register::new().events(events).build();
+        self.stream.write_all(&self.framing.encode(&register)?).await?;
+        let buffer = self.framing.read(&mut self.stream).await?;
+        let decoder = Decoder::new(buffer, self.compression.compression())?;
+        if decoder.is_error()? {
+            bail!(
+                "Failed to register for events due to CqlError: {}",
+                decoder.get_error()?
+            );
+        }
+        ensure!(decoder.is_ready()?, "Failed to register for events!");
+        Ok(())
+    }
+    /// Await the next server event pushed by the node on the registered connection, while skipping any other frame
+    pub async fn next_event(&mut self) -> anyhow::Result<Event> {
+        loop {
+            let buffer = self.framing.read(&mut self.stream).await?;
+            let decoder = Decoder::new(buffer, self.compression.compression())?;
+            if decoder.is_event()? {
+                return Event::new(&decoder);
+            }
+        }
+    }
     /// Get the socket stream behind the cql connection
-    pub fn stream(&mut self) -> &mut TcpStream {
+    pub fn stream(&mut self) -> &mut CqlStream {
         &mut self.stream
     }
+    /// Split the connection into its stream and its framing, ie to multiplex the requests on it
+    pub(crate) fn into_framed(self) -> (CqlStream, Framing) {
+        (self.stream, self.framing)
+    }
     /// Take the associated tokens of the connected scylla node
     pub fn take_tokens(&mut self) -> Option<Vec<i64>> {
         self.tokens.take()
@@ -404,18 +577,82 @@ impl Cql {
     pub fn msb(&self) -> u8 {
         self.msb
     }
+    /// Get the protocol version the connection speaks, as negotiated out of the requested one (see
+    /// `CqlBuilder::protocol_version`) and the ones supported by the node
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.framing.version()
+    }
+    /// Get the compression negotiated on the connection
+    pub fn compression(&self) -> CompressionType {
+        self.compression
+    }
+    /// Get the highest protocol version supported by the node, as negotiated out of its SUPPORTED options
+    pub fn supported_protocol_version(&self) -> ProtocolVersion {
+        self.supported_version
+    }
+}
+
+/// Send the OPTIONS request and decode the SUPPORTED response
+async fn request_options(stream: &mut CqlStream, address: SocketAddr) -> Result<Supported, ConnectError> {
+    // create options frame
+    let Options(opt_buf) = Options::new().build();
+    // write_all options frame to stream
+    stream
+        .write_all(&opt_buf)
+        .await
+        .map_err(|e| ConnectError::tcp(address, e))?;
+    // collect_frame_response
+    let buffer = read_frame(stream).await.map_err(|e| ConnectError::tcp(address, e))?;
+    // a node which doesn't speak v4 replies with its own version
+    if buffer[0] & 0x7F != 4 {
+        return Err(ConnectError::protocol_mismatch(
+            address,
+            format!("The node replied with the protocol version v{}", buffer[0] & 0x7F),
+        ));
+    }
+    // Create Decoder from buffer. OPTIONS cannot be compressed as
+    // the client and protocol didn't yet settle on compression algo (if any)
+    let decode_error = |e: anyhow::Error| ConnectError::bootstrap(address, e);
+    let decoder = Decoder::new(buffer, UNCOMPRESSED).map_err(decode_error)?;
+    // make sure the frame response is not error
+    if decoder.is_error().map_err(decode_error)? {
+        return Err(ConnectError::rejected(
+            address,
+            decoder.get_error().map_err(decode_error)?,
+        ));
+    }
+    if !decoder.is_supported().map_err(decode_error)? {
+        return Err(ConnectError::bootstrap(address, "CQL connection not supported!"));
+    }
+    // decode supported options from decoder
+    Supported::new(&decoder).map_err(decode_error)
+}
+
+/// Query the SUPPORTED options of the node, ie its compression algorithms and protocol versions,
+/// over a short-lived connection which doesn't require authentication.
+pub async fn node_options(address: SocketAddr) -> anyhow::Result<HashMap<String, Vec<String>>> {
+    let mut stream = CqlStream::Tcp(
+        TcpStream::connect(address)
+            .await
+            .map_err(|e| ConnectError::tcp(address, e))?,
+    );
+    Ok(request_options(&mut stream, address).await?.into_options())
 }
 
-async fn collect_frame_response(stream: &mut TcpStream) -> anyhow::Result<Vec<u8>> {
-    // create buffer
-    let mut buffer = vec![0; 9];
-    // read response into buffer
-    stream.read_exact(&mut buffer).await?;
-    let body_length = i32::from_be_bytes(buffer[5..9].try_into()?);
-    // extend buffer
-    buffer.resize((body_length + 9).try_into()?, 0);
-    stream.read_exact(&mut buffer[9..]).await?;
-    Ok(buffer)
+/// Resolve the host, ie `"scylla.local:9042"`, into the node addresses.
+pub async fn resolve(host: &str) -> Result<Vec<SocketAddr>, ConnectError> {
+    let dns_error = |reason: String| ConnectError::Dns {
+        host: host.to_string(),
+        reason,
+    };
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host(host)
+        .await
+        .map_err(|e| dns_error(e.to_string()))?
+        .collect();
+    if addresses.is_empty() {
+        return Err(dns_error("No address found".to_string()));
+    }
+    Ok(addresses)
 }
 
 /// Query the data center, and tokens from the ScyllaDB.
@@ -426,3 +663,225 @@ fn fetch_tokens_query() -> anyhow::Result<Vec<u8>> {
         .build()?;
     Ok(payload)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::frame::{encode_segments, opcode, SegmentDecoder, StatusChange};
+    use std::{collections::VecDeque, convert::TryFrom};
+    use tokio::{
+        io::AsyncReadExt,
+        net::{TcpListener, TcpStream},
+    };
+
+    /// The number of tokens of the stub node, whose rows span several segments
+    const TOKENS: i64 = 20_000;
+
+    /// The connection of a stub node, which reads the requests and writes the responses in the layout of the
+    /// protocol version of the STARTUP request
+    struct StubConnection {
+        socket: TcpStream,
+        version: ProtocolVersion,
+        segmented: bool,
+        segments: SegmentDecoder,
+        frames: VecDeque<Vec<u8>>,
+    }
+
+    impl StubConnection {
+        async fn read(&mut self) -> anyhow::Result<Vec<u8>> {
+            if !self.segmented {
+                return read_frame(&mut self.socket).await;
+            }
+            loop {
+                if let Some(frame) = self.frames.pop_front() {
+                    return Ok(frame);
+                }
+                let mut buffer = vec![0; 1024];
+                let read = self.socket.read(&mut buffer).await?;
+                ensure!(read > 0, "The connection is closed");
+                self.segments.extend(&buffer[..read]);
+                self.frames.extend(self.segments.decode()?);
+            }
+        }
+        async fn write(&mut self, opcodes_and_bodies: &[(u8, Vec<u8>)]) -> anyhow::Result<()> {
+            let frames: Vec<Vec<u8>> = opcodes_and_bodies
+                .iter()
+                .map(|(opcode, body)| {
+                    let mut frame = vec![0x80 | self.version.request_byte(), 0, 0, 0, *opcode];
+                    frame.extend_from_slice(&(body.len() as i32).to_be_bytes());
+                    frame.extend_from_slice(body);
+                    frame
+                })
+                .collect();
+            let bytes = match self.segmented {
+                true => encode_segments(&frames),
+                false => frames.concat(),
+            };
+            // write in small chunks, so the segments are received partially
+            for chunk in bytes.chunks(4096) {
+                self.socket.write_all(chunk).await?;
+                tokio::task::yield_now().await;
+            }
+            Ok(())
+        }
+    }
+
+    fn string(buffer: &mut Vec<u8>, string: &str) {
+        buffer.extend_from_slice(&(string.len() as u16).to_be_bytes());
+        buffer.extend_from_slice(string.as_bytes());
+    }
+
+    fn supported(protocol_versions: &[&str]) -> Vec<u8> {
+        let options: [(&str, &[&str]); 6] = [
+            ("CQL_VERSION", &["3.3.1"]),
+            ("PROTOCOL_VERSIONS", protocol_versions),
+            ("SCYLLA_SHARD", &["0"]),
+            ("SCYLLA_NR_SHARDS", &["1"]),
+            ("SCYLLA_SHARDING_IGNORE_MSB", &["12"]),
+            ("SCYLLA_SHARD_AWARE_PORT", &["19042"]),
+        ];
+        let mut body = (options.len() as u16).to_be_bytes().to_vec();
+        for (name, values) in options.iter() {
+            string(&mut body, name);
+            body.extend_from_slice(&(values.len() as u16).to_be_bytes());
+            for value in values.iter() {
+                string(&mut body, value);
+            }
+        }
+        body
+    }
+
+    /// The rows of `system.local`, where the v5 metadata carries a new result metadata id
+    fn local_rows(version: ProtocolVersion) -> Vec<u8> {
+        let mut body = 2i32.to_be_bytes().to_vec();
+        match version.is_segmented() {
+            true => {
+                body.extend_from_slice(&0x0009i32.to_be_bytes());
+                body.extend_from_slice(&2i32.to_be_bytes());
+                body.extend_from_slice(&[0, 2, 1, 2]);
+            }
+            false => {
+                body.extend_from_slice(&0x0001i32.to_be_bytes());
+                body.extend_from_slice(&2i32.to_be_bytes());
+            }
+        }
+        string(&mut body, "system");
+        string(&mut body, "local");
+        string(&mut body, "data_center");
+        body.extend_from_slice(&0x000Du16.to_be_bytes());
+        string(&mut body, "tokens");
+        body.extend_from_slice(&0x0022u16.to_be_bytes());
+        body.extend_from_slice(&0x000Du16.to_be_bytes());
+        body.extend_from_slice(&1i32.to_be_bytes());
+        body.extend_from_slice(&3i32.to_be_bytes());
+        body.extend_from_slice(b"dc1");
+        let mut tokens = (TOKENS as i32).to_be_bytes().to_vec();
+        for token in 0..TOKENS {
+            let token = token.to_string();
+            tokens.extend_from_slice(&(token.len() as i32).to_be_bytes());
+            tokens.extend_from_slice(token.as_bytes());
+        }
+        body.extend_from_slice(&(tokens.len() as i32).to_be_bytes());
+        body.extend(tokens);
+        body
+    }
+
+    fn status_up() -> Vec<u8> {
+        let mut body = Vec::new();
+        string(&mut body, "STATUS_CHANGE");
+        string(&mut body, "UP");
+        body.extend_from_slice(&[4, 10, 0, 0, 2]);
+        body.extend_from_slice(&9042i32.to_be_bytes());
+        body
+    }
+
+    /// Serve the bootstrap of a connection which fetches the tokens and registers for the events, then push an
+    /// event. Returns the protocol version of the connection.
+    async fn stub_node(listener: TcpListener, protocol_versions: &[&str]) -> anyhow::Result<ProtocolVersion> {
+        let (socket, _) = listener.accept().await?;
+        let mut connection = StubConnection {
+            socket,
+            version: ProtocolVersion::V4,
+            segmented: false,
+            segments: SegmentDecoder::new(),
+            frames: VecDeque::new(),
+        };
+        ensure!(connection.read().await?[4] == opcode::OPTIONS, "Expected OPTIONS");
+        connection
+            .write(&[(opcode::SUPPORTED, supported(protocol_versions))])
+            .await?;
+        let startup = connection.read().await?;
+        ensure!(startup[4] == opcode::STARTUP, "Expected STARTUP");
+        connection.version = ProtocolVersion::try_from(startup[0])?;
+        connection.write(&[(opcode::READY, Vec::new())]).await?;
+        // the next frames are wrapped into segments if the connection speaks v5
+        connection.segmented = connection.version.is_segmented();
+        let query = connection.read().await?;
+        ensure!(
+            connection.version.encode_request(&fetch_tokens_query()?)? == query,
+            "Expected the tokens QUERY in the layout of the version"
+        );
+        connection
+            .write(&[(opcode::RESULT, local_rows(connection.version))])
+            .await?;
+        let register = connection.read().await?;
+        ensure!(
+            register[0] == connection.version.request_byte() && register[4] == opcode::REGISTER,
+            "Expected REGISTER"
+        );
+        connection.write(&[(opcode::READY, Vec::new())]).await?;
+        // a void result, which isn't an event, along with the event
+        connection
+            .write(&[
+                (opcode::RESULT, 1i32.to_be_bytes().to_vec()),
+                (opcode::EVENT, status_up()),
+            ])
+            .await?;
+        Ok(connection.version)
+    }
+
+    async fn bootstrap(
+        requested: Option<ProtocolVersion>,
+        protocol_versions: &'static [&'static str],
+    ) -> ProtocolVersion {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let node = tokio::spawn(stub_node(listener, protocol_versions));
+        let mut builder = Cql::new().address(address).tokens();
+        if let Some(version) = requested {
+            builder = builder.protocol_version(version);
+        }
+        let mut cql = builder.build().await.unwrap();
+        assert_eq!(cql.take_dc().as_deref(), Some("dc1"));
+        assert_eq!(cql.take_tokens().unwrap(), (0..TOKENS).collect::<Vec<i64>>());
+        cql.register(&[EventType::StatusChange]).await.unwrap();
+        assert_eq!(
+            cql.next_event().await.unwrap(),
+            Event::StatusChange {
+                change: StatusChange::Up,
+                address: ([10, 0, 0, 2], 9042).into(),
+            }
+        );
+        assert_eq!(node.await.unwrap().unwrap(), cql.protocol_version());
+        cql.protocol_version()
+    }
+
+    #[tokio::test]
+    async fn v5_segments_round_trip() {
+        assert_eq!(
+            bootstrap(Some(ProtocolVersion::V5), &["4/v4", "5/v5"]).await,
+            ProtocolVersion::V5
+        );
+    }
+
+    #[tokio::test]
+    async fn v4_fallback() {
+        // the node doesn't support v5
+        assert_eq!(
+            bootstrap(Some(ProtocolVersion::V5), &["4/v4", "5/v5-beta"]).await,
+            ProtocolVersion::V4
+        );
+        // v5 isn't requested
+        assert_eq!(bootstrap(None, &["4/v4", "5/v5"]).await, ProtocolVersion::V4);
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::cql::{CqlError, ErrorCodes};
+use std::net::SocketAddr;
+use thiserror::Error;
+
+/// The protocol version spoken by the connections
+const PROTOCOL_VERSION: u8 = 4;
+
+/// The typed failure of a connection bootstrap, ie from the address resolution to the authentication.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "app", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConnectError {
+    /// The builder has no address to connect to
+    #[error("No address provided to connect to")]
+    MissingAddress,
+    /// The host couldn't be resolved
+    #[error("Failed to resolve {host}: {reason}")]
+    Dns {
+        /// The host
+        host: String,
+        /// The error reason
+        reason: String,
+    },
+    /// The TCP connection failed, ie the node is unreachable or refused it
+    #[error("Failed to connect to {address}: {reason}")]
+    Tcp {
+        /// The node address
+        address: SocketAddr,
+        /// The error reason
+        reason: String,
+    },
+    /// The TLS handshake failed
+    #[error("TLS handshake with {address} failed: {reason}")]
+    Tls {
+        /// The node address
+        address: SocketAddr,
+        /// The error reason
+        reason: String,
+    },
+    /// The node rejected the STARTUP request
+    #[error("{address} rejected the STARTUP: {reason}")]
+    StartupRejected {
+        /// The node address
+        address: SocketAddr,
+        /// The error reason
+        reason: String,
+    },
+    /// The node doesn't speak the protocol version of the driver
+    #[error("{address} doesn't support the protocol version v{expected}: {reason}")]
+    ProtocolMismatch {
+        /// The node address
+        address: SocketAddr,
+        /// The protocol version spoken by the driver
+        expected: u8,
+        /// The error reason
+        reason: String,
+    },
+    /// The node rejected the credentials
+    #[error("Authentication with {authenticator} failed on {address}: {reason}")]
+    Authentication {
+        /// The node address
+        address: SocketAddr,
+        /// The authenticator class name of the node
+        authenticator: String,
+        /// The error reason
+        reason: String,
+    },
+    /// The node rejected the negotiated compression
+    #[error("{address} rejected the {requested} compression (supported: {supported:?}): {reason}")]
+    Compression {
+        /// The node address
+        address: SocketAddr,
+        /// The requested compression option
+        requested: String,
+        /// The compression options supported by the node
+        supported: Vec<String>,
+        /// The error reason
+        reason: String,
+    },
+    /// The connection failed to bootstrap for another reason, ie the node lacks the Scylla sharding options
+    #[error("Failed to bootstrap the connection to {address}: {reason}")]
+    Bootstrap {
+        /// The node address
+        address: SocketAddr,
+        /// The error reason
+        reason: String,
+    },
+}
+
+impl ConnectError {
+    pub(crate) fn tcp(address: SocketAddr, error: impl ToString) -> Self {
+        Self::Tcp {
+            address,
+            reason: error.to_string(),
+        }
+    }
+    pub(crate) fn protocol_mismatch(address: SocketAddr, error: impl ToString) -> Self {
+        Self::ProtocolMismatch {
+            address,
+            expected: PROTOCOL_VERSION,
+            reason: error.to_string(),
+        }
+    }
+    pub(crate) fn bootstrap(address: SocketAddr, error: impl ToString) -> Self {
+        Self::Bootstrap {
+            address,
+            reason: error.to_string(),
+        }
+    }
+    /// Classify the error response of the OPTIONS or STARTUP request
+    pub(crate) fn rejected(address: SocketAddr, error: CqlError) -> Self {
+        match error.code {
+            ErrorCodes::ProtocolError => Self::protocol_mismatch(address, error),
+            _ => Self::StartupRejected {
+                address,
+                reason: error.to_string(),
+            },
+        }
+    }
+    /// Get the node address, if the connection reached the address resolution
+    pub fn address(&self) -> Option<SocketAddr> {
+        match self {
+            Self::MissingAddress | Self::Dns { .. } => None,
+            Self::Tcp { address, .. }
+            | Self::Tls { address, .. }
+            | Self::StartupRejected { address, .. }
+            | Self::ProtocolMismatch { address, .. }
+            | Self::Authentication { address, .. }
+            | Self::Compression { address, .. }
+            | Self::Bootstrap { address, .. } => Some(*address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn error(code: i32, message: &str) -> CqlError {
+        let mut body = code.to_be_bytes().to_vec();
+        body.extend(&(message.len() as u16).to_be_bytes());
+        body.extend(message.bytes());
+        CqlError::try_from(&body[..]).unwrap()
+    }
+
+    #[test]
+    fn rejected_startup() {
+        let address: SocketAddr = "127.0.0.1:9042".parse().unwrap();
+        let mismatch = ConnectError::rejected(address, error(0x000A, "Invalid or unsupported protocol version"));
+        assert!(matches!(mismatch, ConnectError::ProtocolMismatch { expected: 4, .. }));
+        assert_eq!(mismatch.address(), Some(address));
+        let rejected = ConnectError::rejected(address, error(0x0000, "Unknown startup option"));
+        assert_eq!(
+            rejected.to_string(),
+            "127.0.0.1:9042 rejected the STARTUP: Unknown startup option"
+        );
+        assert_eq!(ConnectError::MissingAddress.address(), None);
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the framing of a connection, which translates the frames to and from the layout of its
+//! protocol version, compresses them by the negotiated compression, and wraps them into checksummed segments once
+//! the v5 handshake is done.
+
+use crate::cql::{
+    compression::CompressionType,
+    frame::{encode_segments, ProtocolVersion, SegmentDecoder},
+};
+use std::{collections::VecDeque, convert::TryInto};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The size of the reads of the segments
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The framing of a connection
+#[derive(Debug, Default)]
+pub(crate) struct Framing {
+    version: ProtocolVersion,
+    compression: CompressionType,
+    segmented: bool,
+    segments: SegmentDecoder,
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl Framing {
+    /// Create the framing of a connection which speaks the provided protocol version
+    pub(crate) fn new(version: ProtocolVersion) -> Self {
+        Self {
+            version,
+            ..Default::default()
+        }
+    }
+    /// Get the protocol version of the connection
+    pub(crate) fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+    /// Get the framing of the frames written on the connection, while this one keeps reading its frames, ie once
+    /// the connection is split
+    pub(crate) fn writer(&self) -> Self {
+        Self {
+            version: self.version,
+            compression: self.compression,
+            segmented: self.segmented,
+            ..Default::default()
+        }
+    }
+    /// Get the compression negotiated on the connection
+    pub(crate) fn compression(&self) -> CompressionType {
+        self.compression
+    }
+    /// Compress the next request frames by the compression negotiated by the STARTUP request, and decompress the
+    /// responses by it, where the STARTUP request itself is never compressed
+    pub(crate) fn set_compression(&mut self, compression: CompressionType) {
+        self.compression = compression;
+    }
+    /// Wrap the next frames into segments if the version requires it, which is the case once the node replied to
+    /// the STARTUP request of a v5 connection
+    pub(crate) fn start_segments(&mut self) {
+        self.segmented = self.version.is_segmented();
+    }
+    /// Encode the request frame, which is built in the v4 layout, into the bytes to write on the connection
+    pub(crate) fn encode(&self, frame: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let frame = self.compression.encode_request(self.version, frame)?;
+        Ok(match self.segmented {
+            true => encode_segments(&[frame]),
+            false => frame,
+        })
+    }
+    /// Read the next response frame out of the connection, in the v4 layout and decompressed
+    pub(crate) async fn read<R: AsyncRead + Unpin>(&mut self, reader: &mut R) -> anyhow::Result<Vec<u8>> {
+        let frame = match self.segmented {
+            true => loop {
+                if let Some(frame) = self.frames.pop_front() {
+                    break frame;
+                }
+                let mut buffer = vec![0; READ_BUFFER_SIZE];
+                let read = reader.read(&mut buffer).await?;
+                if read == 0 {
+                    return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+                }
+                self.segments.extend(&buffer[..read]);
+                self.frames.extend(self.segments.decode()?);
+            },
+            false => read_frame(reader).await?,
+        };
+        self.version.decode_response(self.compression.decompress_frame(frame)?)
+    }
+}
+
+/// Read the next frame, which isn't wrapped into segments, out of the connection
+pub(crate) async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
+    let mut buffer = vec![0; 9];
+    reader.read_exact(&mut buffer).await?;
+    let body_length = i32::from_be_bytes(buffer[5..9].try_into()?);
+    buffer.resize((body_length + 9).try_into()?, 0);
+    reader.read_exact(&mut buffer[9..]).await?;
+    Ok(buffer)
+}
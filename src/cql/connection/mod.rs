@@ -1,7 +1,17 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+mod contact;
 mod cql;
+mod error;
+mod framing;
+mod stream;
+mod tls;
 mod tokens;
 
-pub use cql::{Cql, CqlBuilder};
+pub use contact::{happy_eyeballs, DEFAULT_STAGGER};
+pub use cql::{node_options, resolve, Cql, CqlBuilder};
+pub use error::ConnectError;
+pub(crate) use framing::Framing;
+pub use stream::{CqlReadHalf, CqlStream, CqlWriteHalf};
+pub use tls::TlsConfig;
@@ -0,0 +1,139 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(feature = "tls")]
+use tokio::io::{ReadHalf, WriteHalf};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+};
+#[cfg(feature = "tls")]
+use tokio_rustls::client::TlsStream;
+
+/// The socket stream of a cql connection, which is either a plain TCP stream or a TLS one.
+pub enum CqlStream {
+    /// The plain TCP stream
+    Tcp(TcpStream),
+    /// The TLS stream, which encrypts the client-to-node traffic
+    #[cfg(feature = "tls")]
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+/// The read half of a `CqlStream`
+pub enum CqlReadHalf {
+    /// The read half of the plain TCP stream
+    Tcp(OwnedReadHalf),
+    /// The read half of the TLS stream
+    #[cfg(feature = "tls")]
+    Tls(ReadHalf<Box<TlsStream<TcpStream>>>),
+}
+
+/// The write half of a `CqlStream`
+pub enum CqlWriteHalf {
+    /// The write half of the plain TCP stream
+    Tcp(OwnedWriteHalf),
+    /// The write half of the TLS stream
+    #[cfg(feature = "tls")]
+    Tls(WriteHalf<Box<TlsStream<TcpStream>>>),
+}
+
+impl CqlStream {
+    /// Split the stream into its read and write halves, which can be used concurrently
+    pub fn into_split(self) -> (CqlReadHalf, CqlWriteHalf) {
+        match self {
+            CqlStream::Tcp(stream) => {
+                let (reader, writer) = stream.into_split();
+                (CqlReadHalf::Tcp(reader), CqlWriteHalf::Tcp(writer))
+            }
+            #[cfg(feature = "tls")]
+            CqlStream::Tls(stream) => {
+                let (reader, writer) = tokio::io::split(stream);
+                (CqlReadHalf::Tls(reader), CqlWriteHalf::Tls(writer))
+            }
+        }
+    }
+    /// Check whether the stream is encrypted
+    pub fn is_tls(&self) -> bool {
+        !matches!(self, CqlStream::Tcp(_))
+    }
+}
+
+impl AsyncRead for CqlStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            CqlStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            CqlStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for CqlStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            CqlStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            CqlStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            CqlStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            CqlStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            CqlStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            CqlStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+impl AsyncRead for CqlReadHalf {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            CqlReadHalf::Tcp(reader) => Pin::new(reader).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            CqlReadHalf::Tls(reader) => Pin::new(reader).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for CqlWriteHalf {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            CqlWriteHalf::Tcp(writer) => Pin::new(writer).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            CqlWriteHalf::Tls(writer) => Pin::new(writer).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            CqlWriteHalf::Tcp(writer) => Pin::new(writer).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            CqlWriteHalf::Tls(writer) => Pin::new(writer).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            CqlWriteHalf::Tcp(writer) => Pin::new(writer).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            CqlWriteHalf::Tls(writer) => Pin::new(writer).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{error::ConnectError, stream::CqlStream};
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+#[cfg(feature = "tls")]
+use tokio_rustls::{
+    rustls::{
+        crypto::ring,
+        pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+        ClientConfig, RootCertStore,
+    },
+    TlsConnector,
+};
+#[cfg(feature = "tls")]
+use {
+    anyhow::anyhow,
+    std::{convert::TryFrom, sync::Arc},
+};
+
+/// The client certificate chain along with its private key
+#[cfg(feature = "tls")]
+type Identity = (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>);
+
+/// The TLS configuration of the connections, for the clusters with the client-to-node encryption enabled.
+///
+/// The node certificates are verified against the provided CA certificates, and the server name (SNI) defaults
+/// to the node ip address unless it's set.
+///
+/// ## Example
+/// ```no_run
+/// use scylla_rs::cql::{CqlBuilder, PasswordAuth, TlsConfig};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let tls = TlsConfig::new()
+///     .ca_pem(&std::fs::read("ca.pem")?)?
+///     .client_auth_pem(&std::fs::read("client.pem")?, &std::fs::read("client.key")?)?
+///     .server_name("scylla.local");
+/// let cql = CqlBuilder::<PasswordAuth>::new()
+///     .address("127.0.0.1:9142".parse()?)
+///     .tls(Some(tls))
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "tls")]
+#[derive(Clone)]
+pub struct TlsConfig {
+    roots: RootCertStore,
+    identity: Option<Arc<Identity>>,
+    client_config: Option<Arc<ClientConfig>>,
+    server_name: Option<String>,
+}
+
+#[cfg(feature = "tls")]
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "tls")]
+impl TlsConfig {
+    /// Create a new TLS configuration without any trusted CA certificate
+    pub fn new() -> Self {
+        Self {
+            roots: RootCertStore::empty(),
+            identity: None,
+            client_config: None,
+            server_name: None,
+        }
+    }
+    /// Use the provided rustls client config as it is, ie with a custom certificate verifier
+    pub fn from_client_config(client_config: Arc<ClientConfig>) -> Self {
+        Self {
+            client_config: Some(client_config),
+            ..Self::new()
+        }
+    }
+    /// Trust the CA certificates of the PEM file content
+    pub fn ca_pem(mut self, pem: &[u8]) -> anyhow::Result<Self> {
+        for certificate in rustls_pemfile::certs(&mut &pem[..]) {
+            self.roots.add(certificate?)?;
+        }
+        Ok(self)
+    }
+    /// Authenticate with the client certificate chain and private key of the PEM file contents, for the nodes
+    /// which require the client certificates
+    pub fn client_auth_pem(mut self, certificates_pem: &[u8], key_pem: &[u8]) -> anyhow::Result<Self> {
+        let certificates = rustls_pemfile::certs(&mut &certificates_pem[..]).collect::<Result<Vec<_>, _>>()?;
+        let key = rustls_pemfile::private_key(&mut &key_pem[..])?.ok_or_else(|| anyhow!("No private key found!"))?;
+        self.identity.replace(Arc::new((certificates, key)));
+        Ok(self)
+    }
+    /// Set the server name (SNI) which the node certificates are verified against
+    pub fn server_name<T: Into<String>>(mut self, server_name: T) -> Self {
+        self.server_name.replace(server_name.into());
+        self
+    }
+    fn connector(&self) -> anyhow::Result<TlsConnector> {
+        if let Some(client_config) = self.client_config.as_ref() {
+            return Ok(TlsConnector::from(client_config.clone()));
+        }
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(self.roots.clone());
+        let client_config = match self.identity.as_deref() {
+            Some((certificates, key)) => builder.with_client_auth_cert(certificates.clone(), key.clone_key())?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(TlsConnector::from(Arc::new(client_config)))
+    }
+    /// Perform the TLS handshake over the TCP stream
+    pub(crate) async fn connect(&self, address: SocketAddr, stream: TcpStream) -> Result<CqlStream, ConnectError> {
+        let tls_error = |reason: String| ConnectError::Tls { address, reason };
+        let server_name = match self.server_name.as_ref() {
+            Some(server_name) => ServerName::try_from(server_name.clone()).map_err(|e| tls_error(e.to_string()))?,
+            None => ServerName::IpAddress(address.ip().into()),
+        };
+        let connector = self.connector().map_err(|e| tls_error(e.to_string()))?;
+        let stream = connector
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| tls_error(e.to_string()))?;
+        Ok(CqlStream::Tls(Box::new(stream)))
+    }
+}
+
+/// The TLS configuration of the connections, which can't be created as the `tls` feature is disabled.
+#[cfg(not(feature = "tls"))]
+#[derive(Clone, Debug)]
+pub enum TlsConfig {}
+
+#[cfg(not(feature = "tls"))]
+impl TlsConfig {
+    pub(crate) async fn connect(&self, _address: SocketAddr, _stream: TcpStream) -> Result<CqlStream, ConnectError> {
+        match *self {}
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the pagination cursors, which wrap the paging state of a query into an authenticated
+//! (and optionally encrypted) token, so it can be handed to untrusted clients, ie by a cursor based REST API.
+
+use super::{frame::consistency::Consistency, statement::Fnv64, StatementIdAlgorithm};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::convert::{TryFrom, TryInto};
+use thiserror::Error;
+
+/// The version of the cursor layout
+const CURSOR_VERSION: u8 = 1;
+/// The mode of the signed cursors
+const SIGNED: u8 = 0;
+/// The mode of the encrypted cursors
+const ENCRYPTED: u8 = 1;
+/// The length of the cursor header, ie its version and mode
+const HEADER_LEN: usize = 2;
+/// The length of the cursor payload, without its paging state
+const PAYLOAD_LEN: usize = 8 + 2;
+/// The context of the encryption key derivation
+const ENCRYPTION_CONTEXT: &[u8] = b"scylla-rs paging cursor encryption";
+
+/// The errors of the cursor validation, which never reveal the content of the cursor
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorError {
+    /// The cursor isn't valid base64, or its layout is invalid
+    #[error("Malformed cursor")]
+    Malformed,
+    /// The cursor wasn't issued with this key, or it was tampered with
+    #[error("Invalid cursor signature")]
+    InvalidSignature,
+    /// The cursor was issued for another statement
+    #[error("The cursor was issued for another statement")]
+    StatementMismatch,
+    /// The cursor was issued with another consistency level
+    #[error("The cursor was issued with consistency {0}")]
+    ConsistencyMismatch(Consistency),
+}
+
+/// The secret key which signs, and optionally encrypts, the pagination cursors. The same key must be used by every
+/// instance which serves the cursors, and rotating it invalidates the issued ones.
+#[derive(Clone)]
+pub struct CursorKey {
+    signing: hmac::Key,
+    encryption: Option<LessSafeKey>,
+}
+
+impl CursorKey {
+    /// Create a key which signs the cursors with HMAC-SHA256, so the clients can't forge them, but can read their
+    /// paging state
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            signing: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            encryption: None,
+        }
+    }
+    /// Create a key which encrypts the cursors with ChaCha20-Poly1305, so the clients can neither forge nor read
+    /// them
+    pub fn encrypted(secret: &[u8]) -> Self {
+        let signing = hmac::Key::new(hmac::HMAC_SHA256, secret);
+        let derived = hmac::sign(&signing, ENCRYPTION_CONTEXT);
+        let encryption = UnboundKey::new(&CHACHA20_POLY1305, derived.as_ref()).expect("Invalid encryption key length");
+        Self {
+            signing,
+            encryption: Some(LessSafeKey::new(encryption)),
+        }
+    }
+}
+
+/// The paging state of a query along with the fingerprint of its statement and its consistency, which are
+/// validated when the cursor is used by the next request.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::{Consistency, CursorKey, PagingCursor};
+///
+/// let key = CursorKey::encrypted(b"a secret shared by the api servers");
+/// let statement = "SELECT * FROM shop.orders WHERE customer = ?";
+/// let cursor = PagingCursor::new(statement, Consistency::One, vec![1, 2, 3]).encode(&key)?;
+/// // on the next request
+/// let paging_state = PagingCursor::validate(&cursor, &key, statement, Consistency::One)?;
+/// assert_eq!(paging_state, vec![1, 2, 3]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PagingCursor {
+    statement_id: u64,
+    consistency: Consistency,
+    paging_state: Vec<u8>,
+}
+
+impl PagingCursor {
+    /// Create the cursor of the statement page
+    pub fn new(statement: &str, consistency: Consistency, paging_state: Vec<u8>) -> Self {
+        Self {
+            statement_id: Fnv64::statement_id(statement),
+            consistency,
+            paging_state,
+        }
+    }
+    /// Get the fingerprint of the statement
+    pub fn statement_id(&self) -> u64 {
+        self.statement_id
+    }
+    /// Get the consistency of the query
+    pub fn consistency(&self) -> Consistency {
+        self.consistency
+    }
+    /// Get the paging state of the query
+    pub fn paging_state(&self) -> &[u8] {
+        &self.paging_state
+    }
+    /// Take the paging state of the query, ie to bind it to the next request
+    pub fn into_paging_state(self) -> Vec<u8> {
+        self.paging_state
+    }
+    /// Encode the cursor into an url safe token
+    pub fn encode(&self, key: &CursorKey) -> anyhow::Result<String> {
+        let mut payload = Vec::with_capacity(PAYLOAD_LEN + self.paging_state.len());
+        payload.extend(&self.statement_id.to_be_bytes());
+        payload.extend(&(self.consistency as u16).to_be_bytes());
+        payload.extend(&self.paging_state);
+        let mut cursor = vec![CURSOR_VERSION];
+        match &key.encryption {
+            Some(encryption) => {
+                cursor.push(ENCRYPTED);
+                let mut nonce = [0; NONCE_LEN];
+                SystemRandom::new()
+                    .fill(&mut nonce)
+                    .map_err(|_| anyhow::anyhow!("Cannot generate the cursor nonce"))?;
+                encryption
+                    .seal_in_place_append_tag(
+                        Nonce::assume_unique_for_key(nonce),
+                        Aad::from(&cursor[..]),
+                        &mut payload,
+                    )
+                    .map_err(|_| anyhow::anyhow!("Cannot encrypt the cursor"))?;
+                cursor.extend(&nonce);
+                cursor.extend(payload);
+            }
+            None => {
+                cursor.push(SIGNED);
+                cursor.extend(payload);
+                let tag = hmac::sign(&key.signing, &cursor);
+                cursor.extend(tag.as_ref());
+            }
+        }
+        Ok(URL_SAFE_NO_PAD.encode(cursor))
+    }
+    /// Decode the cursor token, and verify it was issued with the key
+    pub fn decode(cursor: &str, key: &CursorKey) -> Result<Self, CursorError> {
+        let mut cursor = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| CursorError::Malformed)?;
+        if cursor.len() < HEADER_LEN || cursor[0] != CURSOR_VERSION {
+            return Err(CursorError::Malformed);
+        }
+        let payload = match (cursor[1], &key.encryption) {
+            (ENCRYPTED, Some(encryption)) => {
+                let (header, rest) = cursor.split_at_mut(HEADER_LEN);
+                if rest.len() < NONCE_LEN {
+                    return Err(CursorError::Malformed);
+                }
+                let (nonce, sealed) = rest.split_at_mut(NONCE_LEN);
+                let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| CursorError::Malformed)?;
+                &*encryption
+                    .open_in_place(nonce, Aad::from(&header[..]), sealed)
+                    .map_err(|_| CursorError::InvalidSignature)?
+            }
+            (SIGNED, None) => {
+                let tag_len = hmac::HMAC_SHA256.digest_algorithm().output_len();
+                if cursor.len() < HEADER_LEN + tag_len {
+                    return Err(CursorError::Malformed);
+                }
+                let (signed, tag) = cursor.split_at(cursor.len() - tag_len);
+                hmac::verify(&key.signing, signed, tag).map_err(|_| CursorError::InvalidSignature)?;
+                &signed[HEADER_LEN..]
+            }
+            // the cursor was issued by another kind of key
+            _ => return Err(CursorError::InvalidSignature),
+        };
+        Self::try_from(payload)
+    }
+    /// Decode the cursor token, and verify it was issued with the key, for the statement and the consistency.
+    /// Returns its paging state.
+    pub fn validate(
+        cursor: &str,
+        key: &CursorKey,
+        statement: &str,
+        consistency: Consistency,
+    ) -> Result<Vec<u8>, CursorError> {
+        let cursor = Self::decode(cursor, key)?;
+        if cursor.statement_id != Fnv64::statement_id(statement) {
+            return Err(CursorError::StatementMismatch);
+        }
+        if cursor.consistency != consistency {
+            return Err(CursorError::ConsistencyMismatch(cursor.consistency));
+        }
+        Ok(cursor.paging_state)
+    }
+}
+
+impl TryFrom<&[u8]> for PagingCursor {
+    type Error = CursorError;
+
+    fn try_from(payload: &[u8]) -> Result<Self, Self::Error> {
+        if payload.len() < PAYLOAD_LEN {
+            return Err(CursorError::Malformed);
+        }
+        let statement_id = u64::from_be_bytes(payload[0..8].try_into().map_err(|_| CursorError::Malformed)?);
+        let consistency = Consistency::try_from(&payload[8..10]).map_err(|_| CursorError::Malformed)?;
+        Ok(Self {
+            statement_id,
+            consistency,
+            paging_state: payload[PAYLOAD_LEN..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paging_cursors() {
+        let statement = "SELECT * FROM shop.orders WHERE customer = ?";
+        let page = PagingCursor::new(statement, Consistency::LocalQuorum, vec![7; 40]);
+        for key in [CursorKey::new(b"secret"), CursorKey::encrypted(b"secret")] {
+            let cursor = page.encode(&key).unwrap();
+            assert_eq!(PagingCursor::decode(&cursor, &key), Ok(page.clone()));
+            assert_eq!(
+                PagingCursor::validate(&cursor, &key, "SELECT * FROM shop.orders", Consistency::LocalQuorum),
+                Err(CursorError::StatementMismatch)
+            );
+            assert_eq!(
+                PagingCursor::validate(&cursor, &key, statement, Consistency::One),
+                Err(CursorError::ConsistencyMismatch(Consistency::LocalQuorum))
+            );
+            // flip a bit of the payload
+            let mut tampered = URL_SAFE_NO_PAD.decode(&cursor).unwrap();
+            tampered[HEADER_LEN + 16] ^= 1;
+            assert_eq!(
+                PagingCursor::decode(&URL_SAFE_NO_PAD.encode(tampered), &key),
+                Err(CursorError::InvalidSignature)
+            );
+            assert_eq!(
+                PagingCursor::decode(&cursor, &CursorKey::new(b"other")),
+                Err(CursorError::InvalidSignature)
+            );
+            assert_eq!(PagingCursor::decode("not a cursor", &key), Err(CursorError::Malformed));
+        }
+        // the encrypted cursors don't reveal their paging state
+        let cursor = page.encode(&CursorKey::encrypted(b"secret")).unwrap();
+        let decoded = URL_SAFE_NO_PAD.decode(cursor).unwrap();
+        assert!(!decoded.windows(8).any(|window| window == [7; 8]));
+    }
+}
@@ -0,0 +1,281 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the dynamic requests, which turn the raw DML statements into query frames, and infer
+//! the routing token out of the bound partition key values.
+
+use super::{
+    frame::{
+        consistency::Consistency,
+        encoder::{ColumnEncoder, BE_NULL_BYTES_LEN, BE_UNSET_BYTES_LEN},
+        query::Query,
+        Statements, Values,
+    },
+    murmur3::{Murmur3Partitioner, Partitioner},
+    statement::{batch_statements, marker_count, StatementKind, TokenIndexes},
+};
+use anyhow::{anyhow, bail, ensure};
+
+/// A request built out of a raw DML statement, along with the bind marker indexes of its partition key
+/// columns (if they could be inferred).
+#[derive(Debug, Clone)]
+pub struct DynamicRequest {
+    kind: StatementKind,
+    statement: String,
+    token_indexes: Option<Vec<usize>>,
+}
+
+impl DynamicRequest {
+    fn new(statement: &str, expected: StatementKind, partition_key: &[&str]) -> anyhow::Result<Self> {
+        let kind = StatementKind::of(statement).ok_or_else(|| anyhow!("Not a DML statement: '{}'", statement))?;
+        ensure!(
+            kind == expected,
+            "Expected a {:?} statement, found a {:?} statement: '{}'",
+            expected,
+            kind,
+            statement
+        );
+        let token_indexes = if partition_key.is_empty() {
+            None
+        } else if kind == StatementKind::Batch {
+            batch_token_indexes(statement, partition_key)?
+        } else {
+            statement.token_indexes(partition_key)
+        };
+        Ok(Self {
+            kind,
+            statement: statement.to_string(),
+            token_indexes,
+        })
+    }
+    /// Get the statement kind
+    pub fn kind(&self) -> StatementKind {
+        self.kind
+    }
+    /// Get the statement
+    pub fn statement(&self) -> &str {
+        &self.statement
+    }
+    /// Get the inferred bind marker indexes of the partition key columns
+    pub fn token_indexes(&self) -> Option<&[usize]> {
+        self.token_indexes.as_deref()
+    }
+    /// Start binding the values of the request
+    pub fn consistency(&self, consistency: Consistency) -> DynamicValues<'_> {
+        DynamicValues {
+            request: self,
+            consistency,
+            values: Vec::new(),
+        }
+    }
+}
+
+/// Get the token indexes of the first inner statement of the batch which binds the partition key
+fn batch_token_indexes(statement: &str, partition_key: &[&str]) -> anyhow::Result<Option<Vec<usize>>> {
+    let statements = batch_statements(statement).ok_or_else(|| anyhow!("Invalid batch statement: '{}'", statement))?;
+    let mut offset = 0;
+    for statement in statements {
+        if let Some(indexes) = statement.token_indexes(partition_key) {
+            return Ok(Some(indexes.into_iter().map(|index| offset + index).collect()));
+        }
+        offset += marker_count(statement).unwrap_or_default();
+    }
+    Ok(None)
+}
+
+/// The values bound to a dynamic request
+pub struct DynamicValues<'a> {
+    request: &'a DynamicRequest,
+    consistency: Consistency,
+    values: Vec<Vec<u8>>,
+}
+
+impl<'a> Values for DynamicValues<'a> {
+    type Return = Self;
+    fn value<V: ColumnEncoder + ?Sized>(mut self, value: &V) -> Self {
+        self.values.push(value.encode_new());
+        self
+    }
+    fn unset_value(mut self) -> Self {
+        self.values.push(BE_UNSET_BYTES_LEN.to_vec());
+        self
+    }
+    fn null_value(mut self) -> Self {
+        self.values.push(BE_NULL_BYTES_LEN.to_vec());
+        self
+    }
+}
+
+impl<'a> DynamicValues<'a> {
+    /// Compute the token out of the bound partition key values, or None if the token indexes couldn't be
+    /// inferred, or if any partition key value is unset or null.
+    pub fn token(&self) -> Option<i64> {
+        self.token_with(&Murmur3Partitioner)
+    }
+    /// Compute the token out of the bound partition key values with the partitioner
+    pub fn token_with<P: Partitioner + ?Sized>(&self, partitioner: &P) -> Option<i64> {
+        let indexes = self.request.token_indexes.as_ref()?;
+        let mut components = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let value = self.values.get(*index)?;
+            if i32::from_be_bytes([value[0], value[1], value[2], value[3]]) < 0 {
+                return None;
+            }
+            components.push(value);
+        }
+        match components.as_slice() {
+            [value] => Some(partitioner.token(&value[4..])),
+            _ => {
+                // the composite partition key is encoded as <len: u16><value><0> per component
+                let mut buffer = Vec::new();
+                for value in components {
+                    buffer.extend_from_slice(&value[2..]);
+                    buffer.push(0);
+                }
+                Some(partitioner.token(&buffer))
+            }
+        }
+    }
+    /// Build the query frame, along with the token (if any) to route it with
+    pub fn build(self) -> anyhow::Result<(Option<i64>, Query)> {
+        let token = self.token();
+        let builder = Query::new()
+            .statement(&self.request.statement)
+            .consistency(self.consistency);
+        let query = match self.values.split_first() {
+            None => builder.build()?,
+            Some((first, rest)) => rest
+                .iter()
+                .fold(builder.value(&Encoded(first)), |builder, value| {
+                    builder.value(&Encoded(value))
+                })
+                .build()?,
+        };
+        Ok((token, query))
+    }
+}
+
+/// An already encoded value
+struct Encoded<'a>(&'a [u8]);
+
+impl<'a> ColumnEncoder for Encoded<'a> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.0);
+    }
+}
+
+/// Turns a SELECT statement into a dynamic request.
+pub trait AsDynamicSelectRequest {
+    /// Build the dynamic request, inferring the token indexes of the provided partition key columns
+    fn as_select_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest>;
+}
+
+/// Turns an INSERT statement into a dynamic request.
+pub trait AsDynamicInsertRequest {
+    /// Build the dynamic request, inferring the token indexes of the provided partition key columns
+    fn as_insert_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest>;
+}
+
+/// Turns an UPDATE statement into a dynamic request.
+pub trait AsDynamicUpdateRequest {
+    /// Build the dynamic request, inferring the token indexes of the provided partition key columns
+    fn as_update_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest>;
+}
+
+/// Turns a DELETE statement into a dynamic request.
+pub trait AsDynamicDeleteRequest {
+    /// Build the dynamic request, inferring the token indexes of the provided partition key columns
+    fn as_delete_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest>;
+}
+
+/// Turns a `BEGIN .. APPLY BATCH` statement into a dynamic request.
+pub trait AsDynamicBatchRequest {
+    /// Build the dynamic request, inferring the token indexes of the provided partition key columns out of
+    /// the first inner statement which binds them
+    fn as_batch_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest>;
+}
+
+impl AsDynamicSelectRequest for str {
+    fn as_select_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest> {
+        DynamicRequest::new(self, StatementKind::Select, partition_key)
+    }
+}
+
+impl AsDynamicInsertRequest for str {
+    fn as_insert_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest> {
+        DynamicRequest::new(self, StatementKind::Insert, partition_key)
+    }
+}
+
+impl AsDynamicUpdateRequest for str {
+    fn as_update_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest> {
+        DynamicRequest::new(self, StatementKind::Update, partition_key)
+    }
+}
+
+impl AsDynamicDeleteRequest for str {
+    fn as_delete_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest> {
+        DynamicRequest::new(self, StatementKind::Delete, partition_key)
+    }
+}
+
+impl AsDynamicBatchRequest for str {
+    fn as_batch_request(&self, partition_key: &[&str]) -> anyhow::Result<DynamicRequest> {
+        if batch_statements(self).is_none() {
+            bail!("Invalid batch statement: '{}'", self);
+        }
+        DynamicRequest::new(self, StatementKind::Batch, partition_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::TokenEncoder;
+
+    #[test]
+    fn dynamic_requests() {
+        let request = "INSERT INTO ks.t (b, v, a) VALUES (?, ?, ?)"
+            .as_insert_request(&["a"])
+            .unwrap();
+        assert_eq!(request.token_indexes(), Some(&[2][..]));
+        let (token, _) = request
+            .consistency(Consistency::One)
+            .value(&1i32)
+            .value(&"v")
+            .value(&"key")
+            .build()
+            .unwrap();
+        assert_eq!(token, Some("key".get_token()));
+        // composite partition key
+        let request = "UPDATE ks.t SET v = ? WHERE b = ? AND a = ?"
+            .as_update_request(&["a", "b"])
+            .unwrap();
+        let values = request
+            .consistency(Consistency::One)
+            .value(&"v")
+            .value(&2i32)
+            .value(&"a");
+        assert_eq!(values.token(), Some("a".chain_token(&2i32).finish()));
+        // null partition key values aren't routable
+        let values = request
+            .consistency(Consistency::One)
+            .value(&"v")
+            .null_value()
+            .value(&"a");
+        assert_eq!(values.token(), None);
+        // mismatching statements
+        assert!("SELECT * FROM ks.t".as_delete_request(&[]).is_err());
+        assert!("TRUNCATE ks.t".as_select_request(&[]).is_err());
+        // the batch markers of the preceding statements are counted
+        let request = "BEGIN UNLOGGED BATCH USING TIMESTAMP 1 \
+                       UPDATE ks.c SET n = ? WHERE id = 'x'; INSERT INTO ks.t (a, v) VALUES (?, ?); \
+                       APPLY BATCH"
+            .as_batch_request(&["a"])
+            .unwrap();
+        assert_eq!(request.token_indexes(), Some(&[1][..]));
+        assert!("BEGIN BATCH INSERT INTO ks.t (a) VALUES (?)"
+            .as_batch_request(&["a"])
+            .is_err());
+    }
+}
@@ -9,7 +9,6 @@ use super::decoder::{bytes, Decoder, Frame};
 
 /// The Autentication Challenge structure with the token field.
 pub(crate) struct AuthChallenge {
-    #[allow(unused)]
     token: Option<Vec<u8>>,
 }
 
@@ -18,6 +17,10 @@ impl AuthChallenge {
     pub(crate) fn new(decoder: &Decoder) -> anyhow::Result<Self> {
         Self::try_from(decoder.body()?)
     }
+    /// Take the challenge token.
+    pub(crate) fn into_token(self) -> Option<Vec<u8>> {
+        self.token
+    }
 }
 
 impl TryFrom<&[u8]> for AuthChallenge {
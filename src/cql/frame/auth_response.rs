@@ -3,17 +3,91 @@
 
 //! This module implements the response part of the challenge–response authentication.
 
-use super::opcode::AUTH_RESPONSE;
+use super::{decoder::bytes, opcode::AUTH_RESPONSE};
 use crate::cql::compression::{Compression, MyCompression};
-use std::convert::TryInto;
+use std::{convert::TryInto, future::Future, pin::Pin};
 
 /// Blanket cql frame header for AUTH_RESPONSE frame.
 const AUTH_RESPONSE_HEADER: &'static [u8] = &[4, 0, 0, 0, AUTH_RESPONSE, 0, 0, 0, 0];
 
+/// The future of a SASL step, which resolves to the token of the next AUTH_RESPONSE frame
+pub type AuthFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<Option<Vec<u8>>>> + Send + 'a>>;
+
 /// The Authenticator structure with the token field.
+///
+/// The authenticators which only send a single token (ie `PasswordAuth`) implement `token`, while the SASL
+/// mechanisms which exchange several challenges with the node (ie Kerberos or LDAP) implement `session`, which
+/// drives the challenge–response loop of a single connection.
+///
+/// ## Example
+/// ```
+/// use scylla_rs::cql::{AuthFuture, Authenticator, SaslSession};
+///
+/// /// Answers a single challenge of the node with a signed nonce
+/// #[derive(Clone, Default)]
+/// struct NonceAuth {
+///     key: Vec<u8>,
+/// }
+///
+/// struct NonceSession {
+///     key: Vec<u8>,
+/// }
+///
+/// impl Authenticator for NonceAuth {
+///     fn token(&self) -> Vec<u8> {
+///         unimplemented!("NonceAuth only authenticates through its session")
+///     }
+///     fn session(&self, _authenticator: &str) -> anyhow::Result<Box<dyn SaslSession>> {
+///         Ok(Box::new(NonceSession { key: self.key.clone() }))
+///     }
+/// }
+///
+/// impl SaslSession for NonceSession {
+///     fn initial_response(&mut self) -> AuthFuture<'_> {
+///         Box::pin(async { Ok(Some(b"nonce-auth".to_vec())) })
+///     }
+///     fn evaluate_challenge(&mut self, challenge: Option<Vec<u8>>) -> AuthFuture<'_> {
+///         Box::pin(async move {
+///             let nonce = challenge.ok_or_else(|| anyhow::anyhow!("Missing nonce"))?;
+///             Ok(Some(nonce.iter().zip(self.key.iter().cycle()).map(|(n, k)| n ^ k).collect()))
+///         })
+///     }
+/// }
+/// ```
 pub trait Authenticator: Clone + Default {
     /// Get the token in the Authenticator.
     fn token(&self) -> Vec<u8>;
+    /// Start the SASL session of a connection, with the authenticator class requested by the node (ie
+    /// `org.apache.cassandra.auth.PasswordAuthenticator`). The default session sends the token once, and rejects
+    /// any challenge.
+    fn session(&self, _authenticator: &str) -> anyhow::Result<Box<dyn SaslSession>> {
+        Ok(Box::new(TokenSession(Some(bytes(&self.token())?))))
+    }
+}
+
+/// The challenge–response exchange of an authenticator over a single connection
+pub trait SaslSession: Send {
+    /// Get the token of the first AUTH_RESPONSE frame
+    fn initial_response(&mut self) -> AuthFuture<'_>;
+    /// Evaluate the token of an AUTH_CHALLENGE frame, and get the token of the next AUTH_RESPONSE frame
+    fn evaluate_challenge(&mut self, challenge: Option<Vec<u8>>) -> AuthFuture<'_>;
+    /// Verify the final token of the AUTH_SUCCESS frame, if the mechanism requires it
+    fn on_success(&mut self, _token: Option<Vec<u8>>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// The session of the authenticators which send a single token
+struct TokenSession(Option<Option<Vec<u8>>>);
+
+impl SaslSession for TokenSession {
+    fn initial_response(&mut self) -> AuthFuture<'_> {
+        let token = self.0.take();
+        Box::pin(async move { token.ok_or_else(|| anyhow::anyhow!("The token is already sent")) })
+    }
+    fn evaluate_challenge(&mut self, _challenge: Option<Vec<u8>>) -> AuthFuture<'_> {
+        Box::pin(async { Err(anyhow::anyhow!("Unsupported Auth Challenge")) })
+    }
 }
 #[derive(Clone, Default)]
 /// The unit structure used for letting all users be autenticated.
@@ -44,6 +118,10 @@ impl PasswordAuth {
     pub fn new(user: String, pass: String) -> Self {
         Self { user, pass }
     }
+    /// Get the user of the account.
+    pub fn user(&self) -> &str {
+        &self.user
+    }
 }
 
 impl Authenticator for PasswordAuth {
@@ -70,11 +148,23 @@ impl AuthResponse {
         AuthResponse(buffer)
     }
     /// Update the response token to be the token from autenticator.
+    #[allow(unused)]
     pub(crate) fn token(mut self, authenticator: &impl Authenticator) -> Self {
         let token = authenticator.token();
         self.0.extend(token);
         self
     }
+    /// Update the response token to be the token of a SASL step, where None is encoded as a null token.
+    pub(crate) fn bytes(mut self, token: Option<&[u8]>) -> Self {
+        match token {
+            Some(token) => {
+                self.0.extend(&i32::to_be_bytes(token.len() as i32));
+                self.0.extend(token);
+            }
+            None => self.0.extend(&i32::to_be_bytes(-1)),
+        }
+        self
+    }
     /// Build a response frame with a assigned compression type.
     pub(crate) fn build(mut self, compression: impl Compression) -> anyhow::Result<Self> {
         // apply compression flag(if any to the header)
@@ -83,3 +173,21 @@ impl AuthResponse {
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn token_session() {
+        let auth = PasswordAuth::new("user".to_owned(), "pass".to_owned());
+        let mut session = auth.session("org.apache.cassandra.auth.PasswordAuthenticator").unwrap();
+        let token = session.initial_response().await.unwrap();
+        let response = AuthResponse::new().bytes(token.as_deref());
+        assert_eq!(response.0[AUTH_RESPONSE_HEADER.len()..], auth.token()[..]);
+        assert!(session.evaluate_challenge(None).await.is_err());
+        assert!(session.initial_response().await.is_err());
+        let null = AuthResponse::new().bytes(None);
+        assert_eq!(null.0[AUTH_RESPONSE_HEADER.len()..], [255, 255, 255, 255]);
+    }
+}
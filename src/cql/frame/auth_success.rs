@@ -20,6 +20,10 @@ impl AuthSuccess {
     pub fn token(&self) -> Option<&Vec<u8>> {
         self.token.as_ref()
     }
+    /// Take the autentication token.
+    pub fn into_token(self) -> Option<Vec<u8>> {
+        self.token
+    }
 }
 
 impl TryFrom<&[u8]> for AuthSuccess {
@@ -7,10 +7,11 @@ use super::{
     batchflags::*,
     consistency::Consistency,
     encoder::{ColumnEncoder, BE_8_BYTES_LEN, BE_NULL_BYTES_LEN, BE_UNSET_BYTES_LEN},
+    header::TRACING,
     opcode::BATCH,
     Statements, Values, MD5_BE_LENGTH,
 };
-use crate::cql::compression::{Compression, MyCompression};
+use crate::cql::compression::{CompressionType, FrameCompression};
 
 /// Blanket cql frame header for BATCH frame.
 const BATCH_HEADER: &'static [u8] = &[4, 0, 0, 0, BATCH, 0, 0, 0, 0];
@@ -47,11 +48,38 @@ pub enum BatchTypes {
 /// ```
 pub struct BatchBuilder<Type: Copy + Into<u8>, Stage> {
     buffer: Vec<u8>,
+    compression: FrameCompression,
+    value_kinds: ValueKinds,
     query_count: u16,
     batch_type: Type,
     stage: Stage,
 }
 
+/// The kinds of the values bound so far, as the named and positional values can't be mixed within a batch
+#[derive(Copy, Clone, Default)]
+struct ValueKinds {
+    named: bool,
+    positional: bool,
+}
+
+impl ValueKinds {
+    /// Add the names flag if the values are named
+    fn flags(self, flags: u8) -> u8 {
+        if self.named {
+            flags | WITH_NAMES
+        } else {
+            flags
+        }
+    }
+    fn ensure_unmixed(self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !(self.named && self.positional),
+            "The batch mixes named and positional values"
+        );
+        Ok(())
+    }
+}
+
 /// Gating type for batch headers
 pub struct BatchHeader;
 
@@ -119,6 +147,8 @@ impl BatchBuilder<BatchTypeUnset, BatchHeader> {
         buffer.extend_from_slice(&BATCH_HEADER);
         BatchBuilder {
             buffer,
+            compression: FrameCompression::default(),
+            value_kinds: ValueKinds::default(),
             query_count: 0,
             batch_type: BatchTypeUnset,
             stage: BatchType,
@@ -130,6 +160,8 @@ impl BatchBuilder<BatchTypeUnset, BatchHeader> {
         buffer.extend_from_slice(&BATCH_HEADER);
         BatchBuilder {
             buffer,
+            compression: FrameCompression::default(),
+            value_kinds: ValueKinds::default(),
             query_count: 0,
             batch_type: BatchTypeUnset,
             stage: BatchType,
@@ -144,6 +176,8 @@ impl BatchBuilder<BatchTypeUnset, BatchType> {
         self.buffer.extend(&[batch_type.into(), 0, 0]);
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type,
             stage: BatchStatementOrId,
@@ -155,6 +189,8 @@ impl BatchBuilder<BatchTypeUnset, BatchType> {
         self.buffer.extend(&[0, 0, 0]);
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: BatchTypeLogged,
             stage: BatchStatementOrId,
@@ -166,6 +202,8 @@ impl BatchBuilder<BatchTypeUnset, BatchType> {
         self.buffer.extend(&[1, 0, 0]);
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: BatchTypeUnlogged,
             stage: BatchStatementOrId,
@@ -177,6 +215,8 @@ impl BatchBuilder<BatchTypeUnset, BatchType> {
         self.buffer.extend(&[2, 0, 0]);
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: BatchTypeCounter,
             stage: BatchStatementOrId,
@@ -198,6 +238,8 @@ impl<Type: Copy + Into<u8>> Statements for BatchBuilder<Type, BatchStatementOrId
         self.buffer.extend(&[0, 0]);
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: self.batch_type,
             stage: BatchValues { value_count: 0, index },
@@ -215,6 +257,8 @@ impl<Type: Copy + Into<u8>> Statements for BatchBuilder<Type, BatchStatementOrId
         self.buffer.extend(&[0, 0]);
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: self.batch_type,
             stage: BatchValues { value_count: 0, index },
@@ -225,23 +269,55 @@ impl<Type: Copy + Into<u8>> Statements for BatchBuilder<Type, BatchStatementOrId
 impl<Type: Copy + Into<u8>> Values for BatchBuilder<Type, BatchValues> {
     type Return = BatchBuilder<Type, BatchValues>;
     /// Set the value in the Batch frame.
-    fn value<V: ColumnEncoder>(mut self, value: &V) -> Self {
+    fn value<V: ColumnEncoder + ?Sized>(mut self, value: &V) -> Self {
         value.encode(&mut self.buffer);
         self.stage.value_count += 1;
+        self.value_kinds.positional = true;
         self
     }
     /// Set the value to be unset in the Batch frame.
     fn unset_value(mut self) -> Self {
         self.buffer.extend(&BE_UNSET_BYTES_LEN);
         self.stage.value_count += 1;
+        self.value_kinds.positional = true;
         self
     }
     /// Set the value to be null in the Batch frame.
     fn null_value(mut self) -> Self {
         self.buffer.extend(&BE_NULL_BYTES_LEN);
         self.stage.value_count += 1;
+        self.value_kinds.positional = true;
+        self
+    }
+}
+
+impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchValues> {
+    /// Set the value of the named bind marker (ie `:name`) in the Batch frame.
+    ///
+    /// Note: once a named value is set, all the values of all the batch statements must be named.
+    pub fn named_value<V: ColumnEncoder + ?Sized>(mut self, name: &str, value: &V) -> Self {
+        self.push_name(name);
+        value.encode(&mut self.buffer);
         self
     }
+    /// Set the value of the named bind marker to be unset in the Batch frame.
+    pub fn named_unset_value(mut self, name: &str) -> Self {
+        self.push_name(name);
+        self.buffer.extend(&BE_UNSET_BYTES_LEN);
+        self
+    }
+    /// Set the value of the named bind marker to be null in the Batch frame.
+    pub fn named_null_value(mut self, name: &str) -> Self {
+        self.push_name(name);
+        self.buffer.extend(&BE_NULL_BYTES_LEN);
+        self
+    }
+    fn push_name(&mut self, name: &str) {
+        self.buffer.extend(&u16::to_be_bytes(name.len() as u16));
+        self.buffer.extend(name.bytes());
+        self.stage.value_count += 1;
+        self.value_kinds.named = true;
+    }
 }
 
 impl<Type: Copy + Into<u8>> Statements for BatchBuilder<Type, BatchValues> {
@@ -256,11 +332,13 @@ impl<Type: Copy + Into<u8>> Statements for BatchBuilder<Type, BatchValues> {
         self.buffer.extend(&i32::to_be_bytes(statement.len() as i32));
         self.buffer.extend(statement.bytes());
         self.query_count += 1; // update querycount
-                               // pad zero value_count for the query
-        self.buffer.extend(&[0, 0]);
         let index = self.buffer.len();
+        // pad zero value_count for the query
+        self.buffer.extend(&[0, 0]);
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: self.batch_type,
             stage: BatchValues { value_count: 0, index },
@@ -276,11 +354,13 @@ impl<Type: Copy + Into<u8>> Statements for BatchBuilder<Type, BatchValues> {
         self.buffer.extend(&MD5_BE_LENGTH);
         self.buffer.extend(id);
         self.query_count += 1;
+        let index = self.buffer.len();
         // pad zero value_count for the query
         self.buffer.extend(&[0, 0]);
-        let index = self.buffer.len();
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: self.batch_type,
             stage: BatchValues { value_count: 0, index },
@@ -296,6 +376,8 @@ impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchValues> {
         self.buffer.extend(&u16::to_be_bytes(consistency as u16));
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: self.batch_type,
             stage: BatchFlags,
@@ -307,10 +389,12 @@ impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchFlags> {
     /// Set the serial consistency in the Batch frame.
     pub fn serial_consistency(mut self, consistency: Consistency) -> BatchBuilder<Type, BatchTimestamp> {
         // add serial_consistency byte for batch flags
-        self.buffer.push(SERIAL_CONSISTENCY);
+        self.buffer.push(self.value_kinds.flags(SERIAL_CONSISTENCY));
         self.buffer.extend(&u16::to_be_bytes(consistency as u16));
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: self.batch_type,
             stage: BatchTimestamp,
@@ -319,11 +403,13 @@ impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchFlags> {
     /// Set the timestamp of the Batch frame.
     pub fn timestamp(mut self, timestamp: i64) -> BatchBuilder<Type, BatchBuild> {
         // add timestamp byte for batch flags
-        self.buffer.push(TIMESTAMP);
+        self.buffer.push(self.value_kinds.flags(TIMESTAMP));
         self.buffer.extend(&BE_8_BYTES_LEN);
         self.buffer.extend(&i64::to_be_bytes(timestamp));
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: self.batch_type,
             stage: BatchBuild,
@@ -331,13 +417,12 @@ impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchFlags> {
     }
     /// Build a Batch frame.
     pub fn build(mut self) -> anyhow::Result<Batch> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
+        self.value_kinds.ensure_unmixed()?;
         // add noflags byte for batch flags
-        self.buffer.push(NOFLAGS);
+        self.buffer.push(self.value_kinds.flags(NOFLAGS));
         // adjust the querycount
         self.buffer[10..12].copy_from_slice(&u16::to_be_bytes(self.query_count));
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         Ok(Batch(self.buffer))
     }
 }
@@ -345,11 +430,15 @@ impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchFlags> {
 impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchTimestamp> {
     /// Set the timestamp of the Batch frame.
     pub fn timestamp(mut self, timestamp: i64) -> BatchBuilder<Type, BatchBuild> {
-        self.buffer.last_mut().map(|last_byte| *last_byte |= TIMESTAMP);
+        // the flags precede the serial consistency
+        let flags = self.buffer.len() - 3;
+        self.buffer[flags] |= TIMESTAMP;
         self.buffer.extend(&BE_8_BYTES_LEN);
         self.buffer.extend(&i64::to_be_bytes(timestamp));
         BatchBuilder {
             buffer: self.buffer,
+            compression: self.compression,
+            value_kinds: self.value_kinds,
             query_count: self.query_count,
             batch_type: self.batch_type,
             stage: BatchBuild,
@@ -357,11 +446,10 @@ impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchTimestamp> {
     }
     /// Build a Batch frame.
     pub fn build(mut self) -> anyhow::Result<Batch> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
+        self.value_kinds.ensure_unmixed()?;
         // adjust the querycount
         self.buffer[10..12].copy_from_slice(&u16::to_be_bytes(self.query_count));
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         Ok(Batch(self.buffer))
     }
 }
@@ -369,14 +457,40 @@ impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchTimestamp> {
 impl<Type: Copy + Into<u8>> BatchBuilder<Type, BatchBuild> {
     /// Build a Batch frame.
     pub fn build(mut self) -> anyhow::Result<Batch> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
+        self.value_kinds.ensure_unmixed()?;
         // adjust the querycount
         self.buffer[10..12].copy_from_slice(&u16::to_be_bytes(self.query_count));
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         Ok(Batch(self.buffer))
     }
 }
+impl<Type: Copy + Into<u8>, Stage> BatchBuilder<Type, Stage> {
+    /// Request the tracing of the batch, then the response carries the tracing id of the session (see
+    /// `Decoder::tracing_id`), whose events are recorded in the `system_traces` keyspace (see `Trace`).
+    pub fn tracing(mut self, tracing: bool) -> Self {
+        if tracing {
+            self.buffer[1] |= TRACING;
+        } else {
+            self.buffer[1] &= !TRACING;
+        }
+        self
+    }
+    /// Compress the batch frame by the provided compression, once it reaches the compression threshold, instead of
+    /// leaving it uncompressed for the connection which writes it to compress it by its negotiated compression.
+    /// Note: the frame must only be sent on the connections which negotiated the provided compression.
+    pub fn compression(mut self, compression: CompressionType) -> Self {
+        self.compression.compression = compression;
+        self
+    }
+    /// Override whether the batch frame is compressed, regardless of the compression threshold.
+    /// Note: the frame can only be compressed if it's built with a compression, see `compression`, otherwise the
+    /// connection which writes it decides whether to compress it.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compression.compress = Some(compress);
+        self
+    }
+}
+
 impl Batch {
     /// Create Batch cql frame
     pub fn new() -> BatchBuilder<BatchTypeUnset, BatchType> {
@@ -405,4 +519,49 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn value_counts() {
+        let Batch(payload) = Batch::new()
+            .logged()
+            .statement("A")
+            .value(&1i32)
+            .statement("B")
+            .value(&2i32)
+            .value(&3i32)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        let mut expected = vec![0, 0, 0, 0, 1, b'A', 0, 1, 0, 0, 0, 4, 0, 0, 0, 1];
+        expected.extend(&[
+            0, 0, 0, 0, 1, b'B', 0, 2, 0, 0, 0, 4, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0, 3,
+        ]);
+        assert_eq!(&payload[12..12 + expected.len()], &expected[..]);
+        assert_eq!(&payload[10..12], &[0, 2]);
+    }
+
+    #[test]
+    fn named_values() {
+        let Batch(payload) = Batch::new()
+            .logged()
+            .statement("A")
+            .named_value("k", &1i32)
+            .named_null_value("v")
+            .consistency(Consistency::One)
+            .timestamp(0)
+            .build()
+            .unwrap();
+        let mut expected = vec![0, 0, 0, 0, 1, b'A', 0, 2, 0, 1, b'k', 0, 0, 0, 4, 0, 0, 0, 1];
+        expected.extend(&[0, 1, b'v', 255, 255, 255, 255, 0, 1, WITH_NAMES | TIMESTAMP]);
+        assert_eq!(&payload[12..12 + expected.len()], &expected[..]);
+        let mixed = Batch::new()
+            .logged()
+            .statement("A")
+            .named_value("k", &1i32)
+            .statement("B")
+            .value(&2i32)
+            .consistency(Consistency::One)
+            .build();
+        assert!(mixed.is_err());
+    }
 }
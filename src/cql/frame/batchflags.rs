@@ -12,3 +12,6 @@ pub(crate) const SERIAL_CONSISTENCY: u8 = 0x10;
 /// The batch flag indicates whether to use the default timestamp.
 #[allow(unused)]
 pub(crate) const TIMESTAMP: u8 = 0x20;
+/// The batch flag indicates whether the values are preceded by the names of their bind markers.
+#[allow(unused)]
+pub(crate) const WITH_NAMES: u8 = 0x40;
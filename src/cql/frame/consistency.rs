@@ -6,8 +6,12 @@
 use anyhow::anyhow;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
-use std::convert::{TryFrom, TryInto};
-#[derive(Debug, FromPrimitive)]
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive)]
 #[repr(u16)]
 /// The consistency level enum.
 pub enum Consistency {
@@ -43,3 +47,53 @@ impl TryFrom<&[u8]> for Consistency {
             .ok_or(anyhow!("No consistency representation for provided bytes!"))
     }
 }
+
+impl Consistency {
+    /// Get the name of the consistency level, as used by cqlsh, ie `LOCAL_QUORUM`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Consistency::Any => "ANY",
+            Consistency::One => "ONE",
+            Consistency::Two => "TWO",
+            Consistency::Three => "THREE",
+            Consistency::Quorum => "QUORUM",
+            Consistency::All => "ALL",
+            Consistency::LocalQuorum => "LOCAL_QUORUM",
+            Consistency::EachQuorum => "EACH_QUORUM",
+            Consistency::Serial => "SERIAL",
+            Consistency::LocalSerial => "LOCAL_SERIAL",
+            Consistency::LocalOne => "LOCAL_ONE",
+        }
+    }
+    /// Check if it's a serial consistency level, which applies to the lightweight transactions
+    pub fn is_serial(&self) -> bool {
+        matches!(self, Consistency::Serial | Consistency::LocalSerial)
+    }
+}
+
+impl Display for Consistency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Consistency {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim().to_ascii_uppercase().as_str() {
+            "ANY" => Consistency::Any,
+            "ONE" => Consistency::One,
+            "TWO" => Consistency::Two,
+            "THREE" => Consistency::Three,
+            "QUORUM" => Consistency::Quorum,
+            "ALL" => Consistency::All,
+            "LOCAL_QUORUM" => Consistency::LocalQuorum,
+            "EACH_QUORUM" => Consistency::EachQuorum,
+            "SERIAL" => Consistency::Serial,
+            "LOCAL_SERIAL" => Consistency::LocalSerial,
+            "LOCAL_ONE" => Consistency::LocalOne,
+            _ => return Err(anyhow!("Unknown consistency level: {}", s)),
+        })
+    }
+}
@@ -0,0 +1,448 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module defines the cql data types, their parsing from the cql type names and aliases,
+//! and their conversion to the option ids used in the result metadata.
+
+use anyhow::{anyhow, bail, ensure};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// The option id of the `custom` type.
+pub const CUSTOM: u16 = 0x0000;
+/// The option id of the `ascii` type.
+pub const ASCII: u16 = 0x0001;
+/// The option id of the `bigint` type.
+pub const BIGINT: u16 = 0x0002;
+/// The option id of the `blob` type.
+pub const BLOB: u16 = 0x0003;
+/// The option id of the `boolean` type.
+pub const BOOLEAN: u16 = 0x0004;
+/// The option id of the `counter` type.
+pub const COUNTER: u16 = 0x0005;
+/// The option id of the `decimal` type.
+pub const DECIMAL: u16 = 0x0006;
+/// The option id of the `double` type.
+pub const DOUBLE: u16 = 0x0007;
+/// The option id of the `float` type.
+pub const FLOAT: u16 = 0x0008;
+/// The option id of the `int` type.
+pub const INT: u16 = 0x0009;
+/// The option id of the `timestamp` type.
+pub const TIMESTAMP: u16 = 0x000B;
+/// The option id of the `uuid` type.
+pub const UUID: u16 = 0x000C;
+/// The option id of the `varchar` type.
+pub const VARCHAR: u16 = 0x000D;
+/// The option id of the `varint` type.
+pub const VARINT: u16 = 0x000E;
+/// The option id of the `timeuuid` type.
+pub const TIMEUUID: u16 = 0x000F;
+/// The option id of the `inet` type.
+pub const INET: u16 = 0x0010;
+/// The option id of the `date` type.
+pub const DATE: u16 = 0x0011;
+/// The option id of the `time` type.
+pub const TIME: u16 = 0x0012;
+/// The option id of the `smallint` type.
+pub const SMALLINT: u16 = 0x0013;
+/// The option id of the `tinyint` type.
+pub const TINYINT: u16 = 0x0014;
+/// The option id of the `duration` type.
+pub const DURATION: u16 = 0x0015;
+/// The option id of the `list` type.
+pub const LIST: u16 = 0x0020;
+/// The option id of the `map` type.
+pub const MAP: u16 = 0x0021;
+/// The option id of the `set` type.
+pub const SET: u16 = 0x0022;
+/// The option id of the user defined type.
+pub const UDT: u16 = 0x0030;
+/// The option id of the `tuple` type.
+pub const TUPLE: u16 = 0x0031;
+
+/// The java class of the vector custom type.
+const VECTOR_CLASS: &str = "org.apache.cassandra.db.marshal.VectorType";
+
+/// A cql data type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CqlType {
+    /// The custom type, with its java class name.
+    Custom(String),
+    /// The `ascii` type.
+    Ascii,
+    /// The `bigint` type.
+    BigInt,
+    /// The `blob` type.
+    Blob,
+    /// The `boolean` type.
+    Boolean,
+    /// The `counter` type.
+    Counter,
+    /// The `decimal` type.
+    Decimal,
+    /// The `double` type.
+    Double,
+    /// The `float` type.
+    Float,
+    /// The `int` type.
+    Int,
+    /// The `timestamp` type.
+    Timestamp,
+    /// The `uuid` type.
+    Uuid,
+    /// The `text` type, aliased as `varchar`.
+    Text,
+    /// The `varint` type.
+    VarInt,
+    /// The `timeuuid` type.
+    TimeUuid,
+    /// The `inet` type.
+    Inet,
+    /// The `date` type.
+    Date,
+    /// The `time` type.
+    Time,
+    /// The `smallint` type.
+    SmallInt,
+    /// The `tinyint` type.
+    TinyInt,
+    /// The `duration` type.
+    Duration,
+    /// The `list<T>` type.
+    List(Box<CqlType>),
+    /// The `set<T>` type.
+    Set(Box<CqlType>),
+    /// The `map<K, V>` type.
+    Map(Box<CqlType>, Box<CqlType>),
+    /// The `tuple<T1, T2, ...>` type.
+    Tuple(Vec<CqlType>),
+    /// The `frozen<T>` type, which is serialized as its inner type.
+    Frozen(Box<CqlType>),
+    /// The `vector<T, N>` type, which is serialized as a custom type.
+    Vector(Box<CqlType>, usize),
+    /// The user defined type, with its optionally keyspace qualified name.
+    Udt(String),
+}
+
+impl CqlType {
+    /// Get the option id of the type, as used in the result metadata.
+    pub fn option_id(&self) -> u16 {
+        match self {
+            CqlType::Custom(_) | CqlType::Vector(..) => CUSTOM,
+            CqlType::Ascii => ASCII,
+            CqlType::BigInt => BIGINT,
+            CqlType::Blob => BLOB,
+            CqlType::Boolean => BOOLEAN,
+            CqlType::Counter => COUNTER,
+            CqlType::Decimal => DECIMAL,
+            CqlType::Double => DOUBLE,
+            CqlType::Float => FLOAT,
+            CqlType::Int => INT,
+            CqlType::Timestamp => TIMESTAMP,
+            CqlType::Uuid => UUID,
+            CqlType::Text => VARCHAR,
+            CqlType::VarInt => VARINT,
+            CqlType::TimeUuid => TIMEUUID,
+            CqlType::Inet => INET,
+            CqlType::Date => DATE,
+            CqlType::Time => TIME,
+            CqlType::SmallInt => SMALLINT,
+            CqlType::TinyInt => TINYINT,
+            CqlType::Duration => DURATION,
+            CqlType::List(_) => LIST,
+            CqlType::Set(_) => SET,
+            CqlType::Map(..) => MAP,
+            CqlType::Tuple(_) => TUPLE,
+            CqlType::Frozen(inner) => inner.option_id(),
+            CqlType::Udt(_) => UDT,
+        }
+    }
+    /// Get the native type of the option id, None is returned for the custom and parameterized types.
+    pub fn from_option_id(id: u16) -> Option<Self> {
+        Some(match id {
+            ASCII => CqlType::Ascii,
+            BIGINT => CqlType::BigInt,
+            BLOB => CqlType::Blob,
+            BOOLEAN => CqlType::Boolean,
+            COUNTER => CqlType::Counter,
+            DECIMAL => CqlType::Decimal,
+            DOUBLE => CqlType::Double,
+            FLOAT => CqlType::Float,
+            INT => CqlType::Int,
+            TIMESTAMP => CqlType::Timestamp,
+            UUID => CqlType::Uuid,
+            VARCHAR => CqlType::Text,
+            VARINT => CqlType::VarInt,
+            TIMEUUID => CqlType::TimeUuid,
+            INET => CqlType::Inet,
+            DATE => CqlType::Date,
+            TIME => CqlType::Time,
+            SMALLINT => CqlType::SmallInt,
+            TINYINT => CqlType::TinyInt,
+            DURATION => CqlType::Duration,
+            _ => return None,
+        })
+    }
+    /// Get the custom java class name of the type, if it's serialized as a custom type.
+    pub fn custom_class(&self) -> Option<String> {
+        match self {
+            CqlType::Custom(class) => Some(class.clone()),
+            CqlType::Vector(inner, dimension) => Some(format!("{}({}, {})", VECTOR_CLASS, inner, dimension)),
+            CqlType::Frozen(inner) => inner.custom_class(),
+            _ => None,
+        }
+    }
+    /// Get the type without the frozen wrapper.
+    pub fn unfrozen(&self) -> &CqlType {
+        match self {
+            CqlType::Frozen(inner) => inner.unfrozen(),
+            _ => self,
+        }
+    }
+}
+
+impl Display for CqlType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CqlType::Custom(class) => write!(f, "'{}'", class.replace('\'', "''")),
+            CqlType::Ascii => write!(f, "ascii"),
+            CqlType::BigInt => write!(f, "bigint"),
+            CqlType::Blob => write!(f, "blob"),
+            CqlType::Boolean => write!(f, "boolean"),
+            CqlType::Counter => write!(f, "counter"),
+            CqlType::Decimal => write!(f, "decimal"),
+            CqlType::Double => write!(f, "double"),
+            CqlType::Float => write!(f, "float"),
+            CqlType::Int => write!(f, "int"),
+            CqlType::Timestamp => write!(f, "timestamp"),
+            CqlType::Uuid => write!(f, "uuid"),
+            CqlType::Text => write!(f, "text"),
+            CqlType::VarInt => write!(f, "varint"),
+            CqlType::TimeUuid => write!(f, "timeuuid"),
+            CqlType::Inet => write!(f, "inet"),
+            CqlType::Date => write!(f, "date"),
+            CqlType::Time => write!(f, "time"),
+            CqlType::SmallInt => write!(f, "smallint"),
+            CqlType::TinyInt => write!(f, "tinyint"),
+            CqlType::Duration => write!(f, "duration"),
+            CqlType::List(inner) => write!(f, "list<{}>", inner),
+            CqlType::Set(inner) => write!(f, "set<{}>", inner),
+            CqlType::Map(key, value) => write!(f, "map<{}, {}>", key, value),
+            CqlType::Tuple(types) => {
+                let types: Vec<String> = types.iter().map(ToString::to_string).collect();
+                write!(f, "tuple<{}>", types.join(", "))
+            }
+            CqlType::Frozen(inner) => write!(f, "frozen<{}>", inner),
+            CqlType::Vector(inner, dimension) => write!(f, "vector<{}, {}>", inner, dimension),
+            CqlType::Udt(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl FromStr for CqlType {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut parser = Parser { input: s, position: 0 };
+        let cql_type = parser.parse()?;
+        parser.skip_whitespace();
+        ensure!(
+            parser.position == s.len(),
+            "Unexpected '{}' after the cql type",
+            &s[parser.position..]
+        );
+        Ok(cql_type)
+    }
+}
+
+/// The cql type names parser
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+    fn expect(&mut self, c: char) -> anyhow::Result<()> {
+        self.skip_whitespace();
+        ensure!(self.rest().starts_with(c), "Expected '{}' at '{}'", c, self.rest());
+        self.position += 1;
+        Ok(())
+    }
+    fn try_consume(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+    /// Parse an identifier, which might be quoted and keyspace qualified
+    fn name(&mut self) -> anyhow::Result<String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '"'))
+            .unwrap_or(rest.len());
+        ensure!(len > 0, "Expected a cql type at '{}'", rest);
+        self.position += len;
+        Ok(rest[..len].to_string())
+    }
+    fn parameters(&mut self, count: usize) -> anyhow::Result<Vec<CqlType>> {
+        self.expect('<')?;
+        let mut types = vec![self.parse()?];
+        while self.try_consume(',') {
+            types.push(self.parse()?);
+        }
+        self.expect('>')?;
+        ensure!(
+            count == 0 || types.len() == count,
+            "Expected {} type parameters, found {}",
+            count,
+            types.len()
+        );
+        Ok(types)
+    }
+    fn parse(&mut self) -> anyhow::Result<CqlType> {
+        self.skip_whitespace();
+        if self.rest().starts_with('\'') {
+            // the custom type class name literal
+            let rest = &self.rest()[1..];
+            let mut end = 0;
+            loop {
+                match rest[end..].find('\'') {
+                    Some(i) if rest[end + i + 1..].starts_with('\'') => end += i + 2,
+                    Some(i) => {
+                        end += i;
+                        break;
+                    }
+                    None => bail!("Unterminated custom type literal"),
+                }
+            }
+            self.position += end + 2;
+            return Ok(CqlType::Custom(rest[..end].replace("''", "'")));
+        }
+        let name = self.name()?;
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "ascii" => CqlType::Ascii,
+            "bigint" => CqlType::BigInt,
+            "blob" => CqlType::Blob,
+            "boolean" => CqlType::Boolean,
+            "counter" => CqlType::Counter,
+            "decimal" => CqlType::Decimal,
+            "double" => CqlType::Double,
+            "float" => CqlType::Float,
+            "int" => CqlType::Int,
+            "timestamp" => CqlType::Timestamp,
+            "uuid" => CqlType::Uuid,
+            "text" | "varchar" => CqlType::Text,
+            "varint" => CqlType::VarInt,
+            "timeuuid" => CqlType::TimeUuid,
+            "inet" => CqlType::Inet,
+            "date" => CqlType::Date,
+            "time" => CqlType::Time,
+            "smallint" => CqlType::SmallInt,
+            "tinyint" => CqlType::TinyInt,
+            "duration" => CqlType::Duration,
+            "list" => CqlType::List(Box::new(self.parameters(1)?.remove(0))),
+            "set" => CqlType::Set(Box::new(self.parameters(1)?.remove(0))),
+            "map" => {
+                let mut types = self.parameters(2)?;
+                let value = types.pop().unwrap();
+                CqlType::Map(Box::new(types.pop().unwrap()), Box::new(value))
+            }
+            "tuple" => CqlType::Tuple(self.parameters(0)?),
+            "frozen" => CqlType::Frozen(Box::new(self.parameters(1)?.remove(0))),
+            "vector" => {
+                self.expect('<')?;
+                let inner = self.parse()?;
+                self.expect(',')?;
+                self.skip_whitespace();
+                let rest = self.rest();
+                let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                let dimension = rest[..len]
+                    .parse()
+                    .map_err(|_| anyhow!("Expected the vector dimension at '{}'", rest))?;
+                self.position += len;
+                self.expect('>')?;
+                CqlType::Vector(Box::new(inner), dimension)
+            }
+            _ => CqlType::Udt(name),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cql_type_round_trip() {
+        let names = [
+            "ascii",
+            "bigint",
+            "blob",
+            "boolean",
+            "counter",
+            "decimal",
+            "double",
+            "float",
+            "int",
+            "timestamp",
+            "uuid",
+            "text",
+            "varint",
+            "timeuuid",
+            "inet",
+            "date",
+            "time",
+            "smallint",
+            "tinyint",
+            "duration",
+            "list<int>",
+            "set<frozen<list<text>>>",
+            "map<text, frozen<tuple<int, 'a.B', ks.udt>>>",
+            "vector<float, 3>",
+            "\"MyType\"",
+        ];
+        for name in names.iter() {
+            let cql_type: CqlType = name.parse().unwrap();
+            assert_eq!(&cql_type.to_string(), name);
+        }
+        // aliases and whitespace
+        assert_eq!("VARCHAR".parse::<CqlType>().unwrap(), CqlType::Text);
+        assert_eq!(
+            " Map < varchar,int > ".parse::<CqlType>().unwrap().to_string(),
+            "map<text, int>"
+        );
+        assert!("list<int, int>".parse::<CqlType>().is_err());
+        assert!("list<int".parse::<CqlType>().is_err());
+        assert!("vector<float>".parse::<CqlType>().is_err());
+    }
+
+    #[test]
+    fn cql_type_option_ids() {
+        for id in 0..=TUPLE {
+            if let Some(cql_type) = CqlType::from_option_id(id) {
+                assert_eq!(cql_type.option_id(), id);
+            }
+        }
+        let frozen: CqlType = "frozen<map<int, text>>".parse().unwrap();
+        assert_eq!(frozen.option_id(), MAP);
+        let vector: CqlType = "vector<float, 2>".parse().unwrap();
+        assert_eq!(vector.option_id(), CUSTOM);
+        assert_eq!(
+            vector.custom_class().unwrap(),
+            "org.apache.cassandra.db.marshal.VectorType(float, 2)"
+        );
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module defines the dynamic cql value, and its conversions to the supported rust types.
+//!
+//! ## Coercion rules
+//! - Integers can be widened: `tinyint` -> `smallint` -> `int` -> `bigint`, narrowing is rejected.
+//! - Integers can be converted to floats only when the conversion is lossless: `tinyint`/`smallint` to `f32`,
+//!   `tinyint`/`smallint`/`int` to `f64`.
+//! - `float` can be widened to `f64`, while floats are never converted to integers as it's lossy.
+//! - `null` can only be converted to `Option::None`.
+//! - `blob` can only be converted to `Blob`, as `Vec<u8>` is converted element-wise from a list.
+//! - Collections are converted element-wise using the same rules.
+
+use super::{
+    cql_type::CqlType,
+    decoder::ColumnDecoder,
+    encoder::{ColumnEncoder, BE_0_BYTES_LEN, BE_NULL_BYTES_LEN},
+};
+use anyhow::{anyhow, bail, ensure};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+    hash::Hash,
+    net::IpAddr,
+};
+
+/// A dynamically typed cql value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CqlValue {
+    /// The null value.
+    Null,
+    /// The `boolean` value.
+    Boolean(bool),
+    /// The `tinyint` value.
+    TinyInt(i8),
+    /// The `smallint` value.
+    SmallInt(i16),
+    /// The `int` value.
+    Int(i32),
+    /// The `bigint` value.
+    BigInt(i64),
+    /// The `float` value.
+    Float(f32),
+    /// The `double` value.
+    Double(f64),
+    /// The `text`/`varchar`/`ascii` value.
+    Text(String),
+    /// The `blob` value.
+    Blob(Vec<u8>),
+    /// The `inet` value.
+    Inet(IpAddr),
+    /// The `list`/`set` value.
+    List(Vec<CqlValue>),
+    /// The `map` value.
+    Map(Vec<(CqlValue, CqlValue)>),
+}
+
+/// The bytes of a cql `blob` value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Blob(pub Vec<u8>);
+
+impl From<Blob> for Vec<u8> {
+    fn from(blob: Blob) -> Self {
+        blob.0
+    }
+}
+
+impl CqlValue {
+    /// Get the cql type name of the value.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CqlValue::Null => "null",
+            CqlValue::Boolean(_) => "boolean",
+            CqlValue::TinyInt(_) => "tinyint",
+            CqlValue::SmallInt(_) => "smallint",
+            CqlValue::Int(_) => "int",
+            CqlValue::BigInt(_) => "bigint",
+            CqlValue::Float(_) => "float",
+            CqlValue::Double(_) => "double",
+            CqlValue::Text(_) => "text",
+            CqlValue::Blob(_) => "blob",
+            CqlValue::Inet(_) => "inet",
+            CqlValue::List(_) => "list",
+            CqlValue::Map(_) => "map",
+        }
+    }
+    /// Check if the value is null.
+    pub fn is_null(&self) -> bool {
+        matches!(self, CqlValue::Null)
+    }
+    /// Convert the value into the T type, following the coercion rules.
+    pub fn to<'a, T: TryFrom<&'a CqlValue, Error = anyhow::Error>>(&'a self) -> anyhow::Result<T> {
+        self.try_into()
+    }
+    /// Decode the non null value of the cql type, where the types which have no dedicated variant are decoded as
+    /// blobs, ie the uuids or the decimals.
+    pub fn try_decode(cql_type: &CqlType, slice: &[u8]) -> anyhow::Result<Self> {
+        Ok(match cql_type.unfrozen() {
+            CqlType::Boolean => CqlValue::Boolean(bool::try_decode(slice)?),
+            CqlType::TinyInt => CqlValue::TinyInt(i8::try_decode(slice)?),
+            CqlType::SmallInt => CqlValue::SmallInt(i16::try_decode(slice)?),
+            CqlType::Int => CqlValue::Int(i32::try_decode(slice)?),
+            CqlType::BigInt | CqlType::Counter => CqlValue::BigInt(i64::try_decode(slice)?),
+            CqlType::Float => CqlValue::Float(f32::try_decode(slice)?),
+            CqlType::Double => CqlValue::Double(f64::try_decode(slice)?),
+            CqlType::Ascii | CqlType::Text => CqlValue::Text(String::try_decode(slice)?),
+            CqlType::Inet => CqlValue::Inet(IpAddr::try_decode(slice)?),
+            CqlType::List(element) | CqlType::Set(element) => {
+                let mut elements = Elements::new(slice)?;
+                let list = (0..elements.len)
+                    .map(|_| elements.next(element))
+                    .collect::<anyhow::Result<_>>()?;
+                CqlValue::List(list)
+            }
+            CqlType::Map(key, value) => {
+                let mut elements = Elements::new(slice)?;
+                let map = (0..elements.len)
+                    .map(|_| Ok((elements.next(key)?, elements.next(value)?)))
+                    .collect::<anyhow::Result<_>>()?;
+                CqlValue::Map(map)
+            }
+            _ => CqlValue::Blob(slice.to_vec()),
+        })
+    }
+}
+
+/// The elements of a collection value
+struct Elements<'a> {
+    slice: &'a [u8],
+    len: usize,
+}
+
+impl<'a> Elements<'a> {
+    fn new(slice: &'a [u8]) -> anyhow::Result<Self> {
+        ensure!(slice.len() >= 4, "Buffer is too small!");
+        let len = i32::from_be_bytes(slice[0..4].try_into()?);
+        ensure!(len >= 0, "Invalid collection length: {}", len);
+        Ok(Self {
+            slice: &slice[4..],
+            len: len as usize,
+        })
+    }
+    /// Decode the next `[bytes]` element, whose negative length stands for null
+    fn next(&mut self, cql_type: &CqlType) -> anyhow::Result<CqlValue> {
+        ensure!(self.slice.len() >= 4, "Buffer is too small!");
+        let len = i32::from_be_bytes(self.slice[0..4].try_into()?);
+        self.slice = &self.slice[4..];
+        if len < 0 {
+            return Ok(CqlValue::Null);
+        }
+        ensure!(self.slice.len() >= len as usize, "Buffer is too small!");
+        let (element, rest) = self.slice.split_at(len as usize);
+        self.slice = rest;
+        CqlValue::try_decode(cql_type, element)
+    }
+}
+
+impl ColumnEncoder for CqlValue {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            CqlValue::Null => buffer.extend(&BE_NULL_BYTES_LEN),
+            CqlValue::Boolean(v) => v.encode(buffer),
+            CqlValue::TinyInt(v) => v.encode(buffer),
+            CqlValue::SmallInt(v) => v.encode(buffer),
+            CqlValue::Int(v) => v.encode(buffer),
+            CqlValue::BigInt(v) => v.encode(buffer),
+            CqlValue::Float(v) => v.encode(buffer),
+            CqlValue::Double(v) => v.encode(buffer),
+            CqlValue::Text(v) => v.encode(buffer),
+            CqlValue::Blob(v) => v.as_slice().encode(buffer),
+            CqlValue::Inet(v) => v.encode(buffer),
+            CqlValue::List(v) => v.encode(buffer),
+            CqlValue::Map(v) => {
+                buffer.extend(&BE_0_BYTES_LEN);
+                let current_length = buffer.len();
+                buffer.extend(&i32::to_be_bytes(v.len() as i32));
+                for (k, v) in v {
+                    k.encode(buffer);
+                    v.encode(buffer);
+                }
+                let map_byte_size = buffer.len() - current_length;
+                buffer[(current_length - 4)..current_length].copy_from_slice(&i32::to_be_bytes(map_byte_size as i32));
+            }
+        }
+    }
+}
+
+fn mismatch(value: &CqlValue, target: &str) -> anyhow::Error {
+    anyhow!("Cannot convert cql {} value to {}", value.type_name(), target)
+}
+
+macro_rules! try_from_cql_value {
+    ($t:ty, $target:expr, $($variant:ident),+) => {
+        impl TryFrom<&CqlValue> for $t {
+            type Error = anyhow::Error;
+            fn try_from(value: &CqlValue) -> anyhow::Result<Self> {
+                match value {
+                    $(CqlValue::$variant(v) => Ok(<$t>::from(*v)),)+
+                    _ => Err(mismatch(value, $target)),
+                }
+            }
+        }
+    };
+}
+
+try_from_cql_value!(bool, "bool", Boolean);
+try_from_cql_value!(i8, "i8", TinyInt);
+try_from_cql_value!(i16, "i16", TinyInt, SmallInt);
+try_from_cql_value!(i32, "i32", TinyInt, SmallInt, Int);
+try_from_cql_value!(i64, "i64", TinyInt, SmallInt, Int, BigInt);
+try_from_cql_value!(f32, "f32", TinyInt, SmallInt, Float);
+try_from_cql_value!(f64, "f64", TinyInt, SmallInt, Int, Float, Double);
+try_from_cql_value!(IpAddr, "IpAddr", Inet);
+
+impl TryFrom<&CqlValue> for String {
+    type Error = anyhow::Error;
+    fn try_from(value: &CqlValue) -> anyhow::Result<Self> {
+        match value {
+            CqlValue::Text(v) => Ok(v.clone()),
+            _ => Err(mismatch(value, "String")),
+        }
+    }
+}
+
+impl TryFrom<&CqlValue> for Blob {
+    type Error = anyhow::Error;
+    fn try_from(value: &CqlValue) -> anyhow::Result<Self> {
+        match value {
+            CqlValue::Blob(v) => Ok(Blob(v.clone())),
+            _ => Err(mismatch(value, "Blob")),
+        }
+    }
+}
+
+impl<T> TryFrom<&CqlValue> for Option<T>
+where
+    T: for<'a> TryFrom<&'a CqlValue, Error = anyhow::Error>,
+{
+    type Error = anyhow::Error;
+    fn try_from(value: &CqlValue) -> anyhow::Result<Self> {
+        match value {
+            CqlValue::Null => Ok(None),
+            _ => T::try_from(value).map(Some),
+        }
+    }
+}
+
+impl<T> TryFrom<&CqlValue> for Vec<T>
+where
+    T: for<'a> TryFrom<&'a CqlValue, Error = anyhow::Error>,
+{
+    type Error = anyhow::Error;
+    fn try_from(value: &CqlValue) -> anyhow::Result<Self> {
+        match value {
+            CqlValue::List(list) => list.iter().map(T::try_from).collect(),
+            _ => Err(mismatch(value, "Vec")),
+        }
+    }
+}
+
+impl<K, V, S> TryFrom<&CqlValue> for HashMap<K, V, S>
+where
+    K: Eq + Hash + for<'a> TryFrom<&'a CqlValue, Error = anyhow::Error>,
+    V: for<'a> TryFrom<&'a CqlValue, Error = anyhow::Error>,
+    S: ::std::hash::BuildHasher + Default,
+{
+    type Error = anyhow::Error;
+    fn try_from(value: &CqlValue) -> anyhow::Result<Self> {
+        match value {
+            CqlValue::Map(map) => map
+                .iter()
+                .map(|(k, v)| Ok((K::try_from(k)?, V::try_from(v)?)))
+                .collect(),
+            CqlValue::Null => bail!("Cannot convert cql null value to HashMap"),
+            _ => Err(mismatch(value, "HashMap")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coercion_rules() {
+        // int widening is allowed
+        assert_eq!(CqlValue::TinyInt(7).to::<i64>().unwrap(), 7);
+        assert_eq!(CqlValue::Int(-3).to::<i64>().unwrap(), -3);
+        assert_eq!(CqlValue::Int(3).to::<f64>().unwrap(), 3.0);
+        assert_eq!(CqlValue::Float(1.5).to::<f64>().unwrap(), 1.5);
+        // narrowing and lossy conversions are rejected
+        assert!(CqlValue::BigInt(1).to::<i32>().is_err());
+        assert!(CqlValue::Double(1.0).to::<i64>().is_err());
+        assert!(CqlValue::BigInt(1).to::<f64>().is_err());
+        assert!(CqlValue::Double(1.0).to::<f32>().is_err());
+        // null and collections
+        assert_eq!(CqlValue::Null.to::<Option<i32>>().unwrap(), None);
+        assert!(CqlValue::Null.to::<i32>().is_err());
+        let list = CqlValue::List(vec![CqlValue::SmallInt(1), CqlValue::Int(2)]);
+        assert_eq!(list.to::<Vec<i32>>().unwrap(), vec![1, 2]);
+        let map = CqlValue::Map(vec![(CqlValue::Text("a".to_string()), CqlValue::Boolean(true))]);
+        let map: HashMap<String, bool> = map.to().unwrap();
+        assert_eq!(map.get("a"), Some(&true));
+    }
+
+    #[test]
+    fn blob_conversion() {
+        let blob = CqlValue::Blob(vec![0, 1, 255]);
+        assert_eq!(blob.to::<Blob>().unwrap(), Blob(vec![0, 1, 255]));
+        assert_eq!(Vec::<u8>::from(blob.to::<Blob>().unwrap()), vec![0, 1, 255]);
+        assert_eq!(blob.to::<Option<Blob>>().unwrap(), Some(Blob(vec![0, 1, 255])));
+        assert_eq!(CqlValue::Null.to::<Option<Blob>>().unwrap(), None);
+        let blobs = CqlValue::List(vec![CqlValue::Blob(vec![1]), CqlValue::Blob(Vec::new())]);
+        assert_eq!(blobs.to::<Vec<Blob>>().unwrap(), vec![Blob(vec![1]), Blob(Vec::new())]);
+        // a blob is not text, and text is not a blob
+        assert!(blob.to::<String>().is_err());
+        assert!(CqlValue::Text("a".to_string()).to::<Blob>().is_err());
+        assert!(blob.to::<Vec<i8>>().is_err());
+    }
+
+    #[test]
+    fn null_is_rejected_by_non_option_types() {
+        assert!(CqlValue::Null.to::<bool>().is_err());
+        assert!(CqlValue::Null.to::<i64>().is_err());
+        assert!(CqlValue::Null.to::<f64>().is_err());
+        assert!(CqlValue::Null.to::<String>().is_err());
+        assert!(CqlValue::Null.to::<Blob>().is_err());
+        assert!(CqlValue::Null.to::<IpAddr>().is_err());
+        assert!(CqlValue::Null.to::<Vec<i32>>().is_err());
+        assert!(CqlValue::Null.to::<HashMap<String, i32>>().is_err());
+        // a null element of a collection is only accepted by an optional element type
+        let list = CqlValue::List(vec![CqlValue::Int(1), CqlValue::Null]);
+        assert!(list.to::<Vec<i32>>().is_err());
+        assert_eq!(list.to::<Vec<Option<i32>>>().unwrap(), vec![Some(1), None]);
+    }
+}
@@ -5,16 +5,17 @@
 
 use super::{
     error, header, opcode, result,
-    rows::{ColumnsCount, Flags, Metadata, PagingState},
+    rows::{ColumnsCount, Flags, Iter, LwtResult, Metadata, PagingState, Row, Rows},
 };
 use crate::cql::compression::{Compression, MyCompression};
 use anyhow::{anyhow, ensure};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     convert::{TryFrom, TryInto},
-    hash::Hash,
+    hash::{Hash, Hasher},
     io::Cursor,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::Deref,
     str,
 };
 /// RowsDecoder trait to decode the rows result from scylla
@@ -45,6 +46,31 @@ pub trait VoidDecoder {
     }
 }
 
+/// LwtDecoder trait to decode the result of a lightweight transaction from scylla, ie of the conditional
+/// statements (`INSERT .. IF NOT EXISTS`, `UPDATE .. IF ..`, `DELETE .. IF ..`)
+pub trait LwtDecoder<K, V: Row> {
+    /// Try to decode the provided Decoder with an expected `[applied]` Rows result, along with the current
+    /// values of the row when the statement wasn't applied
+    fn try_decode(decoder: Decoder) -> anyhow::Result<LwtResult<V>> {
+        if decoder.is_error()? {
+            return Err(anyhow!(decoder.get_error()?));
+        }
+        if decoder.columns_count()? > 1 {
+            Iter::<LwtResult<V>>::new(decoder)?.next()
+        } else {
+            Iter::<bool>::new(decoder)?.next().map(|applied| LwtResult {
+                applied,
+                existing: None,
+            })
+        }
+        .ok_or_else(|| anyhow!("Expected the [applied] row in the lightweight transaction result"))
+    }
+    /// Decode the provided Decoder with a deterministic lightweight transaction result
+    fn decode(decoder: Decoder) -> LwtResult<V> {
+        Self::try_decode(decoder).unwrap()
+    }
+}
+
 impl TryFrom<Vec<u8>> for Decoder {
     type Error = anyhow::Error;
 
@@ -81,10 +107,16 @@ pub trait Frame {
     fn is_supported(&self) -> anyhow::Result<bool>;
     /// Check whether the opcode is `READY`.
     fn is_ready(&self) -> anyhow::Result<bool>;
+    /// Check whether the opcode is `EVENT`.
+    fn is_event(&self) -> anyhow::Result<bool>;
     /// Check whether the body kind is `VOID`.
     fn is_void(&self) -> anyhow::Result<bool>;
     /// Check whether the body kind is `ROWS`.
     fn is_rows(&self) -> anyhow::Result<bool>;
+    /// Check whether the body kind is `PREPARED`.
+    fn is_prepared(&self) -> anyhow::Result<bool>;
+    /// Get the prepared statement id assigned by the server.
+    fn get_prepared_id(&self) -> anyhow::Result<[u8; 16]>;
     /// Check whether the opcode is `ERROR`.
     fn is_error(&self) -> anyhow::Result<bool>;
     /// Get the `CqlError`.
@@ -161,6 +193,23 @@ impl Decoder {
     pub fn into_buffer(self) -> Vec<u8> {
         self.buffer
     }
+    /// Get the tracing id of the response, which is set if the tracing of the request was requested.
+    pub fn tracing_id(&self) -> Option<[u8; 16]> {
+        self.header_flags.tracing_id()
+    }
+    /// Take the custom payload of the response, which is set by the server-side plugins.
+    pub fn take_custom_payload(&mut self) -> Option<HashMap<String, Vec<u8>>> {
+        self.header_flags.take_custom_payload()
+    }
+    /// Get the warnings of the response, which are set by the server, ie on large aggregations or on reading too
+    /// many tombstones.
+    pub fn warnings(&self) -> &[String] {
+        self.header_flags.warnings()
+    }
+    /// Take the warnings of the response.
+    pub fn take_warnings(&mut self) -> Option<Vec<String>> {
+        self.header_flags.take_warnings()
+    }
 }
 
 #[allow(dead_code)]
@@ -169,7 +218,7 @@ impl Decoder {
 pub struct HeaderFlags {
     compression: bool,
     tracing: Option<[u8; 16]>,
-    custom_payload: bool,
+    custom_payload: Option<HashMap<String, Vec<u8>>>,
     warnings: Option<Vec<String>>,
     // this not a flag, but it indicates the body start in the buffer.
     body_start: usize,
@@ -205,7 +254,14 @@ impl HeaderFlags {
         } else {
             None
         };
-        let custom_payload = flags & header::CUSTOM_PAYLOAD == header::CUSTOM_PAYLOAD;
+        let custom_payload = if flags & header::CUSTOM_PAYLOAD == header::CUSTOM_PAYLOAD {
+            let (bytes_map, len) = bytes_map_with_returned_bytes_length(&buffer[body_start..])?;
+            // add the bytes map length to the body_start
+            body_start += len;
+            Some(bytes_map)
+        } else {
+            None
+        };
         Ok(Self {
             compression,
             tracing,
@@ -218,12 +274,28 @@ impl HeaderFlags {
     pub fn compression(&self) -> bool {
         self.compression
     }
+    /// Get the tracing id of the frame.
+    pub fn tracing_id(&self) -> Option<[u8; 16]> {
+        self.tracing
+    }
     /// Take the tracing id of the frame.
     pub fn take_tracing_id(&mut self) -> Option<[u8; 16]> {
         self.tracing.take()
     }
+    /// Get the custom payload of the frame.
+    pub fn custom_payload(&self) -> Option<&HashMap<String, Vec<u8>>> {
+        self.custom_payload.as_ref()
+    }
+    /// Take the custom payload of the frame.
+    pub fn take_custom_payload(&mut self) -> Option<HashMap<String, Vec<u8>>> {
+        self.custom_payload.take()
+    }
+    /// Get the warnings of the frame.
+    pub fn warnings(&self) -> &[String] {
+        self.warnings.as_deref().unwrap_or_default()
+    }
     /// Take the warnings of the frame.
-    fn take_warnings(&mut self) -> Option<Vec<String>> {
+    pub fn take_warnings(&mut self) -> Option<Vec<String>> {
         self.warnings.take()
     }
 }
@@ -277,12 +349,25 @@ impl Frame for Decoder {
     fn is_ready(&self) -> anyhow::Result<bool> {
         Ok(self.opcode()? == opcode::READY)
     }
+    fn is_event(&self) -> anyhow::Result<bool> {
+        Ok(self.opcode()? == opcode::EVENT)
+    }
     fn is_void(&self) -> anyhow::Result<bool> {
         Ok((self.opcode()? == opcode::RESULT) && (self.body_kind()? == result::VOID))
     }
     fn is_rows(&self) -> anyhow::Result<bool> {
         Ok((self.opcode()? == opcode::RESULT) && (self.body_kind()? == result::ROWS))
     }
+    fn is_prepared(&self) -> anyhow::Result<bool> {
+        Ok((self.opcode()? == opcode::RESULT) && (self.body_kind()? == result::PREPARED))
+    }
+    fn get_prepared_id(&self) -> anyhow::Result<[u8; 16]> {
+        if self.is_prepared()? {
+            prepared_id(&self.body()?[4..])
+        } else {
+            Err(anyhow!("Not prepared"))
+        }
+    }
     fn is_error(&self) -> anyhow::Result<bool> {
         Ok(self.opcode()? == opcode::ERROR)
     }
@@ -396,7 +481,14 @@ impl Frame for Decoder {
         let flags = self.rows_flags()?;
         let columns_count = self.columns_count()?;
         let paging_state = self.paging_state(flags.has_more_pages())?;
-        Ok(Metadata::new(flags, columns_count, paging_state))
+        let metadata = Metadata::new(flags, columns_count, paging_state);
+        if flags.no_metadata() {
+            Ok(metadata)
+        } else {
+            let (columns, rows_start) =
+                super::rows::column_specs(self.buffer_as_ref(), metadata.rows_start(), flags, columns_count)?;
+            Ok(metadata.with_columns(columns, rows_start))
+        }
     }
 }
 
@@ -480,6 +572,13 @@ impl ColumnDecoder for u8 {
     }
 }
 
+impl ColumnDecoder for bool {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        ensure!(!slice.is_empty(), "Buffer is too small!");
+        Ok(slice[0] != 0)
+    }
+}
+
 impl ColumnDecoder for String {
     fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
         Ok(String::from_utf8(slice.to_vec())?)
@@ -530,29 +629,51 @@ where
     E: ColumnDecoder,
 {
     fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
-        let list_len = i32::from_be_bytes(slice[0..4].try_into()?) as usize;
-        let mut list: Vec<E> = Vec::new();
+        // the null collections are empty
+        if slice.is_empty() {
+            return Ok(Vec::new());
+        }
+        let list_len = collection_len(slice)?;
+        let mut list: Vec<E> = Vec::with_capacity(list_len.min(slice.len() / 4));
         let mut element_start = 4;
         for _ in 0..list_len {
-            // decode element byte_size
-            let element_value_start = element_start + 4;
-            let length = i32::from_be_bytes(slice[element_start..element_value_start].try_into()?) as usize;
-            if length > 0 {
-                let e = E::try_decode(&slice[element_value_start..(element_value_start + length)])?;
-                list.push(e);
-                // next element start
-                element_start = element_value_start + length;
-            } else {
-                let e = E::try_decode(&[])?;
-                list.push(e);
-                // next element start
-                element_start = element_value_start;
-            }
+            let (e, next_element_start) = element::<E>(slice, element_start)?;
+            list.push(e);
+            element_start = next_element_start;
         }
         Ok(list)
     }
 }
 
+impl<E> ColumnDecoder for VecDeque<E>
+where
+    E: ColumnDecoder,
+{
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        Ok(Vec::<E>::try_decode(slice)?.into())
+    }
+}
+
+impl<E, S> ColumnDecoder for HashSet<E, S>
+where
+    E: Eq + Hash + ColumnDecoder,
+    S: ::std::hash::BuildHasher + Default,
+{
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        // the sets are encoded like the lists
+        Ok(Vec::<E>::try_decode(slice)?.into_iter().collect())
+    }
+}
+
+impl<E> ColumnDecoder for BTreeSet<E>
+where
+    E: Ord + ColumnDecoder,
+{
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        Ok(Vec::<E>::try_decode(slice)?.into_iter().collect())
+    }
+}
+
 impl<K, V, S> ColumnDecoder for HashMap<K, V, S>
 where
     K: Eq + Hash + ColumnDecoder,
@@ -560,37 +681,147 @@ where
     S: ::std::hash::BuildHasher + Default,
 {
     fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
-        let map_len = i32::from_be_bytes(slice[0..4].try_into()?) as usize;
+        if slice.is_empty() {
+            return Ok(HashMap::default());
+        }
+        let map_len = collection_len(slice)?;
         let mut map: HashMap<K, V, S> = HashMap::default();
         let mut pair_start = 4;
         for _ in 0..map_len {
-            let k;
-            let v;
-            // decode key_byte_size
-            let length = i32::from_be_bytes(slice[pair_start..][..4].try_into()?) as usize;
-            pair_start += 4;
-            if length > 0 {
-                k = K::try_decode(&slice[pair_start..][..length])?;
-                // modify pair_start to be the vtype_start
-                pair_start += length;
-            } else {
-                k = K::try_decode(&[])?;
-            }
-            let length = i32::from_be_bytes(slice[pair_start..][..4].try_into()?) as usize;
-            pair_start += 4;
-            if length > 0 {
-                v = V::try_decode(&slice[pair_start..][..length])?;
-                pair_start += length;
-            } else {
-                v = V::try_decode(&[])?;
-            }
-            // insert key,value
+            let (k, v_start) = element::<K>(slice, pair_start)?;
+            let (v, next_pair_start) = element::<V>(slice, v_start)?;
+            map.insert(k, v);
+            pair_start = next_pair_start;
+        }
+        Ok(map)
+    }
+}
+
+impl<K, V> ColumnDecoder for BTreeMap<K, V>
+where
+    K: Ord + ColumnDecoder,
+    V: ColumnDecoder,
+{
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        if slice.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        let map_len = collection_len(slice)?;
+        let mut map = BTreeMap::new();
+        let mut pair_start = 4;
+        for _ in 0..map_len {
+            let (k, v_start) = element::<K>(slice, pair_start)?;
+            let (v, next_pair_start) = element::<V>(slice, v_start)?;
             map.insert(k, v);
+            pair_start = next_pair_start;
         }
         Ok(map)
     }
 }
 
+/// Decode the `[int]` number of elements of the collection which starts the slice
+fn collection_len(slice: &[u8]) -> anyhow::Result<usize> {
+    ensure!(slice.len() >= 4, "Buffer is too small!");
+    let len = i32::from_be_bytes(slice[0..4].try_into()?);
+    ensure!(len >= 0, "Invalid collection length: {}", len);
+    Ok(len as usize)
+}
+
+/// Decode the `[bytes]` element which starts at the provided index of the slice,
+/// returns the element along with the start of the next one.
+fn element<T: ColumnDecoder>(slice: &[u8], start: usize) -> anyhow::Result<(T, usize)> {
+    let value_start = start + 4;
+    ensure!(slice.len() >= value_start, "Buffer is too small!");
+    let length = i32::from_be_bytes(slice[start..value_start].try_into()?);
+    if length > 0 {
+        let end = value_start + length as usize;
+        ensure!(slice.len() >= end, "Buffer is too small!");
+        Ok((T::try_decode(&slice[value_start..end])?, end))
+    } else {
+        // empty or null element
+        Ok((T::try_decode(&[])?, value_start))
+    }
+}
+
+macro_rules! tuple {
+    ($($t:tt),*) => {
+        impl<$($t: ColumnDecoder),*> ColumnDecoder for ($($t,)*) {
+            #[allow(unused_assignments)]
+            fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+                let mut start = 0;
+                Ok((
+                    $(
+                        {
+                            let (e, next) = element::<$t>(slice, start)?;
+                            start = next;
+                            e
+                        },
+                    )*
+                ))
+            }
+        }
+    };
+}
+
+tuple!(T);
+tuple!(T, TT);
+tuple!(T, TT, TTT);
+tuple!(T, TT, TTT, TTTT);
+tuple!(T, TT, TTT, TTTT, TTTTT);
+tuple!(T, TT, TTT, TTTT, TTTTT, TTTTTT);
+tuple!(T, TT, TTT, TTTT, TTTTT, TTTTTT, TTTTTTT);
+tuple!(T, TT, TTT, TTTT, TTTTT, TTTTTT, TTTTTTT, TTTTTTTT);
+
+/// A wrapper which makes any decodable value usable as a `HashMap` key (ie frozen tuples with floats, or UDTs),
+/// the key is hashed and compared by its encoded bytes.
+#[derive(Clone, Debug)]
+pub struct HashableKey<T> {
+    bytes: Vec<u8>,
+    value: T,
+}
+
+impl<T> HashableKey<T> {
+    /// Get the encoded bytes of the key
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    /// Consume the wrapper and get the decoded key
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for HashableKey<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> PartialEq for HashableKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl<T> Eq for HashableKey<T> {}
+
+impl<T> Hash for HashableKey<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state)
+    }
+}
+
+impl<T: ColumnDecoder> ColumnDecoder for HashableKey<T> {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self {
+            bytes: slice.to_vec(),
+            value: T::try_decode(slice)?,
+        })
+    }
+}
+
 // helper types decoder functions
 /// Get the string list from a u8 slice.
 pub fn string_list(slice: &[u8]) -> anyhow::Result<Vec<String>> {
@@ -664,6 +895,34 @@ pub fn string_multimap(slice: &[u8]) -> anyhow::Result<HashMap<String, Vec<Strin
     Ok(multimap)
 }
 
+/// Get hashmap of string to bytes and the byte length from slice, where the null bytes are decoded as empty.
+pub fn bytes_map_with_returned_bytes_length(slice: &[u8]) -> anyhow::Result<(HashMap<String, Vec<u8>>, usize)> {
+    ensure!(slice.len() >= 2, "Buffer is too small!");
+    let length = u16::from_be_bytes(slice[0..2].try_into()?) as usize;
+    let mut map = HashMap::with_capacity(length);
+    let mut i = 2;
+    for _ in 0..length {
+        ensure!(slice.len() >= i + 2, "Buffer is too small!");
+        let key_len = u16::from_be_bytes(slice[i..(i + 2)].try_into()?) as usize;
+        ensure!(slice.len() >= i + 2 + key_len + 4, "Buffer is too small!");
+        let key = String::from_utf8_lossy(&slice[(i + 2)..(i + 2 + key_len)]).to_string();
+        // add [short] + string.len()
+        i += 2 + key_len;
+        let value_len = i32::from_be_bytes(slice[i..(i + 4)].try_into()?);
+        i += 4;
+        let value = if value_len > 0 {
+            ensure!(slice.len() >= i + value_len as usize, "Buffer is too small!");
+            let value = slice[i..(i + value_len as usize)].to_vec();
+            i += value_len as usize;
+            value
+        } else {
+            Vec::new()
+        };
+        map.insert(key, value);
+    }
+    Ok((map, i))
+}
+
 // Usefull for multimap.
 /// Get the string list and the byte length from slice.
 pub fn string_list_with_returned_bytes_length(slice: &[u8]) -> anyhow::Result<(Vec<String>, usize)> {
@@ -683,3 +942,153 @@ pub fn string_list_with_returned_bytes_length(slice: &[u8]) -> anyhow::Result<(V
     Ok((list, s))
 }
 // todo inet fn (with port).
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::compression::UNCOMPRESSED;
+
+    fn bytes(value: &[u8], buffer: &mut Vec<u8>) {
+        buffer.extend(&i32::to_be_bytes(value.len() as i32));
+        buffer.extend(value);
+    }
+
+    #[test]
+    fn nested_columns() {
+        // map<text, frozen<list<int>>>
+        let mut list = Vec::new();
+        list.extend(&i32::to_be_bytes(2));
+        bytes(&i32::to_be_bytes(1), &mut list);
+        bytes(&i32::to_be_bytes(2), &mut list);
+        let mut map = i32::to_be_bytes(2).to_vec();
+        bytes(b"a", &mut map);
+        bytes(&list, &mut map);
+        bytes(b"b", &mut map);
+        bytes(&i32::to_be_bytes(0), &mut map);
+        let decoded = HashMap::<String, Vec<i32>>::try_decode(&map).unwrap();
+        assert_eq!(decoded["a"], vec![1, 2]);
+        assert!(decoded["b"].is_empty());
+        let decoded = BTreeMap::<String, BTreeSet<i32>>::try_decode(&map).unwrap();
+        assert_eq!(decoded["a"], BTreeSet::from([1, 2]));
+        assert_eq!(HashSet::<i32>::try_decode(&list).unwrap(), HashSet::from([1, 2]));
+        assert_eq!(VecDeque::<i32>::try_decode(&list).unwrap(), VecDeque::from(vec![1, 2]));
+        // frozen<tuple<text, list<int>, int>>, where the last element is null
+        let mut tuple = Vec::new();
+        bytes(b"a", &mut tuple);
+        bytes(&list, &mut tuple);
+        tuple.extend(&i32::to_be_bytes(-1));
+        let decoded = <(String, Vec<i32>, Option<i32>)>::try_decode(&tuple).unwrap();
+        assert_eq!(decoded, ("a".to_string(), vec![1, 2], None));
+        // list<frozen<tuple<int, text>>>, whose null element is decoded as None
+        let mut element = Vec::new();
+        bytes(&i32::to_be_bytes(7), &mut element);
+        bytes(b"x", &mut element);
+        let mut tuples = i32::to_be_bytes(2).to_vec();
+        bytes(&element, &mut tuples);
+        tuples.extend(&i32::to_be_bytes(-1));
+        let decoded = Vec::<Option<(i32, String)>>::try_decode(&tuples).unwrap();
+        assert_eq!(decoded, vec![Some((7, "x".to_string())), None]);
+        // the truncated or malformed collections are errors rather than panics
+        assert!(Vec::<Vec<i32>>::try_decode(&tuples[..tuples.len() - 6]).is_err());
+        assert!(Vec::<i32>::try_decode(&i32::to_be_bytes(-2)).is_err());
+        assert!(HashMap::<String, Vec<i32>>::try_decode(&map[..10]).is_err());
+        assert!(<(String, Vec<i32>)>::try_decode(&tuple[..7]).is_err());
+    }
+
+    #[test]
+    fn custom_payload() {
+        let mut buffer = vec![0x84, header::CUSTOM_PAYLOAD | header::WARNING, 0, 1, opcode::RESULT];
+        let mut body = Vec::new();
+        // warnings
+        body.extend(&u16::to_be_bytes(1));
+        body.extend(&u16::to_be_bytes(4));
+        body.extend(b"warn");
+        // custom payload
+        body.extend(&u16::to_be_bytes(2));
+        body.extend(&u16::to_be_bytes(3));
+        body.extend(b"key");
+        bytes(b"value", &mut body);
+        body.extend(&u16::to_be_bytes(4));
+        body.extend(b"null");
+        body.extend(&i32::to_be_bytes(-1));
+        // void result
+        body.extend(&result::VOID.to_be_bytes());
+        buffer.extend(&i32::to_be_bytes(body.len() as i32));
+        buffer.extend(body);
+
+        let mut decoder = Decoder::new(buffer, UNCOMPRESSED).unwrap();
+        assert!(decoder.is_void().unwrap());
+        assert_eq!(decoder.warnings(), ["warn"]);
+        assert_eq!(decoder.take_warnings(), Some(vec!["warn".to_string()]));
+        assert!(decoder.warnings().is_empty());
+        let custom_payload = decoder.take_custom_payload().unwrap();
+        assert_eq!(custom_payload.get("key").unwrap(), b"value");
+        assert!(custom_payload.get("null").unwrap().is_empty());
+    }
+
+    fn lwt_result(row: &[Option<&[u8]>]) -> Decoder {
+        let mut buffer = vec![0x84, 0, 0, 1, opcode::RESULT];
+        let mut body = result::ROWS.to_be_bytes().to_vec();
+        // no metadata flag, columns count, rows count
+        body.extend(&i32::to_be_bytes(4));
+        body.extend(&i32::to_be_bytes(row.len() as i32));
+        body.extend(&i32::to_be_bytes(1));
+        for value in row {
+            match value {
+                Some(value) => bytes(value, &mut body),
+                None => body.extend(&i32::to_be_bytes(-1)),
+            }
+        }
+        buffer.extend(&i32::to_be_bytes(body.len() as i32));
+        buffer.extend(body);
+        Decoder::new(buffer, UNCOMPRESSED).unwrap()
+    }
+
+    #[test]
+    fn lwt_results() {
+        struct Keyspace;
+        impl LwtDecoder<i32, (i32, String)> for Keyspace {}
+        let applied = Keyspace::try_decode(lwt_result(&[Some(&[1])])).unwrap();
+        assert_eq!(
+            applied,
+            LwtResult {
+                applied: true,
+                existing: None
+            }
+        );
+        // scylla returns the columns of the row even when the statement is applied
+        let applied = Keyspace::try_decode(lwt_result(&[Some(&[1]), None, None])).unwrap();
+        assert!(applied.applied && applied.existing.is_none());
+        let conflict = lwt_result(&[Some(&[0]), Some(&i32::to_be_bytes(7)), Some(b"value")]);
+        assert_eq!(
+            Keyspace::try_decode(conflict).unwrap(),
+            LwtResult {
+                applied: false,
+                existing: Some((7, "value".to_string()))
+            }
+        );
+        let missing = Keyspace::try_decode(lwt_result(&[Some(&[0])])).unwrap();
+        assert!(!missing.applied && missing.existing.is_none());
+    }
+
+    #[test]
+    fn tuple_map_keys() {
+        // map<frozen<tuple<int, float>>, text> with a single pair
+        let mut key = Vec::new();
+        bytes(&i32::to_be_bytes(1), &mut key);
+        bytes(&f32::to_be_bytes(0.5), &mut key);
+        let mut map = i32::to_be_bytes(1).to_vec();
+        bytes(&key, &mut map);
+        bytes(b"value", &mut map);
+
+        let hashed = HashMap::<HashableKey<(i32, f32)>, String>::try_decode(&map).unwrap();
+        let (k, v) = hashed.into_iter().next().unwrap();
+        assert_eq!(k.into_inner(), (1, 0.5));
+        assert_eq!(v, "value");
+        let ordered = BTreeMap::<(i32, i32), String>::try_decode(&map).unwrap();
+        assert_eq!(
+            ordered.get(&(1, i32::from_be_bytes(0.5f32.to_be_bytes()))).unwrap(),
+            "value"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the cql `duration` column, which is encoded as three zigzag encoded vints.
+
+use super::{decoder::ColumnDecoder, encoder::ColumnEncoder};
+use anyhow::{bail, ensure};
+use std::{convert::TryFrom, time::Duration};
+
+/// A cql `duration` column, ie a number of months, days and nanoseconds, which can't be normalized into each other
+/// as the months and the days don't have a fixed length. The three components must share the same sign.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CqlDuration {
+    /// The number of months
+    pub months: i32,
+    /// The number of days
+    pub days: i32,
+    /// The number of nanoseconds
+    pub nanos: i64,
+}
+
+impl CqlDuration {
+    /// Create a duration
+    pub fn new(months: i32, days: i32, nanos: i64) -> Self {
+        Self { months, days, nanos }
+    }
+}
+
+impl From<Duration> for CqlDuration {
+    fn from(duration: Duration) -> Self {
+        Self::new(0, 0, duration.as_nanos() as i64)
+    }
+}
+
+impl TryFrom<CqlDuration> for Duration {
+    type Error = anyhow::Error;
+
+    fn try_from(duration: CqlDuration) -> Result<Self, Self::Error> {
+        ensure!(
+            duration.months == 0 && duration.days == 0 && duration.nanos >= 0,
+            "The duration {:?} has no fixed length",
+            duration
+        );
+        Ok(Duration::from_nanos(duration.nanos as u64))
+    }
+}
+
+impl ColumnEncoder for CqlDuration {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let mut value = Vec::with_capacity(3 * 9);
+        encode_vint(self.months as i64, &mut value);
+        encode_vint(self.days as i64, &mut value);
+        encode_vint(self.nanos, &mut value);
+        buffer.extend(&i32::to_be_bytes(value.len() as i32));
+        buffer.extend(value);
+    }
+}
+
+impl ColumnDecoder for CqlDuration {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        let mut slice = slice;
+        let months = decode_vint(&mut slice)?;
+        let days = decode_vint(&mut slice)?;
+        let nanos = decode_vint(&mut slice)?;
+        ensure!(slice.is_empty(), "Invalid duration: {} trailing bytes", slice.len());
+        Ok(Self::new(i32::try_from(months)?, i32::try_from(days)?, nanos))
+    }
+}
+
+/// Encode the signed vint, ie the zigzag encoded value whose first byte holds the number of extra bytes as its
+/// leading ones
+fn encode_vint(value: i64, buffer: &mut Vec<u8>) {
+    let value = ((value << 1) ^ (value >> 63)) as u64;
+    let magnitude = 64 - (value | 1).leading_zeros() as usize;
+    // every extra byte holds 8 bits, and takes a bit of the first byte
+    let extra_bytes = (magnitude.div_ceil(7) - 1).min(8);
+    if extra_bytes == 8 {
+        buffer.push(0xFF);
+        buffer.extend(&value.to_be_bytes());
+    } else {
+        let bytes = value.to_be_bytes();
+        let mut encoded = bytes[7 - extra_bytes..].to_vec();
+        encoded[0] |= !(0xFF >> extra_bytes);
+        buffer.extend(encoded);
+    }
+}
+
+/// Decode the signed vint from the head of the slice, which is advanced past it
+fn decode_vint(slice: &mut &[u8]) -> anyhow::Result<i64> {
+    let (first, rest) = match slice.split_first() {
+        Some(split) => split,
+        None => bail!("Invalid duration: missing vint"),
+    };
+    let extra_bytes = first.leading_ones() as usize;
+    ensure!(rest.len() >= extra_bytes, "Invalid duration: truncated vint");
+    let mut value = (*first as u64) & (0xFF >> extra_bytes);
+    for byte in rest[..extra_bytes].iter() {
+        value = (value << 8) | *byte as u64;
+    }
+    *slice = &rest[extra_bytes..];
+    Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vints() {
+        for value in [0, 1, -1, 63, -64, 64, 8191, -8192, 1 << 40, i64::MAX, i64::MIN] {
+            let mut buffer = Vec::new();
+            encode_vint(value, &mut buffer);
+            let mut slice = &buffer[..];
+            assert_eq!(decode_vint(&mut slice).unwrap(), value);
+            assert!(slice.is_empty());
+        }
+        let mut buffer = Vec::new();
+        encode_vint(64, &mut buffer);
+        assert_eq!(buffer, vec![0x80, 0x80]);
+    }
+
+    #[test]
+    fn duration_column() {
+        let duration = CqlDuration::new(-1, -2, -3_000_000_000);
+        assert_eq!(
+            duration.encode_new(),
+            vec![0, 0, 0, 7, 1, 3, 0xF1, 0x65, 0xA0, 0xBB, 0xFF]
+        );
+        assert_eq!(CqlDuration::try_decode(&duration.encode_new()[4..]).unwrap(), duration);
+        assert!(CqlDuration::try_decode(&[2, 4]).is_err());
+        let duration = CqlDuration::from(Duration::from_millis(1_500));
+        assert_eq!(Duration::try_from(duration).unwrap(), Duration::from_millis(1_500));
+        assert!(Duration::try_from(CqlDuration::new(1, 0, 0)).is_err());
+    }
+}
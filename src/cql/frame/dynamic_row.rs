@@ -0,0 +1,178 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the dynamic rows, whose columns are looked up by their names out of the rows metadata,
+//! ie for the generic tooling which doesn't know the schema of the queried tables beforehand.
+
+use super::{
+    decoder::{ColumnDecoder, Decoder, Frame},
+    rows::ColumnSpec,
+    CqlValue,
+};
+use anyhow::{anyhow, ensure};
+use std::{convert::TryInto, sync::Arc};
+
+/// A row of a RESULT frame, whose columns are looked up by their names.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::{Decoder, DynamicRow};
+/// use std::convert::TryFrom;
+///
+/// // the rows of `SELECT id FROM ks.t`, along with their metadata
+/// let mut frame = vec![132, 0, 0, 0, 8, 0, 0, 0, 37, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 1];
+/// frame.extend(&[0, 2, b'k', b's', 0, 1, b't', 0, 2, b'i', b'd', 0, 9]);
+/// frame.extend(&[0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 7]);
+/// let rows = DynamicRow::rows(&Decoder::try_from(frame)?)?;
+/// assert_eq!(rows[0].get::<i32>("id")?, 7);
+/// assert_eq!(rows[0].columns()[0].table, "t");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicRow {
+    columns: Arc<[ColumnSpec]>,
+    values: Vec<Option<Vec<u8>>>,
+}
+
+impl DynamicRow {
+    /// Decode the rows of the RESULT frame, whose metadata must hold the column specs, ie the response of a
+    /// request which was sent without the `SKIP_METADATA` flag.
+    pub fn rows(decoder: &Decoder) -> anyhow::Result<Vec<Self>> {
+        ensure!(decoder.is_rows()?, "Decoded response is not rows!");
+        let metadata = decoder.metadata()?;
+        ensure!(
+            metadata.columns().len() == metadata.columns_count().max(0) as usize,
+            "The rows metadata doesn't hold the column specs"
+        );
+        let columns: Arc<[ColumnSpec]> = metadata.columns().into();
+        let buffer = decoder.buffer_as_ref();
+        let int = |pos: usize| -> anyhow::Result<i32> {
+            let bytes = buffer
+                .get(pos..pos + 4)
+                .ok_or_else(|| anyhow!("Buffer is too small!"))?;
+            Ok(i32::from_be_bytes(bytes.try_into()?))
+        };
+        let mut pos = metadata.rows_start();
+        let rows_count = int(pos)?.max(0) as usize;
+        pos += 4;
+        // don't trust the rows count for the allocation
+        let mut rows = Vec::with_capacity(rows_count.min(1024));
+        for _ in 0..rows_count {
+            let mut values = Vec::with_capacity(columns.len());
+            for _ in 0..columns.len() {
+                let len = int(pos)?;
+                pos += 4;
+                if len < 0 {
+                    values.push(None);
+                } else {
+                    let value = buffer
+                        .get(pos..pos + len as usize)
+                        .ok_or_else(|| anyhow!("Buffer is too small!"))?;
+                    values.push(Some(value.to_vec()));
+                    pos += len as usize;
+                }
+            }
+            rows.push(Self {
+                columns: columns.clone(),
+                values,
+            });
+        }
+        Ok(rows)
+    }
+    /// Get the column specs of the row
+    pub fn columns(&self) -> &[ColumnSpec] {
+        &self.columns
+    }
+    /// Get the index of the column
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column.name == name)
+    }
+    /// Decode the value of the column, where the nulls are decoded as empty values, ie `Option::None`
+    pub fn get<T: ColumnDecoder>(&self, name: &str) -> anyhow::Result<T> {
+        let index = self.index_of(name).ok_or_else(|| anyhow!("Unknown column: {}", name))?;
+        self.get_by_index(index)
+    }
+    /// Decode the value of the column at the index
+    pub fn get_by_index<T: ColumnDecoder>(&self, index: usize) -> anyhow::Result<T> {
+        let value = self
+            .values
+            .get(index)
+            .ok_or_else(|| anyhow!("Column index {} is out of range", index))?;
+        T::try_decode(value.as_deref().unwrap_or(&[]))
+    }
+    /// Decode the dynamically typed value of the column, according to its type
+    pub fn value(&self, name: &str) -> anyhow::Result<CqlValue> {
+        let index = self.index_of(name).ok_or_else(|| anyhow!("Unknown column: {}", name))?;
+        self.value_by_index(index)
+    }
+    /// Decode the dynamically typed value of the column at the index, according to its type
+    pub fn value_by_index(&self, index: usize) -> anyhow::Result<CqlValue> {
+        let column = self
+            .columns
+            .get(index)
+            .ok_or_else(|| anyhow!("Column index {} is out of range", index))?;
+        match &self.values[index] {
+            Some(value) => CqlValue::try_decode(&column.cql_type, value),
+            None => Ok(CqlValue::Null),
+        }
+    }
+    /// Get the raw value of the column at the index, None if it's null
+    pub fn raw(&self, index: usize) -> Option<&[u8]> {
+        self.values.get(index)?.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::{CqlType, Iter, Rows, RowsStream};
+    use std::convert::TryFrom;
+
+    fn string(buffer: &mut Vec<u8>, string: &str) {
+        buffer.extend(&u16::to_be_bytes(string.len() as u16));
+        buffer.extend(string.as_bytes());
+    }
+
+    /// The frame of `SELECT id, tags FROM shop.orders`, which holds the column specs
+    fn frame() -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 2];
+        for (name, cql_type) in [("id", &[0, 9][..]), ("tags", &[0, 0x20, 0, 0x0D][..])] {
+            string(&mut body, "shop");
+            string(&mut body, "orders");
+            string(&mut body, name);
+            body.extend(cql_type);
+        }
+        body.extend(&[0, 0, 0, 1]);
+        body.extend(&[0, 0, 0, 4, 0, 0, 0, 7]);
+        body.extend(&[0, 0, 0, 13, 0, 0, 0, 2, 0, 0, 0, 1, b'a', 255, 255, 255, 255]);
+        let mut frame = vec![132, 0, 0, 0, 8];
+        frame.extend(&i32::to_be_bytes(body.len() as i32));
+        frame.extend(body);
+        frame
+    }
+
+    #[test]
+    fn dynamic_rows() {
+        let decoder = Decoder::try_from(frame()).unwrap();
+        let metadata = decoder.metadata().unwrap();
+        assert_eq!(metadata.column_index("tags"), Some(1));
+        assert_eq!(metadata.columns()[1].cql_type, CqlType::List(Box::new(CqlType::Text)));
+        let rows = DynamicRow::rows(&decoder).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<i32>("id").unwrap(), 7);
+        assert_eq!(
+            rows[0].value("tags").unwrap(),
+            CqlValue::List(vec![CqlValue::Text("a".to_string()), CqlValue::Null])
+        );
+        assert!(rows[0].get::<i32>("total").is_err());
+        // the typed rows start after the column specs
+        let mut iter = Iter::<(i32, Vec<Option<String>>)>::new(decoder).unwrap();
+        assert_eq!(iter.next(), Some((7, vec![Some("a".to_string()), None])));
+        let mut stream = RowsStream::<(i32,)>::new();
+        stream.feed(&frame()[..20]);
+        assert!(stream.next_row().unwrap().is_none());
+        stream.feed(&frame()[20..]);
+        assert_eq!(stream.next_row().unwrap(), Some((7,)));
+        assert_eq!(stream.metadata().unwrap().columns().len(), 2);
+    }
+}
@@ -4,7 +4,8 @@
 //! This module implements the frame encoder.
 
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     io::Cursor,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
 };
@@ -42,7 +43,7 @@ pub struct ColumnEncodeChain {
 
 impl ColumnEncodeChain {
     /// Chain a new column
-    pub fn chain<T: ColumnEncoder>(mut self, other: &T) -> Self {
+    pub fn chain<T: ColumnEncoder + ?Sized>(mut self, other: &T) -> Self {
         other.encode(&mut self.buffer);
         self
     }
@@ -58,6 +59,12 @@ pub trait ColumnEncoder {
     /// Encoder the column buffer.
     fn encode(&self, buffer: &mut Vec<u8>);
 
+    /// Encode the value as an element of a collection, a tuple or a UDT, where the unset values are invalid, so
+    /// the `None` options are encoded as null rather than unset.
+    fn encode_element(&self, buffer: &mut Vec<u8>) {
+        self.encode(buffer)
+    }
+
     /// Encode this value to a new buffer
     fn encode_new(&self) -> Vec<u8> {
         let mut buf = Vec::new();
@@ -73,7 +80,7 @@ pub trait ColumnEncoder {
     }
 
     /// Start an encoding chain
-    fn chain_encode<T: ColumnEncoder>(&self, other: &T) -> ColumnEncodeChain {
+    fn chain_encode<T: ColumnEncoder + ?Sized>(&self, other: &T) -> ColumnEncodeChain {
         let buffer = self.encode_new();
         ColumnEncodeChain { buffer }.chain(other)
     }
@@ -89,6 +96,12 @@ where
             None => ColumnEncoder::encode(&UNSET_VALUE, buffer),
         }
     }
+    fn encode_element(&self, buffer: &mut Vec<u8>) {
+        match self {
+            Some(value) => value.encode_element(buffer),
+            None => ColumnEncoder::encode(&NULL_VALUE, buffer),
+        }
+    }
 }
 
 impl ColumnEncoder for i64 {
@@ -174,16 +187,46 @@ impl ColumnEncoder for String {
         buffer.extend(self.bytes());
     }
 }
-impl ColumnEncoder for &str {
+impl ColumnEncoder for str {
     fn encode(&self, buffer: &mut Vec<u8>) {
         buffer.extend(&i32::to_be_bytes(self.len() as i32));
         buffer.extend(self.bytes());
     }
 }
-impl ColumnEncoder for &[u8] {
+impl ColumnEncoder for [u8] {
     fn encode(&self, buffer: &mut Vec<u8>) {
         buffer.extend(&i32::to_be_bytes(self.len() as i32));
-        buffer.extend(*self);
+        buffer.extend(self);
+    }
+}
+
+impl<T: ColumnEncoder + ?Sized> ColumnEncoder for &T {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        T::encode(*self, buffer)
+    }
+    fn encode_element(&self, buffer: &mut Vec<u8>) {
+        T::encode_element(*self, buffer)
+    }
+}
+
+impl<T: ColumnEncoder + ?Sized> ColumnEncoder for Box<T> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        T::encode(self, buffer)
+    }
+    fn encode_element(&self, buffer: &mut Vec<u8>) {
+        T::encode_element(self, buffer)
+    }
+}
+
+impl<B> ColumnEncoder for Cow<'_, B>
+where
+    B: ColumnEncoder + ToOwned + ?Sized,
+{
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        B::encode(self, buffer)
+    }
+    fn encode_element(&self, buffer: &mut Vec<u8>) {
+        B::encode_element(self, buffer)
     }
 }
 
@@ -229,18 +272,45 @@ where
     E: ColumnEncoder,
 {
     fn encode(&self, buffer: &mut Vec<u8>) {
-        // total byte_size of the list is unknown,
-        // therefore we pad zero length for now.
-        buffer.extend(&BE_0_BYTES_LEN);
-        // in order to compute the byte_size we snapshot
-        // the current buffer length in advance
-        let current_length = buffer.len();
-        buffer.extend(&i32::to_be_bytes(self.len() as i32));
-        for e in self {
-            e.encode(buffer);
-        }
-        let list_byte_size = buffer.len() - current_length;
-        buffer[(current_length - 4)..current_length].copy_from_slice(&i32::to_be_bytes(list_byte_size as i32));
+        encode_list(self.len(), self, buffer)
+    }
+}
+
+impl<E, const N: usize> ColumnEncoder for [E; N]
+where
+    E: ColumnEncoder,
+{
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        // encoded as a list, like a Vec<E>
+        encode_list(N, self, buffer)
+    }
+}
+
+impl<E> ColumnEncoder for VecDeque<E>
+where
+    E: ColumnEncoder,
+{
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        encode_list(self.len(), self, buffer)
+    }
+}
+
+impl<E, S: ::std::hash::BuildHasher> ColumnEncoder for HashSet<E, S>
+where
+    E: ColumnEncoder,
+{
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        // the sets are encoded like the lists
+        encode_list(self.len(), self, buffer)
+    }
+}
+
+impl<E> ColumnEncoder for BTreeSet<E>
+where
+    E: ColumnEncoder,
+{
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        encode_list(self.len(), self, buffer)
     }
 }
 
@@ -250,18 +320,53 @@ where
     V: ColumnEncoder,
 {
     fn encode(&self, buffer: &mut Vec<u8>) {
-        buffer.extend(&BE_0_BYTES_LEN);
-        let current_length = buffer.len();
-        buffer.extend(&i32::to_be_bytes(self.len() as i32));
-        for (k, v) in self {
-            k.encode(buffer);
-            v.encode(buffer);
-        }
-        let map_byte_size = buffer.len() - current_length;
-        buffer[(current_length - 4)..current_length].copy_from_slice(&i32::to_be_bytes(map_byte_size as i32));
+        encode_map(self.len(), self, buffer)
     }
 }
 
+impl<K, V> ColumnEncoder for BTreeMap<K, V>
+where
+    K: ColumnEncoder,
+    V: ColumnEncoder,
+{
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        encode_map(self.len(), self, buffer)
+    }
+}
+
+/// Encode the elements of the list or the set, along with its byte size and its number of elements
+fn encode_list<'a, E: ColumnEncoder + 'a>(len: usize, elements: impl IntoIterator<Item = &'a E>, buffer: &mut Vec<u8>) {
+    // total byte_size of the list is unknown,
+    // therefore we pad zero length for now.
+    buffer.extend(&BE_0_BYTES_LEN);
+    // in order to compute the byte_size we snapshot
+    // the current buffer length in advance
+    let current_length = buffer.len();
+    buffer.extend(&i32::to_be_bytes(len as i32));
+    for e in elements {
+        e.encode_element(buffer);
+    }
+    let list_byte_size = buffer.len() - current_length;
+    buffer[(current_length - 4)..current_length].copy_from_slice(&i32::to_be_bytes(list_byte_size as i32));
+}
+
+/// Encode the pairs of the map, along with its byte size and its number of pairs
+fn encode_map<'a, K: ColumnEncoder + 'a, V: ColumnEncoder + 'a>(
+    len: usize,
+    pairs: impl IntoIterator<Item = (&'a K, &'a V)>,
+    buffer: &mut Vec<u8>,
+) {
+    buffer.extend(&BE_0_BYTES_LEN);
+    let current_length = buffer.len();
+    buffer.extend(&i32::to_be_bytes(len as i32));
+    for (k, v) in pairs {
+        k.encode_element(buffer);
+        v.encode_element(buffer);
+    }
+    let map_byte_size = buffer.len() - current_length;
+    buffer[(current_length - 4)..current_length].copy_from_slice(&i32::to_be_bytes(map_byte_size as i32));
+}
+
 impl ColumnEncoder for Unset {
     fn encode(&self, buffer: &mut Vec<u8>) {
         buffer.extend(&BE_UNSET_BYTES_LEN);
@@ -281,7 +386,7 @@ pub struct TokenEncodeChain {
 
 impl TokenEncodeChain {
     /// Chain a new value
-    pub fn chain<T: TokenEncoder>(mut self, other: &T) -> Self {
+    pub fn chain<T: TokenEncoder + ?Sized>(mut self, other: &T) -> Self {
         self.buffer.extend_from_slice(other.encode_new()[2..].into());
         self.buffer.push(0);
         self
@@ -289,7 +394,7 @@ impl TokenEncodeChain {
 
     /// Complete the chain and return the token
     pub fn finish(self) -> i64 {
-        crate::cql::murmur3_cassandra_x64_128(&self.buffer, 0).0
+        crate::cql::murmur3_token(&self.buffer)
     }
 }
 
@@ -297,15 +402,51 @@ impl TokenEncodeChain {
 pub trait TokenEncoder: ColumnEncoder {
     /// Encode a single token
     fn get_token(&self) -> i64 {
-        crate::cql::murmur3_cassandra_x64_128(&self.encode_new()[4..], 0).0
+        crate::cql::murmur3_token(&self.encode_new()[4..])
     }
 
     /// Start an encode chain
-    fn chain_token<T: TokenEncoder>(&self, other: &T) -> TokenEncodeChain {
+    fn chain_token<T: TokenEncoder + ?Sized>(&self, other: &T) -> TokenEncodeChain {
         let mut buffer: Vec<u8> = self.encode_new()[2..].into();
         buffer.push(0);
         TokenEncodeChain { buffer }.chain(other)
     }
 }
 
-impl<T: ColumnEncoder> TokenEncoder for T {}
+impl<T: ColumnEncoder + ?Sized> TokenEncoder for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borrowed_encoders() {
+        let owned = "key".to_string().encode_new();
+        assert_eq!("key".encode_new(), owned);
+        assert_eq!(Cow::Borrowed("key").encode_new(), owned);
+        assert_eq!(Box::<str>::from("key").encode_new(), owned);
+        assert_eq!([1u8, 2][..].encode_new(), vec![0, 0, 0, 2, 1, 2]);
+        assert_eq!([1i32, 2].encode_new(), vec![1i32, 2].encode_new());
+    }
+
+    #[test]
+    fn collection_encoders() {
+        let list = vec![1i32, 2].encode_new();
+        assert_eq!(VecDeque::from(vec![1i32, 2]).encode_new(), list);
+        assert_eq!(BTreeSet::from([2i32, 1]).encode_new(), list);
+        assert_eq!(HashSet::<i32>::from([1]).encode_new(), vec![1i32].encode_new());
+        let map = BTreeMap::from([("a", 1i32), ("b", 2)]);
+        let mut expected = vec![0, 0, 0, 30, 0, 0, 0, 2];
+        for (k, v) in map.iter() {
+            k.encode(&mut expected);
+            v.encode(&mut expected);
+        }
+        assert_eq!(map.encode_new(), expected);
+        // the None elements are null rather than unset
+        assert_eq!(None::<i32>.encode_new(), BE_UNSET_BYTES_LEN);
+        assert_eq!(
+            vec![Some(1i32), None].encode_new(),
+            vec![0, 0, 0, 16, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0, 1, 255, 255, 255, 255]
+        );
+    }
+}
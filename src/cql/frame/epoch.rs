@@ -0,0 +1,252 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the epoch based timestamp, date and time columns, which are bound and decoded without any
+//! date/time dependency, along with their chrono interop behind the `chrono` feature.
+
+use super::{decoder::ColumnDecoder, encoder::ColumnEncoder, encoder::BE_4_BYTES_LEN, encoder::BE_8_BYTES_LEN};
+use anyhow::ensure;
+use std::{
+    convert::TryInto,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The date of the unix epoch, as encoded by the cql date type
+const EPOCH_DATE: i64 = 1 << 31;
+/// The number of nanoseconds in a day
+const DAY_NANOS: i64 = 86_400_000_000_000;
+
+/// A cql `timestamp` column, ie the number of milliseconds since the unix epoch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EpochMillis(pub i64);
+
+impl EpochMillis {
+    /// Get the current timestamp
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+}
+
+impl From<SystemTime> for EpochMillis {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => Self(duration.as_millis() as i64),
+            Err(e) => Self(-(e.duration().as_millis() as i64)),
+        }
+    }
+}
+
+impl From<EpochMillis> for SystemTime {
+    fn from(millis: EpochMillis) -> Self {
+        let duration = Duration::from_millis(millis.0.unsigned_abs());
+        if millis.0 >= 0 {
+            UNIX_EPOCH + duration
+        } else {
+            UNIX_EPOCH - duration
+        }
+    }
+}
+
+impl ColumnEncoder for EpochMillis {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(&BE_8_BYTES_LEN);
+        buffer.extend(&i64::to_be_bytes(self.0));
+    }
+}
+
+impl ColumnDecoder for EpochMillis {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self(i64::from_be_bytes(slice.try_into()?)))
+    }
+}
+
+/// A cql `date` column, ie the number of days since the unix epoch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EpochDays(pub i32);
+
+impl EpochDays {
+    /// Get the current date
+    pub fn today() -> Self {
+        Self((EpochMillis::now().0.div_euclid(86_400_000)) as i32)
+    }
+}
+
+impl ColumnEncoder for EpochDays {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(&BE_4_BYTES_LEN);
+        buffer.extend(&u32::to_be_bytes((self.0 as i64 + EPOCH_DATE) as u32));
+    }
+}
+
+impl ColumnDecoder for EpochDays {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self((u32::from_be_bytes(slice.try_into()?) as i64 - EPOCH_DATE) as i32))
+    }
+}
+
+/// A cql `time` column, ie the number of nanoseconds since midnight
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DayNanos(pub i64);
+
+impl DayNanos {
+    /// Get the current time of the day
+    pub fn now() -> Self {
+        Self(EpochMillis::now().0.rem_euclid(86_400_000) * 1_000_000)
+    }
+}
+
+impl From<Duration> for DayNanos {
+    fn from(duration: Duration) -> Self {
+        Self(duration.as_nanos() as i64 % DAY_NANOS)
+    }
+}
+
+impl ColumnEncoder for DayNanos {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(&BE_8_BYTES_LEN);
+        buffer.extend(&i64::to_be_bytes(self.0));
+    }
+}
+
+impl ColumnDecoder for DayNanos {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        let nanos = i64::from_be_bytes(slice.try_into()?);
+        ensure!((0..DAY_NANOS).contains(&nanos), "Invalid time of the day: {}ns", nanos);
+        Ok(Self(nanos))
+    }
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_interop {
+    use super::*;
+    use anyhow::anyhow;
+    use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Timelike, Utc};
+    use std::convert::TryFrom;
+
+    /// The number of days from the common era to the unix epoch
+    const EPOCH_DAYS_FROM_CE: i32 = 719_163;
+
+    impl From<DateTime<Utc>> for EpochMillis {
+        fn from(time: DateTime<Utc>) -> Self {
+            Self(time.timestamp_millis())
+        }
+    }
+
+    impl TryFrom<EpochMillis> for DateTime<Utc> {
+        type Error = anyhow::Error;
+
+        fn try_from(millis: EpochMillis) -> Result<Self, Self::Error> {
+            DateTime::from_timestamp_millis(millis.0).ok_or_else(|| anyhow!("Timestamp out of range: {}ms", millis.0))
+        }
+    }
+
+    impl From<NaiveDate> for EpochDays {
+        fn from(date: NaiveDate) -> Self {
+            Self(date.num_days_from_ce() - EPOCH_DAYS_FROM_CE)
+        }
+    }
+
+    impl TryFrom<EpochDays> for NaiveDate {
+        type Error = anyhow::Error;
+
+        fn try_from(days: EpochDays) -> Result<Self, Self::Error> {
+            days.0
+                .checked_add(EPOCH_DAYS_FROM_CE)
+                .and_then(NaiveDate::from_num_days_from_ce_opt)
+                .ok_or_else(|| anyhow!("Date out of range: {} days", days.0))
+        }
+    }
+
+    impl From<NaiveTime> for DayNanos {
+        fn from(time: NaiveTime) -> Self {
+            // the leap seconds are folded into the last second of the day
+            let nanos = time.nanosecond().min(999_999_999) as i64;
+            Self(time.num_seconds_from_midnight() as i64 * 1_000_000_000 + nanos)
+        }
+    }
+
+    impl TryFrom<DayNanos> for NaiveTime {
+        type Error = anyhow::Error;
+
+        fn try_from(nanos: DayNanos) -> Result<Self, Self::Error> {
+            ensure!(
+                (0..DAY_NANOS).contains(&nanos.0),
+                "Invalid time of the day: {}ns",
+                nanos.0
+            );
+            NaiveTime::from_num_seconds_from_midnight_opt(
+                (nanos.0 / 1_000_000_000) as u32,
+                (nanos.0 % 1_000_000_000) as u32,
+            )
+            .ok_or_else(|| anyhow!("Invalid time of the day: {}ns", nanos.0))
+        }
+    }
+
+    impl ColumnEncoder for DateTime<Utc> {
+        fn encode(&self, buffer: &mut Vec<u8>) {
+            EpochMillis::from(*self).encode(buffer)
+        }
+    }
+
+    impl ColumnDecoder for DateTime<Utc> {
+        fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+            EpochMillis::try_decode(slice)?.try_into()
+        }
+    }
+
+    impl ColumnEncoder for NaiveDate {
+        fn encode(&self, buffer: &mut Vec<u8>) {
+            EpochDays::from(*self).encode(buffer)
+        }
+    }
+
+    impl ColumnDecoder for NaiveDate {
+        fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+            EpochDays::try_decode(slice)?.try_into()
+        }
+    }
+
+    impl ColumnEncoder for NaiveTime {
+        fn encode(&self, buffer: &mut Vec<u8>) {
+            DayNanos::from(*self).encode(buffer)
+        }
+    }
+
+    impl ColumnDecoder for NaiveTime {
+        fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+            DayNanos::try_decode(slice)?.try_into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch_columns() {
+        let millis = EpochMillis(-1_500);
+        assert_eq!(EpochMillis::try_decode(&millis.encode_new()[4..]).unwrap(), millis);
+        assert_eq!(EpochMillis::from(SystemTime::from(millis)), millis);
+        assert_eq!(EpochDays(0).encode_new(), vec![0, 0, 0, 4, 0x80, 0, 0, 0]);
+        let days = EpochDays(-3);
+        assert_eq!(EpochDays::try_decode(&days.encode_new()[4..]).unwrap(), days);
+        let time = DayNanos::from(Duration::from_secs(3_600));
+        assert_eq!(DayNanos::try_decode(&time.encode_new()[4..]).unwrap(), time);
+        assert!(DayNanos::try_decode(&DayNanos(DAY_NANOS).encode_new()[4..]).is_err());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_columns() {
+        use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+        let time = DateTime::<Utc>::from_timestamp_millis(1_620_000_000_123).unwrap();
+        assert_eq!(DateTime::<Utc>::try_decode(&time.encode_new()[4..]).unwrap(), time);
+        let date = NaiveDate::from_ymd_opt(1969, 12, 29).unwrap();
+        assert_eq!(EpochDays::from(date), EpochDays(-3));
+        assert_eq!(NaiveDate::try_decode(&date.encode_new()[4..]).unwrap(), date);
+        let time = NaiveTime::from_hms_nano_opt(13, 5, 7, 42).unwrap();
+        assert_eq!(DayNanos::from(time), DayNanos(47_107_000_000_042));
+        assert_eq!(NaiveTime::try_decode(&time.encode_new()[4..]).unwrap(), time);
+    }
+}
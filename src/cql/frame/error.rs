@@ -101,6 +101,51 @@ impl CqlError {
             None
         }
     }
+
+    /// Get the details of the error if it's an `UnavailableException` error
+    pub fn unavailable(&self) -> Option<&UnavailableException> {
+        match &self.additional {
+            Some(Additional::UnavailableException(unavailable)) => Some(unavailable),
+            _ => None,
+        }
+    }
+
+    /// Get the details of the error if it's a `ReadTimeout` error
+    pub fn read_timeout(&self) -> Option<&ReadTimeout> {
+        match &self.additional {
+            Some(Additional::ReadTimeout(read_timeout)) => Some(read_timeout),
+            _ => None,
+        }
+    }
+
+    /// Get the details of the error if it's a `WriteTimeout` error
+    pub fn write_timeout(&self) -> Option<&WriteTimeout> {
+        match &self.additional {
+            Some(Additional::WriteTimeout(write_timeout)) => Some(write_timeout),
+            _ => None,
+        }
+    }
+
+    /// Get the consistency level of the request if the error is an unavailable or timeout one
+    pub fn consistency(&self) -> Option<Consistency> {
+        match &self.additional {
+            Some(Additional::UnavailableException(unavailable)) => Some(unavailable.cl),
+            Some(Additional::ReadTimeout(read_timeout)) => Some(read_timeout.cl),
+            Some(Additional::WriteTimeout(write_timeout)) => Some(write_timeout.cl),
+            _ => None,
+        }
+    }
+
+    /// Get the number of replicas which were missing to achieve the consistency level, if the error is an
+    /// unavailable or timeout one
+    pub fn missing_replicas(&self) -> Option<i32> {
+        match &self.additional {
+            Some(Additional::UnavailableException(unavailable)) => Some(unavailable.missing()),
+            Some(Additional::ReadTimeout(read_timeout)) => Some(read_timeout.missing()),
+            Some(Additional::WriteTimeout(write_timeout)) => Some(write_timeout.missing()),
+            _ => None,
+        }
+    }
 }
 
 // ErrorCodes as consts
@@ -141,7 +186,7 @@ pub const ALREADY_EXISTS: i32 = 0x2400;
 /// The Error code of `UNPREPARED`.
 pub const UNPREPARED: i32 = 0x2500;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(i32)]
 /// The Error code enum.
 pub enum ErrorCodes {
@@ -223,6 +268,12 @@ impl TryFrom<&[u8]> for UnavailableException {
         Ok(Self { cl, required, alive })
     }
 }
+impl UnavailableException {
+    /// Get the number of replicas which were missing to achieve the consistency level.
+    pub fn missing(&self) -> i32 {
+        (self.required - self.alive).max(0)
+    }
+}
 #[derive(Debug)]
 /// The addtional error information, `WriteTimeout`, stucture.
 pub struct WriteTimeout {
@@ -235,6 +286,12 @@ pub struct WriteTimeout {
     /// That describe the type of the write that timed out.
     pub writetype: WriteType,
 }
+impl WriteTimeout {
+    /// Get the number of replicas which didn't acknowledge in time to achieve the consistency level.
+    pub fn missing(&self) -> i32 {
+        (self.blockfor - self.received).max(0)
+    }
+}
 impl TryFrom<&[u8]> for WriteTimeout {
     type Error = anyhow::Error;
 
@@ -269,6 +326,10 @@ impl ReadTimeout {
     pub fn replica_had_not_responded(&self) -> bool {
         self.data_present == 0
     }
+    /// Get the number of replicas which didn't answer in time to achieve the consistency level.
+    pub fn missing(&self) -> i32 {
+        (self.blockfor - self.received).max(0)
+    }
 }
 impl TryFrom<&[u8]> for ReadTimeout {
     type Error = anyhow::Error;
@@ -420,7 +481,7 @@ impl TryFrom<&[u8]> for Unprepared {
         })
     }
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 /// The type of the write that timed out.
 pub enum WriteType {
     /// Simple write type.
@@ -0,0 +1,303 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the Event frame, which is pushed by the node on the connections registered for the
+//! server events (see the Register frame).
+
+use super::decoder::{string_list_with_returned_bytes_length, Decoder, Frame};
+use anyhow::{anyhow, bail, ensure};
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt::{Display, Formatter},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+/// The server event types, which a connection can register for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventType {
+    /// A node joined or left the cluster
+    TopologyChange,
+    /// A node went up or down
+    StatusChange,
+    /// A keyspace, table, type, function or aggregate has been created, updated or dropped
+    SchemaChange,
+}
+
+impl EventType {
+    /// All the event types
+    pub const ALL: [EventType; 3] = [
+        EventType::TopologyChange,
+        EventType::StatusChange,
+        EventType::SchemaChange,
+    ];
+    /// Get the protocol name of the event type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::TopologyChange => "TOPOLOGY_CHANGE",
+            EventType::StatusChange => "STATUS_CHANGE",
+            EventType::SchemaChange => "SCHEMA_CHANGE",
+        }
+    }
+}
+
+/// The change of a `TOPOLOGY_CHANGE` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopologyChange {
+    /// The node joined the cluster
+    NewNode,
+    /// The node left the cluster
+    RemovedNode,
+}
+
+/// The change of a `STATUS_CHANGE` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusChange {
+    /// The node is up
+    Up,
+    /// The node is down
+    Down,
+}
+
+/// The change of a `SCHEMA_CHANGE` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaChangeType {
+    /// The schema element has been created
+    Created,
+    /// The schema element has been updated
+    Updated,
+    /// The schema element has been dropped
+    Dropped,
+}
+
+/// The schema element affected by a `SCHEMA_CHANGE` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaChangeTarget {
+    /// A keyspace
+    Keyspace,
+    /// A table
+    Table,
+    /// A user defined type
+    Type,
+    /// A user defined function
+    Function,
+    /// A user defined aggregate
+    Aggregate,
+}
+
+/// The server event pushed by the node on the registered connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A node joined or left the cluster
+    TopologyChange {
+        /// The change
+        change: TopologyChange,
+        /// The rpc address of the node
+        address: SocketAddr,
+    },
+    /// A node went up or down
+    StatusChange {
+        /// The change
+        change: StatusChange,
+        /// The rpc address of the node
+        address: SocketAddr,
+    },
+    /// A schema element has been created, updated or dropped
+    SchemaChange {
+        /// The change
+        change: SchemaChangeType,
+        /// The affected schema element
+        target: SchemaChangeTarget,
+        /// The keyspace of the element, or the keyspace itself
+        keyspace: String,
+        /// The name of the element, if it isn't a keyspace
+        name: Option<String>,
+        /// The argument types of the function or aggregate
+        arguments: Vec<String>,
+    },
+}
+
+impl Event {
+    /// Create an Event from frame decoder.
+    pub fn new(decoder: &Decoder) -> anyhow::Result<Self> {
+        ensure!(decoder.is_event()?, "The frame is not an EVENT frame!");
+        Self::try_from(decoder.body()?)
+    }
+    /// Get the type of the event
+    pub fn event_type(&self) -> EventType {
+        match self {
+            Event::TopologyChange { .. } => EventType::TopologyChange,
+            Event::StatusChange { .. } => EventType::StatusChange,
+            Event::SchemaChange { .. } => EventType::SchemaChange,
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Event {
+    type Error = anyhow::Error;
+
+    fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
+        let mut offset = 0;
+        let event_type = read_str(slice, &mut offset)?;
+        Ok(match event_type {
+            "TOPOLOGY_CHANGE" => {
+                let change = match read_str(slice, &mut offset)? {
+                    "NEW_NODE" => TopologyChange::NewNode,
+                    "REMOVED_NODE" => TopologyChange::RemovedNode,
+                    change => bail!("Unknown topology change: {}", change),
+                };
+                Event::TopologyChange {
+                    change,
+                    address: read_inet(&slice[offset..])?,
+                }
+            }
+            "STATUS_CHANGE" => {
+                let change = match read_str(slice, &mut offset)? {
+                    "UP" => StatusChange::Up,
+                    "DOWN" => StatusChange::Down,
+                    change => bail!("Unknown status change: {}", change),
+                };
+                Event::StatusChange {
+                    change,
+                    address: read_inet(&slice[offset..])?,
+                }
+            }
+            "SCHEMA_CHANGE" => {
+                let change = match read_str(slice, &mut offset)? {
+                    "CREATED" => SchemaChangeType::Created,
+                    "UPDATED" => SchemaChangeType::Updated,
+                    "DROPPED" => SchemaChangeType::Dropped,
+                    change => bail!("Unknown schema change: {}", change),
+                };
+                let target = match read_str(slice, &mut offset)? {
+                    "KEYSPACE" => SchemaChangeTarget::Keyspace,
+                    "TABLE" => SchemaChangeTarget::Table,
+                    "TYPE" => SchemaChangeTarget::Type,
+                    "FUNCTION" => SchemaChangeTarget::Function,
+                    "AGGREGATE" => SchemaChangeTarget::Aggregate,
+                    target => bail!("Unknown schema change target: {}", target),
+                };
+                let keyspace = read_str(slice, &mut offset)?.to_string();
+                let name = match target {
+                    SchemaChangeTarget::Keyspace => None,
+                    _ => Some(read_str(slice, &mut offset)?.to_string()),
+                };
+                let arguments = match target {
+                    SchemaChangeTarget::Function | SchemaChangeTarget::Aggregate => {
+                        ensure!(slice.len() >= offset + 2, "Buffer is too small!");
+                        string_list_with_returned_bytes_length(&slice[offset..])?.0
+                    }
+                    _ => Vec::new(),
+                };
+                Event::SchemaChange {
+                    change,
+                    target,
+                    keyspace,
+                    name,
+                    arguments,
+                }
+            }
+            event_type => bail!("Unknown event type: {}", event_type),
+        })
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::TopologyChange { change, address } => write!(f, "{:?}: {}", change, address),
+            Event::StatusChange { change, address } => write!(f, "{:?}: {}", change, address),
+            Event::SchemaChange {
+                change,
+                target,
+                keyspace,
+                name,
+                ..
+            } => match name {
+                Some(name) => write!(f, "{:?} {:?}: {}.{}", change, target, keyspace, name),
+                None => write!(f, "{:?} {:?}: {}", change, target, keyspace),
+            },
+        }
+    }
+}
+
+/// Read the `[string]` at the offset, and advance the offset past it.
+fn read_str<'a>(slice: &'a [u8], offset: &mut usize) -> anyhow::Result<&'a str> {
+    ensure!(slice.len() >= *offset + 2, "Buffer is too small!");
+    let length = u16::from_be_bytes(slice[*offset..][..2].try_into()?) as usize;
+    let start = *offset + 2;
+    ensure!(slice.len() >= start + length, "Buffer is too small!");
+    *offset = start + length;
+    std::str::from_utf8(&slice[start..*offset]).map_err(|e| anyhow!(e))
+}
+
+/// Read the `[inet]`, ie the address length, the address bytes and the port.
+fn read_inet(slice: &[u8]) -> anyhow::Result<SocketAddr> {
+    ensure!(!slice.is_empty(), "Buffer is too small!");
+    let length = slice[0] as usize;
+    ensure!(slice.len() >= 1 + length + 4, "Buffer is too small!");
+    let ip = match length {
+        4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(&slice[1..5])?)),
+        16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&slice[1..17])?)),
+        _ => bail!("Invalid inet length: {}", length),
+    };
+    let port = i32::from_be_bytes(slice[1 + length..][..4].try_into()?);
+    Ok(SocketAddr::new(ip, port as u16))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut bytes = (s.len() as u16).to_be_bytes().to_vec();
+        bytes.extend(s.bytes());
+        bytes
+    }
+
+    #[test]
+    fn decode_events() {
+        let mut body = string("TOPOLOGY_CHANGE");
+        body.extend(string("NEW_NODE"));
+        body.extend(&[4, 127, 0, 0, 2]);
+        body.extend(&9042i32.to_be_bytes());
+        assert_eq!(
+            Event::try_from(&body[..]).unwrap(),
+            Event::TopologyChange {
+                change: TopologyChange::NewNode,
+                address: "127.0.0.2:9042".parse().unwrap(),
+            }
+        );
+
+        let mut body = string("STATUS_CHANGE");
+        body.extend(string("DOWN"));
+        body.push(16);
+        body.extend(&Ipv6Addr::LOCALHOST.octets());
+        body.extend(&9042i32.to_be_bytes());
+        let event = Event::try_from(&body[..]).unwrap();
+        assert_eq!(event.event_type(), EventType::StatusChange);
+        assert_eq!(event.to_string(), "Down: [::1]:9042");
+
+        let mut body = string("SCHEMA_CHANGE");
+        body.extend(string("CREATED"));
+        body.extend(string("FUNCTION"));
+        body.extend(string("my_keyspace"));
+        body.extend(string("my_function"));
+        body.extend(&1u16.to_be_bytes());
+        body.extend(string("int"));
+        assert_eq!(
+            Event::try_from(&body[..]).unwrap(),
+            Event::SchemaChange {
+                change: SchemaChangeType::Created,
+                target: SchemaChangeTarget::Function,
+                keyspace: "my_keyspace".to_string(),
+                name: Some("my_function".to_string()),
+                arguments: vec!["int".to_string()],
+            }
+        );
+
+        let mut body = string("SCHEMA_CHANGE");
+        body.extend(string("DROPPED"));
+        body.extend(string("KEYSPACE"));
+        assert!(Event::try_from(&body[..]).is_err());
+    }
+}
@@ -11,38 +11,88 @@ pub(crate) mod authenticate;
 pub(crate) mod batch;
 pub(crate) mod batchflags;
 pub(crate) mod consistency;
+pub(crate) mod cql_type;
+pub(crate) mod cql_value;
 pub(crate) mod decoder;
+pub(crate) mod duration;
+pub(crate) mod dynamic_row;
 pub(crate) mod encoder;
+pub(crate) mod epoch;
 pub(crate) mod error;
+pub(crate) mod event;
 pub(crate) mod header;
+pub(crate) mod numeric;
 pub(crate) mod opcode;
 pub(crate) mod options;
 pub(crate) mod prepare;
 pub(crate) mod query;
+pub(crate) mod query_frame;
 pub(crate) mod queryflags;
+pub(crate) mod register;
 pub(crate) mod result;
 pub(crate) mod rows;
+pub(crate) mod segment;
 pub(crate) mod startup;
 pub(crate) mod supported;
+pub(crate) mod udt;
+pub(crate) mod version;
 
-pub use auth_response::{AllowAllAuth, PasswordAuth};
+pub use auth_response::{AllowAllAuth, AuthFuture, Authenticator, PasswordAuth, SaslSession};
 pub use auth_success::AuthSuccess;
 pub use batch::*;
 pub use consistency::Consistency;
-pub use decoder::{ColumnDecoder, Decoder, Frame, RowsDecoder, VoidDecoder};
-pub use encoder::{ColumnEncodeChain, ColumnEncoder, TokenEncodeChain, TokenEncoder};
-pub use error::{CqlError, ErrorCodes};
-pub use prepare::Prepare;
+pub use cql_type::CqlType;
+pub use cql_value::{Blob, CqlValue};
+pub use decoder::{ColumnDecoder, Decoder, Frame, HashableKey, LwtDecoder, RowsDecoder, VoidDecoder};
+pub use duration::CqlDuration;
+pub use dynamic_row::DynamicRow;
+pub use encoder::{ColumnEncodeChain, ColumnEncoder, Null, TokenEncodeChain, TokenEncoder, Unset};
+pub use epoch::{DayNanos, EpochDays, EpochMillis};
+pub use error::{
+    Additional, AlreadyExists, CqlError, ErrorCodes, FunctionFailure, ReadFailure, ReadTimeout, UnavailableException,
+    Unprepared, WriteFailure, WriteTimeout, WriteType,
+};
+pub use event::{Event, EventType, SchemaChangeTarget, SchemaChangeType, StatusChange, TopologyChange};
+pub use numeric::{Decimal, Varint};
+pub use options::{Options, OptionsBuild, OptionsBuilder};
+pub use prepare::{Prepare, PrepareBuilder, PrepareStatement};
 pub use query::{
     PreparedStatement, Query, QueryBuild, QueryBuilder, QueryConsistency, QueryFlags, QueryPagingState,
-    QuerySerialConsistency, QueryStatement, QueryValues,
+    QuerySerialConsistency, QueryStatement, QueryTimestamp, QueryValues,
 };
+pub use query_frame::{BatchFrame, BatchQuery, QueryFrame};
+pub use register::{Register, RegisterBuilder, RegisterEvents};
 pub use rows::*;
+pub use segment::{encode_segments, SegmentDecoder, MAX_SEGMENT_PAYLOAD};
 pub use std::convert::TryInto;
+pub use supported::Supported;
+pub use udt::{decode_udt, encode_udt, Udt, UdtFields};
+pub use version::ProtocolVersion;
 
 /// Big Endian 16-length, used for MD5 ID
 const MD5_BE_LENGTH: [u8; 2] = [0, 16];
 
+/// Encode the keyspace of the QUERY or PREPARE frame as the v5 keyspace field, ie after the `WITH_KEYSPACE` flag and
+/// the options of the QUERY frame, or after the statement of the PREPARE frame. The connections which speak v4
+/// qualify the table of the statement with it instead, see `ProtocolVersion::encode_request`.
+/// The other frames are left as they are.
+fn encode_keyspace(buffer: &mut Vec<u8>, keyspace: Option<&str>) {
+    let keyspace = match keyspace {
+        Some(keyspace) => keyspace,
+        None => return,
+    };
+    match buffer[4] {
+        opcode::QUERY => {
+            let flags = 13 + i32::from_be_bytes(buffer[9..13].try_into().unwrap()) as usize + 2;
+            buffer[flags] |= queryflags::WITH_KEYSPACE;
+        }
+        opcode::PREPARE => (),
+        _ => return,
+    }
+    buffer.extend(&u16::to_be_bytes(keyspace.len() as u16));
+    buffer.extend(keyspace.bytes());
+}
+
 /// Statement or ID
 pub trait QueryOrPrepared: Sized {
     /// Encode the statement as either a query string or an md5 hash prepared id
@@ -66,7 +116,7 @@ pub trait Values: Sized {
     /// The return type after applying a value
     type Return: Values<Return = Self::Return>;
     /// Value of type V.
-    fn value<V: ColumnEncoder>(self, value: &V) -> Self::Return;
+    fn value<V: ColumnEncoder + ?Sized>(self, value: &V) -> Self::Return;
     /// Unset value.
     fn unset_value(self) -> Self::Return;
     /// Set Null value, note: for write queries this will create tombstone for V;
@@ -0,0 +1,232 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the arbitrary precision `varint` and `decimal` columns, which are bound and decoded without
+//! any bignum dependency, along with their num-bigint and bigdecimal interop behind the `bignum` feature.
+
+use super::{decoder::ColumnDecoder, encoder::ColumnEncoder};
+use anyhow::ensure;
+use std::convert::{TryFrom, TryInto};
+
+/// A cql `varint` column, ie an arbitrary precision integer in the two's complement big endian encoding
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Varint(Vec<u8>);
+
+impl Varint {
+    /// Create the varint of the two's complement big endian bytes
+    pub fn from_signed_bytes_be(bytes: &[u8]) -> Self {
+        Self(minimal(bytes).to_vec())
+    }
+    /// Get the two's complement big endian bytes of the varint
+    pub fn as_signed_bytes_be(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Default for Varint {
+    fn default() -> Self {
+        Self(vec![0])
+    }
+}
+
+impl From<i64> for Varint {
+    fn from(value: i64) -> Self {
+        Self::from_signed_bytes_be(&value.to_be_bytes())
+    }
+}
+
+impl From<i128> for Varint {
+    fn from(value: i128) -> Self {
+        Self::from_signed_bytes_be(&value.to_be_bytes())
+    }
+}
+
+impl TryFrom<&Varint> for i128 {
+    type Error = anyhow::Error;
+
+    fn try_from(varint: &Varint) -> Result<Self, Self::Error> {
+        ensure!(varint.0.len() <= 16, "The varint overflows i128");
+        // sign extend the bytes
+        let fill = if varint.0[0] & 0x80 == 0 { 0 } else { 0xFF };
+        let mut bytes = [fill; 16];
+        bytes[16 - varint.0.len()..].copy_from_slice(&varint.0);
+        Ok(i128::from_be_bytes(bytes))
+    }
+}
+
+impl TryFrom<&Varint> for i64 {
+    type Error = anyhow::Error;
+
+    fn try_from(varint: &Varint) -> Result<Self, Self::Error> {
+        Ok(i128::try_from(varint)?.try_into()?)
+    }
+}
+
+impl ColumnEncoder for Varint {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(&i32::to_be_bytes(self.0.len() as i32));
+        buffer.extend(&self.0);
+    }
+}
+
+impl ColumnDecoder for Varint {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        ensure!(!slice.is_empty(), "Invalid varint: empty value");
+        Ok(Self::from_signed_bytes_be(slice))
+    }
+}
+
+/// A cql `decimal` column, ie the unscaled varint multiplied by ten to the power of minus the scale
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    /// The unscaled value
+    pub unscaled: Varint,
+    /// The number of digits after the decimal point
+    pub scale: i32,
+}
+
+impl Decimal {
+    /// Create a decimal
+    pub fn new(unscaled: impl Into<Varint>, scale: i32) -> Self {
+        Self {
+            unscaled: unscaled.into(),
+            scale,
+        }
+    }
+}
+
+impl ColumnEncoder for Decimal {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(&i32::to_be_bytes(4 + self.unscaled.0.len() as i32));
+        buffer.extend(&i32::to_be_bytes(self.scale));
+        buffer.extend(&self.unscaled.0);
+    }
+}
+
+impl ColumnDecoder for Decimal {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        ensure!(slice.len() > 4, "Invalid decimal: {} bytes", slice.len());
+        let scale = i32::from_be_bytes(slice[0..4].try_into()?);
+        Ok(Self {
+            unscaled: Varint::try_decode(&slice[4..])?,
+            scale,
+        })
+    }
+}
+
+/// Strip the redundant sign bytes of the two's complement big endian bytes
+fn minimal(bytes: &[u8]) -> &[u8] {
+    let mut start = 0;
+    while start + 1 < bytes.len()
+        && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xFF && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    match bytes.get(start..) {
+        Some(bytes) if !bytes.is_empty() => bytes,
+        _ => &[0],
+    }
+}
+
+#[cfg(feature = "bignum")]
+mod bignum_interop {
+    use super::*;
+    use anyhow::anyhow;
+    use bigdecimal::BigDecimal;
+    use num_bigint::BigInt;
+
+    impl From<&BigInt> for Varint {
+        fn from(value: &BigInt) -> Self {
+            Self::from_signed_bytes_be(&value.to_signed_bytes_be())
+        }
+    }
+
+    impl From<&Varint> for BigInt {
+        fn from(varint: &Varint) -> Self {
+            BigInt::from_signed_bytes_be(&varint.0)
+        }
+    }
+
+    impl TryFrom<&BigDecimal> for Decimal {
+        type Error = anyhow::Error;
+
+        fn try_from(value: &BigDecimal) -> Result<Self, Self::Error> {
+            let (unscaled, scale) = value.as_bigint_and_exponent();
+            let scale = i32::try_from(scale).map_err(|_| anyhow!("The decimal scale {} overflows i32", scale))?;
+            Ok(Self::new(&unscaled, scale))
+        }
+    }
+
+    impl From<&Decimal> for BigDecimal {
+        fn from(decimal: &Decimal) -> Self {
+            BigDecimal::new(BigInt::from(&decimal.unscaled), decimal.scale as i64)
+        }
+    }
+
+    impl ColumnEncoder for BigInt {
+        fn encode(&self, buffer: &mut Vec<u8>) {
+            Varint::from(self).encode(buffer)
+        }
+    }
+
+    impl ColumnDecoder for BigInt {
+        fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+            Ok(BigInt::from(&Varint::try_decode(slice)?))
+        }
+    }
+
+    /// Note the decimals whose scale overflows i32 are encoded as null, as they can't be represented by cql
+    impl ColumnEncoder for BigDecimal {
+        fn encode(&self, buffer: &mut Vec<u8>) {
+            Decimal::try_from(self).ok().encode(buffer)
+        }
+    }
+
+    impl ColumnDecoder for BigDecimal {
+        fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+            Ok(BigDecimal::from(&Decimal::try_decode(slice)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_column() {
+        assert_eq!(Varint::from(0i64).encode_new(), vec![0, 0, 0, 1, 0]);
+        assert_eq!(Varint::from(128i64).encode_new(), vec![0, 0, 0, 2, 0, 0x80]);
+        assert_eq!(Varint::from(-129i64).encode_new(), vec![0, 0, 0, 2, 0xFF, 0x7F]);
+        for value in [0, -1, 127, -128, i64::MAX as i128 + 1, i128::MIN] {
+            let varint = Varint::try_decode(&Varint::from(value).encode_new()[4..]).unwrap();
+            assert_eq!(i128::try_from(&varint).unwrap(), value);
+        }
+        let varint = Varint::from_signed_bytes_be(&[0, 0, 0, 0x7F]);
+        assert_eq!(varint.as_signed_bytes_be(), &[0x7F]);
+        assert!(i64::try_from(&Varint::from(i128::MAX)).is_err());
+        assert!(Varint::try_decode(&[]).is_err());
+    }
+
+    #[test]
+    fn decimal_column() {
+        // 12.345
+        let decimal = Decimal::new(12_345i64, 3);
+        assert_eq!(decimal.encode_new(), vec![0, 0, 0, 6, 0, 0, 0, 3, 0x30, 0x39]);
+        assert_eq!(Decimal::try_decode(&decimal.encode_new()[4..]).unwrap(), decimal);
+        assert!(Decimal::try_decode(&[0, 0, 0, 3]).is_err());
+    }
+
+    #[cfg(feature = "bignum")]
+    #[test]
+    fn bignum_columns() {
+        use bigdecimal::BigDecimal;
+        use num_bigint::BigInt;
+        use std::str::FromStr;
+        let value = BigInt::from_str("-123456789012345678901234567890").unwrap();
+        assert_eq!(BigInt::try_decode(&value.encode_new()[4..]).unwrap(), value);
+        let value = BigDecimal::from_str("-1234567890.0987654321").unwrap();
+        assert_eq!(BigDecimal::try_decode(&value.encode_new()[4..]).unwrap(), value);
+    }
+}
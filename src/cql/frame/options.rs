@@ -8,20 +8,22 @@ use super::opcode::OPTIONS;
 /// Blanket cql frame header for OPTIONS frame.
 const OPTIONS_HEADER: &'static [u8] = &[4, 0, 0, 0, OPTIONS, 0, 0, 0, 0];
 
-/// The Options frame structure.
-pub(crate) struct Options(pub Vec<u8>);
+/// The Options frame structure, which asks the node for its supported startup options.
+pub struct Options(pub Vec<u8>);
 
-pub(crate) struct OptionsBuilder<Stage> {
+/// The Options frame builder.
+pub struct OptionsBuilder<Stage> {
     buffer: Vec<u8>,
     #[allow(unused)]
     stage: Stage,
 }
 
-struct OptionsHeader;
-pub(crate) struct OptionsBuild;
+pub struct OptionsHeader;
+/// Gating type for options build
+pub struct OptionsBuild;
 
 impl OptionsBuilder<OptionsHeader> {
-    pub fn new() -> OptionsBuilder<OptionsBuild> {
+    fn new() -> OptionsBuilder<OptionsBuild> {
         let mut buffer: Vec<u8> = Vec::new();
         buffer.extend_from_slice(&OPTIONS_HEADER);
         OptionsBuilder::<OptionsBuild> {
@@ -32,13 +34,15 @@ impl OptionsBuilder<OptionsHeader> {
 }
 
 impl OptionsBuilder<OptionsBuild> {
-    pub(crate) fn build(self) -> Options {
+    /// Build the Options frame, which is never compressed as the compression is negotiated afterwards.
+    pub fn build(self) -> Options {
         Options(self.buffer)
     }
 }
 
 impl Options {
-    pub(crate) fn new() -> OptionsBuilder<OptionsBuild> {
+    /// Create Options cql frame
+    pub fn new() -> OptionsBuilder<OptionsBuild> {
         OptionsBuilder::<OptionsHeader>::new()
     }
 }
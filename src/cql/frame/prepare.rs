@@ -3,9 +3,9 @@
 
 //! This module implements the Prepare frame.
 
-use super::opcode::PREPARE;
+use super::{encode_keyspace, opcode::PREPARE};
 
-use crate::cql::compression::{Compression, MyCompression};
+use crate::cql::compression::{CompressionType, FrameCompression};
 
 /// Blanket cql frame header for prepare frame.
 const PREPARE_HEADER: &'static [u8] = &[4, 0, 0, 0, PREPARE, 0, 0, 0, 0];
@@ -13,12 +13,16 @@ const PREPARE_HEADER: &'static [u8] = &[4, 0, 0, 0, PREPARE, 0, 0, 0, 0];
 /// The prepare frame structure.
 pub struct Prepare(pub Vec<u8>);
 
+/// The Prepare frame builder.
 #[allow(dead_code)]
 pub struct PrepareBuilder<Stage> {
     buffer: Vec<u8>,
+    compression: FrameCompression,
+    keyspace: Option<String>,
     stage: Stage,
 }
 pub struct PrepareHeader;
+/// Gating type for prepare statement
 pub struct PrepareStatement;
 pub struct PrepareBuild;
 
@@ -28,6 +32,8 @@ impl PrepareBuilder<PrepareHeader> {
         buffer.extend_from_slice(&PREPARE_HEADER);
         PrepareBuilder::<PrepareStatement> {
             buffer,
+            compression: FrameCompression::default(),
+            keyspace: None,
             stage: PrepareStatement,
         }
     }
@@ -36,6 +42,8 @@ impl PrepareBuilder<PrepareHeader> {
         buffer.extend_from_slice(&PREPARE_HEADER);
         PrepareBuilder::<PrepareStatement> {
             buffer,
+            compression: FrameCompression::default(),
+            keyspace: None,
             stage: PrepareStatement,
         }
     }
@@ -48,6 +56,8 @@ impl PrepareBuilder<PrepareStatement> {
         self.buffer.extend(statement.bytes());
         PrepareBuilder::<PrepareBuild> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: PrepareBuild,
         }
     }
@@ -56,13 +66,37 @@ impl PrepareBuilder<PrepareStatement> {
 impl PrepareBuilder<PrepareBuild> {
     /// Build the prepare frame with an assigned compression type.
     pub fn build(mut self) -> anyhow::Result<Prepare> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        encode_keyspace(&mut self.buffer, self.keyspace.as_deref());
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         Ok(Prepare(self.buffer))
     }
 }
 
+impl<Stage> PrepareBuilder<Stage> {
+    /// Compress the prepare frame by the provided compression, once it reaches the compression threshold, instead of
+    /// leaving it uncompressed for the connection which writes it to compress it by its negotiated compression.
+    /// Note: the frame must only be sent on the connections which negotiated the provided compression.
+    pub fn compression(mut self, compression: CompressionType) -> Self {
+        self.compression.compression = compression;
+        self
+    }
+    /// Override whether the prepare frame is compressed, regardless of the compression threshold.
+    /// Note: the frame can only be compressed if it's built with a compression, see `compression`, otherwise the
+    /// connection which writes it decides whether to compress it.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compression.compress = Some(compress);
+        self
+    }
+    /// Set the keyspace of the unqualified table of the statement, instead of depending on the keyspace of the
+    /// connection (ie the one set by USE) when it's executed. The keyspace is sent in the keyspace field of the
+    /// frame on the connections which speak v5, otherwise the table of the statement is qualified with it when the
+    /// frame is written, see `qualify_table`.
+    pub fn with_keyspace(mut self, keyspace: &str) -> Self {
+        self.keyspace = Some(keyspace.to_string());
+        self
+    }
+}
+
 impl Prepare {
     /// Create preapre Cql frame
     pub fn new() -> PrepareBuilder<PrepareStatement> {
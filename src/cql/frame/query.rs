@@ -5,12 +5,17 @@
 
 use super::{
     consistency::Consistency,
+    encode_keyspace,
     encoder::{ColumnEncoder, BE_8_BYTES_LEN, BE_NULL_BYTES_LEN, BE_UNSET_BYTES_LEN},
+    header::TRACING,
     opcode::{EXECUTE, QUERY},
     queryflags::*,
     QueryOrPrepared, Statements, Values,
 };
-use crate::cql::compression::{Compression, MyCompression};
+use crate::cql::{
+    compression::{CompressionType, FrameCompression},
+    Md5, StatementIdAlgorithm,
+};
 
 /// Blanket cql frame header for query frame.
 const QUERY_HEADER: &'static [u8] = &[4, 0, 0, 0, QUERY, 0, 0, 0, 0];
@@ -27,13 +32,15 @@ const QUERY_HEADER: &'static [u8] = &[4, 0, 0, 0, QUERY, 0, 0, 0, 0];
 ///     .statement("statement")
 ///     .consistency(Consistency::One)
 ///     .value(&0)
-///     .value(&"val2".to_string())
+///     .value("val2")
 ///     .build()?;
 /// let payload = query.0;
 /// # Ok::<(), anyhow::Error>(())
 /// ```
 pub struct QueryBuilder<Stage> {
     buffer: Vec<u8>,
+    compression: FrameCompression,
+    keyspace: Option<String>,
     stage: Stage,
 }
 
@@ -84,6 +91,8 @@ impl QueryBuilder<QueryHeader> {
         buffer.extend_from_slice(&QUERY_HEADER);
         QueryBuilder::<QueryStatement> {
             buffer,
+            compression: FrameCompression::default(),
+            keyspace: None,
             stage: QueryStatement,
         }
     }
@@ -93,6 +102,8 @@ impl QueryBuilder<QueryHeader> {
         buffer.extend_from_slice(&QUERY_HEADER);
         QueryBuilder::<QueryStatement> {
             buffer,
+            compression: FrameCompression::default(),
+            keyspace: None,
             stage: QueryStatement,
         }
     }
@@ -108,7 +119,7 @@ impl QueryOrPrepared for QueryStatement {
 }
 impl QueryOrPrepared for PreparedStatement {
     fn encode_statement<T: Statements>(query_or_batch: T, statement: &str) -> T::Return {
-        query_or_batch.id(&md5::compute(statement.as_bytes()).into())
+        query_or_batch.id(&Md5::statement_id(statement))
     }
     fn is_prepared() -> bool {
         true
@@ -122,6 +133,8 @@ impl<T: QueryOrPrepared> Statements for QueryBuilder<T> {
         self.buffer.extend(statement.as_bytes());
         QueryBuilder::<QueryConsistency> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: QueryConsistency,
         }
     }
@@ -134,6 +147,8 @@ impl<T: QueryOrPrepared> Statements for QueryBuilder<T> {
         self.buffer.extend(id);
         QueryBuilder::<QueryConsistency> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: QueryConsistency,
         }
     }
@@ -146,6 +161,8 @@ impl QueryBuilder<QueryConsistency> {
         let query_flag_index = self.buffer.len();
         QueryBuilder::<QueryFlags> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: QueryFlags {
                 index: query_flag_index,
             },
@@ -171,6 +188,8 @@ impl Values for QueryBuilder<QueryFlags> {
         };
         QueryBuilder::<QueryValues> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_values,
         }
     }
@@ -190,11 +209,13 @@ impl Values for QueryBuilder<QueryFlags> {
         };
         QueryBuilder::<QueryValues> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_values,
         }
     }
     /// Set the first value in the query frame.
-    fn value<V: ColumnEncoder>(mut self, value: &V) -> QueryBuilder<QueryValues> {
+    fn value<V: ColumnEncoder + ?Sized>(mut self, value: &V) -> QueryBuilder<QueryValues> {
         // push SKIP_METADATA and VALUES query_flag to the buffer
         self.buffer.push(SKIP_METADATA | VALUES);
         let value_count = 1;
@@ -209,6 +230,8 @@ impl Values for QueryBuilder<QueryFlags> {
         value.encode(&mut self.buffer);
         QueryBuilder::<QueryValues> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_values,
         }
     }
@@ -226,6 +249,8 @@ impl QueryBuilder<QueryFlags> {
         };
         QueryBuilder::<QueryPagingState> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_paging_state,
         }
     }
@@ -247,6 +272,8 @@ impl QueryBuilder<QueryFlags> {
         };
         QueryBuilder::<QuerySerialConsistency> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_serial_consistency,
         }
     }
@@ -262,6 +289,8 @@ impl QueryBuilder<QueryFlags> {
         };
         QueryBuilder::<QueryTimestamp> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_timestamp,
         }
     }
@@ -276,17 +305,18 @@ impl QueryBuilder<QueryFlags> {
         let query_build = QueryBuild;
         QueryBuilder::<QueryBuild> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_build,
         }
     }
     /// Build a query frame with an assigned compression type, without any value.
     pub fn build(mut self) -> anyhow::Result<Query> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
         // push SKIP_METADATA query_flag to the buffer
         self.buffer.push(SKIP_METADATA);
+        encode_keyspace(&mut self.buffer, self.keyspace.as_deref());
         // apply compression to query frame
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         // create query
         Ok(Query(self.buffer))
     }
@@ -294,7 +324,7 @@ impl QueryBuilder<QueryFlags> {
 impl Values for QueryBuilder<QueryValues> {
     type Return = QueryBuilder<QueryValues>;
     /// Set the next value in the query frame.
-    fn value<V: ColumnEncoder>(mut self, value: &V) -> Self {
+    fn value<V: ColumnEncoder + ?Sized>(mut self, value: &V) -> Self {
         // increase the value_count
         self.stage.value_count += 1;
         // apply value
@@ -335,6 +365,8 @@ impl QueryBuilder<QueryValues> {
         };
         QueryBuilder::<QueryPagingState> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_page_size,
         }
     }
@@ -357,6 +389,8 @@ impl QueryBuilder<QueryValues> {
         };
         QueryBuilder::<QuerySerialConsistency> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_serial_consistency,
         }
     }
@@ -376,6 +410,8 @@ impl QueryBuilder<QueryValues> {
         };
         QueryBuilder::<QueryTimestamp> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_timestamp,
         }
     }
@@ -394,20 +430,21 @@ impl QueryBuilder<QueryValues> {
         let query_build = QueryBuild;
         QueryBuilder::<QueryBuild> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_build,
         }
     }
 
     /// Build a query frame with an assigned compression type, with values.
     pub fn build(mut self) -> anyhow::Result<Query> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
         // modiy the buffer total value_count
         let start = self.stage.query_flags.index + 1;
         let end = start + 2;
         self.buffer[start..end].copy_from_slice(&self.stage.value_count.to_be_bytes());
+        encode_keyspace(&mut self.buffer, self.keyspace.as_deref());
         // apply compression to query frame
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         // create query
         Ok(Query(self.buffer))
     }
@@ -429,6 +466,8 @@ impl QueryBuilder<QueryPagingState> {
         };
         QueryBuilder::<QuerySerialConsistency> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_serial_consistency,
         }
     }
@@ -444,6 +483,8 @@ impl QueryBuilder<QueryPagingState> {
         };
         QueryBuilder::<QueryTimestamp> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_timestamp,
         }
     }
@@ -458,16 +499,17 @@ impl QueryBuilder<QueryPagingState> {
         let query_build = QueryBuild;
         QueryBuilder::<QueryBuild> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_build,
         }
     }
 
     /// Build a query frame with an assigned compression type.
     pub fn build(mut self) -> anyhow::Result<Query> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
+        encode_keyspace(&mut self.buffer, self.keyspace.as_deref());
         // apply compression to query frame
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         // create query
         Ok(Query(self.buffer))
     }
@@ -486,6 +528,8 @@ impl QueryBuilder<QuerySerialConsistency> {
         };
         QueryBuilder::<QueryTimestamp> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_timestamp,
         }
     }
@@ -500,16 +544,17 @@ impl QueryBuilder<QuerySerialConsistency> {
         let query_build = QueryBuild;
         QueryBuilder::<QueryBuild> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_build,
         }
     }
 
     /// Build a query frame with an assigned compression type.
     pub fn build(mut self) -> anyhow::Result<Query> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
+        encode_keyspace(&mut self.buffer, self.keyspace.as_deref());
         // apply compression to query frame
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         // create query
         Ok(Query(self.buffer))
     }
@@ -527,15 +572,16 @@ impl QueryBuilder<QueryTimestamp> {
         let query_build = QueryBuild;
         QueryBuilder::<QueryBuild> {
             buffer: self.buffer,
+            compression: self.compression,
+            keyspace: self.keyspace,
             stage: query_build,
         }
     }
     /// Build a query frame with an assigned compression type.
     pub fn build(mut self) -> anyhow::Result<Query> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
+        encode_keyspace(&mut self.buffer, self.keyspace.as_deref());
         // apply compression to query frame
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         // create query
         Ok(Query(self.buffer))
     }
@@ -544,15 +590,50 @@ impl QueryBuilder<QueryTimestamp> {
 impl QueryBuilder<QueryBuild> {
     /// Build a query frame with an assigned compression type.
     pub fn build(mut self) -> anyhow::Result<Query> {
-        // apply compression flag(if any to the header)
-        self.buffer[1] |= MyCompression::flag();
+        encode_keyspace(&mut self.buffer, self.keyspace.as_deref());
         // apply compression to query frame
-        self.buffer = MyCompression::get().compress(self.buffer)?;
+        self.buffer = self.compression.compress_frame(self.buffer)?;
         // create query
         Ok(Query(self.buffer))
     }
 }
 
+impl<Stage> QueryBuilder<Stage> {
+    /// Request the tracing of the query, then the response carries the tracing id of the session (see
+    /// `Decoder::tracing_id`), whose events are recorded in the `system_traces` keyspace (see `Trace`).
+    pub fn tracing(mut self, tracing: bool) -> Self {
+        if tracing {
+            self.buffer[1] |= TRACING;
+        } else {
+            self.buffer[1] &= !TRACING;
+        }
+        self
+    }
+    /// Compress the query frame by the provided compression, once it reaches the compression threshold, instead of
+    /// leaving it uncompressed for the connection which writes it to compress it by its negotiated compression.
+    /// Note: the frame must only be sent on the connections which negotiated the provided compression.
+    pub fn compression(mut self, compression: CompressionType) -> Self {
+        self.compression.compression = compression;
+        self
+    }
+    /// Override whether the query frame is compressed, regardless of the compression threshold.
+    /// Note: the frame can only be compressed if it's built with a compression, see `compression`, otherwise the
+    /// connection which writes it decides whether to compress it.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compression.compress = Some(compress);
+        self
+    }
+    /// Set the keyspace of the unqualified table of the statement, instead of depending on the keyspace of the
+    /// connection (ie the one set by USE). The keyspace is sent in the keyspace field of the frame on the
+    /// connections which speak v5, while the v4 frames have no such field, so the table of the statement is
+    /// qualified with the keyspace when the frame is written instead, see `qualify_table`.
+    /// The EXECUTE frames are left as they are, as the keyspace of their statement is resolved when it's prepared.
+    pub fn with_keyspace(mut self, keyspace: &str) -> Self {
+        self.keyspace = Some(keyspace.to_string());
+        self
+    }
+}
+
 #[derive(Default, Clone)]
 /// The query frame structure.
 pub struct Query(pub Vec<u8>);
@@ -595,4 +676,40 @@ mod tests {
             .build()
             .unwrap();
     }
+
+    #[test]
+    fn query_with_keyspace() {
+        let query = Query::new()
+            .with_keyspace("shop")
+            .statement("SELECT * FROM orders WHERE id = ?")
+            .consistency(Consistency::One)
+            .value(&1)
+            .page_size(10)
+            .build()
+            .unwrap();
+        let frame = crate::cql::QueryFrame::decode(&query.0).unwrap();
+        assert_eq!(frame.statement(), Some("SELECT * FROM orders WHERE id = ?"));
+        assert_eq!(frame.keyspace(), Some("shop"));
+        assert_eq!(frame.flags() & WITH_KEYSPACE, WITH_KEYSPACE);
+        assert_eq!(frame.page_size(), Some(10));
+        // the keyspace is kept once the frame is mapped
+        let query = query.map_frame(|frame| {
+            frame.set_page_size(None);
+        });
+        assert_eq!(
+            crate::cql::QueryFrame::decode(&query.unwrap().0).unwrap().keyspace(),
+            Some("shop")
+        );
+        // the prepared statements are left as they are
+        let execute = Query::new()
+            .id(&[7; 16])
+            .consistency(Consistency::One)
+            .with_keyspace("shop")
+            .build()
+            .unwrap();
+        assert_eq!(
+            crate::cql::QueryFrame::decode(&execute.0).unwrap().prepared_id(),
+            Some(&[7; 16][..])
+        );
+    }
 }
@@ -0,0 +1,680 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the inspection and mutation of the built query frames, so the middlewares (ie the
+//! execution profiles or the interceptors) can adjust them without rebuilding them from scratch.
+
+use super::{
+    batch::Batch,
+    batchflags::WITH_NAMES,
+    consistency::Consistency,
+    encoder::BE_8_BYTES_LEN,
+    header::{COMPRESSION, TRACING},
+    opcode::{BATCH, EXECUTE, QUERY},
+    query::Query,
+    queryflags::*,
+};
+use crate::cql::compression::{Compression, MyCompression};
+use anyhow::{anyhow, ensure};
+use std::convert::{TryFrom, TryInto};
+
+/// The decoded QUERY or EXECUTE frame, whose options can be inspected and changed before it's encoded again.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::{Consistency, Query, Statements, Values};
+///
+/// let query = Query::new()
+///     .statement("SELECT * FROM shop.orders WHERE customer = ?")
+///     .consistency(Consistency::One)
+///     .value("alice")
+///     .build()?
+///     .map_frame(|frame| {
+///         frame.set_consistency(Consistency::LocalQuorum);
+///         frame.set_page_size(Some(100));
+///     })?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryFrame {
+    version: u8,
+    header_flags: u8,
+    stream: i16,
+    opcode: u8,
+    /// The statement as a long string, or the prepared id as short bytes
+    statement: Vec<u8>,
+    consistency: Consistency,
+    skip_metadata: bool,
+    /// The values section, along with its value count
+    values: Option<Vec<u8>>,
+    page_size: Option<i32>,
+    paging_state: Option<Vec<u8>>,
+    serial_consistency: Option<Consistency>,
+    timestamp: Option<i64>,
+    /// The keyspace of the statement, see `QueryBuilder::with_keyspace`
+    keyspace: Option<String>,
+    compressed: bool,
+}
+
+impl QueryFrame {
+    /// Decode the QUERY or EXECUTE frame, which is decompressed if needed
+    pub fn decode(payload: &[u8]) -> anyhow::Result<Self> {
+        let buffer = MyCompression::get().decompress(payload.to_vec())?;
+        ensure!(buffer.len() >= 9, "Buffer is too small!");
+        let opcode = buffer[4];
+        ensure!(
+            opcode == QUERY || opcode == EXECUTE,
+            "Unexpected opcode {:#04x}, expected a QUERY or EXECUTE frame",
+            opcode
+        );
+        let mut reader = Reader {
+            buffer: &buffer[9..],
+            position: 0,
+        };
+        let statement = if opcode == QUERY {
+            let length = reader.i32()?;
+            ensure!(length >= 0, "Invalid statement length");
+            reader.take(length as usize)?;
+            reader.buffer[..reader.position].to_vec()
+        } else {
+            let length = reader.u16()?;
+            reader.take(length as usize)?;
+            reader.buffer[..reader.position].to_vec()
+        };
+        let consistency = Consistency::try_from(reader.take(2)?)?;
+        let flags = reader.take(1)?[0];
+        let values = if flags & VALUES == VALUES {
+            let start = reader.position;
+            let count = reader.u16()?;
+            for _ in 0..count {
+                let length = reader.i32()?;
+                // null and unset values don't have any content
+                if length > 0 {
+                    reader.take(length as usize)?;
+                }
+            }
+            Some(reader.buffer[start..reader.position].to_vec())
+        } else {
+            None
+        };
+        let page_size = if flags & PAGE_SIZE == PAGE_SIZE {
+            Some(reader.i32()?)
+        } else {
+            None
+        };
+        let paging_state = if flags & PAGING_STATE == PAGING_STATE {
+            let length = reader.i32()?;
+            ensure!(length >= 0, "Invalid paging state length");
+            Some(reader.take(length as usize)?.to_vec())
+        } else {
+            None
+        };
+        let serial_consistency = if flags & SERIAL_CONSISTENCY == SERIAL_CONSISTENCY {
+            Some(Consistency::try_from(reader.take(2)?)?)
+        } else {
+            None
+        };
+        let timestamp = if flags & TIMESTAMP == TIMESTAMP {
+            // the timestamp is encoded as bytes, ie along with its length
+            reader.i32()?;
+            Some(i64::from_be_bytes(reader.take(8)?.try_into()?))
+        } else {
+            None
+        };
+        let keyspace = if flags & WITH_KEYSPACE == WITH_KEYSPACE {
+            let length = reader.u16()?;
+            Some(String::from_utf8(reader.take(length as usize)?.to_vec())?)
+        } else {
+            None
+        };
+        Ok(Self {
+            version: buffer[0],
+            header_flags: buffer[1] & !COMPRESSION,
+            stream: i16::from_be_bytes(buffer[2..4].try_into()?),
+            opcode,
+            statement,
+            consistency,
+            skip_metadata: flags & SKIP_METADATA == SKIP_METADATA,
+            values,
+            page_size,
+            paging_state,
+            serial_consistency,
+            timestamp,
+            keyspace,
+            compressed: buffer[1] & COMPRESSION == COMPRESSION,
+        })
+    }
+    /// Encode the frame, which is compressed again if it was compressed
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(9 + self.statement.len() + self.values.as_ref().map_or(0, Vec::len) + 32);
+        buffer.extend(&[self.version, self.header_flags]);
+        buffer.extend(&self.stream.to_be_bytes());
+        buffer.extend(&[self.opcode, 0, 0, 0, 0]);
+        buffer.extend(&self.statement);
+        buffer.extend(&(self.consistency as u16).to_be_bytes());
+        buffer.push(self.flags());
+        if let Some(values) = self.values.as_ref() {
+            buffer.extend(values);
+        }
+        if let Some(page_size) = self.page_size {
+            buffer.extend(&page_size.to_be_bytes());
+        }
+        if let Some(paging_state) = self.paging_state.as_ref() {
+            buffer.extend(&(paging_state.len() as i32).to_be_bytes());
+            buffer.extend(paging_state);
+        }
+        if let Some(serial_consistency) = self.serial_consistency {
+            buffer.extend(&(serial_consistency as u16).to_be_bytes());
+        }
+        if let Some(timestamp) = self.timestamp {
+            buffer.extend(&BE_8_BYTES_LEN);
+            buffer.extend(&timestamp.to_be_bytes());
+        }
+        if let Some(keyspace) = self.keyspace.as_ref() {
+            buffer.extend(&(keyspace.len() as u16).to_be_bytes());
+            buffer.extend(keyspace.bytes());
+        }
+        MyCompression::compress_frame(buffer, Some(self.compressed))
+    }
+    /// Get the query flags of the frame
+    pub fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.values.is_some() {
+            flags |= VALUES;
+        }
+        if self.skip_metadata {
+            flags |= SKIP_METADATA;
+        }
+        if self.page_size.is_some() {
+            flags |= PAGE_SIZE;
+        }
+        if self.paging_state.is_some() {
+            flags |= PAGING_STATE;
+        }
+        if self.serial_consistency.is_some() {
+            flags |= SERIAL_CONSISTENCY;
+        }
+        if self.timestamp.is_some() {
+            flags |= TIMESTAMP;
+        }
+        if self.keyspace.is_some() {
+            flags |= WITH_KEYSPACE;
+        }
+        flags
+    }
+    /// Get the stream of the frame, which is assigned by the reporter once the request is sent
+    pub fn stream(&self) -> i16 {
+        self.stream
+    }
+    /// Check whether the frame executes a prepared statement
+    pub fn is_prepared(&self) -> bool {
+        self.opcode == EXECUTE
+    }
+    /// Get the statement of the frame, unless it executes a prepared statement
+    pub fn statement(&self) -> Option<&str> {
+        if self.is_prepared() {
+            None
+        } else {
+            std::str::from_utf8(&self.statement[4..]).ok()
+        }
+    }
+    /// Get the prepared id of the frame, if it executes a prepared statement
+    pub fn prepared_id(&self) -> Option<&[u8]> {
+        if self.is_prepared() {
+            Some(&self.statement[2..])
+        } else {
+            None
+        }
+    }
+    /// Get the consistency of the frame
+    pub fn consistency(&self) -> Consistency {
+        self.consistency
+    }
+    /// Set the consistency of the frame
+    pub fn set_consistency(&mut self, consistency: Consistency) -> &mut Self {
+        self.consistency = consistency;
+        self
+    }
+    /// Get the serial consistency of the frame, if any
+    pub fn serial_consistency(&self) -> Option<Consistency> {
+        self.serial_consistency
+    }
+    /// Set or remove the serial consistency of the frame
+    pub fn set_serial_consistency(&mut self, serial_consistency: Option<Consistency>) -> &mut Self {
+        self.serial_consistency = serial_consistency;
+        self
+    }
+    /// Get the page size of the frame, if any
+    pub fn page_size(&self) -> Option<i32> {
+        self.page_size
+    }
+    /// Set or remove the page size of the frame
+    pub fn set_page_size(&mut self, page_size: Option<i32>) -> &mut Self {
+        self.page_size = page_size;
+        self
+    }
+    /// Get the paging state of the frame, if any
+    pub fn paging_state(&self) -> Option<&[u8]> {
+        self.paging_state.as_deref()
+    }
+    /// Set or remove the paging state of the frame
+    pub fn set_paging_state(&mut self, paging_state: Option<Vec<u8>>) -> &mut Self {
+        self.paging_state = paging_state;
+        self
+    }
+    /// Get the default timestamp of the frame, if any
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+    /// Set or remove the default timestamp of the frame
+    pub fn set_timestamp(&mut self, timestamp: Option<i64>) -> &mut Self {
+        self.timestamp = timestamp;
+        self
+    }
+    /// Get the keyspace of the statement, if any
+    pub fn keyspace(&self) -> Option<&str> {
+        self.keyspace.as_deref()
+    }
+    /// Set or remove the keyspace of the statement, unless the frame executes a prepared statement
+    pub fn set_keyspace(&mut self, keyspace: Option<&str>) -> &mut Self {
+        if !self.is_prepared() {
+            self.keyspace = keyspace.map(ToString::to_string);
+        }
+        self
+    }
+    /// Qualify the table of the statement with the keyspace of the frame, which is then removed, as the v4 frames
+    /// have no keyspace field, see `qualify_table`
+    pub(crate) fn qualify_table(&mut self) {
+        let keyspace = match self.keyspace.take() {
+            Some(keyspace) => keyspace,
+            None => return,
+        };
+        if let Some(statement) = self.statement() {
+            let statement = crate::cql::qualify_table(statement, &keyspace).into_owned();
+            let mut buffer = (statement.len() as i32).to_be_bytes().to_vec();
+            buffer.extend(statement.bytes());
+            self.statement = buffer;
+        }
+    }
+    /// Check whether the frame requests the tracing of the query
+    pub fn tracing(&self) -> bool {
+        self.header_flags & TRACING == TRACING
+    }
+    /// Request the tracing of the query, or not
+    pub fn set_tracing(&mut self, tracing: bool) -> &mut Self {
+        if tracing {
+            self.header_flags |= TRACING;
+        } else {
+            self.header_flags &= !TRACING;
+        }
+        self
+    }
+    /// Check whether the frame skips the metadata of the rows result
+    pub fn skip_metadata(&self) -> bool {
+        self.skip_metadata
+    }
+    /// Set whether the frame skips the metadata of the rows result
+    pub fn set_skip_metadata(&mut self, skip_metadata: bool) -> &mut Self {
+        self.skip_metadata = skip_metadata;
+        self
+    }
+    /// Check whether the frame is compressed once encoded
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
+    /// Set whether the frame is compressed once encoded.
+    /// Note: the frame can only be compressed if the connection compression is enabled.
+    pub fn set_compressed(&mut self, compressed: bool) -> &mut Self {
+        self.compressed = compressed;
+        self
+    }
+}
+
+impl Query {
+    /// Decode the query frame, apply the function to it, then encode it again
+    pub fn map_frame<F: FnOnce(&mut QueryFrame)>(self, f: F) -> anyhow::Result<Query> {
+        let mut frame = QueryFrame::decode(&self.0)?;
+        f(&mut frame);
+        Ok(Query(frame.encode()?))
+    }
+}
+
+/// A statement of a batch frame, along with its values section (which starts with the value count)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchQuery {
+    /// The statement of a query
+    Statement(String, Vec<u8>),
+    /// The id of a prepared statement
+    Prepared(Vec<u8>, Vec<u8>),
+}
+
+/// The decoded BATCH frame, whose statements can be inspected and whose prepared ids can be replaced (ie once they
+/// are re-prepared), while its values and options are kept as they are.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::{Batch, BatchFrame, BatchQuery, Consistency, Statements, Values};
+///
+/// let Batch(payload) = Batch::new()
+///     .logged()
+///     .id(&[1; 16])
+///     .value(&"alice")
+///     .consistency(Consistency::One)
+///     .build()?;
+/// let mut frame = BatchFrame::decode(&payload)?;
+/// assert_eq!(frame.replace_prepared_id(&[1; 16], &[2; 16]), 1);
+/// assert!(matches!(&frame.queries()[0], BatchQuery::Prepared(id, _) if id == &[2; 16]));
+/// let payload = frame.encode()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchFrame {
+    version: u8,
+    header_flags: u8,
+    stream: i16,
+    batch_type: u8,
+    queries: Vec<BatchQuery>,
+    consistency: Consistency,
+    /// The flags along with the following options, ie the serial consistency and the timestamp
+    options: Vec<u8>,
+    compressed: bool,
+}
+
+impl BatchFrame {
+    /// Decode the BATCH frame, which is decompressed if needed
+    pub fn decode(payload: &[u8]) -> anyhow::Result<Self> {
+        let buffer = MyCompression::get().decompress(payload.to_vec())?;
+        ensure!(buffer.len() >= 12, "Buffer is too small!");
+        ensure!(
+            buffer[4] == BATCH,
+            "Unexpected opcode {:#04x}, expected a BATCH frame",
+            buffer[4]
+        );
+        // the queries are followed by the consistency and the flags, which tell whether the values are named
+        let is_complete = |position: usize, with_names: bool| match buffer.get(10 + position + 2) {
+            Some(flags) => {
+                (flags & WITH_NAMES == WITH_NAMES) == with_names
+                    && buffer.len() == 10 + position + 3 + options_length(*flags)
+            }
+            None => false,
+        };
+        let (queries, position) = match Self::decode_queries(&buffer[10..], false) {
+            Ok((queries, position)) if is_complete(position, false) => (queries, position),
+            _ => {
+                let (queries, position) = Self::decode_queries(&buffer[10..], true)?;
+                ensure!(is_complete(position, true), "The batch frame is malformed");
+                (queries, position)
+            }
+        };
+        let mut reader = Reader {
+            buffer: &buffer[10 + position..],
+            position: 0,
+        };
+        let consistency = Consistency::try_from(reader.take(2)?)?;
+        Ok(Self {
+            version: buffer[0],
+            header_flags: buffer[1] & !COMPRESSION,
+            stream: i16::from_be_bytes(buffer[2..4].try_into()?),
+            batch_type: buffer[9],
+            queries,
+            consistency,
+            options: reader.buffer[reader.position..].to_vec(),
+            compressed: buffer[1] & COMPRESSION == COMPRESSION,
+        })
+    }
+    /// Decode the queries of the batch body, which starts with the query count, returns the position of the
+    /// consistency
+    fn decode_queries(buffer: &[u8], with_names: bool) -> anyhow::Result<(Vec<BatchQuery>, usize)> {
+        let mut reader = Reader { buffer, position: 0 };
+        let count = reader.u16()?;
+        let mut queries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let kind = reader.take(1)?[0];
+            queries.push(match kind {
+                0 => {
+                    let length = reader.i32()?;
+                    ensure!(length >= 0, "Invalid statement length");
+                    let statement = String::from_utf8(reader.take(length as usize)?.to_vec())?;
+                    BatchQuery::Statement(statement, reader.values(with_names)?)
+                }
+                1 => {
+                    let length = reader.u16()?;
+                    let id = reader.take(length as usize)?.to_vec();
+                    BatchQuery::Prepared(id, reader.values(with_names)?)
+                }
+                _ => anyhow::bail!("Invalid batch query kind {}", kind),
+            });
+        }
+        Ok((queries, reader.position))
+    }
+    /// Encode the frame, which is compressed again if it was compressed
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        buffer.extend(&[self.version, self.header_flags]);
+        buffer.extend(&self.stream.to_be_bytes());
+        buffer.extend(&[BATCH, 0, 0, 0, 0, self.batch_type]);
+        buffer.extend(&(self.queries.len() as u16).to_be_bytes());
+        for query in self.queries.iter() {
+            match query {
+                BatchQuery::Statement(statement, values) => {
+                    buffer.push(0);
+                    buffer.extend(&(statement.len() as i32).to_be_bytes());
+                    buffer.extend(statement.as_bytes());
+                    buffer.extend(values);
+                }
+                BatchQuery::Prepared(id, values) => {
+                    buffer.push(1);
+                    buffer.extend(&(id.len() as u16).to_be_bytes());
+                    buffer.extend(id);
+                    buffer.extend(values);
+                }
+            }
+        }
+        buffer.extend(&(self.consistency as u16).to_be_bytes());
+        buffer.extend(&self.options);
+        MyCompression::compress_frame(buffer, Some(self.compressed))
+    }
+    /// Get the queries of the batch, in order
+    pub fn queries(&self) -> &[BatchQuery] {
+        &self.queries
+    }
+    /// Replace the prepared id of the queries which execute the prepared statement, returns the number of
+    /// replaced ids
+    pub fn replace_prepared_id(&mut self, id: &[u8], fresh_id: &[u8]) -> usize {
+        let mut count = 0;
+        for query in self.queries.iter_mut() {
+            if let BatchQuery::Prepared(prepared_id, _) = query {
+                if prepared_id.as_slice() == id {
+                    *prepared_id = fresh_id.to_vec();
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+    /// Get the consistency of the frame
+    pub fn consistency(&self) -> Consistency {
+        self.consistency
+    }
+    /// Set the consistency of the frame
+    pub fn set_consistency(&mut self, consistency: Consistency) -> &mut Self {
+        self.consistency = consistency;
+        self
+    }
+}
+
+/// Get the length of the batch options which follow the flags
+pub(crate) fn options_length(flags: u8) -> usize {
+    let mut length = 0;
+    if flags & super::batchflags::SERIAL_CONSISTENCY != 0 {
+        length += 2;
+    }
+    if flags & super::batchflags::TIMESTAMP != 0 {
+        length += 12;
+    }
+    length
+}
+
+impl Batch {
+    /// Decode the batch frame, apply the function to it, then encode it again
+    pub fn map_frame<F: FnOnce(&mut BatchFrame)>(self, f: F) -> anyhow::Result<Batch> {
+        let mut frame = BatchFrame::decode(&self.0)?;
+        f(&mut frame);
+        Ok(Batch(frame.encode()?))
+    }
+}
+
+/// Reads the sections of the frame body
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Take the next `length` bytes
+    fn take(&mut self, length: usize) -> anyhow::Result<&'a [u8]> {
+        let section = self
+            .buffer
+            .get(self.position..self.position + length)
+            .ok_or_else(|| anyhow!("The query frame is truncated"))?;
+        self.position += length;
+        Ok(section)
+    }
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+    /// Take the values section of a batch query, along with its value count
+    fn values(&mut self, with_names: bool) -> anyhow::Result<Vec<u8>> {
+        let start = self.position;
+        let count = self.u16()?;
+        for _ in 0..count {
+            if with_names {
+                let length = self.u16()?;
+                self.take(length as usize)?;
+            }
+            let length = self.i32()?;
+            // null and unset values don't have any content
+            if length > 0 {
+                self.take(length as usize)?;
+            }
+        }
+        Ok(self.buffer[start..self.position].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::{Statements, Values};
+
+    #[test]
+    fn map_query_frame() {
+        let query = Query::new()
+            .statement("SELECT * FROM shop.orders WHERE customer = ?")
+            .consistency(Consistency::One)
+            .value("alice")
+            .null_value()
+            .page_size(10)
+            .timestamp(42)
+            .build()
+            .unwrap();
+        let frame = QueryFrame::decode(&query.0).unwrap();
+        assert_eq!(frame.statement(), Some("SELECT * FROM shop.orders WHERE customer = ?"));
+        assert_eq!(frame.consistency(), Consistency::One);
+        assert_eq!(frame.flags(), VALUES | SKIP_METADATA | PAGE_SIZE | TIMESTAMP);
+        assert_eq!((frame.page_size(), frame.timestamp()), (Some(10), Some(42)));
+        assert_eq!(frame.encode().unwrap(), query.0);
+        let mapped = query
+            .clone()
+            .map_frame(|frame| {
+                frame
+                    .set_consistency(Consistency::LocalQuorum)
+                    .set_page_size(None)
+                    .set_serial_consistency(Some(Consistency::LocalSerial))
+                    .set_timestamp(Some(7))
+                    .set_tracing(true);
+            })
+            .unwrap();
+        let frame = QueryFrame::decode(&mapped.0).unwrap();
+        assert_eq!(frame.consistency(), Consistency::LocalQuorum);
+        assert_eq!(frame.flags(), VALUES | SKIP_METADATA | SERIAL_CONSISTENCY | TIMESTAMP);
+        assert_eq!(frame.serial_consistency(), Some(Consistency::LocalSerial));
+        assert_eq!((frame.page_size(), frame.timestamp()), (None, Some(7)));
+        assert!(frame.tracing());
+        // the values are kept as they are
+        assert_eq!(frame.values, QueryFrame::decode(&query.0).unwrap().values);
+        // the prepared statements keep their id
+        let execute = Query::new()
+            .id(&[3; 16])
+            .consistency(Consistency::One)
+            .build()
+            .unwrap()
+            .map_frame(|frame| {
+                frame.set_consistency(Consistency::All);
+            })
+            .unwrap();
+        let frame = QueryFrame::decode(&execute.0).unwrap();
+        assert_eq!((frame.prepared_id(), frame.statement()), (Some(&[3; 16][..]), None));
+        assert_eq!(frame.consistency(), Consistency::All);
+    }
+
+    #[test]
+    fn map_batch_frame() {
+        let batch = Batch::new()
+            .unlogged()
+            .statement("INSERT INTO shop.orders (customer, id) VALUES (?, ?)")
+            .value("alice")
+            .null_value()
+            .id(&[1; 16])
+            .value(&7)
+            .unset_value()
+            .id(&[1; 16])
+            .id(&[2; 16])
+            .value("bob")
+            .consistency(Consistency::Quorum)
+            .serial_consistency(Consistency::LocalSerial)
+            .timestamp(42)
+            .build()
+            .unwrap();
+        let mut frame = BatchFrame::decode(&batch.0).unwrap();
+        assert_eq!(frame.encode().unwrap(), batch.0);
+        assert_eq!(frame.queries().len(), 4);
+        assert!(matches!(
+            &frame.queries()[0],
+            BatchQuery::Statement(statement, _) if statement == "INSERT INTO shop.orders (customer, id) VALUES (?, ?)"
+        ));
+        assert_eq!(frame.consistency(), Consistency::Quorum);
+        // the prepared ids are replaced while the values are kept
+        assert_eq!(frame.replace_prepared_id(&[1; 16], &[9; 16]), 2);
+        let replaced = BatchFrame::decode(&frame.encode().unwrap()).unwrap();
+        let original = BatchFrame::decode(&batch.0).unwrap();
+        for (replaced, original) in replaced.queries().iter().zip(original.queries()) {
+            match (replaced, original) {
+                (BatchQuery::Prepared(id, values), BatchQuery::Prepared(original_id, original_values)) => {
+                    assert_eq!(values, original_values);
+                    let expected = if original_id == &[1; 16] { [9; 16] } else { [2; 16] };
+                    assert_eq!(id, &expected);
+                }
+                (replaced, original) => assert_eq!(replaced, original),
+            }
+        }
+        assert_eq!(replaced.options, original.options);
+        // the named values are decoded as well
+        let named = Batch::new()
+            .logged()
+            .id(&[3; 16])
+            .named_value("customer", "alice")
+            .named_null_value("note")
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        let frame = BatchFrame::decode(&named.0).unwrap();
+        assert_eq!(frame.encode().unwrap(), named.0);
+        assert!(matches!(&frame.queries()[0], BatchQuery::Prepared(id, _) if id == &[3; 16]));
+        // a truncated frame is rejected
+        assert!(BatchFrame::decode(&batch.0[..batch.0.len() - 4]).is_err());
+    }
+}
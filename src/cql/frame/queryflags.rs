@@ -15,3 +15,6 @@ pub const PAGING_STATE: u8 = 0x08;
 pub const SERIAL_CONSISTENCY: u8 = 0x10;
 /// The query flag indicates whether to use the default timestamp or not.
 pub const TIMESTAMP: u8 = 0x20;
+/// The v5 query flag indicates whether the keyspace of the statement is present or not, see
+/// `ProtocolVersion::encode_request`.
+pub const WITH_KEYSPACE: u8 = 0x80;
@@ -0,0 +1,95 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the Register frame.
+
+use super::{event::EventType, opcode::REGISTER};
+
+/// Blanket cql frame header for REGISTER frame.
+const REGISTER_HEADER: &[u8] = &[4, 0, 0, 0, REGISTER, 0, 0, 0, 0];
+
+/// The Register frame structure, which registers the connection to receive the server events.
+pub struct Register(pub Vec<u8>);
+
+/// The Register frame builder.
+pub struct RegisterBuilder<Stage> {
+    buffer: Vec<u8>,
+    #[allow(unused)]
+    stage: Stage,
+}
+
+pub struct RegisterHeader;
+/// Gating type for register events
+pub struct RegisterEvents;
+pub struct RegisterBuild;
+
+impl RegisterBuilder<RegisterHeader> {
+    fn new() -> RegisterBuilder<RegisterEvents> {
+        let mut buffer: Vec<u8> = Vec::new();
+        buffer.extend_from_slice(REGISTER_HEADER);
+        RegisterBuilder::<RegisterEvents> {
+            buffer,
+            stage: RegisterEvents,
+        }
+    }
+}
+
+impl Default for RegisterBuilder<RegisterEvents> {
+    fn default() -> Self {
+        RegisterBuilder::<RegisterHeader>::new()
+    }
+}
+
+impl RegisterBuilder<RegisterEvents> {
+    /// Set the event types to register for.
+    pub fn events(mut self, events: &[EventType]) -> RegisterBuilder<RegisterBuild> {
+        self.buffer.extend(&u16::to_be_bytes(events.len() as u16));
+        for event in events {
+            let event = event.as_str();
+            self.buffer.extend(&u16::to_be_bytes(event.len() as u16));
+            self.buffer.extend(event.bytes());
+        }
+        let body_length = i32::to_be_bytes((self.buffer.len() as i32) - 9);
+        self.buffer[5..9].copy_from_slice(&body_length);
+        RegisterBuilder {
+            buffer: self.buffer,
+            stage: RegisterBuild,
+        }
+    }
+}
+
+impl RegisterBuilder<RegisterBuild> {
+    /// Build the uncompressed Register frame.
+    pub fn build(self) -> Register {
+        Register(self.buffer)
+    }
+}
+
+impl Register {
+    /// Create Register cql frame
+    pub fn new() -> RegisterBuilder<RegisterEvents> {
+        RegisterBuilder::<RegisterHeader>::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::frame::decoder::string_list;
+
+    #[test]
+    fn simple_register_builder_test() {
+        let Register(payload) = Register::new()
+            .events(&[EventType::TopologyChange, EventType::SchemaChange])
+            .build();
+        assert_eq!(payload[4], REGISTER);
+        assert_eq!(
+            i32::from_be_bytes([payload[5], payload[6], payload[7], payload[8]]) as usize,
+            payload.len() - 9
+        );
+        assert_eq!(
+            string_list(&payload[9..]).unwrap(),
+            vec!["TOPOLOGY_CHANGE", "SCHEMA_CHANGE"]
+        );
+    }
+}
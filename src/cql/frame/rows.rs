@@ -3,15 +3,21 @@
 
 //! This module defines the row/column decoder/encoder for the frame structure.
 
-use super::{ColumnDecoder, Frame};
-use anyhow::ensure;
+use super::{
+    cql_type::{self, CqlType},
+    ColumnDecoder, Frame, HashableKey,
+};
+use crate::cql::compression::Uncompressed;
+use anyhow::{anyhow, bail, ensure};
 use log::error;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     convert::TryInto,
     hash::Hash,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::{Duration, SystemTime},
 };
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 /// The column count type.
 pub type ColumnsCount = i32;
@@ -36,6 +42,14 @@ impl Flags {
     pub fn has_more_pages(&self) -> bool {
         self.has_more_pages
     }
+    /// Check if the column specs were skipped, ie the request was sent with the `SKIP_METADATA` flag.
+    pub fn no_metadata(&self) -> bool {
+        self.no_metadata
+    }
+    /// Check if all the columns belong to the keyspace and the table of the global table spec.
+    pub fn global_table_spec(&self) -> bool {
+        self.global_table_spec
+    }
 }
 #[derive(Debug, Clone)]
 /// The pageing state of the response.
@@ -49,12 +63,26 @@ impl PagingState {
         PagingState { paging_state, end }
     }
 }
+/// The spec of a result column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSpec {
+    /// The keyspace of the column.
+    pub keyspace: String,
+    /// The table of the column.
+    pub table: String,
+    /// The column name, or the alias of the selected expression.
+    pub name: String,
+    /// The column type, where the user defined types are named by their keyspace qualified names.
+    pub cql_type: CqlType,
+}
+
 #[derive(Debug, Clone)]
 /// The meta structure of the row.
 pub struct Metadata {
     flags: Flags,
     columns_count: ColumnsCount,
     paging_state: PagingState,
+    columns: Vec<ColumnSpec>,
 }
 
 impl Metadata {
@@ -64,8 +92,27 @@ impl Metadata {
             flags,
             columns_count,
             paging_state,
+            columns: Vec::new(),
         }
     }
+    /// Set the column specs, which end where the rows start.
+    pub(crate) fn with_columns(mut self, columns: Vec<ColumnSpec>, rows_start: usize) -> Self {
+        self.columns = columns;
+        self.paging_state.end = rows_start;
+        self
+    }
+    /// Get the columns count of the rows.
+    pub fn columns_count(&self) -> ColumnsCount {
+        self.columns_count
+    }
+    /// Get the column specs of the rows, which are empty if they were skipped (see `Flags::no_metadata`).
+    pub fn columns(&self) -> &[ColumnSpec] {
+        &self.columns
+    }
+    /// Get the index of the column, if the column specs weren't skipped.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column.name == name)
+    }
     /// Get the starting rows.
     pub fn rows_start(&self) -> usize {
         self.paging_state.end
@@ -106,6 +153,25 @@ pub trait Row: Sized {
         Self: Sized;
 }
 
+/// The result of a lightweight transaction, ie of a conditional insert, update, delete or batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LwtResult<T> {
+    /// Whether the condition held, so the statement has been applied
+    pub applied: bool,
+    /// The current values of the row when the statement wasn't applied, if the server returned them
+    pub existing: Option<T>,
+}
+
+impl<T: Row> Row for LwtResult<T> {
+    /// Decode the `[applied]` column, followed by the current values of the row when it wasn't applied. The
+    /// values are left out if they're missing or don't decode as `T`, ie the nulls of a missing row.
+    fn try_decode_row<R: Rows + ColumnValue>(rows: &mut R) -> anyhow::Result<Self> {
+        let applied = rows.column_value::<bool>()?;
+        let existing = if applied { None } else { T::try_decode_row(rows).ok() };
+        Ok(Self { applied, existing })
+    }
+}
+
 /// Defines a result-set column value
 pub trait ColumnValue {
     /// Decode the column value of C type;
@@ -328,6 +394,15 @@ impl Row for u8 {
     }
 }
 
+impl Row for bool {
+    fn try_decode_row<R: Rows + ColumnValue>(rows: &mut R) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        rows.column_value()
+    }
+}
+
 impl Row for String {
     fn try_decode_row<R: Rows + ColumnValue>(rows: &mut R) -> anyhow::Result<Self>
     where
@@ -390,6 +465,499 @@ where
     }
 }
 
+impl<K, V> Row for BTreeMap<K, V>
+where
+    K: Ord + ColumnDecoder,
+    V: ColumnDecoder,
+{
+    fn try_decode_row<R: Rows + ColumnValue>(rows: &mut R) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        rows.column_value()
+    }
+}
+
+impl<T: ColumnDecoder> Row for HashableKey<T> {
+    fn try_decode_row<R: Rows + ColumnValue>(rows: &mut R) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        rows.column_value()
+    }
+}
+
+/// A column value decoded alongside its `TTL(col)`, which computes the local expiry instant
+/// of the value, useful for cache layers which mirror the scylla expiry semantics.
+///
+/// The row is expected to select the value column followed by its ttl, as generated by
+/// `ExpiringValue::selector`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpiringValue<T> {
+    /// The decoded column value
+    pub value: T,
+    /// The local instant at which the value expires, or None if the value has no ttl
+    pub expires_at: Option<SystemTime>,
+}
+
+impl<T> ExpiringValue<T> {
+    /// Generate the select clause of the column and its ttl, ie `col, TTL(col)`
+    pub fn selector(column: &str) -> String {
+        format!("{}, TTL({})", column, column)
+    }
+    /// Create an expiring value from the server ttl in seconds, relative to the local clock
+    pub fn new(value: T, ttl: Option<i32>) -> Self {
+        let expires_at = ttl.map(|ttl| SystemTime::now() + Duration::from_secs(ttl.max(0) as u64));
+        Self { value, expires_at }
+    }
+    /// Get the remaining time to live of the value, None if it has no ttl
+    pub fn remaining_ttl(&self) -> Option<Duration> {
+        self.expires_at
+            .map(|expires_at| expires_at.duration_since(SystemTime::now()).unwrap_or_default())
+    }
+    /// Check if the value has already expired according to the local clock
+    pub fn is_expired(&self) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= SystemTime::now())
+    }
+}
+
+impl<T: ColumnDecoder> Row for ExpiringValue<T> {
+    fn try_decode_row<R: Rows + ColumnValue>(rows: &mut R) -> anyhow::Result<Self>
+    where
+        Self: Sized,
+    {
+        let value = rows.column_value::<T>()?;
+        let ttl = rows.column_value::<Option<i32>>()?;
+        Ok(Self::new(value, ttl))
+    }
+}
+
+/// The length of the frame header.
+const HEADER_LEN: usize = 9;
+/// The length of the synthetic frame prefix, ie the header and the rows kind, flags, columns count and rows count.
+const ROW_FRAME_PREFIX_LEN: usize = HEADER_LEN + 16;
+
+/// An incremental decoder of the rows of a RESULT frame, which decodes every row as soon as its bytes have
+/// been fed, and then drops them, so the memory is bounded by the largest row rather than the whole page.
+///
+/// The bytes can be fed by `feed` out of a partially received frame, or read by `read_row` out of the
+/// connection, which never reads beyond the end of the frame.
+///
+/// Note: the frame must be uncompressed, as a compressed body can only be decompressed as a whole, and the
+/// result must skip the metadata, as all the driver queries do.
+///
+/// ## Example
+/// ```
+/// use scylla_rs::cql::RowsStream;
+///
+/// let mut stream = RowsStream::<(i32,)>::new();
+/// let frame = [
+///     132, 0, 0, 0, 8, 0, 0, 0, 24, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 4, 0, 0, 0, 7,
+/// ];
+/// let mut rows = Vec::new();
+/// for chunk in frame.chunks(5) {
+///     stream.feed(chunk);
+///     while let Some(row) = stream.next_row()? {
+///         rows.push(row);
+///     }
+/// }
+/// assert_eq!(rows, vec![(7,)]);
+/// assert!(stream.is_done());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct RowsStream<T: Row> {
+    buffer: Vec<u8>,
+    /// The position of the first unconsumed byte within the buffer
+    position: usize,
+    /// The number of the frame bytes fed so far
+    received: usize,
+    /// The length of the whole frame, once the header has been received
+    frame_len: Option<usize>,
+    state: StreamState,
+    _marker: std::marker::PhantomData<T>,
+}
+
+enum StreamState {
+    /// Waiting for the header and the rows metadata
+    Header,
+    /// Decoding the remaining rows
+    Rows {
+        metadata: Metadata,
+        remaining_rows_count: usize,
+    },
+    /// Waiting for the whole frame, as it's not a rows result
+    Other,
+    /// All the rows have been decoded
+    Done(Option<Metadata>),
+}
+
+/// The rows metadata which precedes the rows of a RESULT frame
+enum RowsPrefix {
+    /// The rows metadata, along with the position of the first row and the rows count
+    Rows(Metadata, usize, usize),
+    /// The frame is not a rows result
+    Other,
+}
+
+impl<T: Row> Default for RowsStream<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Row> RowsStream<T> {
+    /// Create a new rows stream decoder
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            position: 0,
+            received: 0,
+            frame_len: None,
+            state: StreamState::Header,
+            _marker: std::marker::PhantomData,
+        }
+    }
+    /// Feed the next bytes of the frame. Any byte beyond the end of the frame is ignored.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let chunk = match self.frame_len {
+            Some(frame_len) => &chunk[..chunk.len().min(frame_len.saturating_sub(self.received))],
+            None => chunk,
+        };
+        // drop the consumed bytes before growing the buffer
+        if self.position > 0 && self.position >= self.buffer.len() / 2 {
+            self.buffer.drain(..self.position);
+            self.position = 0;
+        }
+        self.buffer.extend_from_slice(chunk);
+        self.received += chunk.len();
+        if self.frame_len.is_none() && self.buffer.len() >= HEADER_LEN {
+            let length = i32::from_be_bytes([self.buffer[5], self.buffer[6], self.buffer[7], self.buffer[8]]);
+            self.frame_len = Some(HEADER_LEN + length.max(0) as usize);
+        }
+    }
+    /// Get the number of the frame bytes which are still expected, or the remaining header length if the
+    /// header hasn't been received yet
+    pub fn remaining_frame_bytes(&self) -> usize {
+        match self.frame_len {
+            Some(frame_len) => frame_len.saturating_sub(self.received),
+            None => HEADER_LEN - self.received,
+        }
+    }
+    /// Check if all the rows have been decoded
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, StreamState::Done(_))
+    }
+    /// Get the rows metadata, once it has been received
+    pub fn metadata(&self) -> Option<&Metadata> {
+        match &self.state {
+            StreamState::Rows { metadata, .. } | StreamState::Done(Some(metadata)) => Some(metadata),
+            _ => None,
+        }
+    }
+    /// Take the paging state of the rows metadata, once it has been received
+    pub fn take_paging_state(&mut self) -> Option<Vec<u8>> {
+        match &mut self.state {
+            StreamState::Rows { metadata, .. } | StreamState::Done(Some(metadata)) => metadata.take_paging_state(),
+            _ => None,
+        }
+    }
+    /// Decode the next row out of the fed bytes. Returns None if the row hasn't been fully fed yet, or if all
+    /// the rows have been decoded. Returns the error of the response if the frame is not a rows result.
+    pub fn next_row(&mut self) -> anyhow::Result<Option<T>> {
+        loop {
+            match &mut self.state {
+                StreamState::Header => match rows_prefix(&self.buffer)? {
+                    Some(RowsPrefix::Rows(metadata, rows_start, rows_count)) => {
+                        self.position = rows_start;
+                        self.state = if rows_count == 0 {
+                            StreamState::Done(Some(metadata))
+                        } else {
+                            StreamState::Rows {
+                                metadata,
+                                remaining_rows_count: rows_count,
+                            }
+                        };
+                    }
+                    Some(RowsPrefix::Other) => self.state = StreamState::Other,
+                    None => return Ok(None),
+                },
+                StreamState::Rows {
+                    metadata,
+                    remaining_rows_count,
+                } => {
+                    let columns_count = metadata.columns_count().max(0) as usize;
+                    let row = &self.buffer[self.position..];
+                    let row_len = match row_len(row, columns_count) {
+                        Some(row_len) => row_len,
+                        None => return Ok(None),
+                    };
+                    // decode the row out of a synthetic single row frame
+                    let mut frame = Vec::with_capacity(ROW_FRAME_PREFIX_LEN + row_len);
+                    frame.extend_from_slice(&[0x84, 0, 0, 0, super::opcode::RESULT]);
+                    frame.extend_from_slice(&i32::to_be_bytes((ROW_FRAME_PREFIX_LEN - HEADER_LEN + row_len) as i32));
+                    frame.extend_from_slice(&i32::to_be_bytes(super::result::ROWS));
+                    frame.extend_from_slice(&i32::to_be_bytes(4));
+                    frame.extend_from_slice(&i32::to_be_bytes(columns_count as i32));
+                    frame.extend_from_slice(&i32::to_be_bytes(1));
+                    frame.extend_from_slice(&row[..row_len]);
+                    let mut iter = Iter::<T>::new(super::Decoder::new(frame, Uncompressed)?)?;
+                    let row = T::try_decode_row(&mut iter)?;
+                    self.position += row_len;
+                    *remaining_rows_count -= 1;
+                    if *remaining_rows_count == 0 {
+                        if let StreamState::Rows { metadata, .. } =
+                            std::mem::replace(&mut self.state, StreamState::Done(None))
+                        {
+                            self.state = StreamState::Done(Some(metadata));
+                        }
+                        self.buffer = Vec::new();
+                        self.position = 0;
+                    }
+                    return Ok(Some(row));
+                }
+                StreamState::Other => {
+                    if self.remaining_frame_bytes() > 0 || self.frame_len.is_none() {
+                        return Ok(None);
+                    }
+                    let decoder = super::Decoder::new(std::mem::take(&mut self.buffer), Uncompressed)?;
+                    self.state = StreamState::Done(None);
+                    if decoder.is_error()? {
+                        bail!(decoder.get_error()?);
+                    }
+                    bail!("Decoded response is not rows!");
+                }
+                StreamState::Done(_) => return Ok(None),
+            }
+        }
+    }
+    /// Read the frame out of the reader until the next row is decoded. Returns None once all the rows have
+    /// been decoded.
+    pub async fn read_row<R: AsyncRead + Unpin>(&mut self, reader: &mut R) -> anyhow::Result<Option<T>> {
+        let mut chunk = [0; 8192];
+        loop {
+            if let Some(row) = self.next_row()? {
+                return Ok(Some(row));
+            }
+            if self.is_done() {
+                return Ok(None);
+            }
+            let len = self.remaining_frame_bytes().min(chunk.len());
+            ensure!(len > 0, "The frame ended before all the rows were received");
+            let read = reader.read(&mut chunk[..len]).await?;
+            ensure!(read > 0, "The connection closed before all the rows were received");
+            self.feed(&chunk[..read]);
+        }
+    }
+}
+
+/// Parse the header and the rows metadata, returns None if they haven't been fully received yet
+fn rows_prefix(buffer: &[u8]) -> anyhow::Result<Option<RowsPrefix>> {
+    let short = |pos: usize| {
+        buffer
+            .get(pos..pos + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+    };
+    let int = |pos: usize| {
+        buffer
+            .get(pos..pos + 4)
+            .map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    if buffer.len() < HEADER_LEN {
+        return Ok(None);
+    }
+    let header_flags = buffer[1];
+    ensure!(
+        header_flags & super::header::COMPRESSION == 0,
+        "The compressed frames can't be decoded incrementally"
+    );
+    if buffer[4] != super::opcode::RESULT {
+        return Ok(Some(RowsPrefix::Other));
+    }
+    let mut pos = HEADER_LEN;
+    if header_flags & super::header::TRACING != 0 {
+        pos += 16;
+    }
+    if header_flags & super::header::WARNING != 0 {
+        let count = match short(pos) {
+            Some(count) => count,
+            None => return Ok(None),
+        };
+        pos += 2;
+        for _ in 0..count {
+            match short(pos) {
+                Some(len) => pos += 2 + len,
+                None => return Ok(None),
+            }
+        }
+    }
+    if header_flags & super::header::CUSTOM_PAYLOAD != 0 {
+        let count = match short(pos) {
+            Some(count) => count,
+            None => return Ok(None),
+        };
+        pos += 2;
+        for _ in 0..count {
+            match short(pos) {
+                Some(len) => pos += 2 + len,
+                None => return Ok(None),
+            }
+            match int(pos) {
+                Some(len) => pos += 4 + len.max(0) as usize,
+                None => return Ok(None),
+            }
+        }
+    }
+    let (kind, flags, columns_count) = match (int(pos), int(pos + 4), int(pos + 8)) {
+        (Some(kind), Some(flags), Some(columns_count)) => (kind, Flags::from_i32(flags), columns_count),
+        (Some(kind), ..) if kind != super::result::ROWS => return Ok(Some(RowsPrefix::Other)),
+        _ => return Ok(None),
+    };
+    if kind != super::result::ROWS {
+        return Ok(Some(RowsPrefix::Other));
+    }
+    pos += 12;
+    let paging_state = if flags.has_more_pages() {
+        match int(pos) {
+            Some(len) if len < 0 => {
+                pos += 4;
+                PagingState::new(None, pos)
+            }
+            Some(len) => match buffer.get(pos + 4..pos + 4 + len as usize) {
+                Some(paging_state) => {
+                    pos += 4 + len as usize;
+                    PagingState::new(Some(paging_state.to_vec()), pos)
+                }
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        }
+    } else {
+        PagingState::new(None, pos)
+    };
+    let mut metadata = Metadata::new(flags, columns_count, paging_state);
+    if !flags.no_metadata {
+        match column_specs(buffer, pos, flags, columns_count) {
+            Ok((columns, end)) => {
+                metadata = metadata.with_columns(columns, end);
+                pos = end;
+            }
+            // the column specs are only invalid if the whole frame has been received
+            Err(e) => {
+                let length = i32::from_be_bytes([buffer[5], buffer[6], buffer[7], buffer[8]]);
+                if buffer.len() >= HEADER_LEN + length.max(0) as usize {
+                    return Err(e);
+                }
+                return Ok(None);
+            }
+        }
+    }
+    let rows_count = match int(pos) {
+        Some(rows_count) => rows_count.max(0) as usize,
+        None => return Ok(None),
+    };
+    Ok(Some(RowsPrefix::Rows(metadata, pos + 4, rows_count)))
+}
+
+/// Parse the column specs of the rows metadata, which start at the position of the buffer. Returns them along
+/// with the position of their end.
+pub(crate) fn column_specs(
+    buffer: &[u8],
+    pos: usize,
+    flags: Flags,
+    columns_count: ColumnsCount,
+) -> anyhow::Result<(Vec<ColumnSpec>, usize)> {
+    let mut reader = SpecReader { buffer, pos };
+    let global_table_spec = if flags.global_table_spec {
+        Some((reader.string()?, reader.string()?))
+    } else {
+        None
+    };
+    // don't trust the columns count for the allocation
+    let mut columns = Vec::with_capacity(columns_count.clamp(0, 64) as usize);
+    for _ in 0..columns_count.max(0) {
+        let (keyspace, table) = match &global_table_spec {
+            Some((keyspace, table)) => (keyspace.clone(), table.clone()),
+            None => (reader.string()?, reader.string()?),
+        };
+        let name = reader.string()?;
+        columns.push(ColumnSpec {
+            keyspace,
+            table,
+            name,
+            cql_type: reader.option()?,
+        });
+    }
+    Ok((columns, reader.pos))
+}
+
+/// The reader of the `[string]` and `[option]` notations of the column specs
+struct SpecReader<'a> {
+    buffer: &'a [u8],
+    pos: usize,
+}
+
+impl SpecReader<'_> {
+    fn short(&mut self) -> anyhow::Result<u16> {
+        let bytes = self
+            .buffer
+            .get(self.pos..self.pos + 2)
+            .ok_or_else(|| anyhow!("Buffer is too small!"))?;
+        self.pos += 2;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.short()? as usize;
+        let bytes = self
+            .buffer
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow!("Buffer is too small!"))?;
+        self.pos += len;
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+    fn option(&mut self) -> anyhow::Result<CqlType> {
+        Ok(match self.short()? {
+            cql_type::CUSTOM => CqlType::Custom(self.string()?),
+            cql_type::LIST => CqlType::List(Box::new(self.option()?)),
+            cql_type::SET => CqlType::Set(Box::new(self.option()?)),
+            cql_type::MAP => {
+                let key = self.option()?;
+                CqlType::Map(Box::new(key), Box::new(self.option()?))
+            }
+            cql_type::UDT => {
+                let keyspace = self.string()?;
+                let name = self.string()?;
+                // the fields are part of the schema of the type, rather than of the column
+                for _ in 0..self.short()? {
+                    self.string()?;
+                    self.option()?;
+                }
+                CqlType::Udt(format!("{}.{}", keyspace, name))
+            }
+            cql_type::TUPLE => {
+                let len = self.short()?;
+                CqlType::Tuple((0..len).map(|_| self.option()).collect::<anyhow::Result<_>>()?)
+            }
+            id => CqlType::from_option_id(id).ok_or_else(|| anyhow!("Unknown column type id: {:#06x}", id))?,
+        })
+    }
+}
+
+/// Get the length of the row, returns None if it hasn't been fully received yet
+fn row_len(row: &[u8], columns_count: usize) -> Option<usize> {
+    let mut pos = 0;
+    for _ in 0..columns_count {
+        let len = row
+            .get(pos..pos + 4)
+            .map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]))?;
+        pos += 4 + len.max(0) as usize;
+    }
+    if row.len() >= pos {
+        Some(pos)
+    } else {
+        None
+    }
+}
+
 #[macro_export]
 /// The rows macro implements the row decoder.
 macro_rules! rows {
@@ -542,3 +1110,58 @@ macro_rules! rows {
         rows!(@common_iter $rows$(<$($t),+>)?, $row {$( $col_field: $col_type),*}, $row_into);
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_stream() {
+        // a rows frame with a warning, a paging state and two (int, text) rows, where the second text is null
+        let mut body = vec![0, 1, 0, 2, b'w', b'!'];
+        body.extend(&[0, 0, 0, 2, 0, 0, 0, 6, 0, 0, 0, 2, 0, 0, 0, 2, 1, 2]);
+        body.extend(&[0, 0, 0, 2]);
+        body.extend(&[0, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0, 3, b'a', b'b', b'c']);
+        body.extend(&[0, 0, 0, 4, 0, 0, 0, 2, 255, 255, 255, 255]);
+        let mut frame = vec![132, super::super::header::WARNING, 0, 0, super::super::opcode::RESULT];
+        frame.extend(&i32::to_be_bytes(body.len() as i32));
+        frame.extend(&body);
+        // trailing bytes of the next frame are ignored
+        frame.extend(&[132, 0, 0, 0]);
+        let mut stream = RowsStream::<(i32, Option<String>)>::new();
+        let mut rows = Vec::new();
+        for byte in frame.iter() {
+            stream.feed(&[*byte]);
+            while let Some(row) = stream.next_row().unwrap() {
+                rows.push(row);
+            }
+        }
+        assert_eq!(rows, vec![(1, Some("abc".to_string())), (2, None)]);
+        assert!(stream.is_done());
+        assert_eq!(stream.remaining_frame_bytes(), 0);
+        assert_eq!(stream.take_paging_state(), Some(vec![1, 2]));
+        // the errors are returned once the whole frame is received
+        let mut stream = RowsStream::<(i32,)>::new();
+        stream.feed(&[
+            132,
+            0,
+            0,
+            0,
+            super::super::opcode::ERROR,
+            0,
+            0,
+            0,
+            8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            2,
+            b'n',
+        ]);
+        assert!(stream.next_row().unwrap().is_none());
+        stream.feed(&[b'o']);
+        assert!(stream.next_row().is_err());
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the uncompressed segments of the native protocol v5, which wrap the frames once the
+//! connection is ready. See `https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v5.spec`.
+//!
+//! Each segment consists of a 3 bytes header (17 bits payload length, 1 bit self-contained flag), followed by
+//! its CRC24, the payload and the payload CRC32, where every field is little-endian.
+
+use anyhow::{bail, ensure};
+
+/// The maximum payload length of a segment
+pub const MAX_SEGMENT_PAYLOAD: usize = 128 * 1024 - 1;
+/// The segment header length, including its CRC24
+const HEADER_LENGTH: usize = 6;
+/// The payload CRC32 length
+const TRAILER_LENGTH: usize = 4;
+const SELF_CONTAINED: u32 = 1 << 17;
+const CRC24_INIT: u32 = 0x875060;
+const CRC24_POLY: u32 = 0x1974F0B;
+/// The bytes which initialize the CRC32 of the payloads
+const CRC32_INITIAL_BYTES: [u8; 4] = [0xFA, 0x2D, 0x55, 0xCA];
+
+/// Compute the CRC24 of the `len` least significant bytes of the value
+fn crc24(mut value: u64, len: usize) -> u32 {
+    let mut crc = CRC24_INIT;
+    for _ in 0..len {
+        crc ^= ((value & 0xff) as u32) << 16;
+        value >>= 8;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+    crc
+}
+
+/// Update the (inverted) CRC32 state with the bytes
+fn crc32_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Compute the CRC32 of the payload
+fn crc32(payload: &[u8]) -> u32 {
+    !crc32_update(crc32_update(!0, &CRC32_INITIAL_BYTES), payload)
+}
+
+fn put_3_bytes(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes()[..3]);
+}
+
+fn get_3_bytes(slice: &[u8]) -> u32 {
+    u32::from_le_bytes([slice[0], slice[1], slice[2], 0])
+}
+
+/// Wrap the frames into segments. The frames which fit in a single segment are packed into self-contained
+/// segments, while a larger frame is split across as many non self-contained segments as needed.
+pub fn encode_segments<T: AsRef<[u8]>>(frames: &[T]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    let mut payload = Vec::new();
+    for frame in frames.iter().map(AsRef::as_ref) {
+        if frame.len() > MAX_SEGMENT_PAYLOAD {
+            if !payload.is_empty() {
+                encode_segment(&mut buffer, &payload, true);
+                payload.clear();
+            }
+            for chunk in frame.chunks(MAX_SEGMENT_PAYLOAD) {
+                encode_segment(&mut buffer, chunk, false);
+            }
+        } else {
+            if payload.len() + frame.len() > MAX_SEGMENT_PAYLOAD {
+                encode_segment(&mut buffer, &payload, true);
+                payload.clear();
+            }
+            payload.extend_from_slice(frame);
+        }
+    }
+    if !payload.is_empty() {
+        encode_segment(&mut buffer, &payload, true);
+    }
+    buffer
+}
+
+fn encode_segment(buffer: &mut Vec<u8>, payload: &[u8], self_contained: bool) {
+    let mut header = payload.len() as u32;
+    if self_contained {
+        header |= SELF_CONTAINED;
+    }
+    put_3_bytes(buffer, header);
+    put_3_bytes(buffer, crc24(header as u64, 3));
+    buffer.extend_from_slice(payload);
+    buffer.extend_from_slice(&crc32(payload).to_le_bytes());
+}
+
+/// Reassembles the frames out of the received segments.
+#[derive(Debug, Default)]
+pub struct SegmentDecoder {
+    buffer: Vec<u8>,
+    partial: Vec<u8>,
+}
+
+impl SegmentDecoder {
+    /// Create a new segment decoder
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Append the received bytes
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+    /// Decode the complete frames out of the received segments, the incomplete segments and frames are kept
+    /// until the remaining bytes are received. Returns an error if any checksum doesn't match.
+    pub fn decode(&mut self) -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut frames = Vec::new();
+        let mut offset = 0;
+        while self.buffer.len() - offset >= HEADER_LENGTH {
+            let header = get_3_bytes(&self.buffer[offset..]);
+            ensure!(
+                crc24(header as u64, 3) == get_3_bytes(&self.buffer[offset + 3..]),
+                "Segment header CRC24 mismatch"
+            );
+            let length = (header & MAX_SEGMENT_PAYLOAD as u32) as usize;
+            let end = offset + HEADER_LENGTH + length + TRAILER_LENGTH;
+            if self.buffer.len() < end {
+                break;
+            }
+            let payload = &self.buffer[offset + HEADER_LENGTH..end - TRAILER_LENGTH];
+            ensure!(
+                crc32(payload).to_le_bytes() == self.buffer[end - TRAILER_LENGTH..end],
+                "Segment payload CRC32 mismatch"
+            );
+            if header & SELF_CONTAINED != 0 {
+                ensure!(self.partial.is_empty(), "Self-contained segment within a split frame");
+                split_frames(payload, &mut frames)?;
+            } else {
+                self.partial.extend_from_slice(payload);
+                if let Some(length) = frame_length(&self.partial) {
+                    if self.partial.len() >= length {
+                        ensure!(self.partial.len() == length, "Split frame segment exceeds the frame");
+                        frames.push(std::mem::take(&mut self.partial));
+                    }
+                }
+            }
+            offset = end;
+        }
+        self.buffer.drain(..offset);
+        Ok(frames)
+    }
+}
+
+/// Get the total length of the frame out of its header, if it's complete
+fn frame_length(frame: &[u8]) -> Option<usize> {
+    if frame.len() < 9 {
+        return None;
+    }
+    Some(9 + i32::from_be_bytes([frame[5], frame[6], frame[7], frame[8]]) as usize)
+}
+
+/// Split the payload of a self-contained segment into its frames
+fn split_frames(mut payload: &[u8], frames: &mut Vec<Vec<u8>>) -> anyhow::Result<()> {
+    while !payload.is_empty() {
+        match frame_length(payload) {
+            Some(length) if length <= payload.len() => {
+                frames.push(payload[..length].to_vec());
+                payload = &payload[length..];
+            }
+            _ => bail!("Self-contained segment with an incomplete frame"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(stream: u8, body_length: usize) -> Vec<u8> {
+        let mut frame = vec![5, 0, 0, stream, 0x08];
+        frame.extend_from_slice(&(body_length as i32).to_be_bytes());
+        frame.extend((0..body_length).map(|i| i as u8));
+        frame
+    }
+
+    #[test]
+    fn segments_round_trip() {
+        let frames = vec![
+            frame(1, 10),
+            frame(2, 0),
+            frame(3, 200_000),
+            frame(4, 100_000),
+            frame(5, 50_000),
+        ];
+        let segments = encode_segments(&frames);
+        let mut decoder = SegmentDecoder::new();
+        let mut decoded = Vec::new();
+        // feed the segments in small chunks to exercise the partial segments
+        for chunk in segments.chunks(7919) {
+            decoder.extend(chunk);
+            decoded.extend(decoder.decode().unwrap());
+        }
+        assert_eq!(decoded, frames);
+        // corrupted payload
+        let mut segments = encode_segments(&[frame(1, 10)]);
+        segments[HEADER_LENGTH + 3] ^= 1;
+        decoder.extend(&segments);
+        assert!(decoder.decode().is_err());
+    }
+
+    #[test]
+    fn checksums() {
+        // the CRC32 without the initial bytes is the standard one
+        assert_eq!(!crc32_update(!0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc24(0, 0), CRC24_INIT);
+    }
+}
@@ -21,4 +21,8 @@ impl Supported {
     pub fn get_options(&self) -> &HashMap<String, Vec<String>> {
         &self.options
     }
+    /// Take the options of the Supported frame.
+    pub fn into_options(self) -> HashMap<String, Vec<String>> {
+        self.options
+    }
 }
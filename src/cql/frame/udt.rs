@@ -0,0 +1,224 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the frozen user defined type values, which are the concatenation of their fields
+//! encoded as `[bytes]`, in the type declaration order.
+
+use super::decoder::ColumnDecoder;
+use anyhow::ensure;
+use std::convert::TryInto;
+
+/// A Rust struct which maps to a CQL user defined type, ie declared by the `udt!` macro.
+pub trait Udt: Sized {
+    /// The name of the type, ie to be used in `frozen<name>`
+    const NAME: &'static str;
+    /// The fields along with their CQL types, in the type declaration order
+    fn fields() -> &'static [(&'static str, &'static str)];
+    /// Encode the fields of the value as `[bytes]`, in the type declaration order
+    fn encode_fields(&self, buffer: &mut Vec<u8>);
+    /// Decode the fields of the value, in the type declaration order
+    fn decode_fields(fields: &mut UdtFields) -> anyhow::Result<Self>;
+    /// Get the `CREATE TYPE` statement of the type in the keyspace
+    fn create_statement(keyspace: &str) -> String {
+        let fields: Vec<String> = Self::fields()
+            .iter()
+            .map(|(name, cql_type)| format!("{} {}", name, cql_type))
+            .collect();
+        format!(
+            "CREATE TYPE IF NOT EXISTS {}.{} ({})",
+            keyspace,
+            Self::NAME,
+            fields.join(", ")
+        )
+    }
+}
+
+/// Encode the user defined type value as `[bytes]`
+pub fn encode_udt<T: Udt>(value: &T, buffer: &mut Vec<u8>) {
+    let length_start = buffer.len();
+    buffer.extend(&[0; 4]);
+    value.encode_fields(buffer);
+    let length = (buffer.len() - length_start - 4) as i32;
+    buffer[length_start..length_start + 4].copy_from_slice(&length.to_be_bytes());
+}
+
+/// Decode the user defined type value out of its column slice
+pub fn decode_udt<T: Udt>(slice: &[u8]) -> anyhow::Result<T> {
+    T::decode_fields(&mut UdtFields::new(slice))
+}
+
+/// The fields of a user defined type value, which are decoded in the type declaration order.
+pub struct UdtFields<'a> {
+    slice: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> UdtFields<'a> {
+    /// Create the fields of the value slice
+    pub fn new(slice: &'a [u8]) -> Self {
+        Self { slice, offset: 0 }
+    }
+    /// Decode the next field. The missing trailing fields, ie added by `ALTER TYPE` after the value was
+    /// written, are decoded as null.
+    pub fn next_field<T: ColumnDecoder>(&mut self) -> anyhow::Result<T> {
+        if self.slice.len() < self.offset + 4 {
+            return T::try_decode(&[]);
+        }
+        let length = i32::from_be_bytes(self.slice[self.offset..self.offset + 4].try_into()?);
+        self.offset += 4;
+        if length > 0 {
+            let end = self.offset + length as usize;
+            ensure!(self.slice.len() >= end, "Buffer is too small!");
+            let field = &self.slice[self.offset..end];
+            self.offset = end;
+            T::try_decode(field)
+        } else {
+            T::try_decode(&[])
+        }
+    }
+}
+
+/// The udt macro declares a struct which maps to a CQL user defined type, and implements `Udt`, `ColumnEncoder`,
+/// `ColumnDecoder` and `Row` (as a single column row) for it.
+///
+/// Each field is declared as `name: RustType => "cql type"`. The nullable fields are declared as `Option`s.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::{
+///     cql::{ColumnDecoder, ColumnEncoder, Udt},
+///     udt,
+/// };
+///
+/// udt! {
+///     /// A postal address
+///     #[derive(PartialEq)]
+///     pub struct Address("address") {
+///         street: String => "text",
+///         zip: Option<i32> => "int",
+///     }
+/// }
+///
+/// let address = Address {
+///     street: "Main street".to_string(),
+///     zip: None,
+/// };
+/// let encoded = address.encode_new();
+/// assert_eq!(Address::try_decode(&encoded[4..]).unwrap(), address);
+/// assert_eq!(
+///     Address::create_statement("shop"),
+///     "CREATE TYPE IF NOT EXISTS shop.address (street text, zip int)"
+/// );
+/// ```
+#[macro_export]
+macro_rules! udt {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $udt:ident($name:literal) {
+            $($field:ident: $ty:ty => $cql:literal),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        $vis struct $udt {
+            $(
+                #[allow(missing_docs)]
+                pub $field: $ty,
+            )+
+        }
+
+        impl $crate::cql::Udt for $udt {
+            const NAME: &'static str = $name;
+            fn fields() -> &'static [(&'static str, &'static str)] {
+                &[$((stringify!($field), $cql)),+]
+            }
+            fn encode_fields(&self, buffer: &mut Vec<u8>) {
+                $($crate::cql::ColumnEncoder::encode_element(&self.$field, buffer);)+
+            }
+            fn decode_fields(fields: &mut $crate::cql::UdtFields) -> anyhow::Result<Self> {
+                Ok(Self {
+                    $($field: fields.next_field::<$ty>()?,)+
+                })
+            }
+        }
+
+        impl $crate::cql::ColumnEncoder for $udt {
+            fn encode(&self, buffer: &mut Vec<u8>) {
+                $crate::cql::encode_udt(self, buffer)
+            }
+        }
+
+        impl $crate::cql::ColumnDecoder for $udt {
+            fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+                $crate::cql::decode_udt(slice)
+            }
+        }
+
+        impl $crate::cql::Row for $udt {
+            fn try_decode_row<R: $crate::cql::Rows + $crate::cql::ColumnValue>(rows: &mut R) -> anyhow::Result<Self> {
+                rows.column_value()
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::ColumnEncoder;
+    use std::collections::HashMap;
+
+    crate::udt! {
+        struct Point("point") {
+            x: i32 => "int",
+            y: i32 => "int",
+            label: Option<String> => "text",
+            tags: Vec<String> => "list<text>",
+        }
+    }
+
+    crate::udt! {
+        struct Shape("shape") {
+            points: Vec<Point> => "list<frozen<point>>",
+            properties: HashMap<String, Point> => "map<text, frozen<point>>",
+        }
+    }
+
+    #[test]
+    fn udts() {
+        let point = Point {
+            x: 1,
+            y: -2,
+            label: Some("a".to_string()),
+            tags: vec!["b".to_string()],
+        };
+        let encoded = point.encode_new();
+        assert_eq!(
+            i32::from_be_bytes(encoded[..4].try_into().unwrap()) as usize,
+            encoded.len() - 4
+        );
+        let decoded = Point::try_decode(&encoded[4..]).unwrap();
+        assert_eq!(
+            (decoded.x, decoded.y, decoded.label, decoded.tags),
+            (1, -2, Some("a".to_string()), vec!["b".to_string()])
+        );
+        // nested udts
+        let mut properties = HashMap::new();
+        properties.insert("center".to_string(), point.clone());
+        let shape = Shape {
+            points: vec![point.clone(), point],
+            properties,
+        };
+        let decoded = Shape::try_decode(&shape.encode_new()[4..]).unwrap();
+        assert_eq!(decoded.points.len(), 2);
+        assert_eq!(decoded.properties["center"].y, -2);
+        // the missing trailing fields are null, ie the value was written before a field was added
+        let mut fields = Vec::new();
+        1i32.encode(&mut fields);
+        2i32.encode(&mut fields);
+        let decoded = Point::try_decode(&fields).unwrap();
+        assert_eq!((decoded.x, decoded.y, decoded.label), (1, 2, None));
+        assert!(decoded.tags.is_empty());
+        assert!(Point::try_decode(&fields[..6]).is_err());
+    }
+}
@@ -0,0 +1,527 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module defines the native protocol versions, their negotiation out of the SUPPORTED options, and the
+//! translation of the frames between the v4 layout, which the frame builders and decoders use, and the layout of
+//! the version.
+
+use super::{
+    header::{COMPRESSION, CUSTOM_PAYLOAD, TRACING, WARNING},
+    opcode::{BATCH, ERROR, EXECUTE, PREPARE, QUERY, RESULT},
+    queryflags::WITH_KEYSPACE,
+    QueryFrame,
+};
+use crate::cql::compression::{Compression, MyCompression};
+use anyhow::{bail, ensure};
+use std::{borrow::Cow, collections::HashMap, convert::TryFrom};
+
+/// The RESULT kind of the rows
+const ROWS: i32 = 0x0002;
+/// The RESULT kind of the prepared statements
+const PREPARED: i32 = 0x0004;
+/// The rows metadata flag of the paging state
+const HAS_MORE_PAGES: i32 = 0x0002;
+/// The v5 PREPARE flag of the keyspace
+const PREPARE_WITH_KEYSPACE: i32 = 0x01;
+/// The v5 rows metadata flag of the new result metadata id
+const METADATA_CHANGED: i32 = 0x0008;
+const READ_FAILURE: i32 = 0x1300;
+const WRITE_FAILURE: i32 = 0x1500;
+
+/// The native protocol version of a connection.
+///
+/// The frames are built and decoded in the v4 layout, and translated from and to the layout of the version by
+/// `encode_request` and `decode_response`. The v5 frames are wrapped into checksummed segments once the
+/// connection is ready, see `encode_segments` and `SegmentDecoder`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum ProtocolVersion {
+    /// The native protocol v4
+    #[default]
+    V4 = 0x04,
+    /// The native protocol v5
+    V5 = 0x05,
+}
+
+impl TryFrom<u8> for ProtocolVersion {
+    type Error = anyhow::Error;
+    fn try_from(version: u8) -> anyhow::Result<Self> {
+        // the response frames have the direction bit set
+        match version & 0x7F {
+            0x04 => Ok(ProtocolVersion::V4),
+            0x05 => Ok(ProtocolVersion::V5),
+            version => anyhow::bail!("Unsupported protocol version: {}", version),
+        }
+    }
+}
+
+impl ProtocolVersion {
+    /// Get the version byte of the request frames
+    pub fn request_byte(self) -> u8 {
+        self as u8
+    }
+    /// Check if the frames are wrapped into segments once the connection is ready
+    pub fn is_segmented(self) -> bool {
+        self >= ProtocolVersion::V5
+    }
+    /// Negotiate the highest version, up to `max`, out of the `PROTOCOL_VERSIONS` SUPPORTED option, whose
+    /// entries look like `4/v4` or `5/v5-beta`. The beta versions are ignored, and V4 is returned if the option
+    /// is missing.
+    pub fn negotiate(options: &HashMap<String, Vec<String>>, max: ProtocolVersion) -> Self {
+        options
+            .get("PROTOCOL_VERSIONS")
+            .into_iter()
+            .flatten()
+            .filter(|version| !version.ends_with("-beta"))
+            .filter_map(|version| version.split('/').next()?.parse::<u8>().ok())
+            .filter_map(|version| ProtocolVersion::try_from(version).ok())
+            .filter(|version| *version <= max)
+            .max()
+            .unwrap_or_default()
+    }
+    /// Encode the request frame, which is built in the v4 layout, in the layout of the version, where the v5
+    /// QUERY, EXECUTE and BATCH flags are ints, EXECUTE carries the result metadata id and PREPARE its flags.
+    ///
+    /// The keyspace of the QUERY and PREPARE frames (see `QueryBuilder::with_keyspace`) is kept in their v5
+    /// keyspace field, while the v4 frames have no such field, so the table of their statement is qualified with
+    /// it instead, see `qualify_table`. The frame is borrowed as it is if it doesn't need any translation.
+    ///
+    /// The v5 frames can't be compressed, as their segments are compressed instead.
+    pub fn encode_request(self, frame: &[u8]) -> anyhow::Result<Cow<'_, [u8]>> {
+        ensure!(frame.len() >= 9, "The frame is too small!");
+        if self == ProtocolVersion::V4 {
+            return Ok(match qualify_table(frame)? {
+                Some(qualified) => Cow::Owned(qualified),
+                None => Cow::Borrowed(frame),
+            });
+        }
+        ensure!(frame[1] & COMPRESSION == 0, "The v5 frames can't be compressed!");
+        let mut start = 9;
+        if frame[1] & CUSTOM_PAYLOAD != 0 {
+            start += bytes_map_length(&frame[start..])?;
+        }
+        let mut encoded = frame[..start].to_vec();
+        encoded[0] = self.request_byte();
+        let body = &frame[start..];
+        match frame[4] {
+            QUERY => {
+                let flags = 4 + int(body, 0)? as usize + 2;
+                widen_flags(&mut encoded, body, flags)?;
+            }
+            EXECUTE => {
+                let id = 2 + short(body, 0)?;
+                encoded.extend_from_slice(&body[..id]);
+                // an empty result metadata id, so the node always sends the metadata of the rows
+                encoded.extend_from_slice(&[0, 0]);
+                widen_flags(&mut encoded, &body[id..], 2)?;
+            }
+            BATCH => {
+                let mut offset = 3;
+                for _ in 0..short(body, 1)? {
+                    ensure!(body.len() > offset, "The batch is too small!");
+                    offset += match body[offset] {
+                        0 => 5 + int(body, offset + 1)? as usize,
+                        _ => 3 + short(body, offset + 1)?,
+                    };
+                    let values = short(body, offset)?;
+                    offset += 2;
+                    for _ in 0..values {
+                        offset += 4 + int(body, offset)?.max(0) as usize;
+                    }
+                }
+                widen_flags(&mut encoded, body, offset + 2)?;
+            }
+            PREPARE => {
+                let statement = 4 + int(body, 0)?.max(0) as usize;
+                ensure!(body.len() >= statement, "The frame is too small!");
+                encoded.extend_from_slice(&body[..statement]);
+                // the keyspace follows the statement, along with its flag
+                let flags = match body.len() > statement {
+                    true => PREPARE_WITH_KEYSPACE,
+                    false => 0,
+                };
+                encoded.extend_from_slice(&flags.to_be_bytes());
+                encoded.extend_from_slice(&body[statement..]);
+            }
+            _ => encoded.extend_from_slice(body),
+        }
+        set_body_length(&mut encoded)?;
+        Ok(Cow::Owned(encoded))
+    }
+    /// Decode the response frame, which is in the layout of the version, in the v4 layout, where the v5 result
+    /// metadata ids are dropped and the failure reasons of the READ_FAILURE and WRITE_FAILURE errors are counted
+    pub fn decode_response(self, mut frame: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        ensure!(frame.len() >= 9, "The frame is too small!");
+        if self == ProtocolVersion::V4 {
+            return Ok(frame);
+        }
+        ensure!(
+            frame[0] & 0x7F == self.request_byte(),
+            "The node replied with the protocol version v{}",
+            frame[0] & 0x7F
+        );
+        frame[0] = 0x80 | ProtocolVersion::V4.request_byte();
+        let mut start = 9;
+        if frame[1] & TRACING != 0 {
+            start += 16;
+        }
+        if frame[1] & WARNING != 0 {
+            let warnings = short(&frame, start)?;
+            start += 2;
+            for _ in 0..warnings {
+                start += 2 + short(&frame, start)?;
+            }
+        }
+        if frame[1] & CUSTOM_PAYLOAD != 0 {
+            start += bytes_map_length(frame.get(start..).unwrap_or_default())?;
+        }
+        match frame[4] {
+            RESULT if int(&frame, start)? == PREPARED => {
+                let id = start + 4 + 2 + short(&frame, start + 4)?;
+                let result_metadata_id = 2 + short(&frame, id)?;
+                frame.drain(id..id + result_metadata_id);
+            }
+            RESULT if int(&frame, start)? == ROWS => {
+                let flags = int(&frame, start + 4)?;
+                if flags & METADATA_CHANGED != 0 {
+                    frame[start + 4..start + 8].copy_from_slice(&(flags & !METADATA_CHANGED).to_be_bytes());
+                    let mut offset = start + 12;
+                    if flags & HAS_MORE_PAGES != 0 {
+                        offset += 4 + int(&frame, offset)?.max(0) as usize;
+                    }
+                    let new_metadata_id = 2 + short(&frame, offset)?;
+                    frame.drain(offset..offset + new_metadata_id);
+                }
+            }
+            ERROR if matches!(int(&frame, start)?, READ_FAILURE | WRITE_FAILURE) => {
+                // the code, the message, the consistency, the received and the block for
+                let reasons = start + 4 + 2 + short(&frame, start + 4)? + 2 + 4 + 4;
+                let count = int(&frame, reasons)?;
+                let mut end = reasons + 4;
+                for _ in 0..count {
+                    ensure!(frame.len() > end, "The failure reasons are too small!");
+                    end += 1 + frame[end] as usize + 2;
+                }
+                ensure!(frame.len() >= end, "The failure reasons are too small!");
+                frame.splice(reasons..end, count.to_be_bytes().iter().copied());
+            }
+            _ => (),
+        }
+        set_body_length(&mut frame)?;
+        Ok(frame)
+    }
+}
+
+fn short(buffer: &[u8], offset: usize) -> anyhow::Result<usize> {
+    match buffer.get(offset..offset + 2) {
+        Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]]) as usize),
+        None => bail!("The frame is too small!"),
+    }
+}
+
+fn int(buffer: &[u8], offset: usize) -> anyhow::Result<i32> {
+    match buffer.get(offset..offset + 4) {
+        Some(bytes) => Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        None => bail!("The frame is too small!"),
+    }
+}
+
+/// Get the length of the `[bytes map]` at the start of the buffer
+fn bytes_map_length(buffer: &[u8]) -> anyhow::Result<usize> {
+    let mut length = 2;
+    for _ in 0..short(buffer, 0)? {
+        length += 2 + short(buffer, length)?;
+        length += 4 + int(buffer, length)?.max(0) as usize;
+    }
+    ensure!(buffer.len() >= length, "The bytes map is too small!");
+    Ok(length)
+}
+
+/// Append the body, where its `[byte]` flags at the offset are widened into `[int]` flags
+fn widen_flags(encoded: &mut Vec<u8>, body: &[u8], flags: usize) -> anyhow::Result<()> {
+    ensure!(body.len() > flags, "The frame is too small!");
+    encoded.extend_from_slice(&body[..flags]);
+    encoded.extend_from_slice(&(body[flags] as i32).to_be_bytes());
+    encoded.extend_from_slice(&body[flags + 1..]);
+    Ok(())
+}
+
+/// Qualify the table of the QUERY or PREPARE statement with the keyspace of the frame, which is then removed, as
+/// the v4 frames have no keyspace field. None is returned if the frame has no keyspace.
+fn qualify_table(frame: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+    let compressed = frame[1] & COMPRESSION == COMPRESSION;
+    match frame[4] {
+        QUERY => {
+            // the flags of the uncompressed frames are checked first, so only the frames with a keyspace are decoded
+            if !compressed {
+                let flags = 9 + 4 + int(frame, 9)?.max(0) as usize + 2;
+                let with_keyspace = frame.get(flags).is_some_and(|flags| flags & WITH_KEYSPACE != 0);
+                if !with_keyspace {
+                    return Ok(None);
+                }
+            }
+            let mut query = QueryFrame::decode(frame)?;
+            if query.keyspace().is_none() {
+                return Ok(None);
+            }
+            query.qualify_table();
+            query.encode().map(Some)
+        }
+        PREPARE => {
+            let frame = match compressed {
+                true => Cow::Owned(MyCompression::get().decompress(frame.to_vec())?),
+                false => Cow::Borrowed(frame),
+            };
+            let statement = 9 + 4 + int(&frame, 9)?.max(0) as usize;
+            if frame.len() <= statement {
+                return Ok(None);
+            }
+            let keyspace = std::str::from_utf8(string(&frame, statement)?)?;
+            let qualified = crate::cql::qualify_table(std::str::from_utf8(&frame[13..statement])?, keyspace);
+            let mut qualified_frame = frame[..9].to_vec();
+            qualified_frame[1] &= !COMPRESSION;
+            qualified_frame.extend_from_slice(&(qualified.len() as i32).to_be_bytes());
+            qualified_frame.extend_from_slice(qualified.as_bytes());
+            // the frame is compressed again like the QUERY frames, see `QueryFrame::encode`
+            MyCompression::compress_frame(qualified_frame, Some(compressed)).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn string(buffer: &[u8], offset: usize) -> anyhow::Result<&[u8]> {
+    let length = short(buffer, offset)?;
+    match buffer.get(offset + 2..offset + 2 + length) {
+        Some(string) => Ok(string),
+        None => bail!("The frame is too small!"),
+    }
+}
+
+fn set_body_length(frame: &mut [u8]) -> anyhow::Result<()> {
+    let length = i32::try_from(frame.len() - 9)?;
+    frame[5..9].copy_from_slice(&length.to_be_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::{Batch, Consistency, Prepare, Query, Statements, Values};
+
+    #[test]
+    fn negotiate_protocol_version() {
+        let mut options = HashMap::new();
+        assert_eq!(
+            ProtocolVersion::negotiate(&options, ProtocolVersion::V5),
+            ProtocolVersion::V4
+        );
+        options.insert(
+            "PROTOCOL_VERSIONS".to_string(),
+            vec!["3/v3".to_string(), "4/v4".to_string(), "5/v5-beta".to_string()],
+        );
+        assert_eq!(
+            ProtocolVersion::negotiate(&options, ProtocolVersion::V5),
+            ProtocolVersion::V4
+        );
+        options.insert(
+            "PROTOCOL_VERSIONS".to_string(),
+            vec!["4/v4".to_string(), "5/v5".to_string()],
+        );
+        assert_eq!(
+            ProtocolVersion::negotiate(&options, ProtocolVersion::V5),
+            ProtocolVersion::V5
+        );
+        assert_eq!(
+            ProtocolVersion::negotiate(&options, ProtocolVersion::V4),
+            ProtocolVersion::V4
+        );
+        assert_eq!(ProtocolVersion::try_from(0x85).unwrap(), ProtocolVersion::V5);
+        assert!(ProtocolVersion::try_from(0x03).is_err());
+    }
+
+    /// Get the expected v5 request, where the byte flags at the offset of the v4 frame are widened
+    fn widened(v4: &[u8], flags: usize) -> Vec<u8> {
+        let mut v5 = v4[..flags].to_vec();
+        v5.extend_from_slice(&[0, 0, 0, v4[flags]]);
+        v5.extend_from_slice(&v4[flags + 1..]);
+        v5[0] = 0x05;
+        set_body_length(&mut v5).unwrap();
+        v5
+    }
+
+    fn response(opcode: u8, body: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x85, 0, 0, 1, opcode];
+        frame.extend_from_slice(&(body.len() as i32).to_be_bytes());
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    #[test]
+    fn encode_requests() {
+        let v5 = ProtocolVersion::V5;
+        let statement = "SELECT * FROM ks.t WHERE k = ?";
+        let Query(query) = Query::new()
+            .statement(statement)
+            .consistency(Consistency::One)
+            .value(&1)
+            .page_size(100)
+            .build()
+            .unwrap();
+        assert_eq!(
+            v5.encode_request(&query).unwrap(),
+            widened(&query, 9 + 4 + statement.len() + 2)
+        );
+        let Query(execute) = Query::new()
+            .id(&[7; 16])
+            .consistency(Consistency::Quorum)
+            .value(&1)
+            .build()
+            .unwrap();
+        let mut expected = widened(&execute, 9 + 18 + 2);
+        expected.splice(9 + 18..9 + 18, vec![0, 0]);
+        set_body_length(&mut expected).unwrap();
+        assert_eq!(v5.encode_request(&execute).unwrap(), expected);
+        let insert = "INSERT INTO ks.t (k) VALUES (?)";
+        let Batch(batch) = Batch::new()
+            .logged()
+            .statement(insert)
+            .value(&1)
+            .id(&[7; 16])
+            .value(&2)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        let flags = 9 + 3 + (1 + 4 + insert.len() + 2 + 8) + (1 + 18 + 2 + 8) + 2;
+        assert_eq!(v5.encode_request(&batch).unwrap(), widened(&batch, flags));
+        let Prepare(prepare) = Prepare::new().statement(statement).build().unwrap();
+        let encoded = v5.encode_request(&prepare).unwrap();
+        assert_eq!(encoded[0], 0x05);
+        assert_eq!(encoded[9..encoded.len() - 4], prepare[9..]);
+        assert_eq!(encoded[encoded.len() - 4..], [0, 0, 0, 0]);
+        assert_eq!(int(&encoded, 5).unwrap() as usize, encoded.len() - 9);
+        // the v4 frames are kept as is, while the compressed frames can't be translated
+        assert_eq!(ProtocolVersion::V4.encode_request(&query).unwrap(), query);
+        let mut compressed = query.clone();
+        compressed[1] |= COMPRESSION;
+        assert!(v5.encode_request(&compressed).is_err());
+    }
+
+    #[test]
+    fn encode_keyspace() {
+        let (v4, v5) = (ProtocolVersion::V4, ProtocolVersion::V5);
+        let Query(query) = Query::new()
+            .statement("SELECT * FROM orders WHERE id = ?")
+            .consistency(Consistency::One)
+            .value(&1)
+            .timestamp(7)
+            .with_keyspace("shop")
+            .build()
+            .unwrap();
+        // v5 keeps the keyspace field, whose flag is widened along with the others
+        let encoded = v5.encode_request(&query).unwrap();
+        let flags = 9 + 4 + "SELECT * FROM orders WHERE id = ?".len() + 2;
+        assert_eq!(int(&encoded, flags).unwrap() as u8 & WITH_KEYSPACE, WITH_KEYSPACE);
+        assert_eq!(&encoded[encoded.len() - 6..], b"\0\x04shop");
+        // v4 qualifies the table of the statement instead
+        let encoded = v4.encode_request(&query).unwrap();
+        let frame = QueryFrame::decode(&encoded).unwrap();
+        assert_eq!(frame.statement(), Some("SELECT * FROM shop.orders WHERE id = ?"));
+        assert_eq!(frame.keyspace(), None);
+        assert_eq!(frame.timestamp(), Some(7));
+        assert_eq!(int(&encoded, 5).unwrap() as usize, encoded.len() - 9);
+        // the qualified tables are left as they are, and the frames without keyspace are borrowed
+        let Query(qualified) = Query::new()
+            .statement("SELECT * FROM bank.accounts")
+            .consistency(Consistency::One)
+            .with_keyspace("shop")
+            .build()
+            .unwrap();
+        let frame = QueryFrame::decode(&v4.encode_request(&qualified).unwrap()).unwrap();
+        assert_eq!(frame.statement(), Some("SELECT * FROM bank.accounts"));
+        let Query(plain) = Query::new()
+            .statement("SELECT * FROM orders")
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        assert!(matches!(v4.encode_request(&plain).unwrap(), Cow::Borrowed(_)));
+        // the PREPARE keyspace follows its flag on v5
+        let statement = "INSERT INTO orders (id) VALUES (?)";
+        let Prepare(prepare) = Prepare::new()
+            .with_keyspace("shop")
+            .statement(statement)
+            .build()
+            .unwrap();
+        let encoded = v5.encode_request(&prepare).unwrap();
+        let keyspace = 9 + 4 + statement.len();
+        assert_eq!(int(&encoded, keyspace).unwrap(), PREPARE_WITH_KEYSPACE);
+        assert_eq!(&encoded[keyspace + 4..], b"\0\x04shop");
+        assert_eq!(int(&encoded, 5).unwrap() as usize, encoded.len() - 9);
+        let encoded = v4.encode_request(&prepare).unwrap();
+        let qualified = "INSERT INTO shop.orders (id) VALUES (?)";
+        assert_eq!(int(&encoded, 9).unwrap() as usize, qualified.len());
+        assert_eq!(&encoded[13..], qualified.as_bytes());
+        assert_eq!(int(&encoded, 5).unwrap() as usize, encoded.len() - 9);
+        // the compressed frames are decompressed by the global compression before they're qualified, then
+        // compressed again, which leaves them uncompressed as the global compression of the tests is disabled
+        for frame in [query, prepare] {
+            let mut compressed = frame.clone();
+            compressed[1] |= COMPRESSION;
+            assert_eq!(
+                v4.encode_request(&compressed).unwrap(),
+                v4.encode_request(&frame).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn decode_responses() {
+        let v5 = ProtocolVersion::V5;
+        // the result metadata id of the prepared result is dropped
+        let mut body = PREPARED.to_be_bytes().to_vec();
+        body.extend_from_slice(&[0, 16]);
+        body.extend_from_slice(&[7; 16]);
+        let id = body.len();
+        body.extend_from_slice(&[0, 2, 9, 9]);
+        body.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0]);
+        let mut expected = body.clone();
+        expected.drain(id..id + 4);
+        let mut expected = response(RESULT, &expected);
+        expected[0] = 0x84;
+        assert_eq!(v5.decode_response(response(RESULT, &body)).unwrap(), expected);
+        // the new metadata id of the rows is dropped, along with its flag
+        let mut body = ROWS.to_be_bytes().to_vec();
+        body.extend_from_slice(&(HAS_MORE_PAGES | METADATA_CHANGED | 0x0004).to_be_bytes());
+        body.extend_from_slice(&1i32.to_be_bytes());
+        body.extend_from_slice(&[0, 0, 0, 3, 1, 2, 3]);
+        let mut expected = body.clone();
+        expected[4..8].copy_from_slice(&(HAS_MORE_PAGES | 0x0004).to_be_bytes());
+        body.extend_from_slice(&[0, 2, 9, 9]);
+        body.extend_from_slice(&0i32.to_be_bytes());
+        expected.extend_from_slice(&0i32.to_be_bytes());
+        let mut expected = response(RESULT, &expected);
+        expected[0] = 0x84;
+        assert_eq!(v5.decode_response(response(RESULT, &body)).unwrap(), expected);
+        // the failure reasons are counted
+        let mut body = READ_FAILURE.to_be_bytes().to_vec();
+        body.extend_from_slice(&[0, 4]);
+        body.extend_from_slice(b"fail");
+        body.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        let mut expected = body.clone();
+        body.extend_from_slice(&2i32.to_be_bytes());
+        body.extend_from_slice(&[4, 127, 0, 0, 1, 0, 1]);
+        body.push(16);
+        body.extend_from_slice(&[0; 16]);
+        body.extend_from_slice(&[0, 1]);
+        body.push(0);
+        expected.extend_from_slice(&2i32.to_be_bytes());
+        expected.push(0);
+        let mut expected = response(ERROR, &expected);
+        expected[0] = 0x84;
+        assert_eq!(v5.decode_response(response(ERROR, &body)).unwrap(), expected);
+        // the frames of another version are rejected
+        let mut v4 = response(RESULT, &1i32.to_be_bytes());
+        v4[0] = 0x84;
+        assert!(v5.decode_response(v4.clone()).is_err());
+        assert_eq!(ProtocolVersion::V4.decode_response(v4.clone()).unwrap(), v4);
+    }
+}
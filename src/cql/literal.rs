@@ -0,0 +1,118 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the parsing and rendering of the CQL string literals.
+
+use anyhow::{anyhow, bail};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// A CQL string literal, which holds the unescaped value.
+///
+/// It's rendered as a single quoted literal, where the single quotes are escaped by doubling them, so that
+/// any value can be safely inlined within a statement.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::LitStr;
+///
+/// assert_eq!(LitStr::new("it's").to_string(), "'it''s'");
+/// assert_eq!(LitStr::parse("'it''s'").unwrap().as_str(), "it's");
+/// assert_eq!(LitStr::parse("$$it's$$").unwrap().as_str(), "it's");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LitStr(String);
+
+impl LitStr {
+    /// Create the literal of the value
+    pub fn new<T: Into<String>>(value: T) -> Self {
+        Self(value.into())
+    }
+
+    /// Parse the literal as it's written in a statement, ie `'it''s'` or `$$it's$$`
+    pub fn parse(literal: &str) -> anyhow::Result<Self> {
+        let literal = literal.trim();
+        if let Some(dollar) = literal.strip_prefix("$$") {
+            let value = dollar
+                .strip_suffix("$$")
+                .ok_or_else(|| anyhow!("Unterminated $$ string literal: {}", literal))?;
+            if value.contains("$$") {
+                bail!("Unexpected chars after the $$ string literal: {}", literal);
+            }
+            return Ok(Self(value.to_string()));
+        }
+        let quoted = literal
+            .strip_prefix('\'')
+            .ok_or_else(|| anyhow!("Not a string literal: {}", literal))?;
+        let mut value = String::with_capacity(quoted.len());
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            if c == '\'' {
+                match chars.next() {
+                    Some('\'') => value.push('\''),
+                    None => return Ok(Self(value)),
+                    Some(_) => bail!("Unexpected chars after the string literal: {}", literal),
+                }
+            } else {
+                value.push(c);
+            }
+        }
+        bail!("Unterminated string literal: {}", literal)
+    }
+
+    /// Get the unescaped value
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Get the unescaped value
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl Display for LitStr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}'", self.0.replace('\'', "''"))
+    }
+}
+
+impl FromStr for LitStr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LitStr::parse(s)
+    }
+}
+
+impl From<&str> for LitStr {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for LitStr {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_literals() {
+        for value in ["", "it's", "''", "'; DROP TABLE ks.t; --", "ünïcödé $$"].iter() {
+            let literal = LitStr::new(*value);
+            assert_eq!(LitStr::parse(&literal.to_string()).unwrap(), literal);
+        }
+        assert_eq!(LitStr::new("'; --").to_string(), "'''; --'");
+        assert!(LitStr::parse("'unterminated").is_err());
+        assert!(LitStr::parse("'a'b'").is_err());
+        assert!(LitStr::parse("$$a$$b$$").is_err());
+        assert!(LitStr::parse("unquoted").is_err());
+    }
+}
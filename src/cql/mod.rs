@@ -7,15 +7,52 @@
 #![warn(missing_docs)]
 pub mod compression;
 mod connection;
+#[cfg(feature = "cursor")]
+mod cursor;
+mod dynamic;
 mod frame;
+mod literal;
 mod murmur3;
+mod name;
+pub(crate) mod query_cache;
+pub(crate) mod read_only;
+mod retry;
+mod script;
+mod session;
+mod statement;
+mod statement_cache;
+pub mod statements;
+mod term;
 mod tests;
+mod trace;
 
 pub use connection::*;
+#[cfg(feature = "cursor")]
+pub use cursor::{CursorError, CursorKey, PagingCursor};
+pub use dynamic::{
+    AsDynamicBatchRequest, AsDynamicDeleteRequest, AsDynamicInsertRequest, AsDynamicSelectRequest,
+    AsDynamicUpdateRequest, DynamicRequest, DynamicValues,
+};
 /// This is the public API of this module
 pub use frame::*;
 
-pub use murmur3::murmur3_cassandra_x64_128;
+pub use literal::LitStr;
+pub use murmur3::{
+    murmur3_cassandra_x64_128, murmur3_token, Murmur3Partitioner, PartitionKey, PartitionKeyEncoder, Partitioner,
+};
+pub use name::{is_reserved_keyword, Dialect, Name, RESERVED_KEYWORDS};
+pub use query_cache::{QueryCacheStats, SystemTable};
+pub use read_only::{is_mutation, ReadOnly, ReadOnlyViolation};
+pub use retry::{DefaultRetryPolicy, RetryDecision, RetryPolicy};
+pub use script::{parse_script, Directive, ScriptItem, DEFAULT_PAGE_SIZE};
+pub use session::{ScopedSession, Session, SessionBuilder};
+pub use statement::{
+    idempotent_ddl, is_conditional, parameterize, qualify_table, with_token_range, with_using_timeout, BindMarker,
+    BindMarkers, Fnv64, MarkerClause, Md5, StatementIdAlgorithm, StatementKind, TokenIndexes,
+};
+pub use statement_cache::{EvictionPolicy, StatementCacheStats, DEFAULT_STATEMENT_CACHE_CAPACITY};
+pub use term::{parse_options, Constant, Operator, Term};
+pub use trace::{Trace, TraceEvent};
 
 /// expose MyCompression
-pub use compression::MyCompression;
+pub use compression::{CompressionType, MyCompression};
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 //! This crates implements the murmur3, which is used to calculate the partition key in scyllaDB.
+use super::ColumnEncoder;
 use std::convert::TryInto;
 
 fn copy_into_array<A, T>(slice: &[T]) -> A
@@ -310,6 +311,132 @@ fn fmix64_i64(k: i64) -> i64 {
     tmp ^= tmp >> R;
     tmp as i64
 }
+
+/// Compute the murmur3 token of the serialized partition key, as the `Murmur3Partitioner` does.
+///
+/// # Example
+/// ```
+/// use scylla_rs::cql::{murmur3_token, TokenEncoder};
+/// assert_eq!(murmur3_token(b"alice"), "alice".get_token());
+/// ```
+pub fn murmur3_token(bytes: &[u8]) -> i64 {
+    murmur3_cassandra_x64_128(bytes, 0).0
+}
+
+/// Defines how the serialized partition keys are mapped to the ring tokens
+pub trait Partitioner {
+    /// Get the partitioner class name, as advertised by the `system.local` table
+    fn class_name(&self) -> &'static str;
+    /// Compute the token of the serialized partition key
+    fn token(&self, partition_key: &[u8]) -> i64;
+}
+
+/// The default partitioner of scylla, which hashes the partition keys with murmur3
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Murmur3Partitioner;
+
+impl Partitioner for Murmur3Partitioner {
+    fn class_name(&self) -> &'static str {
+        "org.apache.cassandra.dht.Murmur3Partitioner"
+    }
+    fn token(&self, partition_key: &[u8]) -> i64 {
+        murmur3_token(partition_key)
+    }
+}
+
+/// Serializes the partition key components exactly like Cassandra, in order to compute the token of the
+/// partition: a single component is hashed as is, while each component of a composite partition key is
+/// serialized as `<length: u16><value><0>`.
+///
+/// # Example
+/// ```
+/// use scylla_rs::cql::{PartitionKeyEncoder, TokenEncoder};
+/// let token = PartitionKeyEncoder::new().component(&"alice").component(&2021i32).token();
+/// assert_eq!(token, "alice".chain_token(&2021i32).finish());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PartitionKeyEncoder {
+    components: Vec<Vec<u8>>,
+}
+
+impl PartitionKeyEncoder {
+    /// Create a new empty partition key encoder
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Append the next partition key component
+    pub fn component<T: ColumnEncoder + ?Sized>(mut self, value: &T) -> Self {
+        self.push(value);
+        self
+    }
+    /// Push the next partition key component
+    pub fn push<T: ColumnEncoder + ?Sized>(&mut self, value: &T) {
+        let mut encoded = value.encode_new();
+        // strip the [int] length of the column value
+        encoded.drain(..4.min(encoded.len()));
+        self.components.push(encoded);
+    }
+    /// Serialize the partition key
+    pub fn encode(&self) -> Vec<u8> {
+        match self.components.as_slice() {
+            [component] => component.clone(),
+            components => {
+                let mut buffer = Vec::new();
+                for component in components {
+                    buffer.extend(&u16::to_be_bytes(component.len() as u16));
+                    buffer.extend(component);
+                    buffer.push(0);
+                }
+                buffer
+            }
+        }
+    }
+    /// Compute the token of the partition key with the `Murmur3Partitioner`
+    pub fn token(&self) -> i64 {
+        self.token_with(&Murmur3Partitioner)
+    }
+    /// Compute the token of the partition key with the partitioner
+    pub fn token_with<P: Partitioner + ?Sized>(&self, partitioner: &P) -> i64 {
+        partitioner.token(&self.encode())
+    }
+}
+
+/// Defines the components of a partition key, which are serialized by the `PartitionKeyEncoder`.
+/// It's implemented for the tuples of column values, ie `(id,)` or `(id, day)`.
+pub trait PartitionKey {
+    /// Push the partition key components to the encoder
+    fn encode_partition_key(&self, encoder: &mut PartitionKeyEncoder);
+    /// Compute the token of the partition key with the `Murmur3Partitioner`
+    fn partition_token(&self) -> i64 {
+        self.partition_token_with(&Murmur3Partitioner)
+    }
+    /// Compute the token of the partition key with the partitioner
+    fn partition_token_with<P: Partitioner + ?Sized>(&self, partitioner: &P) -> i64 {
+        let mut encoder = PartitionKeyEncoder::new();
+        self.encode_partition_key(&mut encoder);
+        encoder.token_with(partitioner)
+    }
+}
+
+macro_rules! impl_partition_key {
+    ($($t:ident: $i:tt),*) => {
+        impl<$($t: ColumnEncoder),*> PartitionKey for ($($t,)*) {
+            fn encode_partition_key(&self, encoder: &mut PartitionKeyEncoder) {
+                $(encoder.push(&self.$i);)*
+            }
+        }
+    };
+}
+
+impl_partition_key!(A: 0);
+impl_partition_key!(A: 0, B: 1);
+impl_partition_key!(A: 0, B: 1, C: 2);
+impl_partition_key!(A: 0, B: 1, C: 2, D: 3);
+impl_partition_key!(A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_partition_key!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+impl_partition_key!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+impl_partition_key!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -328,6 +455,18 @@ mod tests {
         assert_eq!(hash_pair.0, -5381343058315604526);
     }
 
+    #[test]
+    fn partition_key_encoder() {
+        use crate::cql::TokenEncoder;
+        assert_eq!(("alice",).partition_token(), "alice".get_token());
+        assert_eq!(
+            ("alice", 2021i32, 1u8).partition_token(),
+            "alice".chain_token(&2021i32).chain(&1u8).finish()
+        );
+        let encoded = PartitionKeyEncoder::new().component(&1i32).component(&"ab").encode();
+        assert_eq!(encoded, vec![0, 4, 0, 0, 0, 1, 0, 0, 2, b'a', b'b', 0]);
+    }
+
     #[test]
     fn test_1000() {
         let mut file = BufReader::new(Cursor::new(std::include_str!("murmur3_tests.txt")));
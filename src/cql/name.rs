@@ -0,0 +1,324 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the parsing and rendering of the CQL identifiers.
+
+use anyhow::{bail, ensure};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// The reserved keywords of Scylla, which can only be used as identifiers when they are quoted
+pub const RESERVED_KEYWORDS: [&str; 60] = [
+    "add",
+    "allow",
+    "alter",
+    "and",
+    "apply",
+    "asc",
+    "authorize",
+    "batch",
+    "begin",
+    "by",
+    "columnfamily",
+    "create",
+    "delete",
+    "desc",
+    "describe",
+    "drop",
+    "entries",
+    "execute",
+    "from",
+    "full",
+    "grant",
+    "if",
+    "in",
+    "index",
+    "infinity",
+    "insert",
+    "into",
+    "is",
+    "keyspace",
+    "limit",
+    "materialized",
+    "mbean",
+    "mbeans",
+    "modify",
+    "nan",
+    "norecursive",
+    "not",
+    "null",
+    "of",
+    "on",
+    "or",
+    "order",
+    "primary",
+    "rename",
+    "replace",
+    "revoke",
+    "schema",
+    "select",
+    "set",
+    "table",
+    "to",
+    "token",
+    "truncate",
+    "unlogged",
+    "update",
+    "use",
+    "using",
+    "view",
+    "where",
+    "with",
+];
+
+/// The reserved keywords of the other dialects which differ from the Scylla ones, where the keyword is either
+/// reserved (true) or unreserved (false) by the dialect
+const DIALECT_KEYWORDS: [(Dialect, &str, bool); 4] = [
+    // the JMX permissions were introduced by Cassandra 4.0
+    (Dialect::Cassandra3, "mbean", false),
+    (Dialect::Cassandra3, "mbeans", false),
+    (Dialect::Cassandra4, "default", true),
+    (Dialect::Cassandra4, "unset", true),
+];
+
+/// Check if the word is a reserved keyword of Scylla, regardless of its case
+pub fn is_reserved_keyword(word: &str) -> bool {
+    RESERVED_KEYWORDS
+        .iter()
+        .any(|keyword| keyword.eq_ignore_ascii_case(word))
+}
+
+/// The CQL dialect, whose reserved keywords differ between the Scylla and Cassandra releases, so an identifier
+/// which is valid in a dialect might need to be quoted in another one.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::{Dialect, Name};
+///
+/// assert!(Name::parse_in("default", Dialect::Scylla).is_ok());
+/// assert!(Name::parse_in("default", Dialect::Cassandra4).is_err());
+/// assert!(Name::parse_in("mbean", Dialect::Cassandra3).is_ok());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Dialect {
+    /// Cassandra 3.x
+    Cassandra3,
+    /// Cassandra 4.x
+    Cassandra4,
+    /// Scylla, whose reserved keywords are the `RESERVED_KEYWORDS`
+    #[default]
+    Scylla,
+}
+
+impl Dialect {
+    /// All the dialects
+    pub const ALL: [Dialect; 3] = [Dialect::Cassandra3, Dialect::Cassandra4, Dialect::Scylla];
+
+    /// Check if the word is a reserved keyword of the dialect, regardless of its case
+    pub fn is_reserved_keyword(self, word: &str) -> bool {
+        DIALECT_KEYWORDS
+            .iter()
+            .find(|(dialect, keyword, _)| *dialect == self && keyword.eq_ignore_ascii_case(word))
+            .map_or_else(|| is_reserved_keyword(word), |(_, _, reserved)| *reserved)
+    }
+
+    /// Get the reserved keywords of the dialect, in alphabetical order
+    pub fn reserved_keywords(self) -> Vec<&'static str> {
+        let mut keywords: Vec<&'static str> = RESERVED_KEYWORDS
+            .iter()
+            .chain(DIALECT_KEYWORDS.iter().map(|(_, keyword, _)| keyword))
+            .copied()
+            .filter(|keyword| self.is_reserved_keyword(keyword))
+            .collect();
+        keywords.sort_unstable();
+        keywords.dedup();
+        keywords
+    }
+}
+
+/// A CQL identifier, ie the name of a keyspace, a table, a type or a column.
+///
+/// The unquoted identifiers are case insensitive, therefore they are lowercased, while the quoted ones are case
+/// sensitive and can hold any char, where a double quote is escaped by doubling it.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::Name;
+///
+/// assert_eq!(Name::parse("\"my\"\"Table\"").unwrap().as_str(), "my\"Table");
+/// assert_eq!(Name::parse("MyTable").unwrap().as_str(), "mytable");
+/// assert_eq!(Name::auto("my_table").to_string(), "my_table");
+/// assert_eq!(Name::auto("myTable").to_string(), "\"myTable\"");
+/// assert_eq!(Name::auto("select").to_string(), "\"select\"");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Name {
+    /// An unquoted identifier, which is lowercased
+    Unquoted(String),
+    /// A quoted identifier, which is unescaped
+    Quoted(String),
+}
+
+impl Name {
+    /// Parse the identifier as it's written in a statement, ie `my_table` or `"my""Table"`, where the reserved
+    /// keywords are the Scylla ones
+    pub fn parse(name: &str) -> anyhow::Result<Self> {
+        Name::parse_in(name, Dialect::default())
+    }
+
+    /// Parse the identifier as it's written in a statement of the dialect, whose reserved keywords must be quoted
+    pub fn parse_in(name: &str, dialect: Dialect) -> anyhow::Result<Self> {
+        let name = name.trim();
+        if let Some(quoted) = name.strip_prefix('"') {
+            let mut unescaped = String::with_capacity(quoted.len());
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                if c == '"' {
+                    match chars.next() {
+                        Some('"') => unescaped.push('"'),
+                        None => {
+                            ensure!(!unescaped.is_empty(), "Empty quoted identifier");
+                            return Ok(Name::Quoted(unescaped));
+                        }
+                        Some(_) => bail!("Unexpected chars after the quoted identifier: {}", name),
+                    }
+                } else {
+                    unescaped.push(c);
+                }
+            }
+            bail!("Unterminated quoted identifier: {}", name)
+        } else {
+            ensure!(is_unquoted_identifier(name), "Invalid unquoted identifier: {}", name);
+            ensure!(
+                !dialect.is_reserved_keyword(name),
+                "Reserved keyword used as identifier: {}",
+                name
+            );
+            Ok(Name::Unquoted(name.to_ascii_lowercase()))
+        }
+    }
+
+    /// Create the identifier of the exact (case sensitive) name, which is only quoted when needed, ie if it
+    /// isn't lowercase, holds other chars than alphanumerics and underscores, or is a reserved keyword of any
+    /// dialect, so the rendered statements are valid in every dialect.
+    ///
+    /// Note: a name which is already a valid quoted identifier is parsed as it is.
+    pub fn auto(name: &str) -> Self {
+        if name.len() > 2 && name.starts_with('"') {
+            if let Ok(name) = Name::parse(name) {
+                return name;
+            }
+        }
+        if is_unquoted_identifier(name)
+            && !name.bytes().any(|b| b.is_ascii_uppercase())
+            && !Dialect::ALL.iter().any(|dialect| dialect.is_reserved_keyword(name))
+        {
+            Name::Unquoted(name.to_string())
+        } else {
+            Name::Quoted(name.to_string())
+        }
+    }
+
+    /// Render the `keyspace.table` qualified name, where both identifiers are only quoted when needed
+    pub fn qualify(keyspace: &str, table: &str) -> String {
+        format!("{}.{}", Name::auto(keyspace), Name::auto(table))
+    }
+
+    /// Get the name as it's stored in the schema, ie lowercased if it's unquoted
+    pub fn as_str(&self) -> &str {
+        match self {
+            Name::Unquoted(name) | Name::Quoted(name) => name,
+        }
+    }
+
+    /// Check if the identifier is quoted
+    pub fn is_quoted(&self) -> bool {
+        matches!(self, Name::Quoted(_))
+    }
+}
+
+/// Check if the name is a valid unquoted identifier, ie `[a-zA-Z][a-zA-Z0-9_]*`
+fn is_unquoted_identifier(name: &str) -> bool {
+    let mut bytes = name.bytes();
+    matches!(bytes.next(), Some(b) if b.is_ascii_alphabetic()) && bytes.all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Name::Unquoted(name) => f.write_str(name),
+            Name::Quoted(name) => write!(f, "\"{}\"", name.replace('"', "\"\"")),
+        }
+    }
+}
+
+impl FromStr for Name {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Name::parse(s)
+    }
+}
+
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Name::auto(name)
+    }
+}
+
+impl From<String> for Name {
+    fn from(name: String) -> Self {
+        Name::auto(&name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(Name::parse("\"a\"\"b\"").unwrap(), Name::Quoted("a\"b".to_string()));
+        assert_eq!(Name::parse("\"ünïcödé\"").unwrap().as_str(), "ünïcödé");
+        assert_eq!(
+            Name::parse("Mixed_Case1").unwrap(),
+            Name::Unquoted("mixed_case1".to_string())
+        );
+        assert!(Name::parse("\"unterminated").is_err());
+        assert!(Name::parse("\"a\"b\"").is_err());
+        assert!(Name::parse("\"\"").is_err());
+        assert!(Name::parse("1abc").is_err());
+        assert!(Name::parse("from").is_err());
+        assert!(Name::parse("ünïcödé").is_err());
+        // rendering round trips
+        for name in ["a\"b", "ünïcödé", "Table", "token", "with space", "_x"].iter() {
+            let quoted = Name::auto(name);
+            assert!(quoted.is_quoted());
+            assert_eq!(Name::parse(&quoted.to_string()).unwrap(), quoted);
+        }
+        assert_eq!(Name::auto("table_1"), Name::Unquoted("table_1".to_string()));
+        assert_eq!(Name::auto("\"Table\""), Name::Quoted("Table".to_string()));
+        assert_eq!(Name::qualify("ks", "My\"Table"), "ks.\"My\"\"Table\"");
+    }
+
+    #[test]
+    fn dialects() {
+        assert_eq!(Dialect::Scylla.reserved_keywords(), RESERVED_KEYWORDS.to_vec());
+        let cassandra3 = Dialect::Cassandra3.reserved_keywords();
+        assert_eq!(cassandra3.len(), RESERVED_KEYWORDS.len() - 2);
+        assert!(!cassandra3.contains(&"mbean"));
+        let cassandra4 = Dialect::Cassandra4.reserved_keywords();
+        assert_eq!(cassandra4.len(), RESERVED_KEYWORDS.len() + 2);
+        assert!(Dialect::Cassandra4.is_reserved_keyword("UNSET"));
+        assert_eq!(
+            Name::parse_in("MBean", Dialect::Cassandra3).unwrap(),
+            Name::Unquoted("mbean".to_string())
+        );
+        assert!(Name::parse_in("mbean", Dialect::Scylla).is_err());
+        // the names are quoted when they are reserved by any dialect
+        assert!(Name::auto("default").is_quoted());
+        assert!(Name::auto("\"default\"").is_quoted());
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the cache of the system queries results, which are repeated frequently by the driver
+//! subsystems (ie the schema lookups), along with their per table TTLs and their invalidation by the server events.
+
+use super::frame::Event;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// The system tables whose query results can be cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SystemTable {
+    /// The `system_schema` tables, which are invalidated by the schema change events
+    Schema,
+    /// The `system.local` and `system.peers` tables, which are invalidated by the topology and status change events
+    Topology,
+    /// The `system.size_estimates` table
+    SizeEstimates,
+}
+
+impl SystemTable {
+    /// All the system tables
+    pub const ALL: [SystemTable; 3] = [SystemTable::Schema, SystemTable::Topology, SystemTable::SizeEstimates];
+    /// Get the system table which is queried by the statement, if any
+    pub fn of(statement: &str) -> Option<Self> {
+        let statement = statement.to_lowercase();
+        let table = statement.split_whitespace().skip_while(|word| *word != "from").nth(1)?;
+        if table.starts_with("system_schema.") {
+            Some(SystemTable::Schema)
+        } else if table == "system.local" || table.starts_with("system.peers") {
+            Some(SystemTable::Topology)
+        } else if table == "system.size_estimates" {
+            Some(SystemTable::SizeEstimates)
+        } else {
+            None
+        }
+    }
+    /// Get the default TTL of the cached query results
+    pub fn default_ttl(&self) -> Duration {
+        match self {
+            SystemTable::Schema => Duration::from_secs(5),
+            SystemTable::Topology => Duration::from_secs(30),
+            SystemTable::SizeEstimates => Duration::from_secs(60),
+        }
+    }
+}
+
+/// The metrics of the system queries cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryCacheStats {
+    /// The number of cached results, including the expired ones which weren't pruned yet
+    pub len: usize,
+    /// The number of lookups which found a live result
+    pub hits: u64,
+    /// The number of lookups which didn't find a live result
+    pub misses: u64,
+    /// The number of results which were invalidated before their expiry
+    pub invalidations: u64,
+}
+
+#[cfg_attr(not(feature = "app"), allow(dead_code))]
+struct CachedResult {
+    table: SystemTable,
+    keyspace: Option<String>,
+    payload: Vec<u8>,
+    expires_at: Instant,
+}
+
+/// The cache of the system queries results, keyed by their statement and values, which is used by the app
+#[cfg_attr(not(feature = "app"), allow(dead_code))]
+pub(crate) struct QueryCache {
+    ttls: HashMap<SystemTable, Duration>,
+    entries: HashMap<(String, Vec<u8>), CachedResult>,
+    stats: QueryCacheStats,
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self {
+            ttls: SystemTable::ALL
+                .iter()
+                .map(|table| (*table, table.default_ttl()))
+                .collect(),
+            entries: HashMap::new(),
+            stats: QueryCacheStats::default(),
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "app"), allow(dead_code))]
+impl QueryCache {
+    /// Set the TTL of the cached results of the system table, where a zero TTL disables their caching
+    pub(crate) fn set_ttl(&mut self, table: SystemTable, ttl: Duration) {
+        self.ttls.insert(table, ttl);
+        if ttl.is_zero() {
+            self.invalidate(table, None);
+        }
+    }
+
+    /// Get the live result of the query
+    pub(crate) fn get(&mut self, statement: &str, values: &[u8], now: Instant) -> Option<Vec<u8>> {
+        let entries = &self.entries;
+        // avoid allocating the key of the statements which are never cached
+        let entry = SystemTable::of(statement)
+            .and_then(|_| entries.get(&(statement.to_string(), values.to_vec())))
+            .filter(|entry| entry.expires_at > now);
+        match entry {
+            Some(entry) => {
+                self.stats.hits += 1;
+                Some(entry.payload.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Cache the result of the query, which belongs to the keyspace if any, unless it doesn't query a system table
+    /// or its caching is disabled. Returns whether the result is cached.
+    pub(crate) fn insert(
+        &mut self,
+        statement: &str,
+        values: &[u8],
+        keyspace: Option<&str>,
+        payload: Vec<u8>,
+        now: Instant,
+    ) -> bool {
+        let table = match SystemTable::of(statement) {
+            Some(table) => table,
+            None => return false,
+        };
+        let ttl = self.ttls.get(&table).copied().unwrap_or_else(|| table.default_ttl());
+        if ttl.is_zero() {
+            return false;
+        }
+        // prune the expired results, so the cache doesn't outgrow the queries which are still in use
+        self.entries.retain(|_, entry| entry.expires_at > now);
+        self.entries.insert(
+            (statement.to_string(), values.to_vec()),
+            CachedResult {
+                table,
+                keyspace: keyspace.map(ToString::to_string),
+                payload,
+                expires_at: now + ttl,
+            },
+        );
+        true
+    }
+
+    /// Invalidate the cached results of the system table, which belong to the keyspace if any. Returns the number of
+    /// invalidated results.
+    pub(crate) fn invalidate(&mut self, table: SystemTable, keyspace: Option<&str>) -> usize {
+        let len = self.entries.len();
+        self.entries.retain(|_, entry| {
+            entry.table != table || matches!(keyspace, Some(keyspace) if entry.keyspace.as_deref() != Some(keyspace))
+        });
+        let invalidated = len - self.entries.len();
+        self.stats.invalidations += invalidated as u64;
+        invalidated
+    }
+
+    /// Invalidate the cached results which are outdated by the server event, ie the schema of the keyspace which
+    /// changed. Returns the number of invalidated results.
+    pub(crate) fn invalidate_on(&mut self, event: &Event) -> usize {
+        match event {
+            Event::SchemaChange { keyspace, .. } => self.invalidate(SystemTable::Schema, Some(keyspace)),
+            Event::TopologyChange { .. } | Event::StatusChange { .. } => self.invalidate(SystemTable::Topology, None),
+        }
+    }
+
+    /// Get the metrics of the cache
+    pub(crate) fn stats(&self) -> QueryCacheStats {
+        QueryCacheStats {
+            len: self.entries.len(),
+            ..self.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::{SchemaChangeTarget, SchemaChangeType, TopologyChange};
+
+    const TABLES: &str = "SELECT table_name FROM system_schema.tables WHERE keyspace_name = ?";
+
+    #[test]
+    fn system_tables() {
+        assert_eq!(SystemTable::of(TABLES), Some(SystemTable::Schema));
+        assert_eq!(
+            SystemTable::of("SELECT peer, tokens FROM system.peers_v2"),
+            Some(SystemTable::Topology)
+        );
+        assert_eq!(
+            SystemTable::of("select * from SYSTEM.SIZE_ESTIMATES"),
+            Some(SystemTable::SizeEstimates)
+        );
+        assert_eq!(SystemTable::of("SELECT * FROM shop.system_orders"), None);
+    }
+
+    #[test]
+    fn query_cache() {
+        let now = Instant::now();
+        let mut cache = QueryCache::default();
+        assert!(!cache.insert("SELECT * FROM shop.orders", &[], None, vec![1], now));
+        assert!(cache.insert(TABLES, b"shop", Some("shop"), vec![1], now));
+        assert!(cache.insert(TABLES, b"bank", Some("bank"), vec![2], now));
+        assert_eq!(cache.get(TABLES, b"shop", now), Some(vec![1]));
+        // the results expire after their TTL
+        assert_eq!(cache.get(TABLES, b"shop", now + Duration::from_secs(5)), None);
+        // the schema changes invalidate the results of their keyspace only
+        let event = Event::SchemaChange {
+            change: SchemaChangeType::Updated,
+            target: SchemaChangeTarget::Table,
+            keyspace: "shop".to_string(),
+            name: Some("orders".to_string()),
+            arguments: Vec::new(),
+        };
+        assert_eq!(cache.invalidate_on(&event), 1);
+        assert_eq!(cache.get(TABLES, b"shop", now), None);
+        assert_eq!(cache.get(TABLES, b"bank", now), Some(vec![2]));
+        let event = Event::TopologyChange {
+            change: TopologyChange::NewNode,
+            address: "127.0.0.1:9042".parse().unwrap(),
+        };
+        assert_eq!(cache.invalidate_on(&event), 0);
+        // a zero TTL disables the caching
+        cache.set_ttl(SystemTable::Schema, Duration::from_secs(0));
+        assert!(!cache.insert(TABLES, b"shop", Some("shop"), vec![1], now));
+        let stats = cache.stats();
+        assert_eq!((stats.len, stats.hits, stats.misses, stats.invalidations), (0, 2, 2, 2));
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the read-only mode, which rejects the mutation and DDL requests before they are sent.
+
+use super::{
+    compression::{Compression, CompressionType},
+    frame::opcode::{BATCH, PREPARE, QUERY},
+};
+use std::{
+    convert::TryInto,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use thiserror::Error;
+
+/// The statement keywords which mutate the data or the schema
+const MUTATION_KEYWORDS: [&str; 11] = [
+    "INSERT", "UPDATE", "DELETE", "BATCH", "BEGIN", "TRUNCATE", "CREATE", "ALTER", "DROP", "GRANT", "REVOKE",
+];
+
+/// The error reported when a request is rejected by the read-only mode
+#[derive(Error, Debug, Clone)]
+#[error("Read-only violation: {statement}")]
+pub struct ReadOnlyViolation {
+    /// The rejected statement
+    pub statement: String,
+}
+
+/// The read-only mode of a cluster or of a session, which rejects the mutation and DDL requests before they are
+/// sent. The clones share the mode, so it can be toggled at runtime, ie for a maintenance window.
+///
+/// Note: execute frames only hold the prepared statement id, therefore they can't be checked, however preparing
+/// a mutation is rejected.
+///
+/// ## Example
+/// ```
+/// use scylla_rs::cql::{CompressionType, Consistency, Query, ReadOnly, Statements};
+///
+/// let read_only = ReadOnly::new(true);
+/// let Query(payload) = Query::new()
+///     .statement("/* backfill */ INSERT INTO shop.orders (id) VALUES (1)")
+///     .consistency(Consistency::One)
+///     .build()
+///     .unwrap();
+/// assert!(read_only.check(&payload, CompressionType::Uncompressed).is_err());
+/// read_only.clone().set(false);
+/// assert!(read_only.check(&payload, CompressionType::Uncompressed).is_ok());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ReadOnly(Arc<AtomicBool>);
+
+impl ReadOnly {
+    /// Create a new read-only mode, which is either enabled or disabled
+    pub fn new(enabled: bool) -> Self {
+        Self(Arc::new(AtomicBool::new(enabled)))
+    }
+    /// Enable or disable the read-only mode
+    pub fn set(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Relaxed);
+    }
+    /// Check if the read-only mode is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+    /// Check the request frame, which is compressed by the provided compression, against the read-only mode
+    pub fn check(&self, payload: &[u8], compression: CompressionType) -> Result<(), ReadOnlyViolation> {
+        if !self.is_enabled() || payload.len() < 9 {
+            return Ok(());
+        }
+        match payload[4] {
+            BATCH => Err(ReadOnlyViolation {
+                statement: "BATCH".to_string(),
+            }),
+            QUERY | PREPARE => match statement(payload, compression) {
+                Some(statement) if !is_mutation(&statement) => Ok(()),
+                Some(statement) => Err(ReadOnlyViolation { statement }),
+                None => Err(ReadOnlyViolation {
+                    statement: "Unable to decode the statement".to_string(),
+                }),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Check if the statement is a mutation or a DDL statement, once its leading comments are skipped
+pub fn is_mutation(statement: &str) -> bool {
+    let keyword = skip_comments(statement)
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    MUTATION_KEYWORDS.contains(&keyword.as_str())
+}
+
+/// Skip the leading whitespaces and comments of the statement, ie the `/* */`, `--` and `//` comments
+fn skip_comments(mut statement: &str) -> &str {
+    loop {
+        statement = statement.trim_start();
+        if let Some(rest) = statement.strip_prefix("/*") {
+            // an unterminated comment spans the rest of the statement
+            statement = rest.find("*/").map_or("", |end| &rest[end + 2..]);
+        } else if let Some(rest) = statement.strip_prefix("--").or_else(|| statement.strip_prefix("//")) {
+            statement = rest.find('\n').map_or("", |end| &rest[end + 1..]);
+        } else {
+            return statement;
+        }
+    }
+}
+
+/// Decode the long string statement which follows the frame header, where the frame is compressed by the
+/// provided compression
+pub(crate) fn statement(payload: &[u8], compression: CompressionType) -> Option<String> {
+    let payload = compression.compression().decompress(payload.to_vec()).ok()?;
+    let length = i32::from_be_bytes(payload.get(9..13)?.try_into().ok()?) as usize;
+    String::from_utf8(payload.get(13..13 + length)?.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::{frame::header::COMPRESSION, Batch, Consistency, Prepare, Query, Statements, Values};
+
+    fn query(statement: &str) -> Vec<u8> {
+        let Query(payload) = Query::new()
+            .statement(statement)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        payload
+    }
+
+    fn compress(mut payload: Vec<u8>, compression: CompressionType) -> Vec<u8> {
+        payload[1] |= COMPRESSION;
+        compression.compression().compress(payload).unwrap()
+    }
+
+    #[test]
+    fn mutations() {
+        assert!(is_mutation("INSERT INTO ks.t (k) VALUES (1)"));
+        assert!(is_mutation("  update ks.t SET v = 1 WHERE k = 1"));
+        assert!(is_mutation("BEGIN BATCH INSERT INTO ks.t (k) VALUES (1) APPLY BATCH"));
+        assert!(is_mutation("DROP TABLE ks.t"));
+        assert!(!is_mutation("SELECT * FROM ks.t"));
+        assert!(!is_mutation("USE ks"));
+        assert!(!is_mutation(""));
+        // the leading comments don't hide the statement
+        assert!(is_mutation("/* backfill */ INSERT INTO ks.t (k) VALUES (1)"));
+        assert!(is_mutation("-- cleanup\nDELETE FROM ks.t WHERE k = 1"));
+        assert!(is_mutation("// cleanup\n  /* a\n b */ -- c\nTRUNCATE ks.t"));
+        assert!(!is_mutation("/* INSERT */ SELECT * FROM ks.t"));
+        assert!(!is_mutation("-- DROP TABLE ks.t"));
+        assert!(!is_mutation("/* unterminated INSERT"));
+    }
+
+    #[test]
+    fn check() {
+        let read_only = ReadOnly::new(true);
+        let uncompressed = CompressionType::Uncompressed;
+        assert!(read_only.check(&query("SELECT * FROM ks.t"), uncompressed).is_ok());
+        let violation = read_only
+            .check(&query("/* */ DELETE FROM ks.t WHERE k = 1"), uncompressed)
+            .unwrap_err();
+        assert_eq!(violation.statement, "/* */ DELETE FROM ks.t WHERE k = 1");
+        let Prepare(prepare) = Prepare::new()
+            .statement("INSERT INTO ks.t (k) VALUES (?)")
+            .build()
+            .unwrap();
+        assert!(read_only.check(&prepare, uncompressed).is_err());
+        let Prepare(prepare) = Prepare::new().statement("SELECT * FROM ks.t").build().unwrap();
+        assert!(read_only.check(&prepare, uncompressed).is_ok());
+        let Batch(batch) = Batch::new()
+            .logged()
+            .statement("INSERT INTO ks.t (k) VALUES (?)")
+            .value(&1)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        assert_eq!(read_only.check(&batch, uncompressed).unwrap_err().statement, "BATCH");
+        // the execute frames can't be checked
+        let Query(execute) = Query::new().id(&[7; 16]).consistency(Consistency::One).build().unwrap();
+        assert!(read_only.check(&execute, uncompressed).is_ok());
+        // the compressed frames are checked once decompressed
+        for compression in [CompressionType::Lz4, CompressionType::Snappy] {
+            let insert = compress(query("INSERT INTO ks.t (k) VALUES (1)"), compression);
+            assert_eq!(
+                read_only.check(&insert, compression).unwrap_err().statement,
+                "INSERT INTO ks.t (k) VALUES (1)"
+            );
+            let select = compress(query("SELECT * FROM ks.t"), compression);
+            assert!(read_only.check(&select, compression).is_ok());
+        }
+        // the frames which can't be decoded are rejected
+        let insert = compress(query("INSERT INTO ks.t (k) VALUES (1)"), CompressionType::Lz4);
+        assert!(read_only.check(&insert, CompressionType::Snappy).is_err());
+        // the clones share the mode
+        read_only.clone().set(false);
+        assert!(!read_only.is_enabled());
+        assert!(read_only.check(&batch, uncompressed).is_ok());
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the retry policies, which decide whether a failed request should be retried out of the
+//! typed details of its cql error.
+
+use super::frame::error::{CqlError, ErrorCodes, WriteType};
+
+/// The decision of a retry policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// Retry the request, at the same consistency level
+    Retry,
+    /// Return the error to the caller
+    Rethrow,
+}
+
+/// Decides whether a failed request should be retried. The caller bounds the number of retries.
+pub trait RetryPolicy: Send + Sync {
+    /// Decide whether the failed read request should be retried
+    fn on_read_error(&self, error: &CqlError) -> RetryDecision;
+    /// Decide whether the failed write request should be retried
+    fn on_write_error(&self, error: &CqlError) -> RetryDecision;
+}
+
+/// The retry policy recommended by the protocol spec:
+/// - a read timeout is retried only if enough replicas answered but the one which was asked for the data didn't,
+///   as the data is likely to be available on retry;
+/// - a write timeout is retried only if it timed out while writing the batch log, as the batch isn't applied yet;
+/// - the unavailable, overloaded, bootstrapping, server and truncate errors are retried, as the next attempt might
+///   be coordinated by another node, and so are the unprepared errors, once the statement is re-prepared;
+/// - the other errors are returned to the caller, as retrying them won't help.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultRetryPolicy;
+
+impl DefaultRetryPolicy {
+    fn on_error(error: &CqlError) -> RetryDecision {
+        match error.code {
+            ErrorCodes::UnavailableException
+            | ErrorCodes::Overloaded
+            | ErrorCodes::IsBoostrapping
+            | ErrorCodes::ServerError
+            | ErrorCodes::TruncateError
+            | ErrorCodes::Unprepared => RetryDecision::Retry,
+            _ => RetryDecision::Rethrow,
+        }
+    }
+}
+
+impl RetryPolicy for DefaultRetryPolicy {
+    fn on_read_error(&self, error: &CqlError) -> RetryDecision {
+        match error.read_timeout() {
+            Some(read_timeout) if read_timeout.received >= read_timeout.blockfor && read_timeout.data_present == 0 => {
+                RetryDecision::Retry
+            }
+            Some(_) => RetryDecision::Rethrow,
+            None => Self::on_error(error),
+        }
+    }
+
+    fn on_write_error(&self, error: &CqlError) -> RetryDecision {
+        match error.write_timeout() {
+            Some(write_timeout) if write_timeout.writetype == WriteType::BatchLog => RetryDecision::Retry,
+            Some(_) => RetryDecision::Rethrow,
+            None => Self::on_error(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::Consistency;
+    use std::convert::TryFrom;
+
+    fn error(code: i32, details: &[u8]) -> CqlError {
+        let mut body = code.to_be_bytes().to_vec();
+        body.extend(&2u16.to_be_bytes());
+        body.extend(b"Oh");
+        body.extend(details);
+        CqlError::try_from(&body[..]).unwrap()
+    }
+
+    fn timeout(received: i32, blockfor: i32, tail: &[u8]) -> Vec<u8> {
+        let mut details = (Consistency::Quorum as u16).to_be_bytes().to_vec();
+        details.extend(&received.to_be_bytes());
+        details.extend(&blockfor.to_be_bytes());
+        details.extend(tail);
+        details
+    }
+
+    #[test]
+    fn typed_details() {
+        let mut details = (Consistency::LocalQuorum as u16).to_be_bytes().to_vec();
+        details.extend(&3i32.to_be_bytes());
+        details.extend(&1i32.to_be_bytes());
+        let unavailable = error(0x1000, &details);
+        assert_eq!(unavailable.unavailable().unwrap().required, 3);
+        assert_eq!(unavailable.consistency(), Some(Consistency::LocalQuorum));
+        assert_eq!(unavailable.missing_replicas(), Some(2));
+        assert_eq!(DefaultRetryPolicy.on_read_error(&unavailable), RetryDecision::Retry);
+
+        let read_timeout = error(0x1200, &timeout(1, 2, &[1]));
+        assert!(!read_timeout.read_timeout().unwrap().replica_had_not_responded());
+        assert_eq!(read_timeout.missing_replicas(), Some(1));
+        assert_eq!(read_timeout.write_timeout().map(|_| ()), None);
+
+        let syntax = error(0x2000, &[]);
+        assert_eq!(syntax.missing_replicas(), None);
+        assert_eq!(DefaultRetryPolicy.on_read_error(&syntax), RetryDecision::Rethrow);
+    }
+
+    #[test]
+    fn default_policy() {
+        // enough replicas answered, but not the data one
+        let read_timeout = error(0x1200, &timeout(2, 2, &[0]));
+        assert_eq!(DefaultRetryPolicy.on_read_error(&read_timeout), RetryDecision::Retry);
+        // the data is present, so the digests mismatched or a replica is slow
+        let read_timeout = error(0x1200, &timeout(2, 2, &[1]));
+        assert_eq!(DefaultRetryPolicy.on_read_error(&read_timeout), RetryDecision::Rethrow);
+        // not enough replicas answered
+        let read_timeout = error(0x1200, &timeout(1, 2, &[0]));
+        assert_eq!(DefaultRetryPolicy.on_read_error(&read_timeout), RetryDecision::Rethrow);
+
+        let mut batch_log = vec![0, 9];
+        batch_log.extend(b"BATCH_LOG");
+        let write_timeout = error(0x1100, &timeout(0, 1, &batch_log));
+        assert_eq!(write_timeout.write_timeout().unwrap().writetype, WriteType::BatchLog);
+        assert_eq!(DefaultRetryPolicy.on_write_error(&write_timeout), RetryDecision::Retry);
+        let mut simple = vec![0, 6];
+        simple.extend(b"SIMPLE");
+        let write_timeout = error(0x1100, &timeout(0, 1, &simple));
+        assert_eq!(
+            DefaultRetryPolicy.on_write_error(&write_timeout),
+            RetryDecision::Rethrow
+        );
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module splits the cqlsh-style scripts into their statements and directives.
+
+use super::Consistency;
+use anyhow::{anyhow, bail};
+use std::fmt::{Display, Formatter};
+
+/// A cqlsh control directive, which isn't sent to the nodes but changes how the next statements are executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directive {
+    /// `CONSISTENCY <level>`, which sets the consistency of the next statements
+    Consistency(Consistency),
+    /// `SERIAL CONSISTENCY <level>`, which sets the serial consistency of the next conditional statements
+    SerialConsistency(Consistency),
+    /// `PAGING ON | OFF | <page size>`, where None disables the paging
+    Paging(Option<i32>),
+}
+
+/// The page size set by `PAGING ON`, as in cqlsh
+pub const DEFAULT_PAGE_SIZE: i32 = 100;
+
+impl Directive {
+    /// Parse the directive, or return None if the statement isn't a directive
+    pub fn parse(statement: &str) -> anyhow::Result<Option<Self>> {
+        let words: Vec<&str> = statement.trim().trim_end_matches(';').split_whitespace().collect();
+        let keyword = |i: usize| words.get(i).map(|word| word.to_ascii_uppercase());
+        Ok(Some(match (keyword(0).as_deref(), keyword(1).as_deref()) {
+            (Some("CONSISTENCY"), _) => {
+                ensure_args(&words, 2)?;
+                Directive::Consistency(words[1].parse()?)
+            }
+            (Some("SERIAL"), Some("CONSISTENCY")) => {
+                ensure_args(&words, 3)?;
+                let consistency: Consistency = words[2].parse()?;
+                if !consistency.is_serial() {
+                    bail!("Invalid serial consistency level: {}", consistency);
+                }
+                Directive::SerialConsistency(consistency)
+            }
+            (Some("PAGING"), _) => {
+                ensure_args(&words, 2)?;
+                Directive::Paging(match keyword(1).as_deref() {
+                    Some("ON") => Some(DEFAULT_PAGE_SIZE),
+                    Some("OFF") => None,
+                    _ => match words[1].parse::<i32>() {
+                        Ok(page_size) if page_size > 0 => Some(page_size),
+                        _ => bail!("Invalid page size: {}", words[1]),
+                    },
+                })
+            }
+            _ => return Ok(None),
+        }))
+    }
+}
+
+impl Display for Directive {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Directive::Consistency(consistency) => write!(f, "CONSISTENCY {}", consistency),
+            Directive::SerialConsistency(consistency) => write!(f, "SERIAL CONSISTENCY {}", consistency),
+            Directive::Paging(Some(page_size)) => write!(f, "PAGING {}", page_size),
+            Directive::Paging(None) => f.write_str("PAGING OFF"),
+        }
+    }
+}
+
+fn ensure_args(words: &[&str], count: usize) -> anyhow::Result<()> {
+    if words.len() != count {
+        bail!("Invalid directive: {}", words.join(" "));
+    }
+    Ok(())
+}
+
+/// An item of a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptItem {
+    /// A CQL statement, without its terminating semicolon
+    Statement(String),
+    /// A cqlsh control directive
+    Directive(Directive),
+}
+
+/// Split the script into its statements and directives.
+///
+/// The statements are terminated by the semicolons which aren't within string literals, quoted identifiers or
+/// comments, except for the statements of a `BEGIN BATCH .. APPLY BATCH` which remain in the batch statement.
+/// The comments are removed, while the directives can also be terminated by the end of their line, as in cqlsh.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::{parse_script, Consistency, Directive, ScriptItem};
+///
+/// let script = "CONSISTENCY QUORUM\nPAGING 100;\n-- a comment\nSELECT * FROM ks.t WHERE k = 'a;b';";
+/// assert_eq!(
+///     parse_script(script).unwrap(),
+///     vec![
+///         ScriptItem::Directive(Directive::Consistency(Consistency::Quorum)),
+///         ScriptItem::Directive(Directive::Paging(Some(100))),
+///         ScriptItem::Statement("SELECT * FROM ks.t WHERE k = 'a;b'".to_string()),
+///     ]
+/// );
+/// ```
+pub fn parse_script(script: &str) -> anyhow::Result<Vec<ScriptItem>> {
+    let mut items = Vec::new();
+    let mut statement = String::new();
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                statement.push(c);
+                loop {
+                    let next = chars
+                        .next()
+                        .ok_or_else(|| anyhow!("Unterminated quote in: {}", statement))?;
+                    statement.push(next);
+                    if next == c {
+                        break;
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                chars.next();
+                statement.push_str("$$");
+                loop {
+                    let next = chars
+                        .next()
+                        .ok_or_else(|| anyhow!("Unterminated $$ string in: {}", statement))?;
+                    statement.push(next);
+                    if next == '$' && chars.peek() == Some(&'$') {
+                        chars.next();
+                        statement.push('$');
+                        break;
+                    }
+                }
+            }
+            '-' | '/' if chars.peek() == Some(&c) => {
+                // skip the line comment, keeping the line break which might terminate a directive
+                while matches!(chars.peek(), Some(next) if *next != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                loop {
+                    let next = chars.next().ok_or_else(|| anyhow!("Unterminated comment in script"))?;
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+                statement.push(' ');
+            }
+            ';' | '\n' => {
+                if let Some(directive) = Directive::parse(&statement)? {
+                    items.push(ScriptItem::Directive(directive));
+                    statement.clear();
+                } else if c == '\n' {
+                    statement.push(c);
+                } else if !is_open_batch(&statement) {
+                    let trimmed = statement.trim();
+                    if !trimmed.is_empty() {
+                        items.push(ScriptItem::Statement(trimmed.to_string()));
+                    }
+                    statement.clear();
+                } else {
+                    statement.push(c);
+                }
+            }
+            _ => statement.push(c),
+        }
+    }
+    if let Some(directive) = Directive::parse(&statement)? {
+        items.push(ScriptItem::Directive(directive));
+    } else {
+        let trimmed = statement.trim();
+        if !trimmed.is_empty() {
+            if is_open_batch(trimmed) {
+                bail!("Unterminated batch in script: {}", trimmed);
+            }
+            items.push(ScriptItem::Statement(trimmed.to_string()));
+        }
+    }
+    Ok(items)
+}
+
+/// Check if the statement is a batch which isn't yet terminated by `APPLY BATCH`
+fn is_open_batch(statement: &str) -> bool {
+    let words: Vec<String> = statement
+        .split_whitespace()
+        .map(|word| word.to_ascii_uppercase())
+        .collect();
+    words.first().map(String::as_str) == Some("BEGIN") && !words.ends_with(&["APPLY".to_string(), "BATCH".to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script() {
+        let script = r#"
+            consistency local_quorum;
+            SERIAL CONSISTENCY LOCAL_SERIAL
+            /* create the table; */ CREATE TABLE ks.t (k text PRIMARY KEY, v text);
+            INSERT INTO ks.t (k, v) VALUES ('it''s; ok', $$a;b$$); // trailing comment;
+            BEGIN BATCH
+                INSERT INTO ks.t (k, v) VALUES ('a', 'b');
+                UPDATE ks."T;" SET v = 'c' WHERE k = 'a';
+            APPLY BATCH;
+            PAGING OFF
+            SELECT * FROM ks.t
+        "#;
+        assert_eq!(
+            parse_script(script).unwrap(),
+            vec![
+                ScriptItem::Directive(Directive::Consistency(Consistency::LocalQuorum)),
+                ScriptItem::Directive(Directive::SerialConsistency(Consistency::LocalSerial)),
+                ScriptItem::Statement("CREATE TABLE ks.t (k text PRIMARY KEY, v text)".to_string()),
+                ScriptItem::Statement("INSERT INTO ks.t (k, v) VALUES ('it''s; ok', $$a;b$$)".to_string()),
+                ScriptItem::Statement(
+                    "BEGIN BATCH\n                INSERT INTO ks.t (k, v) VALUES ('a', 'b');\n                \
+                     UPDATE ks.\"T;\" SET v = 'c' WHERE k = 'a';\n            APPLY BATCH"
+                        .to_string()
+                ),
+                ScriptItem::Directive(Directive::Paging(None)),
+                ScriptItem::Statement("SELECT * FROM ks.t".to_string()),
+            ]
+        );
+        assert!(parse_script("CONSISTENCY SOME;").is_err());
+        assert!(parse_script("SERIAL CONSISTENCY QUORUM;").is_err());
+        assert!(parse_script("PAGING 0;").is_err());
+        assert!(parse_script("SELECT 'unterminated").is_err());
+        assert!(parse_script("BEGIN BATCH INSERT INTO ks.t (k) VALUES ('a');").is_err());
+    }
+
+    #[test]
+    fn consistency_names() {
+        assert_eq!("local_one".parse::<Consistency>().unwrap(), Consistency::LocalOne);
+        assert_eq!(Consistency::EachQuorum.to_string(), "EACH_QUORUM");
+        assert!("quorums".parse::<Consistency>().is_err());
+    }
+}
@@ -0,0 +1,1207 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements a lightweight session, which issues the requests from a plain tokio app
+//! without spinning up the actor-based runtime.
+
+use super::{
+    compression::CompressionType,
+    frame::{
+        auth_response::{AllowAllAuth, Authenticator},
+        batchflags::WITH_NAMES,
+        consistency::Consistency,
+        decoder::{Decoder, Frame},
+        header, opcode,
+        prepare::Prepare,
+        query::Query,
+        query_frame::options_length,
+        ProtocolVersion, Statements,
+    },
+    happy_eyeballs,
+    name::Name,
+    read_only::ReadOnly,
+    resolve,
+    statement::{table_keyspace, StatementKind},
+    statement_cache::{EvictionPolicy, StatementCache, StatementCacheStats, DEFAULT_STATEMENT_CACHE_CAPACITY},
+    trace::Trace,
+    Cql, CqlBuilder, CqlReadHalf, CqlStream, CqlWriteHalf, Framing, TlsConfig, DEFAULT_STAGGER,
+};
+use anyhow::{anyhow, bail, ensure};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::{TryFrom, TryInto},
+    future::Future,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{mpsc, oneshot, Notify},
+    task::JoinHandle,
+};
+
+/// The default maximum number of in-flight requests per connection
+const DEFAULT_MAX_STREAMS: u16 = 1024;
+/// The default timeout of the connections to the nodes after the first one
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// The delay before a closed connection is reestablished, which doubles after every failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// The maximum delay between the attempts to reestablish a closed connection
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// SessionBuilder struct to establish the session connections with the provided configurations
+pub struct SessionBuilder<Auth: Authenticator> {
+    nodes: Vec<SocketAddr>,
+    hosts: Vec<String>,
+    authenticator: Auth,
+    tls: Option<TlsConfig>,
+    max_streams: u16,
+    stagger: Duration,
+    connect_timeout: Duration,
+    statement_cache_capacity: usize,
+    eviction_policy: EvictionPolicy,
+    read_only: ReadOnly,
+    protocol_version: ProtocolVersion,
+    /// The keyspace set on every connection, ie by the scoped sessions
+    keyspace: Option<Name>,
+}
+
+impl SessionBuilder<AllowAllAuth> {
+    /// Create a new session builder without authentication
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            hosts: Vec::new(),
+            authenticator: AllowAllAuth,
+            tls: None,
+            max_streams: DEFAULT_MAX_STREAMS,
+            stagger: DEFAULT_STAGGER,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            statement_cache_capacity: DEFAULT_STATEMENT_CACHE_CAPACITY,
+            eviction_policy: EvictionPolicy::default(),
+            read_only: ReadOnly::default(),
+            protocol_version: ProtocolVersion::default(),
+            keyspace: None,
+        }
+    }
+}
+
+impl Default for SessionBuilder<AllowAllAuth> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Auth: Authenticator> SessionBuilder<Auth> {
+    /// Add a scylla node address
+    pub fn node(mut self, address: SocketAddr) -> Self {
+        self.nodes.push(address);
+        self
+    }
+    /// Add the scylla node addresses
+    pub fn nodes<I: IntoIterator<Item = SocketAddr>>(mut self, addresses: I) -> Self {
+        self.nodes.extend(addresses);
+        self
+    }
+    /// Add a scylla node host, ie `"scylla.local:9042"`, which is resolved once the session is built
+    pub fn host<H: Into<String>>(mut self, host: H) -> Self {
+        self.hosts.push(host.into());
+        self
+    }
+    /// Use the provided authenticator for establishing the connections
+    pub fn authenticator<A: Authenticator>(self, authenticator: A) -> SessionBuilder<A> {
+        SessionBuilder {
+            nodes: self.nodes,
+            hosts: self.hosts,
+            authenticator,
+            tls: self.tls,
+            max_streams: self.max_streams,
+            stagger: self.stagger,
+            connect_timeout: self.connect_timeout,
+            statement_cache_capacity: self.statement_cache_capacity,
+            eviction_policy: self.eviction_policy,
+            read_only: self.read_only,
+            protocol_version: self.protocol_version,
+            keyspace: self.keyspace,
+        }
+    }
+    /// Encrypt the connections with the TLS configuration (requires the `tls` feature)
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls.replace(tls);
+        self
+    }
+    /// Set the maximum number of in-flight requests per connection
+    pub fn max_streams(mut self, max_streams: u16) -> Self {
+        self.max_streams = max_streams.max(1).min(i16::MAX as u16);
+        self
+    }
+    /// Set the delay between the staggered attempts to connect to the first node (see `happy_eyeballs`)
+    pub fn stagger(mut self, stagger: Duration) -> Self {
+        self.stagger = stagger;
+        self
+    }
+    /// Set the timeout of the connections to the nodes after the first one
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+    /// Set the maximum number of unpinned prepared statements which the session caches to re-prepare them
+    pub fn statement_cache_capacity(mut self, capacity: usize) -> Self {
+        self.statement_cache_capacity = capacity;
+        self
+    }
+    /// Set the eviction policy of the prepared statement cache
+    pub fn eviction_policy(mut self, eviction_policy: EvictionPolicy) -> Self {
+        self.eviction_policy = eviction_policy;
+        self
+    }
+    /// Enable or disable the read-only mode, which rejects the mutation and DDL requests before they are sent,
+    /// see `Session::read_only` to toggle it at runtime
+    pub fn read_only(self, enabled: bool) -> Self {
+        self.read_only.set(enabled);
+        self
+    }
+    /// Set the highest native protocol version of the connections, which speak the highest one supported by their
+    /// node up to it (see `CqlBuilder::protocol_version`), ie v5 to send the keyspace of the statements in the
+    /// keyspace field of their frames (see `QueryBuilder::with_keyspace`). Defaults to v4.
+    pub fn protocol_version(mut self, version: ProtocolVersion) -> Self {
+        self.protocol_version = version;
+        self
+    }
+    /// Connect to every shard of every node, and build the token ring out of their tokens.
+    ///
+    /// The first node is the first reachable one, which is connected through parallel staggered attempts, so a
+    /// down node doesn't block the startup. Then the other nodes are connected concurrently, and the ones which
+    /// are unreachable within the connect timeout are left out of the session. The shards of a node which fail to
+    /// connect don't fail the node, instead their connections are established in the background, like the closed
+    /// connections which are reestablished.
+    ///
+    /// The hosts are resolved beforehand, which fails with a `ConnectError::Dns` if any of them can't be resolved.
+    pub async fn build(mut self) -> anyhow::Result<Session>
+    where
+        Auth: Send + 'static,
+    {
+        for host in self.hosts.iter() {
+            for address in resolve(host).await? {
+                if !self.nodes.contains(&address) {
+                    self.nodes.push(address);
+                }
+            }
+        }
+        ensure!(!self.nodes.is_empty(), "No scylla node address provided!");
+        let settings = Settings {
+            tls: self.tls.clone(),
+            max_streams: self.max_streams,
+            stagger: self.stagger,
+            connect_timeout: self.connect_timeout,
+            statement_cache_capacity: self.statement_cache_capacity,
+            eviction_policy: self.eviction_policy,
+            read_only: self.read_only.clone(),
+            protocol_version: self.protocol_version,
+        };
+        let keyspace = match self.keyspace.as_ref() {
+            Some(keyspace) => {
+                let Query(payload) = Query::new()
+                    .statement(&format!("USE {}", keyspace))
+                    .consistency(Consistency::One)
+                    .build()?;
+                Some(payload)
+            }
+            None => None,
+        };
+        let connector = |address| Connector {
+            address,
+            authenticator: self.authenticator.clone(),
+            tls: self.tls.clone(),
+            max_streams: self.max_streams,
+            protocol_version: self.protocol_version,
+            keyspace: keyspace.clone(),
+        };
+        let first = happy_eyeballs(&self.nodes, self.stagger, |address| connector(address).connect_node()).await?;
+        let others: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|address| **address != first.0.address)
+            .map(|&address| {
+                let node = connector(address).connect_node();
+                (address, tokio::spawn(tokio::time::timeout(self.connect_timeout, node)))
+            })
+            .collect();
+        let mut nodes = Vec::new();
+        let mut ring = Vec::new();
+        let mut add_node = |(node, tokens): (Node, Vec<i64>)| {
+            for token in tokens {
+                ring.push((token, nodes.len()));
+            }
+            nodes.push(node);
+        };
+        add_node(first);
+        for (address, handle) in others {
+            match handle.await? {
+                Ok(Ok(node)) => add_node(node),
+                Ok(Err(e)) => log::warn!("Unable to connect to the node {}: {}", address, e),
+                Err(_) => log::warn!("Unable to connect to the node {}: timed out", address),
+            }
+        }
+        ring.sort_unstable();
+        Ok(Session {
+            nodes,
+            ring,
+            next: AtomicUsize::new(0),
+            statements: Mutex::new(StatementCache::new(self.statement_cache_capacity, self.eviction_policy)),
+            settings,
+        })
+    }
+}
+
+/// The connection settings of a session, which its scoped sessions inherit, where they share its read-only mode
+#[derive(Clone)]
+struct Settings {
+    tls: Option<TlsConfig>,
+    max_streams: u16,
+    stagger: Duration,
+    connect_timeout: Duration,
+    statement_cache_capacity: usize,
+    eviction_policy: EvictionPolicy,
+    read_only: ReadOnly,
+    protocol_version: ProtocolVersion,
+}
+
+/// The settings of the connections to a node, which establish the connections of its shards
+#[derive(Clone)]
+struct Connector<Auth> {
+    address: SocketAddr,
+    authenticator: Auth,
+    tls: Option<TlsConfig>,
+    max_streams: u16,
+    protocol_version: ProtocolVersion,
+    /// The USE request of the keyspace of the connections, if any
+    keyspace: Option<Vec<u8>>,
+}
+
+impl<Auth: Authenticator + Send + 'static> Connector<Auth> {
+    /// Connect to every shard of the node, and fetch its tokens. Only the first connection must succeed, as it
+    /// reveals the shard count and the node tokens, while the other shards are connected in the background if
+    /// they fail to connect.
+    async fn connect_node(self) -> anyhow::Result<(Node, Vec<i64>)> {
+        let mut cql = self.builder().tokens().build().await?;
+        let shard_count = cql.shard_count().max(1);
+        let msb = cql.msb();
+        let tokens = cql.take_tokens().unwrap_or_default();
+        let first_shard = cql.shard_id();
+        let first = link(cql, self.max_streams, self.keyspace.clone()).await?;
+        let mut connections = Vec::with_capacity(shard_count as usize);
+        for shard_id in 0..shard_count {
+            let link = match shard_id == first_shard {
+                true => Some(first.clone()),
+                false => match self.clone().connect(shard_id).await {
+                    Ok(link) => Some(link),
+                    Err(e) => {
+                        log::warn!("Unable to connect to the shard {} of {}: {}", shard_id, self.address, e);
+                        None
+                    }
+                },
+            };
+            let connector = self.clone();
+            let connect = move || connector.clone().connect(shard_id);
+            connections.push(Arc::new(Connection::new(link, connect, RECONNECT_DELAY)));
+        }
+        let node = Node {
+            address: self.address,
+            msb,
+            connections,
+        };
+        Ok((node, tokens))
+    }
+    /// Connect to the shard
+    async fn connect(self, shard_id: u16) -> anyhow::Result<Arc<Link>> {
+        let cql = self.builder().shard_id(shard_id).build().await?;
+        link(cql, self.max_streams, self.keyspace).await
+    }
+    fn builder(&self) -> CqlBuilder<Auth> {
+        CqlBuilder::<Auth>::new()
+            .address(self.address)
+            .authenticator(self.authenticator.clone())
+            .tls(self.tls.clone())
+            .protocol_version(self.protocol_version)
+    }
+}
+
+/// Multiplex the requests on the established connection, once its keyspace is set by the USE request (if any)
+async fn link(cql: Cql, max_streams: u16, keyspace: Option<Vec<u8>>) -> anyhow::Result<Arc<Link>> {
+    let (stream, framing) = cql.into_framed();
+    let link = Arc::new(Link::new(stream, framing, max_streams));
+    if let Some(payload) = keyspace {
+        let decoder = Decoder::try_from(link.request(&payload).await?)?;
+        if decoder.is_error()? {
+            bail!("Unable to use the keyspace: {}", decoder.get_error()?);
+        }
+    }
+    Ok(link)
+}
+
+/// Lightweight session, which keeps a connection to every shard of every node, routes the requests to the
+/// shard owning their token, and transparently re-prepares the cached prepared statements.
+///
+/// Note: the closed connections are reestablished in the background, meanwhile their requests are routed to the
+/// remaining ones instead.
+///
+/// ## Examples
+/// ```no_run
+/// use scylla_rs::cql::{Consistency, Query, Session, Statements, TokenEncoder, Values};
+/// # async fn run() -> anyhow::Result<()> {
+/// let session = Session::connect(vec!["127.0.0.1:9042".parse()?]).await?;
+/// let statement = "SELECT value FROM my_keyspace.table WHERE key = ?";
+/// let id = session.prepare(statement).await?;
+/// let Query(payload) = Query::new()
+///     .id(&id)
+///     .consistency(Consistency::One)
+///     .value(&"key")
+///     .build()?;
+/// let decoder = session.send(Some("key".get_token()), payload).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Session {
+    nodes: Vec<Node>,
+    ring: Vec<(i64, usize)>,
+    next: AtomicUsize,
+    statements: Mutex<StatementCache>,
+    settings: Settings,
+}
+
+impl Session {
+    /// Create a new session builder
+    pub fn builder() -> SessionBuilder<AllowAllAuth> {
+        SessionBuilder::new()
+    }
+    /// Connect to the provided nodes without authentication
+    pub async fn connect<I: IntoIterator<Item = SocketAddr>>(nodes: I) -> anyhow::Result<Self> {
+        SessionBuilder::new().nodes(nodes).build().await
+    }
+    /// Get the addresses of the connected nodes
+    pub fn nodes(&self) -> Vec<SocketAddr> {
+        self.nodes.iter().map(|node| node.address).collect()
+    }
+    /// Get the read-only mode of the session, which is shared by its scoped sessions, and can be toggled at runtime
+    pub fn read_only(&self) -> &ReadOnly {
+        &self.settings.read_only
+    }
+    /// Send the request frame and await its response. The request is routed to the shard which owns the
+    /// token (if any), otherwise to the connections in round robin order.
+    ///
+    /// The requests are rejected by a `ReadOnlyViolation` error if they're refused by the read-only mode.
+    pub async fn send(&self, token: Option<i64>, payload: Vec<u8>) -> anyhow::Result<Decoder> {
+        // the frame is checked before the routing fails, while it's compressed by the connection it's routed to
+        let connection = self.route(token);
+        let compression = connection
+            .as_ref()
+            .map_or(CompressionType::Uncompressed, |connection| connection.compression());
+        self.settings.read_only.check(&payload, compression)?;
+        let connection = connection?;
+        let decoder = Decoder::try_from(connection.request(&payload).await?)?;
+        if decoder.is_error()? {
+            let statement = decoder
+                .get_error()?
+                .take_unprepared_id()
+                .and_then(|id| self.statements.lock().unwrap().get(&id));
+            if let Some(statement) = statement {
+                // the shard evicted (or never had) the statement, so prepare it and retry once
+                connection.prepare(&statement).await?;
+                return Decoder::try_from(connection.request(&payload).await?);
+            }
+        }
+        Ok(decoder)
+    }
+    /// Send the request frame to every node, bypassing the token routing, and await their responses, keyed by
+    /// the node addresses, ie to query the node-local system tables. The nodes without any open connection
+    /// map to an error.
+    pub async fn broadcast(&self, payload: Vec<u8>) -> HashMap<SocketAddr, anyhow::Result<Decoder>> {
+        // the frame is checked by the compression of an open connection, as they all request the same compression
+        let compression = self
+            .nodes
+            .iter()
+            .flat_map(|node| node.connections.iter())
+            .find(|connection| connection.is_open())
+            .map_or(CompressionType::Uncompressed, |connection| connection.compression());
+        if let Err(violation) = self.settings.read_only.check(&payload, compression) {
+            return self
+                .nodes
+                .iter()
+                .map(|node| (node.address, Err(violation.clone().into())))
+                .collect();
+        }
+        let payload = Arc::new(payload);
+        let requests: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let request = node
+                    .connections
+                    .iter()
+                    .find(|connection| connection.is_open())
+                    .cloned()
+                    .map(|connection| {
+                        let payload = payload.clone();
+                        tokio::spawn(async move { connection.request(&payload).await })
+                    });
+                (node.address, request)
+            })
+            .collect();
+        let mut responses = HashMap::new();
+        for (address, request) in requests {
+            let response = match request {
+                Some(request) => match request.await {
+                    Ok(buffer) => buffer.and_then(Decoder::try_from),
+                    Err(e) => Err(anyhow!("The request to {} was aborted: {}", address, e)),
+                },
+                None => Err(anyhow!("No open connection left to the node {}!", address)),
+            };
+            responses.insert(address, response);
+        }
+        responses
+    }
+    /// Prepare the statement on every connection, and cache it to be re-prepared whenever a shard reports it
+    /// as unprepared. Returns the prepared statement id.
+    ///
+    /// Note: the cache evicts the statements beyond its capacity (see `SessionBuilder::statement_cache_capacity`),
+    /// which are no longer re-prepared, unless they're pinned.
+    pub async fn prepare(&self, statement: &str) -> anyhow::Result<[u8; 16]> {
+        self.prepare_with(statement, false).await
+    }
+    /// Prepare the statement like `prepare`, and pin it in the statement cache so it never gets evicted
+    pub async fn prepare_pinned(&self, statement: &str) -> anyhow::Result<[u8; 16]> {
+        self.prepare_with(statement, true).await
+    }
+    /// Pin or unpin the cached statement, returns false if the statement isn't cached
+    pub fn pin(&self, statement: &str, pinned: bool) -> bool {
+        self.statements.lock().unwrap().set_pinned(statement, pinned)
+    }
+    /// Get the metrics of the prepared statement cache
+    pub fn statement_cache_stats(&self) -> StatementCacheStats {
+        self.statements.lock().unwrap().stats()
+    }
+    async fn prepare_with(&self, statement: &str, pinned: bool) -> anyhow::Result<[u8; 16]> {
+        let Prepare(payload) = Prepare::new().statement(statement).build()?;
+        self.settings.read_only.check(&payload, CompressionType::Uncompressed)?;
+        let mut prepared_id = None;
+        for node in self.nodes.iter() {
+            for connection in node.connections.iter().filter(|connection| connection.is_open()) {
+                let id = connection.prepare(statement).await?;
+                ensure!(
+                    *prepared_id.get_or_insert(id) == id,
+                    "The nodes assigned different ids to the prepared statement '{}'",
+                    statement
+                );
+            }
+        }
+        let id = prepared_id.ok_or_else(|| anyhow!("No open connection left in the session!"))?;
+        let evicted = self.statements.lock().unwrap().insert(id, statement, pinned);
+        for statement in evicted {
+            log::debug!("Evicted the prepared statement '{}' from the session cache", statement);
+        }
+        Ok(id)
+    }
+    /// Retrieve the trace of a traced request out of its tracing id (see `Decoder::tracing_id`). Returns None
+    /// if the trace isn't completely recorded yet.
+    pub async fn trace(&self, session_id: [u8; 16]) -> anyhow::Result<Option<Trace>> {
+        let sessions = self.send(None, Trace::sessions_query(&session_id)?.0).await?;
+        let events = self.send(None, Trace::events_query(&session_id)?.0).await?;
+        Trace::decode(session_id, sessions, events)
+    }
+    /// Get the id which the server assigned to the prepared statement, if it was prepared by the session
+    pub fn prepared_id(&self, statement: &str) -> Option<[u8; 16]> {
+        self.statements.lock().unwrap().id(statement)
+    }
+    /// Open a session scoped to the keyspace, whose connections authenticate with the credentials of the role,
+    /// ie a least privileged role which is only granted the access to the keyspace.
+    ///
+    /// The scoped session connects to the nodes of this session with the same settings, and sets the keyspace of
+    /// its connections whenever they're established, so the unqualified tables belong to the keyspace (see
+    /// `ScopedSession`).
+    ///
+    /// ## Examples
+    /// ```no_run
+    /// use scylla_rs::cql::{PasswordAuth, Session};
+    /// # async fn run() -> anyhow::Result<()> {
+    /// let session = Session::connect(vec!["127.0.0.1:9042".parse()?]).await?;
+    /// let orders = session
+    ///     .scoped("shop", PasswordAuth::new("shop_reader".to_string(), "secret".to_string()))
+    ///     .await?;
+    /// assert!(orders.authorize("SELECT * FROM orders WHERE id = ?").is_ok());
+    /// assert!(orders.authorize("SELECT * FROM bank.accounts").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn scoped<A: Authenticator + Send + 'static>(
+        &self,
+        keyspace: &str,
+        credentials: A,
+    ) -> anyhow::Result<ScopedSession> {
+        let settings = self.settings.clone();
+        let keyspace = Name::auto(keyspace);
+        let session = SessionBuilder {
+            nodes: self.nodes(),
+            hosts: Vec::new(),
+            authenticator: credentials,
+            tls: settings.tls,
+            max_streams: settings.max_streams,
+            stagger: settings.stagger,
+            connect_timeout: settings.connect_timeout,
+            statement_cache_capacity: settings.statement_cache_capacity,
+            eviction_policy: settings.eviction_policy,
+            read_only: settings.read_only,
+            protocol_version: settings.protocol_version,
+            keyspace: Some(keyspace.clone()),
+        }
+        .build()
+        .await?;
+        Ok(ScopedSession {
+            session,
+            keyspace,
+            prepared: Mutex::new(HashSet::new()),
+        })
+    }
+    fn route(&self, token: Option<i64>) -> anyhow::Result<&Connection> {
+        if let Some(token) = token.filter(|_| !self.ring.is_empty()) {
+            let node = &self.nodes[owner(&self.ring, token)];
+            let connection = &node.connections[shard(token, node.msb, node.connections.len() as u16) as usize];
+            if connection.is_open() {
+                return Ok(connection);
+            }
+        }
+        let connections: Vec<&Arc<Connection>> = self
+            .nodes
+            .iter()
+            .flat_map(|node| node.connections.iter())
+            .filter(|connection| connection.is_open())
+            .collect();
+        ensure!(!connections.is_empty(), "No open connection left in the session!");
+        Ok(connections[self.next.fetch_add(1, Ordering::Relaxed) % connections.len()])
+    }
+}
+
+/// A session scoped to a keyspace, which only sends the SELECT, INSERT, UPDATE and DELETE statements of its
+/// keyspace, ie to hand least privileged handles to the data access layers of an app.
+///
+/// The statements are checked before they're sent (see `ScopedSession::authorize`), along with the statements of
+/// the BATCH frames. The EXECUTE frames are only sent if their statements were prepared by the scoped session.
+///
+/// Note: the check is a client side safeguard, which complements the permissions of the role rather than replaces
+/// them.
+pub struct ScopedSession {
+    session: Session,
+    keyspace: Name,
+    prepared: Mutex<HashSet<[u8; 16]>>,
+}
+
+impl ScopedSession {
+    /// Get the keyspace of the session
+    pub fn keyspace(&self) -> &Name {
+        &self.keyspace
+    }
+    /// Get the addresses of the connected nodes
+    pub fn nodes(&self) -> Vec<SocketAddr> {
+        self.session.nodes()
+    }
+    /// Check if the session is allowed to send the statement, ie it's a SELECT, INSERT, UPDATE or DELETE statement
+    /// whose table is either unqualified or qualified by the keyspace of the session.
+    pub fn authorize(&self, statement: &str) -> anyhow::Result<()> {
+        authorize(&self.keyspace, statement)
+    }
+    /// Prepare the statement like `Session::prepare`, once it's authorized
+    pub async fn prepare(&self, statement: &str) -> anyhow::Result<[u8; 16]> {
+        self.authorize(statement)?;
+        let id = self.session.prepare(statement).await?;
+        self.prepared.lock().unwrap().insert(id);
+        Ok(id)
+    }
+    /// Send the request frame like `Session::send`, once its statements are authorized
+    pub async fn send(&self, token: Option<i64>, payload: Vec<u8>) -> anyhow::Result<Decoder> {
+        // the keyspace field of the frame is authorized along with its statement, once the table is qualified by it
+        let qualified = ProtocolVersion::V4.encode_request(&payload)?;
+        for statement in frame_statements(&qualified)? {
+            match statement {
+                FrameStatement::Statement(statement) => self.authorize(statement)?,
+                FrameStatement::Id(id) => ensure!(
+                    self.prepared.lock().unwrap().contains(id),
+                    "The statement {:x?} wasn't prepared by the scoped session",
+                    id
+                ),
+            }
+        }
+        let is_prepare = payload[4] == opcode::PREPARE;
+        let decoder = self.session.send(token, payload).await?;
+        if is_prepare && decoder.is_prepared()? {
+            self.prepared.lock().unwrap().insert(decoder.get_prepared_id()?);
+        }
+        Ok(decoder)
+    }
+}
+
+/// Check if the statement is a SELECT, INSERT, UPDATE or DELETE statement of the keyspace
+fn authorize(keyspace: &Name, statement: &str) -> anyhow::Result<()> {
+    match StatementKind::of(statement) {
+        Some(StatementKind::Batch) => bail!(
+            "The BEGIN BATCH statements aren't authorized, send a BATCH frame instead: {}",
+            statement
+        ),
+        Some(_) => {}
+        None => bail!(
+            "Only the SELECT, INSERT, UPDATE and DELETE statements are authorized: {}",
+            statement
+        ),
+    }
+    match table_keyspace(statement) {
+        Some(qualifier) if qualifier != *keyspace => {
+            bail!("The statement is out of the {} keyspace: {}", keyspace, statement)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A statement of a request frame
+#[derive(Debug, PartialEq)]
+enum FrameStatement<'a> {
+    /// A query string
+    Statement(&'a str),
+    /// A prepared statement id
+    Id(&'a [u8]),
+}
+
+/// Get the statements of the QUERY, PREPARE, EXECUTE or BATCH frame, the other frames are rejected
+fn frame_statements(payload: &[u8]) -> anyhow::Result<Vec<FrameStatement<'_>>> {
+    ensure!(payload.len() >= 9, "Invalid request frame!");
+    ensure!(
+        payload[1] & header::COMPRESSION == 0,
+        "The statements of the compressed frames can't be authorized"
+    );
+    let bytes = |pos: usize, len: usize| {
+        payload
+            .get(pos..pos + len)
+            .ok_or_else(|| anyhow!("Buffer is too small!"))
+    };
+    let int = |pos: usize| -> anyhow::Result<i32> { Ok(i32::from_be_bytes(bytes(pos, 4)?.try_into()?)) };
+    let short = |pos: usize| -> anyhow::Result<usize> { Ok(u16::from_be_bytes(bytes(pos, 2)?.try_into()?) as usize) };
+    let long_string = |pos: usize| -> anyhow::Result<(&str, usize)> {
+        let len = int(pos)?.max(0) as usize;
+        Ok((std::str::from_utf8(bytes(pos + 4, len)?)?, pos + 4 + len))
+    };
+    let short_bytes = |pos: usize| -> anyhow::Result<(&[u8], usize)> {
+        let len = short(pos)?;
+        Ok((bytes(pos + 2, len)?, pos + 2 + len))
+    };
+    match payload[4] {
+        opcode::QUERY | opcode::PREPARE => Ok(vec![FrameStatement::Statement(long_string(9)?.0)]),
+        opcode::EXECUTE => Ok(vec![FrameStatement::Id(short_bytes(9)?.0)]),
+        opcode::BATCH => {
+            // walk the statements, which start after the batch type, and return the position of the consistency
+            let walk = |with_names: bool| -> anyhow::Result<(Vec<FrameStatement<'_>>, usize)> {
+                let count = short(10)?;
+                let mut pos = 12;
+                let mut statements = Vec::with_capacity(count.min(1024));
+                for _ in 0..count {
+                    let (statement, end) = match bytes(pos, 1)?[0] {
+                        0 => {
+                            let (statement, end) = long_string(pos + 1)?;
+                            (FrameStatement::Statement(statement), end)
+                        }
+                        1 => {
+                            let (id, end) = short_bytes(pos + 1)?;
+                            (FrameStatement::Id(id), end)
+                        }
+                        kind => bail!("Invalid batch statement kind: {}", kind),
+                    };
+                    statements.push(statement);
+                    // skip the values, along with their names
+                    pos = end + 2;
+                    for _ in 0..short(end)? {
+                        if with_names {
+                            pos += 2 + short(pos)?;
+                        }
+                        pos += 4 + int(pos)?.max(0) as usize;
+                    }
+                }
+                Ok((statements, pos))
+            };
+            // the statements are followed by the consistency and the flags, which tell whether the values are named
+            // and which options end the frame
+            let is_complete = |pos: usize, with_names: bool| match payload.get(pos + 2) {
+                Some(flags) => {
+                    (flags & WITH_NAMES == WITH_NAMES) == with_names
+                        && payload.len() == pos + 3 + options_length(*flags)
+                }
+                None => false,
+            };
+            match walk(false) {
+                Ok((statements, pos)) if is_complete(pos, false) => Ok(statements),
+                _ => {
+                    let (statements, pos) = walk(true)?;
+                    ensure!(is_complete(pos, true), "The batch frame is malformed");
+                    Ok(statements)
+                }
+            }
+        }
+        opcode => bail!("The {:#04x} frames aren't authorized", opcode),
+    }
+}
+
+/// A connected node, along with a connection per shard
+struct Node {
+    address: SocketAddr,
+    msb: u8,
+    connections: Vec<Arc<Connection>>,
+}
+
+/// The in-flight requests of a connection
+struct Streams {
+    free: Vec<i16>,
+    pending: HashMap<i16, oneshot::Sender<anyhow::Result<Vec<u8>>>>,
+    closed: bool,
+}
+
+/// The connection to a shard, which is reestablished in the background once it's closed
+struct Connection {
+    link: Arc<Mutex<Option<Arc<Link>>>>,
+    supervisor: JoinHandle<()>,
+}
+
+impl Connection {
+    /// Create the connection out of the established link, if any, which is (re)established by the provided
+    /// function whenever it's closed
+    fn new<F, Fut>(link: Option<Arc<Link>>, connect: F, delay: Duration) -> Self
+    where
+        F: 'static + FnMut() -> Fut + Send,
+        Fut: 'static + Future<Output = anyhow::Result<Arc<Link>>> + Send,
+    {
+        let slot = Arc::new(Mutex::new(link.clone()));
+        Self {
+            supervisor: tokio::spawn(reconnect(link, slot.clone(), connect, delay)),
+            link: slot,
+        }
+    }
+    /// Get the established link, unless it's closed
+    fn link(&self) -> Option<Arc<Link>> {
+        self.link.lock().unwrap().clone().filter(|link| link.is_open())
+    }
+    fn is_open(&self) -> bool {
+        self.link().is_some()
+    }
+    /// Get the compression negotiated on the connection, which compresses its requests
+    fn compression(&self) -> CompressionType {
+        self.link()
+            .map_or(CompressionType::Uncompressed, |link| link.framing.compression())
+    }
+    async fn request(&self, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let link = self.link().ok_or_else(|| anyhow!("Connection closed!"))?;
+        link.request(payload).await
+    }
+    async fn prepare(&self, statement: &str) -> anyhow::Result<[u8; 16]> {
+        let Prepare(payload) = Prepare::new().statement(statement).build()?;
+        let decoder = Decoder::try_from(self.request(&payload).await?)?;
+        if decoder.is_error()? {
+            bail!("Unable to prepare '{}': {}", statement, decoder.get_error()?);
+        }
+        decoder.get_prepared_id()
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.supervisor.abort();
+    }
+}
+
+/// Reestablish the connection whenever its link is closed, where the delay between the attempts doubles after
+/// every failed attempt, up to `MAX_RECONNECT_DELAY`
+async fn reconnect<F, Fut>(
+    mut link: Option<Arc<Link>>,
+    slot: Arc<Mutex<Option<Arc<Link>>>>,
+    mut connect: F,
+    initial_delay: Duration,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<Arc<Link>>>,
+{
+    let mut delay = initial_delay;
+    loop {
+        if let Some(link) = link.take() {
+            link.closed.notified().await;
+            delay = initial_delay;
+        }
+        tokio::time::sleep(delay).await;
+        match connect().await {
+            Ok(established) => {
+                slot.lock().unwrap().replace(established.clone());
+                link = Some(established);
+            }
+            Err(e) => {
+                log::warn!("Unable to reestablish the session connection: {}", e);
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// An established connection, which multiplexes the requests and dispatches the responses by their stream ids
+struct Link {
+    /// The encoded request frames, which are written by the writer task
+    requests: mpsc::UnboundedSender<Vec<u8>>,
+    framing: Framing,
+    streams: Arc<Mutex<Streams>>,
+    /// Notified once the connection is closed
+    closed: Arc<Notify>,
+    reader: JoinHandle<()>,
+    writer: JoinHandle<()>,
+}
+
+impl Link {
+    fn new(stream: CqlStream, framing: Framing, max_streams: u16) -> Self {
+        let (reader, writer) = stream.into_split();
+        let streams = Arc::new(Mutex::new(Streams {
+            free: (0..max_streams as i16).rev().collect(),
+            pending: HashMap::new(),
+            closed: false,
+        }));
+        let closed = Arc::new(Notify::new());
+        let (requests, requests_rx) = mpsc::unbounded_channel();
+        Self {
+            requests,
+            framing: framing.writer(),
+            reader: tokio::spawn(read_responses(reader, framing, streams.clone(), closed.clone())),
+            writer: tokio::spawn(write_requests(writer, requests_rx, streams.clone(), closed.clone())),
+            streams,
+            closed,
+        }
+    }
+    fn is_open(&self) -> bool {
+        !self.streams.lock().unwrap().closed
+    }
+    async fn request(&self, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+        ensure!(payload.len() >= 9, "Invalid request frame!");
+        let (tx, rx) = oneshot::channel();
+        let stream_id = {
+            let mut streams = self.streams.lock().unwrap();
+            ensure!(!streams.closed, "Connection closed!");
+            let stream_id = streams
+                .free
+                .pop()
+                .ok_or_else(|| anyhow!("No stream id available, the connection is overloaded!"))?;
+            streams.pending.insert(stream_id, tx);
+            stream_id
+        };
+        let mut frame = payload.to_vec();
+        frame[2..4].copy_from_slice(&stream_id.to_be_bytes());
+        let res = self
+            .framing
+            .encode(&frame)
+            .and_then(|frame| self.requests.send(frame).map_err(|_| anyhow!("Connection closed!")));
+        if let Err(e) = res {
+            let mut streams = self.streams.lock().unwrap();
+            if streams.pending.remove(&stream_id).is_some() {
+                streams.free.push(stream_id);
+            }
+            return Err(e);
+        }
+        rx.await.map_err(|_| anyhow!("Connection lost!"))?
+    }
+}
+
+impl Drop for Link {
+    fn drop(&mut self) {
+        self.reader.abort();
+        self.writer.abort();
+    }
+}
+
+/// Write the request frames in the order they're sent, until the connection fails
+async fn write_requests(
+    mut writer: CqlWriteHalf,
+    mut requests: mpsc::UnboundedReceiver<Vec<u8>>,
+    streams: Arc<Mutex<Streams>>,
+    closed: Arc<Notify>,
+) {
+    while let Some(frame) = requests.recv().await {
+        if let Err(e) = writer.write_all(&frame).await {
+            close(&streams, &closed, e.into());
+            return;
+        }
+    }
+}
+
+/// Read the response frames and dispatch them to the pending requests, until the connection fails
+async fn read_responses(
+    mut reader: CqlReadHalf,
+    mut framing: Framing,
+    streams: Arc<Mutex<Streams>>,
+    closed: Arc<Notify>,
+) {
+    let error = loop {
+        match framing.read(&mut reader).await {
+            Ok(buffer) => {
+                let stream_id = i16::from_be_bytes([buffer[2], buffer[3]]);
+                // negative stream ids are reserved for the server events
+                if stream_id < 0 {
+                    continue;
+                }
+                let mut streams = streams.lock().unwrap();
+                if let Some(tx) = streams.pending.remove(&stream_id) {
+                    streams.free.push(stream_id);
+                    tx.send(Ok(buffer)).ok();
+                }
+            }
+            Err(e) => break e,
+        }
+    };
+    close(&streams, &closed, error);
+}
+
+/// Close the connection, which fails its pending requests
+fn close(streams: &Mutex<Streams>, closed: &Notify, error: anyhow::Error) {
+    log::warn!("Session connection closed: {}", error);
+    let mut streams = streams.lock().unwrap();
+    streams.closed = true;
+    for (_, tx) in streams.pending.drain() {
+        tx.send(Err(anyhow!("Connection closed: {}", error))).ok();
+    }
+    closed.notify_one();
+}
+
+/// Get the index of the node owning the token, which is the first node token equal or greater than it
+fn owner(ring: &[(i64, usize)], token: i64) -> usize {
+    let index = ring.partition_point(|(node_token, _)| *node_token < token);
+    ring[index % ring.len()].1
+}
+
+/// Get the shard owning the token, by using the scylla sharding algorithm
+fn shard(token: i64, msb: u8, shard_count: u16) -> u16 {
+    (((((token as i128 + i64::MIN as i128) as u64) << msb) as u128 * shard_count as u128) >> 64) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::ReadOnlyViolation;
+
+    #[test]
+    fn token_routing() {
+        let ring = vec![(-100, 0), (0, 1), (100, 2)];
+        assert_eq!(owner(&ring, -200), 0);
+        assert_eq!(owner(&ring, -100), 0);
+        assert_eq!(owner(&ring, -99), 1);
+        assert_eq!(owner(&ring, 100), 2);
+        // wraps around the ring
+        assert_eq!(owner(&ring, 101), 0);
+        assert_eq!(shard(i64::MIN, 12, 8), 0);
+        assert_eq!(shard(i64::MAX, 0, 8), 7);
+        assert_eq!(shard(0, 0, 8), 4);
+        assert!((0..1000).all(|i| shard(i * 7919, 12, 3) < 3));
+    }
+
+    /// A session without any connection
+    fn disconnected(read_only: bool) -> Session {
+        Session {
+            nodes: Vec::new(),
+            ring: Vec::new(),
+            next: AtomicUsize::new(0),
+            statements: Mutex::new(StatementCache::new(1, EvictionPolicy::default())),
+            settings: Settings {
+                tls: None,
+                max_streams: 1,
+                stagger: DEFAULT_STAGGER,
+                connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+                statement_cache_capacity: 1,
+                eviction_policy: EvictionPolicy::default(),
+                read_only: ReadOnly::new(read_only),
+                protocol_version: ProtocolVersion::V4,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn read_only_session() {
+        let session = disconnected(true);
+        fn is_violation<T>(res: anyhow::Result<T>) -> bool {
+            matches!(res, Err(e) if e.downcast_ref::<ReadOnlyViolation>().is_some())
+        }
+        let Query(insert) = Query::new()
+            .statement("-- backfill\nINSERT INTO shop.orders (id) VALUES (1)")
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        assert!(is_violation(session.send(None, insert.clone()).await));
+        assert!(is_violation(
+            session.prepare("UPDATE shop.orders SET v = 1 WHERE id = ?").await
+        ));
+        // the reads are sent, which fails as the session has no connection
+        let Query(select) = Query::new()
+            .statement("SELECT * FROM shop.orders")
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        assert!(!is_violation(session.send(None, select).await));
+        assert!(!is_violation(session.prepare("SELECT * FROM shop.orders").await));
+        // the mode is toggled at runtime
+        session.read_only().set(false);
+        assert!(!is_violation(session.send(None, insert).await));
+    }
+
+    #[tokio::test]
+    async fn scoped_keyspace_field() {
+        let scoped = ScopedSession {
+            session: disconnected(false),
+            keyspace: Name::auto("shop"),
+            prepared: Mutex::new(HashSet::new()),
+        };
+        let query = |keyspace| {
+            let Query(payload) = Query::new()
+                .statement("SELECT * FROM orders")
+                .consistency(Consistency::One)
+                .with_keyspace(keyspace)
+                .build()
+                .unwrap();
+            payload
+        };
+        // the keyspace field of the frame can't escape the keyspace of the session
+        let error = scoped.send(None, query("bank")).await.err().unwrap();
+        assert!(error.to_string().contains("out of the shop keyspace"), "{}", error);
+        // the statements of the keyspace are sent, which fails as the session has no connection
+        let error = scoped.send(None, query("shop")).await.err().unwrap();
+        assert!(error.to_string().contains("No open connection"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn reconnect_closed_connection() {
+        use tokio::{
+            io::AsyncReadExt,
+            net::{TcpListener, TcpStream},
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        // the stub node closes the first connection once it answered a request, while it keeps the next one open
+        tokio::spawn(async move {
+            for requests in [1, usize::MAX] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                for _ in 0..requests {
+                    let mut header = [0; 9];
+                    if socket.read_exact(&mut header).await.is_err() {
+                        break;
+                    }
+                    let mut body = vec![0; i32::from_be_bytes(header[5..9].try_into().unwrap()) as usize];
+                    socket.read_exact(&mut body).await.unwrap();
+                    // a void RESULT frame of the same stream
+                    let mut response = vec![0x84, 0, header[2], header[3], opcode::RESULT, 0, 0, 0, 4];
+                    response.extend_from_slice(&1i32.to_be_bytes());
+                    socket.write_all(&response).await.unwrap();
+                }
+            }
+        });
+        let connect = move || async move {
+            let stream = CqlStream::Tcp(TcpStream::connect(address).await?);
+            Ok(Arc::new(Link::new(stream, Framing::new(ProtocolVersion::V4), 8)))
+        };
+        // the connection which failed to connect is established in the background
+        let connection = Connection::new(None, connect, Duration::from_millis(10));
+        assert!(!connection.is_open());
+        let await_open = |open: bool| {
+            let connection = &connection;
+            async move {
+                for _ in 0..500 {
+                    if connection.is_open() == open {
+                        return;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                panic!("The connection isn't {}", if open { "open" } else { "closed" });
+            }
+        };
+        let Query(payload) = Query::new()
+            .statement("SELECT * FROM ks.t")
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        await_open(true).await;
+        assert!(Decoder::try_from(connection.request(&payload).await.unwrap())
+            .unwrap()
+            .is_void()
+            .unwrap());
+        // the closed connection is reestablished
+        await_open(false).await;
+        assert!(connection.request(&payload).await.is_err());
+        await_open(true).await;
+        for _ in 0..3 {
+            assert!(Decoder::try_from(connection.request(&payload).await.unwrap())
+                .unwrap()
+                .is_void()
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn scoped_statements() {
+        use crate::cql::{Batch, Values};
+        let shop = Name::auto("shop");
+        assert!(authorize(&shop, "SELECT * FROM orders WHERE id = ?").is_ok());
+        assert!(authorize(&shop, "insert into SHOP.orders (id) values (?)").is_ok());
+        assert!(authorize(&shop, "UPDATE bank.accounts SET v = 1 WHERE id = 2").is_err());
+        assert!(authorize(&shop, "DELETE FROM \"Shop\".orders WHERE id = 1").is_err());
+        assert!(authorize(&shop, "DROP TABLE orders").is_err());
+        assert!(authorize(&shop, "USE bank").is_err());
+        assert!(authorize(&shop, "BEGIN BATCH INSERT INTO bank.t (k) VALUES (1) APPLY BATCH").is_err());
+        let Query(query) = Query::new()
+            .statement("SELECT * FROM orders")
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        assert_eq!(
+            frame_statements(&query).unwrap(),
+            vec![FrameStatement::Statement("SELECT * FROM orders")]
+        );
+        let Batch(batch) = Batch::new()
+            .logged()
+            .statement("INSERT INTO orders (id) VALUES (?)")
+            .value(&1)
+            .id(&[7; 16])
+            .value(&"a")
+            .value(&2)
+            .consistency(Consistency::One)
+            .build()
+            .unwrap();
+        assert_eq!(
+            frame_statements(&batch).unwrap(),
+            vec![
+                FrameStatement::Statement("INSERT INTO orders (id) VALUES (?)"),
+                FrameStatement::Id(&[7; 16])
+            ]
+        );
+        // the named values are skipped along with their names
+        let Batch(batch) = Batch::new()
+            .unlogged()
+            .statement("UPDATE orders SET total = :total WHERE id = :id")
+            .named_value("total", &10)
+            .named_value("id", &1)
+            .id(&[7; 16])
+            .named_value("id", &2)
+            .statement("DELETE FROM bank.accounts WHERE id = :id")
+            .named_value("id", &3)
+            .consistency(Consistency::One)
+            .timestamp(1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            frame_statements(&batch).unwrap(),
+            vec![
+                FrameStatement::Statement("UPDATE orders SET total = :total WHERE id = :id"),
+                FrameStatement::Id(&[7; 16]),
+                FrameStatement::Statement("DELETE FROM bank.accounts WHERE id = :id"),
+            ]
+        );
+        let Prepare(mut prepare) = Prepare::new().statement("SELECT * FROM orders").build().unwrap();
+        prepare[4] = opcode::OPTIONS;
+        assert!(frame_statements(&prepare).is_err());
+    }
+}
@@ -0,0 +1,1234 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the statement helpers which are used by the token-aware routing and the DDL execution.
+
+use super::{statements::service_level::format_duration, Name};
+use anyhow::{anyhow, ensure};
+use std::{borrow::Cow, fmt::Debug, hash::Hash, time::Duration};
+
+/// Computes the ids which key the statements in the local caches and registries.
+///
+/// Note: the wire-level prepared ids are assigned by the server, so they should be taken from the PREPARED
+/// responses (or the UNPREPARED errors), rather than being assumed to match any local algorithm.
+pub trait StatementIdAlgorithm {
+    /// The statement id type
+    type Id: Clone + Eq + Hash + Debug + Send + Sync;
+    /// Compute the id of the statement
+    fn statement_id(statement: &str) -> Self::Id;
+}
+
+/// The MD5 statement ids, which match the prepared ids currently assigned by Scylla.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Md5;
+
+impl StatementIdAlgorithm for Md5 {
+    type Id = [u8; 16];
+    fn statement_id(statement: &str) -> [u8; 16] {
+        md5::compute(statement.as_bytes()).into()
+    }
+}
+
+/// The 64-bit FNV-1a statement ids, which are cheaper to compute than MD5 for the local caches.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fnv64;
+
+impl StatementIdAlgorithm for Fnv64 {
+    type Id = u64;
+    fn statement_id(statement: &str) -> u64 {
+        statement.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    }
+}
+
+/// The keywords which start the top level clauses of the DML statements
+const CLAUSE_KEYWORDS: [&str; 10] = [
+    "values", "set", "where", "if", "using", "limit", "order", "group", "per", "allow",
+];
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    /// An unquoted identifier or keyword, lowercased as it's case insensitive
+    Ident(String),
+    /// A quoted identifier, which is case sensitive and unescaped
+    Quoted(Cow<'a, str>),
+    /// A positional `?` or named `:name` bind marker, along with its lowercased name
+    Marker(Option<String>),
+    /// A literal, ie a string or a number
+    Literal,
+    /// A single punctuation char
+    Punct(char),
+}
+
+impl<'a> Token<'a> {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Ident(ident) if ident == keyword)
+    }
+    fn column(&self) -> Option<&str> {
+        match self {
+            Token::Ident(ident) => Some(ident),
+            Token::Quoted(quoted) => Some(quoted.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Computes the bind marker indexes of the partition key columns of a statement.
+pub trait TokenIndexes {
+    /// Get the index of the bind marker which binds each of the partition key columns, in the partition key
+    /// order, which is the order the values should be token-encoded with.
+    ///
+    /// Every positional `?` and named `:name` marker of the statement counts as a bind value, in the order of
+    /// appearance. The partition key columns are matched against the `column = ?` relations of the WHERE
+    /// clause, regardless of their order, or against the column list of an INSERT statement.
+    ///
+    /// Returns None if any partition key column isn't bound by a single marker, ie it's restricted by a
+    /// literal, an `IN` relation or a `token()` relation, as the request can't be routed by a single token.
+    ///
+    /// Note: unquoted column names are case insensitive, while quoted ones are matched as they are.
+    ///
+    /// ## Examples
+    /// ```
+    /// use scylla_rs::cql::TokenIndexes;
+    ///
+    /// let statement = "SELECT * FROM ks.table WHERE ts > ? AND b = ? AND a = ?";
+    /// assert_eq!(statement.token_indexes(&["a", "b"]), Some(vec![2, 1]));
+    /// ```
+    fn token_indexes(&self, partition_key: &[&str]) -> Option<Vec<usize>>;
+}
+
+impl TokenIndexes for str {
+    fn token_indexes(&self, partition_key: &[&str]) -> Option<Vec<usize>> {
+        let markers = statement_markers(self)?;
+        partition_key
+            .iter()
+            .map(|column| {
+                let column = normalize(column);
+                markers
+                    .iter()
+                    .find(|marker| {
+                        marker.column.as_deref() == Some(column.as_str())
+                            && matches!(
+                                (marker.clause, marker.operator.as_deref()),
+                                (MarkerClause::Values, _) | (MarkerClause::Where, Some("="))
+                            )
+                    })
+                    .map(|marker| marker.index)
+            })
+            .collect()
+    }
+}
+
+/// The clause which holds a bind marker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerClause {
+    /// The `VALUES (..)` of an INSERT statement
+    Values,
+    /// The `SET ..` assignments of an UPDATE statement
+    Set,
+    /// The `WHERE ..` relations
+    Where,
+    /// The `IF ..` conditions of a lightweight transaction
+    If,
+    /// The `USING TTL ?` of an INSERT or an UPDATE statement
+    Ttl,
+    /// The `USING TIMESTAMP ?` of a mutation
+    Timestamp,
+    /// The Scylla `USING TIMEOUT ?` of a statement
+    Timeout,
+    /// The `LIMIT ?` or `PER PARTITION LIMIT ?` of a SELECT statement
+    Limit,
+    /// Any other clause, ie the selectors of a SELECT statement
+    Other,
+}
+
+/// A bind marker of a statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindMarker {
+    /// The index of the marker, ie of its bound value, in the order of appearance
+    pub index: usize,
+    /// The lowercased name of a named `:name` marker, or None for a positional `?` marker
+    pub name: Option<String>,
+    /// The clause which holds the marker
+    pub clause: MarkerClause,
+    /// The column which the marker binds, if it's the value of an INSERT column, the right operand of a
+    /// `column = ?` (or `column = column + ?`) assignment or of a `column <op> ?` relation or condition, or an
+    /// element of a `column IN (..)` relation
+    pub column: Option<String>,
+    /// The operator of the relation or the condition, ie `=`, `<=`, `IN` or `CONTAINS KEY`
+    pub operator: Option<String>,
+}
+
+/// Extracts the bind markers of a statement.
+pub trait BindMarkers {
+    /// Get the bind markers of the statement in the order of appearance, along with the clause which holds them
+    /// and the column they bind, when it's determinable, ie to build the token indexes of a dynamic statement or
+    /// to check the types of its bound values.
+    ///
+    /// The markers of the inner statements of a batch are indexed after the ones of the preceding statements.
+    /// Returns None if the statement has an unterminated quote.
+    ///
+    /// Note: unquoted column names are lowercased, while quoted ones are kept as they are.
+    ///
+    /// ## Examples
+    /// ```
+    /// use scylla_rs::cql::{BindMarkers, MarkerClause};
+    ///
+    /// let markers = "UPDATE ks.t USING TTL ? SET v = ? WHERE k = :key".bind_markers().unwrap();
+    /// assert_eq!(markers[0].clause, MarkerClause::Ttl);
+    /// assert_eq!((markers[1].clause, markers[1].column.as_deref()), (MarkerClause::Set, Some("v")));
+    /// assert_eq!(markers[2].name.as_deref(), Some("key"));
+    /// assert_eq!(
+    ///     (markers[2].column.as_deref(), markers[2].operator.as_deref()),
+    ///     (Some("k"), Some("="))
+    /// );
+    /// ```
+    fn bind_markers(&self) -> Option<Vec<BindMarker>>;
+}
+
+impl BindMarkers for str {
+    fn bind_markers(&self) -> Option<Vec<BindMarker>> {
+        if StatementKind::of(self) != Some(StatementKind::Batch) {
+            return statement_markers(self);
+        }
+        let mut markers = Vec::new();
+        for statement in batch_statements(self)? {
+            let offset = markers.len();
+            markers.extend(statement_markers(statement)?.into_iter().map(|mut marker| {
+                marker.index += offset;
+                marker
+            }));
+        }
+        Some(markers)
+    }
+}
+
+/// The kind of a DML statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementKind {
+    /// A SELECT statement
+    Select,
+    /// An INSERT statement
+    Insert,
+    /// An UPDATE statement
+    Update,
+    /// A DELETE statement
+    Delete,
+    /// A BEGIN .. APPLY BATCH statement
+    Batch,
+}
+
+impl StatementKind {
+    /// Get the kind of the DML statement by its leading keyword, or None if it's not a DML statement
+    pub fn of(statement: &str) -> Option<Self> {
+        let trimmed = statement.trim_start();
+        let len = trimmed
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(trimmed.len());
+        match trimmed[..len].to_ascii_lowercase().as_str() {
+            "select" => Some(StatementKind::Select),
+            "insert" => Some(StatementKind::Insert),
+            "update" => Some(StatementKind::Update),
+            "delete" => Some(StatementKind::Delete),
+            "begin" => Some(StatementKind::Batch),
+            _ => None,
+        }
+    }
+}
+
+/// Check if the statement is a lightweight transaction, ie a conditional insert, update, delete or batch, whose
+/// result is decoded by a `LwtDecoder`
+pub fn is_conditional(statement: &str) -> bool {
+    match StatementKind::of(statement) {
+        Some(StatementKind::Select) | None => false,
+        Some(_) => matches!(tokenize(statement), Some(tokens) if tokens.iter().any(|token| token.is_keyword("if"))),
+    }
+}
+
+/// Get the number of bind markers of the statement, or None if it has an unterminated quote
+pub(crate) fn marker_count(statement: &str) -> Option<usize> {
+    Some(
+        tokenize(statement)?
+            .iter()
+            .filter(|token| matches!(token, Token::Marker(_)))
+            .count(),
+    )
+}
+
+/// Split the `BEGIN [UNLOGGED | COUNTER] BATCH [USING ..] .. APPLY BATCH` statement into its inner statements
+pub(crate) fn batch_statements(statement: &str) -> Option<Vec<&str>> {
+    // split by the semicolons which are not quoted
+    let mut parts = Vec::new();
+    let (mut quote, mut start) = (None, 0);
+    for (i, c) in statement.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, ';') => {
+                parts.push(&statement[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    parts.push(&statement[start..]);
+    let mut parts: Vec<&str> = parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    // strip the APPLY BATCH suffix of the last statement
+    let last = parts.pop()?;
+    let lowercase = last.to_ascii_lowercase();
+    let body = lowercase.strip_suffix("batch")?.trim_end().strip_suffix("apply")?;
+    parts.push(last[..body.len()].trim());
+    // strip the BEGIN [UNLOGGED | COUNTER] BATCH [USING TIMESTAMP ..] prefix of the first statement
+    let mut rest = strip_keyword(parts.first()?, "begin")?;
+    rest = strip_keyword(rest, "unlogged")
+        .or_else(|| strip_keyword(rest, "counter"))
+        .unwrap_or(rest);
+    rest = strip_keyword(rest, "batch")?;
+    if let Some(using) = strip_keyword(rest, "using") {
+        let using = strip_keyword(using, "timestamp")?.trim_start();
+        rest = &using[using.find(char::is_whitespace).unwrap_or(using.len())..];
+    }
+    parts[0] = rest.trim();
+    Some(parts.into_iter().filter(|part| !part.is_empty()).collect())
+}
+
+/// Strip the case insensitive keyword, which is followed by a whitespace or the end of the statement
+fn strip_keyword<'a>(statement: &'a str, keyword: &str) -> Option<&'a str> {
+    let statement = statement.trim_start();
+    let rest = statement.get(keyword.len()..)?;
+    if statement.get(..keyword.len())?.eq_ignore_ascii_case(keyword)
+        && (rest.is_empty() || rest.starts_with(char::is_whitespace))
+    {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+/// Normalize a partition key column name the same way the statement identifiers are
+fn normalize(column: &str) -> String {
+    Name::parse(column)
+        .map(|name| name.as_str().to_string())
+        .unwrap_or_else(|_| column.to_lowercase())
+}
+
+fn tokenize(statement: &str) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let bytes = statement.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            // find the closing quote, where a doubled quote is an escaped one
+            let start = i + 1;
+            let mut end = start;
+            loop {
+                match bytes.get(end) {
+                    Some(b) if *b as char == c && bytes.get(end + 1).map(|b| *b as char) == Some(c) => end += 2,
+                    Some(b) if *b as char == c => break,
+                    Some(_) => end += 1,
+                    None => return None,
+                }
+            }
+            tokens.push(if c == '"' {
+                let quoted = &statement[start..end];
+                Token::Quoted(if quoted.contains("\"\"") {
+                    Cow::Owned(quoted.replace("\"\"", "\""))
+                } else {
+                    Cow::Borrowed(quoted)
+                })
+            } else {
+                Token::Literal
+            });
+            i = end + 1;
+        } else if c == '?' {
+            tokens.push(Token::Marker(None));
+            i += 1;
+        } else if c == ':' && matches!(bytes.get(i + 1), Some(b) if b.is_ascii_alphabetic() || *b == b'_') {
+            i += 1;
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push(Token::Marker(Some(statement[start..i].to_lowercase())));
+        } else if c.is_ascii_alphanumeric()
+            || c == '_'
+            || c == '-' && matches!(bytes.get(i + 1), Some(b) if b.is_ascii_digit())
+        {
+            let start = i;
+            i += 1;
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] == b'.' && c.is_ascii_digit())
+            {
+                i += 1;
+            }
+            if c.is_ascii_alphabetic() || c == '_' {
+                tokens.push(Token::Ident(statement[start..i].to_lowercase()));
+            } else {
+                tokens.push(Token::Literal);
+            }
+        } else {
+            tokens.push(Token::Punct(c));
+            i += c.len_utf8();
+            // skip the remaining bytes of a multi-byte char
+            while i < bytes.len() && !statement.is_char_boundary(i) {
+                i += 1;
+            }
+        }
+    }
+    Some(tokens)
+}
+
+/// Split the tokens by the separator at the top nesting level
+fn split_top_level<'t, 'a>(
+    tokens: &'t [Token<'a>],
+    is_separator: impl Fn(&Token) -> bool,
+) -> Vec<(usize, &'t [Token<'a>])> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('(') | Token::Punct('[') | Token::Punct('{') => depth += 1,
+            Token::Punct(')') | Token::Punct(']') | Token::Punct('}') => depth -= 1,
+            _ if depth == 0 && is_separator(token) => {
+                parts.push((start, &tokens[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push((start, &tokens[start..]));
+    parts
+}
+
+/// Get the bind markers of a single statement
+fn statement_markers(statement: &str) -> Option<Vec<BindMarker>> {
+    let tokens = tokenize(statement)?;
+    let is_insert = tokens.first().is_some_and(|token| token.is_keyword("insert"));
+    // the clause, the column and the operator of every token, by its position
+    let mut bound: Vec<(MarkerClause, Option<String>, Option<String>)> =
+        vec![(MarkerClause::Other, None, None); tokens.len()];
+    // the top level clauses, along with the positions of their first token
+    let mut clauses: Vec<(&str, usize)> = Vec::new();
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('(') | Token::Punct('[') | Token::Punct('{') => depth += 1,
+            Token::Punct(')') | Token::Punct(']') | Token::Punct('}') => depth -= 1,
+            Token::Ident(ident) if depth == 0 => {
+                if let Some(keyword) = CLAUSE_KEYWORDS
+                    .iter()
+                    .find(|keyword| *keyword == ident && (is_insert || **keyword != "values"))
+                {
+                    clauses.push((keyword, i + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    for (i, (keyword, start)) in clauses.iter().enumerate() {
+        let end = clauses.get(i + 1).map_or(tokens.len(), |(_, next)| next - 1);
+        let clause = match *keyword {
+            "values" => MarkerClause::Values,
+            "set" => MarkerClause::Set,
+            "where" => MarkerClause::Where,
+            "if" => MarkerClause::If,
+            "limit" => MarkerClause::Limit,
+            _ => MarkerClause::Other,
+        };
+        bound[*start..end]
+            .iter_mut()
+            .for_each(|(marker_clause, _, _)| *marker_clause = clause);
+        let parts = match *keyword {
+            "set" => split_top_level(&tokens[*start..end], |token| *token == Token::Punct(',')),
+            "where" | "if" | "using" => split_top_level(&tokens[*start..end], |token| token.is_keyword("and")),
+            _ => Vec::new(),
+        };
+        for (offset, part) in parts {
+            let position = start + offset;
+            match (*keyword, part) {
+                ("using", [Token::Ident(option), Token::Marker(_)]) => {
+                    bound[position + 1].0 = match option.as_str() {
+                        "ttl" => MarkerClause::Ttl,
+                        "timestamp" => MarkerClause::Timestamp,
+                        "timeout" => MarkerClause::Timeout,
+                        _ => MarkerClause::Other,
+                    };
+                }
+                ("set", [column, Token::Punct('='), Token::Marker(_)]) => {
+                    bound[position + 2].1 = column.column().map(str::to_string);
+                }
+                ("set", [column, Token::Punct('='), other, Token::Punct('+' | '-'), Token::Marker(_)])
+                    if column == other =>
+                {
+                    bound[position + 4].1 = column.column().map(str::to_string);
+                }
+                ("where" | "if", _) => {
+                    for (index, column, operator) in relation_columns(part) {
+                        bound[position + index].1 = Some(column);
+                        bound[position + index].2 = Some(operator);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    if is_insert {
+        for (column, position) in insert_columns(&tokens).unwrap_or_default() {
+            bound[position].1 = Some(column);
+        }
+    }
+    Some(
+        tokens
+            .into_iter()
+            .zip(bound)
+            .filter_map(|(token, (clause, column, operator))| match token {
+                Token::Marker(name) => Some((name, clause, column, operator)),
+                _ => None,
+            })
+            .enumerate()
+            .map(|(index, (name, clause, column, operator))| BindMarker {
+                index,
+                name,
+                clause,
+                column,
+                operator,
+            })
+            .collect(),
+    )
+}
+
+/// Get the markers of the `column <op> ?` or `column IN (?, ..)` relation along with their column and operator,
+/// by their positions in the relation
+fn relation_columns(relation: &[Token]) -> Vec<(usize, String, String)> {
+    let column = match relation.first().and_then(Token::column) {
+        Some(column) if relation.len() >= 3 => column.to_string(),
+        _ => return Vec::new(),
+    };
+    if let [Token::Ident(keyword), Token::Punct('('), values @ .., Token::Punct(')')] = &relation[1..] {
+        if keyword == "in" {
+            return split_top_level(values, |token| *token == Token::Punct(','))
+                .into_iter()
+                .filter(|(_, value)| matches!(value, [Token::Marker(_)]))
+                .map(|(offset, _)| (offset + 3, column.clone(), "IN".to_string()))
+                .collect();
+        }
+    }
+    if !matches!(relation.last(), Some(Token::Marker(_))) {
+        return Vec::new();
+    }
+    let mut operator = String::new();
+    for token in &relation[1..relation.len() - 1] {
+        match token {
+            Token::Punct(c) if "<>=!".contains(*c) => operator.push(*c),
+            Token::Ident(keyword) if ["in", "contains", "key", "like", "not"].contains(&keyword.as_str()) => {
+                if !operator.is_empty() {
+                    operator.push(' ');
+                }
+                operator.push_str(&keyword.to_uppercase());
+            }
+            _ => return Vec::new(),
+        }
+    }
+    vec![(relation.len() - 1, column, operator)]
+}
+
+/// Get the columns of an INSERT statement which are bound by a single marker, along with the positions of their
+/// markers
+fn insert_columns(tokens: &[Token]) -> Option<Vec<(String, usize)>> {
+    let group = |start: usize| -> Option<(usize, usize)> {
+        let open = start + tokens[start..].iter().position(|token| *token == Token::Punct('('))?;
+        let mut depth = 0;
+        for (i, token) in tokens.iter().enumerate().skip(open) {
+            match token {
+                Token::Punct('(') => depth += 1,
+                Token::Punct(')') if depth == 1 => return Some((open + 1, i)),
+                Token::Punct(')') => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    };
+    let (columns_start, columns_end) = group(0)?;
+    let values = columns_end
+        + tokens[columns_end..]
+            .iter()
+            .position(|token| token.is_keyword("values"))?;
+    let (values_start, values_end) = group(values)?;
+    let is_comma = |token: &Token| *token == Token::Punct(',');
+    let columns = split_top_level(&tokens[columns_start..columns_end], is_comma);
+    let values = split_top_level(&tokens[values_start..values_end], is_comma);
+    Some(
+        columns
+            .into_iter()
+            .zip(values)
+            .filter_map(|((_, column), (offset, value))| match (column, value) {
+                ([column], [Token::Marker(_)]) => column
+                    .column()
+                    .map(|column| (column.to_string(), values_start + offset)),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+/// Rewrite the CREATE statement to include IF NOT EXISTS, and the DROP statement to include IF EXISTS, so
+/// replaying it after an ambiguous failure doesn't error with AlreadyExists (or the object not being found).
+///
+/// The other statements, and the ones which already include the condition, are returned as they are.
+/// Note: `CREATE OR REPLACE` statements are returned as they are, as they can't include IF NOT EXISTS.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::idempotent_ddl;
+///
+/// assert_eq!(
+///     idempotent_ddl("CREATE TABLE ks.t (k int PRIMARY KEY)"),
+///     "CREATE TABLE IF NOT EXISTS ks.t (k int PRIMARY KEY)"
+/// );
+/// assert_eq!(idempotent_ddl("drop materialized view ks.v"), "drop materialized view IF EXISTS ks.v");
+/// ```
+pub fn idempotent_ddl(statement: &str) -> Cow<'_, str> {
+    // the leading words along with their end offsets
+    let mut words = Vec::new();
+    let mut rest = statement;
+    while words.len() < 5 {
+        let trimmed = rest.trim_start();
+        let len = trimmed
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(trimmed.len());
+        if len == 0 {
+            break;
+        }
+        let end = statement.len() - trimmed.len() + len;
+        words.push((trimmed[..len].to_ascii_lowercase(), end));
+        rest = &statement[end..];
+    }
+    let word = |i: usize| words.get(i).map(|(word, _)| word.as_str());
+    let (condition, kind_len) = match (word(0), word(1), word(2)) {
+        (Some("create"), Some("custom"), Some("index")) => (" IF NOT EXISTS", 3),
+        (Some("create"), Some("materialized"), Some("view")) => (" IF NOT EXISTS", 3),
+        (Some("create"), Some(kind), _) if DDL_KINDS.contains(&kind) => (" IF NOT EXISTS", 2),
+        (Some("drop"), Some("materialized"), Some("view")) => (" IF EXISTS", 3),
+        (Some("drop"), Some(kind), _) if DDL_KINDS.contains(&kind) => (" IF EXISTS", 2),
+        _ => return Cow::Borrowed(statement),
+    };
+    if word(kind_len) == Some("if") {
+        return Cow::Borrowed(statement);
+    }
+    let end = words[kind_len - 1].1;
+    Cow::Owned(format!("{}{}{}", &statement[..end], condition, &statement[end..]))
+}
+
+/// Qualify the table of the SELECT, INSERT, UPDATE or DELETE statement with the keyspace, unless it's already
+/// qualified, so it doesn't depend on the keyspace of the connection (ie the one set by USE).
+///
+/// The batch statements, and the other statements, are returned as they are.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::qualify_table;
+///
+/// assert_eq!(
+///     qualify_table("SELECT * FROM orders WHERE id = ?", "shop"),
+///     "SELECT * FROM shop.orders WHERE id = ?"
+/// );
+/// assert_eq!(qualify_table("UPDATE bank.accounts SET v = ?", "shop"), "UPDATE bank.accounts SET v = ?");
+/// ```
+pub fn qualify_table<'a>(statement: &'a str, keyspace: &str) -> Cow<'a, str> {
+    let (table_start, table_len) = match table_span(statement) {
+        Some(span) => span,
+        None => return Cow::Borrowed(statement),
+    };
+    if statement[table_start + table_len..].trim_start().starts_with('.') {
+        return Cow::Borrowed(statement);
+    }
+    Cow::Owned(format!(
+        "{}{}.{}",
+        &statement[..table_start],
+        Name::auto(keyspace),
+        &statement[table_start..]
+    ))
+}
+
+/// Restrict the SELECT statement to the partitions whose token is in `(start, end]`, by prepending the
+/// `token(pk) > ? AND token(pk) <= ?` relation over the partition key columns to its WHERE clause, so the start and
+/// the end of the range are bound before the other values of the statement.
+///
+/// Fails if the statement isn't a SELECT statement, or if it has bind markers before its WHERE clause.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::with_token_range;
+///
+/// assert_eq!(
+///     with_token_range("SELECT * FROM shop.orders WHERE day = ? ALLOW FILTERING", &["customer"])?,
+///     "SELECT * FROM shop.orders WHERE token(customer) > ? AND token(customer) <= ? AND day = ? ALLOW FILTERING"
+/// );
+/// assert_eq!(
+///     with_token_range("SELECT id FROM shop.orders LIMIT 10;", &["customer", "day"])?,
+///     "SELECT id FROM shop.orders WHERE token(customer, day) > ? AND token(customer, day) <= ? LIMIT 10"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn with_token_range(statement: &str, partition_key: &[&str]) -> anyhow::Result<String> {
+    ensure!(
+        StatementKind::of(statement) == Some(StatementKind::Select),
+        "Not a SELECT statement: {}",
+        statement
+    );
+    ensure!(!partition_key.is_empty(), "The partition key has no columns");
+    let statement = statement.trim().trim_end_matches(';').trim_end();
+    let (table_start, table_len) = table_span(statement).ok_or_else(|| anyhow!("No table in: {}", statement))?;
+    let from = table_start + table_len;
+    let columns = partition_key.join(", ");
+    let relation = format!("token({}) > ? AND token({}) <= ?", columns, columns);
+    let (at, ranged) = match unquoted_keyword(statement, from, &["where"]) {
+        Some((_, end)) => (
+            end,
+            format!("{} {} AND{}", &statement[..end], relation, &statement[end..]),
+        ),
+        None => {
+            let at = unquoted_keyword(statement, from, &WHERE_FOLLOWERS).map_or(statement.len(), |(start, _)| start);
+            let (head, tail) = statement.split_at(at);
+            let separator = if tail.is_empty() { "" } else { " " };
+            (
+                at,
+                format!("{} WHERE {}{}{}", head.trim_end(), relation, separator, tail),
+            )
+        }
+    };
+    ensure!(
+        marker_count(&statement[..at]) == Some(0),
+        "The bind markers precede the WHERE clause of: {}",
+        statement
+    );
+    Ok(ranged)
+}
+
+/// Set the Scylla server-side timeout of the SELECT, INSERT, UPDATE or DELETE statement, which overrides the
+/// timeout of the cluster configuration, by adding the `TIMEOUT` option to its USING clause, or by adding the
+/// `USING TIMEOUT` clause. The timeout is a literal, so the bind markers of the statement are left as they are.
+///
+/// Fails if the statement isn't one of those statements, if it already has a timeout, or if the timeout isn't a
+/// whole number of milliseconds.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::with_using_timeout;
+/// use std::time::Duration;
+///
+/// assert_eq!(
+///     with_using_timeout("SELECT * FROM shop.orders WHERE customer = ?", Duration::from_millis(500))?,
+///     "SELECT * FROM shop.orders WHERE customer = ? USING TIMEOUT 500ms"
+/// );
+/// assert_eq!(
+///     with_using_timeout("UPDATE shop.orders USING TTL ? SET total = ? WHERE id = ?", Duration::from_secs(2))?,
+///     "UPDATE shop.orders USING TTL ? AND TIMEOUT 2000ms SET total = ? WHERE id = ?"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn with_using_timeout(statement: &str, timeout: Duration) -> anyhow::Result<String> {
+    ensure!(
+        timeout.subsec_nanos().is_multiple_of(1_000_000),
+        "The timeout {:?} isn't a whole number of milliseconds",
+        timeout
+    );
+    let statement = statement.trim().trim_end_matches(';').trim_end();
+    let (table_start, table_len) = table_span(statement)
+        .ok_or_else(|| anyhow!("Not a SELECT, INSERT, UPDATE or DELETE statement: {}", statement))?;
+    let from = table_start + table_len;
+    // the USING clause of an UPDATE or a DELETE statement precedes its SET or WHERE clause, while it's the last
+    // clause of a SELECT or an INSERT statement
+    let followers: &[&str] = match StatementKind::of(statement) {
+        Some(StatementKind::Update) => &["set"],
+        Some(StatementKind::Delete) => &["where"],
+        _ => &[],
+    };
+    let at = unquoted_keyword(statement, from, followers).map_or(statement.len(), |(start, _)| start);
+    let (head, tail) = statement.split_at(at);
+    let head = head.trim_end();
+    let timeout = format_duration(timeout);
+    let separator = if tail.is_empty() { "" } else { " " };
+    match unquoted_keyword(head, from, &["using"]) {
+        Some((_, end)) => {
+            ensure!(
+                unquoted_keyword(head, end, &["timeout"]).is_none(),
+                "The statement already has a timeout: {}",
+                statement
+            );
+            Ok(format!("{} AND TIMEOUT {}{}{}", head, timeout, separator, tail))
+        }
+        None => Ok(format!("{} USING TIMEOUT {}{}{}", head, timeout, separator, tail)),
+    }
+}
+
+/// The keywords which follow the WHERE clause of a SELECT statement
+const WHERE_FOLLOWERS: [&str; 7] = ["group", "order", "per", "limit", "allow", "bypass", "using"];
+
+/// Find the first unquoted keyword, from the offset, and get its start and end offsets
+fn unquoted_keyword(statement: &str, from: usize, keywords: &[&str]) -> Option<(usize, usize)> {
+    let bytes = statement.as_bytes();
+    let (mut quote, mut i) = (None, from);
+    while i < bytes.len() {
+        let c = bytes[i];
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == b'\'' || c == b'"' => quote = Some(c),
+            None if c.is_ascii_alphabetic() => {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                if keywords
+                    .iter()
+                    .any(|keyword| statement[start..i].eq_ignore_ascii_case(keyword))
+                {
+                    return Some((start, i));
+                }
+                continue;
+            }
+            None => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Replace the string, number, blob and uuid literals of the statement with bind markers, ie the shape shared by
+/// the statements which only differ by their inlined values, which is the statement to prepare instead.
+///
+/// The statement is returned as it is if it has no literals, or an unterminated quote.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::parameterize;
+///
+/// assert_eq!(
+///     parameterize("SELECT * FROM shop.orders WHERE id = 42 AND customer = 'o''neil' LIMIT 10"),
+///     "SELECT * FROM shop.orders WHERE id = ? AND customer = ? LIMIT ?"
+/// );
+/// assert_eq!(parameterize("SELECT * FROM shop.t1 WHERE k = ?"), "SELECT * FROM shop.t1 WHERE k = ?");
+/// ```
+pub fn parameterize(statement: &str) -> Cow<'_, str> {
+    let bytes = statement.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let mut parameterized = String::with_capacity(statement.len());
+    let (mut i, mut replaced) = (0, false);
+    while i < bytes.len() {
+        let c = bytes[i];
+        let previous = parameterized.trim_end().as_bytes().last().copied();
+        let literal_end = if c == b'\'' || c == b'"' {
+            // find the closing quote, where a doubled quote is an escaped one
+            let mut end = i + 1;
+            loop {
+                match bytes.get(end) {
+                    Some(b) if *b == c && bytes.get(end + 1) == Some(&c) => end += 2,
+                    Some(b) if *b == c => break,
+                    Some(_) => end += 1,
+                    None => return Cow::Borrowed(statement),
+                }
+            }
+            if c == b'"' {
+                // a quoted identifier
+                parameterized.push_str(&statement[i..=end]);
+                i = end + 1;
+                continue;
+            }
+            end + 1
+        } else if statement[i..].starts_with("$$") {
+            match statement[i + 2..].find("$$") {
+                Some(len) => i + 2 + len + 2,
+                None => return Cow::Borrowed(statement),
+            }
+        } else if is_uuid(&bytes[i..]) && !bytes.get(i + 36).copied().is_some_and(is_ident) {
+            i + 36
+        } else if c.is_ascii_digit()
+            || c == b'-'
+                && matches!(bytes.get(i + 1), Some(b) if b.is_ascii_digit())
+                && !matches!(previous, Some(b) if is_ident(b) || b == b')' || b == b']' || b == b'"' || b == b'?')
+        {
+            let mut end = i + 1;
+            if c == b'0' && matches!(bytes.get(end), Some(b'x' | b'X')) {
+                end += 1;
+                while end < bytes.len() && bytes[end].is_ascii_hexdigit() {
+                    end += 1;
+                }
+            } else {
+                while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b'.') {
+                    end += 1;
+                }
+                if matches!(bytes.get(end), Some(b'e' | b'E')) {
+                    end += 1;
+                    if matches!(bytes.get(end), Some(b'+' | b'-')) {
+                        end += 1;
+                    }
+                    while end < bytes.len() && bytes[end].is_ascii_digit() {
+                        end += 1;
+                    }
+                }
+            }
+            end
+        } else {
+            // an identifier or a keyword, along with its digits, or any other char
+            let mut end = i + statement[i..].chars().next().map_or(1, char::len_utf8);
+            if is_ident(c) {
+                while end < bytes.len() && is_ident(bytes[end]) {
+                    end += 1;
+                }
+            }
+            parameterized.push_str(&statement[i..end]);
+            i = end;
+            continue;
+        };
+        parameterized.push('?');
+        replaced = true;
+        i = literal_end;
+    }
+    if replaced {
+        Cow::Owned(parameterized)
+    } else {
+        Cow::Borrowed(statement)
+    }
+}
+
+/// Check if the bytes start with an uuid, ie `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`
+fn is_uuid(bytes: &[u8]) -> bool {
+    bytes.len() >= 36
+        && bytes[..36].iter().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => *b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+/// Get the keyspace which qualifies the table of the SELECT, INSERT, UPDATE or DELETE statement, or None if the
+/// table isn't qualified (or it's not one of these statements).
+pub(crate) fn table_keyspace(statement: &str) -> Option<Name> {
+    let (table_start, table_len) = table_span(statement)?;
+    if statement[table_start + table_len..].trim_start().starts_with('.') {
+        Name::parse(&statement[table_start..table_start + table_len]).ok()
+    } else {
+        None
+    }
+}
+
+/// Get the offset and the length of the first name after the FROM, INTO or UPDATE keyword of the SELECT, INSERT,
+/// UPDATE or DELETE statement, ie its keyspace or its unqualified table.
+fn table_span(statement: &str) -> Option<(usize, usize)> {
+    let keyword = match StatementKind::of(statement)? {
+        StatementKind::Select | StatementKind::Delete => "from",
+        StatementKind::Insert => "into",
+        StatementKind::Update => "update",
+        StatementKind::Batch => return None,
+    };
+    // find the end of the first unquoted keyword, which is followed by the table
+    let bytes = statement.as_bytes();
+    let (mut quote, mut i, mut keyword_end) = (None, 0, None);
+    while i < bytes.len() {
+        let c = bytes[i];
+        match quote {
+            // a doubled quote is an escaped one, which closes and reopens the quote
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == b'\'' || c == b'"' => quote = Some(c),
+            None if c.is_ascii_alphabetic() => {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                if statement[start..i].eq_ignore_ascii_case(keyword) {
+                    keyword_end = Some(i);
+                    break;
+                }
+                continue;
+            }
+            None => {}
+        }
+        i += 1;
+    }
+    let table = statement[keyword_end?..].trim_start();
+    let table_start = statement.len() - table.len();
+    let table_len = if let Some(quoted) = table.strip_prefix('"') {
+        quoted.replace("\"\"", "__").find('"')? + 2
+    } else {
+        table
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(table.len())
+    };
+    if table_len == 0 {
+        return None;
+    }
+    Some((table_start, table_len))
+}
+
+/// The object kinds which support CREATE .. IF NOT EXISTS and DROP .. IF EXISTS
+const DDL_KINDS: [&str; 9] = [
+    "keyspace",
+    "table",
+    "columnfamily",
+    "index",
+    "type",
+    "function",
+    "aggregate",
+    "role",
+    "user",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualified_tables() {
+        assert_eq!(
+            qualify_table("insert into \"Orders\" (id) values (?)", "Shop"),
+            "insert into \"Shop\".\"Orders\" (id) values (?)"
+        );
+        assert_eq!(
+            qualify_table("DELETE v FROM orders WHERE id = 'from'", "shop"),
+            "DELETE v FROM shop.orders WHERE id = 'from'"
+        );
+        assert_eq!(
+            qualify_table("SELECT \"from\" FROM \"a\"\"b\" . c", "shop"),
+            "SELECT \"from\" FROM \"a\"\"b\" . c"
+        );
+        assert_eq!(
+            qualify_table("SELECT from_date FROM t", "shop"),
+            "SELECT from_date FROM shop.t"
+        );
+        assert_eq!(
+            qualify_table("BEGIN BATCH INSERT INTO t (k) VALUES (1) APPLY BATCH", "shop"),
+            "BEGIN BATCH INSERT INTO t (k) VALUES (1) APPLY BATCH"
+        );
+        assert_eq!(qualify_table("TRUNCATE t", "shop"), "TRUNCATE t");
+    }
+
+    #[test]
+    fn token_indexes() {
+        // reordered relations, with a marker before the partition key relations
+        let statement = "SELECT * FROM ks.t WHERE c > ? AND b = ? AND a = ? LIMIT ?";
+        assert_eq!(statement.token_indexes(&["a", "b"]), Some(vec![2, 1]));
+        // markers of the SET clause are counted, and the IF clause is ignored
+        let statement = "UPDATE ks.t USING TTL ? SET v = ? WHERE \"Key\" = ? IF v = ?";
+        assert_eq!(statement.token_indexes(&["\"Key\""]), Some(vec![2]));
+        assert_eq!(statement.token_indexes(&["key"]), None);
+        // escaped quotes within the quoted identifiers
+        let statement = "SELECT * FROM ks.t WHERE \"my\"\"Key\" = ?";
+        assert_eq!(statement.token_indexes(&["\"my\"\"Key\""]), Some(vec![0]));
+        // named markers and case insensitive columns
+        let statement = "DELETE FROM ks.t WHERE Part = :part AND ck = :ck";
+        assert_eq!(statement.token_indexes(&["part"]), Some(vec![0]));
+        // IN relations are not routable, while their markers are counted
+        let statement = "SELECT * FROM ks.t WHERE a IN (?, ?) AND b = ?";
+        assert_eq!(statement.token_indexes(&["a"]), None);
+        assert_eq!(statement.token_indexes(&["b"]), Some(vec![2]));
+        assert_eq!("SELECT * FROM ks.t WHERE a IN ?".token_indexes(&["a"]), None);
+        // token relations are not routable, while their markers are counted
+        let statement = "SELECT * FROM ks.t WHERE token(a, b) > ? AND token(a, b) <= ? AND c = ?";
+        assert_eq!(statement.token_indexes(&["a", "b"]), None);
+        assert_eq!(statement.token_indexes(&["c"]), Some(vec![2]));
+        // literals and markers within string literals
+        let statement = "SELECT * FROM ks.t WHERE a = 'x?' AND b = ? AND c = -1";
+        assert_eq!(statement.token_indexes(&["a"]), None);
+        assert_eq!(statement.token_indexes(&["b"]), Some(vec![0]));
+        // insert columns, with a literal value and a function call
+        let statement = "INSERT INTO ks.t (b, v, a, ts) VALUES (?, 'x', ?, toTimestamp(now())) USING TTL ?";
+        assert_eq!(statement.token_indexes(&["a", "b"]), Some(vec![1, 0]));
+        assert_eq!(statement.token_indexes(&["v"]), None);
+    }
+
+    #[test]
+    fn bind_markers() {
+        let summary = |statement: &str| -> Vec<(usize, MarkerClause, Option<String>, Option<String>)> {
+            statement
+                .bind_markers()
+                .unwrap()
+                .into_iter()
+                .map(|marker| (marker.index, marker.clause, marker.column, marker.operator))
+                .collect()
+        };
+        let column = |column: &str| Some(column.to_string());
+        let operator = |operator: &str| Some(operator.to_string());
+        let statement = "SELECT * FROM ks.t WHERE token(a) > ? AND b IN (?, 1, ?) AND c >= ? AND m CONTAINS KEY ? \
+                         PER PARTITION LIMIT ? LIMIT ?";
+        assert_eq!(
+            summary(statement),
+            vec![
+                (0, MarkerClause::Where, None, None),
+                (1, MarkerClause::Where, column("b"), operator("IN")),
+                (2, MarkerClause::Where, column("b"), operator("IN")),
+                (3, MarkerClause::Where, column("c"), operator(">=")),
+                (4, MarkerClause::Where, column("m"), operator("CONTAINS KEY")),
+                (5, MarkerClause::Limit, None, None),
+                (6, MarkerClause::Limit, None, None),
+            ]
+        );
+        let statement = "UPDATE ks.t USING TIMESTAMP ? AND TTL ? SET c = c + ?, l[?] = ?, \"V\" = ? \
+                         WHERE k = ? IF v != ?";
+        assert_eq!(
+            summary(statement),
+            vec![
+                (0, MarkerClause::Timestamp, None, None),
+                (1, MarkerClause::Ttl, None, None),
+                (2, MarkerClause::Set, column("c"), None),
+                (3, MarkerClause::Set, None, None),
+                (4, MarkerClause::Set, None, None),
+                (5, MarkerClause::Set, column("V"), None),
+                (6, MarkerClause::Where, column("k"), operator("=")),
+                (7, MarkerClause::If, column("v"), operator("!=")),
+            ]
+        );
+        // the markers of the inner statements of a batch follow each other
+        let statement = "BEGIN BATCH INSERT INTO ks.t (k, v) VALUES (:k, ?) USING TTL ?; \
+                         DELETE FROM ks.t WHERE k = :K APPLY BATCH";
+        let markers = statement.bind_markers().unwrap();
+        assert_eq!(
+            markers
+                .iter()
+                .map(|marker| (marker.index, marker.name.as_deref(), marker.column.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, Some("k"), Some("k")),
+                (1, None, Some("v")),
+                (2, None, None),
+                (3, Some("k"), Some("k"))
+            ]
+        );
+        assert_eq!(markers[2].clause, MarkerClause::Ttl);
+        assert_eq!("SELECT * FROM ks.t WHERE k = '?".bind_markers(), None);
+    }
+
+    #[test]
+    fn token_ranges() {
+        assert_eq!(
+            with_token_range("select * from \"Where\" where \"k\" = 'where ?'", &["\"Key\""]).unwrap(),
+            "select * from \"Where\" where token(\"Key\") > ? AND token(\"Key\") <= ? AND \"k\" = 'where ?'"
+        );
+        assert_eq!(
+            with_token_range("SELECT * FROM ks.t", &["k"]).unwrap(),
+            "SELECT * FROM ks.t WHERE token(k) > ? AND token(k) <= ?"
+        );
+        assert_eq!(
+            with_token_range("SELECT * FROM ks.t PER PARTITION LIMIT ?", &["k"]).unwrap(),
+            "SELECT * FROM ks.t WHERE token(k) > ? AND token(k) <= ? PER PARTITION LIMIT ?"
+        );
+        let ranged = with_token_range("SELECT * FROM ks.t WHERE c = ?", &["k"]).unwrap();
+        // the range is bound before the other values
+        assert_eq!(ranged.token_indexes(&["c"]), Some(vec![2]));
+        assert!(with_token_range("SELECT * FROM ks.t", &[]).is_err());
+        assert!(with_token_range("SELECT ttl(v), ? FROM ks.t WHERE c = ?", &["k"]).is_err());
+        assert!(with_token_range("DELETE FROM ks.t WHERE k = ?", &["k"]).is_err());
+    }
+
+    #[test]
+    fn using_timeouts() {
+        let timeout = Duration::from_millis(1500);
+        assert_eq!(
+            with_using_timeout("INSERT INTO ks.t (k, v) VALUES (?, ?) IF NOT EXISTS;", timeout).unwrap(),
+            "INSERT INTO ks.t (k, v) VALUES (?, ?) IF NOT EXISTS USING TIMEOUT 1500ms"
+        );
+        assert_eq!(
+            with_using_timeout("INSERT INTO ks.t (k, v) VALUES (?, ?) USING TTL 60", timeout).unwrap(),
+            "INSERT INTO ks.t (k, v) VALUES (?, ?) USING TTL 60 AND TIMEOUT 1500ms"
+        );
+        assert_eq!(
+            with_using_timeout("DELETE v FROM ks.t WHERE k = ? IF v = 'using'", timeout).unwrap(),
+            "DELETE v FROM ks.t USING TIMEOUT 1500ms WHERE k = ? IF v = 'using'"
+        );
+        assert_eq!(
+            with_using_timeout("UPDATE ks.t SET \"timeout\" = ? WHERE k = ?", timeout).unwrap(),
+            "UPDATE ks.t USING TIMEOUT 1500ms SET \"timeout\" = ? WHERE k = ?"
+        );
+        let statement = with_using_timeout("SELECT * FROM ks.t WHERE k = ? BYPASS CACHE", timeout).unwrap();
+        assert_eq!(
+            statement,
+            "SELECT * FROM ks.t WHERE k = ? BYPASS CACHE USING TIMEOUT 1500ms"
+        );
+        assert!(with_using_timeout(&statement, timeout).is_err());
+        assert!(with_using_timeout("SELECT * FROM ks.t", Duration::from_micros(1500)).is_err());
+        assert!(with_using_timeout("BEGIN BATCH DELETE FROM ks.t WHERE k = ? APPLY BATCH", timeout).is_err());
+        let markers = "SELECT * FROM ks.t WHERE k = ? USING TIMEOUT ?".bind_markers().unwrap();
+        assert_eq!(markers[1].clause, MarkerClause::Timeout);
+    }
+
+    #[test]
+    fn statement_ids() {
+        let statement = "SELECT * FROM ks.t WHERE k = ?";
+        assert_eq!(Md5::statement_id(statement), <[u8; 16]>::from(md5::compute(statement)));
+        assert_eq!(Fnv64::statement_id(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(Fnv64::statement_id("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(
+            Fnv64::statement_id(statement),
+            Fnv64::statement_id("SELECT * FROM ks.t")
+        );
+    }
+
+    #[test]
+    fn idempotent_ddl() {
+        use super::idempotent_ddl;
+        assert_eq!(
+            idempotent_ddl("CREATE KEYSPACE ks WITH replication = {}"),
+            "CREATE KEYSPACE IF NOT EXISTS ks WITH replication = {}"
+        );
+        assert_eq!(
+            idempotent_ddl("  create custom index on ks.t (v) using 'x'"),
+            "  create custom index IF NOT EXISTS on ks.t (v) using 'x'"
+        );
+        assert_eq!(idempotent_ddl("DROP TYPE ks.udt"), "DROP TYPE IF EXISTS ks.udt");
+        assert_eq!(
+            idempotent_ddl("CREATE USER u WITH PASSWORD 'p'"),
+            "CREATE USER IF NOT EXISTS u WITH PASSWORD 'p'"
+        );
+        // unchanged statements
+        let unchanged = [
+            "CREATE TABLE IF NOT EXISTS ks.t (k int PRIMARY KEY)",
+            "DROP TABLE if exists ks.t",
+            "CREATE OR REPLACE FUNCTION ks.f ...",
+            "ALTER TABLE ks.t ADD v int",
+            "SELECT * FROM ks.t",
+        ];
+        for statement in unchanged.iter() {
+            assert!(matches!(idempotent_ddl(statement), Cow::Borrowed(s) if s == *statement));
+        }
+    }
+
+    #[test]
+    fn parameterized_statements() {
+        use super::parameterize;
+        assert_eq!(
+            parameterize("INSERT INTO ks.t (k, c, v, b) VALUES (-1, 2.5e-3, 'a''b', 0xCAFE) USING TTL 60"),
+            "INSERT INTO ks.t (k, c, v, b) VALUES (?, ?, ?, ?) USING TTL ?"
+        );
+        assert_eq!(
+            parameterize("UPDATE ks.t SET v = v - 1, s = $$x'y$$ WHERE id = e7cd5752-bc0d-4157-a80f-7523add8dbcd"),
+            "UPDATE ks.t SET v = v - ?, s = ? WHERE id = ?"
+        );
+        assert_eq!(
+            parameterize("SELECT \"a 1\", t2.x FROM ks.t2 WHERE k IN (1,2) AND c = :c"),
+            "SELECT \"a 1\", t2.x FROM ks.t2 WHERE k IN (?,?) AND c = :c"
+        );
+        let statement = "SELECT * FROM ks.t WHERE k = ? AND v = 'unterminated";
+        assert!(matches!(parameterize(statement), Cow::Borrowed(s) if s == statement));
+    }
+
+    #[test]
+    fn conditional_statements() {
+        assert!(is_conditional("INSERT INTO ks.t (k, v) VALUES (?, ?) IF NOT EXISTS"));
+        assert!(is_conditional("update ks.t SET v = ? WHERE k = ? if v = ?"));
+        assert!(is_conditional("DELETE FROM ks.t WHERE k = ? IF EXISTS"));
+        assert!(!is_conditional("UPDATE ks.t SET v = 'if' WHERE k = ?"));
+        assert!(!is_conditional("SELECT \"if\" FROM ks.t"));
+        assert!(!is_conditional("CREATE TABLE IF NOT EXISTS ks.t (k int PRIMARY KEY)"));
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the bounded cache of the prepared statements of a session, which evicts the statements
+//! according to an eviction policy, except the pinned ones.
+
+use super::statement::{Fnv64, StatementIdAlgorithm};
+use std::collections::HashMap;
+
+/// The default capacity of the statement cache
+pub const DEFAULT_STATEMENT_CACHE_CAPACITY: usize = 4096;
+
+/// The eviction policy of the statement cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Evict the least recently used statement
+    #[default]
+    Lru,
+    /// Evict the statement with the lowest Greedy-Dual-Size-Frequency priority, which favors the frequently
+    /// used and short statements, while aging the ones which aren't used anymore
+    Gdsf,
+}
+
+/// The metrics of the statement cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatementCacheStats {
+    /// The number of cached statements
+    pub len: usize,
+    /// The number of pinned statements
+    pub pinned: usize,
+    /// The number of lookups which found the statement
+    pub hits: u64,
+    /// The number of lookups which didn't find the statement
+    pub misses: u64,
+    /// The number of evicted statements
+    pub evictions: u64,
+}
+
+struct CachedStatement {
+    statement: String,
+    pinned: bool,
+    last_used: u64,
+    frequency: u64,
+    priority: f64,
+}
+
+/// The bounded cache of the prepared statements, keyed by their prepared id
+pub(crate) struct StatementCache {
+    capacity: usize,
+    policy: EvictionPolicy,
+    entries: HashMap<[u8; 16], CachedStatement>,
+    ids: HashMap<u64, [u8; 16]>,
+    clock: u64,
+    /// The GDSF inflation value, ie the priority of the last evicted statement
+    inflation: f64,
+    stats: StatementCacheStats,
+}
+
+impl StatementCache {
+    /// Create a new statement cache, which holds up to `capacity` unpinned statements
+    pub(crate) fn new(capacity: usize, policy: EvictionPolicy) -> Self {
+        Self {
+            capacity,
+            policy,
+            entries: HashMap::new(),
+            ids: HashMap::new(),
+            clock: 0,
+            inflation: 0.0,
+            stats: StatementCacheStats::default(),
+        }
+    }
+
+    /// Cache the prepared statement, or pin it if it's already cached and `pinned` is set. Returns the evicted
+    /// statements, which never include the inserted one.
+    pub(crate) fn insert(&mut self, id: [u8; 16], statement: &str, pinned: bool) -> Vec<String> {
+        self.clock += 1;
+        let (clock, inflation) = (self.clock, self.inflation);
+        let entry = self.entries.entry(id).or_insert_with(|| CachedStatement {
+            statement: statement.to_string(),
+            pinned,
+            last_used: clock,
+            frequency: 0,
+            priority: inflation,
+        });
+        entry.pinned |= pinned;
+        Self::touch(entry, clock, inflation);
+        self.ids.insert(Fnv64::statement_id(statement), id);
+        self.evict(&id)
+    }
+
+    /// Get the statement of the prepared id
+    pub(crate) fn get(&mut self, id: &[u8; 16]) -> Option<String> {
+        self.clock += 1;
+        let (clock, inflation) = (self.clock, self.inflation);
+        match self.entries.get_mut(id) {
+            Some(entry) => {
+                self.stats.hits += 1;
+                Self::touch(entry, clock, inflation);
+                Some(entry.statement.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Get the prepared id of the statement
+    pub(crate) fn id(&mut self, statement: &str) -> Option<[u8; 16]> {
+        let id = self.ids.get(&Fnv64::statement_id(statement)).copied();
+        match id {
+            Some(id) => self.get(&id).map(|_| id),
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Pin or unpin the cached statement, returns false if it isn't cached
+    pub(crate) fn set_pinned(&mut self, statement: &str, pinned: bool) -> bool {
+        let id = self.ids.get(&Fnv64::statement_id(statement)).copied();
+        let entry = id.and_then(|id| self.entries.get_mut(&id));
+        match entry {
+            Some(entry) => {
+                entry.pinned = pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the metrics of the cache
+    pub(crate) fn stats(&self) -> StatementCacheStats {
+        StatementCacheStats {
+            len: self.entries.len(),
+            pinned: self.entries.values().filter(|entry| entry.pinned).count(),
+            ..self.stats
+        }
+    }
+
+    fn touch(entry: &mut CachedStatement, clock: u64, inflation: f64) {
+        entry.last_used = clock;
+        entry.frequency += 1;
+        entry.priority = inflation + entry.frequency as f64 / entry.statement.len().max(1) as f64;
+    }
+
+    fn evict(&mut self, inserted: &[u8; 16]) -> Vec<String> {
+        let mut evicted = Vec::new();
+        loop {
+            let unpinned = self.entries.values().filter(|entry| !entry.pinned).count();
+            if unpinned <= self.capacity {
+                break;
+            }
+            let candidates = self
+                .entries
+                .iter()
+                .filter(|(id, entry)| !entry.pinned && *id != inserted);
+            let victim = match self.policy {
+                EvictionPolicy::Lru => candidates.min_by_key(|(_, entry)| entry.last_used).map(|(id, _)| *id),
+                EvictionPolicy::Gdsf => candidates
+                    .min_by(|(_, a), (_, b)| a.priority.total_cmp(&b.priority))
+                    .map(|(id, _)| *id),
+            };
+            let entry = match victim.and_then(|id| self.entries.remove(&id)) {
+                Some(entry) => entry,
+                None => break,
+            };
+            if self.policy == EvictionPolicy::Gdsf {
+                self.inflation = entry.priority;
+            }
+            self.ids.remove(&Fnv64::statement_id(&entry.statement));
+            self.stats.evictions += 1;
+            evicted.push(entry.statement);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u8) -> [u8; 16] {
+        [n; 16]
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let mut cache = StatementCache::new(2, EvictionPolicy::Lru);
+        assert!(cache.insert(id(1), "SELECT a FROM t", false).is_empty());
+        assert!(cache.insert(id(2), "SELECT b FROM t", false).is_empty());
+        // the first statement is now the most recently used one
+        assert_eq!(cache.id("SELECT a FROM t"), Some(id(1)));
+        assert_eq!(cache.insert(id(3), "SELECT c FROM t", false), vec!["SELECT b FROM t"]);
+        assert_eq!(cache.get(&id(2)), None);
+        assert_eq!(cache.id("SELECT b FROM t"), None);
+        assert_eq!(cache.get(&id(1)).as_deref(), Some("SELECT a FROM t"));
+        let stats = cache.stats();
+        assert_eq!((stats.len, stats.hits, stats.misses, stats.evictions), (2, 2, 2, 1));
+    }
+
+    #[test]
+    fn gdsf_eviction() {
+        let mut cache = StatementCache::new(2, EvictionPolicy::Gdsf);
+        cache.insert(id(1), "SELECT a FROM t", false);
+        cache.insert(id(2), "SELECT b FROM t", false);
+        // the frequently used statement outlives the more recently used one
+        for _ in 0..3 {
+            cache.get(&id(1));
+        }
+        cache.get(&id(2));
+        assert_eq!(cache.insert(id(3), "SELECT c FROM t", false), vec!["SELECT b FROM t"]);
+        // the short statement outlives the long one with the same frequency
+        let long = "SELECT a, b, c, d, e, f, g, h FROM t";
+        let mut cache = StatementCache::new(2, EvictionPolicy::Gdsf);
+        cache.insert(id(1), "SELECT a FROM t", false);
+        cache.insert(id(2), long, false);
+        assert_eq!(cache.insert(id(3), "SELECT c FROM t", false), vec![long]);
+    }
+
+    #[test]
+    fn pinned_statements() {
+        let mut cache = StatementCache::new(1, EvictionPolicy::Lru);
+        cache.insert(id(1), "SELECT a FROM t", true);
+        cache.insert(id(2), "SELECT b FROM t", false);
+        assert_eq!(cache.insert(id(3), "SELECT c FROM t", false), vec!["SELECT b FROM t"]);
+        assert!(cache.get(&id(1)).is_some());
+        assert_eq!(cache.stats().pinned, 1);
+        assert!(cache.set_pinned("SELECT a FROM t", false));
+        assert!(!cache.set_pinned("SELECT b FROM t", true));
+        assert_eq!(
+            cache.insert(id(4), "SELECT d FROM t", false),
+            vec!["SELECT c FROM t", "SELECT a FROM t"]
+        );
+        assert_eq!(cache.stats().len, 1);
+    }
+}
@@ -0,0 +1,385 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the pretty-printer of the schema statements, which lays their canonical form out over
+//! several lines once it exceeds the line width, ie to turn a schema dump into a human readable one.
+
+use super::{
+    schema::{ClusteringOrder, CreateKeyspaceStatement, CreateTableStatement, KeyspaceSchema},
+    scylla_ext::{SchemaObject, ScyllaOptions, ScyllaStatement},
+};
+use crate::cql::{parse_script, Name, ScriptItem};
+
+/// The casing of the keywords
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeywordCase {
+    /// `CREATE TABLE`
+    Upper,
+    /// `create table`
+    Lower,
+}
+
+/// The options of the pretty-printer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// The number of spaces of an indentation level
+    pub indent: usize,
+    /// The width above which the statements are split over several lines
+    pub max_width: usize,
+    /// The casing of the keywords
+    pub keyword_case: KeywordCase,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent: 4,
+            max_width: 80,
+            keyword_case: KeywordCase::Upper,
+        }
+    }
+}
+
+impl FormatOptions {
+    fn keywords(&self, keywords: &str) -> String {
+        match self.keyword_case {
+            KeywordCase::Upper => keywords.to_string(),
+            KeywordCase::Lower => keywords.to_ascii_lowercase(),
+        }
+    }
+    fn indentation(&self) -> String {
+        " ".repeat(self.indent)
+    }
+    fn fits(&self, text: &str) -> bool {
+        text.lines().all(|line| line.chars().count() <= self.max_width)
+    }
+    /// Append the `WITH` clause to the head after the separator, with all its options on a line if it fits,
+    /// otherwise with an option per line
+    fn with(&self, head: String, separator: &str, options: Vec<String>) -> String {
+        if options.is_empty() {
+            return head;
+        }
+        let (with, and) = (self.keywords("WITH"), self.keywords("AND"));
+        let single = format!("{}{}{} {}", head, separator, with, options.join(&format!(" {} ", and)));
+        if self.fits(&single) {
+            return single;
+        }
+        let and = format!("\n{}{} ", self.indentation(), and);
+        format!("{}{}{} {}", head, separator, with, options.join(&and))
+    }
+    /// Get the option entries, whose leading keywords are cased
+    fn entries(&self, options: &ScyllaOptions) -> Vec<String> {
+        let mut options = options.clone();
+        let clustering_order = options
+            .clustering_order
+            .take()
+            .map(|order| format!("{} {}", self.keywords("CLUSTERING ORDER BY"), order));
+        clustering_order.into_iter().chain(options.entries()).collect()
+    }
+}
+
+/// The pretty-printing of a statement
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::statements::{
+///     format::{FormatOptions, KeywordCase, PrettyPrint},
+///     schema::CreateTableStatement,
+/// };
+///
+/// let table = CreateTableStatement::parse(
+///     "CREATE TABLE shop.orders (customer text, id bigint, total double, PRIMARY KEY (customer, id)) \
+///      WITH CLUSTERING ORDER BY (id DESC) AND gc_grace_seconds = 3600",
+/// )?;
+/// let options = FormatOptions {
+///     indent: 2,
+///     max_width: 40,
+///     keyword_case: KeywordCase::Lower,
+/// };
+/// assert_eq!(
+///     table.pretty(&options),
+///     "create table shop.orders (
+///   customer text,
+///   id bigint,
+///   total double,
+///   primary key ((customer), id)
+/// ) with clustering order by (id desc)
+///   and gc_grace_seconds = 3600"
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub trait PrettyPrint {
+    /// Format the statement with the options, without its terminating semicolon
+    fn pretty(&self, options: &FormatOptions) -> String;
+}
+
+impl PrettyPrint for CreateKeyspaceStatement {
+    fn pretty(&self, options: &FormatOptions) -> String {
+        let mut head = options.keywords("CREATE KEYSPACE ");
+        if self.if_not_exists {
+            head.push_str(&options.keywords("IF NOT EXISTS "));
+        }
+        head.push_str(&self.name.to_string());
+        let mut entries = vec![format!("replication = {}", self.replication)];
+        if let Some(durable_writes) = self.durable_writes {
+            entries.push(format!("durable_writes = {}", durable_writes));
+        }
+        options.with(head, " ", entries)
+    }
+}
+
+impl PrettyPrint for CreateTableStatement {
+    fn pretty(&self, options: &FormatOptions) -> String {
+        let mut head = options.keywords("CREATE TABLE ");
+        if self.if_not_exists {
+            head.push_str(&options.keywords("IF NOT EXISTS "));
+        }
+        head.push_str(&qualified(&self.keyspace, &self.name));
+        let mut definitions: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| {
+                let mut definition = format!("{} {}", column.name, column.cql_type);
+                if column.is_static {
+                    definition.push_str(&options.keywords(" STATIC"));
+                }
+                definition
+            })
+            .collect();
+        let partition_key: Vec<String> = self.partition_key.iter().map(ToString::to_string).collect();
+        let mut primary_key = vec![format!("({})", partition_key.join(", "))];
+        primary_key.extend(self.clustering_key.iter().map(|(key, _)| key.to_string()));
+        definitions.push(format!(
+            "{} ({})",
+            options.keywords("PRIMARY KEY"),
+            primary_key.join(", ")
+        ));
+        let mut table_options = self.options.clone();
+        if self
+            .clustering_key
+            .iter()
+            .any(|(_, order)| *order == ClusteringOrder::Desc)
+        {
+            let orders: Vec<String> = self
+                .clustering_key
+                .iter()
+                .map(|(key, order)| match order {
+                    ClusteringOrder::Asc => format!("{} {}", key, options.keywords("ASC")),
+                    ClusteringOrder::Desc => format!("{} {}", key, options.keywords("DESC")),
+                })
+                .collect();
+            table_options.clustering_order = Some(format!("({})", orders.join(", ")));
+        }
+        let entries = options.entries(&table_options);
+        let single = options.with(format!("{} ({})", head, definitions.join(", ")), " ", entries.clone());
+        if options.fits(&single) {
+            return single;
+        }
+        let separator = format!(",\n{}", options.indentation());
+        let head = format!(
+            "{} (\n{}{}\n)",
+            head,
+            options.indentation(),
+            definitions.join(&separator)
+        );
+        options.with(head, " ", entries)
+    }
+}
+
+impl PrettyPrint for ScyllaStatement {
+    fn pretty(&self, options: &FormatOptions) -> String {
+        match self {
+            ScyllaStatement::CreateTable { .. } => match CreateTableStatement::parse(&self.to_string()) {
+                Ok(table) => table.pretty(options),
+                Err(_) => self.to_string(),
+            },
+            ScyllaStatement::CreateMaterializedView {
+                if_not_exists,
+                keyspace,
+                name,
+                select,
+                per_partition_limit,
+                options: view_options,
+            } => {
+                let mut head = options.keywords("CREATE MATERIALIZED VIEW ");
+                if *if_not_exists {
+                    head.push_str(&options.keywords("IF NOT EXISTS "));
+                }
+                head.push_str(&qualified(keyspace, name));
+                let mut definition = vec![select.clone()];
+                if let Some(limit) = per_partition_limit {
+                    definition.push(format!("{} {}", options.keywords("PER PARTITION LIMIT"), limit));
+                }
+                let as_ = options.keywords("AS");
+                let entries = options.entries(view_options);
+                let single = options.with(
+                    format!("{} {} {}", head, as_, definition.join(" ")),
+                    " ",
+                    entries.clone(),
+                );
+                if options.fits(&single) {
+                    return single;
+                }
+                let separator = format!("\n{}", options.indentation());
+                let head = format!("{} {}{}{}", head, as_, separator, definition.join(&separator));
+                // the options start on their own line, as the view definition isn't enclosed
+                options.with(head, "\n", entries)
+            }
+            ScyllaStatement::Alter {
+                object,
+                keyspace,
+                name,
+                options: altered,
+            } => {
+                let object = match object {
+                    SchemaObject::Table => "ALTER TABLE",
+                    SchemaObject::MaterializedView => "ALTER MATERIALIZED VIEW",
+                };
+                let head = format!("{} {}", options.keywords(object), qualified(keyspace, name));
+                options.with(head, " ", options.entries(altered))
+            }
+            ScyllaStatement::PruneMaterializedView {
+                keyspace,
+                name,
+                clauses,
+            } => {
+                let mut statement = format!(
+                    "{} {}",
+                    options.keywords("PRUNE MATERIALIZED VIEW"),
+                    qualified(keyspace, name)
+                );
+                if !clauses.is_empty() {
+                    statement.push(' ');
+                    statement.push_str(clauses);
+                }
+                statement
+            }
+        }
+    }
+}
+
+impl PrettyPrint for KeyspaceSchema {
+    fn pretty(&self, options: &FormatOptions) -> String {
+        let mut statements = vec![self.keyspace.pretty(options)];
+        statements.extend(self.tables.iter().map(|table| table.pretty(options)));
+        statements.join(";\n\n") + ";"
+    }
+}
+
+fn qualified(keyspace: &Option<Name>, name: &Name) -> String {
+    match keyspace {
+        Some(keyspace) => format!("{}.{}", keyspace, name),
+        None => name.to_string(),
+    }
+}
+
+/// Format the statement, without its terminating semicolon. The keyspace creation statements and the statements of
+/// `ScyllaStatement` are pretty-printed, while the other statements are returned as they are.
+pub fn format_statement(statement: &str, options: &FormatOptions) -> anyhow::Result<String> {
+    let statement = statement.trim().trim_end_matches(';').trim_end();
+    let words: Vec<String> = statement
+        .split_whitespace()
+        .take(2)
+        .map(str::to_ascii_lowercase)
+        .collect();
+    match words.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["create", "keyspace"] => return Ok(CreateKeyspaceStatement::parse(statement)?.pretty(options)),
+        ["create", "table"] | ["create", "columnfamily"] => {
+            return Ok(CreateTableStatement::parse(statement)?.pretty(options))
+        }
+        _ => {}
+    }
+    Ok(match ScyllaStatement::parse(statement)? {
+        Some(parsed) => parsed.pretty(options),
+        None => statement.to_string(),
+    })
+}
+
+/// Format the script, ie a schema dump, where the statements are separated by blank lines (see `format_statement`)
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::statements::format::{format_script, FormatOptions};
+///
+/// let script = "create keyspace shop with replication = {'class': 'SimpleStrategy', 'replication_factor': 1};
+///     create table shop.tags (name text primary key);";
+/// assert_eq!(
+///     format_script(script, &FormatOptions::default())?,
+///     "CREATE KEYSPACE shop WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1};
+///
+/// CREATE TABLE shop.tags (name text, PRIMARY KEY ((name)));
+/// "
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn format_script(script: &str, options: &FormatOptions) -> anyhow::Result<String> {
+    let mut statements = Vec::new();
+    for item in parse_script(script)? {
+        statements.push(match item {
+            ScriptItem::Statement(statement) => format!("{};\n", format_statement(&statement, options)?),
+            ScriptItem::Directive(directive) => format!("{};\n", directive),
+        });
+    }
+    Ok(statements.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pretty_statements() {
+        let options = FormatOptions {
+            max_width: 50,
+            ..Default::default()
+        };
+        let keyspace = "CREATE KEYSPACE IF NOT EXISTS shop WITH replication = {'class': 'NetworkTopologyStrategy', \
+                        'dc1': 3} AND durable_writes = true";
+        assert_eq!(
+            format_statement(keyspace, &options).unwrap(),
+            "CREATE KEYSPACE IF NOT EXISTS shop WITH replication = {'class': 'NetworkTopologyStrategy', 'dc1': 3}
+    AND durable_writes = true"
+        );
+        let table = "create table shop.orders (customer text, id bigint, note text static, primary key ((customer), \
+                     id)) with cdc = {'enabled': true} and comment = 'orders';";
+        assert_eq!(
+            format_statement(table, &options).unwrap(),
+            "CREATE TABLE shop.orders (
+    customer text,
+    id bigint,
+    note text STATIC,
+    PRIMARY KEY ((customer), id)
+) WITH cdc = {'enabled': true, 'preimage': false, 'postimage': false, 'delta': 'full', 'ttl': 86400}
+    AND comment = 'orders'"
+        );
+        let view = "CREATE MATERIALIZED VIEW shop.by_total AS SELECT * FROM shop.orders WHERE total IS NOT NULL AND \
+                    customer IS NOT NULL AND id IS NOT NULL PRIMARY KEY (total, customer, id) WITH comment = 'x'";
+        assert_eq!(
+            format_statement(view, &options).unwrap(),
+            "CREATE MATERIALIZED VIEW shop.by_total AS
+    SELECT * FROM shop.orders WHERE total IS NOT NULL AND customer IS NOT NULL AND id IS NOT NULL PRIMARY KEY \
+             (total, customer, id)
+WITH comment = 'x'"
+        );
+        let alter = "ALTER TABLE shop.orders WITH gc_grace_seconds = 3600";
+        assert_eq!(format_statement(alter, &options).unwrap(), alter);
+        // the short statements remain on a single line, and the other statements are kept as they are
+        let options = FormatOptions {
+            keyword_case: KeywordCase::Lower,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_statement("CREATE TABLE t (k int PRIMARY KEY, v int STATIC)", &options).unwrap(),
+            "create table t (k int, v int static, primary key ((k)))"
+        );
+        assert_eq!(
+            format_statement("SELECT * FROM shop.orders ;", &options).unwrap(),
+            "SELECT * FROM shop.orders"
+        );
+        assert!(format_statement("CREATE TABLE t (k int)", &options).is_err());
+        assert_eq!(
+            format_script("CONSISTENCY QUORUM\nPAGING OFF\nDROP TABLE t;", &options).unwrap(),
+            "CONSISTENCY QUORUM;\n\nPAGING OFF;\n\nDROP TABLE t;\n"
+        );
+    }
+}
@@ -0,0 +1,12 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the parsing of the schema statements, ie to read the schemas of the existing clusters,
+//! along with the Scylla administration statements, their pretty-printing and their visitors.
+
+pub mod format;
+pub mod schema;
+pub mod schema_docs;
+pub mod scylla_ext;
+pub mod service_level;
+pub mod visit;
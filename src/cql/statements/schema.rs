@@ -0,0 +1,1397 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the parsing of the keyspace and table creation statements, and the diff of the schemas
+//! they declare, ie to compare the live schema of a cluster with the expected one, along with the `ALTER TABLE`
+//! statements and their builder, and the secondary index and materialized view creation statements.
+
+use super::scylla_ext::{find_keywords, top_level, Cursor, ScyllaOptions, ScyllaStatement};
+use crate::cql::{parse_options, Constant, CqlType, Name, Term};
+use anyhow::{anyhow, bail, ensure};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+
+/// The prefix of the built-in replication strategy classes, as they are stored in the schema
+const STRATEGY_PREFIX: &str = "org.apache.cassandra.locator.";
+
+/// `CREATE KEYSPACE [IF NOT EXISTS] ks WITH replication = {..} [AND durable_writes = ..]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateKeyspaceStatement {
+    /// Whether the statement holds IF NOT EXISTS
+    pub if_not_exists: bool,
+    /// The keyspace name
+    pub name: Name,
+    /// The replication map, ie `{'class': 'SimpleStrategy', 'replication_factor': 1}`
+    pub replication: Term,
+    /// The `durable_writes` option
+    pub durable_writes: Option<bool>,
+}
+
+impl CreateKeyspaceStatement {
+    /// Parse the statement
+    pub fn parse(statement: &str) -> anyhow::Result<Self> {
+        let statement = statement.trim().trim_end_matches(';');
+        let mut cursor = Cursor::new(statement);
+        ensure!(
+            cursor.keywords(&["create", "keyspace"]),
+            "Not a CREATE KEYSPACE statement: {}",
+            statement
+        );
+        let if_not_exists = cursor.keywords(&["if", "not", "exists"]);
+        let name = cursor.name()?;
+        ensure!(cursor.keywords(&["with"]), "Expected WITH in: {}", statement);
+        let mut replication = None;
+        let mut durable_writes = None;
+        for (option, term) in parse_options(cursor.rest())? {
+            match option.as_str() {
+                "replication" => replication = Some(term),
+                "durable_writes" => durable_writes = Some(term.evaluate()?),
+                _ => bail!("Unknown keyspace option: {}", option),
+            }
+        }
+        let replication = replication.ok_or_else(|| anyhow!("Missing replication in: {}", statement))?;
+        replication.as_map()?;
+        Ok(Self {
+            if_not_exists,
+            name,
+            replication,
+            durable_writes,
+        })
+    }
+    /// Get the replication map as strings, where the built-in strategy classes are unqualified, ie to compare
+    /// the declared replication with the one stored in the schema.
+    pub fn replication(&self) -> anyhow::Result<BTreeMap<String, String>> {
+        let mut replication = BTreeMap::new();
+        for (key, value) in self.replication.as_map()? {
+            let value = match value.fold()? {
+                Constant::String(value) => value,
+                value => value.to_string(),
+            };
+            let value = value.strip_prefix(STRATEGY_PREFIX).map(str::to_string).unwrap_or(value);
+            replication.insert(key.evaluate()?, value);
+        }
+        Ok(replication)
+    }
+}
+
+impl Display for CreateKeyspaceStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "CREATE KEYSPACE {}{} WITH replication = {}",
+            if self.if_not_exists { "IF NOT EXISTS " } else { "" },
+            self.name,
+            self.replication
+        )?;
+        if let Some(durable_writes) = self.durable_writes {
+            write!(f, " AND durable_writes = {}", durable_writes)?;
+        }
+        Ok(())
+    }
+}
+
+/// The definition of a table column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDefinition {
+    /// The column name
+    pub name: Name,
+    /// The CQL type of the column, normalized (see `normalize_type`)
+    pub cql_type: String,
+    /// Whether the column is static
+    pub is_static: bool,
+}
+
+impl ColumnDefinition {
+    /// Parse the `name type [STATIC]` column definition
+    pub fn parse(definition: &str) -> anyhow::Result<Self> {
+        let mut cursor = Cursor::new(definition);
+        let name = cursor.name()?;
+        let mut cql_type = cursor.rest();
+        let is_static = match find_keywords(cql_type, &["static"]) {
+            Some((start, end)) => {
+                ensure!(
+                    cql_type[end..].trim().is_empty(),
+                    "Invalid column definition: {}",
+                    definition
+                );
+                cql_type = &cql_type[..start];
+                true
+            }
+            None => false,
+        };
+        ensure!(!cql_type.trim().is_empty(), "Missing column type: {}", definition);
+        Ok(Self {
+            name,
+            cql_type: normalize_type(cql_type),
+            is_static,
+        })
+    }
+}
+
+impl Display for ColumnDefinition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.cql_type)?;
+        if self.is_static {
+            f.write_str(" STATIC")?;
+        }
+        Ok(())
+    }
+}
+
+/// The order of a clustering column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusteringOrder {
+    /// Ascending, which is the default one
+    Asc,
+    /// Descending
+    Desc,
+}
+
+/// `CREATE TABLE [IF NOT EXISTS] [ks.]table (..) [WITH ..]`, whose clustering order is part of its clustering
+/// key rather than its options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTableStatement {
+    /// Whether the statement holds IF NOT EXISTS
+    pub if_not_exists: bool,
+    /// The keyspace of the table, if it's qualified
+    pub keyspace: Option<Name>,
+    /// The table name
+    pub name: Name,
+    /// The columns, in their declaration order
+    pub columns: Vec<ColumnDefinition>,
+    /// The partition key columns
+    pub partition_key: Vec<Name>,
+    /// The clustering columns along with their order
+    pub clustering_key: Vec<(Name, ClusteringOrder)>,
+    /// The table options, without the clustering order
+    pub options: ScyllaOptions,
+}
+
+impl CreateTableStatement {
+    /// Parse the statement
+    pub fn parse(statement: &str) -> anyhow::Result<Self> {
+        let (if_not_exists, keyspace, name, definitions, mut options) = match ScyllaStatement::parse(statement)? {
+            Some(ScyllaStatement::CreateTable {
+                if_not_exists,
+                keyspace,
+                name,
+                definitions,
+                options,
+            }) => (if_not_exists, keyspace, name, definitions, options),
+            _ => bail!("Not a CREATE TABLE statement: {}", statement),
+        };
+        let mut columns = Vec::new();
+        let mut primary_key = None;
+        for definition in split_commas(&definitions) {
+            let mut cursor = Cursor::new(definition);
+            if cursor.keywords(&["primary", "key"]) {
+                ensure!(primary_key.is_none(), "Multiple primary keys in: {}", statement);
+                primary_key = Some(cursor.parenthesized()?.to_string());
+                continue;
+            }
+            let column = match find_keywords(definition, &["primary", "key"]) {
+                Some((start, end)) => {
+                    ensure!(
+                        primary_key.is_none() && definition[end..].trim().is_empty(),
+                        "Invalid column definition: {}",
+                        definition
+                    );
+                    let column = ColumnDefinition::parse(&definition[..start])?;
+                    primary_key = Some(column.name.to_string());
+                    column
+                }
+                None => ColumnDefinition::parse(definition)?,
+            };
+            columns.push(column);
+        }
+        let primary_key = primary_key.ok_or_else(|| anyhow!("Missing primary key in: {}", statement))?;
+        let (partition_key, mut clustering_key) = parse_primary_key(&primary_key, statement)?;
+        apply_clustering_order(&mut options, &mut clustering_key)?;
+        for key in partition_key.iter().chain(clustering_key.iter().map(|(key, _)| key)) {
+            ensure!(
+                columns.iter().any(|column| column.name == *key),
+                "Unknown primary key column: {}",
+                key
+            );
+        }
+        Ok(Self {
+            if_not_exists,
+            keyspace,
+            name,
+            columns,
+            partition_key,
+            clustering_key,
+            options,
+        })
+    }
+    /// Get the column by its name
+    pub fn column(&self, name: &Name) -> Option<&ColumnDefinition> {
+        self.columns.iter().find(|column| column.name == *name)
+    }
+    /// Get the statement which creates the secondary index of the regular column, if it doesn't exist, so the
+    /// index matches the table definition
+    pub fn index_statement(&self, column: &Name) -> anyhow::Result<CreateIndexStatement> {
+        ensure!(
+            self.column(column).is_some(),
+            "Unknown column {} of the {} table",
+            column,
+            self.name
+        );
+        ensure!(
+            !self.partition_key.contains(column) && !self.clustering_key.iter().any(|(key, _)| key == column),
+            "The primary key column {} of the {} table can't have a secondary index",
+            column,
+            self.name
+        );
+        Ok(CreateIndexStatement {
+            if_not_exists: true,
+            name: None,
+            keyspace: self.keyspace.clone(),
+            table: self.name.clone(),
+            column: column.clone(),
+            kind: IndexKind::Column,
+        })
+    }
+}
+
+impl Display for CreateTableStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CREATE TABLE ")?;
+        if self.if_not_exists {
+            f.write_str("IF NOT EXISTS ")?;
+        }
+        if let Some(keyspace) = &self.keyspace {
+            write!(f, "{}.", keyspace)?;
+        }
+        let columns: Vec<String> = self.columns.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "{} ({}, PRIMARY KEY ({}))",
+            self.name,
+            columns.join(", "),
+            primary_key_definition(&self.partition_key, &self.clustering_key)
+        )?;
+        let options = with_clustering_order(&self.options, &self.clustering_key);
+        if !options.is_empty() {
+            write!(f, " WITH {}", options)?;
+        }
+        Ok(())
+    }
+}
+
+/// The alteration of an `ALTER TABLE` statement, which holds a single kind of alteration
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlterTableInstruction {
+    /// `ADD column type [STATIC]` or `ADD (column type [STATIC], ..)`
+    Add(Vec<ColumnDefinition>),
+    /// `DROP column` or `DROP (column, ..)`
+    Drop(Vec<Name>),
+    /// `RENAME column TO column [AND column TO column ..]`
+    Rename(Vec<(Name, Name)>),
+    /// `WITH option = value [AND ..]`
+    With(ScyllaOptions),
+}
+
+/// `ALTER TABLE [ks.]table ADD ..|DROP ..|RENAME ..|WITH ..`, whose options alterations are also parsed by
+/// `ScyllaStatement::parse`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlterTableStatement {
+    /// The keyspace of the table, if it's qualified
+    pub keyspace: Option<Name>,
+    /// The table name
+    pub name: Name,
+    /// The alteration
+    pub instruction: AlterTableInstruction,
+}
+
+impl AlterTableStatement {
+    /// Parse the statement
+    pub fn parse(statement: &str) -> anyhow::Result<Self> {
+        let statement = statement.trim().trim_end_matches(';');
+        let mut cursor = Cursor::new(statement);
+        ensure!(
+            cursor.keywords(&["alter", "table"]) || cursor.keywords(&["alter", "columnfamily"]),
+            "Not an ALTER TABLE statement: {}",
+            statement
+        );
+        let (keyspace, name) = cursor.qualified_name()?;
+        let instruction = if cursor.keywords(&["add"]) {
+            AlterTableInstruction::Add(
+                column_list(cursor.rest())?
+                    .into_iter()
+                    .map(ColumnDefinition::parse)
+                    .collect::<anyhow::Result<_>>()?,
+            )
+        } else if cursor.keywords(&["drop"]) {
+            AlterTableInstruction::Drop(
+                column_list(cursor.rest())?
+                    .into_iter()
+                    .map(Name::parse)
+                    .collect::<anyhow::Result<_>>()?,
+            )
+        } else if cursor.keywords(&["rename"]) {
+            let mut renames = Vec::new();
+            loop {
+                let from = cursor.name()?;
+                ensure!(cursor.keywords(&["to"]), "Expected TO in: {}", statement);
+                renames.push((from, cursor.name()?));
+                if !cursor.keywords(&["and"]) {
+                    break;
+                }
+            }
+            let rest = cursor.rest();
+            ensure!(rest.is_empty(), "Unexpected chars: {}", rest);
+            AlterTableInstruction::Rename(renames)
+        } else if cursor.keywords(&["with"]) {
+            AlterTableInstruction::With(ScyllaOptions::parse(cursor.rest())?)
+        } else {
+            bail!("Unsupported table alteration: {}", statement)
+        };
+        Ok(Self {
+            keyspace,
+            name,
+            instruction,
+        })
+    }
+}
+
+impl Display for AlterTableStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn list<T: Display>(items: &[T]) -> String {
+            match items {
+                [item] => item.to_string(),
+                items => {
+                    let items: Vec<String> = items.iter().map(ToString::to_string).collect();
+                    format!("({})", items.join(", "))
+                }
+            }
+        }
+        f.write_str("ALTER TABLE ")?;
+        if let Some(keyspace) = &self.keyspace {
+            write!(f, "{}.", keyspace)?;
+        }
+        write!(f, "{} ", self.name)?;
+        match &self.instruction {
+            AlterTableInstruction::Add(columns) => write!(f, "ADD {}", list(columns)),
+            AlterTableInstruction::Drop(columns) => write!(f, "DROP {}", list(columns)),
+            AlterTableInstruction::Rename(renames) => {
+                let renames: Vec<String> = renames.iter().map(|(from, to)| format!("{} TO {}", from, to)).collect();
+                write!(f, "RENAME {}", renames.join(" AND "))
+            }
+            AlterTableInstruction::With(options) => write!(f, "WITH {}", options),
+        }
+    }
+}
+
+/// The builder of the `ALTER TABLE` statements, whose names are quoted only when they need to be (see `Name::auto`).
+///
+/// A statement holds a single kind of alteration, so the build fails if the added, dropped or renamed columns and
+/// the options are mixed.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::{statements::schema::AlterTableBuilder, CqlType};
+///
+/// let statement = AlterTableBuilder::new("orders")
+///     .keyspace("shop")
+///     .add_column("note", CqlType::Text)
+///     .add_column("tags", CqlType::Set(Box::new(CqlType::Text)))
+///     .build()?;
+/// assert_eq!(statement.to_string(), "ALTER TABLE shop.orders ADD (note text, tags set<text>)");
+/// assert!(AlterTableBuilder::new("orders")
+///     .add_column("note", CqlType::Text)
+///     .drop_column("total")
+///     .build()
+///     .is_err());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct AlterTableBuilder {
+    keyspace: Option<Name>,
+    name: Name,
+    added: Vec<ColumnDefinition>,
+    dropped: Vec<Name>,
+    renamed: Vec<(Name, Name)>,
+    options: Option<ScyllaOptions>,
+}
+
+impl AlterTableBuilder {
+    /// Create the builder of the table alteration
+    pub fn new(table: &str) -> Self {
+        Self {
+            keyspace: None,
+            name: Name::auto(table),
+            added: Vec::new(),
+            dropped: Vec::new(),
+            renamed: Vec::new(),
+            options: None,
+        }
+    }
+    /// Qualify the table with its keyspace
+    pub fn keyspace(mut self, keyspace: &str) -> Self {
+        self.keyspace = Some(Name::auto(keyspace));
+        self
+    }
+    /// Add a regular column
+    pub fn add_column(self, name: &str, cql_type: CqlType) -> Self {
+        self.add(name, cql_type, false)
+    }
+    /// Add a static column
+    pub fn add_static_column(self, name: &str, cql_type: CqlType) -> Self {
+        self.add(name, cql_type, true)
+    }
+    fn add(mut self, name: &str, cql_type: CqlType, is_static: bool) -> Self {
+        self.added.push(ColumnDefinition {
+            name: Name::auto(name),
+            cql_type: normalize_type(&cql_type.to_string()),
+            is_static,
+        });
+        self
+    }
+    /// Drop a column
+    pub fn drop_column(mut self, name: &str) -> Self {
+        self.dropped.push(Name::auto(name));
+        self
+    }
+    /// Rename a primary key column
+    pub fn rename(mut self, from: &str, to: &str) -> Self {
+        self.renamed.push((Name::auto(from), Name::auto(to)));
+        self
+    }
+    /// Set the table options, which replace the previously set ones
+    pub fn with_options(mut self, options: ScyllaOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+    /// Build the statement, which fails if it holds none or several kinds of alterations
+    pub fn build(self) -> anyhow::Result<AlterTableStatement> {
+        let mut instructions = Vec::new();
+        if !self.added.is_empty() {
+            instructions.push(AlterTableInstruction::Add(self.added));
+        }
+        if !self.dropped.is_empty() {
+            instructions.push(AlterTableInstruction::Drop(self.dropped));
+        }
+        if !self.renamed.is_empty() {
+            instructions.push(AlterTableInstruction::Rename(self.renamed));
+        }
+        if let Some(options) = self.options {
+            ensure!(
+                !options.is_empty(),
+                "Empty options of the {} table alteration",
+                self.name
+            );
+            instructions.push(AlterTableInstruction::With(options));
+        }
+        ensure!(
+            instructions.len() == 1,
+            "The {} table alteration must hold a single kind of alteration, found {}",
+            self.name,
+            instructions.len()
+        );
+        Ok(AlterTableStatement {
+            keyspace: self.keyspace,
+            name: self.name,
+            instruction: instructions.remove(0),
+        })
+    }
+}
+
+/// The indexed values of a secondary index column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexKind {
+    /// The values of a regular column, or the values of a collection column, ie `(column)`
+    Column,
+    /// The keys of a map column, ie `(KEYS(column))`
+    Keys,
+    /// The values of a collection column, ie `(VALUES(column))`
+    Values,
+    /// The entries of a map column, ie `(ENTRIES(column))`
+    Entries,
+    /// The whole value of a frozen collection column, ie `(FULL(column))`
+    Full,
+}
+
+/// `CREATE INDEX [IF NOT EXISTS] [name] ON [ks.]table (target)`, whose target is a global secondary index column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateIndexStatement {
+    /// Whether the statement holds IF NOT EXISTS
+    pub if_not_exists: bool,
+    /// The index name, or None for the `table_column_idx` default one
+    pub name: Option<Name>,
+    /// The keyspace of the table, if it's qualified
+    pub keyspace: Option<Name>,
+    /// The table name
+    pub table: Name,
+    /// The indexed column
+    pub column: Name,
+    /// The indexed values of the column
+    pub kind: IndexKind,
+}
+
+impl CreateIndexStatement {
+    /// Parse the statement
+    pub fn parse(statement: &str) -> anyhow::Result<Self> {
+        let statement = statement.trim().trim_end_matches(';');
+        let mut cursor = Cursor::new(statement);
+        ensure!(
+            cursor.keywords(&["create", "index"]),
+            "Not a CREATE INDEX statement: {}",
+            statement
+        );
+        let if_not_exists = cursor.keywords(&["if", "not", "exists"]);
+        let name = if cursor.keywords(&["on"]) {
+            None
+        } else {
+            let name = cursor.name()?;
+            ensure!(cursor.keywords(&["on"]), "Expected ON in: {}", statement);
+            Some(name)
+        };
+        let (keyspace, table) = cursor.qualified_name()?;
+        let target = cursor.parenthesized()?.trim();
+        let rest = cursor.rest();
+        ensure!(rest.is_empty(), "Unsupported index options: {}", rest);
+        ensure!(
+            !target.starts_with('('),
+            "The local secondary indexes aren't supported: {}",
+            statement
+        );
+        let mut kind = IndexKind::Column;
+        let mut column = target;
+        for (keyword, function) in [
+            ("keys", IndexKind::Keys),
+            ("values", IndexKind::Values),
+            ("entries", IndexKind::Entries),
+            ("full", IndexKind::Full),
+        ] {
+            let mut cursor = Cursor::new(target);
+            if cursor.keywords(&[keyword]) {
+                if let Ok(inner) = cursor.parenthesized() {
+                    let rest = cursor.rest();
+                    ensure!(rest.is_empty(), "Unexpected chars: {}", rest);
+                    kind = function;
+                    column = inner.trim();
+                    break;
+                }
+            }
+        }
+        Ok(Self {
+            if_not_exists,
+            name,
+            keyspace,
+            table,
+            column: Name::parse(column)?,
+            kind,
+        })
+    }
+}
+
+impl Display for CreateIndexStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CREATE INDEX ")?;
+        if self.if_not_exists {
+            f.write_str("IF NOT EXISTS ")?;
+        }
+        if let Some(name) = &self.name {
+            write!(f, "{} ", name)?;
+        }
+        f.write_str("ON ")?;
+        if let Some(keyspace) = &self.keyspace {
+            write!(f, "{}.", keyspace)?;
+        }
+        match self.kind {
+            IndexKind::Column => write!(f, "{} ({})", self.table, self.column),
+            IndexKind::Keys => write!(f, "{} (KEYS({}))", self.table, self.column),
+            IndexKind::Values => write!(f, "{} (VALUES({}))", self.table, self.column),
+            IndexKind::Entries => write!(f, "{} (ENTRIES({}))", self.table, self.column),
+            IndexKind::Full => write!(f, "{} (FULL({}))", self.table, self.column),
+        }
+    }
+}
+
+/// `CREATE MATERIALIZED VIEW [IF NOT EXISTS] [ks.]view AS SELECT .. FROM [ks.]table WHERE .. PRIMARY KEY (..)
+/// [PER PARTITION LIMIT n] [WITH ..]`, whose clustering order is part of its clustering key rather than its options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateMaterializedViewStatement {
+    /// Whether the statement holds IF NOT EXISTS
+    pub if_not_exists: bool,
+    /// The keyspace of the view, if it's qualified
+    pub keyspace: Option<Name>,
+    /// The view name
+    pub name: Name,
+    /// The selected columns of the base table, or none for all of them
+    pub columns: Vec<Name>,
+    /// The keyspace of the base table, if it's qualified
+    pub base_keyspace: Option<Name>,
+    /// The base table name
+    pub base_table: Name,
+    /// The relations which filter the base rows, as they are, ie `k IS NOT NULL AND c IS NOT NULL`
+    pub where_clause: String,
+    /// The partition key columns of the view
+    pub partition_key: Vec<Name>,
+    /// The clustering columns of the view along with their order
+    pub clustering_key: Vec<(Name, ClusteringOrder)>,
+    /// The `PER PARTITION LIMIT` of the view
+    pub per_partition_limit: Option<u32>,
+    /// The view options, without the clustering order
+    pub options: ScyllaOptions,
+}
+
+impl CreateMaterializedViewStatement {
+    /// Parse the statement
+    pub fn parse(statement: &str) -> anyhow::Result<Self> {
+        let (if_not_exists, keyspace, name, select, per_partition_limit, mut options) =
+            match ScyllaStatement::parse(statement)? {
+                Some(ScyllaStatement::CreateMaterializedView {
+                    if_not_exists,
+                    keyspace,
+                    name,
+                    select,
+                    per_partition_limit,
+                    options,
+                }) => (if_not_exists, keyspace, name, select, per_partition_limit, options),
+                _ => bail!("Not a CREATE MATERIALIZED VIEW statement: {}", statement),
+            };
+        let mut cursor = Cursor::new(&select);
+        ensure!(cursor.keywords(&["select"]), "Expected SELECT in: {}", statement);
+        let columns = match cursor.until_keyword("from").trim() {
+            "*" => Vec::new(),
+            columns => split_commas(columns)
+                .into_iter()
+                .map(Name::parse)
+                .collect::<anyhow::Result<_>>()?,
+        };
+        ensure!(cursor.keywords(&["from"]), "Expected FROM in: {}", statement);
+        let (base_keyspace, base_table) = cursor.qualified_name()?;
+        ensure!(cursor.keywords(&["where"]), "Expected WHERE in: {}", statement);
+        let where_clause = cursor.until_keyword("primary").trim().to_string();
+        ensure!(
+            cursor.keywords(&["primary", "key"]),
+            "Expected PRIMARY KEY in: {}",
+            statement
+        );
+        let (partition_key, mut clustering_key) = parse_primary_key(cursor.parenthesized()?, statement)?;
+        let rest = cursor.rest();
+        ensure!(rest.is_empty(), "Unexpected chars: {}", rest);
+        apply_clustering_order(&mut options, &mut clustering_key)?;
+        for key in clustering_key.iter().map(|(key, _)| key).chain(partition_key.iter()) {
+            ensure!(
+                columns.is_empty() || columns.contains(key),
+                "The primary key column {} isn't selected by: {}",
+                key,
+                statement
+            );
+        }
+        Ok(Self {
+            if_not_exists,
+            keyspace,
+            name,
+            columns,
+            base_keyspace,
+            base_table,
+            where_clause,
+            partition_key,
+            clustering_key,
+            per_partition_limit,
+            options,
+        })
+    }
+}
+
+impl Display for CreateMaterializedViewStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CREATE MATERIALIZED VIEW ")?;
+        if self.if_not_exists {
+            f.write_str("IF NOT EXISTS ")?;
+        }
+        if let Some(keyspace) = &self.keyspace {
+            write!(f, "{}.", keyspace)?;
+        }
+        write!(f, "{} AS SELECT ", self.name)?;
+        if self.columns.is_empty() {
+            f.write_str("*")?;
+        } else {
+            let columns: Vec<String> = self.columns.iter().map(ToString::to_string).collect();
+            f.write_str(&columns.join(", "))?;
+        }
+        f.write_str(" FROM ")?;
+        if let Some(keyspace) = &self.base_keyspace {
+            write!(f, "{}.", keyspace)?;
+        }
+        write!(
+            f,
+            "{} WHERE {} PRIMARY KEY ({})",
+            self.base_table,
+            self.where_clause,
+            primary_key_definition(&self.partition_key, &self.clustering_key)
+        )?;
+        if let Some(limit) = self.per_partition_limit {
+            write!(f, " PER PARTITION LIMIT {}", limit)?;
+        }
+        let options = with_clustering_order(&self.options, &self.clustering_key);
+        if !options.is_empty() {
+            write!(f, " WITH {}", options)?;
+        }
+        Ok(())
+    }
+}
+
+/// The schema of a keyspace, as declared by its creation statements, ie the ones of `Model::schema_statements`,
+/// or as it's stored in the `system_schema` keyspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyspaceSchema {
+    /// The keyspace creation statement
+    pub keyspace: CreateKeyspaceStatement,
+    /// The table creation statements
+    pub tables: Vec<CreateTableStatement>,
+}
+
+impl KeyspaceSchema {
+    /// Parse the schema out of the keyspace and table creation statements. The other statements, ie
+    /// `CREATE TYPE`, are ignored.
+    pub fn from_statements<S: AsRef<str>>(statements: &[S]) -> anyhow::Result<Self> {
+        let mut keyspace = None;
+        let mut tables = Vec::new();
+        for statement in statements {
+            let statement = statement.as_ref();
+            let mut cursor = Cursor::new(statement);
+            if cursor.keywords(&["create", "keyspace"]) {
+                ensure!(keyspace.is_none(), "Multiple keyspaces in the schema statements");
+                keyspace = Some(CreateKeyspaceStatement::parse(statement)?);
+            } else if cursor.keywords(&["create", "table"]) || cursor.keywords(&["create", "columnfamily"]) {
+                tables.push(CreateTableStatement::parse(statement)?);
+            }
+        }
+        let keyspace = keyspace.ok_or_else(|| anyhow!("No keyspace in the schema statements"))?;
+        Ok(Self { keyspace, tables })
+    }
+    /// Get the table by its name
+    pub fn table(&self, name: &Name) -> Option<&CreateTableStatement> {
+        self.tables.iter().find(|table| table.name == *name)
+    }
+    /// Get the changes from the expected schema (self) to the actual one, which is None if the keyspace
+    /// doesn't exist. Only the standard options which are set by the expected tables are compared, ie the
+    /// Scylla specific ones aren't.
+    pub fn diff(&self, actual: Option<&KeyspaceSchema>) -> anyhow::Result<Vec<SchemaChange>> {
+        let actual = match actual {
+            Some(actual) => actual,
+            None => return Ok(vec![SchemaChange::MissingKeyspace(self.keyspace.name.clone())]),
+        };
+        let mut changes = Vec::new();
+        let (expected_replication, actual_replication) = (self.keyspace.replication()?, actual.keyspace.replication()?);
+        if expected_replication != actual_replication {
+            changes.push(SchemaChange::Replication {
+                expected: expected_replication,
+                actual: actual_replication,
+            });
+        }
+        for expected in self.tables.iter() {
+            let table = expected.name.clone();
+            let actual = match actual.table(&table) {
+                Some(actual) => actual,
+                None => {
+                    changes.push(SchemaChange::MissingTable(table));
+                    continue;
+                }
+            };
+            for column in expected.columns.iter() {
+                match actual.column(&column.name) {
+                    None => changes.push(SchemaChange::MissingColumn {
+                        table: table.clone(),
+                        column: column.clone(),
+                    }),
+                    Some(actual) if actual != column => changes.push(SchemaChange::ColumnType {
+                        table: table.clone(),
+                        expected: column.clone(),
+                        actual: actual.clone(),
+                    }),
+                    Some(_) => {}
+                }
+            }
+            for column in actual
+                .columns
+                .iter()
+                .filter(|column| expected.column(&column.name).is_none())
+            {
+                changes.push(SchemaChange::UnexpectedColumn {
+                    table: table.clone(),
+                    column: column.clone(),
+                });
+            }
+            if expected.partition_key != actual.partition_key || expected.clustering_key != actual.clustering_key {
+                changes.push(SchemaChange::PrimaryKey(table.clone()));
+            }
+            for (option, expected) in expected.options.options.iter() {
+                let actual = actual.options.get(option);
+                let matches = match actual {
+                    Some(actual) => option_matches(expected, actual)?,
+                    None => false,
+                };
+                if !matches {
+                    changes.push(SchemaChange::TableOption {
+                        table: table.clone(),
+                        option: option.clone(),
+                        expected: expected.clone(),
+                        actual: actual.cloned(),
+                    });
+                }
+            }
+        }
+        for table in actual.tables.iter().filter(|table| self.table(&table.name).is_none()) {
+            changes.push(SchemaChange::UnexpectedTable(table.name.clone()));
+        }
+        Ok(changes)
+    }
+    /// Get the `ALTER TABLE` statements which apply the changes from the expected schema (self), ie which add the
+    /// missing columns and set the changed options. The other changes can't be applied without dropping data, so
+    /// they're left to the user.
+    pub fn alter_statements(&self, changes: &[SchemaChange]) -> Vec<String> {
+        let keyspace = &self.keyspace.name;
+        let mut statements = Vec::new();
+        let mut options: Vec<(&Name, Vec<String>)> = Vec::new();
+        for change in changes {
+            match change {
+                SchemaChange::MissingColumn { table, column } => {
+                    statements.push(format!("ALTER TABLE {}.{} ADD {}", keyspace, table, column))
+                }
+                SchemaChange::TableOption {
+                    table,
+                    option,
+                    expected,
+                    ..
+                } => {
+                    let option = format!("{} = {}", Name::auto(option), expected);
+                    match options.iter_mut().find(|(t, _)| *t == table) {
+                        Some((_, table_options)) => table_options.push(option),
+                        None => options.push((table, vec![option])),
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (table, table_options) in options {
+            statements.push(format!(
+                "ALTER TABLE {}.{} WITH {}",
+                keyspace,
+                table,
+                table_options.join(" AND ")
+            ));
+        }
+        statements
+    }
+}
+
+/// Check if the actual option value matches the expected one, ie the expected map entries are set in the actual
+/// map, where the built-in classes match their qualified names.
+fn option_matches(expected: &Term, actual: &Term) -> anyhow::Result<bool> {
+    fn value(term: &Term) -> anyhow::Result<String> {
+        Ok(match term.fold()? {
+            Constant::String(value) => value,
+            value => value.to_string(),
+        })
+    }
+    if let Term::Map(expected) = expected {
+        for (key, expected) in expected {
+            let actual = match actual.get(&key.evaluate::<String>()?)? {
+                Some(actual) => value(actual)?,
+                None => return Ok(false),
+            };
+            let expected = value(expected)?;
+            if actual != expected && !actual.ends_with(&format!(".{}", expected)) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    } else {
+        Ok(value(expected)? == value(actual)?)
+    }
+}
+
+/// Get the name of the user defined type created by the `CREATE TYPE` statement, or None if it's another statement
+pub fn created_type(statement: &str) -> anyhow::Result<Option<Name>> {
+    let mut cursor = Cursor::new(statement);
+    if !cursor.keywords(&["create", "type"]) {
+        return Ok(None);
+    }
+    cursor.keywords(&["if", "not", "exists"]);
+    let (_, name) = cursor.qualified_name()?;
+    Ok(Some(name))
+}
+
+/// A difference between the expected schema of a keyspace and its actual one
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaChange {
+    /// The keyspace doesn't exist
+    MissingKeyspace(Name),
+    /// The keyspace replication differs
+    Replication {
+        /// The expected replication
+        expected: BTreeMap<String, String>,
+        /// The actual replication
+        actual: BTreeMap<String, String>,
+    },
+    /// The table doesn't exist
+    MissingTable(Name),
+    /// The table exists but isn't expected
+    UnexpectedTable(Name),
+    /// The column doesn't exist
+    MissingColumn {
+        /// The table of the column
+        table: Name,
+        /// The expected column
+        column: ColumnDefinition,
+    },
+    /// The column exists but isn't expected
+    UnexpectedColumn {
+        /// The table of the column
+        table: Name,
+        /// The actual column
+        column: ColumnDefinition,
+    },
+    /// The column type (or its static modifier) differs
+    ColumnType {
+        /// The table of the column
+        table: Name,
+        /// The expected column
+        expected: ColumnDefinition,
+        /// The actual column
+        actual: ColumnDefinition,
+    },
+    /// The primary key of the table, or its clustering order, differs
+    PrimaryKey(Name),
+    /// The table option differs
+    TableOption {
+        /// The table of the option
+        table: Name,
+        /// The option name
+        option: String,
+        /// The expected value
+        expected: Term,
+        /// The actual value, if it's set
+        actual: Option<Term>,
+    },
+}
+
+impl Display for SchemaChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaChange::MissingKeyspace(keyspace) => write!(f, "missing keyspace {}", keyspace),
+            SchemaChange::Replication { expected, actual } => {
+                write!(f, "replication {:?} instead of {:?}", actual, expected)
+            }
+            SchemaChange::MissingTable(table) => write!(f, "missing table {}", table),
+            SchemaChange::UnexpectedTable(table) => write!(f, "unexpected table {}", table),
+            SchemaChange::MissingColumn { table, column } => write!(f, "missing column {}.{}", table, column),
+            SchemaChange::UnexpectedColumn { table, column } => write!(f, "unexpected column {}.{}", table, column),
+            SchemaChange::ColumnType {
+                table,
+                expected,
+                actual,
+            } => {
+                write!(f, "column {}.{} instead of {}", table, actual, expected)
+            }
+            SchemaChange::PrimaryKey(table) => write!(f, "different primary key of table {}", table),
+            SchemaChange::TableOption {
+                table,
+                option,
+                expected,
+                actual: Some(actual),
+            } => write!(
+                f,
+                "option {} of table {} is {} instead of {}",
+                option, table, actual, expected
+            ),
+            SchemaChange::TableOption {
+                table,
+                option,
+                expected,
+                ..
+            } => write!(
+                f,
+                "option {} of table {} is unset instead of {}",
+                option, table, expected
+            ),
+        }
+    }
+}
+
+/// Normalize the CQL type, ie `Map<TEXT,varchar>` to `map<text, text>`, so the declared types compare equal to
+/// the ones stored in the schema. The quoted user defined type names are kept as they are.
+pub fn normalize_type(cql_type: &str) -> String {
+    let mut normalized = String::with_capacity(cql_type.len());
+    let mut word = String::new();
+    let mut quoted = false;
+    let flush = |word: &mut String, normalized: &mut String| {
+        let lowercase = word.to_ascii_lowercase();
+        normalized.push_str(if lowercase == "varchar" { "text" } else { &lowercase });
+        word.clear();
+    };
+    for c in cql_type.trim().chars() {
+        if quoted {
+            normalized.push(c);
+            quoted = c != '"';
+        } else if c == '"' {
+            flush(&mut word, &mut normalized);
+            normalized.push(c);
+            quoted = true;
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            word.push(c);
+        } else if !c.is_whitespace() {
+            flush(&mut word, &mut normalized);
+            normalized.push(c);
+            if c == ',' {
+                normalized.push(' ');
+            }
+        }
+    }
+    flush(&mut word, &mut normalized);
+    normalized
+}
+
+/// The partition key columns and the clustering columns of a primary key, along with their order
+type PrimaryKey = (Vec<Name>, Vec<(Name, ClusteringOrder)>);
+
+/// Parse the `(partition key columns), clustering columns` primary key definition, without its parentheses, where the
+/// clustering columns are ascending
+fn parse_primary_key(primary_key: &str, statement: &str) -> anyhow::Result<PrimaryKey> {
+    let mut keys = split_commas(primary_key).into_iter();
+    let partition_key = keys
+        .next()
+        .ok_or_else(|| anyhow!("Empty primary key in: {}", statement))?;
+    let partition_key = match partition_key.strip_prefix('(') {
+        Some(_) => split_commas(Cursor::new(partition_key).parenthesized()?)
+            .into_iter()
+            .map(Name::parse)
+            .collect::<anyhow::Result<_>>()?,
+        None => vec![Name::parse(partition_key)?],
+    };
+    let clustering_key = keys
+        .map(|key| Ok((Name::parse(key)?, ClusteringOrder::Asc)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok((partition_key, clustering_key))
+}
+
+/// Move the `CLUSTERING ORDER BY` option into the order of the clustering columns
+fn apply_clustering_order(
+    options: &mut ScyllaOptions,
+    clustering_key: &mut [(Name, ClusteringOrder)],
+) -> anyhow::Result<()> {
+    if let Some(clustering_order) = options.clustering_order.take() {
+        for order in split_commas(Cursor::new(&clustering_order).parenthesized()?) {
+            let mut cursor = Cursor::new(order);
+            let name = cursor.name()?;
+            let order = if cursor.keywords(&["desc"]) {
+                ClusteringOrder::Desc
+            } else if cursor.keywords(&["asc"]) || cursor.rest().is_empty() {
+                ClusteringOrder::Asc
+            } else {
+                bail!("Invalid clustering order: {}", order)
+            };
+            let column = clustering_key
+                .iter_mut()
+                .find(|(column, _)| *column == name)
+                .ok_or_else(|| anyhow!("Unknown clustering column in the clustering order: {}", name))?;
+            column.1 = order;
+        }
+    }
+    Ok(())
+}
+
+/// Render the `(partition key columns), clustering columns` primary key definition, without its parentheses
+fn primary_key_definition(partition_key: &[Name], clustering_key: &[(Name, ClusteringOrder)]) -> String {
+    let partition_key: Vec<String> = partition_key.iter().map(ToString::to_string).collect();
+    let mut primary_key = vec![format!("({})", partition_key.join(", "))];
+    primary_key.extend(clustering_key.iter().map(|(key, _)| key.to_string()));
+    primary_key.join(", ")
+}
+
+/// Get the options along with the `CLUSTERING ORDER BY` option, if any clustering column is descending
+fn with_clustering_order(options: &ScyllaOptions, clustering_key: &[(Name, ClusteringOrder)]) -> ScyllaOptions {
+    let mut options = options.clone();
+    if clustering_key.iter().any(|(_, order)| *order == ClusteringOrder::Desc) {
+        let orders: Vec<String> = clustering_key
+            .iter()
+            .map(|(key, order)| match order {
+                ClusteringOrder::Asc => format!("{} ASC", key),
+                ClusteringOrder::Desc => format!("{} DESC", key),
+            })
+            .collect();
+        options.clustering_order = Some(format!("({})", orders.join(", ")));
+    }
+    options
+}
+
+/// Split the optionally parenthesized list of the columns or the column definitions
+fn column_list(list: &str) -> anyhow::Result<Vec<&str>> {
+    let list = if list.starts_with('(') {
+        let mut cursor = Cursor::new(list);
+        let content = cursor.parenthesized()?;
+        let rest = cursor.rest();
+        ensure!(rest.is_empty(), "Unexpected chars: {}", rest);
+        content
+    } else {
+        list
+    };
+    let columns = split_commas(list);
+    ensure!(!columns.is_empty(), "Missing columns in: {}", list);
+    Ok(columns)
+}
+
+/// Split the definitions by their top level commas, which aren't nested in the type parameters either
+fn split_commas(definitions: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut angles = 0usize;
+    for (i, depth, c) in top_level(definitions) {
+        match c {
+            '<' => angles += 1,
+            '>' => angles = angles.saturating_sub(1),
+            _ => {}
+        }
+        if depth == 0 && angles == 0 && c == ',' {
+            parts.push(definitions[start..i].trim());
+            start = i + 1;
+        }
+    }
+    parts.push(definitions[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_statements() {
+        let keyspace = CreateKeyspaceStatement::parse(
+            "CREATE KEYSPACE IF NOT EXISTS shop WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+        )
+        .unwrap();
+        assert_eq!(keyspace.name.as_str(), "shop");
+        assert_eq!(CreateKeyspaceStatement::parse(&keyspace.to_string()).unwrap(), keyspace);
+        let table = CreateTableStatement::parse(
+            "CREATE TABLE IF NOT EXISTS shop.orders (customer text, id bigint, region text STATIC, \
+             items Map<TEXT,frozen<\"Item\">>, PRIMARY KEY ((customer), id)) \
+             WITH CLUSTERING ORDER BY (id DESC) AND comment = 'orders'",
+        )
+        .unwrap();
+        assert_eq!(table.partition_key, vec![Name::auto("customer")]);
+        assert_eq!(table.clustering_key, vec![(Name::auto("id"), ClusteringOrder::Desc)]);
+        assert!(table.column(&Name::auto("region")).unwrap().is_static);
+        assert_eq!(
+            table.column(&Name::auto("items")).unwrap().cql_type,
+            "map<text, frozen<\"Item\">>"
+        );
+        assert!(table.options.clustering_order.is_none());
+        assert_eq!(CreateTableStatement::parse(&table.to_string()).unwrap(), table);
+        let inline = CreateTableStatement::parse("CREATE TABLE t (k int PRIMARY KEY, v varchar)").unwrap();
+        assert_eq!(inline.partition_key, vec![Name::auto("k")]);
+        assert_eq!(inline.column(&Name::auto("v")).unwrap().cql_type, "text");
+        assert!(CreateTableStatement::parse("CREATE TABLE t (k int, PRIMARY KEY (x))").is_err());
+    }
+
+    #[test]
+    fn alter_table() {
+        let statement = AlterTableBuilder::new("Orders")
+            .keyspace("shop")
+            .add_static_column("region", CqlType::Text)
+            .build()
+            .unwrap();
+        assert_eq!(
+            statement.to_string(),
+            "ALTER TABLE shop.\"Orders\" ADD region text STATIC"
+        );
+        assert_eq!(AlterTableStatement::parse(&statement.to_string()).unwrap(), statement);
+        let statement = AlterTableBuilder::new("orders")
+            .drop_column("note")
+            .drop_column("select")
+            .build()
+            .unwrap();
+        assert_eq!(statement.to_string(), "ALTER TABLE orders DROP (note, \"select\")");
+        assert_eq!(AlterTableStatement::parse(&statement.to_string()).unwrap(), statement);
+        let statement = AlterTableBuilder::new("orders")
+            .rename("id", "order_id")
+            .rename("customer", "client")
+            .build()
+            .unwrap();
+        assert_eq!(
+            statement.to_string(),
+            "ALTER TABLE orders RENAME id TO order_id AND customer TO client"
+        );
+        assert_eq!(AlterTableStatement::parse(&statement.to_string()).unwrap(), statement);
+        let statement = AlterTableBuilder::new("orders")
+            .with_options(ScyllaOptions::parse("default_time_to_live = 3600").unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(
+            statement.to_string(),
+            "ALTER TABLE orders WITH default_time_to_live = 3600"
+        );
+        assert_eq!(AlterTableStatement::parse(&statement.to_string()).unwrap(), statement);
+        let parsed =
+            AlterTableStatement::parse("alter columnfamily ks.t add (a Map<TEXT,varchar>, b int static);").unwrap();
+        assert_eq!(
+            parsed.instruction,
+            AlterTableInstruction::Add(vec![
+                ColumnDefinition::parse("a map<text, text>").unwrap(),
+                ColumnDefinition::parse("b int STATIC").unwrap(),
+            ])
+        );
+        assert!(AlterTableBuilder::new("orders").build().is_err());
+        assert!(AlterTableStatement::parse("ALTER TABLE ks.t ALTER v MASKED WITH mask_default()").is_err());
+        assert!(AlterTableStatement::parse("ALTER TABLE ks.t RENAME a b").is_err());
+        assert!(AlterTableStatement::parse("ALTER MATERIALIZED VIEW ks.v WITH comment = 'v'").is_err());
+    }
+
+    #[test]
+    fn secondary_indexes() {
+        let table = CreateTableStatement::parse(
+            "CREATE TABLE shop.orders (customer text, id bigint, email text, tags map<text, int>, \
+             PRIMARY KEY (customer, id))",
+        )
+        .unwrap();
+        let index = table.index_statement(&Name::auto("email")).unwrap();
+        assert_eq!(index.to_string(), "CREATE INDEX IF NOT EXISTS ON shop.orders (email)");
+        assert_eq!(CreateIndexStatement::parse(&index.to_string()).unwrap(), index);
+        assert!(table.index_statement(&Name::auto("id")).is_err());
+        assert!(table.index_statement(&Name::auto("missing")).is_err());
+        let index = CreateIndexStatement::parse("create index tag_idx on orders ( keys ( \"Tags\" ) );").unwrap();
+        assert_eq!(
+            (index.name, index.kind, index.column),
+            (Some(Name::auto("tag_idx")), IndexKind::Keys, Name::auto("\"Tags\""))
+        );
+        assert_eq!(
+            CreateIndexStatement::parse("CREATE INDEX ON t (keys)").unwrap().column,
+            Name::auto("keys")
+        );
+        assert!(CreateIndexStatement::parse("CREATE INDEX ON t ((k), v)").is_err());
+        assert!(CreateIndexStatement::parse("CREATE INDEX ON t (v) USING 'vector_index'").is_err());
+    }
+
+    #[test]
+    fn materialized_views() {
+        let view = CreateMaterializedViewStatement::parse(
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS shop.orders_by_total AS \
+             SELECT customer, id, total FROM shop.orders \
+             WHERE total IS NOT NULL AND customer IS NOT NULL AND id IS NOT NULL \
+             PRIMARY KEY (total, customer, id) PER PARTITION LIMIT 10 \
+             WITH CLUSTERING ORDER BY (customer DESC) AND comment = 'by total'",
+        )
+        .unwrap();
+        assert_eq!(view.base_table, Name::auto("orders"));
+        assert_eq!(view.partition_key, vec![Name::auto("total")]);
+        assert_eq!(
+            view.clustering_key,
+            vec![
+                (Name::auto("customer"), ClusteringOrder::Desc),
+                (Name::auto("id"), ClusteringOrder::Asc)
+            ]
+        );
+        assert_eq!(
+            view.where_clause,
+            "total IS NOT NULL AND customer IS NOT NULL AND id IS NOT NULL"
+        );
+        assert_eq!(view.per_partition_limit, Some(10));
+        assert_eq!(
+            view.to_string(),
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS shop.orders_by_total AS SELECT customer, id, total FROM \
+             shop.orders WHERE total IS NOT NULL AND customer IS NOT NULL AND id IS NOT NULL PRIMARY KEY ((total), \
+             customer, id) PER PARTITION LIMIT 10 WITH CLUSTERING ORDER BY (customer DESC, id ASC) AND comment = \
+             'by total'"
+        );
+        assert_eq!(CreateMaterializedViewStatement::parse(&view.to_string()).unwrap(), view);
+        let all = CreateMaterializedViewStatement::parse(
+            "CREATE MATERIALIZED VIEW v AS SELECT * FROM t WHERE v IS NOT NULL AND k IS NOT NULL PRIMARY KEY ((v, k))",
+        )
+        .unwrap();
+        assert!(all.columns.is_empty() && all.clustering_key.is_empty());
+        assert_eq!(all.partition_key, vec![Name::auto("v"), Name::auto("k")]);
+        assert!(CreateMaterializedViewStatement::parse(
+            "CREATE MATERIALIZED VIEW v AS SELECT k FROM t WHERE v IS NOT NULL PRIMARY KEY (v, k)"
+        )
+        .is_err());
+        assert!(CreateMaterializedViewStatement::parse("CREATE TABLE t (k int PRIMARY KEY)").is_err());
+    }
+
+    #[test]
+    fn schema_diff() {
+        let expected = KeyspaceSchema::from_statements(&[
+            "CREATE KEYSPACE shop WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+            "CREATE TYPE shop.address (street text)",
+            "CREATE TABLE shop.orders (customer text, id bigint, total double, PRIMARY KEY (customer, id))",
+            "CREATE TABLE shop.customers (name text PRIMARY KEY)",
+        ])
+        .unwrap();
+        assert_eq!(expected.diff(Some(&expected)).unwrap(), vec![]);
+        let actual = KeyspaceSchema::from_statements(&[
+            "CREATE KEYSPACE shop WITH replication = \
+             {'class': 'org.apache.cassandra.locator.SimpleStrategy', 'replication_factor': '1'}",
+            "CREATE TABLE shop.orders (customer text, id bigint, total float, note text, PRIMARY KEY (customer, id)) \
+             WITH CLUSTERING ORDER BY (id DESC)",
+            "CREATE TABLE shop.legacy (k int PRIMARY KEY)",
+        ])
+        .unwrap();
+        let (orders, total) = (Name::auto("orders"), Name::auto("total"));
+        assert_eq!(
+            expected.diff(Some(&actual)).unwrap(),
+            vec![
+                SchemaChange::ColumnType {
+                    table: orders.clone(),
+                    expected: expected.table(&orders).unwrap().column(&total).unwrap().clone(),
+                    actual: actual.table(&orders).unwrap().column(&total).unwrap().clone(),
+                },
+                SchemaChange::UnexpectedColumn {
+                    table: orders.clone(),
+                    column: actual
+                        .table(&orders)
+                        .unwrap()
+                        .column(&Name::auto("note"))
+                        .unwrap()
+                        .clone(),
+                },
+                SchemaChange::PrimaryKey(orders),
+                SchemaChange::MissingTable(Name::auto("customers")),
+                SchemaChange::UnexpectedTable(Name::auto("legacy")),
+            ]
+        );
+        assert_eq!(
+            expected.diff(None).unwrap(),
+            vec![SchemaChange::MissingKeyspace(Name::auto("shop"))]
+        );
+    }
+
+    #[test]
+    fn migration() {
+        let expected = KeyspaceSchema::from_statements(&[
+            "CREATE KEYSPACE shop WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+            "CREATE TABLE shop.orders (customer text, id bigint, total double, region text STATIC, \
+             PRIMARY KEY (customer, id)) WITH compaction = {'class': 'LeveledCompactionStrategy'} \
+             AND default_time_to_live = 3600 AND comment = 'orders'",
+        ])
+        .unwrap();
+        let actual = KeyspaceSchema::from_statements(&[
+            "CREATE KEYSPACE shop WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+            "CREATE TABLE shop.orders (customer text, id bigint, total double, PRIMARY KEY (customer, id)) \
+             WITH compaction = {'class': 'org.apache.cassandra.db.compaction.LeveledCompactionStrategy', \
+             'sstable_size_in_mb': '160'} AND default_time_to_live = 0 AND comment = 'orders'",
+        ])
+        .unwrap();
+        let changes = expected.diff(Some(&actual)).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            expected.alter_statements(&changes),
+            vec![
+                "ALTER TABLE shop.orders ADD region text STATIC",
+                "ALTER TABLE shop.orders WITH default_time_to_live = 3600",
+            ]
+        );
+        assert_eq!(
+            created_type("CREATE TYPE IF NOT EXISTS shop.address (street text)").unwrap(),
+            Some(Name::auto("address"))
+        );
+        assert_eq!(created_type("CREATE TABLE shop.t (k int PRIMARY KEY)").unwrap(), None);
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module renders the schema of a keyspace into Markdown or HTML documentation, ie for the internal
+//! documentation portals.
+
+use super::schema::{ClusteringOrder, ColumnDefinition, CreateTableStatement, KeyspaceSchema};
+use std::fmt::Write;
+
+/// The format of the schema documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+    /// GitHub flavored Markdown
+    Markdown,
+    /// An HTML fragment, without the `<html>` and `<body>` elements
+    Html,
+}
+
+/// Render the schema documentation in the format
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::statements::{
+///     schema::KeyspaceSchema,
+///     schema_docs::{render, DocFormat},
+/// };
+///
+/// let schema = KeyspaceSchema::from_statements(&[
+///     "CREATE KEYSPACE shop WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+///     "CREATE TABLE shop.orders (customer text, id bigint, total double, PRIMARY KEY (customer, id))",
+/// ])?;
+/// let docs = render(&schema, DocFormat::Markdown);
+/// assert!(docs.contains("| `customer` | `text` | partition key (1) |"));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn render(schema: &KeyspaceSchema, format: DocFormat) -> String {
+    match format {
+        DocFormat::Markdown => markdown(schema),
+        DocFormat::Html => html(schema),
+    }
+}
+
+/// Render the schema documentation in Markdown, with a section per table which lists its columns, keys and options
+pub fn markdown(schema: &KeyspaceSchema) -> String {
+    let mut docs = String::new();
+    let keyspace = &schema.keyspace;
+    writeln!(docs, "# Keyspace `{}`\n", keyspace.name).ok();
+    writeln!(docs, "```cql\n{};\n```\n", keyspace).ok();
+    for (key, value) in keyspace.replication().unwrap_or_default() {
+        writeln!(docs, "- {}: `{}`", key, escape_markdown(&value)).ok();
+    }
+    if let Some(durable_writes) = keyspace.durable_writes {
+        writeln!(docs, "- durable_writes: `{}`", durable_writes).ok();
+    }
+    for table in schema.tables.iter() {
+        writeln!(docs, "\n## Table `{}`\n", table.name).ok();
+        writeln!(docs, "```cql\n{};\n```\n", table).ok();
+        docs.push_str("| Column | Type | Key |\n|---|---|---|\n");
+        for column in table.columns.iter() {
+            writeln!(
+                docs,
+                "| `{}` | `{}` | {} |",
+                escape_markdown(&column.name.to_string()),
+                escape_markdown(&column.cql_type),
+                column_key(table, column)
+            )
+            .ok();
+        }
+        let options = table_options(table);
+        if !options.is_empty() {
+            docs.push_str("\n| Option | Value |\n|---|---|\n");
+            for (option, value) in options {
+                writeln!(docs, "| {} | `{}` |", option, escape_markdown(&value)).ok();
+            }
+        }
+    }
+    docs
+}
+
+/// Render the schema documentation in HTML, with a section per table which lists its columns, keys and options
+pub fn html(schema: &KeyspaceSchema) -> String {
+    let mut docs = String::new();
+    let keyspace = &schema.keyspace;
+    let name = escape_html(&keyspace.name.to_string());
+    writeln!(docs, "<section class=\"keyspace\" id=\"{}\">", name).ok();
+    writeln!(docs, "<h1>Keyspace <code>{}</code></h1>", name).ok();
+    writeln!(docs, "<pre><code>{};</code></pre>", escape_html(&keyspace.to_string())).ok();
+    docs.push_str("<ul>\n");
+    for (key, value) in keyspace.replication().unwrap_or_default() {
+        writeln!(
+            docs,
+            "<li>{}: <code>{}</code></li>",
+            escape_html(&key),
+            escape_html(&value)
+        )
+        .ok();
+    }
+    if let Some(durable_writes) = keyspace.durable_writes {
+        writeln!(docs, "<li>durable_writes: <code>{}</code></li>", durable_writes).ok();
+    }
+    docs.push_str("</ul>\n");
+    for table in schema.tables.iter() {
+        let table_name = escape_html(&table.name.to_string());
+        writeln!(docs, "<section class=\"table\" id=\"{}.{}\">", name, table_name).ok();
+        writeln!(docs, "<h2>Table <code>{}</code></h2>", table_name).ok();
+        writeln!(docs, "<pre><code>{};</code></pre>", escape_html(&table.to_string())).ok();
+        docs.push_str("<table class=\"columns\">\n<tr><th>Column</th><th>Type</th><th>Key</th></tr>\n");
+        for column in table.columns.iter() {
+            writeln!(
+                docs,
+                "<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}</td></tr>",
+                escape_html(&column.name.to_string()),
+                escape_html(&column.cql_type),
+                column_key(table, column)
+            )
+            .ok();
+        }
+        docs.push_str("</table>\n");
+        let options = table_options(table);
+        if !options.is_empty() {
+            docs.push_str("<table class=\"options\">\n<tr><th>Option</th><th>Value</th></tr>\n");
+            for (option, value) in options {
+                writeln!(
+                    docs,
+                    "<tr><td>{}</td><td><code>{}</code></td></tr>",
+                    escape_html(&option),
+                    escape_html(&value)
+                )
+                .ok();
+            }
+            docs.push_str("</table>\n");
+        }
+        docs.push_str("</section>\n");
+    }
+    docs.push_str("</section>\n");
+    docs
+}
+
+/// Describe the role of the column in the primary key, ie `clustering (2, DESC)`
+fn column_key(table: &CreateTableStatement, column: &ColumnDefinition) -> String {
+    if let Some(position) = table.partition_key.iter().position(|key| *key == column.name) {
+        format!("partition key ({})", position + 1)
+    } else if let Some(position) = table.clustering_key.iter().position(|(key, _)| *key == column.name) {
+        let order = match table.clustering_key[position].1 {
+            ClusteringOrder::Asc => "ASC",
+            ClusteringOrder::Desc => "DESC",
+        };
+        format!("clustering ({}, {})", position + 1, order)
+    } else if column.is_static {
+        "static".to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Get the options of the table along with their CQL values, where the Scylla specific options come first
+fn table_options(table: &CreateTableStatement) -> Vec<(String, String)> {
+    let mut scylla_options = table.options.clone();
+    scylla_options.options.clear();
+    scylla_options.clustering_order.take();
+    let mut options: Vec<(String, String)> = if scylla_options.is_empty() {
+        Vec::new()
+    } else {
+        // the Scylla specific options don't hold any free text, so their separators are unambiguous
+        scylla_options
+            .to_string()
+            .split(" AND ")
+            .filter_map(|option| option.split_once(" = "))
+            .map(|(option, value)| (option.to_string(), value.to_string()))
+            .collect()
+    };
+    options.extend(
+        table
+            .options
+            .options
+            .iter()
+            .map(|(option, value)| (option.clone(), value.to_string())),
+    );
+    options
+}
+
+/// Escape the Markdown table cell, whose pipes would split it otherwise
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// Escape the HTML text or attribute value
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_docs() {
+        let schema = KeyspaceSchema::from_statements(&[
+            "CREATE KEYSPACE shop WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 1}",
+            "CREATE TABLE shop.orders (customer text, id bigint, region text STATIC, items map<text, frozen<\"Item\">>, \
+             PRIMARY KEY (customer, id)) WITH CLUSTERING ORDER BY (id DESC) AND comment = 'a | b' \
+             AND tombstone_gc = {'mode': 'repair'}",
+        ])
+        .unwrap();
+        let markdown = render(&schema, DocFormat::Markdown);
+        assert!(markdown.starts_with("# Keyspace `shop`\n"));
+        assert!(markdown.contains("- class: `SimpleStrategy`\n"));
+        assert!(markdown.contains("| `id` | `bigint` | clustering (1, DESC) |\n"));
+        assert!(markdown.contains("| `region` | `text` | static |\n"));
+        assert!(markdown.contains("| comment | `'a \\| b'` |\n"));
+        assert!(markdown.contains("| tombstone_gc | `{'mode': 'repair'}` |\n"));
+        let html = render(&schema, DocFormat::Html);
+        assert!(html.contains("<td><code>map&lt;text, frozen&lt;&quot;Item&quot;&gt;&gt;</code></td>"));
+        assert!(html.contains("<td><code>customer</code></td><td><code>text</code></td><td>partition key (1)</td>"));
+        assert_eq!(html.matches("<section").count(), html.matches("</section>").count());
+    }
+}
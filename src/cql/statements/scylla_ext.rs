@@ -0,0 +1,759 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the parsing of the Scylla specific schema statements and options, which aren't part of
+//! the standard CQL, ie the `cdc` and `synchronous_updates` options or `PRUNE MATERIALIZED VIEW`.
+
+use crate::cql::{parse_options, Name, Term};
+use anyhow::{anyhow, bail, ensure};
+use std::fmt::{Display, Formatter};
+
+/// The schema object which is altered by an `ALTER .. WITH` statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaObject {
+    /// A table
+    Table,
+    /// A materialized view
+    MaterializedView,
+}
+
+/// The content of the change data capture log entries, ie the `delta` CDC option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CdcDelta {
+    /// The entries hold all the written columns
+    Full,
+    /// The entries only hold the primary key columns
+    Keys,
+}
+
+/// The `cdc` table option
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CdcOptions {
+    /// Whether the change data capture is enabled
+    pub enabled: bool,
+    /// Whether the entries hold the preimage of the changed columns
+    pub preimage: bool,
+    /// Whether the preimage holds all the columns, ie `'preimage': 'full'`
+    pub full_preimage: bool,
+    /// Whether the entries hold the postimage of the rows
+    pub postimage: bool,
+    /// The content of the delta entries
+    pub delta: CdcDelta,
+    /// The time to live of the log entries, in seconds
+    pub ttl: u32,
+}
+
+impl Default for CdcOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            preimage: false,
+            full_preimage: false,
+            postimage: false,
+            delta: CdcDelta::Full,
+            ttl: 86400,
+        }
+    }
+}
+
+impl CdcOptions {
+    /// Parse the `cdc` option map, ie `{'enabled': true, 'preimage': 'full'}`
+    pub fn parse(term: &Term) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        for (key, value) in term.as_map()? {
+            let key: String = key.evaluate()?;
+            match key.as_str() {
+                "enabled" => options.enabled = value.evaluate()?,
+                "preimage" => match value.evaluate::<String>() {
+                    Ok(full) if full.eq_ignore_ascii_case("full") => {
+                        options.preimage = true;
+                        options.full_preimage = true;
+                    }
+                    _ => options.preimage = value.evaluate()?,
+                },
+                "postimage" => options.postimage = value.evaluate()?,
+                "delta" => {
+                    options.delta = match value.evaluate::<String>()?.to_ascii_lowercase().as_str() {
+                        "full" => CdcDelta::Full,
+                        "keys" => CdcDelta::Keys,
+                        delta => bail!("Invalid cdc delta: {}", delta),
+                    }
+                }
+                "ttl" => options.ttl = value.evaluate()?,
+                _ => bail!("Unknown cdc option: {}", key),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The garbage collection mode of the tombstones, ie the `mode` of the `tombstone_gc` option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TombstoneGcMode {
+    /// The tombstones are collected after `gc_grace_seconds`
+    Timeout,
+    /// The tombstones are collected once they are repaired
+    Repair,
+    /// The tombstones are never collected
+    Disabled,
+    /// The tombstones are collected right away
+    Immediate,
+}
+
+/// The `tombstone_gc` table option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TombstoneGc {
+    /// The garbage collection mode
+    pub mode: TombstoneGcMode,
+    /// The delay of the repair mode, in seconds
+    pub propagation_delay_in_seconds: Option<u32>,
+}
+
+impl TombstoneGc {
+    /// Parse the `tombstone_gc` option map, ie `{'mode': 'repair'}`
+    pub fn parse(term: &Term) -> anyhow::Result<Self> {
+        let mut mode = TombstoneGcMode::Timeout;
+        let mut propagation_delay_in_seconds = None;
+        for (key, value) in term.as_map()? {
+            let key: String = key.evaluate()?;
+            match key.as_str() {
+                "mode" => {
+                    mode = match value.evaluate::<String>()?.to_ascii_lowercase().as_str() {
+                        "timeout" => TombstoneGcMode::Timeout,
+                        "repair" => TombstoneGcMode::Repair,
+                        "disabled" => TombstoneGcMode::Disabled,
+                        "immediate" => TombstoneGcMode::Immediate,
+                        mode => bail!("Invalid tombstone_gc mode: {}", mode),
+                    }
+                }
+                // the numeric options are commonly written as strings
+                "propagation_delay_in_seconds" => {
+                    propagation_delay_in_seconds = Some(match value.evaluate::<String>() {
+                        Ok(delay) => delay
+                            .parse()
+                            .map_err(|_| anyhow!("Invalid propagation_delay_in_seconds: {}", delay))?,
+                        Err(_) => value.evaluate()?,
+                    })
+                }
+                _ => bail!("Unknown tombstone_gc option: {}", key),
+            }
+        }
+        Ok(Self {
+            mode,
+            propagation_delay_in_seconds,
+        })
+    }
+}
+
+/// The `per_partition_rate_limit` table option
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerPartitionRateLimit {
+    /// The max reads per second of a partition
+    pub max_reads_per_second: Option<u32>,
+    /// The max writes per second of a partition
+    pub max_writes_per_second: Option<u32>,
+}
+
+impl PerPartitionRateLimit {
+    /// Parse the `per_partition_rate_limit` option map, ie `{'max_writes_per_second': 100}`
+    pub fn parse(term: &Term) -> anyhow::Result<Self> {
+        let mut limit = Self::default();
+        for (key, value) in term.as_map()? {
+            let key: String = key.evaluate()?;
+            match key.as_str() {
+                "max_reads_per_second" => limit.max_reads_per_second = Some(value.evaluate()?),
+                "max_writes_per_second" => limit.max_writes_per_second = Some(value.evaluate()?),
+                _ => bail!("Unknown per_partition_rate_limit option: {}", key),
+            }
+        }
+        Ok(limit)
+    }
+}
+
+/// The options of a `WITH` clause, where the Scylla specific ones are typed while the standard ones are kept as
+/// they are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScyllaOptions {
+    /// The `cdc` option
+    pub cdc: Option<CdcOptions>,
+    /// The `synchronous_updates` option of the materialized views and the indexes
+    pub synchronous_updates: Option<bool>,
+    /// The `tombstone_gc` option
+    pub tombstone_gc: Option<TombstoneGc>,
+    /// The `per_partition_rate_limit` option
+    pub per_partition_rate_limit: Option<PerPartitionRateLimit>,
+    /// The `paxos_grace_seconds` option
+    pub paxos_grace_seconds: Option<u32>,
+    /// The `CLUSTERING ORDER BY (..)` option, without its leading keywords
+    pub clustering_order: Option<String>,
+    /// The standard options, ie `gc_grace_seconds` or `compaction`
+    pub options: Vec<(String, Term)>,
+}
+
+impl ScyllaOptions {
+    /// Parse the options of the `WITH` clause, without its leading `WITH` keyword
+    pub fn parse(with: &str) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        for option in split_options(with)? {
+            let mut cursor = Cursor::new(option);
+            if cursor.keywords(&["clustering", "order", "by"]) {
+                options.clustering_order = Some(cursor.rest().to_string());
+                continue;
+            }
+            for (name, term) in parse_options(option)? {
+                match name.as_str() {
+                    "cdc" => options.cdc = Some(CdcOptions::parse(&term)?),
+                    "synchronous_updates" => options.synchronous_updates = Some(term.evaluate()?),
+                    "tombstone_gc" => options.tombstone_gc = Some(TombstoneGc::parse(&term)?),
+                    "per_partition_rate_limit" => {
+                        options.per_partition_rate_limit = Some(PerPartitionRateLimit::parse(&term)?)
+                    }
+                    "paxos_grace_seconds" => options.paxos_grace_seconds = Some(term.evaluate()?),
+                    _ => options.options.push((name, term)),
+                }
+            }
+        }
+        Ok(options)
+    }
+    /// Get the standard option by its name
+    pub fn get(&self, name: &str) -> Option<&Term> {
+        self.options.iter().find(|(n, _)| n == name).map(|(_, term)| term)
+    }
+    /// Check if the clause holds no option
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+    /// Get the options in their canonical form, ie `name = value`
+    pub(super) fn entries(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        if let Some(clustering_order) = &self.clustering_order {
+            options.push(format!("CLUSTERING ORDER BY {}", clustering_order));
+        }
+        if let Some(cdc) = &self.cdc {
+            let preimage = if cdc.full_preimage {
+                "'full'".to_string()
+            } else {
+                cdc.preimage.to_string()
+            };
+            let delta = match cdc.delta {
+                CdcDelta::Full => "full",
+                CdcDelta::Keys => "keys",
+            };
+            options.push(format!(
+                "cdc = {{'enabled': {}, 'preimage': {}, 'postimage': {}, 'delta': '{}', 'ttl': {}}}",
+                cdc.enabled, preimage, cdc.postimage, delta, cdc.ttl
+            ));
+        }
+        if let Some(synchronous_updates) = self.synchronous_updates {
+            options.push(format!("synchronous_updates = {}", synchronous_updates));
+        }
+        if let Some(tombstone_gc) = &self.tombstone_gc {
+            let mode = match tombstone_gc.mode {
+                TombstoneGcMode::Timeout => "timeout",
+                TombstoneGcMode::Repair => "repair",
+                TombstoneGcMode::Disabled => "disabled",
+                TombstoneGcMode::Immediate => "immediate",
+            };
+            let delay = tombstone_gc
+                .propagation_delay_in_seconds
+                .map(|delay| format!(", 'propagation_delay_in_seconds': '{}'", delay))
+                .unwrap_or_default();
+            options.push(format!("tombstone_gc = {{'mode': '{}'{}}}", mode, delay));
+        }
+        if let Some(limit) = &self.per_partition_rate_limit {
+            let limits: Vec<String> = [
+                ("max_reads_per_second", limit.max_reads_per_second),
+                ("max_writes_per_second", limit.max_writes_per_second),
+            ]
+            .iter()
+            .filter_map(|(name, limit)| limit.map(|limit| format!("'{}': {}", name, limit)))
+            .collect();
+            options.push(format!("per_partition_rate_limit = {{{}}}", limits.join(", ")));
+        }
+        if let Some(paxos_grace_seconds) = self.paxos_grace_seconds {
+            options.push(format!("paxos_grace_seconds = {}", paxos_grace_seconds));
+        }
+        for (name, term) in &self.options {
+            options.push(format!("{} = {}", Name::auto(name), term));
+        }
+        options
+    }
+}
+
+impl Display for ScyllaOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.entries().join(" AND "))
+    }
+}
+
+/// A schema statement which holds Scylla specific options or syntax.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::statements::scylla_ext::{SchemaObject, ScyllaStatement};
+///
+/// let statement = "ALTER MATERIALIZED VIEW ks.v WITH synchronous_updates = true";
+/// match ScyllaStatement::parse(statement).unwrap() {
+///     Some(ScyllaStatement::Alter { object, name, options, .. }) => {
+///         assert_eq!(object, SchemaObject::MaterializedView);
+///         assert_eq!(name.as_str(), "v");
+///         assert_eq!(options.synchronous_updates, Some(true));
+///     }
+///     _ => unreachable!(),
+/// }
+/// assert!(ScyllaStatement::parse("SELECT * FROM ks.t").unwrap().is_none());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScyllaStatement {
+    /// `CREATE TABLE [IF NOT EXISTS] [ks.]table (..) [WITH ..]`
+    CreateTable {
+        /// Whether the statement holds IF NOT EXISTS
+        if_not_exists: bool,
+        /// The keyspace of the table, if it's qualified
+        keyspace: Option<Name>,
+        /// The table name
+        name: Name,
+        /// The column and primary key definitions, without their parentheses
+        definitions: String,
+        /// The table options
+        options: ScyllaOptions,
+    },
+    /// `CREATE MATERIALIZED VIEW [IF NOT EXISTS] [ks.]view AS SELECT .. [PER PARTITION LIMIT n] [WITH ..]`
+    CreateMaterializedView {
+        /// Whether the statement holds IF NOT EXISTS
+        if_not_exists: bool,
+        /// The keyspace of the view, if it's qualified
+        keyspace: Option<Name>,
+        /// The view name
+        name: Name,
+        /// The `SELECT .. PRIMARY KEY (..)` definition of the view, without its `PER PARTITION LIMIT`
+        select: String,
+        /// The `PER PARTITION LIMIT` of the view definition
+        per_partition_limit: Option<u32>,
+        /// The view options
+        options: ScyllaOptions,
+    },
+    /// `ALTER TABLE | MATERIALIZED VIEW [ks.]name WITH ..`
+    Alter {
+        /// The altered schema object
+        object: SchemaObject,
+        /// The keyspace of the object, if it's qualified
+        keyspace: Option<Name>,
+        /// The object name
+        name: Name,
+        /// The altered options
+        options: ScyllaOptions,
+    },
+    /// `PRUNE MATERIALIZED VIEW [ks.]view [WHERE ..] [USING ..]`, which removes the view rows which have no
+    /// base rows
+    PruneMaterializedView {
+        /// The keyspace of the view, if it's qualified
+        keyspace: Option<Name>,
+        /// The view name
+        name: Name,
+        /// The `WHERE ..` and `USING ..` clauses, as they are
+        clauses: String,
+    },
+}
+
+impl ScyllaStatement {
+    /// Parse the statement, or return None if it's not one of the supported schema statements
+    pub fn parse(statement: &str) -> anyhow::Result<Option<Self>> {
+        let statement = statement.trim().trim_end_matches(';');
+        let mut cursor = Cursor::new(statement);
+        if cursor.keywords(&["create", "table"]) || cursor.keywords(&["create", "columnfamily"]) {
+            let if_not_exists = cursor.keywords(&["if", "not", "exists"]);
+            let (keyspace, name) = cursor.qualified_name()?;
+            let definitions = cursor.parenthesized()?.to_string();
+            Ok(Some(ScyllaStatement::CreateTable {
+                if_not_exists,
+                keyspace,
+                name,
+                definitions,
+                options: cursor.with_options()?,
+            }))
+        } else if cursor.keywords(&["create", "materialized", "view"]) {
+            let if_not_exists = cursor.keywords(&["if", "not", "exists"]);
+            let (keyspace, name) = cursor.qualified_name()?;
+            ensure!(cursor.keywords(&["as"]), "Expected AS in: {}", statement);
+            // the view definition ends by the top level WITH, and might hold a PER PARTITION LIMIT
+            let definition = cursor.until_keyword("with");
+            let (select, per_partition_limit) = match find_keywords(definition, &["per", "partition", "limit"]) {
+                Some((start, end)) => {
+                    let limit = definition[end..].trim();
+                    let limit = limit
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid PER PARTITION LIMIT: {}", limit))?;
+                    (definition[..start].trim().to_string(), Some(limit))
+                }
+                None => (definition.trim().to_string(), None),
+            };
+            Ok(Some(ScyllaStatement::CreateMaterializedView {
+                if_not_exists,
+                keyspace,
+                name,
+                select,
+                per_partition_limit,
+                options: cursor.with_options()?,
+            }))
+        } else if cursor.keywords(&["alter"]) {
+            let object = if cursor.keywords(&["table"]) || cursor.keywords(&["columnfamily"]) {
+                SchemaObject::Table
+            } else if cursor.keywords(&["materialized", "view"]) {
+                SchemaObject::MaterializedView
+            } else {
+                return Ok(None);
+            };
+            let (keyspace, name) = cursor.qualified_name()?;
+            if !cursor.keywords(&["with"]) {
+                // the column alterations are standard CQL
+                return Ok(None);
+            }
+            Ok(Some(ScyllaStatement::Alter {
+                object,
+                keyspace,
+                name,
+                options: ScyllaOptions::parse(cursor.rest())?,
+            }))
+        } else if cursor.keywords(&["prune", "materialized", "view"]) {
+            let (keyspace, name) = cursor.qualified_name()?;
+            Ok(Some(ScyllaStatement::PruneMaterializedView {
+                keyspace,
+                name,
+                clauses: cursor.rest().to_string(),
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Display for ScyllaStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn qualified(keyspace: &Option<Name>, name: &Name) -> String {
+            match keyspace {
+                Some(keyspace) => format!("{}.{}", keyspace, name),
+                None => name.to_string(),
+            }
+        }
+        fn with(options: &ScyllaOptions) -> String {
+            if options.is_empty() {
+                String::new()
+            } else {
+                format!(" WITH {}", options)
+            }
+        }
+        let if_not_exists = |if_not_exists: bool| if if_not_exists { "IF NOT EXISTS " } else { "" };
+        match self {
+            ScyllaStatement::CreateTable {
+                if_not_exists: condition,
+                keyspace,
+                name,
+                definitions,
+                options,
+            } => write!(
+                f,
+                "CREATE TABLE {}{} ({}){}",
+                if_not_exists(*condition),
+                qualified(keyspace, name),
+                definitions,
+                with(options)
+            ),
+            ScyllaStatement::CreateMaterializedView {
+                if_not_exists: condition,
+                keyspace,
+                name,
+                select,
+                per_partition_limit,
+                options,
+            } => {
+                write!(
+                    f,
+                    "CREATE MATERIALIZED VIEW {}{} AS {}",
+                    if_not_exists(*condition),
+                    qualified(keyspace, name),
+                    select
+                )?;
+                if let Some(limit) = per_partition_limit {
+                    write!(f, " PER PARTITION LIMIT {}", limit)?;
+                }
+                f.write_str(&with(options))
+            }
+            ScyllaStatement::Alter {
+                object,
+                keyspace,
+                name,
+                options,
+            } => {
+                let object = match object {
+                    SchemaObject::Table => "TABLE",
+                    SchemaObject::MaterializedView => "MATERIALIZED VIEW",
+                };
+                write!(f, "ALTER {} {}{}", object, qualified(keyspace, name), with(options))
+            }
+            ScyllaStatement::PruneMaterializedView {
+                keyspace,
+                name,
+                clauses,
+            } => {
+                write!(f, "PRUNE MATERIALIZED VIEW {}", qualified(keyspace, name))?;
+                if !clauses.is_empty() {
+                    write!(f, " {}", clauses)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A cursor over the words of a statement
+pub(super) struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    pub(super) fn new(statement: &'a str) -> Self {
+        Self {
+            rest: statement.trim_start(),
+        }
+    }
+    /// Consume the keywords if the statement continues with them, regardless of their case
+    pub(super) fn keywords(&mut self, keywords: &[&str]) -> bool {
+        let mut rest = self.rest;
+        for keyword in keywords {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            if !rest[..len].eq_ignore_ascii_case(keyword) {
+                return false;
+            }
+            rest = rest[len..].trim_start();
+        }
+        self.rest = rest;
+        true
+    }
+    /// Consume the identifier, which might be quoted
+    pub(super) fn name(&mut self) -> anyhow::Result<Name> {
+        let len = if self.rest.starts_with('"') {
+            let mut chars = self.rest.char_indices().skip(1).peekable();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) if matches!(chars.peek(), Some((_, '"'))) => {
+                        chars.next();
+                    }
+                    Some((i, '"')) => break i + 1,
+                    Some(_) => {}
+                    None => bail!("Unterminated quoted identifier: {}", self.rest),
+                }
+            }
+        } else {
+            self.rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(self.rest.len())
+        };
+        let name = Name::parse(&self.rest[..len])?;
+        self.rest = self.rest[len..].trim_start();
+        Ok(name)
+    }
+    /// Consume the `[keyspace.]name` qualified name
+    pub(super) fn qualified_name(&mut self) -> anyhow::Result<(Option<Name>, Name)> {
+        let first = self.name()?;
+        if let Some(rest) = self.rest.strip_prefix('.') {
+            self.rest = rest.trim_start();
+            Ok((Some(first), self.name()?))
+        } else {
+            Ok((None, first))
+        }
+    }
+    /// Consume the parenthesized content, returning it without its parentheses
+    pub(super) fn parenthesized(&mut self) -> anyhow::Result<&'a str> {
+        ensure!(self.rest.starts_with('('), "Expected '(' in: {}", self.rest);
+        let end = top_level(self.rest)
+            .find(|(_, depth, c)| *depth == 0 && *c == ')')
+            .map(|(i, _, _)| i)
+            .ok_or_else(|| anyhow!("Unbalanced parentheses in: {}", self.rest))?;
+        let content = &self.rest[1..end];
+        self.rest = self.rest[end + 1..].trim_start();
+        Ok(content)
+    }
+    /// Consume the statement until the top level keyword, which isn't consumed
+    pub(super) fn until_keyword(&mut self, keyword: &str) -> &'a str {
+        let end = find_keywords(self.rest, &[keyword])
+            .map(|(start, _)| start)
+            .unwrap_or(self.rest.len());
+        let until = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        until
+    }
+    /// Consume the optional `WITH ..` clause
+    pub(super) fn with_options(&mut self) -> anyhow::Result<ScyllaOptions> {
+        if self.keywords(&["with"]) {
+            ScyllaOptions::parse(self.rest())
+        } else {
+            ensure!(self.rest.trim().is_empty(), "Unexpected chars: {}", self.rest);
+            Ok(ScyllaOptions::default())
+        }
+    }
+    pub(super) fn rest(&mut self) -> &'a str {
+        std::mem::take(&mut self.rest).trim()
+    }
+}
+
+/// Iterate the chars which are not quoted, along with their offsets and their nesting depth, ie the depth of the
+/// closing chars is the one of their opening chars.
+pub(super) fn top_level(statement: &str) -> impl Iterator<Item = (usize, usize, char)> + '_ {
+    let mut quote: Option<char> = None;
+    let mut depth = 0usize;
+    let mut previous = ' ';
+    statement.char_indices().filter_map(move |(i, c)| {
+        let dollar = c == '$' && previous == '$';
+        previous = if dollar { ' ' } else { c };
+        match (quote, c) {
+            (Some('$'), _) if dollar => {
+                quote = None;
+                None
+            }
+            (Some('$'), _) => None,
+            // the escaped quotes are handled as two consecutive quotes
+            (Some(q), _) if q == c => {
+                quote = None;
+                None
+            }
+            (Some(_), _) => None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                None
+            }
+            (None, '$') if dollar => {
+                quote = Some('$');
+                None
+            }
+            (None, '(' | '{' | '[') => {
+                depth += 1;
+                Some((i, depth - 1, c))
+            }
+            (None, ')' | '}' | ']') => {
+                depth = depth.saturating_sub(1);
+                Some((i, depth, c))
+            }
+            (None, _) => Some((i, depth, c)),
+        }
+    })
+}
+
+/// Find the top level keywords, returning their start and end offsets
+pub(super) fn find_keywords(statement: &str, keywords: &[&str]) -> Option<(usize, usize)> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    top_level(statement)
+        .filter(|(i, depth, c)| {
+            *depth == 0 && is_word(*c) && !statement[..*i].ends_with(|p: char| is_word(p) || p == '"' || p == '.')
+        })
+        .find_map(|(i, _, _)| {
+            let mut cursor = Cursor::new(&statement[i..]);
+            if cursor.keywords(keywords) {
+                Some((i, statement.len() - cursor.rest.len()))
+            } else {
+                None
+            }
+        })
+}
+
+/// Split the options of the `WITH` clause by their top level `AND` keywords
+pub(super) fn split_options(with: &str) -> anyhow::Result<Vec<&str>> {
+    let mut options = Vec::new();
+    let mut rest = with;
+    while let Some((start, end)) = find_keywords(rest, &["and"]) {
+        options.push(rest[..start].trim());
+        rest = &rest[end..];
+    }
+    options.push(rest.trim());
+    ensure!(
+        options.iter().all(|option| !option.is_empty()),
+        "Empty option in: {}",
+        with
+    );
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scylla_statements() {
+        let statement = "CREATE TABLE IF NOT EXISTS ks.\"My\"\"Table\" (k text, c int, v text, PRIMARY KEY (k, c)) \
+                         WITH CLUSTERING ORDER BY (c DESC) AND cdc = {'enabled': true, 'preimage': 'full', 'ttl': 3600} \
+                         AND comment = 'a AND b' AND tombstone_gc = {'mode': 'repair', 'propagation_delay_in_seconds': '3600'} \
+                         AND gc_grace_seconds = 60 * 60 * 24;";
+        match ScyllaStatement::parse(statement).unwrap().unwrap() {
+            ScyllaStatement::CreateTable {
+                if_not_exists,
+                keyspace,
+                name,
+                definitions,
+                options,
+            } => {
+                assert!(if_not_exists);
+                assert_eq!(keyspace.unwrap().as_str(), "ks");
+                assert_eq!(name.as_str(), "My\"Table");
+                assert_eq!(definitions, "k text, c int, v text, PRIMARY KEY (k, c)");
+                assert_eq!(options.clustering_order.as_deref(), Some("(c DESC)"));
+                let cdc = options.cdc.clone().unwrap();
+                assert!(cdc.enabled && cdc.preimage && cdc.full_preimage && !cdc.postimage);
+                assert_eq!((cdc.delta, cdc.ttl), (CdcDelta::Full, 3600));
+                let tombstone_gc = options.tombstone_gc.unwrap();
+                assert_eq!(tombstone_gc.mode, TombstoneGcMode::Repair);
+                assert_eq!(tombstone_gc.propagation_delay_in_seconds, Some(3600));
+                assert_eq!(options.get("comment").unwrap().evaluate::<String>().unwrap(), "a AND b");
+                assert_eq!(
+                    options.get("gc_grace_seconds").unwrap().evaluate::<i32>().unwrap(),
+                    86400
+                );
+            }
+            statement => panic!("unexpected statement: {:?}", statement),
+        }
+        let statement = "create materialized view v as select * from ks.t where k is not null and c is not null \
+                         primary key (c, k) per partition limit 10 with synchronous_updates = true";
+        match ScyllaStatement::parse(statement).unwrap().unwrap() {
+            ScyllaStatement::CreateMaterializedView {
+                keyspace,
+                select,
+                per_partition_limit,
+                options,
+                ..
+            } => {
+                assert!(keyspace.is_none());
+                assert_eq!(
+                    select,
+                    "select * from ks.t where k is not null and c is not null primary key (c, k)"
+                );
+                assert_eq!(per_partition_limit, Some(10));
+                assert_eq!(options.synchronous_updates, Some(true));
+            }
+            statement => panic!("unexpected statement: {:?}", statement),
+        }
+        let statement = "ALTER TABLE ks.t WITH per_partition_rate_limit = {'max_writes_per_second': 100}";
+        match ScyllaStatement::parse(statement).unwrap().unwrap() {
+            ScyllaStatement::Alter { object, options, .. } => {
+                assert_eq!(object, SchemaObject::Table);
+                assert_eq!(
+                    options.per_partition_rate_limit.unwrap().max_writes_per_second,
+                    Some(100)
+                );
+            }
+            statement => panic!("unexpected statement: {:?}", statement),
+        }
+        assert_eq!(
+            ScyllaStatement::parse("PRUNE MATERIALIZED VIEW ks.v WHERE v = 1 USING TIMEOUT 10s").unwrap(),
+            Some(ScyllaStatement::PruneMaterializedView {
+                keyspace: Some(Name::parse("ks").unwrap()),
+                name: Name::parse("v").unwrap(),
+                clauses: "WHERE v = 1 USING TIMEOUT 10s".to_string(),
+            })
+        );
+        assert!(ScyllaStatement::parse("ALTER TABLE ks.t ADD v text").unwrap().is_none());
+        assert!(ScyllaStatement::parse("ALTER TABLE ks.t WITH cdc = {'unknown': 1}").is_err());
+        assert!(ScyllaStatement::parse("CREATE TABLE ks.t (k text PRIMARY KEY").is_err());
+        assert!(ScyllaStatement::parse("CREATE TABLE ks.t (k text PRIMARY KEY) WITH a = 1 AND").is_err());
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the parsing of the Scylla service level statements, which prioritize the workloads of
+//! the roles they are attached to, ie `CREATE SERVICE LEVEL` or `ATTACH SERVICE LEVEL`.
+
+use super::scylla_ext::{split_options, Cursor};
+use crate::cql::{Name, Term};
+use anyhow::{anyhow, bail, ensure};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+
+/// The workload type of a service level, which lets Scylla favor either the latency or the throughput of its
+/// requests when it's overloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WorkloadType {
+    /// The requests aren't classified
+    Unspecified,
+    /// The latency sensitive requests, which are shed rather than queued when the node is overloaded
+    Interactive,
+    /// The throughput oriented requests, which are queued when the node is overloaded
+    Batch,
+}
+
+impl WorkloadType {
+    /// Get the option value of the workload type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkloadType::Unspecified => "unspecified",
+            WorkloadType::Interactive => "interactive",
+            WorkloadType::Batch => "batch",
+        }
+    }
+}
+
+impl FromStr for WorkloadType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "unspecified" => Ok(WorkloadType::Unspecified),
+            "interactive" => Ok(WorkloadType::Interactive),
+            "batch" => Ok(WorkloadType::Batch),
+            _ => bail!("Unknown workload type: {}", s),
+        }
+    }
+}
+
+/// The options of a service level, ie `WITH timeout = 30ms AND workload_type = 'interactive'`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceLevelOptions {
+    /// The timeout of the requests, where `Some(None)` removes the timeout, ie `timeout = null`
+    pub timeout: Option<Option<Duration>>,
+    /// The workload type of the requests
+    pub workload_type: Option<WorkloadType>,
+    /// The share of the resources of the requests, which is only supported by Scylla Enterprise
+    pub shares: Option<u32>,
+}
+
+impl ServiceLevelOptions {
+    /// Parse the options of the `WITH` clause, without the `WITH` keyword
+    pub fn parse(with: &str) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        for option in split_options(with)? {
+            let (name, value) = option
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid service level option: {}", option))?;
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                // the timeout is a duration literal, which isn't a term
+                "timeout" if value.eq_ignore_ascii_case("null") => options.timeout = Some(None),
+                "timeout" => options.timeout = Some(Some(parse_duration(value.trim_matches('\''))?)),
+                "workload_type" => options.workload_type = Some(Term::parse(value)?.evaluate::<String>()?.parse()?),
+                "shares" => options.shares = Some(Term::parse(value)?.evaluate()?),
+                name => bail!("Unknown service level option: {}", name),
+            }
+        }
+        Ok(options)
+    }
+    /// Check if there are no options
+    pub fn is_empty(&self) -> bool {
+        self.timeout.is_none() && self.workload_type.is_none() && self.shares.is_none()
+    }
+}
+
+impl Display for ServiceLevelOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut options = Vec::new();
+        match self.timeout {
+            Some(Some(timeout)) => options.push(format!("timeout = {}", format_duration(timeout))),
+            Some(None) => options.push("timeout = null".to_string()),
+            None => {}
+        }
+        if let Some(workload_type) = self.workload_type {
+            options.push(format!("workload_type = '{}'", workload_type.as_str()));
+        }
+        if let Some(shares) = self.shares {
+            options.push(format!("shares = {}", shares));
+        }
+        f.write_str(&options.join(" AND "))
+    }
+}
+
+/// A Scylla service level statement
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::statements::service_level::{ServiceLevelStatement, WorkloadType};
+/// use std::time::Duration;
+///
+/// let statement = "CREATE SERVICE LEVEL IF NOT EXISTS olap WITH timeout = 1s AND workload_type = 'batch'";
+/// match ServiceLevelStatement::parse(statement).unwrap().unwrap() {
+///     ServiceLevelStatement::Create { name, options, .. } => {
+///         assert_eq!(name.as_str(), "olap");
+///         assert_eq!(options.timeout, Some(Some(Duration::from_secs(1))));
+///         assert_eq!(options.workload_type, Some(WorkloadType::Batch));
+///     }
+///     _ => unreachable!(),
+/// }
+/// assert!(ServiceLevelStatement::parse("CREATE ROLE app").unwrap().is_none());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceLevelStatement {
+    /// `CREATE SERVICE LEVEL [IF NOT EXISTS] name [WITH ..]`
+    Create {
+        /// Whether the statement holds IF NOT EXISTS
+        if_not_exists: bool,
+        /// The service level name
+        name: Name,
+        /// The service level options
+        options: ServiceLevelOptions,
+    },
+    /// `ALTER SERVICE LEVEL name WITH ..`
+    Alter {
+        /// The service level name
+        name: Name,
+        /// The altered options
+        options: ServiceLevelOptions,
+    },
+    /// `DROP SERVICE LEVEL [IF EXISTS] name`
+    Drop {
+        /// Whether the statement holds IF EXISTS
+        if_exists: bool,
+        /// The service level name
+        name: Name,
+    },
+    /// `ATTACH SERVICE LEVEL name TO role`
+    Attach {
+        /// The service level name
+        name: Name,
+        /// The role which the service level is attached to
+        role: Name,
+    },
+    /// `DETACH SERVICE LEVEL FROM role`
+    Detach {
+        /// The role whose service level is detached
+        role: Name,
+    },
+    /// `LIST SERVICE LEVEL name` or `LIST ALL SERVICE LEVELS`
+    List {
+        /// The listed service level, or None to list all of them
+        name: Option<Name>,
+    },
+    /// `LIST ATTACHED SERVICE LEVEL OF role` or `LIST ALL ATTACHED SERVICE LEVELS`
+    ListAttached {
+        /// The role whose attached service level is listed, or None to list the ones of all the roles
+        role: Option<Name>,
+    },
+    /// `LIST EFFECTIVE SERVICE LEVEL OF role`, ie the service level which results from the ones of the role and of
+    /// the roles it's granted
+    ListEffective {
+        /// The role
+        role: Name,
+    },
+}
+
+impl ServiceLevelStatement {
+    /// Parse the statement, or return None if it's not a service level statement
+    pub fn parse(statement: &str) -> anyhow::Result<Option<Self>> {
+        let statement = statement.trim().trim_end_matches(';');
+        let mut cursor = Cursor::new(statement);
+        let parsed = if cursor.keywords(&["create", "service", "level"]) {
+            let if_not_exists = cursor.keywords(&["if", "not", "exists"]);
+            let name = cursor.name()?;
+            let options = if cursor.keywords(&["with"]) {
+                ServiceLevelOptions::parse(cursor.rest())?
+            } else {
+                ServiceLevelOptions::default()
+            };
+            ServiceLevelStatement::Create {
+                if_not_exists,
+                name,
+                options,
+            }
+        } else if cursor.keywords(&["alter", "service", "level"]) {
+            let name = cursor.name()?;
+            ensure!(cursor.keywords(&["with"]), "Expected WITH in: {}", statement);
+            ServiceLevelStatement::Alter {
+                name,
+                options: ServiceLevelOptions::parse(cursor.rest())?,
+            }
+        } else if cursor.keywords(&["drop", "service", "level"]) {
+            ServiceLevelStatement::Drop {
+                if_exists: cursor.keywords(&["if", "exists"]),
+                name: cursor.name()?,
+            }
+        } else if cursor.keywords(&["attach", "service", "level"]) {
+            let name = cursor.name()?;
+            ensure!(cursor.keywords(&["to"]), "Expected TO in: {}", statement);
+            ServiceLevelStatement::Attach {
+                name,
+                role: cursor.name()?,
+            }
+        } else if cursor.keywords(&["detach", "service", "level", "from"]) {
+            ServiceLevelStatement::Detach { role: cursor.name()? }
+        } else if cursor.keywords(&["list", "all", "service", "levels"]) {
+            ServiceLevelStatement::List { name: None }
+        } else if cursor.keywords(&["list", "service", "level"]) {
+            ServiceLevelStatement::List {
+                name: Some(cursor.name()?),
+            }
+        } else if cursor.keywords(&["list", "all", "attached", "service", "levels"]) {
+            ServiceLevelStatement::ListAttached { role: None }
+        } else if cursor.keywords(&["list", "attached", "service", "level", "of"]) {
+            ServiceLevelStatement::ListAttached {
+                role: Some(cursor.name()?),
+            }
+        } else if cursor.keywords(&["list", "effective", "service", "level", "of"]) {
+            ServiceLevelStatement::ListEffective { role: cursor.name()? }
+        } else {
+            return Ok(None);
+        };
+        let rest = cursor.rest();
+        ensure!(rest.is_empty(), "Unexpected chars: {}", rest);
+        Ok(Some(parsed))
+    }
+}
+
+impl Display for ServiceLevelStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceLevelStatement::Create {
+                if_not_exists,
+                name,
+                options,
+            } => {
+                write!(
+                    f,
+                    "CREATE SERVICE LEVEL {}{}",
+                    if *if_not_exists { "IF NOT EXISTS " } else { "" },
+                    name
+                )?;
+                if !options.is_empty() {
+                    write!(f, " WITH {}", options)?;
+                }
+                Ok(())
+            }
+            ServiceLevelStatement::Alter { name, options } => {
+                write!(f, "ALTER SERVICE LEVEL {} WITH {}", name, options)
+            }
+            ServiceLevelStatement::Drop { if_exists, name } => write!(
+                f,
+                "DROP SERVICE LEVEL {}{}",
+                if *if_exists { "IF EXISTS " } else { "" },
+                name
+            ),
+            ServiceLevelStatement::Attach { name, role } => write!(f, "ATTACH SERVICE LEVEL {} TO {}", name, role),
+            ServiceLevelStatement::Detach { role } => write!(f, "DETACH SERVICE LEVEL FROM {}", role),
+            ServiceLevelStatement::List { name: Some(name) } => write!(f, "LIST SERVICE LEVEL {}", name),
+            ServiceLevelStatement::List { name: None } => f.write_str("LIST ALL SERVICE LEVELS"),
+            ServiceLevelStatement::ListAttached { role: Some(role) } => {
+                write!(f, "LIST ATTACHED SERVICE LEVEL OF {}", role)
+            }
+            ServiceLevelStatement::ListAttached { role: None } => f.write_str("LIST ALL ATTACHED SERVICE LEVELS"),
+            ServiceLevelStatement::ListEffective { role } => write!(f, "LIST EFFECTIVE SERVICE LEVEL OF {}", role),
+        }
+    }
+}
+
+/// Parse the duration literal, ie `1h30m` or `500ms`
+fn parse_duration(literal: &str) -> anyhow::Result<Duration> {
+    ensure!(!literal.is_empty(), "Empty duration literal");
+    let mut duration = Duration::default();
+    let mut rest = literal;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let unit = rest[digits..]
+            .find(|c: char| c.is_ascii_digit())
+            .map_or(rest.len(), |len| digits + len);
+        let value: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| anyhow!("Invalid duration literal: {}", literal))?;
+        let nanos = match rest[digits..unit].to_ascii_lowercase().as_str() {
+            "d" => 86_400_000_000_000,
+            "h" => 3_600_000_000_000,
+            "m" => 60_000_000_000,
+            "s" => 1_000_000_000,
+            "ms" => 1_000_000,
+            "us" | "µs" => 1_000,
+            "ns" => 1,
+            unit => bail!("Invalid duration unit {} in: {}", unit, literal),
+        };
+        duration += Duration::from_nanos(
+            value
+                .checked_mul(nanos)
+                .ok_or_else(|| anyhow!("The duration literal {} overflows", literal))?,
+        );
+        rest = &rest[unit..];
+    }
+    Ok(duration)
+}
+
+/// Format the duration literal in its largest exact unit, ie `30ms`
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let nanos = duration.as_nanos();
+    if nanos.is_multiple_of(1_000_000) {
+        format!("{}ms", nanos / 1_000_000)
+    } else if nanos.is_multiple_of(1_000) {
+        format!("{}us", nanos / 1_000)
+    } else {
+        format!("{}ns", nanos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_level_statements() {
+        let statement =
+            "create service level sl with timeout = '1m30s' and workload_type = 'INTERACTIVE' and shares = 200;";
+        let parsed = ServiceLevelStatement::parse(statement).unwrap().unwrap();
+        assert_eq!(
+            parsed,
+            ServiceLevelStatement::Create {
+                if_not_exists: false,
+                name: Name::parse("sl").unwrap(),
+                options: ServiceLevelOptions {
+                    timeout: Some(Some(Duration::from_secs(90))),
+                    workload_type: Some(WorkloadType::Interactive),
+                    shares: Some(200),
+                },
+            }
+        );
+        assert_eq!(
+            parsed.to_string(),
+            "CREATE SERVICE LEVEL sl WITH timeout = 90000ms AND workload_type = 'interactive' AND shares = 200"
+        );
+        // the statements are rendered as they are parsed
+        for statement in [
+            "ALTER SERVICE LEVEL sl WITH timeout = null",
+            "DROP SERVICE LEVEL IF EXISTS \"Sl\"",
+            "ATTACH SERVICE LEVEL sl TO app",
+            "DETACH SERVICE LEVEL FROM app",
+            "LIST SERVICE LEVEL sl",
+            "LIST ALL SERVICE LEVELS",
+            "LIST ATTACHED SERVICE LEVEL OF app",
+            "LIST ALL ATTACHED SERVICE LEVELS",
+            "LIST EFFECTIVE SERVICE LEVEL OF app",
+        ] {
+            let parsed = ServiceLevelStatement::parse(statement).unwrap().unwrap();
+            assert_eq!(parsed.to_string(), statement);
+        }
+        assert_eq!(parse_duration("1h500us").unwrap(), Duration::from_micros(3_600_000_500));
+        assert!(ServiceLevelStatement::parse("SELECT * FROM system.local")
+            .unwrap()
+            .is_none());
+        assert!(ServiceLevelStatement::parse("ALTER SERVICE LEVEL sl").is_err());
+        assert!(ServiceLevelStatement::parse("CREATE SERVICE LEVEL sl WITH timeout = 10 parsecs").is_err());
+        assert!(ServiceLevelStatement::parse("CREATE SERVICE LEVEL sl WITH priority = 1").is_err());
+        assert!(ServiceLevelStatement::parse("ATTACH SERVICE LEVEL sl TO app, admin").is_err());
+    }
+}
@@ -0,0 +1,365 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the `Visitor` and `VisitorMut` traits, which walk the parsed statements and terms down to
+//! their names and constants, so a transformation only overrides the nodes it's interested in rather than matching
+//! every variant of every statement.
+//!
+//! Every node has a `visit_*` method, which is the one to override, and a `walk_*` method which visits its children.
+//! An overridden `visit_*` method calls the `walk_*` one to keep walking down the node.
+
+use super::{
+    schema::{
+        AlterTableInstruction, AlterTableStatement, ColumnDefinition, CreateKeyspaceStatement,
+        CreateMaterializedViewStatement, CreateTableStatement, KeyspaceSchema,
+    },
+    scylla_ext::{ScyllaOptions, ScyllaStatement},
+    service_level::ServiceLevelStatement,
+};
+use crate::cql::{Constant, Name, Term};
+
+/// Generate the visitor trait over the shared or the mutable references of the nodes
+macro_rules! visitor {
+    ($(#[$attr:meta])* $visitor:ident, [$($r:tt)+]) => {
+        $(#[$attr])*
+        pub trait $visitor {
+            /// Visit the schema of a keyspace
+            fn visit_keyspace_schema(&mut self, schema: $($r)+ KeyspaceSchema) {
+                self.walk_keyspace_schema(schema)
+            }
+            /// Visit the keyspace creation statement, then its table creation statements
+            fn walk_keyspace_schema(&mut self, schema: $($r)+ KeyspaceSchema) {
+                self.visit_create_keyspace($($r)+ schema.keyspace);
+                for table in $($r)+ schema.tables {
+                    self.visit_create_table(table);
+                }
+            }
+            /// Visit a `CREATE KEYSPACE` statement
+            fn visit_create_keyspace(&mut self, statement: $($r)+ CreateKeyspaceStatement) {
+                self.walk_create_keyspace(statement)
+            }
+            /// Visit the name and the replication of the keyspace
+            fn walk_create_keyspace(&mut self, statement: $($r)+ CreateKeyspaceStatement) {
+                self.visit_keyspace_name($($r)+ statement.name);
+                self.visit_term($($r)+ statement.replication);
+            }
+            /// Visit a `CREATE TABLE` statement
+            fn visit_create_table(&mut self, statement: $($r)+ CreateTableStatement) {
+                self.walk_create_table(statement)
+            }
+            /// Visit the name of the table, its columns, its primary key columns and its options
+            fn walk_create_table(&mut self, statement: $($r)+ CreateTableStatement) {
+                self.visit_table_name($($r)+ statement.keyspace, $($r)+ statement.name);
+                for column in $($r)+ statement.columns {
+                    self.visit_column_definition(column);
+                }
+                for column in $($r)+ statement.partition_key {
+                    self.visit_column_name(column);
+                }
+                for (column, _) in $($r)+ statement.clustering_key {
+                    self.visit_column_name(column);
+                }
+                self.visit_options($($r)+ statement.options);
+            }
+            /// Visit a `CREATE MATERIALIZED VIEW` statement
+            fn visit_create_materialized_view(&mut self, statement: $($r)+ CreateMaterializedViewStatement) {
+                self.walk_create_materialized_view(statement)
+            }
+            /// Visit the name of the view and of its base table, its selected columns, its primary key columns and
+            /// its options
+            fn walk_create_materialized_view(&mut self, statement: $($r)+ CreateMaterializedViewStatement) {
+                self.visit_table_name($($r)+ statement.keyspace, $($r)+ statement.name);
+                self.visit_table_name($($r)+ statement.base_keyspace, $($r)+ statement.base_table);
+                for column in $($r)+ statement.columns {
+                    self.visit_column_name(column);
+                }
+                for column in $($r)+ statement.partition_key {
+                    self.visit_column_name(column);
+                }
+                for (column, _) in $($r)+ statement.clustering_key {
+                    self.visit_column_name(column);
+                }
+                self.visit_options($($r)+ statement.options);
+            }
+            /// Visit an `ALTER TABLE` statement
+            fn visit_alter_table(&mut self, statement: $($r)+ AlterTableStatement) {
+                self.walk_alter_table(statement)
+            }
+            /// Visit the name of the table, then its added, dropped or renamed columns, or its options
+            fn walk_alter_table(&mut self, statement: $($r)+ AlterTableStatement) {
+                self.visit_table_name($($r)+ statement.keyspace, $($r)+ statement.name);
+                match $($r)+ statement.instruction {
+                    AlterTableInstruction::Add(columns) => {
+                        for column in columns {
+                            self.visit_column_definition(column);
+                        }
+                    }
+                    AlterTableInstruction::Drop(columns) => {
+                        for column in columns {
+                            self.visit_column_name(column);
+                        }
+                    }
+                    AlterTableInstruction::Rename(renames) => {
+                        for (from, to) in renames {
+                            self.visit_column_name(from);
+                            self.visit_column_name(to);
+                        }
+                    }
+                    AlterTableInstruction::With(options) => self.visit_options(options),
+                }
+            }
+            /// Visit a column definition of a `CREATE TABLE` or an `ALTER TABLE` statement
+            fn visit_column_definition(&mut self, column: $($r)+ ColumnDefinition) {
+                self.walk_column_definition(column)
+            }
+            /// Visit the name of the column
+            fn walk_column_definition(&mut self, column: $($r)+ ColumnDefinition) {
+                self.visit_column_name($($r)+ column.name);
+            }
+            /// Visit a Scylla schema statement
+            fn visit_scylla_statement(&mut self, statement: $($r)+ ScyllaStatement) {
+                self.walk_scylla_statement(statement)
+            }
+            /// Visit the name of the table or the view, and its options
+            fn walk_scylla_statement(&mut self, statement: $($r)+ ScyllaStatement) {
+                match statement {
+                    ScyllaStatement::CreateTable { keyspace, name, options, .. }
+                    | ScyllaStatement::CreateMaterializedView { keyspace, name, options, .. }
+                    | ScyllaStatement::Alter { keyspace, name, options, .. } => {
+                        self.visit_table_name(keyspace, name);
+                        self.visit_options(options);
+                    }
+                    ScyllaStatement::PruneMaterializedView { keyspace, name, .. } => {
+                        self.visit_table_name(keyspace, name)
+                    }
+                }
+            }
+            /// Visit a service level statement
+            fn visit_service_level_statement(&mut self, statement: $($r)+ ServiceLevelStatement) {
+                self.walk_service_level_statement(statement)
+            }
+            /// Visit the names of the service level and of the role
+            fn walk_service_level_statement(&mut self, statement: $($r)+ ServiceLevelStatement) {
+                match statement {
+                    ServiceLevelStatement::Create { name, .. }
+                    | ServiceLevelStatement::Alter { name, .. }
+                    | ServiceLevelStatement::Drop { name, .. }
+                    | ServiceLevelStatement::List { name: Some(name) } => self.visit_service_level_name(name),
+                    ServiceLevelStatement::Attach { name, role } => {
+                        self.visit_service_level_name(name);
+                        self.visit_role_name(role);
+                    }
+                    ServiceLevelStatement::Detach { role }
+                    | ServiceLevelStatement::ListAttached { role: Some(role) }
+                    | ServiceLevelStatement::ListEffective { role } => self.visit_role_name(role),
+                    ServiceLevelStatement::List { name: None }
+                    | ServiceLevelStatement::ListAttached { role: None } => {}
+                }
+            }
+            /// Visit the options of a table or a view
+            fn visit_options(&mut self, options: $($r)+ ScyllaOptions) {
+                self.walk_options(options)
+            }
+            /// Visit the values of the options which aren't parsed into the Scylla extensions
+            fn walk_options(&mut self, options: $($r)+ ScyllaOptions) {
+                for (_, value) in $($r)+ options.options {
+                    self.visit_term(value);
+                }
+            }
+            /// Visit a term
+            fn visit_term(&mut self, term: $($r)+ Term) {
+                self.walk_term(term)
+            }
+            /// Visit the constant or the nested terms of the term
+            fn walk_term(&mut self, term: $($r)+ Term) {
+                match term {
+                    Term::Constant(constant) => self.visit_constant(constant),
+                    Term::List(terms) | Term::Set(terms) => {
+                        for term in terms {
+                            self.visit_term(term);
+                        }
+                    }
+                    Term::Map(entries) => {
+                        for (key, value) in entries {
+                            self.visit_term(key);
+                            self.visit_term(value);
+                        }
+                    }
+                    Term::Neg(term) => self.visit_term(term),
+                    Term::Binary(left, _, right) => {
+                        self.visit_term(left);
+                        self.visit_term(right);
+                    }
+                }
+            }
+            /// Visit a constant
+            fn visit_constant(&mut self, _constant: $($r)+ Constant) {}
+            /// Visit the name of a created keyspace
+            fn visit_keyspace_name(&mut self, _name: $($r)+ Name) {}
+            /// Visit the name of a table or a view, along with its keyspace if it's qualified
+            fn visit_table_name(&mut self, _keyspace: $($r)+ Option<Name>, _name: $($r)+ Name) {}
+            /// Visit the name of a column
+            fn visit_column_name(&mut self, _name: $($r)+ Name) {}
+            /// Visit the name of a service level
+            fn visit_service_level_name(&mut self, _name: $($r)+ Name) {}
+            /// Visit the name of a role
+            fn visit_role_name(&mut self, _name: $($r)+ Name) {}
+        }
+    };
+}
+
+visitor!(
+    /// The visitor of the statements and the terms, which walks them by their shared references
+    ///
+    /// ## Examples
+    /// ```
+    /// use scylla_rs::cql::{
+    ///     statements::{scylla_ext::ScyllaStatement, visit::Visitor},
+    ///     Name,
+    /// };
+    ///
+    /// #[derive(Default)]
+    /// struct Tables(Vec<String>);
+    ///
+    /// impl Visitor for Tables {
+    ///     fn visit_table_name(&mut self, keyspace: &Option<Name>, name: &Name) {
+    ///         self.0.push(match keyspace {
+    ///             Some(keyspace) => format!("{}.{}", keyspace, name),
+    ///             None => name.to_string(),
+    ///         });
+    ///     }
+    /// }
+    ///
+    /// let statement = ScyllaStatement::parse("ALTER TABLE shop.orders WITH cdc = {'enabled': true}")
+    ///     .unwrap()
+    ///     .unwrap();
+    /// let mut tables = Tables::default();
+    /// tables.visit_scylla_statement(&statement);
+    /// assert_eq!(tables.0, vec!["shop.orders"]);
+    /// ```
+    Visitor,
+    [&]
+);
+
+visitor!(
+    /// The visitor of the statements and the terms, which walks them by their mutable references so it can rewrite
+    /// them
+    ///
+    /// ## Examples
+    /// ```
+    /// use scylla_rs::cql::{
+    ///     statements::{schema::CreateTableStatement, visit::VisitorMut},
+    ///     Name,
+    /// };
+    ///
+    /// /// Qualify the unqualified tables with the keyspace
+    /// struct Qualify(Name);
+    ///
+    /// impl VisitorMut for Qualify {
+    ///     fn visit_table_name(&mut self, keyspace: &mut Option<Name>, _name: &mut Name) {
+    ///         keyspace.get_or_insert_with(|| self.0.clone());
+    ///     }
+    /// }
+    ///
+    /// let mut statement = CreateTableStatement::parse("CREATE TABLE orders (id int PRIMARY KEY)").unwrap();
+    /// Qualify(Name::parse("shop").unwrap()).visit_create_table(&mut statement);
+    /// assert_eq!(statement.keyspace.unwrap().as_str(), "shop");
+    /// ```
+    VisitorMut,
+    [&mut]
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::Operator;
+
+    #[derive(Default)]
+    struct Names {
+        tables: Vec<String>,
+        columns: Vec<String>,
+        roles: Vec<String>,
+        constants: Vec<Constant>,
+    }
+
+    impl Visitor for Names {
+        fn visit_table_name(&mut self, _keyspace: &Option<Name>, name: &Name) {
+            self.tables.push(name.to_string());
+        }
+        fn visit_column_name(&mut self, name: &Name) {
+            self.columns.push(name.to_string());
+        }
+        fn visit_role_name(&mut self, name: &Name) {
+            self.roles.push(name.to_string());
+        }
+        fn visit_constant(&mut self, constant: &Constant) {
+            self.constants.push(constant.clone());
+        }
+    }
+
+    /// Fold the arithmetic operations into their constants
+    struct Fold;
+
+    impl VisitorMut for Fold {
+        fn visit_term(&mut self, term: &mut Term) {
+            if let Term::Binary(..) | Term::Neg(_) = term {
+                if let Ok(constant) = term.fold() {
+                    *term = Term::Constant(constant);
+                    return;
+                }
+            }
+            self.walk_term(term)
+        }
+    }
+
+    #[test]
+    fn visited_statements() {
+        let schema = KeyspaceSchema::from_statements(&[
+            "CREATE KEYSPACE shop WITH replication = {'class': 'SimpleStrategy', 'replication_factor': 3}",
+            "CREATE TABLE shop.orders (customer text, id int, total double, PRIMARY KEY (customer, id)) \
+             WITH gc_grace_seconds = 60 * 60",
+        ])
+        .unwrap();
+        let mut names = Names::default();
+        names.visit_keyspace_schema(&schema);
+        assert_eq!(names.tables, vec!["orders"]);
+        assert_eq!(names.columns, vec!["customer", "id", "total", "customer", "id"]);
+        assert!(names.constants.contains(&Constant::Integer(3)));
+        assert!(names.constants.contains(&Constant::Integer(60)));
+        let statement = ServiceLevelStatement::parse("ATTACH SERVICE LEVEL gold TO app")
+            .unwrap()
+            .unwrap();
+        names.visit_service_level_statement(&statement);
+        assert_eq!(names.roles, vec!["app"]);
+        let statement = AlterTableStatement::parse("ALTER TABLE shop.orders RENAME id TO order_id").unwrap();
+        names.visit_alter_table(&statement);
+        assert_eq!(names.tables, vec!["orders", "orders"]);
+        assert!(names.columns.ends_with(&["id".to_string(), "order_id".to_string()]));
+        let statement = CreateMaterializedViewStatement::parse(
+            "CREATE MATERIALIZED VIEW shop.by_id AS SELECT * FROM shop.orders \
+             WHERE id IS NOT NULL AND customer IS NOT NULL PRIMARY KEY (id, customer)",
+        )
+        .unwrap();
+        names.visit_create_materialized_view(&statement);
+        assert_eq!(names.tables, vec!["orders", "orders", "by_id", "orders"]);
+        assert!(names
+            .columns
+            .ends_with(&["order_id".to_string(), "id".to_string(), "customer".to_string()]));
+        // rewrite the options of the table
+        let mut table = schema.tables[0].clone();
+        let gc_grace_seconds = |table: &CreateTableStatement| {
+            table
+                .options
+                .options
+                .iter()
+                .find(|(name, _)| name == "gc_grace_seconds")
+                .map(|(_, value)| value.clone())
+        };
+        assert!(matches!(
+            gc_grace_seconds(&table),
+            Some(Term::Binary(_, Operator::Mul, _))
+        ));
+        Fold.visit_create_table(&mut table);
+        assert_eq!(gc_grace_seconds(&table), Some(Term::Constant(Constant::Integer(3600))));
+    }
+}
@@ -0,0 +1,818 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the parsing and the evaluation of the CQL terms, ie the constants, the collection
+//! literals and the arithmetic operations over the constants, as found in the statement options.
+
+use super::{LitStr, Name};
+use anyhow::{anyhow, bail, ensure};
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt::{Display, Formatter},
+    iter::Peekable,
+    str::{Chars, FromStr},
+};
+
+/// A CQL constant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    /// The `null` constant
+    Null,
+    /// A `true` or `false` constant
+    Boolean(bool),
+    /// An integer constant, which is wide enough to hold any `bigint` and its intermediate folding results
+    Integer(i128),
+    /// A float constant, including `NaN` and `Infinity`
+    Float(f64),
+    /// A string constant, which is unescaped
+    String(String),
+    /// A uuid constant
+    Uuid([u8; 16]),
+    /// A blob constant, ie `0xcafe`
+    Blob(Vec<u8>),
+}
+
+impl Constant {
+    /// Get the kind of the constant
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Constant::Null => "null",
+            Constant::Boolean(_) => "boolean",
+            Constant::Integer(_) => "integer",
+            Constant::Float(_) => "float",
+            Constant::String(_) => "string",
+            Constant::Uuid(_) => "uuid",
+            Constant::Blob(_) => "blob",
+        }
+    }
+    /// Convert the constant into the T type
+    pub fn to<'a, T: TryFrom<&'a Constant, Error = anyhow::Error>>(&'a self) -> anyhow::Result<T> {
+        self.try_into()
+    }
+    fn mismatch(&self, target: &str) -> anyhow::Error {
+        anyhow!(
+            "Expected a {} constant, found the {} constant {}",
+            target,
+            self.kind(),
+            self
+        )
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constant::Null => f.write_str("null"),
+            Constant::Boolean(v) => write!(f, "{}", v),
+            Constant::Integer(v) => write!(f, "{}", v),
+            Constant::Float(v) if v.is_nan() => f.write_str("NaN"),
+            Constant::Float(v) if v.is_infinite() => f.write_str(if *v > 0.0 { "Infinity" } else { "-Infinity" }),
+            Constant::Float(v) if v.fract() == 0.0 => write!(f, "{:.1}", v),
+            Constant::Float(v) => write!(f, "{}", v),
+            Constant::String(v) => write!(f, "{}", LitStr::new(v.as_str())),
+            Constant::Uuid(v) => {
+                for (i, byte) in v.iter().enumerate() {
+                    if [4, 6, 8, 10].contains(&i) {
+                        f.write_str("-")?;
+                    }
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            Constant::Blob(v) => {
+                f.write_str("0x")?;
+                v.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+            }
+        }
+    }
+}
+
+macro_rules! try_from_integer_constant {
+    ($($t:ty),+) => {
+        $(
+            impl TryFrom<&Constant> for $t {
+                type Error = anyhow::Error;
+                fn try_from(constant: &Constant) -> anyhow::Result<Self> {
+                    match constant {
+                        Constant::Integer(v) => <$t>::try_from(*v)
+                            .map_err(|_| anyhow!("The integer constant {} is out of the {} range", v, stringify!($t))),
+                        _ => Err(constant.mismatch(stringify!($t))),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+try_from_integer_constant!(i8, i16, i32, i64, i128, u8, u16, u32, u64, usize);
+
+macro_rules! try_from_float_constant {
+    ($($t:ty: $max_exact:expr),+) => {
+        $(
+            impl TryFrom<&Constant> for $t {
+                type Error = anyhow::Error;
+                fn try_from(constant: &Constant) -> anyhow::Result<Self> {
+                    match constant {
+                        Constant::Float(v) => Ok(*v as $t),
+                        // only the integers which are exactly representable are converted
+                        Constant::Integer(v) if v.abs() <= $max_exact => Ok(*v as $t),
+                        Constant::Integer(v) => bail!("The integer constant {} can't be exactly represented as {}", v, stringify!($t)),
+                        _ => Err(constant.mismatch(stringify!($t))),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+try_from_float_constant!(f32: 1 << 24, f64: 1 << 53);
+
+impl TryFrom<&Constant> for bool {
+    type Error = anyhow::Error;
+    fn try_from(constant: &Constant) -> anyhow::Result<Self> {
+        match constant {
+            Constant::Boolean(v) => Ok(*v),
+            // the boolean options are commonly written as strings, ie `'enabled': 'true'`
+            Constant::String(v) if v.eq_ignore_ascii_case("true") => Ok(true),
+            Constant::String(v) if v.eq_ignore_ascii_case("false") => Ok(false),
+            _ => Err(constant.mismatch("bool")),
+        }
+    }
+}
+
+impl TryFrom<&Constant> for String {
+    type Error = anyhow::Error;
+    fn try_from(constant: &Constant) -> anyhow::Result<Self> {
+        match constant {
+            Constant::String(v) => Ok(v.clone()),
+            _ => Err(constant.mismatch("String")),
+        }
+    }
+}
+
+impl TryFrom<&Constant> for [u8; 16] {
+    type Error = anyhow::Error;
+    fn try_from(constant: &Constant) -> anyhow::Result<Self> {
+        match constant {
+            Constant::Uuid(v) => Ok(*v),
+            _ => Err(constant.mismatch("uuid")),
+        }
+    }
+}
+
+impl TryFrom<&Constant> for Vec<u8> {
+    type Error = anyhow::Error;
+    fn try_from(constant: &Constant) -> anyhow::Result<Self> {
+        match constant {
+            Constant::Blob(v) => Ok(v.clone()),
+            _ => Err(constant.mismatch("blob")),
+        }
+    }
+}
+
+impl<T> TryFrom<&Constant> for Option<T>
+where
+    T: for<'a> TryFrom<&'a Constant, Error = anyhow::Error>,
+{
+    type Error = anyhow::Error;
+    fn try_from(constant: &Constant) -> anyhow::Result<Self> {
+        match constant {
+            Constant::Null => Ok(None),
+            _ => T::try_from(constant).map(Some),
+        }
+    }
+}
+
+/// An arithmetic operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `%`
+    Rem,
+}
+
+impl Operator {
+    fn symbol(self) -> char {
+        match self {
+            Operator::Add => '+',
+            Operator::Sub => '-',
+            Operator::Mul => '*',
+            Operator::Div => '/',
+            Operator::Rem => '%',
+        }
+    }
+    fn apply(self, lhs: &Constant, rhs: &Constant) -> anyhow::Result<Constant> {
+        match (lhs, rhs) {
+            (Constant::Integer(l), Constant::Integer(r)) => {
+                let (l, r) = (*l, *r);
+                let result = match self {
+                    Operator::Add => l.checked_add(r),
+                    Operator::Sub => l.checked_sub(r),
+                    Operator::Mul => l.checked_mul(r),
+                    Operator::Div | Operator::Rem if r == 0 => bail!("Division by zero: {} {} {}", l, self.symbol(), r),
+                    Operator::Div => l.checked_div(r),
+                    Operator::Rem => l.checked_rem(r),
+                };
+                result
+                    .map(Constant::Integer)
+                    .ok_or_else(|| anyhow!("Integer overflow: {} {} {}", l, self.symbol(), r))
+            }
+            (Constant::Integer(_), Constant::Float(_))
+            | (Constant::Float(_), Constant::Integer(_))
+            | (Constant::Float(_), Constant::Float(_)) => {
+                let (l, r) = (float(lhs), float(rhs));
+                Ok(Constant::Float(match self {
+                    Operator::Add => l + r,
+                    Operator::Sub => l - r,
+                    Operator::Mul => l * r,
+                    Operator::Div => l / r,
+                    Operator::Rem => l % r,
+                }))
+            }
+            _ => bail!(
+                "Cannot apply {} to the {} constant {} and the {} constant {}",
+                self.symbol(),
+                lhs.kind(),
+                lhs,
+                rhs.kind(),
+                rhs
+            ),
+        }
+    }
+}
+
+fn float(constant: &Constant) -> f64 {
+    match constant {
+        Constant::Integer(v) => *v as f64,
+        Constant::Float(v) => *v,
+        _ => f64::NAN,
+    }
+}
+
+/// A CQL term, ie a constant, a collection literal, or an arithmetic operation which can be folded into a
+/// constant.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::{Constant, Term};
+///
+/// let term: Term = "60 * 60 * 24".parse().unwrap();
+/// assert_eq!(term.fold().unwrap(), Constant::Integer(86400));
+/// assert_eq!(term.evaluate::<i32>().unwrap(), 86400);
+/// assert!(Term::parse("1 + 'a'").unwrap().fold().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// A constant
+    Constant(Constant),
+    /// A list literal, ie `[1, 2]`
+    List(Vec<Term>),
+    /// A set literal, ie `{1, 2}`
+    Set(Vec<Term>),
+    /// A map literal, ie `{'class': 'SimpleStrategy'}`
+    Map(Vec<(Term, Term)>),
+    /// A negated term, ie `-(1 + 2)`
+    Neg(Box<Term>),
+    /// A binary arithmetic operation
+    Binary(Box<Term>, Operator, Box<Term>),
+}
+
+impl Term {
+    /// Parse the term
+    pub fn parse(term: &str) -> anyhow::Result<Self> {
+        let mut parser = Parser {
+            chars: term.chars().peekable(),
+        };
+        let parsed = parser.expression()?;
+        parser.skip_whitespaces();
+        ensure!(
+            parser.chars.peek().is_none(),
+            "Unexpected chars after the term: {}",
+            term
+        );
+        Ok(parsed)
+    }
+    /// Fold the term into a constant, evaluating its arithmetic operations
+    pub fn fold(&self) -> anyhow::Result<Constant> {
+        match self {
+            Term::Constant(constant) => Ok(constant.clone()),
+            Term::Neg(term) => match term.fold()? {
+                Constant::Integer(v) => v
+                    .checked_neg()
+                    .map(Constant::Integer)
+                    .ok_or_else(|| anyhow!("Integer overflow: -{}", v)),
+                Constant::Float(v) => Ok(Constant::Float(-v)),
+                constant => bail!("Cannot negate the {} constant {}", constant.kind(), constant),
+            },
+            Term::Binary(lhs, operator, rhs) => operator.apply(&lhs.fold()?, &rhs.fold()?),
+            _ => bail!("Cannot fold the collection {} into a constant", self),
+        }
+    }
+    /// Fold the term into a constant, and convert it into the T type
+    pub fn evaluate<T: for<'a> TryFrom<&'a Constant, Error = anyhow::Error>>(&self) -> anyhow::Result<T> {
+        T::try_from(&self.fold()?)
+    }
+    /// Fold the arithmetic operations of the term, including the ones of its collection elements
+    pub fn folded(&self) -> anyhow::Result<Term> {
+        Ok(match self {
+            Term::List(list) => Term::List(list.iter().map(Term::folded).collect::<anyhow::Result<_>>()?),
+            Term::Set(set) => Term::Set(set.iter().map(Term::folded).collect::<anyhow::Result<_>>()?),
+            Term::Map(map) => Term::Map(
+                map.iter()
+                    .map(|(k, v)| Ok((k.folded()?, v.folded()?)))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            _ => Term::Constant(self.fold()?),
+        })
+    }
+    /// Get the map entries of the map term, ie the ones of an option like `compaction`
+    pub fn as_map(&self) -> anyhow::Result<&[(Term, Term)]> {
+        match self {
+            Term::Map(map) => Ok(map),
+            _ => bail!("Expected a map, found {}", self),
+        }
+    }
+    /// Get the value of the map term entry by its string key
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<&Term>> {
+        Ok(self
+            .as_map()?
+            .iter()
+            .find(|(k, _)| matches!(k, Term::Constant(Constant::String(k)) if k == key))
+            .map(|(_, v)| v))
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn join(f: &mut Formatter<'_>, terms: &[Term]) -> std::fmt::Result {
+            for (i, term) in terms.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", term)?;
+            }
+            Ok(())
+        }
+        match self {
+            Term::Constant(constant) => write!(f, "{}", constant),
+            Term::List(list) => {
+                f.write_str("[")?;
+                join(f, list)?;
+                f.write_str("]")
+            }
+            Term::Set(set) => {
+                f.write_str("{")?;
+                join(f, set)?;
+                f.write_str("}")
+            }
+            Term::Map(map) => {
+                f.write_str("{")?;
+                for (i, (k, v)) in map.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}: {}", k, v)?;
+                }
+                f.write_str("}")
+            }
+            Term::Neg(term) => match **term {
+                Term::Binary(..) => write!(f, "-({})", term),
+                _ => write!(f, "-{}", term),
+            },
+            Term::Binary(lhs, operator, rhs) => {
+                let precedence = |term: &Term| match term {
+                    Term::Binary(_, Operator::Add, _) | Term::Binary(_, Operator::Sub, _) => 1,
+                    Term::Binary(..) => 2,
+                    _ => 3,
+                };
+                let own = precedence(self);
+                if precedence(lhs) < own {
+                    write!(f, "({})", lhs)?;
+                } else {
+                    write!(f, "{}", lhs)?;
+                }
+                write!(f, " {} ", operator.symbol())?;
+                // the operators are left associative
+                if precedence(rhs) <= own {
+                    write!(f, "({})", rhs)
+                } else {
+                    write!(f, "{}", rhs)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Term {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Term::parse(s)
+    }
+}
+
+impl From<Constant> for Term {
+    fn from(constant: Constant) -> Self {
+        Term::Constant(constant)
+    }
+}
+
+impl TryFrom<&Term> for Constant {
+    type Error = anyhow::Error;
+    fn try_from(term: &Term) -> anyhow::Result<Self> {
+        term.fold()
+    }
+}
+
+/// Parse the options of a `WITH` clause, ie `gc_grace_seconds = 60 * 60 * 24 AND compaction = {..}`, into
+/// their names and terms. The leading `WITH` keyword is optional.
+///
+/// Note: the clustering order option, ie `CLUSTERING ORDER BY (..)`, isn't a term, therefore it's rejected.
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::cql::parse_options;
+///
+/// let options = parse_options("WITH gc_grace_seconds = 60 * 60 * 24 AND compaction = {'class': 'LeveledCompactionStrategy'}")?;
+/// assert_eq!(options[0].0, "gc_grace_seconds");
+/// assert_eq!(options[0].1.evaluate::<i32>()?, 86400);
+/// assert_eq!(options[1].1.get("class")?.unwrap().evaluate::<String>()?, "LeveledCompactionStrategy");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn parse_options(options: &str) -> anyhow::Result<Vec<(String, Term)>> {
+    let mut parser = Parser {
+        chars: options.chars().peekable(),
+    };
+    let mut parsed = Vec::new();
+    parser.skip_whitespaces();
+    let first = parser.word();
+    if !first.eq_ignore_ascii_case("with") {
+        parser = Parser {
+            chars: options.chars().peekable(),
+        };
+    }
+    loop {
+        parser.skip_whitespaces();
+        let name = parser.identifier()?;
+        parser.expect('=')?;
+        parsed.push((name, parser.expression()?));
+        parser.skip_whitespaces();
+        if parser.chars.peek().is_none() {
+            return Ok(parsed);
+        }
+        let and = parser.word();
+        ensure!(
+            and.eq_ignore_ascii_case("and"),
+            "Expected AND between the options, found: {}",
+            and
+        );
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespaces(&mut self) {
+        while matches!(self.chars.peek(), Some(c) if c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+    fn expect(&mut self, expected: char) -> anyhow::Result<()> {
+        self.skip_whitespaces();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => bail!("Expected '{}', found '{}'", expected, c),
+            None => bail!("Expected '{}', found the end of the term", expected),
+        }
+    }
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespaces();
+        if self.chars.peek() == Some(&expected) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+    /// Read the alphanumeric word, which also holds the hyphens of the uuids
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.chars.peek() {
+            if c.is_ascii_alphanumeric() || *c == '_' || *c == '.' || (*c == '-' && is_uuid_prefix(&word)) {
+                word.push(*c);
+                self.chars.next();
+            } else if matches!(*c, '+' | '-') && word.ends_with(['e', 'E']) && is_number(&word) {
+                // the sign of the float exponent
+                word.push(*c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        word
+    }
+    fn identifier(&mut self) -> anyhow::Result<String> {
+        self.skip_whitespaces();
+        let identifier = if self.chars.peek() == Some(&'"') {
+            let mut quoted = String::from('"');
+            self.chars.next();
+            loop {
+                match self.chars.next() {
+                    Some('"') if self.chars.peek() == Some(&'"') => {
+                        self.chars.next();
+                        quoted.push_str("\"\"");
+                    }
+                    Some('"') => break,
+                    Some(c) => quoted.push(c),
+                    None => bail!("Unterminated quoted identifier: {}", quoted),
+                }
+            }
+            quoted.push('"');
+            quoted
+        } else {
+            self.word()
+        };
+        Ok(Name::parse(&identifier)?.as_str().to_string())
+    }
+    fn expression(&mut self) -> anyhow::Result<Term> {
+        let mut lhs = self.product()?;
+        loop {
+            let operator = if self.eat('+') {
+                Operator::Add
+            } else if self.eat('-') {
+                Operator::Sub
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Term::Binary(Box::new(lhs), operator, Box::new(self.product()?));
+        }
+    }
+    fn product(&mut self) -> anyhow::Result<Term> {
+        let mut lhs = self.unary()?;
+        loop {
+            let operator = if self.eat('*') {
+                Operator::Mul
+            } else if self.eat('/') {
+                Operator::Div
+            } else if self.eat('%') {
+                Operator::Rem
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Term::Binary(Box::new(lhs), operator, Box::new(self.unary()?));
+        }
+    }
+    fn unary(&mut self) -> anyhow::Result<Term> {
+        if self.eat('-') {
+            Ok(match self.unary()? {
+                // fold the negative numeric constants right away, ie -9223372036854775808
+                Term::Constant(Constant::Integer(v)) => Term::Constant(Constant::Integer(-v)),
+                Term::Constant(Constant::Float(v)) => Term::Constant(Constant::Float(-v)),
+                term => Term::Neg(Box::new(term)),
+            })
+        } else {
+            self.primary()
+        }
+    }
+    fn primary(&mut self) -> anyhow::Result<Term> {
+        self.skip_whitespaces();
+        match self.chars.peek().copied() {
+            Some('(') => {
+                self.chars.next();
+                let term = self.expression()?;
+                self.expect(')')?;
+                Ok(term)
+            }
+            Some('[') => {
+                self.chars.next();
+                Ok(Term::List(self.elements(']')?))
+            }
+            Some('{') => {
+                self.chars.next();
+                if self.eat('}') {
+                    return Ok(Term::Map(Vec::new()));
+                }
+                let first = self.expression()?;
+                if self.eat(':') {
+                    let mut map = vec![(first, self.expression()?)];
+                    while self.eat(',') {
+                        let key = self.expression()?;
+                        self.expect(':')?;
+                        map.push((key, self.expression()?));
+                    }
+                    self.expect('}')?;
+                    Ok(Term::Map(map))
+                } else {
+                    let mut set = vec![first];
+                    while self.eat(',') {
+                        set.push(self.expression()?);
+                    }
+                    self.expect('}')?;
+                    Ok(Term::Set(set))
+                }
+            }
+            Some('\'') => {
+                let mut literal = String::new();
+                self.chars.next();
+                loop {
+                    match self.chars.next() {
+                        Some('\'') if self.chars.peek() == Some(&'\'') => {
+                            self.chars.next();
+                            literal.push('\'');
+                        }
+                        Some('\'') => break,
+                        Some(c) => literal.push(c),
+                        None => bail!("Unterminated string constant: '{}", literal),
+                    }
+                }
+                Ok(Term::Constant(Constant::String(literal)))
+            }
+            Some('$') => {
+                self.chars.next();
+                ensure!(self.chars.next() == Some('$'), "Expected a $$ string constant");
+                let mut literal = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some('$') if self.chars.peek() == Some(&'$') => {
+                            self.chars.next();
+                            break;
+                        }
+                        Some(c) => literal.push(c),
+                        None => bail!("Unterminated $$ string constant: $${}", literal),
+                    }
+                }
+                Ok(Term::Constant(Constant::String(literal)))
+            }
+            Some(_) => {
+                let word = self.word();
+                ensure!(!word.is_empty(), "Expected a term");
+                Ok(Term::Constant(constant(&word)?))
+            }
+            None => bail!("Expected a term, found the end of the term"),
+        }
+    }
+    fn elements(&mut self, end: char) -> anyhow::Result<Vec<Term>> {
+        let mut elements = Vec::new();
+        if self.eat(end) {
+            return Ok(elements);
+        }
+        loop {
+            elements.push(self.expression()?);
+            if !self.eat(',') {
+                self.expect(end)?;
+                return Ok(elements);
+            }
+        }
+    }
+}
+
+/// Check if the word is a prefix of an hyphenated uuid, ie its hyphens are well placed
+fn is_uuid_prefix(word: &str) -> bool {
+    let groups: Vec<&str> = word.split('-').collect();
+    let lens = [8, 4, 4, 4, 12];
+    groups.len() < lens.len()
+        && groups
+            .iter()
+            .zip(lens.iter())
+            .all(|(group, len)| group.len() == *len && group.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn is_number(word: &str) -> bool {
+    matches!(word.bytes().next(), Some(b) if b.is_ascii_digit())
+}
+
+/// Parse the constant of the word, ie a number, a boolean, a uuid, a blob or null
+fn constant(word: &str) -> anyhow::Result<Constant> {
+    let lowercase = word.to_ascii_lowercase();
+    Ok(match lowercase.as_str() {
+        "null" => Constant::Null,
+        "true" => Constant::Boolean(true),
+        "false" => Constant::Boolean(false),
+        "nan" => Constant::Float(f64::NAN),
+        "infinity" => Constant::Float(f64::INFINITY),
+        _ if lowercase.starts_with("0x") => {
+            let hex = &lowercase[2..];
+            ensure!(
+                hex.len() & 1 == 0 && hex.bytes().all(|b| b.is_ascii_hexdigit()),
+                "Invalid blob constant: {}",
+                word
+            );
+            Constant::Blob(
+                (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        _ if lowercase.len() == 36 && lowercase.matches('-').count() == 4 => {
+            let hex: String = lowercase.split('-').collect();
+            ensure!(
+                is_uuid_prefix(&lowercase[..23]) && hex.len() == 32 && hex.bytes().all(|b| b.is_ascii_hexdigit()),
+                "Invalid uuid constant: {}",
+                word
+            );
+            let mut uuid = [0; 16];
+            for (i, byte) in uuid.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)?;
+            }
+            Constant::Uuid(uuid)
+        }
+        _ if lowercase.bytes().all(|b| b.is_ascii_digit()) => Constant::Integer(
+            lowercase
+                .parse()
+                .map_err(|_| anyhow!("The integer constant {} is out of range", word))?,
+        ),
+        _ if is_number(&lowercase) => Constant::Float(
+            lowercase
+                .parse()
+                .map_err(|_| anyhow!("Invalid float constant: {}", word))?,
+        ),
+        _ => bail!("Invalid constant: {}", word),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terms() {
+        // folding follows the precedence and the associativity of the operators
+        assert_eq!(Term::parse("60*60*24").unwrap().evaluate::<i64>().unwrap(), 86400);
+        assert_eq!(Term::parse("10 - 4 - 3").unwrap().evaluate::<i32>().unwrap(), 3);
+        assert_eq!(
+            Term::parse("2 + 3 * (4 - 1) % 5").unwrap().evaluate::<i32>().unwrap(),
+            6
+        );
+        assert_eq!(Term::parse("-(1 + 2)").unwrap().evaluate::<i8>().unwrap(), -3);
+        assert_eq!(Term::parse("1 / 2 + 0.5").unwrap().evaluate::<f64>().unwrap(), 0.5);
+        assert_eq!(Term::parse("1.5e2").unwrap().evaluate::<f32>().unwrap(), 150.0);
+        assert_eq!(
+            Term::parse("-9223372036854775808").unwrap().evaluate::<i64>().unwrap(),
+            i64::MIN
+        );
+        // descriptive errors
+        let error = Term::parse("300").unwrap().evaluate::<u8>().unwrap_err();
+        assert_eq!(error.to_string(), "The integer constant 300 is out of the u8 range");
+        let error = Term::parse("'a'").unwrap().evaluate::<i32>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Expected a i32 constant, found the string constant 'a'"
+        );
+        assert!(Term::parse("1 / 0").unwrap().fold().is_err());
+        assert!(Term::parse("9223372036854775807 * 9223372036854775807 * 4")
+            .unwrap()
+            .fold()
+            .is_err());
+        assert!(Term::parse("1 +").is_err());
+        assert!(Term::parse("(1").is_err());
+        // other constants
+        let uuid = "123e4567-e89b-12d3-a456-426614174000";
+        let term = Term::parse(uuid).unwrap();
+        assert_eq!(term.evaluate::<[u8; 16]>().unwrap()[..2], [0x12, 0x3e]);
+        assert_eq!(term.to_string(), uuid);
+        assert_eq!(
+            Term::parse("0xCAFE").unwrap().evaluate::<Vec<u8>>().unwrap(),
+            vec![0xca, 0xfe]
+        );
+        assert_eq!(Term::parse("'it''s'").unwrap().evaluate::<String>().unwrap(), "it's");
+        assert_eq!(Term::parse("$$it's$$").unwrap().evaluate::<String>().unwrap(), "it's");
+        assert_eq!(Term::parse("null").unwrap().evaluate::<Option<i32>>().unwrap(), None);
+        assert!(Term::parse("'true'").unwrap().evaluate::<bool>().unwrap());
+        // collections and rendering
+        let term = Term::parse("{'a': 1 + 1, 'b': [1, -2]}").unwrap();
+        assert_eq!(term.to_string(), "{'a': 1 + 1, 'b': [1, -2]}");
+        assert_eq!(term.folded().unwrap().to_string(), "{'a': 2, 'b': [1, -2]}");
+        assert_eq!(
+            Term::parse("(1 + 2) * -(3 - 4)").unwrap().to_string(),
+            "(1 + 2) * -(3 - 4)"
+        );
+        assert_eq!(Term::parse("1 - (2 - 3)").unwrap().to_string(), "1 - (2 - 3)");
+    }
+
+    #[test]
+    fn options() {
+        let options = parse_options(
+            "with \"Comment\" = 'a' AND default_time_to_live = 7 * 24 * 3600 and caching = {'keys': 'ALL'}",
+        )
+        .unwrap();
+        assert_eq!(options.len(), 3);
+        assert_eq!(options[0].0, "Comment");
+        assert_eq!(options[1].1.evaluate::<i32>().unwrap(), 604800);
+        assert_eq!(
+            options[2].1.get("keys").unwrap().unwrap().evaluate::<String>().unwrap(),
+            "ALL"
+        );
+        assert!(parse_options("a = 1 b = 2").is_err());
+        assert!(parse_options("a 1").is_err());
+    }
+}
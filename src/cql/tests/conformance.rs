@@ -0,0 +1,338 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! The conformance tests of the CQL parsing, which are enabled by the `conformance` feature:
+//! - the randomized round trips, which render the generated values and parse them back;
+//! - the checklist of the grammar productions, where the uncovered ones are ignored failing tests which track the
+//!   parser completeness, ie `cargo test --features conformance -- --ignored`.
+
+use crate::cql::{
+    parse_options, parse_script,
+    statements::{
+        schema::{AlterTableStatement, CreateIndexStatement},
+        scylla_ext::{
+            CdcDelta, CdcOptions, PerPartitionRateLimit, SchemaObject, ScyllaOptions, ScyllaStatement, TombstoneGc,
+            TombstoneGcMode,
+        },
+    },
+    Constant, LitStr, Name, Operator, Term,
+};
+use anyhow::{bail, ensure};
+use std::convert::TryInto;
+
+/// The iterations of every randomized round trip
+const ITERATIONS: usize = 2000;
+
+/// A xorshift generator, which is seeded so the failures are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+    fn chance(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+    fn string(&mut self) -> String {
+        const CHARS: [char; 14] = ['a', 'Z', '0', '_', ' ', '\'', '"', '$', ';', '-', '(', '{', 'é', '字'];
+        (0..self.below(8)).map(|_| *self.pick(&CHARS)).collect()
+    }
+    fn name(&mut self) -> Name {
+        match self.below(3) {
+            0 => Name::auto(&format!("t{}", self.below(100))),
+            1 => Name::auto(*self.pick(&["select", "token", "MyTable"])),
+            _ => Name::Quoted(format!("q{}", self.string())),
+        }
+    }
+    fn constant(&mut self) -> Constant {
+        match self.below(7) {
+            0 => Constant::Null,
+            1 => Constant::Boolean(self.chance(2)),
+            2 => Constant::Integer(self.next() as i64 as i128 >> self.below(64)),
+            3 => Constant::Float((self.next() as i32) as f64 / (1 << self.below(20)) as f64),
+            4 => Constant::String(self.string()),
+            5 => Constant::Uuid(self.next().to_be_bytes().repeat(2).try_into().unwrap()),
+            _ => Constant::Blob(self.next().to_be_bytes()[..self.below(9) as usize].to_vec()),
+        }
+    }
+    fn term(&mut self, depth: usize) -> Term {
+        if depth == 0 || self.chance(3) {
+            return Term::Constant(self.constant());
+        }
+        match self.below(5) {
+            0 => Term::List((0..self.below(4)).map(|_| self.term(depth - 1)).collect()),
+            // the empty braces are parsed as an empty map
+            1 => Term::Set((0..self.below(3) + 1).map(|_| self.term(depth - 1)).collect()),
+            2 => Term::Map(
+                (0..self.below(3))
+                    .map(|_| (self.term(depth - 1), self.term(depth - 1)))
+                    .collect(),
+            ),
+            // the negated constants are parsed as negative constants
+            3 => Term::Neg(Box::new(self.binary(depth - 1))),
+            _ => self.binary(depth - 1),
+        }
+    }
+    fn binary(&mut self, depth: usize) -> Term {
+        let operator = *self.pick(&[
+            Operator::Add,
+            Operator::Sub,
+            Operator::Mul,
+            Operator::Div,
+            Operator::Rem,
+        ]);
+        Term::Binary(Box::new(self.term(depth)), operator, Box::new(self.term(depth)))
+    }
+    fn options(&mut self) -> ScyllaOptions {
+        let mut options = ScyllaOptions::default();
+        if self.chance(2) {
+            options.clustering_order = Some(self.pick(&["(c DESC)", "(c ASC, d DESC)"]).to_string());
+        }
+        if self.chance(2) {
+            let preimage = self.chance(2);
+            options.cdc = Some(CdcOptions {
+                enabled: self.chance(2),
+                preimage,
+                full_preimage: preimage && self.chance(2),
+                postimage: self.chance(2),
+                delta: *self.pick(&[CdcDelta::Full, CdcDelta::Keys]),
+                ttl: self.below(100_000) as u32,
+            });
+        }
+        if self.chance(2) {
+            options.synchronous_updates = Some(self.chance(2));
+        }
+        if self.chance(2) {
+            options.tombstone_gc = Some(TombstoneGc {
+                mode: *self.pick(&[
+                    TombstoneGcMode::Timeout,
+                    TombstoneGcMode::Repair,
+                    TombstoneGcMode::Disabled,
+                    TombstoneGcMode::Immediate,
+                ]),
+                propagation_delay_in_seconds: Some(self.below(10_000) as u32).filter(|_| self.chance(2)),
+            });
+        }
+        if self.chance(2) {
+            options.per_partition_rate_limit = Some(PerPartitionRateLimit {
+                max_reads_per_second: Some(self.below(1000) as u32).filter(|_| self.chance(2)),
+                max_writes_per_second: Some(self.below(1000) as u32).filter(|_| self.chance(2)),
+            });
+        }
+        if self.chance(2) {
+            options.paxos_grace_seconds = Some(self.below(100_000) as u32);
+        }
+        for _ in 0..self.below(3) {
+            options.options.push((self.name().as_str().to_string(), self.term(3)));
+        }
+        options
+    }
+    fn statement(&mut self) -> ScyllaStatement {
+        let keyspace = Some(self.name()).filter(|_| self.chance(2));
+        let name = self.name();
+        match self.below(4) {
+            0 => ScyllaStatement::CreateTable {
+                if_not_exists: self.chance(2),
+                keyspace,
+                name,
+                definitions: self
+                    .pick(&["k text PRIMARY KEY", "k text, c int, \"V\" text, PRIMARY KEY ((k), c)"])
+                    .to_string(),
+                options: self.options(),
+            },
+            1 => ScyllaStatement::CreateMaterializedView {
+                if_not_exists: self.chance(2),
+                keyspace,
+                name,
+                select: "SELECT * FROM ks.t WHERE k IS NOT NULL AND c IS NOT NULL PRIMARY KEY (c, k)".to_string(),
+                per_partition_limit: Some(self.below(100) as u32).filter(|_| self.chance(2)),
+                options: self.options(),
+            },
+            2 => {
+                let mut options = self.options();
+                // the ALTER statement requires at least an option
+                options.synchronous_updates = Some(self.chance(2));
+                ScyllaStatement::Alter {
+                    object: *self.pick(&[SchemaObject::Table, SchemaObject::MaterializedView]),
+                    keyspace,
+                    name,
+                    options,
+                }
+            }
+            _ => ScyllaStatement::PruneMaterializedView {
+                keyspace,
+                name,
+                clauses: self
+                    .pick(&["", "WHERE v = 1", "WHERE v = 'a''b' USING TIMEOUT 10s"])
+                    .to_string(),
+            },
+        }
+    }
+}
+
+#[test]
+fn name_round_trips() {
+    let mut rng = Rng(0x5EED_0001);
+    for _ in 0..ITERATIONS {
+        let name = rng.name();
+        assert_eq!(Name::parse(&name.to_string()).unwrap(), name, "{}", name);
+        let auto = Name::auto(&rng.string());
+        if !auto.as_str().is_empty() {
+            assert_eq!(Name::parse(&auto.to_string()).unwrap(), auto, "{}", auto);
+        }
+    }
+}
+
+#[test]
+fn string_literal_round_trips() {
+    let mut rng = Rng(0x5EED_0002);
+    for _ in 0..ITERATIONS {
+        let literal = LitStr::new(rng.string());
+        assert_eq!(LitStr::parse(&literal.to_string()).unwrap(), literal, "{}", literal);
+    }
+}
+
+#[test]
+fn term_round_trips() {
+    let mut rng = Rng(0x5EED_0003);
+    for _ in 0..ITERATIONS {
+        let term = rng.term(4);
+        assert_eq!(Term::parse(&term.to_string()).unwrap(), term, "{}", term);
+        // the folding never panics, ie on overflows or divisions by zero
+        let _ = term.fold();
+    }
+}
+
+#[test]
+fn scylla_statement_round_trips() {
+    let mut rng = Rng(0x5EED_0004);
+    for _ in 0..ITERATIONS {
+        let statement = rng.statement();
+        assert_eq!(
+            ScyllaStatement::parse(&statement.to_string()).unwrap(),
+            Some(statement.clone()),
+            "{}",
+            statement
+        );
+    }
+}
+
+/// A production of the CQL grammar, along with a sample of it
+struct Production {
+    name: &'static str,
+    sample: &'static str,
+    covered: bool,
+    parse: fn(&str) -> anyhow::Result<()>,
+}
+
+fn name(sample: &str) -> anyhow::Result<()> {
+    Name::parse(sample).map(drop)
+}
+
+fn string_literal(sample: &str) -> anyhow::Result<()> {
+    LitStr::parse(sample).map(drop)
+}
+
+fn term(sample: &str) -> anyhow::Result<()> {
+    Term::parse(sample)?.folded().map(drop)
+}
+
+fn options(sample: &str) -> anyhow::Result<()> {
+    parse_options(sample).map(drop)
+}
+
+fn script(sample: &str) -> anyhow::Result<()> {
+    parse_script(sample).map(drop)
+}
+
+fn scylla_statement(sample: &str) -> anyhow::Result<()> {
+    ensure!(
+        ScyllaStatement::parse(sample)?.is_some(),
+        "Unsupported statement: {}",
+        sample
+    );
+    Ok(())
+}
+
+fn alter_table(sample: &str) -> anyhow::Result<()> {
+    AlterTableStatement::parse(sample).map(drop)
+}
+
+fn create_index(sample: &str) -> anyhow::Result<()> {
+    CreateIndexStatement::parse(sample).map(drop)
+}
+
+fn unsupported(sample: &str) -> anyhow::Result<()> {
+    bail!("No parser for: {}", sample)
+}
+
+/// The checklist of the grammar productions, where the covered ones must be parsed
+const CHECKLIST: [Production; 21] = [
+    Production { name: "identifier", sample: "\"My\"\"Table\"", covered: true, parse: name },
+    Production { name: "string_literal", sample: "$$it's$$", covered: true, parse: string_literal },
+    Production { name: "integer_constant", sample: "-9223372036854775808", covered: true, parse: term },
+    Production { name: "float_constant", sample: "1.5e-3", covered: true, parse: term },
+    Production { name: "uuid_constant", sample: "123e4567-e89b-12d3-a456-426614174000", covered: true, parse: term },
+    Production { name: "blob_constant", sample: "0xcafe", covered: true, parse: term },
+    Production { name: "collection_literal", sample: "{'a': [1, 2], 'b': {3}}", covered: true, parse: term },
+    Production { name: "arithmetic_term", sample: "60 * 60 * 24", covered: true, parse: term },
+    Production { name: "table_options", sample: "WITH comment = 'a' AND gc_grace_seconds = 10", covered: true, parse: options },
+    Production { name: "cqlsh_script", sample: "CONSISTENCY ONE\nSELECT * FROM ks.t;", covered: true, parse: script },
+    Production { name: "create_table", sample: "CREATE TABLE ks.t (k text PRIMARY KEY) WITH cdc = {'enabled': true}", covered: true, parse: scylla_statement },
+    Production { name: "create_materialized_view", sample: "CREATE MATERIALIZED VIEW ks.v AS SELECT * FROM ks.t WHERE k IS NOT NULL PRIMARY KEY (k) PER PARTITION LIMIT 1", covered: true, parse: scylla_statement },
+    Production { name: "alter_with_options", sample: "ALTER MATERIALIZED VIEW ks.v WITH synchronous_updates = true", covered: true, parse: scylla_statement },
+    Production { name: "prune_materialized_view", sample: "PRUNE MATERIALIZED VIEW ks.v WHERE v = 1", covered: true, parse: scylla_statement },
+    Production { name: "create_index", sample: "CREATE INDEX IF NOT EXISTS ON ks.t (KEYS(m))", covered: true, parse: create_index },
+    Production { name: "duration_constant", sample: "1h30m", covered: false, parse: term },
+    Production { name: "function_call_term", sample: "now()", covered: false, parse: term },
+    Production { name: "alter_table_columns", sample: "ALTER TABLE ks.t ADD v text", covered: true, parse: alter_table },
+    Production { name: "column_masking", sample: "ALTER TABLE ks.t ALTER v MASKED WITH mask_default()", covered: false, parse: alter_table },
+    Production { name: "vector_type", sample: "vector<float, 3>", covered: false, parse: unsupported },
+    Production { name: "select_statement", sample: "SELECT v FROM ks.t WHERE k = ? PER PARTITION LIMIT 1", covered: false, parse: scylla_statement },
+];
+
+#[test]
+fn covered_productions() {
+    for production in CHECKLIST.iter().filter(|production| production.covered) {
+        if let Err(error) = (production.parse)(production.sample) {
+            panic!("The covered production {} failed: {}", production.name, error);
+        }
+    }
+}
+
+#[test]
+#[ignore = "tracks the productions which the parser doesn't cover yet"]
+fn uncovered_productions() {
+    let failures: Vec<String> = CHECKLIST
+        .iter()
+        .filter(|production| !production.covered)
+        .filter_map(|production| {
+            (production.parse)(production.sample)
+                .err()
+                .map(|error| format!("{}: {}", production.name, error))
+        })
+        .collect();
+    assert!(failures.is_empty(), "uncovered productions:\n{}", failures.join("\n"));
+}
+
+/// Print the checklist as `covered|uncovered <production>` lines, ie to be collected by
+/// `cargo test --features conformance checklist -- --nocapture`
+#[test]
+fn checklist() {
+    for production in CHECKLIST.iter() {
+        println!(
+            "{} {}",
+            if production.covered { "covered" } else { "uncovered" },
+            production.name
+        );
+    }
+}
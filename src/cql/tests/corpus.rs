@@ -0,0 +1,315 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! The corpus round trips of the statement parsers, which are enabled by the `conformance` feature.
+//!
+//! The statements of every `.cql` file of the corpus directory, ie `src/cql/tests/corpus` or the `SCYLLA_RS_CORPUS`
+//! directory, are parsed, rendered and parsed back by the parsers of their statement type, then the coverage of
+//! every statement type is reported, ie by `cargo test --features conformance corpus -- --nocapture`.
+
+use crate::cql::{
+    parse_script,
+    statements::{
+        schema::{
+            AlterTableStatement, CreateIndexStatement, CreateKeyspaceStatement, CreateMaterializedViewStatement,
+            CreateTableStatement,
+        },
+        scylla_ext::ScyllaStatement,
+        service_level::ServiceLevelStatement,
+    },
+    ScriptItem,
+};
+use anyhow::{anyhow, ensure, Context};
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Display},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A statement of the corpus, along with the file which holds it
+struct CorpusStatement {
+    file: String,
+    statement: String,
+}
+
+/// Load the statements of the `.cql` files of the corpus directory, in the order of their file names
+fn load_corpus(dir: &Path) -> anyhow::Result<Vec<CorpusStatement>> {
+    let mut files = fs::read_dir(dir)
+        .with_context(|| format!("Unable to read the corpus directory {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    files.retain(|file| file.extension().map_or(false, |extension| extension == "cql"));
+    files.sort();
+    let mut statements = Vec::new();
+    for file in files {
+        let script = fs::read_to_string(&file)?;
+        let name = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+        for item in parse_script(&script).with_context(|| format!("Unable to split {}", name))? {
+            if let ScriptItem::Statement(statement) = item {
+                statements.push(CorpusStatement {
+                    file: name.clone(),
+                    statement,
+                });
+            }
+        }
+    }
+    Ok(statements)
+}
+
+fn corpus_dir() -> PathBuf {
+    std::env::var_os("SCYLLA_RS_CORPUS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("src/cql/tests/corpus"))
+}
+
+/// Parse the statement, render it and parse it back, where both parsed statements and both renderings must match
+fn round_trip<T, F>(statement: &str, parse: F) -> anyhow::Result<()>
+where
+    T: PartialEq + Debug + Display,
+    F: Fn(&str) -> anyhow::Result<T>,
+{
+    let parsed = parse(statement)?;
+    let rendered = parsed.to_string();
+    let reparsed = parse(&rendered).with_context(|| format!("Unable to parse the rendering: {}", rendered))?;
+    ensure!(
+        reparsed == parsed,
+        "The rendering {} is parsed as {:?} instead of {:?}",
+        rendered,
+        reparsed,
+        parsed
+    );
+    ensure!(
+        reparsed.to_string() == rendered,
+        "The rendering {} isn't stable: {}",
+        rendered,
+        reparsed
+    );
+    Ok(())
+}
+
+fn scylla_statement(statement: &str) -> anyhow::Result<ScyllaStatement> {
+    ScyllaStatement::parse(statement)?.ok_or_else(|| anyhow!("Unsupported statement: {}", statement))
+}
+
+fn service_level_statement(statement: &str) -> anyhow::Result<ServiceLevelStatement> {
+    ServiceLevelStatement::parse(statement)?.ok_or_else(|| anyhow!("Unsupported statement: {}", statement))
+}
+
+fn create_keyspace(statement: &str) -> anyhow::Result<()> {
+    round_trip(statement, CreateKeyspaceStatement::parse)
+}
+
+fn create_table(statement: &str) -> anyhow::Result<()> {
+    round_trip(statement, CreateTableStatement::parse)?;
+    round_trip(statement, scylla_statement)
+}
+
+fn alter_table(statement: &str) -> anyhow::Result<()> {
+    round_trip(statement, AlterTableStatement::parse)
+}
+
+fn create_index(statement: &str) -> anyhow::Result<()> {
+    round_trip(statement, CreateIndexStatement::parse)
+}
+
+fn create_materialized_view(statement: &str) -> anyhow::Result<()> {
+    round_trip(statement, CreateMaterializedViewStatement::parse)?;
+    round_trip(statement, scylla_statement)
+}
+
+fn scylla_extension(statement: &str) -> anyhow::Result<()> {
+    round_trip(statement, scylla_statement)
+}
+
+fn service_level(statement: &str) -> anyhow::Result<()> {
+    round_trip(statement, service_level_statement)
+}
+
+/// A statement type, along with the leading keywords of its statements and its round trip
+struct StatementType {
+    name: &'static str,
+    prefixes: &'static [&'static [&'static str]],
+    round_trip: fn(&str) -> anyhow::Result<()>,
+}
+
+/// The statement types which are covered by the parsers, in the order of the coverage report
+const STATEMENT_TYPES: [StatementType; 8] = [
+    StatementType {
+        name: "create_keyspace",
+        prefixes: &[&["CREATE", "KEYSPACE"]],
+        round_trip: create_keyspace,
+    },
+    StatementType {
+        name: "create_table",
+        prefixes: &[&["CREATE", "TABLE"]],
+        round_trip: create_table,
+    },
+    StatementType {
+        name: "alter_table",
+        prefixes: &[&["ALTER", "TABLE"]],
+        round_trip: alter_table,
+    },
+    StatementType {
+        name: "create_index",
+        prefixes: &[&["CREATE", "INDEX"], &["CREATE", "CUSTOM", "INDEX"]],
+        round_trip: create_index,
+    },
+    StatementType {
+        name: "create_materialized_view",
+        prefixes: &[&["CREATE", "MATERIALIZED", "VIEW"]],
+        round_trip: create_materialized_view,
+    },
+    StatementType {
+        name: "alter_materialized_view",
+        prefixes: &[&["ALTER", "MATERIALIZED", "VIEW"]],
+        round_trip: scylla_extension,
+    },
+    StatementType {
+        name: "prune_materialized_view",
+        prefixes: &[&["PRUNE", "MATERIALIZED", "VIEW"]],
+        round_trip: scylla_extension,
+    },
+    StatementType {
+        name: "service_level",
+        prefixes: &[
+            &["CREATE", "SERVICE", "LEVEL"],
+            &["ALTER", "SERVICE", "LEVEL"],
+            &["DROP", "SERVICE", "LEVEL"],
+            &["ATTACH", "SERVICE", "LEVEL"],
+            &["DETACH", "SERVICE", "LEVEL"],
+            &["LIST", "SERVICE", "LEVEL"],
+            &["LIST", "ALL", "SERVICE", "LEVELS"],
+            &["LIST", "ATTACHED", "SERVICE", "LEVEL"],
+            &["LIST", "ALL", "ATTACHED", "SERVICE", "LEVELS"],
+            &["LIST", "EFFECTIVE", "SERVICE", "LEVEL"],
+        ],
+        round_trip: service_level,
+    },
+];
+
+/// The leading keywords of the statement, in upper case
+fn keywords(statement: &str) -> Vec<String> {
+    statement
+        .split(|c: char| !c.is_ascii_alphabetic() && c != '_')
+        .filter(|word| !word.is_empty())
+        .take(5)
+        .map(|word| word.to_ascii_uppercase())
+        .collect()
+}
+
+/// Find the statement type of the statement, if any
+fn classify(statement: &str) -> Option<&'static StatementType> {
+    let keywords = keywords(statement);
+    STATEMENT_TYPES.iter().find(|statement_type| {
+        statement_type
+            .prefixes
+            .iter()
+            .any(|prefix| keywords.len() >= prefix.len() && keywords.iter().zip(prefix.iter()).all(|(k, p)| k == p))
+    })
+}
+
+/// The coverage of a statement type
+#[derive(Default)]
+struct Coverage {
+    passed: usize,
+    failures: Vec<String>,
+}
+
+/// The coverage report of the corpus
+#[derive(Default)]
+struct Report {
+    coverage: BTreeMap<&'static str, Coverage>,
+    /// The statements without a parser, by their leading keyword
+    unsupported: BTreeMap<String, usize>,
+}
+
+impl Report {
+    fn run(statements: &[CorpusStatement]) -> Self {
+        let mut report = Report::default();
+        for statement_type in STATEMENT_TYPES.iter() {
+            report.coverage.insert(statement_type.name, Coverage::default());
+        }
+        for CorpusStatement { file, statement } in statements {
+            match classify(statement) {
+                Some(statement_type) => {
+                    let coverage = report.coverage.get_mut(statement_type.name).unwrap();
+                    match (statement_type.round_trip)(statement) {
+                        Ok(()) => coverage.passed += 1,
+                        Err(error) => coverage
+                            .failures
+                            .push(format!("{}: {}\n    {:#}", file, statement, error)),
+                    }
+                }
+                None => {
+                    let keyword = keywords(statement).into_iter().next().unwrap_or_default();
+                    *report.unsupported.entry(keyword).or_default() += 1;
+                }
+            }
+        }
+        report
+    }
+    fn failures(&self) -> Vec<&String> {
+        self.coverage
+            .values()
+            .flat_map(|coverage| coverage.failures.iter())
+            .collect()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<28}{:>8}{:>8}", "statement type", "passed", "failed")?;
+        for statement_type in STATEMENT_TYPES.iter() {
+            let coverage = &self.coverage[statement_type.name];
+            writeln!(
+                f,
+                "{:<28}{:>8}{:>8}",
+                statement_type.name,
+                coverage.passed,
+                coverage.failures.len()
+            )?;
+        }
+        for (keyword, count) in self.unsupported.iter() {
+            writeln!(f, "unsupported {:<16}{:>8}", keyword, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn corpus_round_trips() {
+    let statements = load_corpus(&corpus_dir()).unwrap();
+    assert!(!statements.is_empty(), "The corpus is empty");
+    let report = Report::run(&statements);
+    println!("{}", report);
+    let failures = report.failures();
+    assert!(
+        failures.is_empty(),
+        "The corpus statements which failed to round trip:\n{}",
+        failures
+            .iter()
+            .map(|failure| failure.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    // every statement type is covered by the corpus, so the coverage can't silently regress
+    for (name, coverage) in report.coverage.iter() {
+        assert!(coverage.passed > 0, "The corpus doesn't cover {}", name);
+    }
+}
+
+#[test]
+fn classification() {
+    let name = |statement| classify(statement).map(|statement_type| statement_type.name);
+    assert_eq!(name("create table ks.t (k int PRIMARY KEY)"), Some("create_table"));
+    assert_eq!(name("CREATE CUSTOM INDEX ON t (v) USING 'x'"), Some("create_index"));
+    assert_eq!(
+        name("ALTER MATERIALIZED VIEW ks.mv WITH comment = ''"),
+        Some("alter_materialized_view")
+    );
+    assert_eq!(name("LIST ALL ATTACHED SERVICE LEVELS"), Some("service_level"));
+    assert_eq!(name("DROP SERVICE LEVEL sl"), Some("service_level"));
+    assert_eq!(name("DROP TABLE ks.t"), None);
+    assert_eq!(name("SELECT * FROM ks.t"), None);
+}
@@ -1,3 +1,7 @@
 // Copyright 2021 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
+#[cfg(all(test, feature = "conformance"))]
+mod conformance;
 mod connection;
+#[cfg(all(test, feature = "conformance"))]
+mod corpus;
@@ -0,0 +1,189 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! This module implements the retrieval of the request traces, which the coordinator and the replicas record in
+//! the `system_traces` keyspace once the tracing of a request is requested (see `QueryBuilder::tracing`).
+
+use super::{
+    frame::{
+        consistency::Consistency,
+        decoder::{ColumnDecoder, Decoder, Frame},
+        query::Query,
+        rows::{ColumnValue, Row, Rows},
+        Statements,
+    },
+    term::Constant,
+};
+use anyhow::ensure;
+use std::{collections::HashMap, net::IpAddr};
+
+/// A traced request, as recorded in `system_traces.sessions` and `system_traces.events`.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    /// The tracing id of the request
+    pub session_id: [u8; 16],
+    /// The coordinator of the request
+    pub coordinator: Option<IpAddr>,
+    /// The client which issued the request
+    pub client: Option<IpAddr>,
+    /// The request kind, ie `QUERY` or `EXECUTE`
+    pub command: Option<String>,
+    /// The request description, ie `Execute CQL3 query`
+    pub request: Option<String>,
+    /// The request parameters, ie the statement and its consistency
+    pub parameters: HashMap<String, String>,
+    /// The start of the request, in milliseconds since the unix epoch
+    pub started_at: Option<i64>,
+    /// The duration of the request in microseconds, which is only set once the request completed
+    pub duration: Option<i32>,
+    /// The events of the request, in their recording order
+    pub events: Vec<TraceEvent>,
+}
+
+/// An event of a traced request
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    /// The `timeuuid` of the event
+    pub event_id: [u8; 16],
+    /// The event description
+    pub activity: Option<String>,
+    /// The node which recorded the event
+    pub source: Option<IpAddr>,
+    /// The elapsed time on the source node since the request started, in microseconds
+    pub source_elapsed: Option<i32>,
+    /// The thread (shard) which recorded the event
+    pub thread: Option<String>,
+}
+
+impl Trace {
+    /// Create the query which selects the session of the tracing id
+    pub fn sessions_query(session_id: &[u8; 16]) -> anyhow::Result<Query> {
+        Query::new()
+            .statement(&format!(
+                "SELECT coordinator, client, command, request, parameters, started_at, duration \
+                 FROM system_traces.sessions WHERE session_id = {}",
+                Constant::Uuid(*session_id)
+            ))
+            .consistency(Consistency::One)
+            .build()
+    }
+    /// Create the query which selects the events of the tracing id
+    pub fn events_query(session_id: &[u8; 16]) -> anyhow::Result<Query> {
+        Query::new()
+            .statement(&format!(
+                "SELECT event_id, activity, source, source_elapsed, thread \
+                 FROM system_traces.events WHERE session_id = {}",
+                Constant::Uuid(*session_id)
+            ))
+            .consistency(Consistency::One)
+            .build()
+    }
+    /// Decode the trace out of the responses of the sessions and events queries. Returns None if the session
+    /// isn't completely recorded yet, as the traces are written asynchronously, so the caller should retry later.
+    pub fn decode(session_id: [u8; 16], sessions: Decoder, events: Decoder) -> anyhow::Result<Option<Self>> {
+        ensure!(sessions.is_rows()?, "Decoded response is not rows!");
+        ensure!(events.is_rows()?, "Decoded response is not rows!");
+        let session = match SessionRow::rows_iter(sessions)?.next() {
+            Some(session) if session.duration.is_some() => session,
+            _ => return Ok(None),
+        };
+        Ok(Some(Self {
+            session_id,
+            coordinator: session.coordinator,
+            client: session.client,
+            command: session.command,
+            request: session.request,
+            parameters: session.parameters,
+            started_at: session.started_at,
+            duration: session.duration,
+            events: TraceEvent::rows_iter(events)?.collect(),
+        }))
+    }
+}
+
+/// The row of `system_traces.sessions`
+struct SessionRow {
+    coordinator: Option<IpAddr>,
+    client: Option<IpAddr>,
+    command: Option<String>,
+    request: Option<String>,
+    parameters: HashMap<String, String>,
+    started_at: Option<i64>,
+    duration: Option<i32>,
+}
+
+impl Row for SessionRow {
+    fn try_decode_row<R: Rows + ColumnValue>(rows: &mut R) -> anyhow::Result<Self> {
+        Ok(Self {
+            coordinator: rows.column_value()?,
+            client: rows.column_value()?,
+            command: rows.column_value()?,
+            request: rows.column_value()?,
+            parameters: rows.column_value()?,
+            started_at: rows.column_value()?,
+            duration: rows.column_value()?,
+        })
+    }
+}
+
+impl Row for TraceEvent {
+    fn try_decode_row<R: Rows + ColumnValue>(rows: &mut R) -> anyhow::Result<Self> {
+        Ok(Self {
+            event_id: rows.column_value::<TimeUuid>()?.0,
+            activity: rows.column_value()?,
+            source: rows.column_value()?,
+            source_elapsed: rows.column_value()?,
+            thread: rows.column_value()?,
+        })
+    }
+}
+
+/// The `timeuuid` column of the events
+struct TimeUuid([u8; 16]);
+
+impl ColumnDecoder for TimeUuid {
+    fn try_decode(slice: &[u8]) -> anyhow::Result<Self> {
+        ensure!(slice.len() == 16, "Invalid timeuuid length: {}", slice.len());
+        let mut uuid = [0; 16];
+        uuid.copy_from_slice(slice);
+        Ok(Self(uuid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cql::{
+        compression::UNCOMPRESSED,
+        frame::{header::TRACING, opcode::RESULT, result::VOID},
+    };
+
+    #[test]
+    fn tracing_id() {
+        let Query(payload) = Query::new()
+            .statement("SELECT * FROM system.local")
+            .consistency(Consistency::One)
+            .tracing(true)
+            .build()
+            .unwrap();
+        assert_eq!(payload[1] & TRACING, TRACING);
+        let Query(payload) = Query::new()
+            .statement("SELECT * FROM system.local")
+            .consistency(Consistency::One)
+            .tracing(true)
+            .tracing(false)
+            .build()
+            .unwrap();
+        assert_eq!(payload[1] & TRACING, 0);
+        // a traced void response
+        let session_id = [7; 16];
+        let mut buffer = vec![0x84, TRACING, 0, 0, RESULT, 0, 0, 0, 20];
+        buffer.extend(&session_id);
+        buffer.extend(&VOID.to_be_bytes());
+        let decoder = Decoder::new(buffer, UNCOMPRESSED).unwrap();
+        assert_eq!(decoder.tracing_id(), Some(session_id));
+        assert!(decoder.is_void().unwrap());
+        let statement = Trace::sessions_query(&session_id).unwrap().0;
+        assert!(String::from_utf8_lossy(&statement).contains("WHERE session_id = 07070707-0707-0707-0707-070707070707"));
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! The preludes of the crate, whose public items are classified by stability tier:
+//! - **stable**: the items re-exported by `prelude::v2`, which are only removed or changed by a major version. They're
+//!   listed by the `v2::CQL_API` and `v2::APP_API` constants, which are checked against the public API snapshots of
+//!   `tests/public_api.rs`, so an unintended change of the stable surface fails the tests;
+//! - **unstable**: the other public items, reached through their module paths (ie `cql::statements`), which may
+//!   change by a minor version;
+//! - **legacy**: the glob re-exports of the original prelude, which also re-export the unstable items along with
+//!   `backstage`, so any internal change might break the crates which rely on them. They're kept for compatibility,
+//!   while the new code should import `prelude::v2` instead.
+
+#[cfg(feature = "app")]
+pub use super::{
+    app::{access::*, worker::*, *},
+    cql::{
+        Batch, ColumnDecoder, ColumnEncoder, ColumnValue, Consistency, Decoder, Frame, Iter, Prepare,
+        PreparedStatement, Query, QueryStatement, Row, Rows, RowsDecoder, Statements, TokenEncoder, Values,
+        VoidDecoder,
+    },
+};
+#[cfg(feature = "app")]
+pub use backstage::*;
+
+/// Re-export the stable items by their paths, and list them in the API constant
+macro_rules! stable_api {
+    ($(#[$attr:meta])* $api:ident: $($first:ident $(:: $rest:ident)*),+ $(,)?) => {
+        $(pub use crate::$first$(::$rest)*;)+
+        $(#[$attr])*
+        pub const $api: &[&str] = &[$(concat!(stringify!($first) $(, "::", stringify!($rest))*)),+];
+    };
+}
+
+/// The curated prelude, which only re-exports the stable items (see the module docs for the stability tiers)
+///
+/// ## Examples
+/// ```
+/// use scylla_rs::prelude::v2::*;
+///
+/// let query = Query::new()
+///     .statement("SELECT id FROM shop.orders WHERE customer = ?")
+///     .consistency(Consistency::One)
+///     .value("alice")
+///     .build()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub mod v2 {
+    stable_api! {
+        /// The paths of the stable items of the `cql` module, which are available without the `app` feature
+        CQL_API:
+        cql::Batch,
+        cql::ColumnDecoder,
+        cql::ColumnEncoder,
+        cql::ColumnValue,
+        cql::Consistency,
+        cql::CqlError,
+        cql::CqlValue,
+        cql::Decoder,
+        cql::DynamicRow,
+        cql::ErrorCodes,
+        cql::Frame,
+        cql::Iter,
+        cql::Md5,
+        cql::Name,
+        cql::PartitionKey,
+        cql::Prepare,
+        cql::PreparedStatement,
+        cql::Query,
+        cql::QueryStatement,
+        cql::Row,
+        cql::Rows,
+        cql::RowsDecoder,
+        cql::Session,
+        cql::SessionBuilder,
+        cql::StatementIdAlgorithm,
+        cql::Statements,
+        cql::TokenEncoder,
+        cql::Values,
+        cql::VoidDecoder,
+    }
+
+    #[cfg(feature = "app")]
+    stable_api! {
+        /// The paths of the stable items of the `app` module
+        APP_API:
+        app::access::ComputeToken,
+        app::access::DecodeResult,
+        app::access::DecodeRows,
+        app::access::DecodeVoid,
+        app::access::Delete,
+        app::access::DeleteRequest,
+        app::access::GetDeleteRequest,
+        app::access::GetDeleteStatement,
+        app::access::GetInsertRequest,
+        app::access::GetInsertStatement,
+        app::access::GetSelectRequest,
+        app::access::GetSelectStatement,
+        app::access::GetUpdateRequest,
+        app::access::GetUpdateStatement,
+        app::access::Insert,
+        app::access::InsertRequest,
+        app::access::Keyspace,
+        app::access::PagedIter,
+        app::access::Request,
+        app::access::Select,
+        app::access::SelectRequest,
+        app::access::Table,
+        app::access::Update,
+        app::access::UpdateRequest,
+        app::worker::AsyncWorker,
+        app::worker::ValueWorker,
+        app::worker::Worker,
+        app::worker::WorkerError,
+    }
+}
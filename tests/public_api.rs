@@ -0,0 +1,50 @@
+// Copyright 2021 IOTA Stiftung
+// SPDX-License-Identifier: Apache-2.0
+
+//! The public API snapshots of the stable items, which are re-exported by `prelude::v2`.
+//!
+//! A stable item which is removed (or renamed) is a breaking change, while a new one extends the stable surface,
+//! so both fail the tests until the snapshot is updated on purpose, by running them with `UPDATE_API_SNAPSHOTS=1`.
+
+use scylla_rs::prelude::v2;
+use std::path::PathBuf;
+
+fn check_snapshot(name: &str, api: &[&str]) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/api").join(name);
+    let actual: String = api.iter().map(|item| format!("{}\n", item)).collect();
+    if std::env::var_os("UPDATE_API_SNAPSHOTS").is_some() {
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_default();
+    let removed: Vec<&str> = expected.lines().filter(|item| !api.contains(item)).collect();
+    let added: Vec<&str> = api
+        .iter()
+        .copied()
+        .filter(|item| !expected.lines().any(|line| line == *item))
+        .collect();
+    assert!(
+        removed.is_empty(),
+        "The stable items {:?} were removed from {}, which is a breaking change",
+        removed,
+        name
+    );
+    assert!(
+        added.is_empty(),
+        "The stable items {:?} were added to {}, run the tests with UPDATE_API_SNAPSHOTS=1 to accept them",
+        added,
+        name
+    );
+    assert_eq!(expected, actual, "The stable items of {} were reordered", name);
+}
+
+#[test]
+fn cql_api() {
+    check_snapshot("prelude_v2_cql.txt", v2::CQL_API);
+}
+
+#[cfg(feature = "app")]
+#[test]
+fn app_api() {
+    check_snapshot("prelude_v2_app.txt", v2::APP_API);
+}